@@ -0,0 +1,948 @@
+//! Access pattern analysis of compute shaders in SPIR-V assembly: how far
+//! apart consecutive invocations of a subgroup read and write shared and
+//! storage buffer memory, and the bank conflicts and uncoalesced accesses
+//! those strides likely cause.
+
+use crate::spirv::{Instruction, Module, SourceLocation};
+use std::collections::{HashMap, HashSet};
+
+/// The hardware the strides are judged against.
+#[derive(Debug, Clone, Copy)]
+pub struct Hardware {
+    /// Consecutive invocations that access memory together.
+    pub subgroup_size: u32,
+    /// Shared memory banks and the bytes each serves per cycle.
+    pub banks: u32,
+    pub bank_width: u32,
+    /// Bytes of storage buffer memory fetched together.
+    pub segment_size: u32,
+}
+
+/// How a value differs between consecutive invocations of a subgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lane {
+    /// The same known value in every invocation.
+    Constant(i64),
+    /// The same value in every invocation, unknown until run.
+    Uniform,
+    /// Grows by this much from one invocation to the next.
+    Linear(i64),
+    /// Differs in a way the analysis doesn't follow.
+    Varying,
+}
+
+impl Lane {
+    fn is_uniform(self) -> bool {
+        matches!(self, Lane::Constant(_) | Lane::Uniform)
+    }
+
+    /// Lanes of a value that comes from either of two.
+    fn join(self, other: Lane) -> Lane {
+        match (self, other) {
+            (Lane::Constant(a), Lane::Constant(b)) if a == b => self,
+            (a, b) if a.is_uniform() && b.is_uniform() => Lane::Uniform,
+            // Such as a loop counter starting at the invocation ID
+            (Lane::Linear(a), b) | (b, Lane::Linear(a)) if b.is_uniform() => Lane::Linear(a),
+            (Lane::Linear(a), Lane::Linear(b)) if a == b => self,
+            _ => Lane::Varying,
+        }
+    }
+
+    fn add(self, other: Lane) -> Lane {
+        match (self, other) {
+            (Lane::Constant(a), Lane::Constant(b)) => Lane::Constant(a.wrapping_add(b)),
+            (a, b) if a.is_uniform() && b.is_uniform() => Lane::Uniform,
+            (Lane::Linear(a), Lane::Linear(b)) => Lane::Linear(a + b),
+            (Lane::Linear(a), b) | (b, Lane::Linear(a)) if b.is_uniform() => Lane::Linear(a),
+            _ => Lane::Varying,
+        }
+    }
+
+    fn negate(self) -> Lane {
+        match self {
+            Lane::Constant(a) => Lane::Constant(a.wrapping_neg()),
+            Lane::Linear(a) => Lane::Linear(-a),
+            other => other,
+        }
+    }
+
+    fn multiply(self, other: Lane) -> Lane {
+        match (self, other) {
+            (Lane::Constant(a), Lane::Constant(b)) => Lane::Constant(a.wrapping_mul(b)),
+            (a, b) if a.is_uniform() && b.is_uniform() => Lane::Uniform,
+            (Lane::Linear(a), Lane::Constant(b)) | (Lane::Constant(b), Lane::Linear(a)) => {
+                Lane::Linear(a * b)
+            }
+            _ => Lane::Varying,
+        }
+    }
+
+    /// Division by a constant, for subgroups whose first invocation
+    /// starts a multiple of the subgroup size.
+    fn divide(self, other: Lane, subgroup_size: u32) -> Lane {
+        match (self, other) {
+            (Lane::Constant(a), Lane::Constant(b)) if b != 0 => Lane::Constant(a / b),
+            (a, b) if a.is_uniform() && b.is_uniform() => Lane::Uniform,
+            (Lane::Linear(a), Lane::Constant(b)) if b != 0 && a % b == 0 => Lane::Linear(a / b),
+            // Such as `gl_LocalInvocationIndex / 32`, the subgroup index
+            (Lane::Linear(a), Lane::Constant(b))
+                if a != 0 && b % a == 0 && b / a >= i64::from(subgroup_size) =>
+            {
+                Lane::Uniform
+            }
+            _ => Lane::Varying,
+        }
+    }
+
+    /// Remainder of a constant, under the same assumption as `divide`.
+    fn remainder(self, other: Lane, subgroup_size: u32) -> Lane {
+        match (self, other) {
+            (Lane::Constant(a), Lane::Constant(b)) if b != 0 => Lane::Constant(a % b),
+            (a, b) if a.is_uniform() && b.is_uniform() => Lane::Uniform,
+            (Lane::Linear(a), Lane::Constant(b))
+                if a != 0 && b % (a * i64::from(subgroup_size)) == 0 =>
+            {
+                self
+            }
+            _ => Lane::Varying,
+        }
+    }
+}
+
+/// A pointer into shared or storage buffer memory.
+#[derive(Debug, Clone)]
+struct Pointer<'a> {
+    variable: &'a str,
+    shared: bool,
+    /// Type it points at.
+    pointee: &'a str,
+    /// Byte offset into the variable.
+    offset: Lane,
+    /// `MatrixStride` of the struct member it points into.
+    matrix_stride: Option<u32>,
+}
+
+/// A load, store or atomic through a pointer into shared or storage
+/// buffer memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Access {
+    pub variable: String,
+    /// Whether the variable is shared (`Workgroup`) memory rather than a
+    /// storage buffer.
+    pub shared: bool,
+    pub kind: &'static str,
+    /// Bytes each invocation accesses.
+    pub width: u32,
+    /// Bytes between the addresses of consecutive invocations, if they
+    /// follow a fixed stride.
+    pub stride: Option<i64>,
+    /// The likely problem with the stride, if any.
+    pub problem: Option<String>,
+    pub location: Option<SourceLocation>,
+}
+
+fn id(operand: &str) -> Option<&str> {
+    operand.strip_prefix('%')
+}
+
+/// Sizes and strides of types, from layout decorations where the module
+/// has them and std430 rules otherwise.
+struct Layout<'a> {
+    definitions: HashMap<&'a str, &'a Instruction>,
+    constants: HashMap<&'a str, i64>,
+    array_strides: HashMap<&'a str, u32>,
+    matrix_strides: HashMap<(&'a str, usize), u32>,
+    offsets: HashMap<(&'a str, usize), u32>,
+    /// Structs decorated `BufferBlock`: storage buffers in the `Uniform`
+    /// storage class before SPIR-V 1.3.
+    buffer_blocks: HashSet<&'a str>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(module: &'a Module) -> Self {
+        let mut layout = Layout {
+            definitions: HashMap::new(),
+            constants: HashMap::new(),
+            array_strides: HashMap::new(),
+            matrix_strides: HashMap::new(),
+            offsets: HashMap::new(),
+            buffer_blocks: HashSet::new(),
+        };
+
+        for instruction in &module.instructions {
+            let operands = &instruction.operands;
+            if let Some(result) = &instruction.result_id {
+                layout.definitions.insert(result, instruction);
+            }
+            match (instruction.opcode.as_str(), operands.as_slice()) {
+                ("OpConstant", [_, value]) => {
+                    if let (Some(result), Ok(value)) = (&instruction.result_id, value.parse()) {
+                        layout.constants.insert(result, value);
+                    }
+                }
+                ("OpDecorate", [target, decoration]) if decoration == "BufferBlock" => {
+                    layout.buffer_blocks.extend(id(target));
+                }
+                ("OpDecorate", [target, decoration, stride]) if decoration == "ArrayStride" => {
+                    if let (Some(target), Ok(stride)) = (id(target), stride.parse()) {
+                        layout.array_strides.insert(target, stride);
+                    }
+                }
+                ("OpMemberDecorate", [target, member, decoration, value]) => {
+                    let (Some(target), Ok(member), Ok(value)) =
+                        (id(target), member.parse(), value.parse())
+                    else {
+                        continue;
+                    };
+                    match decoration.as_str() {
+                        "Offset" => {
+                            layout.offsets.insert((target, member), value);
+                        }
+                        "MatrixStride" => {
+                            layout.matrix_strides.insert((target, member), value);
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        layout
+    }
+
+    fn definition(&self, ty: &str) -> Option<&'a Instruction> {
+        self.definitions.get(ty).copied()
+    }
+
+    /// The type's std430 size and alignment.
+    fn size_align(&self, ty: &str) -> Option<(u32, u32)> {
+        let definition = self.definition(ty)?;
+        let operands = &definition.operands;
+        let count = |operand: Option<&String>| operand?.parse::<u32>().ok();
+
+        match definition.opcode.as_str() {
+            "OpTypeInt" | "OpTypeFloat" => {
+                let bytes = count(operands.first())? / 8;
+                Some((bytes, bytes))
+            }
+            "OpTypeBool" => Some((4, 4)),
+            "OpTypeVector" => {
+                let (component, _) = self.size_align(id(operands.first()?)?)?;
+                let components = count(operands.get(1))?;
+                let aligned = if components == 3 { 4 } else { components };
+                Some((component * components, component * aligned))
+            }
+            "OpTypeMatrix" | "OpTypeArray" => {
+                let (stride, align) = self.element_stride(ty)?;
+                let length = if definition.opcode == "OpTypeMatrix" {
+                    count(operands.get(1))?
+                } else {
+                    u32::try_from(*self.constants.get(id(operands.get(1)?)?)?).ok()?
+                };
+                Some((stride * length, align))
+            }
+            "OpTypeStruct" => {
+                let mut size = 0u32;
+                let mut struct_align = 1;
+                for (member, member_type) in operands.iter().enumerate() {
+                    let (member_size, align) = self.size_align(id(member_type)?)?;
+                    size = self
+                        .member_offset(ty, member)?
+                        .max(size.next_multiple_of(align));
+                    size += member_size;
+                    struct_align = struct_align.max(align);
+                }
+                Some((size.next_multiple_of(struct_align), struct_align))
+            }
+            _ => None,
+        }
+    }
+
+    /// Bytes between the elements of an array, or the columns of a
+    /// matrix, and the alignment of the elements.
+    fn element_stride(&self, ty: &str) -> Option<(u32, u32)> {
+        let definition = self.definition(ty)?;
+        let (size, align) = self.size_align(id(definition.operands.first()?)?)?;
+        let stride = self
+            .array_strides
+            .get(ty)
+            .copied()
+            .unwrap_or(size.next_multiple_of(align));
+        Some((stride, align))
+    }
+
+    /// Offset of a struct member: its `Offset` decoration, or the end of
+    /// the previous member rounded up to its alignment.
+    fn member_offset(&self, ty: &str, member: usize) -> Option<u32> {
+        if let Some(offset) = self.offsets.get(&(ty, member)) {
+            return Some(*offset);
+        }
+        let operands = &self.definition(ty)?.operands;
+        let (_, align) = self.size_align(id(operands.get(member)?)?)?;
+        if member == 0 {
+            return Some(0);
+        }
+        let (previous_size, _) = self.size_align(id(&operands[member - 1])?)?;
+        let previous = self.member_offset(ty, member - 1)?;
+        Some((previous + previous_size).next_multiple_of(align))
+    }
+}
+
+struct Analyzer<'a> {
+    layout: Layout<'a>,
+    subgroup_size: u32,
+    builtins: HashMap<&'a str, &'a str>,
+    /// Scalar values, by id.
+    values: HashMap<&'a str, Lane>,
+    /// Components of vectors built from invocation IDs, by id.
+    vectors: HashMap<&'a str, Vec<Lane>>,
+    pointers: HashMap<&'a str, Pointer<'a>>,
+    /// Pointers into push constants and uniform buffers, which hold the
+    /// same values for every invocation.
+    uniform_pointers: HashSet<&'a str>,
+    /// Pointers to built-in variables, with the component they select.
+    builtin_pointers: HashMap<&'a str, (&'a str, Option<usize>)>,
+    /// Function variables and the id last stored to each, with the ones
+    /// stored more than once.
+    locals: HashMap<&'a str, Option<&'a str>>,
+    reassigned: HashSet<&'a str>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn lane(&self, operand: &str) -> Lane {
+        let Some(operand) = id(operand) else {
+            return Lane::Varying;
+        };
+        if let Some(constant) = self.layout.constants.get(operand) {
+            return Lane::Constant(*constant);
+        }
+        self.values.get(operand).copied().unwrap_or(Lane::Varying)
+    }
+
+    /// The components of a loaded built-in: the x invocation IDs step by
+    /// one from invocation to invocation, the rest stay the same within a
+    /// subgroup that lies along x.
+    fn builtin(&mut self, result: &'a str, builtin: &str, component: Option<usize>) {
+        let components = match builtin {
+            "LocalInvocationId" | "GlobalInvocationId" => {
+                vec![Lane::Linear(1), Lane::Uniform, Lane::Uniform]
+            }
+            "LocalInvocationIndex" | "SubgroupLocalInvocationId" => vec![Lane::Linear(1)],
+            _ => vec![Lane::Uniform; 3],
+        };
+        match component {
+            Some(component) => {
+                let lane = components.get(component).copied().unwrap_or(Lane::Varying);
+                self.values.insert(result, lane);
+            }
+            None if components.len() == 1 => {
+                self.values.insert(result, components[0]);
+            }
+            None => {
+                self.values.insert(result, Lane::Varying);
+                self.vectors.insert(result, components);
+            }
+        }
+    }
+
+    /// Follows an access chain from `base` through `indices`.
+    fn access_chain(&self, base: &Pointer<'a>, indices: &[String]) -> Option<Pointer<'a>> {
+        let mut pointer = base.clone();
+        for index in indices {
+            let definition = self.layout.definition(pointer.pointee)?;
+            let operands = &definition.operands;
+            let index_lane = self.lane(index);
+
+            let (element, stride) = match definition.opcode.as_str() {
+                "OpTypeStruct" => {
+                    let Lane::Constant(member) = index_lane else {
+                        return None;
+                    };
+                    let member = usize::try_from(member).ok()?;
+                    let offset = self.layout.member_offset(pointer.pointee, member)?;
+                    pointer.offset = pointer.offset.add(Lane::Constant(offset.into()));
+                    pointer.matrix_stride = self
+                        .layout
+                        .matrix_strides
+                        .get(&(pointer.pointee, member))
+                        .copied();
+                    pointer.pointee = id(operands.get(member)?)?;
+                    continue;
+                }
+                "OpTypeArray" | "OpTypeRuntimeArray" => (
+                    id(operands.first()?)?,
+                    self.layout.element_stride(pointer.pointee)?.0,
+                ),
+                "OpTypeMatrix" => {
+                    let column = id(operands.first()?)?;
+                    let stride = match pointer.matrix_stride {
+                        Some(stride) => stride,
+                        None => self.layout.element_stride(pointer.pointee)?.0,
+                    };
+                    (column, stride)
+                }
+                "OpTypeVector" => {
+                    let component = id(operands.first()?)?;
+                    (component, self.layout.size_align(component)?.0)
+                }
+                _ => return None,
+            };
+            pointer.offset = pointer
+                .offset
+                .add(index_lane.multiply(Lane::Constant(stride.into())));
+            pointer.pointee = element;
+        }
+        Some(pointer)
+    }
+
+    fn instruction(&mut self, instruction: &'a Instruction) -> Option<Access> {
+        let result = instruction.result_id.as_deref();
+        let operands = &instruction.operands;
+        let lane = |index: usize| {
+            operands
+                .get(index)
+                .map_or(Lane::Varying, |operand| self.lane(operand))
+        };
+        let subgroup_size = self.subgroup_size;
+
+        let value = match instruction.opcode.as_str() {
+            "OpVariable" => {
+                let pointer_type = self.layout.definition(id(operands.first()?)?)?;
+                let class = operands.get(1)?.as_str();
+                let pointee = id(pointer_type.operands.get(1)?)?;
+                let shared = match class {
+                    "Workgroup" => true,
+                    "StorageBuffer" => false,
+                    "Uniform" if self.layout.buffer_blocks.contains(pointee) => false,
+                    "Uniform" | "PushConstant" => {
+                        self.uniform_pointers.insert(result?);
+                        return None;
+                    }
+                    _ => return None,
+                };
+                self.pointers.insert(
+                    result?,
+                    Pointer {
+                        variable: result?,
+                        shared,
+                        pointee,
+                        offset: Lane::Constant(0),
+                        matrix_stride: None,
+                    },
+                );
+                return None;
+            }
+            "OpAccessChain" | "OpInBoundsAccessChain" => {
+                let base = id(operands.get(1)?)?;
+                if self.uniform_pointers.contains(base) {
+                    self.uniform_pointers.insert(result?);
+                    return None;
+                }
+                if let Some((builtin, None)) = self.builtin_pointers.get(base).copied() {
+                    if let Lane::Constant(component) = self.lane(operands.get(2)?) {
+                        let component = usize::try_from(component).ok();
+                        self.builtin_pointers.insert(result?, (builtin, component));
+                    }
+                    return None;
+                }
+                let pointer = self.access_chain(self.pointers.get(base)?, &operands[2..])?;
+                self.pointers.insert(result?, pointer);
+                return None;
+            }
+            "OpCopyObject" => {
+                let source = id(operands.get(1)?)?;
+                self.alias(result?, source);
+                return None;
+            }
+            "OpLoad" => {
+                let pointer = id(operands.get(1)?)?;
+                let result = result?;
+                if let Some((builtin, component)) = self.builtin_pointers.get(pointer).copied() {
+                    self.builtin(result, builtin, component);
+                    return None;
+                }
+                if self.uniform_pointers.contains(pointer) {
+                    self.values.insert(result, Lane::Uniform);
+                    return None;
+                }
+                if let Some(stored) = self.locals.get(pointer).copied() {
+                    match stored {
+                        Some(stored) => self.alias(result, stored),
+                        None => {
+                            self.values.insert(result, Lane::Varying);
+                        }
+                    }
+                    if self.reassigned.contains(pointer) {
+                        // Later stores, as in loops, change a constant
+                        if let Some(Lane::Constant(_)) = self.values.get(result) {
+                            self.values.insert(result, Lane::Uniform);
+                        }
+                    }
+                    return None;
+                }
+                let access = self.access(pointer, "load");
+                let loaded = match self.pointers.get(pointer) {
+                    Some(pointer) if pointer.offset.is_uniform() => Lane::Uniform,
+                    _ => Lane::Varying,
+                };
+                self.values.insert(result, loaded);
+                return access;
+            }
+            "OpStore" => {
+                let pointer = id(operands.first()?)?;
+                if self.locals.contains_key(pointer) {
+                    let value = id(operands.get(1)?)?;
+                    self.locals.insert(pointer, Some(value));
+                    return None;
+                }
+                return self.access(pointer, "store");
+            }
+            "OpAtomicStore" => return self.access(id(operands.first()?)?, "atomic"),
+            opcode if opcode.starts_with("OpAtomic") => {
+                let pointer = id(operands.get(1)?)?;
+                self.values.insert(result?, Lane::Varying);
+                return self.access(pointer, "atomic");
+            }
+            "OpCompositeExtract" => {
+                let composite = id(operands.get(1)?)?;
+                match self.vectors.get(composite) {
+                    Some(components) => {
+                        let component = operands.get(2)?.parse::<usize>().ok()?;
+                        components.get(component).copied().unwrap_or(Lane::Varying)
+                    }
+                    None => lane(1),
+                }
+            }
+            "OpVectorShuffle" => {
+                let first = self.vectors.get(id(operands.get(1)?)?)?;
+                let second = self.vectors.get(id(operands.get(2)?)?)?;
+                let all = first.iter().chain(second).copied().collect::<Vec<_>>();
+                let result = result?;
+                let components = operands[3..]
+                    .iter()
+                    .map(|component| {
+                        component
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|component| all.get(component).copied())
+                            .unwrap_or(Lane::Varying)
+                    })
+                    .collect();
+                self.vectors.insert(result, components);
+                Lane::Varying
+            }
+            "OpBitcast" | "OpUConvert" | "OpSConvert" => {
+                let converted = lane(1);
+                if let Some(components) = self.vectors.get(id(operands.get(1)?)?).cloned() {
+                    self.vectors.insert(result?, components);
+                }
+                converted
+            }
+            "OpIAdd" => lane(1).add(lane(2)),
+            "OpISub" => lane(1).add(lane(2).negate()),
+            "OpSNegate" => lane(1).negate(),
+            "OpIMul" => lane(1).multiply(lane(2)),
+            "OpShiftLeftLogical" => match lane(2) {
+                Lane::Constant(shift @ 0..=31) => lane(1).multiply(Lane::Constant(1 << shift)),
+                shift => lane(1).multiply(shift),
+            },
+            "OpUDiv" | "OpSDiv" => lane(1).divide(lane(2), subgroup_size),
+            "OpShiftRightLogical" | "OpShiftRightArithmetic" => match lane(2) {
+                Lane::Constant(shift @ 0..=31) => {
+                    lane(1).divide(Lane::Constant(1 << shift), subgroup_size)
+                }
+                shift => lane(1).divide(shift, subgroup_size),
+            },
+            "OpUMod" | "OpSMod" | "OpSRem" => lane(1).remainder(lane(2), subgroup_size),
+            "OpBitwiseAnd" => match (lane(1), lane(2)) {
+                (value, Lane::Constant(mask)) | (Lane::Constant(mask), value)
+                    if mask >= 0 && (mask + 1).count_ones() == 1 =>
+                {
+                    value.remainder(Lane::Constant(mask + 1), subgroup_size)
+                }
+                (a, b) if a.is_uniform() && b.is_uniform() => Lane::Uniform,
+                _ => Lane::Varying,
+            },
+            // `%result = OpPhi %type %value %block ...`, where values from
+            // later blocks aren't known yet
+            "OpPhi" => operands[1..]
+                .iter()
+                .step_by(2)
+                .filter_map(|operand| {
+                    let operand = id(operand)?;
+                    match self.layout.constants.get(operand) {
+                        Some(constant) => Some(Lane::Constant(*constant)),
+                        None => self.values.get(operand).copied(),
+                    }
+                })
+                .reduce(Lane::join)
+                .unwrap_or(Lane::Uniform),
+            "OpFunctionParameter" => Lane::Varying,
+            _ => {
+                // Anything else of uniform values is uniform
+                let uniform = operands
+                    .iter()
+                    .skip(1)
+                    .filter_map(|operand| id(operand))
+                    .filter(|operand| self.values.contains_key(operand))
+                    .all(|operand| self.values[operand].is_uniform());
+                if uniform {
+                    Lane::Uniform
+                } else {
+                    Lane::Varying
+                }
+            }
+        };
+
+        if let Some(result) = result {
+            self.values.insert(result, value);
+        }
+        None
+    }
+
+    /// Makes `result` stand for the same value or pointer as `source`.
+    fn alias(&mut self, result: &'a str, source: &'a str) {
+        if let Some(constant) = self.layout.constants.get(source) {
+            self.values.insert(result, Lane::Constant(*constant));
+        }
+        if let Some(value) = self.values.get(source).copied() {
+            self.values.insert(result, value);
+        }
+        if let Some(components) = self.vectors.get(source).cloned() {
+            self.vectors.insert(result, components);
+        }
+        if let Some(pointer) = self.pointers.get(source).cloned() {
+            self.pointers.insert(result, pointer);
+        }
+    }
+
+    fn access(&self, pointer: &str, kind: &'static str) -> Option<Access> {
+        let pointer = self.pointers.get(pointer)?;
+        let pointee = self.layout.definition(pointer.pointee)?;
+        // Copies of whole arrays and structs aren't single accesses
+        if !matches!(
+            pointee.opcode.as_str(),
+            "OpTypeInt" | "OpTypeFloat" | "OpTypeBool" | "OpTypeVector"
+        ) {
+            return None;
+        }
+        let (width, _) = self.layout.size_align(pointer.pointee)?;
+
+        let stride = match pointer.offset {
+            Lane::Constant(_) | Lane::Uniform => Some(0),
+            Lane::Linear(stride) => Some(stride),
+            Lane::Varying => None,
+        };
+        Some(Access {
+            variable: pointer.variable.to_string(),
+            shared: pointer.shared,
+            kind,
+            width,
+            stride,
+            problem: None,
+            location: None,
+        })
+    }
+}
+
+/// Shared memory: how many times more cycles than necessary a subgroup's
+/// access takes, from the most distinct words any one bank serves.
+fn bank_conflict(stride: i64, width: u32, hardware: &Hardware) -> u32 {
+    let bank_width = i64::from(hardware.bank_width);
+    let mut words = HashSet::new();
+    for lane in 0..i64::from(hardware.subgroup_size) {
+        let start = lane * stride;
+        let end = start + i64::from(width) - 1;
+        words.extend(start.div_euclid(bank_width)..=end.div_euclid(bank_width));
+    }
+
+    let mut per_bank = HashMap::<i64, u32>::new();
+    for word in &words {
+        *per_bank
+            .entry(word.rem_euclid(hardware.banks.into()))
+            .or_default() += 1;
+    }
+    let worst = per_bank.values().copied().max().unwrap_or(1);
+    let best = (words.len() as u32).div_ceil(hardware.banks).max(1);
+    worst.div_ceil(best)
+}
+
+/// Storage buffers: the segments a subgroup's access touches and the
+/// fewest that could hold the bytes it uses.
+fn segments(stride: i64, width: u32, hardware: &Hardware) -> (u32, u32) {
+    let segment_size = i64::from(hardware.segment_size);
+    let lanes = i64::from(hardware.subgroup_size);
+    let mut touched = HashSet::new();
+    for lane in 0..lanes {
+        let start = lane * stride;
+        let end = start + i64::from(width) - 1;
+        touched.extend(start.div_euclid(segment_size)..=end.div_euclid(segment_size));
+    }
+
+    let stride = stride.unsigned_abs();
+    let width = u64::from(width);
+    let bytes = if stride >= width {
+        lanes as u64 * width
+    } else {
+        (lanes as u64 - 1) * stride + width
+    };
+    (
+        touched.len() as u32,
+        bytes.div_ceil(hardware.segment_size.into()) as u32,
+    )
+}
+
+/// Every access of the module to shared and storage buffer memory, with
+/// the problems their strides likely cause on `hardware`.
+pub fn accesses(module: &Module, hardware: &Hardware) -> Vec<Access> {
+    let layout = Layout::new(module);
+    let names = module
+        .with_opcode("OpName")
+        .filter_map(|instruction| {
+            Some((
+                id(instruction.operands.first()?)?,
+                instruction.string_operand(1)?,
+            ))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut analyzer = Analyzer {
+        subgroup_size: hardware.subgroup_size,
+        builtins: HashMap::new(),
+        values: HashMap::new(),
+        vectors: HashMap::new(),
+        pointers: HashMap::new(),
+        uniform_pointers: HashSet::new(),
+        builtin_pointers: HashMap::new(),
+        locals: HashMap::new(),
+        reassigned: HashSet::new(),
+        layout,
+    };
+
+    let mut stores = HashMap::<&str, usize>::new();
+    for instruction in &module.instructions {
+        let operands = &instruction.operands;
+        match instruction.opcode.as_str() {
+            "OpDecorate" if operands.get(1).map(String::as_str) == Some("BuiltIn") => {
+                if let (Some(target), Some(builtin)) = (
+                    operands.first().and_then(|target| id(target)),
+                    operands.get(2),
+                ) {
+                    analyzer.builtins.insert(target, builtin);
+                }
+            }
+            "OpStore" => {
+                if let Some(pointer) = operands.first().and_then(|pointer| id(pointer)) {
+                    *stores.entry(pointer).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    for instruction in module.with_opcode("OpVariable") {
+        let (Some(result), Some(class)) = (&instruction.result_id, instruction.operands.get(1))
+        else {
+            continue;
+        };
+        if let Some(builtin) = analyzer.builtins.get(result.as_str()).copied() {
+            analyzer.builtin_pointers.insert(result, (builtin, None));
+        }
+        if class == "Function" {
+            let initializer = instruction.operands.get(2).and_then(|value| id(value));
+            analyzer.locals.insert(result, initializer);
+            if stores.get(result.as_str()).copied().unwrap_or(0) > 1 {
+                analyzer.reassigned.insert(result);
+            }
+        }
+    }
+
+    let locations = module.instruction_locations();
+    let mut accesses = Vec::new();
+    for (instruction, location) in module.instructions.iter().zip(locations) {
+        let Some(mut access) = analyzer.instruction(instruction) else {
+            continue;
+        };
+        // Blocks without an instance name go by their type's name
+        let block = analyzer
+            .layout
+            .definition(&access.variable)
+            .and_then(|variable| analyzer.layout.definition(id(variable.operands.first()?)?))
+            .and_then(|pointer| id(pointer.operands.get(1)?));
+        let name = [Some(access.variable.as_str()), block]
+            .into_iter()
+            .flatten()
+            .filter_map(|id| names.get(id))
+            .find(|name| !name.is_empty());
+        if let Some(name) = name {
+            access.variable = (*name).to_string();
+        }
+        access.problem = match access.stride {
+            None => Some(
+                "the address depends on the invocation in a way this analysis doesn't follow, such as an index read from memory or returned by a function"
+                    .to_string(),
+            ),
+            Some(0) => None,
+            Some(stride) if access.shared => {
+                let conflict = bank_conflict(stride, access.width, hardware);
+                (conflict > 1).then(|| {
+                    format!(
+                        "likely {conflict}-way bank conflict, taking {conflict} times the cycles of a conflict-free access; padding the row length or swizzling the index spreads it over the banks"
+                    )
+                })
+            }
+            Some(stride) => {
+                let (touched, needed) = segments(stride, access.width, hardware);
+                (touched > needed).then(|| {
+                    format!(
+                        "likely uncoalesced: a subgroup touches {touched} {}-byte segments where {needed} would hold its data{}",
+                        hardware.segment_size,
+                        if stride.unsigned_abs() > u64::from(access.width) {
+                            "; a structure-of-arrays layout or staging through shared memory makes consecutive invocations access consecutive addresses"
+                        } else {
+                            ""
+                        }
+                    )
+                })
+            }
+        };
+        access.location = location;
+        accesses.push(access);
+    }
+    accesses
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HARDWARE: Hardware = Hardware {
+        subgroup_size: 32,
+        banks: 32,
+        bank_width: 4,
+        segment_size: 128,
+    };
+
+    #[test]
+    fn test_lanes() {
+        let index = Lane::Linear(1);
+        assert_eq!(index.multiply(Lane::Constant(4)), Lane::Linear(4));
+        assert_eq!(index.add(Lane::Uniform), Lane::Linear(1));
+        assert_eq!(index.multiply(index), Lane::Varying);
+        // The subgroup index and the invocation within a subgroup
+        assert_eq!(index.divide(Lane::Constant(32), 32), Lane::Uniform);
+        assert_eq!(index.divide(Lane::Constant(16), 32), Lane::Varying);
+        assert_eq!(index.remainder(Lane::Constant(64), 32), index);
+        assert_eq!(index.remainder(Lane::Constant(16), 32), Lane::Varying);
+        // Loop counters starting at the invocation ID
+        assert_eq!(index.join(Lane::Constant(0)), index);
+        assert_eq!(index.join(Lane::Linear(2)), Lane::Varying);
+        assert_eq!(Lane::Constant(1).join(Lane::Constant(2)), Lane::Uniform);
+    }
+
+    #[test]
+    fn test_bank_conflict() {
+        assert_eq!(bank_conflict(4, 4, &HARDWARE), 1);
+        assert_eq!(bank_conflict(8, 4, &HARDWARE), 2);
+        assert_eq!(bank_conflict(128, 4, &HARDWARE), 32);
+        // A padded row
+        assert_eq!(bank_conflict(132, 4, &HARDWARE), 1);
+        // Wider accesses take several words and cycles regardless
+        assert_eq!(bank_conflict(16, 16, &HARDWARE), 1);
+        assert_eq!(bank_conflict(-4, 4, &HARDWARE), 1);
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(segments(4, 4, &HARDWARE), (1, 1));
+        assert_eq!(segments(16, 16, &HARDWARE), (4, 4));
+        assert_eq!(segments(8, 4, &HARDWARE), (2, 1));
+        assert_eq!(segments(128, 4, &HARDWARE), (32, 1));
+    }
+
+    #[test]
+    fn test_accesses() {
+        let shader = r#"
+               OpCapability Shader
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint GLCompute %main "main" %index
+               OpExecutionMode %main LocalSize 32 1 1
+               OpName %tile "tile"
+               OpName %data "data"
+               OpDecorate %index BuiltIn LocalInvocationIndex
+               OpDecorate %runtime ArrayStride 4
+               OpMemberDecorate %Data 0 Offset 0
+               OpDecorate %Data Block
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+       %uint = OpTypeInt 32 0
+      %float = OpTypeFloat 32
+     %uint_0 = OpConstant %uint 0
+    %uint_32 = OpConstant %uint 32
+  %uint_1024 = OpConstant %uint 1024
+      %array = OpTypeArray %float %uint_1024
+   %wg_array = OpTypePointer Workgroup %array
+       %tile = OpVariable %wg_array Workgroup
+   %wg_float = OpTypePointer Workgroup %float
+    %runtime = OpTypeRuntimeArray %float
+       %Data = OpTypeStruct %runtime
+    %sb_Data = OpTypePointer StorageBuffer %Data
+       %data = OpVariable %sb_Data StorageBuffer
+   %sb_float = OpTypePointer StorageBuffer %float
+    %in_uint = OpTypePointer Input %uint
+      %index = OpVariable %in_uint Input
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+          %i = OpLoad %uint %index
+        %row = OpIMul %uint %i %uint_32
+     %shared = OpAccessChain %wg_float %tile %row
+      %value = OpLoad %float %shared
+  %coalesced = OpAccessChain %sb_float %data %uint_0 %i
+               OpStore %coalesced %value
+    %strided = OpAccessChain %sb_float %data %uint_0 %row
+               OpStore %strided %value
+       %same = OpAccessChain %sb_float %data %uint_0 %uint_0
+    %uniform = OpLoad %float %same
+     %square = OpIMul %uint %i %i
+  %scattered = OpAccessChain %sb_float %data %uint_0 %square
+               OpStore %scattered %uniform
+               OpReturn
+               OpFunctionEnd
+"#;
+        let accesses = accesses(&Module::parse(shader), &HARDWARE);
+        let summary = accesses
+            .iter()
+            .map(|access| {
+                (
+                    access.variable.as_str(),
+                    access.shared,
+                    access.kind,
+                    access.width,
+                    access.stride,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("tile", true, "load", 4, Some(128)),
+                ("data", false, "store", 4, Some(4)),
+                ("data", false, "store", 4, Some(128)),
+                ("data", false, "load", 4, Some(0)),
+                ("data", false, "store", 4, None),
+            ]
+        );
+
+        let problems = accesses
+            .iter()
+            .map(|access| access.problem.as_deref().unwrap_or(""))
+            .collect::<Vec<_>>();
+        assert!(problems[0].starts_with("likely 32-way bank conflict"));
+        assert_eq!(problems[1], "");
+        assert!(problems[2].starts_with(
+            "likely uncoalesced: a subgroup touches 32 128-byte segments where 1 would"
+        ));
+        assert_eq!(problems[3], "");
+        assert!(problems[4].contains("doesn't follow"));
+    }
+}
@@ -0,0 +1,216 @@
+//! In-shader assertions for compute shaders: a GLSL helper with
+//! `ASSERT(cond)` macros that record failures into a storage buffer,
+//! and the decoding of that buffer after the run.
+
+use crate::{LineOrigin, TaggedLines, is_version_or_extension};
+
+/// Words per failure record: shader, line, invocation x, y and z, then
+/// a tag and a value for each of two recorded values.
+pub const RECORD_WORDS: usize = 9;
+
+/// The helper, after the shader's `#version` and `#extension` lines and
+/// the `#define`s that place its buffer.
+const HELPER: &str = r#"layout(std430, set = 0, binding = SHADER_ASSERTION_BINDING) buffer ShaderAssertionRecords {
+    uint shader_assertion_count;
+    uint shader_assertion_words[];
+};
+
+uint shader_assertion_begin(uint line) {
+    uint record = atomicAdd(shader_assertion_count, 1u);
+    if (record >= SHADER_ASSERTION_CAPACITY) {
+        return 0xffffffffu;
+    }
+    uint base = record * 9u;
+    shader_assertion_words[base] = SHADER_ASSERTION_SHADER;
+    shader_assertion_words[base + 1u] = line;
+    shader_assertion_words[base + 2u] = gl_GlobalInvocationID.x;
+    shader_assertion_words[base + 3u] = gl_GlobalInvocationID.y;
+    shader_assertion_words[base + 4u] = gl_GlobalInvocationID.z;
+    return base;
+}
+
+void shader_assertion_store(uint base, uint index, uint tag, uint bits) {
+    if (base != 0xffffffffu) {
+        shader_assertion_words[base + 5u + 2u * index] = tag;
+        shader_assertion_words[base + 6u + 2u * index] = bits;
+    }
+}
+
+void shader_assertion_value(uint base, uint index, uint value) { shader_assertion_store(base, index, 1u, value); }
+void shader_assertion_value(uint base, uint index, int value) { shader_assertion_store(base, index, 2u, uint(value)); }
+void shader_assertion_value(uint base, uint index, float value) { shader_assertion_store(base, index, 3u, floatBitsToUint(value)); }
+void shader_assertion_value(uint base, uint index, bool value) { shader_assertion_store(base, index, 4u, uint(value)); }
+
+#define ASSERT(cond) do { if (!(cond)) { shader_assertion_begin(uint(__LINE__)); } } while (false)
+#define ASSERT_VALUE(cond, value) do { if (!(cond)) { uint shader_assertion_base_ = shader_assertion_begin(uint(__LINE__)); shader_assertion_value(shader_assertion_base_, 0u, (value)); } } while (false)
+#define SHADER_ASSERT_COMPARE(a, op, b) do { if (!((a) op (b))) { uint shader_assertion_base_ = shader_assertion_begin(uint(__LINE__)); shader_assertion_value(shader_assertion_base_, 0u, (a)); shader_assertion_value(shader_assertion_base_, 1u, (b)); } } while (false)
+#define ASSERT_EQ(a, b) SHADER_ASSERT_COMPARE(a, ==, b)
+#define ASSERT_NE(a, b) SHADER_ASSERT_COMPARE(a, !=, b)
+#define ASSERT_LT(a, b) SHADER_ASSERT_COMPARE(a, <, b)
+#define ASSERT_LE(a, b) SHADER_ASSERT_COMPARE(a, <=, b)
+#define ASSERT_GT(a, b) SHADER_ASSERT_COMPARE(a, >, b)
+#define ASSERT_GE(a, b) SHADER_ASSERT_COMPARE(a, >=, b)"#;
+
+/// Where a compute shader's helper records its failures.
+#[derive(Debug, Clone, Copy)]
+pub struct Assertions {
+    /// Binding of the records buffer at set 0.
+    pub binding: u32,
+    /// Failures recorded before further ones are only counted.
+    pub capacity: u32,
+    /// Index of the shader's compile request, stored in its records.
+    pub shader: usize,
+}
+
+impl Assertions {
+    /// Size in bytes of the records buffer: the failure count, then the
+    /// records.
+    pub fn buffer_size(&self) -> u32 {
+        4 + 4 * RECORD_WORDS as u32 * self.capacity
+    }
+
+    /// Adds the helper after the shader's `#version` and `#extension`
+    /// lines, which have to come first.
+    pub(crate) fn inject(&self, lines: TaggedLines) -> TaggedLines {
+        let insert_at = lines
+            .iter()
+            .rposition(|(_, line)| is_version_or_extension(line))
+            .map_or(0, |line| line + 1);
+
+        let mut helper = vec![
+            format!("#define SHADER_ASSERTION_BINDING {}", self.binding),
+            format!("#define SHADER_ASSERTION_CAPACITY {}u", self.capacity),
+            format!("#define SHADER_ASSERTION_SHADER {}u", self.shader),
+        ];
+        helper.extend(HELPER.lines().map(str::to_string));
+
+        let mut result = lines;
+        result.splice(
+            insert_at..insert_at,
+            helper.into_iter().map(|line| (LineOrigin::Injected, line)),
+        );
+        result
+    }
+}
+
+/// A value recorded with a failure, by its tag.
+fn value(tag: u32, bits: u32) -> Option<String> {
+    match tag {
+        1 => Some(format!("{bits}u")),
+        2 => Some((bits as i32).to_string()),
+        3 => Some(format!("{:?}", f32::from_bits(bits))),
+        4 => Some((bits != 0).to_string()),
+        _ => None,
+    }
+}
+
+/// One recorded failure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub shader: usize,
+    /// 1-based line of the compiled source.
+    pub line: usize,
+    pub invocation: [u32; 3],
+    pub values: Vec<String>,
+}
+
+/// The total number of failures and the recorded ones, from a dump of
+/// the records buffer.
+pub fn decode(dump: &[u8]) -> (u32, Vec<Failure>) {
+    let words = dump
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect::<Vec<_>>();
+    let Some((&count, records)) = words.split_first() else {
+        return (0, Vec::new());
+    };
+
+    let failures = records
+        .chunks_exact(RECORD_WORDS)
+        .take(count as usize)
+        .map(|record| Failure {
+            shader: record[0] as usize,
+            line: record[1] as usize,
+            invocation: [record[2], record[3], record[4]],
+            values: record[5..]
+                .chunks_exact(2)
+                .filter_map(|pair| value(pair[0], pair[1]))
+                .collect(),
+        })
+        .collect();
+    (count, failures)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_inject() {
+        let assertions = Assertions {
+            binding: 3,
+            capacity: 4,
+            shader: 1,
+        };
+        assert_eq!(assertions.buffer_size(), 4 + 4 * 9 * 4);
+
+        let lines = crate::tag_lines(
+            "#version 450\n#extension GL_EXT_debug_printf : enable\nvoid main() {}",
+            LineOrigin::Source,
+        );
+        let injected = assertions.inject(lines);
+        assert_eq!(
+            injected[..3],
+            [
+                (LineOrigin::Source(1), "#version 450".to_string()),
+                (
+                    LineOrigin::Source(2),
+                    "#extension GL_EXT_debug_printf : enable".to_string()
+                ),
+                (
+                    LineOrigin::Injected,
+                    "#define SHADER_ASSERTION_BINDING 3".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            injected.last(),
+            Some(&(LineOrigin::Source(3), "void main() {}".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        // Three failures, but room for two records
+        let mut words = vec![3];
+        // Shader 0, line 12, invocation (1, 2, 0), a float and an int
+        words.extend([0, 12, 1, 2, 0, 3, 1.5f32.to_bits(), 2, -4i32 as u32]);
+        // Shader 1, line 7, no values
+        words.extend([1, 7, 0, 0, 0, 0, 0, 0, 0]);
+        let dump = words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let (count, failures) = decode(&dump);
+        assert_eq!(count, 3);
+        assert_eq!(
+            failures,
+            [
+                Failure {
+                    shader: 0,
+                    line: 12,
+                    invocation: [1, 2, 0],
+                    values: vec!["1.5".to_string(), "-4".to_string()],
+                },
+                Failure {
+                    shader: 1,
+                    line: 7,
+                    invocation: [0, 0, 0],
+                    values: Vec::new(),
+                },
+            ]
+        );
+        assert_eq!(decode(&[]), (0, Vec::new()));
+    }
+}
@@ -0,0 +1,213 @@
+//! Color blending state as vkrunner's pipeline properties, and the CPU
+//! evaluation of the blend equations on a UNORM framebuffer so composites
+//! can be predicted exactly.
+
+use rmcp::schemars::{self, JsonSchema};
+
+/// Blend factors, except the constant ones vkrunner has no command for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, JsonSchema)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    SrcAlphaSaturate,
+}
+
+impl std::fmt::Display for BlendFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BlendFactor::Zero => "VK_BLEND_FACTOR_ZERO",
+            BlendFactor::One => "VK_BLEND_FACTOR_ONE",
+            BlendFactor::SrcColor => "VK_BLEND_FACTOR_SRC_COLOR",
+            BlendFactor::OneMinusSrcColor => "VK_BLEND_FACTOR_ONE_MINUS_SRC_COLOR",
+            BlendFactor::DstColor => "VK_BLEND_FACTOR_DST_COLOR",
+            BlendFactor::OneMinusDstColor => "VK_BLEND_FACTOR_ONE_MINUS_DST_COLOR",
+            BlendFactor::SrcAlpha => "VK_BLEND_FACTOR_SRC_ALPHA",
+            BlendFactor::OneMinusSrcAlpha => "VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA",
+            BlendFactor::DstAlpha => "VK_BLEND_FACTOR_DST_ALPHA",
+            BlendFactor::OneMinusDstAlpha => "VK_BLEND_FACTOR_ONE_MINUS_DST_ALPHA",
+            BlendFactor::SrcAlphaSaturate => "VK_BLEND_FACTOR_SRC_ALPHA_SATURATE",
+        })
+    }
+}
+
+impl BlendFactor {
+    /// The factor for channel `c` (3 is alpha) of `src` over `dst`.
+    fn value(self, c: usize, src: [f64; 4], dst: [f64; 4]) -> f64 {
+        match self {
+            BlendFactor::Zero => 0.0,
+            BlendFactor::One => 1.0,
+            BlendFactor::SrcColor => src[c],
+            BlendFactor::OneMinusSrcColor => 1.0 - src[c],
+            BlendFactor::DstColor => dst[c],
+            BlendFactor::OneMinusDstColor => 1.0 - dst[c],
+            BlendFactor::SrcAlpha => src[3],
+            BlendFactor::OneMinusSrcAlpha => 1.0 - src[3],
+            BlendFactor::DstAlpha => dst[3],
+            BlendFactor::OneMinusDstAlpha => 1.0 - dst[3],
+            BlendFactor::SrcAlphaSaturate if c == 3 => 1.0,
+            BlendFactor::SrcAlphaSaturate => src[3].min(1.0 - dst[3]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, JsonSchema)]
+pub enum BlendOp {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+impl std::fmt::Display for BlendOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BlendOp::Add => "VK_BLEND_OP_ADD",
+            BlendOp::Subtract => "VK_BLEND_OP_SUBTRACT",
+            BlendOp::ReverseSubtract => "VK_BLEND_OP_REVERSE_SUBTRACT",
+            BlendOp::Min => "VK_BLEND_OP_MIN",
+            BlendOp::Max => "VK_BLEND_OP_MAX",
+        })
+    }
+}
+
+/// The blend state of the color attachment, as in
+/// `VkPipelineColorBlendAttachmentState`.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, JsonSchema)]
+pub struct BlendState {
+    #[schemars(description = "True to blend; false writes the shader's color unchanged")]
+    pub enable: bool,
+    #[schemars(description = "Factor of the shader's color (default: SrcAlpha)")]
+    pub src_color: Option<BlendFactor>,
+    #[schemars(description = "Factor of the framebuffer's color (default: OneMinusSrcAlpha)")]
+    pub dst_color: Option<BlendFactor>,
+    #[schemars(description = "How the weighted colors combine (default: Add)")]
+    pub color_op: Option<BlendOp>,
+    #[schemars(description = "Factor of the shader's alpha (default: One)")]
+    pub src_alpha: Option<BlendFactor>,
+    #[schemars(description = "Factor of the framebuffer's alpha (default: OneMinusSrcAlpha)")]
+    pub dst_alpha: Option<BlendFactor>,
+    #[schemars(description = "How the weighted alphas combine (default: Add)")]
+    pub alpha_op: Option<BlendOp>,
+}
+
+impl BlendState {
+    /// Straight-alpha "over": the default of every field.
+    pub const OVER: BlendState = BlendState {
+        enable: true,
+        src_color: None,
+        dst_color: None,
+        color_op: None,
+        src_alpha: None,
+        dst_alpha: None,
+        alpha_op: None,
+    };
+
+    fn factors(&self) -> [(BlendFactor, BlendFactor, BlendOp); 2] {
+        [
+            (
+                self.src_color.unwrap_or(BlendFactor::SrcAlpha),
+                self.dst_color.unwrap_or(BlendFactor::OneMinusSrcAlpha),
+                self.color_op.unwrap_or(BlendOp::Add),
+            ),
+            (
+                self.src_alpha.unwrap_or(BlendFactor::One),
+                self.dst_alpha.unwrap_or(BlendFactor::OneMinusSrcAlpha),
+                self.alpha_op.unwrap_or(BlendOp::Add),
+            ),
+        ]
+    }
+
+    /// The pipeline properties setting this state.
+    pub fn properties(&self) -> Vec<(&'static str, String)> {
+        let [
+            (src_color, dst_color, color_op),
+            (src_alpha, dst_alpha, alpha_op),
+        ] = self.factors();
+        vec![
+            ("blendEnable", self.enable.to_string()),
+            ("srcColorBlendFactor", src_color.to_string()),
+            ("dstColorBlendFactor", dst_color.to_string()),
+            ("colorBlendOp", color_op.to_string()),
+            ("srcAlphaBlendFactor", src_alpha.to_string()),
+            ("dstAlphaBlendFactor", dst_alpha.to_string()),
+            ("alphaBlendOp", alpha_op.to_string()),
+        ]
+    }
+
+    /// The framebuffer value after drawing `src` over `dst` on an 8-bit
+    /// UNORM attachment: the shader's color is clamped, blended and the
+    /// result rounded to the stored precision.
+    pub fn composite(&self, src: [f64; 4], dst: [f64; 4]) -> [f64; 4] {
+        let src = src.map(|c| c.clamp(0.0, 1.0));
+        let blended = if self.enable {
+            let factors = self.factors();
+            [0, 1, 2, 3].map(|c| {
+                let (src_factor, dst_factor, op) = factors[usize::from(c == 3)];
+                let s = src[c] * src_factor.value(c, src, dst);
+                let d = dst[c] * dst_factor.value(c, src, dst);
+                match op {
+                    BlendOp::Add => s + d,
+                    BlendOp::Subtract => s - d,
+                    BlendOp::ReverseSubtract => d - s,
+                    // Min and max ignore the factors
+                    BlendOp::Min => src[c].min(dst[c]),
+                    BlendOp::Max => src[c].max(dst[c]),
+                }
+            })
+        } else {
+            src
+        };
+        blended.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() / 255.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_composite() {
+        let background = [0.0, 0.0, 1.0, 1.0];
+        let red = [1.0, 0.0, 0.0, 0.5];
+        let over = BlendState::OVER.composite(red, background);
+        assert_eq!(over, [128.0 / 255.0, 0.0, 128.0 / 255.0, 1.0]);
+
+        let additive = BlendState {
+            src_color: Some(BlendFactor::One),
+            dst_color: Some(BlendFactor::One),
+            ..BlendState::OVER
+        };
+        assert_eq!(
+            additive.composite([0.75, 0.5, 0.0, 1.0], [0.5, 0.25, 0.0, 1.0])[..2],
+            [1.0, 191.0 / 255.0]
+        );
+
+        let disabled = BlendState {
+            enable: false,
+            ..BlendState::OVER
+        };
+        assert_eq!(
+            disabled.composite(red, background),
+            [1.0, 0.0, 0.0, 128.0 / 255.0]
+        );
+        assert_eq!(
+            disabled.properties()[..2],
+            [
+                ("blendEnable", "false".to_string()),
+                (
+                    "srcColorBlendFactor",
+                    "VK_BLEND_FACTOR_SRC_ALPHA".to_string()
+                )
+            ]
+        );
+    }
+}
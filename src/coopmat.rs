@@ -0,0 +1,345 @@
+//! A verified matrix multiplication on cooperative matrices
+//! (VK_KHR_cooperative_matrix): each subgroup computes tiles of
+//! C = A × B with coopMatMulAdd, and the result is checked against a
+//! CPU GEMM with error bounds for the accumulator's precision.
+
+use rmcp::schemars::{self, JsonSchema};
+
+/// Component types of cooperative matrices, spelled as in GLSL and in
+/// vkrunner's `cooperative_matrix` requirement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, JsonSchema)]
+pub enum Component {
+    #[serde(rename = "float16_t")]
+    Float16,
+    #[serde(rename = "float")]
+    Float32,
+    #[serde(rename = "int8_t")]
+    Int8,
+    #[serde(rename = "int")]
+    Int32,
+}
+
+impl Component {
+    pub fn glsl(self) -> &'static str {
+        match self {
+            Component::Float16 => "float16_t",
+            Component::Float32 => "float",
+            Component::Int8 => "int8_t",
+            Component::Int32 => "int",
+        }
+    }
+
+    /// The type of the component in vkrunner's subdata commands.
+    pub fn data_type(self) -> crate::DataType {
+        match self {
+            Component::Float16 => crate::DataType::Float16T,
+            Component::Float32 => crate::DataType::Float,
+            Component::Int8 => crate::DataType::Int8T,
+            Component::Int32 => crate::DataType::Int,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Component::Float16 => 2,
+            Component::Float32 | Component::Int32 => 4,
+            Component::Int8 => 1,
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        matches!(self, Component::Int8 | Component::Int32)
+    }
+
+    /// Relative rounding error of one operation, 0 for integers.
+    fn epsilon(self) -> f64 {
+        match self {
+            Component::Float16 => 2f64.powi(-11),
+            Component::Float32 => 2f64.powi(-24),
+            Component::Int8 | Component::Int32 => 0.0,
+        }
+    }
+
+    /// Reads the little-endian value at `index` of a dumped array.
+    fn read(self, dump: &[u8], index: usize) -> Option<f64> {
+        let bytes = dump.get(index * self.size()..(index + 1) * self.size())?;
+        Some(match self {
+            Component::Float16 => crate::fp::half_value(u16::from_le_bytes([bytes[0], bytes[1]])),
+            Component::Float32 => {
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).into()
+            }
+            Component::Int8 => f64::from(bytes[0] as i8),
+            Component::Int32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).into(),
+        })
+    }
+}
+
+/// A cooperative matrix configuration: the tile sizes of A (M × K),
+/// B (K × N) and the accumulator (M × N) and their component types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub m: u32,
+    pub n: u32,
+    pub k: u32,
+    /// Component type of A and B.
+    pub input: Component,
+    /// Component type of the accumulator and the result.
+    pub accumulator: Component,
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{}x{} {} -> {}",
+            self.m,
+            self.n,
+            self.k,
+            self.input.glsl(),
+            self.accumulator.glsl()
+        )
+    }
+}
+
+const fn config(m: u32, n: u32, k: u32, input: Component, accumulator: Component) -> Config {
+    Config {
+        m,
+        n,
+        k,
+        input,
+        accumulator,
+    }
+}
+
+/// Configurations drivers commonly report, tried in order until the
+/// device supports one.
+pub const CANDIDATES: &[Config] = &[
+    config(16, 16, 16, Component::Float16, Component::Float32),
+    config(16, 16, 16, Component::Float16, Component::Float16),
+    config(16, 8, 16, Component::Float16, Component::Float32),
+    config(16, 8, 8, Component::Float16, Component::Float32),
+    config(8, 16, 16, Component::Float16, Component::Float32),
+    config(16, 16, 8, Component::Float32, Component::Float32),
+    config(16, 16, 16, Component::Int8, Component::Int32),
+    config(16, 16, 32, Component::Int8, Component::Int32),
+    config(16, 8, 32, Component::Int8, Component::Int32),
+    config(8, 16, 32, Component::Int8, Component::Int32),
+    config(8, 8, 32, Component::Int8, Component::Int32),
+];
+
+/// Dimensions of the whole product: C is `m` × `n` and the sums run
+/// over `k`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Problem {
+    pub m: u32,
+    pub n: u32,
+    pub k: u32,
+}
+
+impl Problem {
+    /// A product of about `size` in every dimension, rounded up to whole
+    /// tiles of `config`.
+    pub fn new(size: u32, config: &Config) -> Self {
+        Self {
+            m: size.div_ceil(config.m) * config.m,
+            n: size.div_ceil(config.n) * config.n,
+            k: size.div_ceil(config.k) * config.k,
+        }
+    }
+
+    pub fn tiles(&self, config: &Config) -> u32 {
+        (self.m / config.m) * (self.n / config.n)
+    }
+
+    /// Multiply-adds counted as two operations each.
+    pub fn operations(&self) -> f64 {
+        2.0 * f64::from(self.m) * f64::from(self.n) * f64::from(self.k)
+    }
+
+    pub fn result_bytes(&self, config: &Config) -> usize {
+        (self.m * self.n) as usize * config.accumulator.size()
+    }
+}
+
+/// Small values every input type holds exactly, eighths in [-1, 1] for
+/// floats and integers in [-8, 8].
+fn input_value(matrix: u32, index: u32, component: Component) -> f64 {
+    let step = index
+        .wrapping_mul(37 + 2 * matrix)
+        .wrapping_add(11 * matrix + 5)
+        % 17;
+    let value = f64::from(step) - 8.0;
+    if component.is_integer() {
+        value
+    } else {
+        value / 8.0
+    }
+}
+
+/// The values of A (matrix 0) or B (matrix 1) in row-major order, as
+/// SSBO subdata values.
+pub fn inputs(matrix: u32, problem: &Problem, config: &Config) -> Vec<String> {
+    let count = match matrix {
+        0 => problem.m * problem.k,
+        _ => problem.k * problem.n,
+    };
+    (0..count)
+        .map(|index| input_value(matrix, index, config.input).to_string())
+        .collect()
+}
+
+/// Compute shader computing C = A × B with A, B and C at bindings 0, 1
+/// and 2. Subgroups take the tiles of C in turn, so it is correct for
+/// any subgroup size and number of workgroups.
+pub fn shader(problem: &Problem, config: &Config) -> String {
+    let zero = if config.accumulator.is_integer() {
+        "0"
+    } else {
+        "0.0"
+    };
+    format!(
+        "#version 450
+#extension GL_KHR_cooperative_matrix : require
+#extension GL_KHR_memory_scope_semantics : require
+#extension GL_KHR_shader_subgroup_basic : require
+#extension GL_EXT_shader_explicit_arithmetic_types : require
+layout(local_size_x = 64) in;
+layout(std430, binding = 0) readonly buffer A {{ {input} a[]; }};
+layout(std430, binding = 1) readonly buffer B {{ {input} b[]; }};
+layout(std430, binding = 2) writeonly buffer C {{ {accumulator} c[]; }};
+const uint M = {pm}u, N = {pn}u, K = {pk}u;
+void main()
+{{
+    uint tiles = (M / {m}u) * (N / {n}u);
+    uint first = gl_WorkGroupID.x * gl_NumSubgroups + gl_SubgroupID;
+    uint stride = gl_NumWorkGroups.x * gl_NumSubgroups;
+    for (uint tile = first; tile < tiles; tile += stride) {{
+        uint row = tile / (N / {n}u) * {m}u;
+        uint col = tile % (N / {n}u) * {n}u;
+        coopmat<{accumulator}, gl_ScopeSubgroup, {m}, {n}, gl_MatrixUseAccumulator> sum =
+            coopmat<{accumulator}, gl_ScopeSubgroup, {m}, {n}, gl_MatrixUseAccumulator>({zero});
+        for (uint k = 0u; k < K; k += {k}u) {{
+            coopmat<{input}, gl_ScopeSubgroup, {m}, {k}, gl_MatrixUseA> tile_a;
+            coopmat<{input}, gl_ScopeSubgroup, {k}, {n}, gl_MatrixUseB> tile_b;
+            coopMatLoad(tile_a, a, row * K + k, K, gl_CooperativeMatrixLayoutRowMajor);
+            coopMatLoad(tile_b, b, k * N + col, N, gl_CooperativeMatrixLayoutRowMajor);
+            sum = coopMatMulAdd(tile_a, tile_b, sum);
+        }}
+        coopMatStore(sum, c, row * N + col, N, gl_CooperativeMatrixLayoutRowMajor);
+    }}
+}}
+",
+        input = config.input.glsl(),
+        accumulator = config.accumulator.glsl(),
+        pm = problem.m,
+        pn = problem.n,
+        pk = problem.k,
+        m = config.m,
+        n = config.n,
+        k = config.k,
+    )
+}
+
+/// How the dumped C compares with the CPU GEMM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub max_error: f64,
+    /// The largest error relative to the magnitude of its sum.
+    pub max_relative_error: f64,
+    /// Elements off by more than the accumulator's rounding allows.
+    pub mismatches: usize,
+    /// Row, column, result and reference of the first of them.
+    pub first: Option<(u32, u32, f64, f64)>,
+}
+
+/// Compares the C of a run with the CPU GEMM. A float element may be off
+/// by K rounding errors of the accumulator on the sum of the magnitudes
+/// of its products; an integer one must be exact.
+pub fn verify(dump: &[u8], problem: &Problem, config: &Config) -> Verification {
+    let a = |row: u32, k: u32| input_value(0, row * problem.k + k, config.input);
+    let b = |k: u32, col: u32| input_value(1, k * problem.n + col, config.input);
+
+    let mut verification = Verification {
+        max_error: 0.0,
+        max_relative_error: 0.0,
+        mismatches: 0,
+        first: None,
+    };
+    for row in 0..problem.m {
+        for col in 0..problem.n {
+            let (expected, magnitude) = (0..problem.k).fold((0.0, 0.0), |(sum, magnitude), k| {
+                let product = a(row, k) * b(k, col);
+                (sum + product, magnitude + product.abs())
+            });
+            let actual = config
+                .accumulator
+                .read(dump, (row * problem.n + col) as usize)
+                .unwrap_or(f64::NAN);
+
+            let error = (actual - expected).abs();
+            let bound = f64::from(problem.k) * config.accumulator.epsilon() * magnitude;
+            if error.is_nan() || error > bound {
+                verification.mismatches += 1;
+                verification
+                    .first
+                    .get_or_insert((row, col, actual, expected));
+            }
+            if error.is_nan() || error > verification.max_error {
+                verification.max_error = error;
+            }
+            let relative = error / magnitude.max(1.0);
+            if relative.is_nan() || relative > verification.max_relative_error {
+                verification.max_relative_error = relative;
+            }
+        }
+    }
+    verification
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let config = config(16, 8, 16, Component::Float16, Component::Float32);
+        let problem = Problem::new(20, &config);
+        assert_eq!(
+            problem,
+            Problem {
+                m: 32,
+                n: 24,
+                k: 32
+            }
+        );
+
+        let mut dump = Vec::new();
+        for row in 0..problem.m {
+            for col in 0..problem.n {
+                let sum = (0..problem.k)
+                    .map(|k| {
+                        input_value(0, row * problem.k + k, config.input)
+                            * input_value(1, k * problem.n + col, config.input)
+                    })
+                    .sum::<f64>();
+                dump.extend((sum as f32).to_le_bytes());
+            }
+        }
+        assert_eq!(dump.len(), problem.result_bytes(&config));
+        let verification = verify(&dump, &problem, &config);
+        assert_eq!(verification.mismatches, 0);
+        assert_eq!(verification.max_error, 0.0);
+
+        // One element off by a quarter
+        let index = (3 * problem.n + 5) as usize * 4;
+        let value = f32::from_le_bytes(dump[index..index + 4].try_into().unwrap()) + 0.25;
+        dump[index..index + 4].copy_from_slice(&value.to_le_bytes());
+        let verification = verify(&dump, &problem, &config);
+        assert_eq!(verification.mismatches, 1);
+        assert_eq!(verification.max_error, 0.25);
+        assert_eq!(
+            verification.first.map(|first| (first.0, first.1)),
+            Some((3, 5))
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Instruments SPIR-V assembly with a counter per basic block, so a run
+//! can report which parts of a shader actually executed.
+
+use crate::spirv::{Module, SourceLocation};
+
+/// A basic block of the instrumented module.
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Id of the block's `OpLabel`, without the leading `%`.
+    pub label: String,
+    /// Location of the block's first `OpLine`.
+    pub location: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Instrumented {
+    pub text: String,
+    /// Blocks in the order of their counters in the buffer.
+    pub blocks: Vec<Block>,
+}
+
+/// Opcode of an assembly line, after any `%id =`.
+fn opcode(line: &str) -> Option<&str> {
+    let mut tokens = line.split_whitespace();
+    let first = tokens.next()?;
+    if first.starts_with('%') {
+        tokens.nth(1)
+    } else {
+        Some(first)
+    }
+}
+
+/// `; Version: 1.6` from the header that the disassembler writes.
+fn version(text: &str) -> (u32, u32) {
+    text.lines()
+        .find_map(|line| line.strip_prefix("; Version: "))
+        .and_then(|version| version.trim().split_once('.'))
+        .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)))
+        .unwrap_or((1, 0))
+}
+
+/// Adds a buffer of one `uint` per basic block at set 0, `binding`, and
+/// an atomic increment of the block's counter at the start of each
+/// block. The counters count invocations, so a block in a loop counts
+/// each iteration.
+pub fn instrument(text: &str, binding: u32) -> Result<Instrumented, String> {
+    let module = Module::parse(text);
+    let locations = module.instruction_locations();
+
+    let mut blocks = Vec::<Block>::new();
+    let mut pending = false;
+    for (instruction, location) in module.instructions.iter().zip(locations) {
+        match instruction.opcode.as_str() {
+            "OpLabel" => {
+                blocks.push(Block {
+                    label: instruction.result_id.clone().unwrap_or_default(),
+                    location: None,
+                });
+                pending = true;
+            }
+            "OpFunctionEnd" => pending = false,
+            // A line carried over from another block says nothing
+            // about this one
+            "OpLine" => {
+                if let (true, Some(block)) = (pending, blocks.last_mut()) {
+                    block.location = location;
+                    pending = false;
+                }
+            }
+            _ => {}
+        }
+    }
+    if blocks.is_empty() {
+        return Err("The module has no function bodies to instrument".to_string());
+    }
+
+    let lines = text.lines().collect::<Vec<_>>();
+    let (Some(first_type), Some(first_function)) = (
+        lines
+            .iter()
+            .position(|line| opcode(line).is_some_and(|op| op.starts_with("OpType"))),
+        lines
+            .iter()
+            .position(|line| opcode(line) == Some("OpFunction")),
+    ) else {
+        return Err("The module has no types or functions".to_string());
+    };
+
+    // StorageBuffer is core from SPIR-V 1.3, and from 1.4 entry points
+    // list every global variable they use
+    let version = version(text);
+    let (class, block_decoration) = if version >= (1, 3) {
+        ("StorageBuffer", "Block")
+    } else {
+        ("Uniform", "BufferBlock")
+    };
+
+    // Integer types can't be declared twice
+    let existing_uint = module
+        .with_opcode("OpTypeInt")
+        .find(|instruction| instruction.operands == ["32", "0"])
+        .and_then(|instruction| instruction.result_id.as_deref());
+    let uint = format!("%{}", existing_uint.unwrap_or("cov_uint"));
+
+    let decorations = [
+        "OpDecorate %cov_array ArrayStride 4".to_string(),
+        "OpMemberDecorate %cov_struct 0 Offset 0".to_string(),
+        format!("OpDecorate %cov_struct {block_decoration}"),
+        "OpDecorate %cov_counters DescriptorSet 0".to_string(),
+        format!("OpDecorate %cov_counters Binding {binding}"),
+    ];
+    let mut globals = Vec::new();
+    if existing_uint.is_none() {
+        globals.push("%cov_uint = OpTypeInt 32 0".to_string());
+    }
+    globals.extend([
+        format!("%cov_0 = OpConstant {uint} 0"),
+        format!("%cov_1 = OpConstant {uint} 1"),
+        format!("%cov_count = OpConstant {uint} {}", blocks.len()),
+        format!("%cov_array = OpTypeArray {uint} %cov_count"),
+        "%cov_struct = OpTypeStruct %cov_array".to_string(),
+        format!("%cov_struct_ptr = OpTypePointer {class} %cov_struct"),
+        format!("%cov_uint_ptr = OpTypePointer {class} {uint}"),
+        format!("%cov_counters = OpVariable %cov_struct_ptr {class}"),
+    ]);
+    globals.extend(
+        (0..blocks.len()).map(|index| format!("%cov_index_{index} = OpConstant {uint} {index}")),
+    );
+
+    let mut instrumented = Vec::new();
+    let mut labels = 0;
+    let mut pending = false;
+    for (index, line) in lines.iter().enumerate() {
+        let op = opcode(line);
+        let block_start = !matches!(
+            op,
+            None | Some("OpPhi" | "OpVariable" | "OpLine" | "OpNoLine")
+        );
+        if pending && block_start {
+            // Scope Device (1), semantics Relaxed (0)
+            let block = labels - 1;
+            instrumented.push(format!(
+                "%cov_ptr_{block} = OpAccessChain %cov_uint_ptr %cov_counters %cov_0 %cov_index_{block}"
+            ));
+            instrumented.push(format!(
+                "%cov_add_{block} = OpAtomicIAdd {uint} %cov_ptr_{block} %cov_1 %cov_0 %cov_1"
+            ));
+            pending = false;
+        }
+        if index == first_type {
+            instrumented.extend(decorations.iter().cloned());
+        }
+        if index == first_function {
+            instrumented.append(&mut globals);
+        }
+
+        match op {
+            Some("OpEntryPoint") if version >= (1, 4) => {
+                instrumented.push(format!("{line} %cov_counters"));
+            }
+            Some("OpLabel") => {
+                labels += 1;
+                pending = true;
+                instrumented.push(line.to_string());
+            }
+            _ => instrumented.push(line.to_string()),
+        }
+    }
+
+    if labels != blocks.len() {
+        return Err(format!(
+            "Found {labels} block labels in the text but {} in the module",
+            blocks.len()
+        ));
+    }
+
+    Ok(Instrumented {
+        text: instrumented.join("\n"),
+        blocks,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHADER: &str = "; SPIR-V
+; Version: 1.0
+OpCapability Shader
+OpEntryPoint GLCompute %main \"main\"
+%file = OpString \"test.comp\"
+%void = OpTypeVoid
+%fn = OpTypeFunction %void
+%bool = OpTypeBool
+%cond = OpConstantTrue %bool
+%uint = OpTypeInt 32 0
+%main = OpFunction %void None %fn
+%entry = OpLabel
+%x = OpVariable %ptr Function
+OpLine %file 3 0
+OpSelectionMerge %merge None
+OpBranchConditional %cond %then %merge
+%then = OpLabel
+OpLine %file 5 0
+OpBranch %merge
+%merge = OpLabel
+OpReturn
+OpFunctionEnd";
+
+    #[test]
+    fn test_instrument() {
+        let instrumented = instrument(SHADER, 4).unwrap();
+        assert_eq!(
+            instrumented
+                .blocks
+                .iter()
+                .map(|block| (
+                    block.label.as_str(),
+                    block.location.as_ref().map(|location| location.line)
+                ))
+                .collect::<Vec<_>>(),
+            [("entry", Some(3)), ("then", Some(5)), ("merge", None)]
+        );
+
+        let lines = instrumented.text.lines().collect::<Vec<_>>();
+        // The module's uint is reused, and SPIR-V 1.0 has no StorageBuffer
+        assert!(!instrumented.text.contains("%cov_uint = OpTypeInt"));
+        assert!(lines.contains(&"%cov_count = OpConstant %uint 3"));
+        assert!(lines.contains(&"OpDecorate %cov_struct BufferBlock"));
+        assert!(lines.contains(&"OpDecorate %cov_counters Binding 4"));
+        assert!(lines.contains(&"OpEntryPoint GLCompute %main \"main\""));
+
+        // Counting starts after the block's variables and lines
+        let entry = lines.iter().position(|line| *line == "%entry = OpLabel");
+        assert_eq!(
+            entry.map(|entry| &lines[entry + 1..entry + 5]),
+            Some(
+                &[
+                    "%x = OpVariable %ptr Function",
+                    "OpLine %file 3 0",
+                    "%cov_ptr_0 = OpAccessChain %cov_uint_ptr %cov_counters %cov_0 %cov_index_0",
+                    "%cov_add_0 = OpAtomicIAdd %uint %cov_ptr_0 %cov_1 %cov_0 %cov_1",
+                ][..]
+            )
+        );
+    }
+
+    #[test]
+    fn test_instrument_version() {
+        let text = SHADER
+            .replace("; Version: 1.0", "; Version: 1.5")
+            .replace("%uint = OpTypeInt 32 0\n", "");
+        let instrumented = instrument(&text, 0).unwrap();
+        let lines = instrumented.text.lines().collect::<Vec<_>>();
+        assert!(lines.contains(&"%cov_uint = OpTypeInt 32 0"));
+        assert!(lines.contains(&"OpDecorate %cov_struct Block"));
+        assert!(lines.contains(&"%cov_counters = OpVariable %cov_struct_ptr StorageBuffer"));
+        assert!(lines.contains(&"OpEntryPoint GLCompute %main \"main\" %cov_counters"));
+
+        assert!(instrument("OpCapability Shader", 0).is_err());
+    }
+}
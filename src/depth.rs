@@ -0,0 +1,95 @@
+//! Empirical depth-buffer precision: every row of the frame draws two
+//! surfaces a small separation apart at one view distance, log-spaced
+//! from the near plane at the top to the far plane at the bottom. The back
+//! surface is green and drawn first, the front one red; rows where green
+//! survives are distances the depth buffer can't tell the two apart.
+
+/// vkrunner's default framebuffer size.
+pub const RESOLUTION: u32 = 250;
+
+/// Writes the depth a perspective projection with the pushed near and
+/// far planes gives the surface at the row's distance. Flags: 1 for
+/// reversed Z, 2 for the background, which is written at the far plane,
+/// and 4 for an offset relative to the distance.
+pub const SHADER: &str = "#version 450
+layout(push_constant) uniform Layer {
+    vec4 color;
+    float near;
+    float far;
+    float offset;
+    uint flags;
+};
+layout(location = 0) out vec4 frag_color;
+void main()
+{
+    bool reversed = (flags & 1u) != 0u;
+    float w = near * pow(far / near, gl_FragCoord.y / 250.0);
+    w += (flags & 4u) != 0u ? w * offset : offset;
+    float depth = reversed ? near * (far - w) / (w * (far - near))
+                           : far * (w - near) / (w * (far - near));
+    if ((flags & 2u) != 0u)
+        depth = reversed ? 0.0 : 1.0;
+    gl_FragDepth = depth;
+    frag_color = color;
+}
+";
+
+pub const REVERSED: u32 = 1;
+pub const BACKGROUND: u32 = 2;
+pub const RELATIVE: u32 = 4;
+
+/// The view distance of the surfaces in row `row`.
+pub fn distance(row: u32, near: f64, far: f64) -> f64 {
+    near * (far / near).powf((f64::from(row) + 0.5) / f64::from(RESOLUTION))
+}
+
+/// Whether each row shows the back surface (z-fighting) in more than
+/// half of its pixels: green above red.
+pub fn fighting_rows(img: &image::RgbImage) -> Vec<bool> {
+    img.rows()
+        .map(|row| {
+            let pixels = row.len();
+            let green = row.filter(|pixel| pixel[1] > pixel[0]).count();
+            2 * green > pixels
+        })
+        .collect()
+}
+
+/// The distance from which on every row fights, if any: beyond it the
+/// two surfaces are never resolved.
+pub fn resolved_until(fighting: &[bool], near: f64, far: f64) -> Option<f64> {
+    let last_resolved = fighting.iter().rposition(|fighting| !fighting);
+    match last_resolved {
+        Some(row) if row + 1 == fighting.len() => None,
+        Some(row) => Some(distance(row as u32 + 1, near, far)),
+        None => Some(near),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fighting_rows() {
+        assert!((distance(0, 0.1, 1000.0) - 0.1 * 10_000f64.powf(0.5 / 250.0)).abs() < 1e-12);
+        assert!(distance(RESOLUTION - 1, 0.1, 1000.0) < 1000.0);
+
+        // Red in the top 100 rows, green below
+        let img = image::RgbImage::from_fn(RESOLUTION, RESOLUTION, |_, y| {
+            if y < 100 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 255, 0])
+            }
+        });
+        let fighting = fighting_rows(&img);
+        assert_eq!(fighting.iter().filter(|fighting| **fighting).count(), 150);
+        assert_eq!(
+            resolved_until(&fighting, 1.0, 100.0),
+            Some(distance(100, 1.0, 100.0))
+        );
+        assert_eq!(resolved_until(&[false, false], 1.0, 100.0), None);
+        assert_eq!(resolved_until(&[true, true], 1.0, 100.0), Some(1.0));
+    }
+}
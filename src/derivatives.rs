@@ -0,0 +1,203 @@
+//! Checks screen-space derivatives: a fragment shader stores the
+//! dFdx/dFdy it computes for `float value(vec2 p)` in a buffer, a compute
+//! kernel stores central differences of the same function taken at a
+//! finer step, and the two are compared pixel by pixel.
+
+/// The frame the fragment shader covers, vkrunner's default framebuffer.
+pub const RESOLUTION: u32 = 250;
+
+/// The derivatives checked when no `derivatives` function is given.
+const DEFAULT_DERIVATIVES: &str = "
+vec2 derivatives(vec2 p)
+{
+    float v = value(p);
+    return vec2(dFdx(v), dFdy(v));
+}
+";
+
+fn header(source: &str, pixel_size: f64) -> String {
+    format!(
+        "#version 450
+layout(std430, binding = 0) buffer Results {{ vec4 results[]; }};
+const float PIXEL_SIZE = {pixel_size:?};
+{source}
+"
+    )
+}
+
+/// The fragment shader storing value(p) and derivatives(p) for every
+/// pixel, with p = gl_FragCoord.xy * pixel_size. Without a
+/// `derivatives` function, it takes dFdx and dFdy of value(p).
+pub fn fragment_shader(source: &str, derivatives: Option<&str>, pixel_size: f64) -> String {
+    format!(
+        "{}
+{}
+layout(location = 0) out vec4 frag_color;
+void main()
+{{
+    vec2 p = gl_FragCoord.xy * PIXEL_SIZE;
+    vec2 d = derivatives(p);
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    results[pixel.y * {RESOLUTION} + pixel.x] = vec4(value(p), d, 1.0);
+    frag_color = vec4(0.0, 0.0, 0.0, 1.0);
+}}
+",
+        header(source, pixel_size),
+        derivatives.unwrap_or(DEFAULT_DERIVATIVES)
+    )
+}
+
+/// The compute kernel storing, per pixel, the value and the derivatives
+/// per pixel that fine dFdx/dFdy should produce: central differences at
+/// `1 / refine` of a pixel, taken midway between the pixel's 2 × 2 quad
+/// neighbors as the GPU's differences are.
+pub fn reference_shader(source: &str, pixel_size: f64, refine: u32) -> String {
+    format!(
+        "{}
+layout(local_size_x = 8, local_size_y = 8) in;
+float sample_at(vec2 pixel)
+{{
+    return value(pixel * PIXEL_SIZE);
+}}
+void main()
+{{
+    uvec2 id = gl_GlobalInvocationID.xy;
+    if (id.x >= {RESOLUTION}u || id.y >= {RESOLUTION}u)
+        return;
+    vec2 center = vec2(id) + 0.5;
+    vec2 quad = vec2(id & ~1u) + 1.0;
+    const float h = 1.0 / {refine}.0;
+    float dx = (sample_at(vec2(quad.x + h, center.y)) - sample_at(vec2(quad.x - h, center.y))) / (2.0 * h);
+    float dy = (sample_at(vec2(center.x, quad.y + h)) - sample_at(vec2(center.x, quad.y - h))) / (2.0 * h);
+    results[id.y * {RESOLUTION}u + id.x] = vec4(sample_at(center), dx, dy, 1.0);
+}}
+",
+        header(source, pixel_size)
+    )
+}
+
+/// Per-pixel (value, dx, dy) of a dumped results buffer, none for pixels
+/// the shader didn't write.
+pub fn read(dump: &[u8]) -> Vec<Option<[f64; 3]>> {
+    dump.chunks_exact(16)
+        .take((RESOLUTION * RESOLUTION) as usize)
+        .map(|bytes| {
+            let [value, dx, dy, written] = [0, 1, 2, 3].map(|i| {
+                f64::from(f32::from_le_bytes([
+                    bytes[4 * i],
+                    bytes[4 * i + 1],
+                    bytes[4 * i + 2],
+                    bytes[4 * i + 3],
+                ]))
+            });
+            (written == 1.0).then_some([value, dx, dy])
+        })
+        .collect()
+}
+
+/// How the shader's derivatives along one axis compare with the
+/// reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisComparison {
+    pub compared: usize,
+    pub mismatches: usize,
+    /// Root mean square of the reference, the scale errors are judged at.
+    pub reference_rms: f64,
+    pub rms_error: f64,
+    /// Mean of shader minus reference: a bias in one direction.
+    pub mean_error: f64,
+    /// Least-squares k with shader ≈ k × reference: -1 for flipped signs,
+    /// 2 or 0.5 for a wrong scale.
+    pub scale: f64,
+    /// Pixel, shader value and reference of the first mismatch.
+    pub first: Option<((u32, u32), f64, f64)>,
+    /// Bounding box of the mismatches as (min x, min y, max x, max y).
+    pub bounds: Option<(u32, u32, u32, u32)>,
+}
+
+/// Compares axis 1 (dx) or 2 (dy) of the shader's results with the
+/// reference's. A pixel mismatches when it is off by more than
+/// `tolerance` times the larger of its reference and the reference RMS.
+pub fn compare(
+    shader: &[Option<[f64; 3]>],
+    reference: &[Option<[f64; 3]>],
+    axis: usize,
+    tolerance: f64,
+) -> AxisComparison {
+    let pairs = shader
+        .iter()
+        .zip(reference)
+        .enumerate()
+        .filter_map(|(index, (shader, reference))| {
+            Some((index as u32, (*shader)?[axis], (*reference)?[axis]))
+        })
+        .filter(|(_, shader, reference)| shader.is_finite() && reference.is_finite())
+        .collect::<Vec<_>>();
+    let count = pairs.len().max(1) as f64;
+    let reference_rms = (pairs.iter().map(|(_, _, r)| r * r).sum::<f64>() / count).sqrt();
+    let squared_reference = pairs.iter().map(|(_, _, r)| r * r).sum::<f64>();
+
+    let mut comparison = AxisComparison {
+        compared: pairs.len(),
+        mismatches: 0,
+        reference_rms,
+        rms_error: (pairs.iter().map(|(_, s, r)| (s - r).powi(2)).sum::<f64>() / count).sqrt(),
+        mean_error: pairs.iter().map(|(_, s, r)| s - r).sum::<f64>() / count,
+        scale: if squared_reference > 0.0 {
+            pairs.iter().map(|(_, s, r)| s * r).sum::<f64>() / squared_reference
+        } else {
+            1.0
+        },
+        first: None,
+        bounds: None,
+    };
+    for (index, shader, reference) in pairs {
+        let bound = tolerance * reference.abs().max(reference_rms).max(1e-6);
+        if (shader - reference).abs() > bound {
+            let (x, y) = (index % RESOLUTION, index / RESOLUTION);
+            comparison.mismatches += 1;
+            comparison.first.get_or_insert(((x, y), shader, reference));
+            let bounds = comparison.bounds.get_or_insert((x, y, x, y));
+            *bounds = (
+                bounds.0.min(x),
+                bounds.1.min(y),
+                bounds.2.max(x),
+                bounds.3.max(y),
+            );
+        }
+    }
+    comparison
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let pixels = (RESOLUTION * RESOLUTION) as usize;
+        let reference = (0..pixels)
+            .map(|i| Some([0.0, 1.0 + (i % 7) as f64, 2.0]))
+            .collect::<Vec<_>>();
+        let comparison = compare(&reference, &reference, 1, 0.05);
+        assert_eq!((comparison.compared, comparison.mismatches), (pixels, 0));
+        assert_eq!(comparison.scale, 1.0);
+
+        // dy negated in the right half, as if a branch flipped it
+        let shader = reference
+            .iter()
+            .enumerate()
+            .map(|(i, pixel)| {
+                let [value, dx, dy] = pixel.unwrap();
+                let flipped = i as u32 % RESOLUTION >= RESOLUTION / 2;
+                (i != 0).then_some([value, dx, if flipped { -dy } else { dy }])
+            })
+            .collect::<Vec<_>>();
+        let comparison = compare(&shader, &reference, 2, 0.05);
+        assert_eq!(comparison.compared, pixels - 1);
+        assert_eq!(comparison.mismatches, pixels / 2);
+        assert_eq!(comparison.first, Some(((125, 0), -2.0, 2.0)));
+        assert_eq!(comparison.bounds, Some((125, 0, 249, 249)));
+        assert!(comparison.scale.abs() < 0.01);
+    }
+}
@@ -0,0 +1,696 @@
+//! Semantic comparison of two SPIR-V assembly modules: what changed in
+//! the capabilities, entry points, resource layout, constants and the
+//! instructions of each function, ignoring the renumbering of result ids
+//! and debug info such as names and line numbers.
+
+use crate::spirv::{Instruction, Module};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Changes of one aspect of the modules, as lines starting with `+` for
+/// what only the second module has, `-` for what only the first has and
+/// `~` for what both have in different forms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: &'static str,
+    pub changes: Vec<String>,
+}
+
+/// Largest number of instruction pairs aligned per function; longer
+/// functions only get their opcode counts compared.
+const MAX_ALIGNMENT: usize = 4_000_000;
+
+/// Lookups over one module.
+struct Index<'a> {
+    module: &'a Module,
+    definitions: HashMap<&'a str, &'a Instruction>,
+    names: HashMap<&'a str, String>,
+}
+
+fn is_numbered(id: &str) -> bool {
+    id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The instruction with ids that shaderc numbers rather than names
+/// replaced by `%_`, so that renumbering doesn't count as a change.
+fn normalize(instruction: &Instruction) -> String {
+    let operands = instruction
+        .operands
+        .iter()
+        .map(|operand| match operand.strip_prefix('%') {
+            Some(id) if is_numbered(id) => "%_",
+            _ => operand.as_str(),
+        });
+    let mut text = match &instruction.result_id {
+        Some(id) if is_numbered(id) => "%_ = ".to_string(),
+        Some(id) => format!("%{id} = "),
+        None => String::new(),
+    };
+    text.push_str(&instruction.opcode);
+    for operand in operands {
+        text.push(' ');
+        text.push_str(operand);
+    }
+    text
+}
+
+impl<'a> Index<'a> {
+    fn new(module: &'a Module) -> Index<'a> {
+        let definitions = module
+            .instructions
+            .iter()
+            .filter_map(|instruction| Some((instruction.result_id.as_deref()?, instruction)))
+            .collect();
+        let names = module
+            .with_opcode("OpName")
+            .filter_map(|instruction| {
+                let target = instruction.operands.first()?.strip_prefix('%')?;
+                let name = instruction.string_operand(1)?;
+                (!name.is_empty()).then(|| (target, name.to_string()))
+            })
+            .collect();
+        Index {
+            module,
+            definitions,
+            names,
+        }
+    }
+
+    /// What to call `id` (with or without its `%`): its debug name, or
+    /// the id itself.
+    fn name(&self, id: &str) -> String {
+        let id = id.trim_start_matches('%');
+        self.names
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| format!("%{id}"))
+    }
+
+    /// Decorations of `id` as "Decoration args" strings.
+    fn decorations(&self, id: &str) -> Vec<String> {
+        let target = format!("%{}", id.trim_start_matches('%'));
+        self.module
+            .with_opcode("OpDecorate")
+            .filter(|instruction| instruction.operands.first() == Some(&target))
+            .map(|instruction| instruction.operands[1..].join(" "))
+            .collect()
+    }
+
+    /// Decorations of member `member` of struct `id`.
+    fn member_decorations(&self, id: &str, member: usize) -> Vec<String> {
+        let target = format!("%{}", id.trim_start_matches('%'));
+        let member = member.to_string();
+        self.module
+            .with_opcode("OpMemberDecorate")
+            .filter(|instruction| {
+                instruction.operands.first() == Some(&target)
+                    && instruction.operands.get(1) == Some(&member)
+            })
+            .map(|instruction| instruction.operands[2..].join(" "))
+            .collect()
+    }
+
+    fn member_name(&self, id: &str, member: usize) -> Option<String> {
+        let target = format!("%{}", id.trim_start_matches('%'));
+        let member = member.to_string();
+        self.module
+            .with_opcode("OpMemberName")
+            .find(|instruction| {
+                instruction.operands.first() == Some(&target)
+                    && instruction.operands.get(1) == Some(&member)
+            })
+            .and_then(|instruction| instruction.string_operand(2))
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+    }
+
+    /// The value of a constant, such as an array length.
+    fn constant(&self, id: &str) -> String {
+        match self.definitions.get(id.trim_start_matches('%')) {
+            Some(instruction) if instruction.opcode.contains("Constant") => {
+                instruction.operands[1..].join(" ")
+            }
+            _ => self.name(id),
+        }
+    }
+
+    /// A readable description of type `id`, with the array strides of
+    /// its layout. Structs are described by name; `layout` gives their
+    /// members.
+    fn type_name(&self, id: &str, depth: usize) -> String {
+        let Some(instruction) = self.definitions.get(id.trim_start_matches('%')) else {
+            return self.name(id);
+        };
+        let operand = |index: usize| {
+            instruction
+                .operands
+                .get(index)
+                .map(String::as_str)
+                .unwrap_or("?")
+        };
+        if depth > 8 {
+            return self.name(id);
+        }
+        let inner = |index: usize| self.type_name(operand(index), depth + 1);
+        let stride = self
+            .decorations(id)
+            .into_iter()
+            .find(|decoration| decoration.starts_with("ArrayStride"))
+            .map(|decoration| format!(" ({decoration})"))
+            .unwrap_or_default();
+
+        match instruction.opcode.as_str() {
+            "OpTypeVoid" => "void".to_string(),
+            "OpTypeBool" => "bool".to_string(),
+            "OpTypeFloat" => match operand(0) {
+                "16" => "half",
+                "64" => "double",
+                _ => "float",
+            }
+            .to_string(),
+            "OpTypeInt" => format!(
+                "{}int{}",
+                if operand(1) == "0" { "u" } else { "" },
+                if operand(0) == "32" { "" } else { operand(0) }
+            ),
+            "OpTypeVector" => format!("{}{}", inner(0), operand(1)),
+            "OpTypeMatrix" => format!("{}x{}", inner(0), operand(1)),
+            "OpTypeArray" => format!("{}[{}]{stride}", inner(0), self.constant(operand(1))),
+            "OpTypeRuntimeArray" => format!("{}[]{stride}", inner(0)),
+            "OpTypePointer" => format!("{} {}", operand(0), inner(1)),
+            "OpTypeStruct" => format!("struct {}", self.name(id)),
+            _ => self.name(id),
+        }
+    }
+
+    /// The element type of array type `id`, through nested arrays, or
+    /// `id` itself for other types.
+    fn element(&self, mut id: &'a str) -> &'a str {
+        while let Some(array) = self
+            .definitions
+            .get(id.trim_start_matches('%'))
+            .filter(|array| array.opcode.ends_with("Array") && !array.operands.is_empty())
+        {
+            id = &array.operands[0];
+        }
+        id
+    }
+
+    /// The members of struct `id` with their offsets and layout
+    /// decorations, nested structs included.
+    fn layout(&self, id: &str, depth: usize) -> Vec<String> {
+        let Some(instruction) = self
+            .definitions
+            .get(id.trim_start_matches('%'))
+            .filter(|instruction| instruction.opcode == "OpTypeStruct")
+        else {
+            return Vec::new();
+        };
+        if depth > 8 {
+            return Vec::new();
+        }
+
+        let mut members = Vec::new();
+        for (member, member_type) in instruction.operands.iter().enumerate() {
+            let name = self
+                .member_name(id, member)
+                .unwrap_or_else(|| format!("member {member}"));
+            let decorations = self.member_decorations(id, member);
+            members.push(format!(
+                "{}.{name}: {}{}",
+                self.name(id),
+                self.type_name(member_type, 0),
+                if decorations.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", decorations.join(", "))
+                }
+            ));
+            // Arrays of structs lay out their elements too
+            members.extend(self.layout(self.element(member_type), depth + 1));
+        }
+        members
+    }
+
+    /// Each interface or resource variable by name, described by its
+    /// storage class, type, decorations and the layout of its block.
+    fn resources(&self) -> BTreeMap<String, Vec<String>> {
+        self.module
+            .with_opcode("OpVariable")
+            .filter(|instruction| {
+                instruction
+                    .operands
+                    .get(1)
+                    .is_some_and(|storage| storage != "Function")
+            })
+            .filter_map(|instruction| {
+                let id = instruction.result_id.as_deref()?;
+                let pointer = self
+                    .definitions
+                    .get(instruction.operands[0].trim_start_matches('%'))?;
+                let pointee = pointer.operands.get(1)?;
+                let block = self.element(pointee);
+
+                let mut decorations = self.decorations(id);
+                decorations.extend(self.decorations(block));
+                let mut description = vec![format!(
+                    "{} {}{}",
+                    instruction.operands[1],
+                    self.type_name(pointee, 0),
+                    if decorations.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" ({})", decorations.join(", "))
+                    }
+                )];
+                description.extend(self.layout(block, 0));
+                Some((self.name(id), description))
+            })
+            .collect()
+    }
+
+    /// Constants by type, with spec constants by their SpecId instead.
+    fn constants(&self) -> (BTreeMap<String, BTreeSet<String>>, BTreeMap<String, String>) {
+        let mut constants = BTreeMap::<String, BTreeSet<String>>::new();
+        let mut spec_constants = BTreeMap::new();
+        for instruction in &self.module.instructions {
+            let Some(id) = instruction.result_id.as_deref() else {
+                continue;
+            };
+            let value = match instruction.opcode.as_str() {
+                "OpConstant" | "OpSpecConstant" => instruction.operands[1..].join(" "),
+                "OpConstantTrue" | "OpSpecConstantTrue" => "true".to_string(),
+                "OpConstantFalse" | "OpSpecConstantFalse" => "false".to_string(),
+                "OpConstantNull" => "null".to_string(),
+                "OpConstantComposite" | "OpSpecConstantComposite" => format!(
+                    "({})",
+                    instruction.operands[1..]
+                        .iter()
+                        .map(|operand| self.constant(operand))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                _ => continue,
+            };
+            let type_name =
+                self.type_name(instruction.operands.first().map_or("", String::as_str), 0);
+            let spec_id = self
+                .decorations(id)
+                .into_iter()
+                .find_map(|decoration| decoration.strip_prefix("SpecId ").map(str::to_string));
+            match spec_id {
+                Some(spec_id) => {
+                    spec_constants.insert(
+                        format!("spec constant {spec_id} ({})", self.name(id)),
+                        format!("{type_name} {value}"),
+                    );
+                }
+                None => {
+                    constants.entry(type_name).or_default().insert(value);
+                }
+            }
+        }
+        (constants, spec_constants)
+    }
+
+    /// The normalized instructions of each function by name, without
+    /// debug line info.
+    fn functions(&self) -> BTreeMap<String, Vec<String>> {
+        let mut functions = BTreeMap::new();
+        let mut current: Option<(String, Vec<String>)> = None;
+        for instruction in &self.module.instructions {
+            if instruction.opcode == "OpFunction" {
+                let name = instruction
+                    .result_id
+                    .as_deref()
+                    .map_or_else(|| "?".to_string(), |id| self.name(id));
+                current = Some((name, Vec::new()));
+            }
+            let Some((_, body)) = current.as_mut() else {
+                continue;
+            };
+            if !matches!(instruction.opcode.as_str(), "OpLine" | "OpNoLine") {
+                body.push(normalize(instruction));
+            }
+            if instruction.opcode == "OpFunctionEnd" {
+                let (name, body) = current.take().unwrap_or_default();
+                functions.insert(name, body);
+            }
+        }
+        functions
+    }
+}
+
+/// Lines only in `after` as `+`, only in `before` as `-`.
+fn set_changes<'a>(
+    before: impl IntoIterator<Item = &'a str>,
+    after: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let before = before.into_iter().collect::<BTreeSet<_>>();
+    let after = after.into_iter().collect::<BTreeSet<_>>();
+    let removed = before.difference(&after).map(|line| format!("- {line}"));
+    let added = after.difference(&before).map(|line| format!("+ {line}"));
+    removed.chain(added).collect()
+}
+
+/// The lines to remove from `before` and add to make it `after`, from a
+/// longest common subsequence, as (index, line) in their own sequence.
+fn align(before: &[String], after: &[String]) -> Option<Vec<(char, usize)>> {
+    let prefix = before.iter().zip(after).take_while(|(a, b)| a == b).count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &before[prefix..before.len() - suffix];
+    let new = &after[prefix..after.len() - suffix];
+    if old.len().saturating_mul(new.len()) > MAX_ALIGNMENT {
+        return None;
+    }
+
+    // lengths[i][j]: common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            edits.push(('-', prefix + i));
+            i += 1;
+        } else {
+            edits.push(('+', prefix + j));
+            j += 1;
+        }
+    }
+    Some(edits)
+}
+
+/// How the opcode counts differ, such as "OpFMul +2, OpLoad -1".
+fn opcode_counts(before: &[String], after: &[String]) -> String {
+    let opcode = |line: &String| {
+        line.split_whitespace()
+            .find(|token| token.starts_with("Op"))
+            .unwrap_or("")
+            .to_string()
+    };
+    let mut counts = BTreeMap::<String, i64>::new();
+    for line in before {
+        *counts.entry(opcode(line)).or_default() -= 1;
+    }
+    for line in after {
+        *counts.entry(opcode(line)).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count != 0)
+        .map(|(opcode, count)| format!("{opcode} {count:+}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Compares `before` with `after`, leaving out unchanged aspects. Each
+/// function lists at most `max_instructions` changed instructions.
+pub fn compare(before: &Module, after: &Module, max_instructions: usize) -> Vec<Section> {
+    let (old, new) = (Index::new(before), Index::new(after));
+    let mut sections = Vec::new();
+
+    let header = |index: &Index| {
+        index
+            .module
+            .instructions
+            .iter()
+            .filter(|instruction| {
+                matches!(
+                    instruction.opcode.as_str(),
+                    "OpCapability" | "OpExtension" | "OpExtInstImport" | "OpMemoryModel"
+                )
+            })
+            .map(|instruction| format!("{} {}", instruction.opcode, instruction.operands.join(" ")))
+            .collect::<Vec<_>>()
+    };
+    sections.push(Section {
+        title: "Capabilities and extensions",
+        changes: set_changes(
+            header(&old).iter().map(String::as_str),
+            header(&new).iter().map(String::as_str),
+        ),
+    });
+
+    let entry_points = |index: &Index| {
+        let mut lines = index
+            .module
+            .entry_points()
+            .into_iter()
+            .map(|entry_point| format!("{} {}", entry_point.execution_model, entry_point.name))
+            .collect::<Vec<_>>();
+        lines.extend(
+            index
+                .module
+                .instructions
+                .iter()
+                .filter(|instruction| instruction.opcode.starts_with("OpExecutionMode"))
+                .filter_map(|instruction| {
+                    let (target, mode) = instruction.operands.split_first()?;
+                    Some(format!("{} {}", index.name(target), mode.join(" ")))
+                }),
+        );
+        lines
+    };
+    sections.push(Section {
+        title: "Entry points",
+        changes: set_changes(
+            entry_points(&old).iter().map(String::as_str),
+            entry_points(&new).iter().map(String::as_str),
+        ),
+    });
+
+    let (old_resources, new_resources) = (old.resources(), new.resources());
+    let names = old_resources
+        .keys()
+        .chain(new_resources.keys())
+        .collect::<BTreeSet<_>>();
+    let mut changes = Vec::new();
+    for name in names {
+        match (old_resources.get(name), new_resources.get(name)) {
+            (Some(description), None) => changes.push(format!("- {name}: {}", description[0])),
+            (None, Some(description)) => changes.push(format!("+ {name}: {}", description[0])),
+            (Some(before), Some(after)) if before != after => {
+                changes.push(format!("~ {name}"));
+                let removed = before.iter().filter(|line| !after.contains(line));
+                let added = after.iter().filter(|line| !before.contains(line));
+                changes.extend(removed.map(|line| format!("  - {line}")));
+                changes.extend(added.map(|line| format!("  + {line}")));
+            }
+            _ => {}
+        }
+    }
+    sections.push(Section {
+        title: "Resources",
+        changes,
+    });
+
+    let ((old_constants, old_spec), (new_constants, new_spec)) = (old.constants(), new.constants());
+    let mut changes = Vec::new();
+    for key in old_spec
+        .keys()
+        .chain(new_spec.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        match (old_spec.get(key), new_spec.get(key)) {
+            (Some(value), None) => changes.push(format!("- {key}: {value}")),
+            (None, Some(value)) => changes.push(format!("+ {key}: {value}")),
+            (Some(before), Some(after)) if before != after => {
+                changes.push(format!("~ {key}: {before} -> {after}"));
+            }
+            _ => {}
+        }
+    }
+    let empty = BTreeSet::new();
+    for type_name in old_constants
+        .keys()
+        .chain(new_constants.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let before = old_constants.get(type_name).unwrap_or(&empty);
+        let after = new_constants.get(type_name).unwrap_or(&empty);
+        changes.extend(
+            set_changes(
+                before.iter().map(String::as_str),
+                after.iter().map(String::as_str),
+            )
+            .into_iter()
+            .map(|line| format!("{} {type_name} {}", &line[..1], &line[2..])),
+        );
+    }
+    sections.push(Section {
+        title: "Constants",
+        changes,
+    });
+
+    let (old_functions, new_functions) = (old.functions(), new.functions());
+    let mut changes = Vec::new();
+    for name in old_functions
+        .keys()
+        .chain(new_functions.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let (before, after) = match (old_functions.get(name), new_functions.get(name)) {
+            (Some(body), None) => {
+                changes.push(format!("- {name} ({} instructions)", body.len()));
+                continue;
+            }
+            (None, Some(body)) => {
+                changes.push(format!("+ {name} ({} instructions)", body.len()));
+                continue;
+            }
+            (Some(before), Some(after)) if before != after => (before, after),
+            _ => continue,
+        };
+
+        let counts = opcode_counts(before, after);
+        changes.push(format!(
+            "~ {name} ({} -> {} instructions{})",
+            before.len(),
+            after.len(),
+            if counts.is_empty() {
+                String::new()
+            } else {
+                format!("; {counts}")
+            }
+        ));
+        let Some(edits) = align(before, after) else {
+            changes.push("  (too long to compare instruction by instruction)".to_string());
+            continue;
+        };
+        for (sign, index) in edits.iter().take(max_instructions) {
+            let line = if *sign == '-' {
+                &before[*index]
+            } else {
+                &after[*index]
+            };
+            changes.push(format!("  {sign} {line}"));
+        }
+        if edits.len() > max_instructions {
+            changes.push(format!(
+                "  ... {} more changed instructions",
+                edits.len() - max_instructions
+            ));
+        }
+    }
+    sections.push(Section {
+        title: "Functions",
+        changes,
+    });
+
+    sections.retain(|section| !section.changes.is_empty());
+    sections
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BEFORE: &str = r#"
+               OpCapability Shader
+          %1 = OpExtInstImport "GLSL.std.450"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint GLCompute %main "main"
+               OpExecutionMode %main LocalSize 8 8 1
+               OpName %main "main"
+               OpName %Data "Data"
+               OpMemberName %Data 0 "values"
+               OpName %data "data"
+               OpDecorate %_runtimearr_float ArrayStride 4
+               OpMemberDecorate %Data 0 Offset 0
+               OpDecorate %Data BufferBlock
+               OpDecorate %data DescriptorSet 0
+               OpDecorate %data Binding 0
+       %void = OpTypeVoid
+          %3 = OpTypeFunction %void
+      %float = OpTypeFloat 32
+        %int = OpTypeInt 32 1
+%_runtimearr_float = OpTypeRuntimeArray %float
+       %Data = OpTypeStruct %_runtimearr_float
+%_ptr_Uniform_Data = OpTypePointer Uniform %Data
+       %data = OpVariable %_ptr_Uniform_Data Uniform
+      %int_0 = OpConstant %int 0
+  %float_0_5 = OpConstant %float 0.5
+%_ptr_Uniform_float = OpTypePointer Uniform %float
+       %main = OpFunction %void None %3
+          %5 = OpLabel
+         %12 = OpAccessChain %_ptr_Uniform_float %data %int_0 %int_0
+         %13 = OpLoad %float %12
+         %14 = OpFMul %float %13 %float_0_5
+               OpStore %12 %14
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    #[test]
+    fn test_compare() {
+        let before = Module::parse(BEFORE);
+        assert!(compare(&before, &before, 40).is_empty());
+
+        // Renumbering ids changes nothing
+        let renumbered = Module::parse(&BEFORE.replace("%12", "%20").replace("%13 ", "%21 "));
+        assert!(compare(&before, &renumbered, 40).is_empty());
+
+        let after = Module::parse(
+            &BEFORE
+                .replace("LocalSize 8 8 1", "LocalSize 16 16 1")
+                .replace("Binding 0", "Binding 2")
+                .replace("ArrayStride 4", "ArrayStride 8")
+                .replace("0.5", "0.25")
+                .replace(
+                    "OpStore %12 %14",
+                    "%15 = OpFAdd %float %14 %13\n               OpStore %12 %15",
+                ),
+        );
+        let sections = compare(&before, &after, 40);
+        let titles = sections
+            .iter()
+            .map(|section| section.title)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            ["Entry points", "Resources", "Constants", "Functions"]
+        );
+        assert_eq!(
+            sections[0].changes,
+            ["- main LocalSize 8 8 1", "+ main LocalSize 16 16 1"]
+        );
+        assert_eq!(
+            sections[1].changes,
+            [
+                "~ data",
+                "  - Uniform struct Data (DescriptorSet 0, Binding 0, BufferBlock)",
+                "  - Data.values: float[] (ArrayStride 4) (Offset 0)",
+                "  + Uniform struct Data (DescriptorSet 0, Binding 2, BufferBlock)",
+                "  + Data.values: float[] (ArrayStride 8) (Offset 0)",
+            ]
+        );
+        assert_eq!(sections[2].changes, ["- float 0.5", "+ float 0.25"]);
+        assert_eq!(
+            sections[3].changes,
+            [
+                "~ main (8 -> 9 instructions; OpFAdd +1)",
+                "  + %_ = OpFAdd %float %_ %_",
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Stable codes for the ways a tool call can fail, so that clients can
+//! branch on the class of a failure instead of matching its wording.
+//! Errors carry the code as `code` in their data; results of runs that
+//! failed start with an `error_code: ...` line.
+
+use rmcp::Error as McpError;
+use rmcp::model::ErrorCode as RpcErrorCode;
+use serde_json::{Value, json};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// A shader failed to compile.
+    CompileError,
+    /// The device lacks a feature or extension the test requires, so
+    /// vkrunner skipped it.
+    UnsupportedFeature,
+    /// A probe or an expected image didn't match.
+    ProbeFailed,
+    /// A queue submission or fence wait failed, as when the GPU resets.
+    DeviceLost,
+    /// No Vulkan device was found.
+    NoDevice,
+    /// The work would outlast the GPU watchdog.
+    Timeout,
+    /// vkrunner failed for another reason, such as a script error.
+    RunFailed,
+    /// A path is outside /tmp, missing or unreadable.
+    BadRequestPath,
+    /// An ID or name doesn't refer to a known texture, source, template,
+    /// shader, entry point or compile request.
+    UnknownReference,
+    /// The request is over one of the server's budgets.
+    BudgetExceeded,
+    /// Server or client policy forbids the call.
+    NotAllowed,
+    /// The server is shutting down.
+    ShuttingDown,
+    /// Any other problem with the request.
+    BadRequest,
+    /// The server failed, e.g. writing a file.
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::CompileError => "COMPILE_ERROR",
+            ErrorCode::UnsupportedFeature => "UNSUPPORTED_FEATURE",
+            ErrorCode::ProbeFailed => "PROBE_FAILED",
+            ErrorCode::DeviceLost => "DEVICE_LOST",
+            ErrorCode::NoDevice => "NO_DEVICE",
+            ErrorCode::Timeout => "TIMEOUT",
+            ErrorCode::RunFailed => "RUN_FAILED",
+            ErrorCode::BadRequestPath => "BAD_REQUEST_PATH",
+            ErrorCode::UnknownReference => "UNKNOWN_REFERENCE",
+            ErrorCode::BudgetExceeded => "BUDGET_EXCEEDED",
+            ErrorCode::NotAllowed => "NOT_ALLOWED",
+            ErrorCode::ShuttingDown => "SHUTTING_DOWN",
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+        }
+    }
+
+    /// `data` with this code added: as a `code` field of an object, or
+    /// next to any other value as `details`.
+    fn data(self, data: Option<Value>) -> Value {
+        match data {
+            Some(Value::Object(mut object)) => {
+                object.insert("code".to_string(), json!(self.as_str()));
+                Value::Object(object)
+            }
+            Some(details) => json!({"code": self.as_str(), "details": details}),
+            None => json!({"code": self.as_str()}),
+        }
+    }
+
+    pub fn invalid_params(
+        self,
+        message: impl Into<Cow<'static, str>>,
+        data: Option<Value>,
+    ) -> McpError {
+        McpError::invalid_params(message, Some(self.data(data)))
+    }
+
+    pub fn invalid_request(
+        self,
+        message: impl Into<Cow<'static, str>>,
+        data: Option<Value>,
+    ) -> McpError {
+        McpError::invalid_request(message, Some(self.data(data)))
+    }
+
+    pub fn internal_error(
+        self,
+        message: impl Into<Cow<'static, str>>,
+        data: Option<Value>,
+    ) -> McpError {
+        McpError::internal_error(message, Some(self.data(data)))
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Gives an error that has no code yet the one its JSON-RPC kind implies:
+/// BAD_REQUEST for invalid parameters or requests, such as arguments that
+/// don't deserialize, and INTERNAL_ERROR otherwise.
+pub fn with_code(mut error: McpError) -> McpError {
+    let has_code = error
+        .data
+        .as_ref()
+        .is_some_and(|data| data.get("code").is_some());
+    if !has_code {
+        let code = match error.code {
+            RpcErrorCode::INVALID_PARAMS | RpcErrorCode::INVALID_REQUEST => ErrorCode::BadRequest,
+            _ => ErrorCode::InternalError,
+        };
+        error.data = Some(code.data(error.data.take()));
+    }
+    error
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_data() {
+        let error = ErrorCode::BadRequestPath.invalid_params("bad", Some(json!({"path": "/x"})));
+        assert_eq!(error.code, RpcErrorCode::INVALID_PARAMS);
+        assert_eq!(
+            error.data,
+            Some(json!({"path": "/x", "code": "BAD_REQUEST_PATH"}))
+        );
+
+        let error = ErrorCode::Timeout.internal_error("slow", Some(json!([1, 2])));
+        assert_eq!(
+            error.data,
+            Some(json!({"code": "TIMEOUT", "details": [1, 2]}))
+        );
+        assert_eq!(
+            ErrorCode::NotAllowed.invalid_request("no", None).data,
+            Some(json!({"code": "NOT_ALLOWED"}))
+        );
+        assert_eq!(ErrorCode::CompileError.to_string(), "COMPILE_ERROR");
+    }
+
+    #[test]
+    fn test_with_code() {
+        let error = with_code(McpError::invalid_params("bad", None));
+        assert_eq!(error.data, Some(json!({"code": "BAD_REQUEST"})));
+
+        let error = with_code(McpError::internal_error(
+            "failed",
+            Some(json!({"error": "disk full"})),
+        ));
+        assert_eq!(
+            error.data,
+            Some(json!({"error": "disk full", "code": "INTERNAL_ERROR"}))
+        );
+
+        // A code already given is kept
+        let error = with_code(ErrorCode::NoDevice.internal_error("none", None));
+        assert_eq!(error.data, Some(json!({"code": "NO_DEVICE"})));
+    }
+}
@@ -0,0 +1,806 @@
+//! Partial evaluation of SPIR-V assembly: finds the shader outputs whose
+//! stored values follow from constants, spec constants and push constant
+//! values alone, so a shader that is constant by construction shows up
+//! before it is run.
+
+use crate::spirv::{Instruction, Module};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Composite(Vec<Value>),
+}
+
+/// Values the evaluation may assume for the shader's inputs.
+#[derive(Debug, Clone, Default)]
+pub struct Inputs {
+    /// Spec constant values by `SpecId`, replacing their defaults.
+    pub spec_constants: HashMap<u32, f64>,
+    /// Components of top-level push constant block members by index.
+    pub push_constants: HashMap<u32, Vec<f64>>,
+}
+
+/// One store to a shader output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputStore {
+    /// Name of the output variable (and member, for blocks).
+    pub output: String,
+    pub location: Option<u32>,
+    /// The stored value, if the inputs determine it.
+    pub value: Option<String>,
+}
+
+/// Scalar kinds of the types a value can be converted to or printed as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Bool,
+    Int { signed: bool },
+    Float,
+}
+
+struct Evaluator<'a> {
+    definitions: HashMap<&'a str, &'a Instruction>,
+    values: HashMap<&'a str, Value>,
+    /// Push constant access chains: id to the block member they point at.
+    push_members: HashMap<&'a str, u32>,
+    inputs: &'a Inputs,
+    spec_ids: HashMap<&'a str, u32>,
+}
+
+fn id(operand: &str) -> &str {
+    operand.strip_prefix('%').unwrap_or(operand)
+}
+
+fn float(value: f64) -> Value {
+    // Shaders compute in single precision
+    Value::Float(f64::from(value as f32))
+}
+
+fn truthy(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Applies `f` to matching components of same-shaped values.
+fn componentwise(args: &[&Value], f: &dyn Fn(&[&Value]) -> Option<Value>) -> Option<Value> {
+    match args.first()? {
+        Value::Composite(first) => {
+            let columns = (0..first.len())
+                .map(|i| {
+                    let column = args
+                        .iter()
+                        .map(|arg| match arg {
+                            Value::Composite(components) => components.get(i),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>()?;
+                    componentwise(&column, f)
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(Value::Composite(columns))
+        }
+        _ => f(args),
+    }
+}
+
+fn floats(args: &[&Value]) -> Option<Vec<f64>> {
+    args.iter()
+        .map(|arg| match arg {
+            Value::Float(value) => Some(*value),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ints(args: &[&Value]) -> Option<Vec<i64>> {
+    args.iter()
+        .map(|arg| match arg {
+            Value::Int(value) => Some(*value),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `GLSL.std.450` instructions on floats, by name.
+fn glsl_std(name: &str, x: &[f64]) -> Option<f64> {
+    Some(match (name, x) {
+        ("FAbs", [x]) => x.abs(),
+        ("Floor", [x]) => x.floor(),
+        ("Ceil", [x]) => x.ceil(),
+        ("Fract", [x]) => x - x.floor(),
+        ("Sqrt", [x]) => x.sqrt(),
+        ("InverseSqrt", [x]) => 1.0 / x.sqrt(),
+        ("Sin", [x]) => x.sin(),
+        ("Cos", [x]) => x.cos(),
+        ("Tan", [x]) => x.tan(),
+        ("Exp", [x]) => x.exp(),
+        ("Exp2", [x]) => x.exp2(),
+        ("Log", [x]) => x.ln(),
+        ("Log2", [x]) => x.log2(),
+        ("Pow", [x, y]) => x.powf(*y),
+        ("FMin", [x, y]) => x.min(*y),
+        ("FMax", [x, y]) => x.max(*y),
+        ("Step", [edge, x]) => f64::from(u8::from(x >= edge)),
+        ("FClamp", [x, min, max]) => x.max(*min).min(*max),
+        ("FMix", [x, y, a]) => x + (y - x) * a,
+        _ => return None,
+    })
+}
+
+impl<'a> Evaluator<'a> {
+    fn scalar(&self, type_id: &str) -> Option<Scalar> {
+        let definition = self.definitions.get(id(type_id))?;
+        match definition.opcode.as_str() {
+            "OpTypeBool" => Some(Scalar::Bool),
+            "OpTypeFloat" => Some(Scalar::Float),
+            "OpTypeInt" => Some(Scalar::Int {
+                signed: definition.operands.get(1).map(String::as_str) == Some("1"),
+            }),
+            "OpTypeVector" | "OpTypeMatrix" => self.scalar(definition.operands.first()?),
+            _ => None,
+        }
+    }
+
+    /// Component type ids of a composite type.
+    fn components(&self, type_id: &str) -> Option<Vec<&'a str>> {
+        let definition = self.definitions.get(id(type_id))?;
+        match definition.opcode.as_str() {
+            "OpTypeVector" | "OpTypeMatrix" => {
+                let count = definition.operands.get(1)?.parse().ok()?;
+                Some(vec![definition.operands.first()?.as_str(); count])
+            }
+            "OpTypeArray" => {
+                let length = match self.values.get(id(definition.operands.get(1)?))? {
+                    Value::Int(length) => usize::try_from(*length).ok()?,
+                    _ => return None,
+                };
+                Some(vec![definition.operands.first()?.as_str(); length])
+            }
+            "OpTypeStruct" => Some(definition.operands.iter().map(String::as_str).collect()),
+            _ => None,
+        }
+    }
+
+    /// Wraps integers to 32 bits and rounds floats to single precision.
+    fn normalize(&self, type_id: &str, value: Value) -> Option<Value> {
+        Some(match value {
+            Value::Int(value) => match self.scalar(type_id)? {
+                Scalar::Int { signed: true } => Value::Int(i64::from(value as i32)),
+                Scalar::Int { signed: false } => Value::Int(i64::from(value as u32)),
+                _ => return None,
+            },
+            Value::Float(value) => float(value),
+            Value::Composite(components) => Value::Composite(
+                components
+                    .into_iter()
+                    .zip(self.components(type_id)?)
+                    .map(|(component, type_id)| self.normalize(type_id, component))
+                    .collect::<Option<_>>()?,
+            ),
+            value => value,
+        })
+    }
+
+    fn zero(&self, type_id: &str) -> Option<Value> {
+        match self.components(type_id) {
+            Some(components) => Some(Value::Composite(
+                components
+                    .into_iter()
+                    .map(|component| self.zero(component))
+                    .collect::<Option<_>>()?,
+            )),
+            None => Some(match self.scalar(type_id)? {
+                Scalar::Bool => Value::Bool(false),
+                Scalar::Int { .. } => Value::Int(0),
+                Scalar::Float => Value::Float(0.0),
+            }),
+        }
+    }
+
+    fn scalar_from(&self, type_id: &str, number: f64) -> Option<Value> {
+        Some(match self.scalar(type_id)? {
+            Scalar::Bool => Value::Bool(number != 0.0),
+            Scalar::Int { .. } => Value::Int(number as i64),
+            Scalar::Float => float(number),
+        })
+    }
+
+    /// Builds a value of `type_id` from a flat list of numbers.
+    fn value_from_numbers(
+        &self,
+        type_id: &str,
+        numbers: &mut impl Iterator<Item = f64>,
+    ) -> Option<Value> {
+        match self.components(type_id) {
+            Some(components) => Some(Value::Composite(
+                components
+                    .into_iter()
+                    .map(|component| self.value_from_numbers(component, numbers))
+                    .collect::<Option<_>>()?,
+            )),
+            None => self.scalar_from(type_id, numbers.next()?),
+        }
+    }
+
+    fn literal(&self, type_id: &str, literal: &str) -> Option<Value> {
+        Some(match self.scalar(type_id)? {
+            Scalar::Float => float(literal.parse().ok()?),
+            Scalar::Int { .. } => Value::Int(literal.parse().ok()?),
+            Scalar::Bool => return None,
+        })
+    }
+
+    fn operand(&self, instruction: &Instruction, index: usize) -> Option<&Value> {
+        self.values.get(id(instruction.operands.get(index)?))
+    }
+
+    fn evaluate(&mut self, instruction: &'a Instruction) -> Option<Value> {
+        let result_type = instruction.operands.first().map(String::as_str);
+        let operands = &instruction.operands;
+
+        let value = match instruction.opcode.as_str() {
+            "OpConstantTrue" => Value::Bool(true),
+            "OpConstantFalse" => Value::Bool(false),
+            "OpSpecConstantTrue" | "OpSpecConstantFalse" => {
+                let default = instruction.opcode == "OpSpecConstantTrue";
+                let spec_id = self.spec_ids.get(instruction.result_id.as_deref()?);
+                match spec_id.and_then(|spec_id| self.inputs.spec_constants.get(spec_id)) {
+                    Some(value) => Value::Bool(*value != 0.0),
+                    None => Value::Bool(default),
+                }
+            }
+            "OpConstant" => self.literal(result_type?, operands.get(1)?)?,
+            "OpSpecConstant" => {
+                let spec_id = self.spec_ids.get(instruction.result_id.as_deref()?);
+                match spec_id.and_then(|spec_id| self.inputs.spec_constants.get(spec_id)) {
+                    Some(value) => self.scalar_from(result_type?, *value)?,
+                    None => self.literal(result_type?, operands.get(1)?)?,
+                }
+            }
+            "OpConstantNull" => self.zero(result_type?)?,
+            "OpConstantComposite" | "OpSpecConstantComposite" | "OpCompositeConstruct" => {
+                let parts = operands
+                    .get(1..)?
+                    .iter()
+                    .map(|operand| self.values.get(id(operand)).cloned())
+                    .collect::<Option<Vec<_>>>()?;
+                let vector = self
+                    .definitions
+                    .get(id(result_type?))
+                    .is_some_and(|definition| definition.opcode == "OpTypeVector");
+                // vec4(v.xyz, 1.0) passes a vector for several components
+                let parts = if vector {
+                    parts
+                        .into_iter()
+                        .flat_map(|part| match part {
+                            Value::Composite(components) => components,
+                            scalar => vec![scalar],
+                        })
+                        .collect()
+                } else {
+                    parts
+                };
+                Value::Composite(parts)
+            }
+            "OpCompositeExtract" => {
+                let mut value = self.operand(instruction, 1)?;
+                for index in operands.get(2..)? {
+                    let index = index.parse::<usize>().ok()?;
+                    value = match value {
+                        Value::Composite(components) => components.get(index)?,
+                        _ => return None,
+                    };
+                }
+                value.clone()
+            }
+            "OpCompositeInsert" => {
+                let object = self.operand(instruction, 1)?.clone();
+                let mut composite = self.operand(instruction, 2)?.clone();
+                let mut target = &mut composite;
+                for index in operands.get(3..)? {
+                    let index = index.parse::<usize>().ok()?;
+                    target = match target {
+                        Value::Composite(components) => components.get_mut(index)?,
+                        _ => return None,
+                    };
+                }
+                *target = object;
+                composite
+            }
+            "OpVectorShuffle" => {
+                let mut components = Vec::new();
+                for vector in [self.operand(instruction, 1)?, self.operand(instruction, 2)?] {
+                    match vector {
+                        Value::Composite(vector) => components.extend(vector.iter().cloned()),
+                        _ => return None,
+                    }
+                }
+                Value::Composite(
+                    operands
+                        .get(3..)?
+                        .iter()
+                        .map(|index| components.get(index.parse::<usize>().ok()?).cloned())
+                        .collect::<Option<_>>()?,
+                )
+            }
+            "OpCopyObject" => self.operand(instruction, 1)?.clone(),
+            "OpLoad" => {
+                let member = self.push_members.get(id(operands.get(1)?))?;
+                let numbers = self.inputs.push_constants.get(member)?;
+                self.value_from_numbers(result_type?, &mut numbers.iter().copied())?
+            }
+            "OpAccessChain" | "OpInBoundsAccessChain" => {
+                let base = self.definitions.get(id(operands.get(1)?))?;
+                let push_constant = base.opcode == "OpVariable"
+                    && base.operands.get(1).map(String::as_str) == Some("PushConstant");
+                // Only whole top-level members can be given as input
+                if !push_constant || operands.len() != 3 {
+                    return None;
+                }
+                if let Value::Int(member) = self.operand(instruction, 2)? {
+                    let member = u32::try_from(*member).ok()?;
+                    self.push_members
+                        .insert(instruction.result_id.as_deref()?, member);
+                }
+                return None;
+            }
+            "OpSelect" => {
+                let condition = self.operand(instruction, 1)?;
+                let (a, b) = (self.operand(instruction, 2)?, self.operand(instruction, 3)?);
+                match condition {
+                    Value::Bool(condition) => {
+                        if *condition {
+                            a.clone()
+                        } else {
+                            b.clone()
+                        }
+                    }
+                    _ => componentwise(&[condition, a, b], &|args| {
+                        Some(if truthy(args[0])? {
+                            args[1].clone()
+                        } else {
+                            args[2].clone()
+                        })
+                    })?,
+                }
+            }
+            "OpVectorTimesScalar" => {
+                let scalar = match self.operand(instruction, 2)? {
+                    Value::Float(scalar) => *scalar,
+                    _ => return None,
+                };
+                componentwise(&[self.operand(instruction, 1)?], &|args| {
+                    Some(float(floats(args)?[0] * scalar))
+                })?
+            }
+            "OpDot" => {
+                let (Value::Composite(a), Value::Composite(b)) =
+                    (self.operand(instruction, 1)?, self.operand(instruction, 2)?)
+                else {
+                    return None;
+                };
+                let mut sum = 0.0;
+                for (a, b) in a.iter().zip(b) {
+                    sum += floats(&[a, b])?.iter().product::<f64>();
+                }
+                float(sum)
+            }
+            "OpExtInst" => {
+                let set = self.definitions.get(id(operands.get(1)?))?;
+                if set.operands.first().map(String::as_str) != Some("\"GLSL.std.450\"") {
+                    return None;
+                }
+                let name = operands.get(2)?.clone();
+                let args = operands
+                    .get(3..)?
+                    .iter()
+                    .map(|operand| self.values.get(id(operand)))
+                    .collect::<Option<Vec<_>>>()?;
+                componentwise(&args, &|args| Some(float(glsl_std(&name, &floats(args)?)?)))?
+            }
+            opcode => {
+                let f = arithmetic(opcode)?;
+                let args = operands
+                    .get(1..)?
+                    .iter()
+                    .map(|operand| self.values.get(id(operand)))
+                    .collect::<Option<Vec<_>>>()?;
+                componentwise(&args, &*f)?
+            }
+        };
+
+        self.normalize(result_type?, value)
+    }
+}
+
+type Operation = Box<dyn Fn(&[&Value]) -> Option<Value>>;
+
+/// Component operations of the arithmetic, comparison, logic and
+/// conversion instructions.
+fn arithmetic(opcode: &str) -> Option<Operation> {
+    fn float_op(f: fn(f64, f64) -> f64) -> Operation {
+        Box::new(move |args| {
+            let [a, b] = floats(args)?[..] else {
+                return None;
+            };
+            Some(float(f(a, b)))
+        })
+    }
+    fn int_op(f: fn(i64, i64) -> Option<i64>) -> Operation {
+        Box::new(move |args| {
+            let [a, b] = ints(args)?[..] else {
+                return None;
+            };
+            f(a, b).map(Value::Int)
+        })
+    }
+    fn float_compare(f: fn(f64, f64) -> bool) -> Operation {
+        Box::new(move |args| {
+            let [a, b] = floats(args)?[..] else {
+                return None;
+            };
+            Some(Value::Bool(f(a, b)))
+        })
+    }
+    fn int_compare(f: fn(i64, i64) -> bool) -> Operation {
+        Box::new(move |args| {
+            let [a, b] = ints(args)?[..] else {
+                return None;
+            };
+            Some(Value::Bool(f(a, b)))
+        })
+    }
+    fn logic(f: fn(bool, bool) -> bool) -> Operation {
+        Box::new(move |args| {
+            let [a, b] = args else {
+                return None;
+            };
+            Some(Value::Bool(f(truthy(a)?, truthy(b)?)))
+        })
+    }
+
+    Some(match opcode {
+        "OpFAdd" => float_op(|a, b| a + b),
+        "OpFSub" => float_op(|a, b| a - b),
+        "OpFMul" => float_op(|a, b| a * b),
+        "OpFDiv" => float_op(|a, b| a / b),
+        "OpFMod" => float_op(|a, b| a - b * (a / b).floor()),
+        "OpIAdd" => int_op(|a, b| Some(a.wrapping_add(b))),
+        "OpISub" => int_op(|a, b| Some(a.wrapping_sub(b))),
+        "OpIMul" => int_op(|a, b| Some(a.wrapping_mul(b))),
+        "OpSDiv" | "OpUDiv" => int_op(|a, b| a.checked_div(b)),
+        "OpFOrdEqual" => float_compare(|a, b| a == b),
+        "OpFOrdNotEqual" => float_compare(|a, b| a != b),
+        "OpFOrdLessThan" => float_compare(|a, b| a < b),
+        "OpFOrdGreaterThan" => float_compare(|a, b| a > b),
+        "OpFOrdLessThanEqual" => float_compare(|a, b| a <= b),
+        "OpFOrdGreaterThanEqual" => float_compare(|a, b| a >= b),
+        "OpIEqual" => int_compare(|a, b| a == b),
+        "OpINotEqual" => int_compare(|a, b| a != b),
+        "OpSLessThan" | "OpULessThan" => int_compare(|a, b| a < b),
+        "OpSGreaterThan" | "OpUGreaterThan" => int_compare(|a, b| a > b),
+        "OpSLessThanEqual" | "OpULessThanEqual" => int_compare(|a, b| a <= b),
+        "OpSGreaterThanEqual" | "OpUGreaterThanEqual" => int_compare(|a, b| a >= b),
+        "OpLogicalAnd" => logic(|a, b| a && b),
+        "OpLogicalOr" => logic(|a, b| a || b),
+        "OpLogicalEqual" => logic(|a, b| a == b),
+        "OpLogicalNotEqual" => logic(|a, b| a != b),
+        "OpLogicalNot" => Box::new(|args| Some(Value::Bool(!truthy(args.first()?)?))),
+        "OpFNegate" => Box::new(|args| Some(float(-*floats(args)?.first()?))),
+        "OpSNegate" => Box::new(|args| Some(Value::Int(ints(args)?.first()?.wrapping_neg()))),
+        "OpConvertSToF" | "OpConvertUToF" => {
+            Box::new(|args| Some(float(*ints(args)?.first()? as f64)))
+        }
+        "OpConvertFToS" | "OpConvertFToU" => {
+            Box::new(|args| Some(Value::Int(floats(args)?.first()?.trunc() as i64)))
+        }
+        _ => return None,
+    })
+}
+
+impl Value {
+    fn format(&self, evaluator: &Evaluator, type_id: &str) -> String {
+        match self {
+            Value::Bool(value) => value.to_string(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => (*value as f32).to_string(),
+            Value::Composite(components) => {
+                let types = evaluator.components(type_id).unwrap_or_default();
+                let components = components
+                    .iter()
+                    .zip(types)
+                    .map(|(component, type_id)| component.format(evaluator, type_id))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let vector = evaluator
+                    .definitions
+                    .get(id(type_id))
+                    .filter(|definition| definition.opcode == "OpTypeVector");
+                match (vector, evaluator.scalar(type_id)) {
+                    (Some(definition), Some(scalar)) => {
+                        let prefix = match scalar {
+                            Scalar::Bool => "b",
+                            Scalar::Int { signed: true } => "i",
+                            Scalar::Int { signed: false } => "u",
+                            Scalar::Float => "",
+                        };
+                        let size = definition.operands.get(1).map_or("", String::as_str);
+                        format!("{prefix}vec{size}({components})")
+                    }
+                    _ => format!("{{{components}}}"),
+                }
+            }
+        }
+    }
+}
+
+/// Evaluates every store to an `Output` variable of the module.
+pub fn evaluate_outputs(module: &Module, inputs: &Inputs) -> Vec<OutputStore> {
+    let mut names = HashMap::new();
+    let mut member_names = HashMap::new();
+    let mut locations = HashMap::new();
+    let mut spec_ids = HashMap::new();
+
+    for instruction in &module.instructions {
+        let operands = &instruction.operands;
+        match instruction.opcode.as_str() {
+            "OpName" => {
+                if let (Some(target), Some(name)) = (operands.first(), operands.get(1)) {
+                    names.insert(id(target), name.trim_matches('"'));
+                }
+            }
+            "OpMemberName" => {
+                if let (Some(target), Some(member), Some(name)) =
+                    (operands.first(), operands.get(1), operands.get(2))
+                {
+                    member_names.insert((id(target), member.as_str()), name.trim_matches('"'));
+                }
+            }
+            "OpDecorate" => {
+                let (Some(target), Some(decoration), Some(value)) =
+                    (operands.first(), operands.get(1), operands.get(2))
+                else {
+                    continue;
+                };
+                let Ok(value) = value.parse::<u32>() else {
+                    continue;
+                };
+                match decoration.as_str() {
+                    "Location" => {
+                        locations.insert(id(target), value);
+                    }
+                    "SpecId" => {
+                        spec_ids.insert(id(target), value);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut evaluator = Evaluator {
+        definitions: module
+            .instructions
+            .iter()
+            .filter_map(|instruction| Some((instruction.result_id.as_deref()?, instruction)))
+            .collect(),
+        values: HashMap::new(),
+        push_members: HashMap::new(),
+        inputs,
+        spec_ids,
+    };
+
+    // Output variables and access chains into members of output
+    // blocks: id to the variable and member name
+    let mut outputs = HashMap::<&str, (&str, Option<String>)>::new();
+    let mut stores = Vec::new();
+
+    for instruction in &module.instructions {
+        let operands = &instruction.operands;
+        match (
+            instruction.opcode.as_str(),
+            instruction.result_id.as_deref(),
+        ) {
+            ("OpVariable", Some(result))
+                if operands.get(1).map(String::as_str) == Some("Output") =>
+            {
+                outputs.insert(result, (result, None));
+            }
+            ("OpAccessChain" | "OpInBoundsAccessChain", Some(result)) if operands.len() == 3 => {
+                let base = outputs.get(id(&operands[1]));
+                let member = evaluator.values.get(id(&operands[2]));
+                if let (Some((variable, None)), Some(Value::Int(member))) = (base, member) {
+                    let member = member.to_string();
+                    let name = evaluator
+                        .definitions
+                        .get(variable)
+                        .and_then(|variable| variable.operands.first())
+                        .and_then(|pointer| evaluator.definitions.get(id(pointer)))
+                        .and_then(|pointer| pointer.operands.get(1))
+                        .and_then(|pointee| member_names.get(&(id(pointee), member.as_str())))
+                        .map_or(member.clone(), |name| (*name).to_string());
+                    outputs.insert(result, (variable, Some(name)));
+                }
+            }
+            ("OpStore", None) => {
+                let (Some(pointer), Some(value)) = (operands.first(), operands.get(1)) else {
+                    continue;
+                };
+                if let Some(target) = outputs.get(id(pointer)) {
+                    stores.push((target.clone(), id(pointer), id(value)));
+                }
+            }
+            _ => {}
+        }
+
+        let Some(result) = instruction.result_id.as_deref() else {
+            continue;
+        };
+        if let Some(value) = evaluator.evaluate(instruction) {
+            evaluator.values.insert(result, value);
+        }
+    }
+
+    stores
+        .into_iter()
+        .map(|((variable, member), pointer, value)| {
+            let variable_name = names
+                .get(variable)
+                .copied()
+                .filter(|name| !name.is_empty())
+                .map_or_else(|| format!("%{variable}"), str::to_string);
+            let output = match member {
+                Some(member) => format!("{variable_name}.{member}"),
+                None => variable_name,
+            };
+
+            // The stored value has the pointee type of the target pointer
+            let value_type = evaluator
+                .definitions
+                .get(pointer)
+                .and_then(|target| target.operands.first())
+                .and_then(|pointer| evaluator.definitions.get(id(pointer)))
+                .and_then(|pointer| pointer.operands.get(1))
+                .map(|pointee| id(pointee));
+
+            OutputStore {
+                output,
+                location: locations.get(variable).copied(),
+                value: evaluator
+                    .values
+                    .get(value)
+                    .zip(value_type)
+                    .map(|(value, value_type)| value.format(&evaluator, value_type)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Stores a spec-constant-derived color to `color`, a push constant
+    /// to `tint` and an interpolated input to `passthrough`.
+    const SHADER: &str = r#"
+               OpCapability Shader
+          %1 = OpExtInstImport "GLSL.std.450"
+               OpMemoryModel Logical GLSL450
+               OpEntryPoint Fragment %main "main" %color %tint %passthrough %input
+               OpExecutionMode %main OriginUpperLeft
+               OpName %main "main"
+               OpName %color "color"
+               OpName %tint "tint"
+               OpName %passthrough "passthrough"
+               OpDecorate %color Location 0
+               OpDecorate %tint Location 1
+               OpDecorate %passthrough Location 2
+               OpDecorate %input Location 0
+               OpDecorate %scale SpecId 3
+               OpMemberDecorate %Push 0 Offset 0
+               OpDecorate %Push Block
+       %void = OpTypeVoid
+         %fn = OpTypeFunction %void
+      %float = OpTypeFloat 32
+        %int = OpTypeInt 32 1
+    %v4float = OpTypeVector %float 4
+ %out_v4float = OpTypePointer Output %v4float
+  %in_v4float = OpTypePointer Input %v4float
+      %color = OpVariable %out_v4float Output
+       %tint = OpVariable %out_v4float Output
+%passthrough = OpVariable %out_v4float Output
+      %input = OpVariable %in_v4float Input
+       %Push = OpTypeStruct %float
+  %push_type = OpTypePointer PushConstant %Push
+       %push = OpVariable %push_type PushConstant
+ %push_float = OpTypePointer PushConstant %float
+      %int_0 = OpConstant %int 0
+    %float_0 = OpConstant %float 0
+    %float_1 = OpConstant %float 1
+  %float_0_5 = OpConstant %float 0.5
+      %scale = OpSpecConstant %float 2
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+       %half = OpFMul %float %float_0_5 %scale
+    %clamped = OpExtInst %float %1 FClamp %scale %float_0 %float_1
+         %rg = OpCompositeConstruct %v4float %half %clamped %float_0 %float_1
+               OpStore %color %rg
+    %pointer = OpAccessChain %push_float %push %int_0
+     %loaded = OpLoad %float %pointer
+       %gray = OpCompositeConstruct %v4float %loaded %loaded %loaded %float_1
+               OpStore %tint %gray
+   %varying = OpLoad %v4float %input
+               OpStore %passthrough %varying
+               OpReturn
+               OpFunctionEnd
+"#;
+
+    fn values(inputs: &Inputs) -> Vec<(String, Option<u32>, Option<String>)> {
+        evaluate_outputs(&Module::parse(SHADER), inputs)
+            .into_iter()
+            .map(|store| (store.output, store.location, store.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(
+            values(&Inputs::default()),
+            [
+                (
+                    "color".to_string(),
+                    Some(0),
+                    Some("vec4(1, 1, 0, 1)".to_string())
+                ),
+                ("tint".to_string(), Some(1), None),
+                ("passthrough".to_string(), Some(2), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_given_inputs() {
+        let inputs = Inputs {
+            spec_constants: HashMap::from([(3, 0.5)]),
+            push_constants: HashMap::from([(0, vec![0.25])]),
+        };
+        let values = values(&inputs);
+        assert_eq!(values[0].2.as_deref(), Some("vec4(0.25, 0.5, 0, 1)"));
+        assert_eq!(values[1].2.as_deref(), Some("vec4(0.25, 0.25, 0.25, 1)"));
+        // Inputs never determine an interpolated value
+        assert_eq!(values[2].2, None);
+    }
+
+    #[test]
+    fn test_integer_wrapping() {
+        let shader = r#"
+               OpEntryPoint Fragment %main "main" %out
+               OpName %out "out"
+       %uint = OpTypeInt 32 0
+        %int = OpTypeInt 32 1
+   %out_uint = OpTypePointer Output %uint
+    %out_int = OpTypePointer Output %int
+        %out = OpVariable %out_uint Output
+   %negative = OpVariable %out_int Output
+        %max = OpConstant %uint 4294967295
+        %one = OpConstant %uint 1
+    %int_min = OpConstant %int -2147483648
+    %int_one = OpConstant %int 1
+    %wrapped = OpIAdd %uint %max %one
+        %sub = OpISub %int %int_min %int_one
+               OpStore %out %wrapped
+               OpStore %negative %sub
+"#;
+        let stores = evaluate_outputs(&Module::parse(shader), &Inputs::default());
+        assert_eq!(stores[0].value.as_deref(), Some("0"));
+        assert_eq!(stores[1].output, "%negative");
+        assert_eq!(stores[1].value.as_deref(), Some("2147483647"));
+    }
+}
@@ -0,0 +1,341 @@
+//! Result tables of batch runs, such as suite tests and scenes, written
+//! out for analysis in notebooks: as CSV, or as Parquet for paths ending
+//! in `.parquet`.
+
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Number(f64),
+    Flag(bool),
+    Missing,
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Value {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Value {
+        Value::Text(text)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Value {
+        Value::Number(number)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(flag: bool) -> Value {
+        Value::Flag(flag)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Value {
+        value.map_or(Value::Missing, Into::into)
+    }
+}
+
+/// How a column is stored in Parquet: doubles or booleans when every
+/// value present is one, otherwise text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnType {
+    Number,
+    Flag,
+    Text,
+}
+
+/// A table with one row per run; every row has a value per column.
+#[derive(Debug, Default)]
+pub struct Table {
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: &[&'static str]) -> Table {
+        Table {
+            columns: columns.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    fn column_type(&self, column: usize) -> ColumnType {
+        let present = self
+            .rows
+            .iter()
+            .map(|row| &row[column])
+            .filter(|value| **value != Value::Missing)
+            .collect::<Vec<_>>();
+        if present.is_empty() {
+            ColumnType::Text
+        } else if present
+            .iter()
+            .all(|value| matches!(value, Value::Number(_)))
+        {
+            ColumnType::Number
+        } else if present.iter().all(|value| matches!(value, Value::Flag(_))) {
+            ColumnType::Flag
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    /// The table as CSV with a header row. Missing values are empty, and
+    /// fields with commas, quotes or line breaks are quoted.
+    pub fn to_csv(&self) -> String {
+        let field = |value: &Value| {
+            let text = match value {
+                Value::Text(text) => text.clone(),
+                Value::Number(number) => number.to_string(),
+                Value::Flag(flag) => flag.to_string(),
+                Value::Missing => String::new(),
+            };
+            if text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text
+            }
+        };
+
+        let mut csv = self.columns.join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            csv.push_str(&row.iter().map(field).collect::<Vec<_>>().join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Writes the table as one uncompressed row group with an optional
+    /// column per table column.
+    fn write_parquet(&self, path: &Path) -> Result<(), parquet::errors::ParquetError> {
+        let types = (0..self.columns.len())
+            .map(|column| self.column_type(column))
+            .collect::<Vec<_>>();
+        let fields = self
+            .columns
+            .iter()
+            .zip(&types)
+            .map(|(name, column_type)| match column_type {
+                ColumnType::Number => format!("OPTIONAL DOUBLE {name};"),
+                ColumnType::Flag => format!("OPTIONAL BOOLEAN {name};"),
+                ColumnType::Text => format!("OPTIONAL BYTE_ARRAY {name} (UTF8);"),
+            })
+            .collect::<Vec<_>>();
+        let schema = parquet::schema::parser::parse_message_type(&format!(
+            "message results {{ {} }}",
+            fields.join(" ")
+        ))?;
+
+        let file = std::fs::File::create(path)?;
+        let mut writer = SerializedFileWriter::new(
+            file,
+            Arc::new(schema),
+            Arc::new(WriterProperties::builder().build()),
+        )?;
+        let mut row_group = writer.next_row_group()?;
+        for (column, column_type) in types.iter().enumerate() {
+            let Some(mut column_writer) = row_group.next_column()? else {
+                break;
+            };
+            let values = self.rows.iter().map(|row| &row[column]);
+            let levels = values
+                .clone()
+                .map(|value| i16::from(*value != Value::Missing))
+                .collect::<Vec<_>>();
+            match column_type {
+                ColumnType::Number => {
+                    let numbers = values
+                        .filter_map(|value| match value {
+                            Value::Number(number) => Some(*number),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    column_writer.typed::<DoubleType>().write_batch(
+                        &numbers,
+                        Some(&levels),
+                        None,
+                    )?;
+                }
+                ColumnType::Flag => {
+                    let flags = values
+                        .filter_map(|value| match value {
+                            Value::Flag(flag) => Some(*flag),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    column_writer
+                        .typed::<BoolType>()
+                        .write_batch(&flags, Some(&levels), None)?;
+                }
+                ColumnType::Text => {
+                    let texts = values
+                        .filter_map(|value| match value {
+                            Value::Text(text) => Some(ByteArray::from(text.as_str())),
+                            Value::Number(number) => {
+                                Some(ByteArray::from(number.to_string().as_str()))
+                            }
+                            Value::Flag(flag) => Some(ByteArray::from(flag.to_string().as_str())),
+                            Value::Missing => None,
+                        })
+                        .collect::<Vec<_>>();
+                    column_writer.typed::<ByteArrayType>().write_batch(
+                        &texts,
+                        Some(&levels),
+                        None,
+                    )?;
+                }
+            }
+            column_writer.close()?;
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+
+    /// Writes the table to `path`, as Parquet if it ends in `.parquet`
+    /// and as CSV otherwise.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("parquet"))
+        {
+            self.write_parquet(path).map_err(|e| e.to_string())
+        } else {
+            std::fs::write(path, self.to_csv()).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Parses CSV as `Table::to_csv` writes it, or as spreadsheets and
+/// notebooks save it: the header's column names and the rows of fields.
+pub fn read_csv(text: &str) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("A quoted field is not closed".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    let mut records = records.into_iter();
+    let header = records.next().ok_or("The CSV file is empty")?;
+    let rows = records
+        .filter(|row| row.iter().any(|field| !field.is_empty()))
+        .collect::<Vec<_>>();
+    if let Some((index, row)) = rows
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != header.len())
+    {
+        return Err(format!(
+            "Row {} has {} fields, but the header has {}",
+            index + 1,
+            row.len(),
+            header.len()
+        ));
+    }
+    Ok((header, rows))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn table() -> Table {
+        let mut table = Table::new(&["test", "passed", "duration_ms", "errors"]);
+        table.push(vec!["red".into(), true.into(), 12.5.into(), Value::Missing]);
+        table.push(vec![
+            "blue, dark".into(),
+            false.into(),
+            Value::Missing,
+            "line 3: \"probe\" failed\n  expected 1".into(),
+        ]);
+        table
+    }
+
+    #[test]
+    fn test_write() {
+        assert_eq!(
+            table().to_csv(),
+            "test,passed,duration_ms,errors\n\
+             red,true,12.5,\n\
+             \"blue, dark\",false,,\"line 3: \"\"probe\"\" failed\n  expected 1\"\n"
+        );
+
+        let (header, rows) = read_csv(&table().to_csv()).unwrap();
+        assert_eq!(header, ["test", "passed", "duration_ms", "errors"]);
+        assert_eq!(rows[1][0], "blue, dark");
+        assert_eq!(rows[1][3], "line 3: \"probe\" failed\n  expected 1");
+        assert_eq!(rows[0][3], "");
+        assert!(read_csv("a,b\n1\n").is_err());
+
+        let path = std::env::temp_dir().join(format!("export_test_{}.parquet", std::process::id()));
+        table().write(&path).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        let types = metadata
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| column.physical_type().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(types, ["BYTE_ARRAY", "BOOLEAN", "DOUBLE", "BYTE_ARRAY"]);
+
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows[0],
+            "{test: \"red\", passed: true, duration_ms: 12.5, errors: null}"
+        );
+    }
+}
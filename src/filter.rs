@@ -0,0 +1,153 @@
+//! Image filters as compute shaders over packed pixels: each pixel is one
+//! uint holding RGBA8 as packUnorm4x8 does (red in the lowest byte), so
+//! a filter reads and writes plain storage buffers instead of images.
+
+use image::RgbaImage;
+use rmcp::schemars::{self, JsonSchema};
+
+/// Declarations every filter starts with.
+const INTERFACE: &str =
+    "layout(std430, binding = 0) readonly buffer InputImage { uint pixels_in[]; };
+layout(std430, binding = 1) writeonly buffer OutputImage { uint pixels_out[]; };
+layout(push_constant) uniform Size { uint width; uint height; };";
+
+/// Built-in filters, with 8 × 8 workgroups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, JsonSchema)]
+pub enum Preset {
+    Invert,
+    /// Rec. 709 luma of the sRGB-encoded values.
+    Grayscale,
+    /// Mean of the 3 × 3 neighborhood, clamping at the edges.
+    BoxBlur,
+    /// 3 × 3 unsharp mask.
+    Sharpen,
+    /// Gradient magnitude of the luma.
+    Sobel,
+}
+
+impl Preset {
+    pub fn shader(self) -> String {
+        let body = match self {
+            Preset::Invert => "vec4 color = load(x, y);
+    color.rgb = 1.0 - color.rgb;",
+            Preset::Grayscale => "vec4 color = load(x, y);
+    color.rgb = vec3(luma(color));",
+            Preset::BoxBlur => "vec4 color = vec4(0.0);
+    for (int dy = -1; dy <= 1; dy++)
+        for (int dx = -1; dx <= 1; dx++)
+            color += load(x + dx, y + dy);
+    color /= 9.0;",
+            Preset::Sharpen => "vec4 color = 5.0 * load(x, y) - load(x - 1, y) - load(x + 1, y)
+        - load(x, y - 1) - load(x, y + 1);
+    color.a = load(x, y).a;",
+            Preset::Sobel => "float gx = luma(load(x + 1, y - 1)) + 2.0 * luma(load(x + 1, y)) + luma(load(x + 1, y + 1))
+        - luma(load(x - 1, y - 1)) - 2.0 * luma(load(x - 1, y)) - luma(load(x - 1, y + 1));
+    float gy = luma(load(x - 1, y + 1)) + 2.0 * luma(load(x, y + 1)) + luma(load(x + 1, y + 1))
+        - luma(load(x - 1, y - 1)) - 2.0 * luma(load(x, y - 1)) - luma(load(x + 1, y - 1));
+    vec4 color = vec4(vec3(length(vec2(gx, gy))), 1.0);",
+        };
+        format!(
+            "#version 450
+layout(local_size_x = 8, local_size_y = 8) in;
+{INTERFACE}
+vec4 load(int x, int y)
+{{
+    x = clamp(x, 0, int(width) - 1);
+    y = clamp(y, 0, int(height) - 1);
+    return unpackUnorm4x8(pixels_in[y * int(width) + x]);
+}}
+float luma(vec4 color)
+{{
+    return dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+}}
+void main()
+{{
+    int x = int(gl_GlobalInvocationID.x);
+    int y = int(gl_GlobalInvocationID.y);
+    if (x >= int(width) || y >= int(height))
+        return;
+    {body}
+    pixels_out[y * int(width) + x] = packUnorm4x8(clamp(color, 0.0, 1.0));
+}}
+"
+        )
+    }
+}
+
+/// The pixels of `img` as packed uint SSBO subdata values.
+pub fn pack(img: &RgbaImage) -> Vec<String> {
+    img.pixels()
+        .map(|pixel| u32::from_le_bytes(pixel.0).to_string())
+        .collect()
+}
+
+/// The image in a dumped output buffer, if it holds enough pixels.
+pub fn unpack(dump: &[u8], width: u32, height: u32) -> Option<RgbaImage> {
+    let bytes = (width * height * 4) as usize;
+    RgbaImage::from_raw(width, height, dump.get(..bytes)?.to_vec())
+}
+
+/// How an image differs from a reference of the same size, over the
+/// color channels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difference {
+    /// Peak signal-to-noise ratio in dB, infinite for identical images.
+    pub psnr: f64,
+    /// Largest channel difference in 0-255.
+    pub max: u8,
+    /// Pixels with any channel different.
+    pub differing: usize,
+}
+
+pub fn difference(img: &RgbaImage, reference: &RgbaImage) -> Difference {
+    let mut squared = 0.0;
+    let mut max = 0;
+    let mut differing = 0;
+    for (pixel, expected) in img.pixels().zip(reference.pixels()) {
+        let mut differs = false;
+        for c in 0..3 {
+            let delta = pixel[c].abs_diff(expected[c]);
+            squared += f64::from(delta).powi(2);
+            max = max.max(delta);
+            differs |= delta > 0;
+        }
+        differing += usize::from(differs);
+    }
+    let mse = squared / (3 * img.pixels().len()).max(1) as f64;
+    Difference {
+        psnr: 10.0 * (255.0f64.powi(2) / mse).log10(),
+        max,
+        differing,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_difference() {
+        let img = RgbaImage::from_fn(4, 4, |x, y| {
+            image::Rgba([x as u8 * 60, y as u8 * 60, 0, 255])
+        });
+        let packed = pack(&img)
+            .iter()
+            .flat_map(|value| value.parse::<u32>().unwrap().to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(unpack(&packed, 4, 4).as_ref(), Some(&img));
+        assert_eq!(unpack(&packed, 4, 5), None);
+
+        let identical = difference(&img, &img);
+        assert_eq!(
+            (identical.psnr, identical.max, identical.differing),
+            (f64::INFINITY, 0, 0)
+        );
+
+        // One channel of one pixel off by 16: MSE 256 / 48
+        let mut other = img.clone();
+        other.get_pixel_mut(1, 2)[2] = 16;
+        let different = difference(&other, &img);
+        assert_eq!((different.max, different.differing), (16, 1));
+        assert!((different.psnr - 10.0 * (255.0f64.powi(2) * 48.0 / 256.0).log10()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,757 @@
+//! Probes of the device's floating-point environment: one compute shader
+//! per float type evaluates statements on inputs it reads from a buffer,
+//! so the shader compiler cannot fold them, and the results tell how the
+//! device treats denormals, contraction, rounding and special values and
+//! how accurate its built-in functions are.
+
+use rmcp::schemars::{self, JsonSchema};
+
+/// Float types the device may support, each probed by its own kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, JsonSchema)]
+pub enum FloatType {
+    Float16,
+    Float32,
+    Float64,
+}
+
+impl FloatType {
+    pub const ALL: &[FloatType] = &[FloatType::Float16, FloatType::Float32, FloatType::Float64];
+
+    pub fn glsl(self) -> &'static str {
+        match self {
+            FloatType::Float16 => "float16_t",
+            FloatType::Float32 => "float",
+            FloatType::Float64 => "double",
+        }
+    }
+
+    /// Explicitly stored mantissa bits.
+    fn mantissa_bits(self) -> i32 {
+        match self {
+            FloatType::Float16 => 10,
+            FloatType::Float32 => 23,
+            FloatType::Float64 => 52,
+        }
+    }
+
+    /// Exponent of the smallest normal value.
+    fn min_exponent(self) -> i32 {
+        match self {
+            FloatType::Float16 => -14,
+            FloatType::Float32 => -126,
+            FloatType::Float64 => -1022,
+        }
+    }
+
+    fn min_normal(self) -> f64 {
+        2f64.powi(self.min_exponent())
+    }
+
+    fn min_subnormal(self) -> f64 {
+        2f64.powi(self.min_exponent() - self.mantissa_bits())
+    }
+
+    fn max(self) -> f64 {
+        match self {
+            FloatType::Float16 => 65504.0,
+            FloatType::Float32 => f32::MAX.into(),
+            FloatType::Float64 => f64::MAX,
+        }
+    }
+
+    /// The bits of `value` rounded to this type, to nearest even.
+    fn bits_of(self, value: f64) -> u64 {
+        match self {
+            FloatType::Float16 => half_bits(value).into(),
+            FloatType::Float32 => (value as f32).to_bits().into(),
+            FloatType::Float64 => value.to_bits(),
+        }
+    }
+
+    fn value_of(self, bits: u64) -> f64 {
+        match self {
+            FloatType::Float16 => half_value(bits as u16),
+            FloatType::Float32 => f32::from_bits(bits as u32).into(),
+            FloatType::Float64 => f64::from_bits(bits),
+        }
+    }
+
+    /// `value` rounded to this type.
+    fn round(self, value: f64) -> f64 {
+        self.value_of(self.bits_of(value))
+    }
+
+    /// `value`, a value of this type, as short as it reads back.
+    fn display(self, value: f64) -> String {
+        match self {
+            FloatType::Float32 => (value as f32).to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// The distance between the values of this type around `value`.
+    fn ulp(self, value: f64) -> f64 {
+        let exponent = if value == 0.0 || !value.is_finite() {
+            self.min_exponent()
+        } else {
+            (value.abs().log2().floor() as i32).max(self.min_exponent())
+        };
+        2f64.powi(exponent - self.mantissa_bits())
+    }
+}
+
+/// The value of a half float.
+pub fn half_value(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f64::from(bits & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1f if mantissa == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
+
+fn half_bits(value: f64) -> u16 {
+    if value.is_nan() {
+        return 0x7e00;
+    }
+    let sign = if value.is_sign_negative() { 0x8000 } else { 0 };
+    let magnitude = value.abs();
+    if magnitude >= 65520.0 {
+        return sign | 0x7c00;
+    }
+    if magnitude < 2f64.powi(-14) {
+        // A mantissa rounded up to 0x400 is the smallest normal value
+        return sign | (magnitude * 2f64.powi(24)).round_ties_even() as u16;
+    }
+    let mut exponent = magnitude.log2().floor() as i32;
+    let mut mantissa = ((magnitude / 2f64.powi(exponent) - 1.0) * 1024.0).round_ties_even() as u16;
+    if mantissa == 0x400 {
+        exponent += 1;
+        mantissa = 0;
+    }
+    sign | (((exponent + 15) as u16) << 10) | mantissa
+}
+
+/// A behavior probed by one statement that computes `r` from the inputs
+/// `a`, `b` and `c`, with the results it is known to have and what each
+/// of them means.
+struct Behavior {
+    section: &'static str,
+    name: &'static str,
+    body: &'static str,
+    inputs: fn(FloatType) -> [f64; 3],
+    outcomes: fn(FloatType) -> Vec<(f64, &'static str)>,
+}
+
+/// The term `h` of the contraction probes: (1 + h)² - (1 + 2h) is h²,
+/// which an FMA keeps and a separately rounded product loses.
+fn contraction_term(float: FloatType) -> f64 {
+    2f64.powi(-(float.mantissa_bits() / 2 + 2))
+}
+
+fn contraction_inputs(float: FloatType) -> [f64; 3] {
+    let h = contraction_term(float);
+    [1.0 + h, 1.0 + h, -(1.0 + 2.0 * h)]
+}
+
+/// Inputs whose exact sum lies halfway between two values.
+fn tie_inputs(float: FloatType) -> [f64; 3] {
+    let ulp = float.ulp(1.0);
+    [1.0 + ulp, ulp / 2.0, 0.0]
+}
+
+const BEHAVIORS: &[Behavior] = &[
+    Behavior {
+        section: "Denormals",
+        name: "denormal operands",
+        body: "r = a * b;",
+        inputs: |float| [3.0 * float.min_subnormal(), 1.0, 0.0],
+        outcomes: |float| {
+            vec![
+                (3.0 * float.min_subnormal(), "preserved"),
+                (0.0, "flushed to zero"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Denormals",
+        name: "denormal results",
+        body: "r = a * b;",
+        inputs: |float| [float.min_normal(), 0.25, 0.0],
+        outcomes: |float| {
+            vec![
+                (float.min_normal() / 4.0, "preserved"),
+                (0.0, "flushed to zero"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Denormals",
+        name: "denormal addition",
+        body: "r = a + b;",
+        inputs: |float| {
+            [
+                5.0 * float.min_subnormal(),
+                -2.0 * float.min_subnormal(),
+                0.0,
+            ]
+        },
+        outcomes: |float| {
+            vec![
+                (3.0 * float.min_subnormal(), "preserved"),
+                (0.0, "flushed to zero"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Contraction",
+        name: "a * b + c",
+        body: "r = a * b + c;",
+        inputs: contraction_inputs,
+        outcomes: |float| {
+            vec![
+                (contraction_term(float).powi(2), "contracted into an FMA"),
+                (0.0, "rounded separately"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Contraction",
+        name: "precise a * b + c",
+        body: "precise T t = a * b + c;\n        r = t;",
+        inputs: contraction_inputs,
+        outcomes: |float| {
+            vec![
+                (
+                    contraction_term(float).powi(2),
+                    "CONTRACTED despite precise",
+                ),
+                (0.0, "rounded separately, as precise requires"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Contraction",
+        name: "fma(a, b, c)",
+        body: "r = fma(a, b, c);",
+        inputs: contraction_inputs,
+        outcomes: |float| {
+            vec![
+                (contraction_term(float).powi(2), "fused"),
+                (0.0, "not fused, the product is rounded first"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Rounding",
+        name: "addition of a halfway value",
+        body: "r = a + b;",
+        inputs: tie_inputs,
+        outcomes: |float| {
+            let ulp = float.ulp(1.0);
+            vec![
+                (1.0 + 2.0 * ulp, "round to nearest even"),
+                (1.0 + ulp, "round toward zero"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Rounding",
+        name: "negative addition of a halfway value",
+        body: "r = -a - b;",
+        inputs: tie_inputs,
+        outcomes: |float| {
+            let ulp = float.ulp(1.0);
+            vec![
+                (-1.0 - 2.0 * ulp, "round to nearest even"),
+                (-1.0 - ulp, "round toward zero"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Special values",
+        name: "overflow",
+        body: "r = a * b;",
+        inputs: |float| [float.max(), 2.0, 0.0],
+        outcomes: |float| {
+            vec![
+                (f64::INFINITY, "rounds to infinity"),
+                (float.max(), "saturates to the largest finite value"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Special values",
+        name: "0 / 0",
+        body: "r = a / b;",
+        inputs: |_| [0.0, 0.0, 0.0],
+        outcomes: |_| vec![(f64::NAN, "NaN")],
+    },
+    Behavior {
+        section: "Special values",
+        name: "isnan(0 / 0)",
+        body: "r = isnan(a / b) ? c : a;",
+        inputs: |_| [0.0, 0.0, 1.0],
+        outcomes: |_| {
+            vec![
+                (1.0, "true"),
+                (0.0, "FALSE, NaN checks may be optimized away"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Special values",
+        name: "min(NaN, 1)",
+        body: "r = min(a / b, c);",
+        inputs: |_| [0.0, 0.0, 1.0],
+        outcomes: |_| {
+            vec![
+                (1.0, "returns the other operand"),
+                (f64::NAN, "returns NaN"),
+            ]
+        },
+    },
+    Behavior {
+        section: "Special values",
+        name: "0 * -1",
+        body: "r = a * b;",
+        inputs: |_| [0.0, -1.0, 0.0],
+        outcomes: |_| {
+            vec![
+                (-0.0, "keeps the sign of zero"),
+                (0.0, "LOSES the sign of zero"),
+            ]
+        },
+    },
+];
+
+/// What Vulkan requires of a 32-bit float function at an input.
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    Ulp(f64),
+    Absolute(f64),
+}
+
+impl std::fmt::Display for Bound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bound::Ulp(ulp) => write!(f, "{ulp} ULP"),
+            Bound::Absolute(error) => write!(f, "{error:.2e} absolute"),
+        }
+    }
+}
+
+/// A function whose accuracy is measured against a CPU reference at
+/// some inputs.
+struct Accuracy {
+    expression: &'static str,
+    reference: fn(f64, f64) -> f64,
+    points: &'static [(f64, f64)],
+    /// Whether GLSL has the function for doubles.
+    double: bool,
+    /// The precision Vulkan requires of 32-bit floats at an input.
+    bound: fn(f64) -> Option<Bound>,
+}
+
+const UNARY_POINTS: &[(f64, f64)] = &[
+    (-3.0, 0.0),
+    (-1.7, 0.0),
+    (-0.9, 0.0),
+    (-0.3, 0.0),
+    (0.1, 0.0),
+    (0.6, 0.0),
+    (1.3, 0.0),
+    (2.2, 0.0),
+    (2.9, 0.0),
+];
+
+const POSITIVE_POINTS: &[(f64, f64)] = &[
+    (0.01, 0.0),
+    (0.3, 0.0),
+    (0.75, 0.0),
+    (1.0, 0.0),
+    (1.6, 0.0),
+    (3.7, 0.0),
+    (12.5, 0.0),
+    (100.0, 0.0),
+    (1000.0, 0.0),
+];
+
+const ACCURACY: &[Accuracy] = &[
+    Accuracy {
+        expression: "a / b",
+        reference: |a, b| a / b,
+        points: &[
+            (1.0, 3.0),
+            (2.0, 7.0),
+            (10.0, 3.0),
+            (-5.0, 9.0),
+            (1.0, 10.0),
+            (22.0, 7.0),
+            (0.3, 0.7),
+        ],
+        double: true,
+        bound: |_| Some(Bound::Ulp(2.5)),
+    },
+    Accuracy {
+        expression: "sqrt(a)",
+        reference: |a, _| a.sqrt(),
+        points: POSITIVE_POINTS,
+        double: true,
+        bound: |_| None,
+    },
+    Accuracy {
+        expression: "inversesqrt(a)",
+        reference: |a, _| 1.0 / a.sqrt(),
+        points: POSITIVE_POINTS,
+        double: true,
+        bound: |_| Some(Bound::Ulp(2.0)),
+    },
+    Accuracy {
+        expression: "exp(a)",
+        reference: |a, _| a.exp(),
+        points: UNARY_POINTS,
+        double: false,
+        bound: |a| Some(Bound::Ulp(3.0 + 2.0 * a.abs())),
+    },
+    Accuracy {
+        expression: "exp2(a)",
+        reference: |a, _| a.exp2(),
+        points: UNARY_POINTS,
+        double: false,
+        bound: |a| Some(Bound::Ulp(3.0 + 2.0 * a.abs())),
+    },
+    Accuracy {
+        expression: "log(a)",
+        reference: |a, _| a.ln(),
+        points: POSITIVE_POINTS,
+        double: false,
+        bound: |a| {
+            Some(if (0.5..=2.0).contains(&a) {
+                Bound::Absolute(2f64.powi(-21))
+            } else {
+                Bound::Ulp(3.0)
+            })
+        },
+    },
+    Accuracy {
+        expression: "log2(a)",
+        reference: |a, _| a.log2(),
+        points: POSITIVE_POINTS,
+        double: false,
+        bound: |a| {
+            Some(if (0.5..=2.0).contains(&a) {
+                Bound::Absolute(2f64.powi(-21))
+            } else {
+                Bound::Ulp(3.0)
+            })
+        },
+    },
+    Accuracy {
+        expression: "sin(a)",
+        reference: |a, _| a.sin(),
+        points: UNARY_POINTS,
+        double: false,
+        bound: |_| Some(Bound::Absolute(2f64.powi(-11))),
+    },
+    Accuracy {
+        expression: "cos(a)",
+        reference: |a, _| a.cos(),
+        points: UNARY_POINTS,
+        double: false,
+        bound: |_| Some(Bound::Absolute(2f64.powi(-11))),
+    },
+    Accuracy {
+        expression: "tan(a)",
+        reference: |a, _| a.tan(),
+        points: UNARY_POINTS,
+        double: false,
+        bound: |_| None,
+    },
+    Accuracy {
+        expression: "atan(a)",
+        reference: |a, _| a.atan(),
+        points: UNARY_POINTS,
+        double: false,
+        bound: |_| Some(Bound::Ulp(4096.0)),
+    },
+    Accuracy {
+        expression: "pow(a, b)",
+        reference: f64::powf,
+        points: &[(2.0, 0.5), (3.0, 1.7), (0.5, 3.3), (10.0, 2.5), (1.5, -2.2)],
+        double: false,
+        bound: |_| None,
+    },
+];
+
+fn accuracy(float: FloatType) -> impl Iterator<Item = &'static Accuracy> {
+    ACCURACY
+        .iter()
+        .filter(move |accuracy| accuracy.double || float != FloatType::Float64)
+}
+
+/// Words per evaluation: three inputs and the result, two words each so
+/// that doubles fit.
+const SLOT_WORDS: usize = 8;
+
+/// One statement of the kernel and its inputs.
+struct Evaluation {
+    body: String,
+    inputs: [f64; 3],
+}
+
+/// The statements of the kernel of `float`: the behaviors, then every
+/// point of every function.
+fn evaluations(float: FloatType) -> Vec<Evaluation> {
+    let mut evaluations = BEHAVIORS
+        .iter()
+        .map(|behavior| Evaluation {
+            body: behavior.body.to_string(),
+            inputs: (behavior.inputs)(float),
+        })
+        .collect::<Vec<_>>();
+    for accuracy in accuracy(float) {
+        evaluations.extend(accuracy.points.iter().map(|&(a, b)| Evaluation {
+            body: format!("r = {};", accuracy.expression),
+            inputs: [a, b, 0.0],
+        }));
+    }
+    evaluations
+}
+
+/// Compute shader of the kernel of `float`, run by a single invocation.
+pub fn shader(float: FloatType) -> String {
+    let mut lines = vec!["#version 450".to_string()];
+    if float == FloatType::Float16 {
+        lines.push(
+            "#extension GL_EXT_shader_explicit_arithmetic_types_float16 : require".to_string(),
+        );
+    }
+    lines.push(format!("#define T {}", float.glsl()));
+    lines.push("layout(local_size_x = 1) in;".to_string());
+    lines.push("layout(std430, binding = 0) buffer Data { uint words[]; };".to_string());
+    lines.push(
+        match float {
+            FloatType::Float16 => {
+                "T load(uint i) { return unpackFloat2x16(words[i]).x; }\n\
+                 void store(uint i, T v) { words[i] = packFloat2x16(f16vec2(v, v)); }"
+            }
+            FloatType::Float32 => {
+                "T load(uint i) { return uintBitsToFloat(words[i]); }\n\
+                 void store(uint i, T v) { words[i] = floatBitsToUint(v); }"
+            }
+            FloatType::Float64 => {
+                "T load(uint i) { return packDouble2x32(uvec2(words[i], words[i + 1u])); }\n\
+                 void store(uint i, T v) { uvec2 bits = unpackDouble2x32(v); words[i] = bits.x; words[i + 1u] = bits.y; }"
+            }
+        }
+        .to_string(),
+    );
+    lines.push("void main()\n{".to_string());
+    for (index, evaluation) in evaluations(float).iter().enumerate() {
+        let base = index * SLOT_WORDS;
+        lines.push(format!(
+            "    {{\n        T a = load({base}u), b = load({}u), c = load({}u);\n        T r;\n        {}\n        store({}u, r);\n    }}",
+            base + 2,
+            base + 4,
+            evaluation.body,
+            base + 6
+        ));
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// Initial words of the kernel's buffer: the inputs of every
+/// evaluation and room for its result.
+pub fn buffer(float: FloatType) -> Vec<u32> {
+    let mut words = Vec::new();
+    for evaluation in evaluations(float) {
+        for input in evaluation.inputs {
+            let bits = float.bits_of(input);
+            words.extend([bits as u32, (bits >> 32) as u32]);
+        }
+        words.extend([0, 0]);
+    }
+    words
+}
+
+/// Whether a result is the known outcome `expected`, telling zeros
+/// apart by sign.
+fn same(actual: f64, expected: f64) -> bool {
+    (actual.is_nan() && expected.is_nan())
+        || (actual == expected && actual.is_sign_negative() == expected.is_sign_negative())
+}
+
+/// The report of a kernel run of `float`, one indented line per
+/// behavior and function under a heading per section.
+pub fn report(float: FloatType, dump: &[u8]) -> Vec<String> {
+    let words = dump
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect::<Vec<_>>();
+    let mut results = words.chunks_exact(SLOT_WORDS).map(|slot| {
+        let bits = u64::from(slot[6]) | (u64::from(slot[7]) << 32);
+        float.value_of(match float {
+            FloatType::Float16 => bits & 0xffff,
+            FloatType::Float32 => bits & 0xffff_ffff,
+            FloatType::Float64 => bits,
+        })
+    });
+
+    let mut lines = Vec::new();
+    let mut section = "";
+    for behavior in BEHAVIORS {
+        if behavior.section != section {
+            section = behavior.section;
+            lines.push(format!("{section}:"));
+        }
+        let Some(actual) = results.next() else {
+            lines.push(format!("  - {}: no result", behavior.name));
+            continue;
+        };
+        let outcome = (behavior.outcomes)(float)
+            .into_iter()
+            .find(|(expected, _)| same(actual, float.round(*expected)))
+            .map_or_else(
+                || format!("unexpected result {actual:e}"),
+                |(_, meaning)| meaning.to_string(),
+            );
+        lines.push(format!("  - {}: {outcome}", behavior.name));
+    }
+
+    lines.push("Accuracy (largest error over the sampled inputs):".to_string());
+    for accuracy in accuracy(float) {
+        let describe = |a: f64, b: f64| {
+            if accuracy.expression.contains('b') {
+                format!("a = {}, b = {}", float.display(a), float.display(b))
+            } else {
+                format!("a = {}", float.display(a))
+            }
+        };
+        let mut worst_ulp = 0f64;
+        let mut worst_absolute = 0f64;
+        let mut worst_input = None;
+        let mut bounded = false;
+        let mut violations = Vec::new();
+        for &(a, b) in accuracy.points {
+            let Some(actual) = results.next() else {
+                break;
+            };
+            let (a, b) = (float.round(a), float.round(b));
+            let expected = (accuracy.reference)(a, b);
+            let absolute = (actual - expected).abs();
+            let ulp = absolute / float.ulp(expected);
+            if actual.is_nan() || ulp > worst_ulp {
+                worst_ulp = if actual.is_nan() { f64::INFINITY } else { ulp };
+                worst_absolute = absolute;
+                worst_input = Some((a, b));
+            }
+            let bound = (accuracy.bound)(a).filter(|_| float == FloatType::Float32);
+            bounded |= bound.is_some();
+            let within = match bound {
+                Some(Bound::Ulp(limit)) => ulp <= limit,
+                Some(Bound::Absolute(limit)) => absolute <= limit,
+                None => true,
+            };
+            if let (false, Some(bound)) = (within, bound) {
+                violations.push(format!("{} (over {bound})", describe(a, b)));
+            }
+        }
+
+        let Some((a, b)) = worst_input else {
+            lines.push(format!("  - {}: exact", accuracy.expression));
+            continue;
+        };
+        let input = describe(a, b);
+        let verdict = match violations.first() {
+            Some(first) => format!(
+                "; EXCEEDS Vulkan's precision at {} of {} inputs, first {first}",
+                violations.len(),
+                accuracy.points.len()
+            ),
+            None if bounded => "; within Vulkan's precision".to_string(),
+            None => String::new(),
+        };
+        lines.push(format!(
+            "  - {}: {worst_ulp:.2} ULP, {worst_absolute:.2e} absolute at {input}{verdict}",
+            accuracy.expression
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_half_bits() {
+        assert_eq!(half_bits(1.0), 0x3c00);
+        assert_eq!(half_bits(-2.0), 0xc000);
+        assert_eq!(half_bits(65504.0), 0x7bff);
+        assert_eq!(half_bits(70000.0), 0x7c00);
+        assert_eq!(half_bits(2f64.powi(-24)), 0x0001);
+        assert_eq!(half_bits(-0.0), 0x8000);
+        // Halfway between 1 and the next value rounds to even
+        assert_eq!(half_bits(1.0 + 2f64.powi(-11)), 0x3c00);
+        assert_eq!(half_bits(1.0 + 3.0 * 2f64.powi(-11)), 0x3c02);
+        for bits in [0x0001, 0x03ff, 0x0400, 0x3555, 0x7bff, 0xfbff] {
+            assert_eq!(half_bits(half_value(bits)), bits);
+        }
+    }
+
+    /// A dump of a device that rounds every result correctly.
+    fn ieee_dump(float: FloatType) -> Vec<u8> {
+        let behaviors = BEHAVIORS
+            .iter()
+            .map(|behavior| (behavior.outcomes)(float)[0].0);
+        let functions = accuracy(float).flat_map(|accuracy| {
+            accuracy
+                .points
+                .iter()
+                .map(|&(a, b)| (accuracy.reference)(float.round(a), float.round(b)))
+        });
+        behaviors
+            .chain(functions)
+            .flat_map(|result| {
+                let bits = float.bits_of(result);
+                [0, 0, 0, 0, 0, 0, bits as u32, (bits >> 32) as u32]
+            })
+            .flat_map(u32::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn test_report() {
+        for &float in FloatType::ALL {
+            assert_eq!(buffer(float).len(), evaluations(float).len() * SLOT_WORDS);
+            let report = report(float, &ieee_dump(float));
+            assert!(report.contains(&"  - denormal results: preserved".to_string()));
+            assert!(report.contains(&"  - fma(a, b, c): fused".to_string()));
+            assert!(
+                report.contains(
+                    &"  - addition of a halfway value: round to nearest even".to_string()
+                )
+            );
+            assert!(report.contains(&"  - 0 * -1: keeps the sign of zero".to_string()));
+            assert!(!report.iter().any(|line| line.contains("EXCEEDS")));
+            assert!(!report.iter().any(|line| line.contains("unexpected")));
+        }
+
+        let report = report(
+            FloatType::Float32,
+            &vec![0; ieee_dump(FloatType::Float32).len()],
+        );
+        assert!(report.contains(&"  - denormal operands: flushed to zero".to_string()));
+        assert!(report.contains(&"  - a * b + c: rounded separately".to_string()));
+        assert!(
+            report
+                .iter()
+                .any(|line| line.starts_with("  - sin(a): ") && line.contains("EXCEEDS"))
+        );
+    }
+}
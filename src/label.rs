@@ -0,0 +1,296 @@
+//! Text annotations drawn onto saved images with a built-in 5 × 7 bitmap
+//! font, and captioned grids of several images, so the images of many
+//! runs can be told apart and compared at a glance.
+
+use image::{Rgb, RgbImage};
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Pixels from one character or line to the next, including the gap.
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+/// Space between the text and the edges of its backdrop.
+const PADDING: u32 = 2;
+
+/// Printable ASCII from ' ' to '~', one byte per column with the top row
+/// in the least significant bit.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x56, 0x20, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x2a, 0x1c, 0x7f, 0x1c, 0x2a], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+/// The columns of `c`, with '?' standing in for characters the font
+/// doesn't have.
+fn glyph(c: char) -> [u8; 5] {
+    let index = u32::from(c).wrapping_sub(u32::from(' ')) as usize;
+    FONT.get(index)
+        .copied()
+        .unwrap_or(FONT['?' as usize - ' ' as usize])
+}
+
+/// Splits `text` into lines at newlines and wherever a line would be
+/// wider than `columns` characters, preferring to break at spaces.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let columns = columns.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let fits = line.chars().count() + usize::from(!line.is_empty()) + word.chars().count()
+                <= columns;
+            if !line.is_empty() && !fits {
+                lines.push(std::mem::take(&mut line));
+            } else if !line.is_empty() {
+                line.push(' ');
+            }
+            for c in word.chars() {
+                if line.chars().count() == columns {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(c);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Draws `text` in white on a black backdrop in the top-left corner of
+/// `img`, `scale` image pixels per font pixel. Lines are wrapped to the
+/// image's width, and those that don't fit below are left out.
+pub fn draw(img: &mut RgbImage, text: &str, scale: u32) {
+    let scale = scale.max(1);
+    let columns = (img.width().saturating_sub(2 * PADDING * scale) / (ADVANCE * scale)) as usize;
+    let rows = (img.height().saturating_sub(2 * PADDING * scale) / (LINE_HEIGHT * scale)) as usize;
+    let lines = wrap(text, columns);
+    let lines = &lines[..lines.len().min(rows)];
+    let Some(longest) = lines.iter().map(|line| line.chars().count()).max() else {
+        return;
+    };
+
+    let width = ((longest as u32 * ADVANCE + 2 * PADDING) * scale).min(img.width());
+    let height = ((lines.len() as u32 * LINE_HEIGHT + 2 * PADDING) * scale).min(img.height());
+    for y in 0..height {
+        for x in 0..width {
+            img.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+    }
+
+    for (row, line) in lines.iter().enumerate() {
+        let top = (PADDING + row as u32 * LINE_HEIGHT) * scale;
+        write(img, PADDING * scale, top, line, scale, Rgb([255, 255, 255]));
+    }
+}
+
+/// Width in pixels of `text` as one line at `scale`.
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as u32 * ADVANCE).saturating_sub(1) * scale.max(1)
+}
+
+/// Draws `text` as one line in `color` with its top-left corner at
+/// (left, top), without a backdrop. Pixels outside `img` are left out.
+pub fn write(img: &mut RgbImage, left: u32, top: u32, text: &str, scale: u32, color: Rgb<u8>) {
+    let scale = scale.max(1);
+    for (column, c) in text.chars().enumerate() {
+        let left = left + column as u32 * ADVANCE * scale;
+        for (gx, bits) in glyph(c).into_iter().enumerate() {
+            for gy in (0..GLYPH_HEIGHT).filter(|gy| bits >> gy & 1 != 0) {
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (x, y) = (left + gx as u32 * scale + dx, top + gy * scale + dy);
+                        if x < img.width() && y < img.height() {
+                            img.put_pixel(x, y, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Space between the cells of a montage.
+const GAP: u32 = 4;
+
+/// Lays out `tiles` in a grid of `columns`, each above its caption. Cells
+/// are as large as the largest image; a tile without an image is left
+/// dark gray.
+pub fn montage(tiles: &[(Option<RgbImage>, String)], columns: u32) -> RgbImage {
+    let columns = columns.clamp(1, tiles.len().max(1) as u32);
+    let rows = (tiles.len() as u32).div_ceil(columns);
+    let images = tiles.iter().filter_map(|(img, _)| img.as_ref());
+    let width = images.clone().map(RgbImage::width).max().unwrap_or(64);
+    let height = images.map(RgbImage::height).max().unwrap_or(64);
+    let caption_height = LINE_HEIGHT + 2 * PADDING;
+    let cell_height = height + caption_height;
+
+    let mut sheet = RgbImage::from_pixel(
+        columns * (width + GAP) + GAP,
+        rows * (cell_height + GAP) + GAP,
+        Rgb([255, 255, 255]),
+    );
+    for (i, (img, caption)) in tiles.iter().enumerate() {
+        let x = GAP + i as u32 % columns * (width + GAP);
+        let y = GAP + i as u32 / columns * (cell_height + GAP);
+        match img {
+            Some(img) => image::imageops::replace(&mut sheet, img, x.into(), y.into()),
+            None => image::imageops::replace(
+                &mut sheet,
+                &RgbImage::from_pixel(width, height, Rgb([64, 64, 64])),
+                x.into(),
+                y.into(),
+            ),
+        }
+        let mut strip = RgbImage::from_pixel(width, caption_height, Rgb([0, 0, 0]));
+        draw(&mut strip, caption, 1);
+        image::imageops::replace(&mut sheet, &strip, x.into(), (y + height).into());
+    }
+    sheet
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        assert_eq!(
+            wrap("roughness=0.25 metallic=1\nPASS", 16),
+            ["roughness=0.25", "metallic=1", "PASS"]
+        );
+        assert_eq!(wrap("abcdefgh", 3), ["abc", "def", "gh"]);
+        assert_eq!(glyph('\u{e9}'), glyph('?'));
+
+        let mut img = RgbImage::from_pixel(40, 30, Rgb([0, 0, 255]));
+        draw(&mut img, "I", 2);
+        // The I's stem is its middle column, from the top row down
+        let (left, top) = (PADDING * 2, PADDING * 2);
+        assert_eq!(img.get_pixel(left + 4, top)[0], 255);
+        assert_eq!(img.get_pixel(left + 4, top + 13)[0], 255);
+        assert_eq!(*img.get_pixel(left, top + 6), Rgb([0, 0, 0]));
+        // The backdrop only covers one character
+        assert_eq!(*img.get_pixel(0, 0), Rgb([0, 0, 0]));
+        assert_eq!(*img.get_pixel(30, 0), Rgb([0, 0, 255]));
+
+        let tiles = [
+            (
+                Some(RgbImage::from_pixel(10, 8, Rgb([255, 0, 0]))),
+                "a".to_string(),
+            ),
+            (None, "b".to_string()),
+            (
+                Some(RgbImage::from_pixel(6, 6, Rgb([0, 255, 0]))),
+                "c".to_string(),
+            ),
+        ];
+        let sheet = montage(&tiles, 2);
+        let cell_height = 8 + LINE_HEIGHT + 2 * PADDING;
+        assert_eq!(
+            sheet.dimensions(),
+            (2 * 14 + GAP, 2 * (cell_height + GAP) + GAP)
+        );
+        assert_eq!(*sheet.get_pixel(GAP, GAP), Rgb([255, 0, 0]));
+        assert_eq!(*sheet.get_pixel(GAP + 14, GAP), Rgb([64, 64, 64]));
+        assert_eq!(
+            *sheet.get_pixel(GAP, 2 * GAP + cell_height),
+            Rgb([0, 255, 0])
+        );
+        // Smaller images leave the rest of their cell white
+        assert_eq!(
+            *sheet.get_pixel(GAP + 7, 2 * GAP + cell_height),
+            Rgb([255, 255, 255])
+        );
+    }
+}
@@ -1,20 +1,57 @@
 use anyhow::Result;
+use base64::prelude::*;
 use clap::Parser;
+use errors::ErrorCode;
 use image::codecs::pnm::PnmDecoder;
 use image::{DynamicImage, ImageError, RgbImage};
 use rmcp::{
-    Error as McpError, RoleServer, ServerHandler, ServiceExt, const_string, model::*, schemars,
-    service::RequestContext, tool, transport::stdio,
+    Error as McpError, RoleServer, ServerHandler, ServiceExt, const_string,
+    handler::server::tool::ToolCallContext,
+    model::*,
+    schemars,
+    service::{Peer, RequestContext},
+    tool,
 };
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use shaderc::{self, CompileOptions, Compiler, OptimizationLevel, ShaderKind};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime};
 use tracing_subscriber::{self, EnvFilter};
 
+mod access;
+mod analysis;
+mod assertions;
+mod blend;
+mod coopmat;
+mod coverage;
+mod depth;
+mod derivatives;
+mod diff;
+mod errors;
+mod evaluate;
+mod export;
+mod filter;
+mod fp;
+mod label;
+mod mutation;
+mod noise;
+mod plot;
+mod pool;
+mod raymarch;
+mod scan;
+mod sort;
+mod spirv;
+mod subgroups;
+mod suite;
+mod watch;
+mod watchdog;
+
 pub fn read_and_decode_ppm_file<P: AsRef<Path>>(path: P) -> Result<RgbImage, ImageError> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
@@ -24,7 +61,1085 @@ pub fn read_and_decode_ppm_file<P: AsRef<Path>>(path: P) -> Result<RgbImage, Ima
     Ok(rgb_image)
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+/// Manifest name prefixes of CPU-based Vulkan drivers, in order of preference.
+const SOFTWARE_ICD_PREFIXES: &[&str] = &["lvp_icd", "vk_swiftshader_icd"];
+
+/// Directories the Vulkan loader searches for driver manifests.
+const ICD_MANIFEST_DIRS: &[&str] = &[
+    "/usr/share/vulkan/icd.d",
+    "/usr/local/share/vulkan/icd.d",
+    "/etc/vulkan/icd.d",
+];
+
+fn find_icd_manifest(prefix: &str) -> Option<PathBuf> {
+    ICD_MANIFEST_DIRS
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(prefix) && name.ends_with(".json"))
+        })
+}
+
+/// Finds the manifest of an installed software driver (lavapipe or SwiftShader).
+pub fn find_software_icd() -> Option<PathBuf> {
+    SOFTWARE_ICD_PREFIXES
+        .iter()
+        .find_map(|prefix| find_icd_manifest(prefix))
+}
+
+fn is_software_icd(files: &str) -> bool {
+    SOFTWARE_ICD_PREFIXES
+        .iter()
+        .any(|prefix| files.contains(prefix))
+}
+
+/// Resolves an ICD selection, which is either a manifest path or one of the
+/// `lavapipe`/`swiftshader` shorthands.
+fn resolve_icd(icd: &str) -> Result<PathBuf, McpError> {
+    let path = match icd {
+        "lavapipe" => find_icd_manifest("lvp_icd"),
+        "swiftshader" => find_icd_manifest("vk_swiftshader_icd"),
+        _ => Some(PathBuf::from(icd)).filter(|path| path.is_file()),
+    };
+
+    path.ok_or_else(|| {
+        ErrorCode::BadRequestPath.invalid_params(
+            format!("Vulkan driver manifest not found: {icd}"),
+            Some(json!({"searched": ICD_MANIFEST_DIRS})),
+        )
+    })
+}
+
+/// Returns the driver manifest the environment pins vkrunner to if it is a software driver.
+fn software_icd_from_env() -> Option<String> {
+    ["VK_DRIVER_FILES", "VK_ICD_FILENAMES"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|files| is_software_icd(files))
+}
+
+/// Whether vkrunner failed because the loader found no usable driver at all.
+fn reports_no_vulkan_device(output: &Output) -> bool {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    ["reported zero drivers", "VK_ERROR_INCOMPATIBLE_DRIVER"]
+        .iter()
+        .any(|marker| stdout.contains(marker) || stderr.contains(marker))
+}
+
+/// The code of a vkrunner run that failed or skipped its test, from
+/// the messages vkrunner prints for each kind of failure.
+fn vkrunner_failure_code(output: &Output) -> Option<ErrorCode> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let found = |markers: &[&str]| {
+        markers
+            .iter()
+            .any(|marker| stdout.contains(marker) || stderr.contains(marker))
+    };
+
+    // A skip exits successfully
+    if found(&["\"result\": \"skip\""]) {
+        return Some(ErrorCode::UnsupportedFeature);
+    }
+    if output.status.success() {
+        return None;
+    }
+
+    Some(if reports_no_vulkan_device(output) {
+        ErrorCode::NoDevice
+    } else if found(&[
+        "VK_ERROR_DEVICE_LOST",
+        "vkQueueSubmit failed",
+        "vkWaitForFences failed",
+    ]) {
+        ErrorCode::DeviceLost
+    } else if found(&["Missing required"]) {
+        ErrorCode::UnsupportedFeature
+    } else if found(&["Probe color at", "SSBO probe failed"]) {
+        ErrorCode::ProbeFailed
+    } else {
+        ErrorCode::RunFailed
+    })
+}
+
+/// Driver and loader debug switches a request may set for its vkrunner run.
+const ALLOWED_ENV_VARS: &[&str] = &[
+    "MESA_DEBUG",
+    "MESA_LOG_LEVEL",
+    "RADV_DEBUG",
+    "ACO_DEBUG",
+    "ANV_DEBUG",
+    "INTEL_DEBUG",
+    "NIR_DEBUG",
+    "LP_DEBUG",
+    "VK_LOADER_DEBUG",
+    "VK_INSTANCE_LAYERS",
+    "MVK_CONFIG_LOG_LEVEL",
+];
+
+/// Sends the stderr lines of a child process to the client as logging
+/// notifications while it runs, so long runs show progress.
+#[derive(Clone)]
+struct StderrForwarder {
+    peer: Peer<RoleServer>,
+    runtime: tokio::runtime::Handle,
+}
+
+impl StderrForwarder {
+    fn send(&self, line: String) {
+        self.send_as("vkrunner", line);
+    }
+
+    /// Sends a line of the server's own, such as the queue position of a
+    /// run, under another logger name.
+    fn send_as(&self, logger: &str, line: String) {
+        let peer = self.peer.clone();
+        let logger = logger.to_string();
+        self.runtime.spawn(async move {
+            let _ = peer
+                .notify_logging_message(LoggingMessageNotificationParam {
+                    level: LoggingLevel::Info,
+                    logger: Some(logger),
+                    data: serde_json::Value::String(line),
+                })
+                .await;
+        });
+    }
+}
+
+/// Sends progress notifications for a tool call that came with a
+/// progress token.
+#[derive(Clone)]
+struct ProgressNotifier {
+    peer: Peer<RoleServer>,
+    runtime: tokio::runtime::Handle,
+    token: ProgressToken,
+}
+
+impl ProgressNotifier {
+    fn send(&self, progress: u32, total: Option<u32>) {
+        let peer = self.peer.clone();
+        let param = ProgressNotificationParam {
+            progress_token: self.token.clone(),
+            progress,
+            total,
+        };
+        self.runtime.spawn(async move {
+            let _ = peer.notify_progress(param).await;
+        });
+    }
+}
+
+/// Progress tokens of the tool calls that haven't started yet, by
+/// request ID.
+type ProgressTokens = Arc<std::sync::Mutex<std::collections::HashMap<RequestId, ProgressToken>>>;
+
+/// rmcp drops the `_meta` of tools/call requests, so the progress tokens
+/// are read from the client's messages on their way to it. Returns the
+/// stdin the server reads those messages from.
+fn stdin_recording_progress(tokens: ProgressTokens) -> tokio::io::DuplexStream {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    let (reader, mut writer) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some((id, token)) = call_progress_token(&line) {
+                tokens
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(id, token);
+            }
+            let line = line + "\n";
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+    reader
+}
+
+/// The request ID and progress token of a tools/call message that has
+/// one.
+fn call_progress_token(line: &str) -> Option<(RequestId, ProgressToken)> {
+    let message = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    if message["method"] != "tools/call" {
+        return None;
+    }
+    let id = serde_json::from_value(message.get("id")?.clone()).ok()?;
+    let token = message["params"]["_meta"].get("progressToken")?.clone();
+    Some((id, serde_json::from_value(token).ok()?))
+}
+
+/// Orders logging levels by severity, as `logging/setLevel` compares them.
+fn logging_severity(level: &LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// Runs vkrunner on a pooled worker that keeps its Vulkan device from
+/// earlier runs when it can, and as a process of its own otherwise.
+fn run_vkrunner(
+    pool: &pool::WorkerPool,
+    program: &Path,
+    args: &[String],
+    icd: Option<&Path>,
+    env: &[EnvironmentVariable],
+    forwarder: Option<&StderrForwarder>,
+) -> Result<Output, McpError> {
+    let run_err = |e: std::io::Error| {
+        McpError::internal_error(
+            "Failed to run vkrunner",
+            Some(json!({"program": program, "error": e.to_string()})),
+        )
+    };
+
+    if pool.accepts(program, args) {
+        let mut variables = env
+            .iter()
+            .map(|variable| (variable.name.clone(), variable.value.clone()))
+            .collect::<Vec<_>>();
+        if let Some(icd) = icd {
+            let icd = icd.display().to_string();
+            variables.push(("VK_ICD_FILENAMES".to_string(), icd.clone()));
+            variables.push(("VK_DRIVER_FILES".to_string(), icd));
+        }
+        let output = pool
+            .run(program, args, &variables, |line| {
+                if let Some(forwarder) = forwarder {
+                    forwarder.send(line.to_string());
+                }
+            })
+            .map_err(run_err)?;
+        if let Some(output) = output {
+            return Ok(output);
+        }
+    }
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    for variable in env {
+        command.env(&variable.name, &variable.value);
+    }
+
+    if let Some(icd) = icd {
+        command
+            .env("VK_ICD_FILENAMES", icd)
+            .env("VK_DRIVER_FILES", icd);
+    }
+
+    let Some(forwarder) = forwarder else {
+        return command.output().map_err(run_err);
+    };
+
+    let mut child = command.spawn().map_err(run_err)?;
+    let stderr = child.stderr.take();
+    let forwarder = forwarder.clone();
+    // stdout is drained by wait_with_output meanwhile, so neither pipe
+    // can fill up and stall the child
+    let reader = std::thread::spawn(move || {
+        use std::io::BufRead;
+
+        let mut collected = Vec::new();
+        let Some(stderr) = stderr else {
+            return collected;
+        };
+        let mut stderr = BufReader::new(stderr);
+        let mut line = Vec::new();
+        while let Ok(read) = stderr.read_until(b'\n', &mut line) {
+            if read == 0 {
+                break;
+            }
+            collected.extend_from_slice(&line);
+            forwarder.send(String::from_utf8_lossy(&line).trim_end().to_string());
+            line.clear();
+        }
+        collected
+    });
+
+    let mut output = child.wait_with_output().map_err(run_err)?;
+    output.stderr = reader.join().unwrap_or_default();
+    Ok(output)
+}
+
+fn tmp_path(path: &str) -> String {
+    if path.starts_with("/tmp") {
+        path.to_string()
+    } else {
+        format!("/tmp/{path}")
+    }
+}
+
+/// Whether a path is absolute under /tmp, without `..` to leave it.
+fn is_tmp_path(path: &str) -> bool {
+    let path = Path::new(path);
+    path.starts_with("/tmp")
+        && !path
+            .components()
+            .any(|component| component == std::path::Component::ParentDir)
+}
+
+/// The request's `path` for its `what` placed under /tmp like
+/// [`tmp_path`], refused if it would leave /tmp.
+fn confined_tmp_path(what: &str, path: &str) -> Result<String, McpError> {
+    let path = tmp_path(path);
+    if !is_tmp_path(&path) {
+        return Err(ErrorCode::BadRequestPath.invalid_params(
+            format!("{what} {path} is outside /tmp"),
+            Some(json!({"path": path})),
+        ));
+    }
+    Ok(path)
+}
+
+/// Most IDs of an error that are looked up in the shaders' debug info.
+const MAX_ERROR_IDS: usize = 8;
+
+/// Locates the `%id`s that vkrunner's error output mentions in the source
+/// lines that produced them, through the OpLine debug info of each pass's
+/// module. Modules are `(stage, path, spvasm)`.
+fn error_source_locations(output: &str, modules: &[(String, String, String)]) -> Vec<String> {
+    let mut ids = Vec::new();
+    for (i, _) in output.match_indices('%') {
+        let id = output[i + 1..]
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or_default();
+        if !id.is_empty() && !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+
+    let modules = modules
+        .iter()
+        .map(|(stage, path, spvasm)| {
+            let locations = spirv::Module::parse(spvasm).source_locations();
+            (stage, path, locations)
+        })
+        .collect::<Vec<_>>();
+
+    let mut lines = Vec::new();
+    for id in ids {
+        for (stage, path, locations) in &modules {
+            let Some(location) = locations.get(id) else {
+                continue;
+            };
+            let mut line = format!(
+                "- %{id} in the {stage} ({path}): {}:{}",
+                location.file, location.line
+            );
+            if let Some(text) = &location.text {
+                line.push_str(&format!(": {}", text.trim()));
+            }
+            lines.push(line);
+        }
+        if lines.len() >= MAX_ERROR_IDS {
+            break;
+        }
+    }
+    lines
+}
+
+/// Cuts a generated script after each draw command of its `[test]`
+/// section, leaving out probes so a failing probe can't stop a snapshot.
+fn snapshot_scripts(script: &str) -> Vec<String> {
+    let mut head = String::new();
+    let mut test_lines = Vec::new();
+    let mut in_test = false;
+
+    for line in script.lines() {
+        if line.starts_with('[') {
+            in_test = line.trim_end() == "[test]";
+        }
+
+        if !in_test || line.starts_with('[') {
+            head.push_str(line);
+            head.push('\n');
+            continue;
+        }
+
+        let command = line.trim_start();
+        if command.starts_with("probe ") || command.starts_with("relative probe ") {
+            continue;
+        }
+        test_lines.push(line);
+    }
+
+    test_lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with("draw "))
+        .map(|(end, _)| {
+            let mut script = head.clone();
+            for line in &test_lines[..=end] {
+                script.push_str(line);
+                script.push('\n');
+            }
+            script
+        })
+        .collect()
+}
+
+/// Appends the section `title` listing `lines` to a report, unless there
+/// are none.
+fn push_report_section(report: &mut String, title: &str, lines: &[String]) {
+    if lines.is_empty() {
+        return;
+    }
+    report.push_str(title);
+    report.push_str(":\n");
+    report.push_str(&lines.join("\n"));
+    report.push_str("\n\n");
+}
+
+/// Tolerances tried, in order, when searching for the one a failing
+/// probe passes at.
+const TOLERANCE_LADDER: [f64; 10] = [0.02, 0.03, 0.05, 0.075, 0.1, 0.15, 0.2, 0.3, 0.5, 1.0];
+
+/// Script line numbers of the probes vkrunner reports as failed.
+fn failed_probe_lines(output: &str) -> Vec<usize> {
+    let mut lines = output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once("line ")?;
+            let (number, message) = rest.split_once(": ")?;
+            let is_probe =
+                message.starts_with("Probe color at") || message.starts_with("SSBO probe failed");
+            is_probe.then(|| number.parse().ok()).flatten()
+        })
+        .collect::<Vec<usize>>();
+    lines.dedup();
+    lines
+}
+
+/// Rewrites `script` so the probes on `lines` (1-based) run at
+/// `tolerance`, restoring the script's own tolerance after each. Also
+/// returns the original line number of every new line, 0 for the added
+/// ones.
+fn with_probe_tolerance(script: &str, lines: &[usize], tolerance: f64) -> (String, Vec<usize>) {
+    let mut rewritten = Vec::new();
+    let mut origins = Vec::new();
+    let mut current = "tolerance 0.01".to_string();
+
+    for (line, number) in script.lines().zip(1..) {
+        if line.trim_start().starts_with("tolerance ") {
+            current = line.trim().to_string();
+        }
+
+        if lines.contains(&number) {
+            rewritten.extend([
+                format!("tolerance {tolerance}"),
+                line.to_string(),
+                current.clone(),
+            ]);
+            origins.extend([0, number, 0]);
+        } else {
+            rewritten.push(line.to_string());
+            origins.push(number);
+        }
+    }
+
+    (rewritten.join("\n"), origins)
+}
+
+/// Standard and URL-safe base64 decoders that take input with or without
+/// padding.
+const BASE64_LENIENT: [base64::engine::GeneralPurpose; 2] = {
+    let config = base64::engine::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent);
+    [
+        base64::engine::GeneralPurpose::new(&base64::alphabet::STANDARD, config),
+        base64::engine::GeneralPurpose::new(&base64::alphabet::URL_SAFE, config),
+    ]
+};
+
+/// Decodes base64 from a client, which may be URL-safe, unpadded or
+/// wrapped over several lines.
+fn base64_decode(encoded: &str) -> Result<Vec<u8>, String> {
+    let symbols = encoded
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    let [standard, url_safe] = &BASE64_LENIENT;
+    let engine = if symbols.iter().any(|c| matches!(c, b'-' | b'_')) {
+        url_safe
+    } else {
+        standard
+    };
+    engine.decode(symbols).map_err(|e| e.to_string())
+}
+
+/// Embeds an image as a PNG resource that points at its full-resolution
+/// file, downscaled first so that neither side exceeds `max_dim`.
+fn image_resource(img: &RgbImage, path: &str, max_dim: Option<u32>) -> Result<Content, ImageError> {
+    let scaled;
+    let img = match max_dim.filter(|&max_dim| img.width().max(img.height()) > max_dim) {
+        Some(max_dim) => {
+            let scale = f64::from(max_dim) / f64::from(img.width().max(img.height()));
+            let scaled_size = |size: u32| ((f64::from(size) * scale).round() as u32).max(1);
+            scaled = image::imageops::resize(
+                img,
+                scaled_size(img.width()),
+                scaled_size(img.height()),
+                image::imageops::FilterType::Triangle,
+            );
+            &scaled
+        }
+        None => img,
+    };
+
+    let mut png = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+
+    Ok(Content::resource(ResourceContents::BlobResourceContents {
+        uri: format!("file://{path}"),
+        mime_type: Some("image/png".to_string()),
+        blob: BASE64_STANDARD.encode(&png),
+    }))
+}
+
+/// Where artifacts are stored when the configuration doesn't say.
+const DEFAULT_ARTIFACT_DIR: &str = "/tmp/artifacts";
+
+/// The configured artifact directory, set once at startup.
+static ARTIFACT_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+/// Directory of the spv-... and tex-... artifacts.
+fn artifact_dir() -> &'static Path {
+    ARTIFACT_DIR
+        .get()
+        .map_or(Path::new(DEFAULT_ARTIFACT_DIR), PathBuf::as_path)
+}
+
+/// Hex SHA-256 digest, reported so clients can cite exactly what ran
+/// and naming content-addressed artifacts and sources.
+fn sha256_hex(contents: impl AsRef<[u8]>) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Opaque ID of a server-named artifact: a hash of its contents, so
+/// identical compilations share one file.
+fn artifact_id(contents: &str) -> String {
+    format!("spv-{}", sha256_hex(contents))
+}
+
+/// Fragment shader that diagnose_black_output swaps in: solid magenta,
+/// which a correct render is unlikely to produce.
+const SOLID_FRAGMENT_SHADER: &str = "#version 450
+layout(location = 0) out vec4 color;
+void main()
+{
+    color = vec4(1.0, 0.0, 1.0, 1.0);
+}
+";
+
+/// Vertex shader that diagnose_black_output swaps in to draw vkrunner's
+/// rectangle as given.
+const PASSTHROUGH_VERTEX_SHADER: &str = "#version 450
+layout(location = 0) in vec4 position;
+void main()
+{
+    gl_Position = position;
+}
+";
+
+/// Built-in fragment shaders that `debug_views` renders the geometry with.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub enum DebugView {
+    #[schemars(
+        description = "The vec2 the vertex stage outputs at location 0 (usually the UVs) as red and green ramps, repeating outside 0..1"
+    )]
+    Uv,
+    #[schemars(
+        description = "Face orientation from the screen-space slopes of each primitive as normal * 0.5 + 0.5, back faces at half brightness"
+    )]
+    FaceNormals,
+    #[schemars(description = "Fragment depth as gray, black at 0 and white at 1")]
+    Depth,
+    #[schemars(
+        description = "Primitive edges in magenta, drawn with polygonMode LINE (needs fillModeNonSolid)"
+    )]
+    Wireframe,
+}
+
+impl DebugView {
+    /// Suffix of the view's image file.
+    fn name(self) -> &'static str {
+        match self {
+            DebugView::Uv => "uv",
+            DebugView::FaceNormals => "normals",
+            DebugView::Depth => "depth",
+            DebugView::Wireframe => "wireframe",
+        }
+    }
+
+    fn fragment_shader(self) -> &'static str {
+        match self {
+            DebugView::Uv => {
+                "#version 450
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+void main()
+{
+    color = vec4(fract(uv), 0.0, 1.0);
+}
+"
+            }
+            // Depth is scaled to about the default 250 pixel window so
+            // that its slope is comparable with the screen-space ones
+            DebugView::FaceNormals => {
+                "#version 450
+layout(location = 0) out vec4 color;
+void main()
+{
+    vec3 position = vec3(gl_FragCoord.xy, gl_FragCoord.z * 250.0);
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+    color = vec4((normal * 0.5 + 0.5) * (gl_FrontFacing ? 1.0 : 0.5), 1.0);
+}
+"
+            }
+            DebugView::Depth => {
+                "#version 450
+layout(location = 0) out vec4 color;
+void main()
+{
+    color = vec4(vec3(gl_FragCoord.z), 1.0);
+}
+"
+            }
+            DebugView::Wireframe => SOLID_FRAGMENT_SHADER,
+        }
+    }
+}
+
+/// A step of diagnose_black_output: what it changes, and what pixels
+/// first appearing after it say about the cause.
+struct BlackOutputStep {
+    change: &'static str,
+    cause: &'static str,
+    apply: fn(&mut CompileRunShadersRequest) -> Result<(), McpError>,
+}
+
+/// The standard checklist for a blank render. Each step keeps the
+/// changes of the ones before it.
+const BLACK_OUTPUT_STEPS: &[BlackOutputStep] = &[
+    BlackOutputStep {
+        change: "culling disabled",
+        cause: "Back-face culling discarded the primitives: their winding is the opposite of the front face. Reverse the vertex order, or change FrontFace or CullMode.",
+        apply: |request| {
+            request.set_state(ShaderRunnerTest::CullMode {
+                mode: "VK_CULL_MODE_NONE".to_string(),
+            });
+            Ok(())
+        },
+    },
+    BlackOutputStep {
+        change: "depth test off",
+        cause: "The depth test rejected the fragments. Clear the depth buffer to 1.0 for a LESS comparison, and check the depth the vertex stage produces (z outside 0..w is clipped).",
+        apply: |request| {
+            request.set_state(ShaderRunnerTest::DepthTestEnable { enable: false });
+            Ok(())
+        },
+    },
+    BlackOutputStep {
+        change: "solid magenta fragment shader",
+        cause: "The geometry is rasterized but the fragment shader writes black. Check its inputs, uniforms and textures; evaluate_constant_expressions shows outputs that are constant by construction.",
+        apply: |request| request.replace_shader(ShaderStage::Frag, SOLID_FRAGMENT_SHADER),
+    },
+    BlackOutputStep {
+        change: "full-screen rectangle through a pass-through vertex shader",
+        cause: "The vertex stage places the geometry outside the view. Check the vertex positions, the transforms and that gl_Position.w is positive.",
+        apply: |request| {
+            request.replace_shader(ShaderStage::Vert, PASSTHROUGH_VERTEX_SHADER)?;
+            let first_draw = request.tests.iter().position(ShaderRunnerTest::is_draw);
+            request.tests.retain(|test| !test.is_draw());
+            request.tests.insert(
+                first_draw.unwrap_or(request.tests.len()),
+                ShaderRunnerTest::DrawRect {
+                    x: -1.0,
+                    y: -1.0,
+                    width: 2.0,
+                    height: 2.0,
+                },
+            );
+            Ok(())
+        },
+    },
+];
+
+/// A known-good compile_run_shaders request that instantiate_template
+/// hands out as a starting point.
+struct RequestTemplate {
+    name: &'static str,
+    description: &'static str,
+    request: fn() -> serde_json::Value,
+}
+
+const REQUEST_TEMPLATES: &[RequestTemplate] = &[
+    RequestTemplate {
+        name: "fullscreen-gradient",
+        description: "Full-screen rectangle through the pass-through vertex shader with a fragment shader that writes the pixel position as red and green; probes three pixels",
+        request: || {
+            json!({
+                "requests": [{
+                    "stage": "Frag",
+                    "source": "#version 450
+layout(location = 0) out vec4 color;
+void main()
+{
+    // 250x250 is vkrunner's default framebuffer size
+    color = vec4(gl_FragCoord.xy / 250.0, 0.0, 1.0);
+}
+",
+                }],
+                "passes": ["VertPassthrough", {"FragSpirv": {"frag_spvasm_path": "request:0"}}],
+                "tests": [
+                    {"DrawRect": {"x": -1.0, "y": -1.0, "width": 2.0, "height": 2.0}},
+                    {"Probe": {"probe_type": "rect", "format": "rgb", "args": ["(0, 0, 1, 1)", "(0.002, 0.002, 0)"]}},
+                    {"Probe": {"probe_type": "rect", "format": "rgb", "args": ["(249, 0, 1, 1)", "(0.998, 0.002, 0)"]}},
+                    {"Probe": {"probe_type": "rect", "format": "rgb", "args": ["(125, 125, 1, 1)", "(0.502, 0.502, 0)"]}},
+                ],
+                "output_path": "/tmp/templates/fullscreen_gradient.png",
+            })
+        },
+    },
+    RequestTemplate {
+        name: "ssbo-saxpy",
+        description: "Compute shader computing y = a * x + y over two 8-float SSBOs with a from a push constant; verifies every element of y",
+        request: || {
+            json!({
+                "requests": [{
+                    "stage": "Comp",
+                    "source": "#version 450
+layout(local_size_x = 8) in;
+layout(push_constant) uniform Parameters { float a; };
+layout(std430, binding = 0) readonly buffer X { float x[]; };
+layout(std430, binding = 1) buffer Y { float y[]; };
+void main()
+{
+    uint i = gl_GlobalInvocationID.x;
+    y[i] = a * x[i] + y[i];
+}
+",
+                }],
+                "passes": [{"CompSpirv": {"comp_spvasm_path": "request:0"}}],
+                "tests": [
+                    {"SSBOSubData": {"binding": 0, "data_type": "float", "offset": 0, "values": ["1 2 3 4 5 6 7 8"]}},
+                    {"SSBOSubData": {"binding": 1, "data_type": "float", "offset": 0, "values": ["10 20 30 40 50 60 70 80"]}},
+                    {"Push": {"data_type": "float", "offset": 0, "values": ["2"]}},
+                    {"Compute": {"x": 1, "y": 1, "z": 1}},
+                    {"ProbeSsbo": {"binding": 1, "data_type": "float", "offset": 0, "comparison": "==", "values": ["12 24 36 48 60 72 84 96"]}},
+                ],
+            })
+        },
+    },
+    RequestTemplate {
+        name: "depth-test-sandwich",
+        description: "Three rectangles at different depths with a LESS depth test: red at 0.5 fills the screen, green at 0.25 covers the center and blue at 0.75, drawn last over the left half, stays hidden; probes each region",
+        request: || {
+            json!({
+                "requests": [
+                    {
+                        "stage": "Vert",
+                        "source": "#version 450
+layout(location = 0) in vec4 position;
+layout(push_constant) uniform Layer { vec4 color; float depth; };
+void main()
+{
+    gl_Position = vec4(position.xy, depth, 1.0);
+}
+",
+                    },
+                    {
+                        "stage": "Frag",
+                        "source": "#version 450
+layout(push_constant) uniform Layer { vec4 color; float depth; };
+layout(location = 0) out vec4 out_color;
+void main()
+{
+    out_color = color;
+}
+",
+                    },
+                ],
+                "passes": [
+                    {"VertSpirv": {"vert_spvasm_path": "request:0"}},
+                    {"FragSpirv": {"frag_spvasm_path": "request:1"}},
+                ],
+                "tests": [
+                    {"DepthTestEnable": {"enable": true}},
+                    {"DepthWriteEnable": {"enable": true}},
+                    {"DepthCompareOp": {"op": "VK_COMPARE_OP_LESS"}},
+                    "Clear",
+                    {"Push": {"data_type": "vec4", "offset": 0, "values": ["1 0 0 1"]}},
+                    {"Push": {"data_type": "float", "offset": 16, "values": ["0.5"]}},
+                    {"DrawRect": {"x": -1.0, "y": -1.0, "width": 2.0, "height": 2.0}},
+                    {"Push": {"data_type": "vec4", "offset": 0, "values": ["0 1 0 1"]}},
+                    {"Push": {"data_type": "float", "offset": 16, "values": ["0.25"]}},
+                    {"DrawRect": {"x": -0.5, "y": -0.5, "width": 1.0, "height": 1.0}},
+                    {"Push": {"data_type": "vec4", "offset": 0, "values": ["0 0 1 1"]}},
+                    {"Push": {"data_type": "float", "offset": 16, "values": ["0.75"]}},
+                    {"DrawRect": {"x": -1.0, "y": -1.0, "width": 1.0, "height": 2.0}},
+                    {"Probe": {"probe_type": "rect", "format": "rgb", "args": ["(120, 120, 10, 10)", "(0, 1, 0)"]}},
+                    {"Probe": {"probe_type": "rect", "format": "rgb", "args": ["(10, 120, 10, 10)", "(1, 0, 0)"]}},
+                    {"Probe": {"probe_type": "rect", "format": "rgb", "args": ["(230, 120, 10, 10)", "(1, 0, 0)"]}},
+                ],
+                "output_path": "/tmp/templates/depth_test_sandwich.png",
+            })
+        },
+    },
+];
+
+/// The color of a pixel as `#rrggbb`.
+fn hex_color(pixel: image::Rgb<u8>) -> String {
+    let [r, g, b] = pixel.0;
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Number of pixels that differ from `background`, and the colors at the
+/// corners of normalized device coordinates and at the center.
+fn describe_drawn_pixels(image: &RgbImage, background: image::Rgb<u8>) -> String {
+    let drawn = image.pixels().filter(|pixel| **pixel != background).count();
+    let (right, bottom) = (image.width() - 1, image.height() - 1);
+    let samples = [
+        ("(-1, -1)", 0, 0),
+        ("(1, -1)", right, 0),
+        ("(-1, 1)", 0, bottom),
+        ("(1, 1)", right, bottom),
+        ("center", right / 2, bottom / 2),
+    ]
+    .map(|(name, x, y)| format!("{name} {}", hex_color(*image.get_pixel(x, y))))
+    .join(", ");
+
+    format!(
+        "{drawn} of {} pixels drawn; {samples}",
+        image.width() * image.height()
+    )
+}
+
+/// Names a scene's copy of an output file `<stem>_<scene>.<ext>`.
+fn scene_path(path: &str, scene: &str) -> String {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    path.with_file_name(format!("{stem}_{scene}{extension}"))
+        .display()
+        .to_string()
+}
+
+fn artifact_path(id: &str) -> String {
+    artifact_dir()
+        .join(format!("{id}.spvasm"))
+        .display()
+        .to_string()
+}
+
+/// Uploaded textures are stored as PNG so the artifact can be inspected.
+fn texture_artifact_path(id: &str) -> String {
+    artifact_dir()
+        .join(format!("{id}.png"))
+        .display()
+        .to_string()
+}
+
+/// Loads a texture stored by `upload_texture`.
+fn uploaded_texture(id: &str) -> Result<image::RgbaImage, McpError> {
+    let unknown = |error: String| {
+        ErrorCode::UnknownReference.invalid_params(
+            format!("Unknown texture ID {id}; upload it with upload_texture first"),
+            Some(json!({"error": error})),
+        )
+    };
+
+    if !id.starts_with("tex-") || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(unknown("not a tex-... ID".to_string()));
+    }
+
+    image::open(texture_artifact_path(id))
+        .map(|img| img.to_rgba8())
+        .map_err(|e| unknown(e.to_string()))
+}
+
+/// Writes `path` through a temporary file and a rename, so a server
+/// stopped mid-write never leaves a truncated artifact behind.
+fn write_atomically(path: &str, contents: &[u8]) -> std::io::Result<()> {
+    let tmp = format!("{path}.{}.tmp", std::process::id());
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+fn write_spvasm(path: &str, spvasm: &str) -> Result<(), McpError> {
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            McpError::internal_error(
+                "Failed to create temporary output directory",
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+    }
+
+    write_atomically(path, spvasm.as_bytes()).map_err(|e| {
+        McpError::internal_error(
+            "Failed to write compiled shader to file",
+            Some(json!({"error": e.to_string()})),
+        )
+    })
+}
+
+/// Applies a unified diff to `base`. Hunks must match exactly (no fuzz)
+/// and appear in order; file headers and other lines between hunks are
+/// ignored. The result ends with a newline like `base` unless a hunk
+/// reaching the end says otherwise with "\ No newline at end of file".
+fn apply_patch(base: &str, patch: &str) -> Result<String, String> {
+    let base_lines = base.lines().collect::<Vec<_>>();
+    let mut result = Vec::new();
+    let mut next = 0;
+    let mut hunks = 0;
+    let mut final_newline = base.ends_with('\n');
+    let mut lines = patch.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let Some(header) = line.strip_prefix("@@ -") else {
+            continue;
+        };
+        hunks += 1;
+
+        let range = |range: Option<&str>| -> Option<(usize, usize)> {
+            let mut parts = range?.splitn(2, ',');
+            let start = parts.next()?.parse().ok()?;
+            let count = parts.next().map_or(Some(1), |count| count.parse().ok())?;
+            Some((start, count))
+        };
+        let mut ranges = header.split_whitespace();
+        let old = range(ranges.next());
+        let new = range(ranges.next().and_then(|range| range.strip_prefix('+')));
+        let (Some((old_start, mut old_count)), Some((_, mut new_count))) = (old, new) else {
+            return Err(format!("Malformed hunk header: {line}"));
+        };
+
+        // An empty old range names the line the hunk is inserted after
+        let start = if old_count == 0 {
+            old_start
+        } else {
+            old_start.saturating_sub(1)
+        };
+        if start < next || start > base_lines.len() {
+            return Err(format!(
+                "Hunk {hunks} starts at line {old_start}, which is outside the base source or before the previous hunk"
+            ));
+        }
+        result.extend_from_slice(&base_lines[next..start]);
+        next = start;
+
+        // Whether the new side's last line lacks a newline: the marker
+        // follows a line of the new side, not a removed one
+        let mut previous = " ";
+        let mut new_unterminated = false;
+        while old_count > 0 || new_count > 0 || lines.peek().is_some_and(|l| l.starts_with('\\')) {
+            let Some(line) = lines.next() else {
+                return Err(format!("Hunk {hunks} is truncated"));
+            };
+            if line.starts_with('\\') {
+                new_unterminated |= previous != "-";
+                continue;
+            }
+
+            // Some tools strip the space from empty context lines
+            let (kind, text) = line.split_at_checked(1).unwrap_or((" ", ""));
+            match kind {
+                "+" if new_count > 0 => {
+                    result.push(text);
+                    new_count -= 1;
+                }
+                " " | "-" if old_count > 0 => {
+                    if base_lines.get(next) != Some(&text) {
+                        return Err(format!(
+                            "Hunk {hunks} does not apply: line {} of the base source is {:?}, not {text:?}",
+                            next + 1,
+                            base_lines.get(next).copied().unwrap_or_default()
+                        ));
+                    }
+                    next += 1;
+                    old_count -= 1;
+                    if kind == " " {
+                        result.push(text);
+                        new_count = new_count.saturating_sub(1);
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "Hunk {hunks} has more lines than its header declares: {line}"
+                    ));
+                }
+            }
+            previous = kind;
+        }
+        if next == base_lines.len() {
+            final_newline = !new_unterminated;
+        }
+    }
+
+    if hunks == 0 {
+        return Err("The patch contains no hunks".to_string());
+    }
+
+    result.extend_from_slice(&base_lines[next..]);
+    let mut patched = result.join("\n");
+    if final_newline && !result.is_empty() {
+        patched.push('\n');
+    }
+    Ok(patched)
+}
+
+fn check_entrypoint(spvasm: &str, name: &str, path: &str) -> Result<(), McpError> {
+    let entry_points = spirv::Module::parse(spvasm).entry_points();
+
+    if entry_points
+        .iter()
+        .any(|entry_point| entry_point.name == name)
+    {
+        return Ok(());
+    }
+
+    Err(ErrorCode::UnknownReference.invalid_params(
+        format!("Entry point {name} not found in {path}"),
+        Some(json!({"available": entry_points})),
+    ))
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub enum ShaderStage {
     #[schemars(description = "Vertex processing stage (transforms vertices)")]
     Vert,
@@ -40,7 +1155,44 @@ pub enum ShaderStage {
     Comp,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+impl ShaderStage {
+    fn shader_kind(&self) -> ShaderKind {
+        match self {
+            ShaderStage::Vert => ShaderKind::Vertex,
+            ShaderStage::Frag => ShaderKind::Fragment,
+            ShaderStage::Tesc => ShaderKind::TessControl,
+            ShaderStage::Tese => ShaderKind::TessEvaluation,
+            ShaderStage::Geom => ShaderKind::Geometry,
+            ShaderStage::Comp => ShaderKind::Compute,
+        }
+    }
+
+    fn display_name(&self) -> &'static str {
+        match self {
+            ShaderStage::Vert => "Vertex",
+            ShaderStage::Frag => "Fragment",
+            ShaderStage::Tesc => "Tessellation Control",
+            ShaderStage::Tese => "Tessellation Evaluation",
+            ShaderStage::Geom => "Geometry",
+            ShaderStage::Comp => "Compute",
+        }
+    }
+
+    /// The stage as vkrunner scripts name it in section headers and
+    /// entrypoint commands.
+    fn script_name(&self) -> &'static str {
+        match self {
+            ShaderStage::Vert => "vertex",
+            ShaderStage::Frag => "fragment",
+            ShaderStage::Tesc => "tessellation control",
+            ShaderStage::Tese => "tessellation evaluation",
+            ShaderStage::Geom => "geometry",
+            ShaderStage::Comp => "compute",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub enum ShaderRunnerRequire {
     #[schemars(
         description = "Enables cooperative matrix operations with specified dimensions and data type"
@@ -49,6 +1201,14 @@ pub enum ShaderRunnerRequire {
         m: u32,
         n: u32,
         component_type: String,
+        #[schemars(description = "K size the configuration must have (default: any)")]
+        k: Option<u32>,
+        #[schemars(
+            description = "Component type of the A and B matrices, e.g. float16_t or int8_t (default: any)"
+        )]
+        ab_type: Option<String>,
+        #[schemars(description = "Component type of the result (default: any)")]
+        result_type: Option<String>,
     },
 
     #[schemars(
@@ -79,45 +1239,329 @@ pub enum ShaderRunnerRequire {
 
     #[schemars(description = "Enables shaders to use raw buffer addresses")]
     BufferDeviceAddress,
-}
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
-pub enum ShaderRunnerPass {
     #[schemars(
-        description = "Use a built-in pass-through vertex shader (forwards attributes to fragment shader)"
+        description = "Enables loads from storage images declared without a format qualifier"
     )]
-    VertPassthrough,
+    ShaderStorageImageReadWithoutFormat,
 
-    #[schemars(description = "Use compiled vertex shader from specified SPIR-V assembly file")]
-    VertSpirv {
-        #[schemars(
-            description = "Path to the compiled vertex shader SPIR-V assembly (.spvasm) file"
-        )]
-        vert_spvasm_path: String,
-    },
+    #[schemars(
+        description = "Enables stores to storage images declared without a format qualifier"
+    )]
+    ShaderStorageImageWriteWithoutFormat,
 
-    #[schemars(description = "Use compiled fragment shader from specified SPIR-V assembly file")]
-    FragSpirv {
-        #[schemars(
-            description = "Path to the compiled fragment shader SPIR-V assembly (.spvasm) file"
-        )]
-        frag_spvasm_path: String,
-    },
+    #[schemars(
+        description = "Enables reading the subgroup clock in shaders (clockARB, VK_KHR_shader_clock)"
+    )]
+    ShaderSubgroupClock,
+
+    #[schemars(
+        description = "Enables reading the device clock in shaders (clockRealtimeEXT, VK_KHR_shader_clock)"
+    )]
+    ShaderDeviceClock,
+}
+
+/// Explains why the feature of a `[require]` line is known to be
+/// unavailable on portability implementations such as MoltenVK.
+fn portability_gap(require_line: &str) -> Option<&'static str> {
+    match require_line {
+        "geometryShader" => Some("geometryShader: Metal has no geometry shader stage"),
+        "wideLines" => Some("wideLines: Metal only rasterizes lines 1 pixel wide"),
+        "logicOp" => Some("logicOp: Metal has no framebuffer logic operations"),
+        "shaderFloat64" => Some("shaderFloat64: Metal has no 64-bit float support"),
+        _ => None,
+    }
+}
+
+impl ShaderRunnerRequire {
+    /// The line this requirement adds to the `[require]` section.
+    fn require_line(&self) -> String {
+        match self {
+            Self::CooperativeMatrix {
+                m,
+                n,
+                component_type,
+                k,
+                ab_type,
+                result_type,
+            } => {
+                let mut line = format!("cooperative_matrix m={m} n={n} c={component_type}");
+                if let Some(k) = k {
+                    line.push_str(&format!(" k={k}"));
+                }
+                if let Some(ab_type) = ab_type {
+                    line.push_str(&format!(" a={ab_type} b={ab_type}"));
+                }
+                if let Some(result_type) = result_type {
+                    line.push_str(&format!(" result={result_type}"));
+                }
+                line
+            }
+            Self::DepthStencil(format) => format!("depthstencil {format}"),
+            Self::Framebuffer(format) => format!("framebuffer {format}"),
+            Self::ShaderFloat64 => "shaderFloat64".to_string(),
+            Self::GeometryShader => "geometryShader".to_string(),
+            Self::WideLines => "wideLines".to_string(),
+            Self::LogicOp => "logicOp".to_string(),
+            Self::SubgroupSize(size) => format!("subgroup_size {size}"),
+            Self::FragmentStoresAndAtomics => "fragmentStoresAndAtomics".to_string(),
+            Self::BufferDeviceAddress => "bufferDeviceAddress".to_string(),
+            Self::ShaderStorageImageReadWithoutFormat => {
+                "shaderStorageImageReadWithoutFormat".to_string()
+            }
+            Self::ShaderStorageImageWriteWithoutFormat => {
+                "shaderStorageImageWriteWithoutFormat".to_string()
+            }
+            Self::ShaderSubgroupClock => "shaderSubgroupClock".to_string(),
+            Self::ShaderDeviceClock => "shaderDeviceClock".to_string(),
+        }
+    }
+}
+
+/// Depth formats for depth testing, best first. Vulkan guarantees
+/// D16_UNORM and one of D32_SFLOAT and a 24-bit depth format.
+const DEPTH_FORMATS: &[&str] = &["D32_SFLOAT", "D24_UNORM_S8_UINT", "D16_UNORM"];
+
+/// Depth/stencil formats for stencil testing, best first. Vulkan
+/// guarantees one of the two.
+const STENCIL_FORMATS: &[&str] = &["D32_SFLOAT_S8_UINT", "D24_UNORM_S8_UINT"];
+
+/// The device feature (as named in a `[require]` section) that a SPIR-V
+/// `OpCapability` needs, for capabilities that core Vulkan does not
+/// always support.
+fn capability_feature(capability: &str) -> Option<&'static str> {
+    Some(match capability {
+        "Float64" => "shaderFloat64",
+        "Int64" => "shaderInt64",
+        "Int16" => "shaderInt16",
+        "Float16" => "shaderFloat16",
+        "Int8" => "shaderInt8",
+        "Int64Atomics" => "shaderBufferInt64Atomics",
+        "StorageBuffer16BitAccess" => "storageBuffer16BitAccess",
+        "UniformAndStorageBuffer16BitAccess" => "uniformAndStorageBuffer16BitAccess",
+        "StoragePushConstant16" => "storagePushConstant16",
+        "StorageInputOutput16" => "storageInputOutput16",
+        "StorageBuffer8BitAccess" => "storageBuffer8BitAccess",
+        "UniformAndStorageBuffer8BitAccess" => "uniformAndStorageBuffer8BitAccess",
+        "StoragePushConstant8" => "storagePushConstant8",
+        "Geometry" => "geometryShader",
+        "Tessellation" => "tessellationShader",
+        "GeometryPointSize" | "TessellationPointSize" => "shaderTessellationAndGeometryPointSize",
+        "ImageGatherExtended" => "shaderImageGatherExtended",
+        "StorageImageExtendedFormats" => "shaderStorageImageExtendedFormats",
+        "StorageImageMultisample" => "shaderStorageImageMultisample",
+        "StorageImageReadWithoutFormat" => "shaderStorageImageReadWithoutFormat",
+        "StorageImageWriteWithoutFormat" => "shaderStorageImageWriteWithoutFormat",
+        "ClipDistance" => "shaderClipDistance",
+        "CullDistance" => "shaderCullDistance",
+        "ImageCubeArray" | "SampledCubeArray" => "imageCubeArray",
+        "SampleRateShading" => "sampleRateShading",
+        "MultiViewport" => "multiViewport",
+        "MultiView" => "multiview",
+        "VariablePointersStorageBuffer" => "variablePointersStorageBuffer",
+        "VariablePointers" => "variablePointers",
+        "PhysicalStorageBufferAddresses" => "bufferDeviceAddress",
+        "VulkanMemoryModel" => "vulkanMemoryModel",
+        "ShaderClockKHR" => "shaderSubgroupClock",
+        _ => return None,
+    })
+}
+
+/// Device features vkrunner enables through an extension, for tracing a
+/// missing extension back to the features that pulled it in.
+fn extension_features(extension: &str) -> &'static [&'static str] {
+    match extension {
+        "VK_KHR_16bit_storage" => &[
+            "storageBuffer16BitAccess",
+            "uniformAndStorageBuffer16BitAccess",
+            "storagePushConstant16",
+            "storageInputOutput16",
+        ],
+        "VK_KHR_8bit_storage" => &[
+            "storageBuffer8BitAccess",
+            "uniformAndStorageBuffer8BitAccess",
+            "storagePushConstant8",
+        ],
+        "VK_KHR_shader_float16_int8" => &["shaderFloat16", "shaderInt8"],
+        "VK_KHR_shader_atomic_int64" => &["shaderBufferInt64Atomics"],
+        "VK_KHR_multiview" => &["multiview"],
+        "VK_KHR_variable_pointers" => &["variablePointersStorageBuffer", "variablePointers"],
+        "VK_KHR_buffer_device_address" => &["bufferDeviceAddress"],
+        "VK_KHR_vulkan_memory_model" => &["vulkanMemoryModel"],
+        "VK_KHR_shader_clock" => &["shaderSubgroupClock", "shaderDeviceClock"],
+        _ => &[],
+    }
+}
+
+/// How a shader can avoid needing a device feature.
+fn feature_alternative(feature: &str) -> Option<&'static str> {
+    Some(match feature {
+        "shaderFloat64" => {
+            "emulate doubles with pairs of floats (double-float arithmetic), or use float where its precision suffices"
+        }
+        "shaderInt64" => {
+            "use uvec2 pairs with uaddCarry/usubBorrow/umulExtended for 64-bit integer math"
+        }
+        "shaderBufferInt64Atomics" => {
+            "use 32-bit atomics, e.g. on the low and high halves separately"
+        }
+        "shaderFloat16" | "shaderInt16" | "shaderInt8" => {
+            "compute in 32-bit types and pack results with packHalf2x16 or bitfieldInsert"
+        }
+        "storageBuffer16BitAccess"
+        | "uniformAndStorageBuffer16BitAccess"
+        | "storagePushConstant16"
+        | "storageInputOutput16" => {
+            "store 16-bit values packed in 32-bit uints and unpack them with unpackHalf2x16 or bitfieldExtract"
+        }
+        "storageBuffer8BitAccess"
+        | "uniformAndStorageBuffer8BitAccess"
+        | "storagePushConstant8" => {
+            "store bytes packed in 32-bit uints and unpack them with unpackUnorm4x8 or bitfieldExtract"
+        }
+        "geometryShader" => {
+            "expand the primitives in the vertex data, or generate them with a compute pass"
+        }
+        "tessellationShader" => {
+            "pre-tessellate the geometry in the vertex data or with a compute pass"
+        }
+        "shaderStorageImageReadWithoutFormat" | "shaderStorageImageWriteWithoutFormat" => {
+            "declare the storage image with an explicit format qualifier such as rgba8"
+        }
+        "shaderClipDistance" | "shaderCullDistance" => {
+            "discard the clipped fragments in the fragment shader instead"
+        }
+        "imageCubeArray" => "use a 2D array texture with six layers per cube",
+        "bufferDeviceAddress" => "bind the buffers as descriptors instead of passing addresses",
+        "wideLines" => "draw wide lines as thin quads",
+        "shaderSubgroupClock" | "shaderDeviceClock" => {
+            "time the whole run from the Timings section of the result instead of reading clocks in the shader"
+        }
+        _ => return None,
+    })
+}
+
+/// A capability a compiled pass declares and the device feature it
+/// needs, if any.
+#[derive(Debug)]
+struct CapabilityDemand {
+    capability: String,
+    feature: Option<&'static str>,
+    reference: String,
+}
+
+/// Explains vkrunner's missing feature/extension messages in terms of the
+/// shader capabilities that demanded them, with ways around them.
+fn explain_missing_features(output: &str, demands: &[CapabilityDemand]) -> Vec<String> {
+    let mut explanations = Vec::new();
+
+    for line in output.lines() {
+        let (missing, features) =
+            if let Some(extension) = line.split("Missing required extension: ").nth(1) {
+                let extension = extension.trim();
+                (
+                    format!("extension {extension}"),
+                    extension_features(extension).to_vec(),
+                )
+            } else if let Some(rest) = line.split("Missing required feature").nth(1) {
+                // Either "feature: name" or "feature “name” from extension “ext”"
+                let name = rest
+                    .trim_start_matches([':', ' ', '“'])
+                    .split(['”', ' '])
+                    .next()
+                    .unwrap_or_default();
+                (format!("feature {name}"), vec![name])
+            } else {
+                continue;
+            };
+
+        let mut explanation = format!("- The device lacks {missing}.");
+        let causes = demands
+            .iter()
+            .filter(|demand| {
+                demand
+                    .feature
+                    .is_some_and(|feature| features.contains(&feature))
+            })
+            .map(|demand| format!("OpCapability {} in {}", demand.capability, demand.reference))
+            .collect::<Vec<_>>();
+        if causes.is_empty() {
+            explanation.push_str(
+                " No compiled SPIR-V pass declares a capability that needs it, so it comes from the requirements or a GLSL pass.",
+            );
+        } else {
+            explanation.push_str(&format!(" It is needed by {}.", causes.join(", ")));
+        }
+        let mut alternatives = features
+            .iter()
+            .filter_map(|feature| feature_alternative(feature))
+            .collect::<Vec<_>>();
+        alternatives.dedup();
+        for alternative in alternatives {
+            explanation.push_str(&format!(" Alternative: {alternative}."));
+        }
+
+        if !explanations.contains(&explanation) {
+            explanations.push(explanation);
+        }
+    }
+
+    explanations
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum ShaderRunnerPass {
+    #[schemars(
+        description = "Use a built-in pass-through vertex shader (forwards attributes to fragment shader)"
+    )]
+    VertPassthrough,
+
+    #[schemars(description = "Use compiled vertex shader from specified SPIR-V assembly file")]
+    VertSpirv {
+        #[schemars(
+            description = "Path to the compiled vertex shader SPIR-V assembly (.spvasm) file, a request:<index> reference to a compile request of this call, an spv-... artifact ID, or lib:<name> for a register_shader shader"
+        )]
+        vert_spvasm_path: String,
+        #[schemars(
+            description = "Optional entry point to run when the module has several (see list_entrypoints); default is main"
+        )]
+        entrypoint: Option<String>,
+    },
+
+    #[schemars(description = "Use compiled fragment shader from specified SPIR-V assembly file")]
+    FragSpirv {
+        #[schemars(
+            description = "Path to the compiled fragment shader SPIR-V assembly (.spvasm) file, a request:<index> reference to a compile request of this call, an spv-... artifact ID, or lib:<name> for a register_shader shader"
+        )]
+        frag_spvasm_path: String,
+        #[schemars(
+            description = "Optional entry point to run when the module has several (see list_entrypoints); default is main"
+        )]
+        entrypoint: Option<String>,
+    },
 
     #[schemars(description = "Use compiled compute shader from specified SPIR-V assembly file")]
     CompSpirv {
         #[schemars(
-            description = "Path to the compiled compute shader SPIR-V assembly (.spvasm) file"
+            description = "Path to the compiled compute shader SPIR-V assembly (.spvasm) file, a request:<index> reference to a compile request of this call, an spv-... artifact ID, or lib:<name> for a register_shader shader"
         )]
         comp_spvasm_path: String,
+        #[schemars(
+            description = "Optional entry point to run when the module has several (see list_entrypoints); default is main"
+        )]
+        entrypoint: Option<String>,
     },
 
     #[schemars(description = "Use compiled geometry shader from specified SPIR-V assembly file")]
     GeomSpirv {
         #[schemars(
-            description = "Path to the compiled geometry shader SPIR-V assembly (.spvasm) file"
+            description = "Path to the compiled geometry shader SPIR-V assembly (.spvasm) file, a request:<index> reference to a compile request of this call, an spv-... artifact ID, or lib:<name> for a register_shader shader"
         )]
         geom_spvasm_path: String,
+        #[schemars(
+            description = "Optional entry point to run when the module has several (see list_entrypoints); default is main"
+        )]
+        entrypoint: Option<String>,
     },
 
     #[schemars(
@@ -125,9 +1569,13 @@ pub enum ShaderRunnerPass {
     )]
     TescSpirv {
         #[schemars(
-            description = "Path to the compiled tessellation control shader SPIR-V assembly (.spvasm) file"
+            description = "Path to the compiled tessellation control shader SPIR-V assembly (.spvasm) file, a request:<index> reference to a compile request of this call, an spv-... artifact ID, or lib:<name> for a register_shader shader"
         )]
         tesc_spvasm_path: String,
+        #[schemars(
+            description = "Optional entry point to run when the module has several (see list_entrypoints); default is main"
+        )]
+        entrypoint: Option<String>,
     },
 
     #[schemars(
@@ -135,12 +1583,168 @@ pub enum ShaderRunnerPass {
     )]
     TeseSpirv {
         #[schemars(
-            description = "Path to the compiled tessellation evaluation shader SPIR-V assembly (.spvasm) file"
+            description = "Path to the compiled tessellation evaluation shader SPIR-V assembly (.spvasm) file, a request:<index> reference to a compile request of this call, an spv-... artifact ID, or lib:<name> for a register_shader shader"
         )]
         tese_spvasm_path: String,
+        #[schemars(
+            description = "Optional entry point to run when the module has several (see list_entrypoints); default is main"
+        )]
+        entrypoint: Option<String>,
+    },
+
+    #[schemars(
+        description = "Use GLSL vertex shader source compiled by VkRunner itself instead of shaderc"
+    )]
+    VertGlsl {
+        #[schemars(description = "GLSL source of the vertex shader, including the #version line")]
+        source: String,
+    },
+
+    #[schemars(
+        description = "Use GLSL fragment shader source compiled by VkRunner itself instead of shaderc"
+    )]
+    FragGlsl {
+        #[schemars(
+            description = "GLSL source of the fragment shader, including the #version line"
+        )]
+        source: String,
+    },
+
+    #[schemars(
+        description = "Use GLSL compute shader source compiled by VkRunner itself instead of shaderc"
+    )]
+    CompGlsl {
+        #[schemars(description = "GLSL source of the compute shader, including the #version line")]
+        source: String,
+    },
+
+    #[schemars(
+        description = "Use GLSL geometry shader source compiled by VkRunner itself instead of shaderc"
+    )]
+    GeomGlsl {
+        #[schemars(
+            description = "GLSL source of the geometry shader, including the #version line"
+        )]
+        source: String,
+    },
+
+    #[schemars(
+        description = "Use GLSL tessellation control shader source compiled by VkRunner itself instead of shaderc"
+    )]
+    TescGlsl {
+        #[schemars(
+            description = "GLSL source of the tessellation control shader, including the #version line"
+        )]
+        source: String,
+    },
+
+    #[schemars(
+        description = "Use GLSL tessellation evaluation shader source compiled by VkRunner itself instead of shaderc"
+    )]
+    TeseGlsl {
+        #[schemars(
+            description = "GLSL source of the tessellation evaluation shader, including the #version line"
+        )]
+        source: String,
     },
 }
 
+impl ShaderRunnerPass {
+    /// Stage and path of the SPIR-V assembly this pass loads, if any.
+    fn spirv_input(&self) -> Option<(ShaderStage, &str)> {
+        match self {
+            ShaderRunnerPass::VertSpirv {
+                vert_spvasm_path, ..
+            } => Some((ShaderStage::Vert, vert_spvasm_path)),
+            ShaderRunnerPass::FragSpirv {
+                frag_spvasm_path, ..
+            } => Some((ShaderStage::Frag, frag_spvasm_path)),
+            ShaderRunnerPass::CompSpirv {
+                comp_spvasm_path, ..
+            } => Some((ShaderStage::Comp, comp_spvasm_path)),
+            ShaderRunnerPass::GeomSpirv {
+                geom_spvasm_path, ..
+            } => Some((ShaderStage::Geom, geom_spvasm_path)),
+            ShaderRunnerPass::TescSpirv {
+                tesc_spvasm_path, ..
+            } => Some((ShaderStage::Tesc, tesc_spvasm_path)),
+            ShaderRunnerPass::TeseSpirv {
+                tese_spvasm_path, ..
+            } => Some((ShaderStage::Tese, tese_spvasm_path)),
+            _ => None,
+        }
+    }
+
+    /// Entry point a SPIR-V pass selects, if any.
+    fn entrypoint(&self) -> Option<&str> {
+        match self {
+            ShaderRunnerPass::VertSpirv { entrypoint, .. }
+            | ShaderRunnerPass::FragSpirv { entrypoint, .. }
+            | ShaderRunnerPass::CompSpirv { entrypoint, .. }
+            | ShaderRunnerPass::GeomSpirv { entrypoint, .. }
+            | ShaderRunnerPass::TescSpirv { entrypoint, .. }
+            | ShaderRunnerPass::TeseSpirv { entrypoint, .. } => entrypoint.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Stage and source of the GLSL this pass embeds, if any.
+    fn glsl_input(&self) -> Option<(ShaderStage, &str)> {
+        match self {
+            ShaderRunnerPass::VertGlsl { source } => Some((ShaderStage::Vert, source)),
+            ShaderRunnerPass::FragGlsl { source } => Some((ShaderStage::Frag, source)),
+            ShaderRunnerPass::CompGlsl { source } => Some((ShaderStage::Comp, source)),
+            ShaderRunnerPass::GeomGlsl { source } => Some((ShaderStage::Geom, source)),
+            ShaderRunnerPass::TescGlsl { source } => Some((ShaderStage::Tesc, source)),
+            ShaderRunnerPass::TeseGlsl { source } => Some((ShaderStage::Tese, source)),
+            _ => None,
+        }
+    }
+
+    /// The header of the pass's section in a vkrunner script.
+    fn script_header(&self) -> String {
+        match (self.spirv_input(), self.glsl_input()) {
+            (Some((stage, _)), _) => format!("[{} shader spirv]", stage.script_name()),
+            (None, Some((stage, _))) => format!("[{} shader]", stage.script_name()),
+            (None, None) => "[vertex shader passthrough]".to_string(),
+        }
+    }
+
+    /// Mutable access to the reference `spirv_input` returns.
+    fn spirv_input_mut(&mut self) -> Option<&mut String> {
+        match self {
+            ShaderRunnerPass::VertSpirv {
+                vert_spvasm_path, ..
+            } => Some(vert_spvasm_path),
+            ShaderRunnerPass::FragSpirv {
+                frag_spvasm_path, ..
+            } => Some(frag_spvasm_path),
+            ShaderRunnerPass::CompSpirv {
+                comp_spvasm_path, ..
+            } => Some(comp_spvasm_path),
+            ShaderRunnerPass::GeomSpirv {
+                geom_spvasm_path, ..
+            } => Some(geom_spvasm_path),
+            ShaderRunnerPass::TescSpirv {
+                tesc_spvasm_path, ..
+            } => Some(tesc_spvasm_path),
+            ShaderRunnerPass::TeseSpirv {
+                tese_spvasm_path, ..
+            } => Some(tese_spvasm_path),
+            _ => None,
+        }
+    }
+
+    fn is_vertex_stage(&self) -> bool {
+        matches!(
+            self,
+            ShaderRunnerPass::VertPassthrough
+                | ShaderRunnerPass::VertSpirv { .. }
+                | ShaderRunnerPass::VertGlsl { .. }
+        )
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub enum ShaderRunnerVertexData {
     #[schemars(description = "Defines an attribute format at a shader location/binding")]
@@ -188,6 +1792,78 @@ pub enum ShaderRunnerVertexData {
         w: f32,
     },
 
+    #[schemars(description = "2D double-precision data (for R64G64_SFLOAT format)")]
+    Dvec2 {
+        #[schemars(description = "X coordinate value")]
+        x: f64,
+
+        #[schemars(description = "Y coordinate value")]
+        y: f64,
+    },
+
+    #[schemars(description = "3D double-precision data (for R64G64B64_SFLOAT format)")]
+    Dvec3 {
+        #[schemars(description = "X coordinate value")]
+        x: f64,
+
+        #[schemars(description = "Y coordinate value")]
+        y: f64,
+
+        #[schemars(description = "Z coordinate value")]
+        z: f64,
+    },
+
+    #[schemars(description = "4D double-precision data (for R64G64B64A64_SFLOAT format)")]
+    Dvec4 {
+        #[schemars(description = "X coordinate value")]
+        x: f64,
+
+        #[schemars(description = "Y coordinate value")]
+        y: f64,
+
+        #[schemars(description = "Z coordinate value")]
+        z: f64,
+
+        #[schemars(description = "W coordinate value")]
+        w: f64,
+    },
+
+    #[schemars(description = "2D half-float data (for R16G16_SFLOAT format)")]
+    F16vec2 {
+        #[schemars(description = "X coordinate value (at most 65504 in magnitude)")]
+        x: f32,
+
+        #[schemars(description = "Y coordinate value (at most 65504 in magnitude)")]
+        y: f32,
+    },
+
+    #[schemars(description = "3D half-float data (for R16G16B16_SFLOAT format)")]
+    F16vec3 {
+        #[schemars(description = "X coordinate value (at most 65504 in magnitude)")]
+        x: f32,
+
+        #[schemars(description = "Y coordinate value (at most 65504 in magnitude)")]
+        y: f32,
+
+        #[schemars(description = "Z coordinate value (at most 65504 in magnitude)")]
+        z: f32,
+    },
+
+    #[schemars(description = "4D half-float data (for R16G16B16A16_SFLOAT format)")]
+    F16vec4 {
+        #[schemars(description = "X coordinate value (at most 65504 in magnitude)")]
+        x: f32,
+
+        #[schemars(description = "Y coordinate value (at most 65504 in magnitude)")]
+        y: f32,
+
+        #[schemars(description = "Z coordinate value (at most 65504 in magnitude)")]
+        z: f32,
+
+        #[schemars(description = "W coordinate value (at most 65504 in magnitude)")]
+        w: f32,
+    },
+
     #[schemars(description = "RGB color data (for R8G8B8_UNORM format)")]
     RGB {
         #[schemars(description = "Red component (0-255)")]
@@ -200,10 +1876,20 @@ pub enum ShaderRunnerVertexData {
         b: u8,
     },
 
-    #[schemars(description = "ARGB color as hex (for A8B8G8R8_UNORM_PACK32 format)")]
+    #[schemars(
+        description = "One packed value as hex (for A8B8G8R8_UNORM_PACK32 format by default; see packing)"
+    )]
     Hex {
-        #[schemars(description = "Color as hex string (0xAARRGGBB format)")]
+        #[schemars(
+            description = "Packed value as a hex string (0xAARRGGBB format for the default packing)"
+        )]
         value: String,
+
+        #[schemars(description = "How the value is packed (default: A8B8G8R8)")]
+        packing: Option<HexPacking>,
+
+        #[schemars(description = "Order of the bytes in the hex string (default: BigEndian)")]
+        byte_order: Option<ByteOrder>,
     },
 
     #[schemars(description = "Generic data components for custom formats")]
@@ -213,69 +1899,856 @@ pub enum ShaderRunnerVertexData {
     },
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
-pub enum ShaderRunnerTest {
-    #[schemars(description = "Set fragment shader entrypoint function name")]
-    FragmentEntrypoint {
-        #[schemars(description = "Function name to use as entrypoint")]
-        name: String,
-    },
+/// Value types accepted by vkrunner's `subdata` and `push` commands,
+/// spelled as in GLSL. Bools aren't among them; they are written as the
+/// 4-byte unsigned integers that buffers store them as.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub enum DataType {
+    #[serde(rename = "bool")]
+    Bool,
+    #[serde(rename = "bvec2")]
+    Bvec2,
+    #[serde(rename = "bvec3")]
+    Bvec3,
+    #[serde(rename = "bvec4")]
+    Bvec4,
+    #[serde(rename = "float")]
+    Float,
+    #[serde(rename = "double")]
+    Double,
+    #[serde(rename = "int")]
+    Int,
+    #[serde(rename = "uint")]
+    Uint,
+    #[serde(rename = "int8_t")]
+    Int8T,
+    #[serde(rename = "uint8_t")]
+    Uint8T,
+    #[serde(rename = "int16_t")]
+    Int16T,
+    #[serde(rename = "uint16_t")]
+    Uint16T,
+    #[serde(rename = "int64_t")]
+    Int64T,
+    #[serde(rename = "uint64_t")]
+    Uint64T,
+    #[serde(rename = "float16_t")]
+    Float16T,
+    #[serde(rename = "vec2")]
+    Vec2,
+    #[serde(rename = "vec3")]
+    Vec3,
+    #[serde(rename = "vec4")]
+    Vec4,
+    #[serde(rename = "dvec2")]
+    Dvec2,
+    #[serde(rename = "dvec3")]
+    Dvec3,
+    #[serde(rename = "dvec4")]
+    Dvec4,
+    #[serde(rename = "ivec2")]
+    Ivec2,
+    #[serde(rename = "ivec3")]
+    Ivec3,
+    #[serde(rename = "ivec4")]
+    Ivec4,
+    #[serde(rename = "uvec2")]
+    Uvec2,
+    #[serde(rename = "uvec3")]
+    Uvec3,
+    #[serde(rename = "uvec4")]
+    Uvec4,
+    #[serde(rename = "i8vec2")]
+    I8vec2,
+    #[serde(rename = "i8vec3")]
+    I8vec3,
+    #[serde(rename = "i8vec4")]
+    I8vec4,
+    #[serde(rename = "u8vec2")]
+    U8vec2,
+    #[serde(rename = "u8vec3")]
+    U8vec3,
+    #[serde(rename = "u8vec4")]
+    U8vec4,
+    #[serde(rename = "i16vec2")]
+    I16vec2,
+    #[serde(rename = "i16vec3")]
+    I16vec3,
+    #[serde(rename = "i16vec4")]
+    I16vec4,
+    #[serde(rename = "u16vec2")]
+    U16vec2,
+    #[serde(rename = "u16vec3")]
+    U16vec3,
+    #[serde(rename = "u16vec4")]
+    U16vec4,
+    #[serde(rename = "i64vec2")]
+    I64vec2,
+    #[serde(rename = "i64vec3")]
+    I64vec3,
+    #[serde(rename = "i64vec4")]
+    I64vec4,
+    #[serde(rename = "u64vec2")]
+    U64vec2,
+    #[serde(rename = "u64vec3")]
+    U64vec3,
+    #[serde(rename = "u64vec4")]
+    U64vec4,
+    #[serde(rename = "f16vec2")]
+    F16vec2,
+    #[serde(rename = "f16vec3")]
+    F16vec3,
+    #[serde(rename = "f16vec4")]
+    F16vec4,
+    #[serde(rename = "mat2", alias = "mat2x2")]
+    Mat2,
+    #[serde(rename = "mat2x3")]
+    Mat2x3,
+    #[serde(rename = "mat2x4")]
+    Mat2x4,
+    #[serde(rename = "mat3x2")]
+    Mat3x2,
+    #[serde(rename = "mat3", alias = "mat3x3")]
+    Mat3,
+    #[serde(rename = "mat3x4")]
+    Mat3x4,
+    #[serde(rename = "mat4x2")]
+    Mat4x2,
+    #[serde(rename = "mat4x3")]
+    Mat4x3,
+    #[serde(rename = "mat4", alias = "mat4x4")]
+    Mat4,
+    #[serde(rename = "dmat2", alias = "dmat2x2")]
+    Dmat2,
+    #[serde(rename = "dmat2x3")]
+    Dmat2x3,
+    #[serde(rename = "dmat2x4")]
+    Dmat2x4,
+    #[serde(rename = "dmat3x2")]
+    Dmat3x2,
+    #[serde(rename = "dmat3", alias = "dmat3x3")]
+    Dmat3,
+    #[serde(rename = "dmat3x4")]
+    Dmat3x4,
+    #[serde(rename = "dmat4x2")]
+    Dmat4x2,
+    #[serde(rename = "dmat4x3")]
+    Dmat4x3,
+    #[serde(rename = "dmat4", alias = "dmat4x4")]
+    Dmat4,
+}
 
-    #[schemars(description = "Set vertex shader entrypoint function name")]
-    VertexEntrypoint {
-        #[schemars(description = "Function name to use as entrypoint")]
-        name: String,
-    },
+impl DataType {
+    fn glsl_name(self) -> &'static str {
+        match self {
+            DataType::Bool => "bool",
+            DataType::Bvec2 => "bvec2",
+            DataType::Bvec3 => "bvec3",
+            DataType::Bvec4 => "bvec4",
+            DataType::Float => "float",
+            DataType::Double => "double",
+            DataType::Int => "int",
+            DataType::Uint => "uint",
+            DataType::Int8T => "int8_t",
+            DataType::Uint8T => "uint8_t",
+            DataType::Int16T => "int16_t",
+            DataType::Uint16T => "uint16_t",
+            DataType::Int64T => "int64_t",
+            DataType::Uint64T => "uint64_t",
+            DataType::Float16T => "float16_t",
+            DataType::Vec2 => "vec2",
+            DataType::Vec3 => "vec3",
+            DataType::Vec4 => "vec4",
+            DataType::Dvec2 => "dvec2",
+            DataType::Dvec3 => "dvec3",
+            DataType::Dvec4 => "dvec4",
+            DataType::Ivec2 => "ivec2",
+            DataType::Ivec3 => "ivec3",
+            DataType::Ivec4 => "ivec4",
+            DataType::Uvec2 => "uvec2",
+            DataType::Uvec3 => "uvec3",
+            DataType::Uvec4 => "uvec4",
+            DataType::I8vec2 => "i8vec2",
+            DataType::I8vec3 => "i8vec3",
+            DataType::I8vec4 => "i8vec4",
+            DataType::U8vec2 => "u8vec2",
+            DataType::U8vec3 => "u8vec3",
+            DataType::U8vec4 => "u8vec4",
+            DataType::I16vec2 => "i16vec2",
+            DataType::I16vec3 => "i16vec3",
+            DataType::I16vec4 => "i16vec4",
+            DataType::U16vec2 => "u16vec2",
+            DataType::U16vec3 => "u16vec3",
+            DataType::U16vec4 => "u16vec4",
+            DataType::I64vec2 => "i64vec2",
+            DataType::I64vec3 => "i64vec3",
+            DataType::I64vec4 => "i64vec4",
+            DataType::U64vec2 => "u64vec2",
+            DataType::U64vec3 => "u64vec3",
+            DataType::U64vec4 => "u64vec4",
+            DataType::F16vec2 => "f16vec2",
+            DataType::F16vec3 => "f16vec3",
+            DataType::F16vec4 => "f16vec4",
+            DataType::Mat2 => "mat2",
+            DataType::Mat2x3 => "mat2x3",
+            DataType::Mat2x4 => "mat2x4",
+            DataType::Mat3x2 => "mat3x2",
+            DataType::Mat3 => "mat3",
+            DataType::Mat3x4 => "mat3x4",
+            DataType::Mat4x2 => "mat4x2",
+            DataType::Mat4x3 => "mat4x3",
+            DataType::Mat4 => "mat4",
+            DataType::Dmat2 => "dmat2",
+            DataType::Dmat2x3 => "dmat2x3",
+            DataType::Dmat2x4 => "dmat2x4",
+            DataType::Dmat3x2 => "dmat3x2",
+            DataType::Dmat3 => "dmat3",
+            DataType::Dmat3x4 => "dmat3x4",
+            DataType::Dmat4x2 => "dmat4x2",
+            DataType::Dmat4x3 => "dmat4x3",
+            DataType::Dmat4 => "dmat4",
+        }
+    }
 
-    #[schemars(description = "Set compute shader entrypoint function name")]
-    ComputeEntrypoint {
-        #[schemars(description = "Function name to use as entrypoint")]
-        name: String,
-    },
+    /// The type vkrunner is given for the values.
+    fn script_name(self) -> &'static str {
+        match self {
+            DataType::Bool => "uint",
+            DataType::Bvec2 => "uvec2",
+            DataType::Bvec3 => "uvec3",
+            DataType::Bvec4 => "uvec4",
+            _ => self.glsl_name(),
+        }
+    }
 
-    #[schemars(description = "Set geometry shader entrypoint function name")]
-    GeometryEntrypoint {
-        #[schemars(description = "Function name to use as entrypoint")]
-        name: String,
-    },
+    /// Size in bytes of one component of the type.
+    fn component_size(self) -> u32 {
+        let name = self.glsl_name();
+        if name.starts_with('d') || name.contains("64") {
+            8
+        } else if name.contains("16") {
+            2
+        } else if name.contains('8') {
+            1
+        } else {
+            4
+        }
+    }
 
-    #[schemars(description = "Draw a rectangle using normalized device coordinates")]
-    DrawRect {
-        #[schemars(description = "X coordinate of lower-left corner")]
-        x: f32,
+    fn is_double(self) -> bool {
+        self.component_size() == 8 && ValueKind::of(self) == ValueKind::Float
+    }
 
-        #[schemars(description = "Y coordinate of lower-left corner")]
-        y: f32,
+    fn is_half(self) -> bool {
+        ValueKind::of(self) == ValueKind::Half
+    }
+}
 
-        #[schemars(description = "Width of the rectangle")]
-        width: f32,
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.glsl_name())
+    }
+}
 
-        #[schemars(description = "Height of the rectangle")]
-        height: f32,
-    },
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueKind {
+    Bool,
+    Float,
+    /// A 16-bit float, which vkrunner rounds to the nearest half.
+    Half,
+    Int,
+    Uint,
+}
 
-    #[schemars(description = "Draw primitives using vertex data")]
-    DrawArrays {
-        #[schemars(description = "Primitive type (TRIANGLE_LIST, POINT_LIST, etc.)")]
-        primitive_type: String,
+/// Finite values from here on round to infinity as half floats.
+const HALF_OVERFLOW: f64 = 65520.0;
+
+impl ValueKind {
+    fn of(data_type: DataType) -> ValueKind {
+        match data_type.glsl_name().as_bytes()[0] {
+            b'b' => ValueKind::Bool,
+            b'u' => ValueKind::Uint,
+            b'i' => ValueKind::Int,
+            _ if data_type.component_size() == 2 => ValueKind::Half,
+            _ => ValueKind::Float,
+        }
+    }
 
-        #[schemars(description = "Index of first vertex")]
-        first: u32,
+    /// Mirrors what vkrunner's number parsers accept, including the
+    /// `0x` bit-pattern notation for floats. Half floats must also fit:
+    /// a bit pattern in 16 bits and a finite value below the overflow.
+    fn accepts(self, value: &str) -> bool {
+        match (self, value.strip_prefix("0x")) {
+            (ValueKind::Float, Some(bits)) => return u64::from_str_radix(bits, 16).is_ok(),
+            (ValueKind::Half, Some(bits)) => return u16::from_str_radix(bits, 16).is_ok(),
+            (ValueKind::Float, None) => return value.parse::<f64>().is_ok(),
+            (ValueKind::Half, None) => {
+                return value
+                    .parse::<f64>()
+                    .is_ok_and(|value| !value.is_finite() || value.abs() < HALF_OVERFLOW);
+            }
+            (ValueKind::Bool, _) => return matches!(value, "true" | "false" | "1" | "0"),
+            _ => {}
+        }
 
-        #[schemars(description = "Number of vertices to draw")]
-        count: u32,
-    },
+        let digits = match value.strip_prefix('-') {
+            Some(_) if self == ValueKind::Uint => return false,
+            Some(digits) => digits,
+            None => value.strip_prefix('+').unwrap_or(value),
+        };
 
-    #[schemars(description = "Draw primitives using indexed vertex data")]
-    DrawArraysIndexed {
-        #[schemars(description = "Primitive type (TRIANGLE_LIST, etc.)")]
-        primitive_type: String,
+        match digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            Some(hex) => u64::from_str_radix(hex, 16).is_ok(),
+            None => !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()),
+        }
+    }
 
-        #[schemars(description = "Index of first index")]
-        first: u32,
+    fn description(self) -> &'static str {
+        match self {
+            ValueKind::Bool => "a bool (true, false, 1 or 0)",
+            ValueKind::Float => "a float",
+            ValueKind::Half => "a half float (at most 65504 in magnitude, or 0x0000 to 0xffff)",
+            ValueKind::Int => "an integer",
+            ValueKind::Uint => "an unsigned integer",
+        }
+    }
+}
 
-        #[schemars(description = "Number of indices to use")]
-        count: u32,
+fn check_values(kind: ValueKind, values: &[String], context: &str) -> Result<(), McpError> {
+    let tokens = values.iter().flat_map(|value| value.split_whitespace());
+
+    for (i, token) in tokens.enumerate() {
+        if !kind.accepts(token) {
+            // GLSL bools take 4 bytes in buffers, so writing them as
+            // anything smaller shifts every later member
+            let hint = if matches!(token, "true" | "false") {
+                "; use the bool or bvec data types, which write 4 bytes per component as GLSL lays bools out"
+            } else {
+                ""
+            };
+            return Err(McpError::invalid_params(
+                format!(
+                    "value {i} of {context} is not {}: {token:?}{hint}",
+                    kind.description()
+                ),
+                None,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Packs a bitfield written as `value:width` fields separated by `|`,
+/// the first field in the least significant bits. Returns `None` for
+/// values not written that way.
+fn pack_bitfield(value: &str, bits: u32) -> Option<Result<u64, String>> {
+    if !value.contains(':') {
+        return None;
+    }
+
+    let mut packed = 0u64;
+    let mut shift = 0;
+    for field in value.split('|') {
+        let Some((field_value, width)) = field.split_once(':') else {
+            return Some(Err(format!("bitfield field {field:?} is not value:width")));
+        };
+        let field_value = match field_value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => field_value.parse(),
+        };
+        let (Ok(field_value), Some(width)) = (
+            field_value,
+            width
+                .parse::<u32>()
+                .ok()
+                .filter(|width| (1..=64).contains(width)),
+        ) else {
+            return Some(Err(format!(
+                "bitfield field {field:?} needs an unsigned value and a width of 1 to 64 bits"
+            )));
+        };
+        if width < 64 && field_value >> width != 0 {
+            return Some(Err(format!(
+                "bitfield field {field:?}: {field_value} does not fit in {width} bits"
+            )));
+        }
+        if shift + width > bits {
+            return Some(Err(format!(
+                "bitfield {value:?} is {} bits wide but the type only has {bits}",
+                shift + width
+            )));
+        }
+        packed |= field_value << shift;
+        shift += width;
+    }
+
+    Some(Ok(packed))
+}
+
+/// The values as vkrunner reads them: bools as 1 or 0 and bitfields
+/// packed into hexadecimal.
+fn script_values(data_type: DataType, values: &[String]) -> Result<Vec<String>, String> {
+    let kind = ValueKind::of(data_type);
+    let bits = data_type.component_size() * 8;
+
+    values
+        .iter()
+        .flat_map(|value| value.split_whitespace())
+        .map(|token| match (kind, token) {
+            (ValueKind::Bool, "true") => Ok("1".to_string()),
+            (ValueKind::Bool, "false") => Ok("0".to_string()),
+            (ValueKind::Uint, _) => match pack_bitfield(token, bits) {
+                Some(packed) => packed.map(|packed| format!("{packed:#x}")),
+                None => Ok(token.to_string()),
+            },
+            _ => Ok(token.to_string()),
+        })
+        .collect()
+}
+
+/// Checks the values of a typed command and that its offset is aligned
+/// for the type's components. vkrunner writes at any offset, but no
+/// buffer layout puts a component where it would be misaligned.
+fn check_data(
+    data_type: DataType,
+    offset: u32,
+    values: &[String],
+    context: &str,
+) -> Result<(), McpError> {
+    let alignment = data_type.component_size();
+    if offset & (alignment - 1) != 0 {
+        return Err(McpError::invalid_params(
+            format!(
+                "{context}: {data_type} components are {alignment} bytes, so the offset must be a multiple of {alignment}"
+            ),
+            Some(json!({"offset": offset, "alignment": alignment})),
+        ));
+    }
+
+    let values = script_values(data_type, values)
+        .map_err(|message| McpError::invalid_params(format!("{context}: {message}"), None))?;
+    check_values(ValueKind::of(data_type), &values, context)
+}
+
+fn check_probe_args(probe: &str, args: &[String]) -> Result<(), McpError> {
+    const COMPARISONS: [&str; 7] = ["==", "!=", "<", "<=", ">", ">=", "~="];
+
+    let tokens = args.iter().flat_map(|arg| {
+        arg.split(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | ',' | ':'))
+            .filter(|token| !token.is_empty())
+    });
+
+    for (i, token) in tokens.enumerate() {
+        if COMPARISONS.contains(&token) && probe.contains("ssbo") {
+            continue;
+        }
+
+        if !ValueKind::Float.accepts(token) {
+            return Err(McpError::invalid_params(
+                format!("argument {i} of {probe} is not a number: {token:?}"),
+                None,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Packed layouts of a `Hex` vertex value.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    schemars::JsonSchema,
+)]
+pub enum HexPacking {
+    #[schemars(description = "A 32-bit color for A8B8G8R8_UNORM_PACK32")]
+    #[default]
+    A8B8G8R8,
+    #[schemars(description = "A 16-bit color for R5G6B5_UNORM_PACK16, with red in the top 5 bits")]
+    R5G6B5,
+    #[schemars(
+        description = "A 32-bit value for A2B10G10R10_UNORM_PACK32, with alpha in the top 2 bits and red in the low 10"
+    )]
+    A2B10G10R10,
+    #[schemars(
+        description = "Two half floats in 32 bits for R16G16_SFLOAT, with the first component in the low 16 bits"
+    )]
+    HalfPair,
+}
+
+impl HexPacking {
+    fn bytes(self) -> usize {
+        match self {
+            HexPacking::R5G6B5 => 2,
+            HexPacking::A8B8G8R8 | HexPacking::A2B10G10R10 | HexPacking::HalfPair => 4,
+        }
+    }
+
+    /// The attribute format that reads the packed value.
+    fn format(self) -> &'static str {
+        match self {
+            HexPacking::A8B8G8R8 => "A8B8G8R8_UNORM_PACK32",
+            HexPacking::R5G6B5 => "R5G6B5_UNORM_PACK16",
+            HexPacking::A2B10G10R10 => "A2B10G10R10_UNORM_PACK32",
+            HexPacking::HalfPair => "R16G16_SFLOAT",
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    schemars::JsonSchema,
+)]
+pub enum ByteOrder {
+    #[schemars(description = "Most significant byte first, the way the number is written")]
+    #[default]
+    BigEndian,
+    #[schemars(
+        description = "Least significant byte first, the order of the bytes in a little-endian buffer such as a hex dump. Every byte must be given"
+    )]
+    LittleEndian,
+}
+
+/// Parses a `Hex` vertex value into the values vkrunner reads for its
+/// packing. vkrunner stores them in the machine's byte order, so a
+/// little-endian string is converted to the number it spells.
+fn packed_hex_values(
+    value: &str,
+    packing: HexPacking,
+    byte_order: ByteOrder,
+) -> Result<Vec<String>, String> {
+    let digits = packing.bytes() * 2;
+    let hex = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .filter(|hex| !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("{value:?} is not a 0x-prefixed hex number"))?;
+
+    let full_width = match byte_order {
+        ByteOrder::BigEndian => hex.len() <= digits,
+        ByteOrder::LittleEndian => hex.len() == digits,
+    };
+    if !full_width {
+        return Err(format!(
+            "{value:?} needs {} {digits} hex digits for {packing:?} packing in {byte_order:?} order",
+            if byte_order == ByteOrder::LittleEndian {
+                "exactly"
+            } else {
+                "at most"
+            }
+        ));
+    }
+
+    let number = u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+    let number = match (byte_order, packing.bytes()) {
+        (ByteOrder::BigEndian, _) => number,
+        (ByteOrder::LittleEndian, 2) => u32::from((number as u16).swap_bytes()),
+        (ByteOrder::LittleEndian, _) => number.swap_bytes(),
+    };
+
+    Ok(match packing {
+        HexPacking::HalfPair => vec![
+            format!("0x{:04x}", number & 0xffff),
+            format!("0x{:04x}", number >> 16),
+        ],
+        _ => vec![format!("0x{number:0digits$x}")],
+    })
+}
+
+/// Checks the `Hex` rows of a vertex data section: the value must fit
+/// its packing, and a section of one attribute must declare the format
+/// that reads that packing.
+fn check_hex_rows(section: &str, rows: &[ShaderRunnerVertexData]) -> Result<(), McpError> {
+    let formats = rows
+        .iter()
+        .filter_map(|data| match data {
+            ShaderRunnerVertexData::AttributeFormat { format, .. } => {
+                Some(format.strip_prefix("VK_FORMAT_").unwrap_or(format))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    for (row, data) in rows.iter().enumerate() {
+        let ShaderRunnerVertexData::Hex {
+            value,
+            packing,
+            byte_order,
+        } = data
+        else {
+            continue;
+        };
+        let packing = packing.unwrap_or_default();
+
+        packed_hex_values(value, packing, byte_order.unwrap_or_default())
+            .map_err(|e| McpError::invalid_params(format!("{section} row {row}: {e}"), None))?;
+
+        match formats[..] {
+            [format] if format != packing.format() => {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "{section} row {row} is packed as {packing:?}, which needs the {} attribute format, not {format}",
+                        packing.format()
+                    ),
+                    Some(json!({"row": row})),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+impl ShaderRunnerVertexData {
+    /// Values of a data row as they are written to the script, or `None`
+    /// for an attribute format.
+    fn row_values(&self) -> Option<Vec<String>> {
+        let values = match self {
+            ShaderRunnerVertexData::AttributeFormat { .. } => return None,
+            ShaderRunnerVertexData::Vec2 { x, y } | ShaderRunnerVertexData::F16vec2 { x, y } => {
+                vec![x.to_string(), y.to_string()]
+            }
+            ShaderRunnerVertexData::Vec3 { x, y, z }
+            | ShaderRunnerVertexData::F16vec3 { x, y, z } => {
+                vec![x.to_string(), y.to_string(), z.to_string()]
+            }
+            ShaderRunnerVertexData::Vec4 { x, y, z, w }
+            | ShaderRunnerVertexData::F16vec4 { x, y, z, w } => {
+                vec![x.to_string(), y.to_string(), z.to_string(), w.to_string()]
+            }
+            ShaderRunnerVertexData::Dvec2 { x, y } => vec![x.to_string(), y.to_string()],
+            ShaderRunnerVertexData::Dvec3 { x, y, z } => {
+                vec![x.to_string(), y.to_string(), z.to_string()]
+            }
+            ShaderRunnerVertexData::Dvec4 { x, y, z, w } => {
+                vec![x.to_string(), y.to_string(), z.to_string(), w.to_string()]
+            }
+            ShaderRunnerVertexData::RGB { r, g, b } => {
+                vec![r.to_string(), g.to_string(), b.to_string()]
+            }
+            ShaderRunnerVertexData::Hex {
+                value,
+                packing,
+                byte_order,
+            } => packed_hex_values(
+                value,
+                packing.unwrap_or_default(),
+                byte_order.unwrap_or_default(),
+            )
+            // Rejected by validate_values before any script is written
+            .unwrap_or_else(|_| vec![value.clone()]),
+            ShaderRunnerVertexData::GenericComponents { components } => components
+                .iter()
+                .flat_map(|component| component.split_whitespace())
+                .map(str::to_string)
+                .collect(),
+        };
+
+        Some(values)
+    }
+}
+
+/// Number and kind of the values vkrunner reads for one attribute of a
+/// data row. Accepts both `R32G32_SFLOAT` style names and the
+/// `float/vec2` GL type form. Returns `None` for formats it doesn't know,
+/// leaving those to vkrunner.
+fn attribute_layout(format: &str) -> Option<(usize, ValueKind)> {
+    if let Some((gl_type, glsl_type)) = format.split_once('/') {
+        let kind = match gl_type {
+            "half" => ValueKind::Half,
+            "float" | "double" => ValueKind::Float,
+            "byte" | "short" | "int" => ValueKind::Int,
+            "ubyte" | "ushort" | "uint" => ValueKind::Uint,
+            _ => return None,
+        };
+        let components = match glsl_type {
+            "int" | "uint" | "float" | "double" => 1,
+            _ => glsl_type
+                .trim_start_matches(['i', 'u', 'd'])
+                .strip_prefix("vec")?
+                .parse()
+                .ok()
+                .filter(|n| (2..=4).contains(n))?,
+        };
+        return Some((components, kind));
+    }
+
+    let format = format.strip_prefix("VK_FORMAT_").unwrap_or(format);
+
+    if format.contains("_PACK") {
+        return Some((1, ValueKind::Uint));
+    }
+
+    let (channels, mode) = format.split_once('_')?;
+    let kind = match mode {
+        "SFLOAT" if channels.contains("16") => ValueKind::Half,
+        "SFLOAT" => ValueKind::Float,
+        "UNORM" | "USCALED" | "UINT" | "SRGB" => ValueKind::Uint,
+        "SNORM" | "SSCALED" | "SINT" => ValueKind::Int,
+        _ => return None,
+    };
+    let components = channels.chars().filter(char::is_ascii_alphabetic).count();
+
+    Some((components, kind))
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum Comparison {
+    #[serde(rename = "==")]
+    Equal,
+    #[serde(rename = "!=")]
+    NotEqual,
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = "<=")]
+    LessEqual,
+    #[serde(rename = ">")]
+    Greater,
+    #[serde(rename = ">=")]
+    GreaterEqual,
+    #[schemars(description = "Equal within the current tolerance")]
+    #[serde(rename = "~=")]
+    FuzzyEqual,
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterEqual => ">=",
+            Comparison::FuzzyEqual => "~=",
+        })
+    }
+}
+
+/// Formats a buffer binding the way vkrunner expects it, `set:binding`
+/// or just `binding` for set 0.
+fn binding_ref(descriptor_set: Option<u32>, binding: u32) -> String {
+    match descriptor_set {
+        Some(set) => format!("{set}:{binding}"),
+        None => binding.to_string(),
+    }
+}
+
+/// Splits a dispatch of `groups` workgroups into `(first workgroup,
+/// workgroups)` pieces of at most `max_workgroups` (at least 1), filling
+/// whole rows along x before y and z.
+fn dispatch_chunks(groups: [u32; 3], max_workgroups: u64) -> Vec<([u32; 3], [u32; 3])> {
+    let x = u64::from(groups[0]).clamp(1, max_workgroups.max(1));
+    let y = u64::from(groups[1]).clamp(1, (max_workgroups / x).max(1));
+    let z = u64::from(groups[2]).clamp(1, (max_workgroups / (x * y)).max(1));
+    let chunk = [x, y, z].map(|n| n as u32);
+
+    let mut chunks = Vec::new();
+    for oz in (0..groups[2]).step_by(chunk[2] as usize) {
+        for oy in (0..groups[1]).step_by(chunk[1] as usize) {
+            for ox in (0..groups[0]).step_by(chunk[0] as usize) {
+                let origin = [ox, oy, oz];
+                let size = [0, 1, 2].map(|axis| chunk[axis].min(groups[axis] - origin[axis]));
+                chunks.push((origin, size));
+            }
+        }
+    }
+    chunks
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum ShaderRunnerTest {
+    #[schemars(description = "Set fragment shader entrypoint function name")]
+    FragmentEntrypoint {
+        #[schemars(description = "Function name to use as entrypoint")]
+        name: String,
+    },
+
+    #[schemars(description = "Set vertex shader entrypoint function name")]
+    VertexEntrypoint {
+        #[schemars(description = "Function name to use as entrypoint")]
+        name: String,
+    },
+
+    #[schemars(description = "Set compute shader entrypoint function name")]
+    ComputeEntrypoint {
+        #[schemars(description = "Function name to use as entrypoint")]
+        name: String,
+    },
+
+    #[schemars(description = "Set geometry shader entrypoint function name")]
+    GeometryEntrypoint {
+        #[schemars(description = "Function name to use as entrypoint")]
+        name: String,
+    },
+
+    #[schemars(description = "Draw a rectangle using normalized device coordinates")]
+    DrawRect {
+        #[schemars(description = "X coordinate of lower-left corner")]
+        x: f32,
+
+        #[schemars(description = "Y coordinate of lower-left corner")]
+        y: f32,
+
+        #[schemars(description = "Width of the rectangle")]
+        width: f32,
+
+        #[schemars(description = "Height of the rectangle")]
+        height: f32,
+    },
+
+    #[schemars(description = "Draw primitives using vertex data")]
+    DrawArrays {
+        #[schemars(description = "Primitive type (TRIANGLE_LIST, POINT_LIST, etc.)")]
+        primitive_type: String,
+
+        #[schemars(description = "Index of first vertex")]
+        first: u32,
+
+        #[schemars(description = "Number of vertices to draw")]
+        count: u32,
+
+        #[schemars(
+            description = "Optional number of instances to draw (instanced draw), e.g. to consume instance_data"
+        )]
+        instance_count: Option<u32>,
+    },
+
+    #[schemars(description = "Draw primitives using indexed vertex data")]
+    DrawArraysIndexed {
+        #[schemars(description = "Primitive type (TRIANGLE_LIST, etc.)")]
+        primitive_type: String,
+
+        #[schemars(description = "Index of first index")]
+        first: u32,
+
+        #[schemars(description = "Number of indices to use")]
+        count: u32,
+
+        #[schemars(
+            description = "Optional number of instances to draw (instanced draw), e.g. to consume instance_data"
+        )]
+        instance_count: Option<u32>,
     },
 
     #[schemars(description = "Create or initialize a Shader Storage Buffer Object (SSBO)")]
@@ -289,7 +2762,9 @@ pub enum ShaderRunnerTest {
         #[schemars(description = "Initial buffer contents")]
         data: Option<Vec<u8>>,
 
-        #[schemars(description = "Descriptor set number (default: 0)")]
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
         descriptor_set: Option<u32>,
     },
 
@@ -298,16 +2773,22 @@ pub enum ShaderRunnerTest {
         #[schemars(description = "Binding point in the shader")]
         binding: u32,
 
-        #[schemars(description = "Data type (float, vec4, etc.)")]
-        data_type: String,
+        #[schemars(
+            description = "Data type as spelled in GLSL (float, vec4, uvec2, mat4, float16_t, bool, bvec2, etc.). bool and bvec values are written as 4-byte 1 or 0, as buffers store them"
+        )]
+        data_type: DataType,
 
         #[schemars(description = "Byte offset into the buffer")]
         offset: u32,
 
-        #[schemars(description = "Values to write (as strings)")]
+        #[schemars(
+            description = "Values to write (as strings). Unsigned values may be bitfields written as value:width fields separated by |, the first in the lowest bits (e.g. 1:1|5:3)"
+        )]
         values: Vec<String>,
 
-        #[schemars(description = "Descriptor set number (default: 0)")]
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
         descriptor_set: Option<u32>,
     },
 
@@ -319,7 +2800,9 @@ pub enum ShaderRunnerTest {
         #[schemars(description = "Buffer contents")]
         data: Vec<u8>,
 
-        #[schemars(description = "Descriptor set number (default: 0)")]
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
         descriptor_set: Option<u32>,
     },
 
@@ -328,16 +2811,22 @@ pub enum ShaderRunnerTest {
         #[schemars(description = "Binding point in the shader")]
         binding: u32,
 
-        #[schemars(description = "Data type (float, vec4, etc.)")]
-        data_type: String,
+        #[schemars(
+            description = "Data type as spelled in GLSL (float, vec4, uvec2, mat4, float16_t, bool, bvec2, etc.). bool and bvec values are written as 4-byte 1 or 0, as buffers store them"
+        )]
+        data_type: DataType,
 
         #[schemars(description = "Byte offset into the buffer")]
         offset: u32,
 
-        #[schemars(description = "Values to write (as strings)")]
+        #[schemars(
+            description = "Values to write (as strings). Unsigned values may be bitfields written as value:width fields separated by |, the first in the lowest bits (e.g. 1:1|5:3)"
+        )]
         values: Vec<String>,
 
-        #[schemars(description = "Descriptor set number (default: 0)")]
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
         descriptor_set: Option<u32>,
     },
 
@@ -352,16 +2841,95 @@ pub enum ShaderRunnerTest {
 
     #[schemars(description = "Set push constant values")]
     Push {
-        #[schemars(description = "Data type (float, vec4, etc.)")]
-        data_type: String,
+        #[schemars(
+            description = "Data type as spelled in GLSL (float, vec4, uvec2, mat4, float16_t, bool, bvec2, etc.). bool and bvec values are written as 4-byte 1 or 0, as buffers store them"
+        )]
+        data_type: DataType,
 
         #[schemars(description = "Byte offset into push constant block")]
         offset: u32,
 
-        #[schemars(description = "Values to write (as strings)")]
+        #[schemars(
+            description = "Values to write (as strings). Unsigned values may be bitfields written as value:width fields separated by |, the first in the lowest bits (e.g. 1:1|5:3)"
+        )]
         values: Vec<String>,
     },
 
+    #[schemars(
+        description = "Push the 64-bit device address of a buffer, for shaders using GL_EXT_buffer_reference (needs the BufferDeviceAddress feature)"
+    )]
+    PushAddress {
+        #[schemars(description = "Byte offset into push constant block")]
+        offset: u32,
+
+        #[schemars(description = "Binding point of the buffer whose address is pushed")]
+        binding: u32,
+
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
+        descriptor_set: Option<u32>,
+    },
+
+    #[schemars(
+        description = "Write the 64-bit device address of a buffer into an SSBO (needs the BufferDeviceAddress feature)"
+    )]
+    SSBOAddress {
+        #[schemars(description = "Binding point of the SSBO that receives the address")]
+        binding: u32,
+
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
+        descriptor_set: Option<u32>,
+
+        #[schemars(description = "Byte offset into the buffer")]
+        offset: u32,
+
+        #[schemars(description = "Binding point of the buffer whose address is written")]
+        target_binding: u32,
+
+        #[schemars(description = "Descriptor set of the target buffer (default: 0)")]
+        target_descriptor_set: Option<u32>,
+    },
+
+    #[schemars(
+        description = "Write the 64-bit device address of a buffer into a UBO (needs the BufferDeviceAddress feature)"
+    )]
+    UBOAddress {
+        #[schemars(description = "Binding point of the UBO that receives the address")]
+        binding: u32,
+
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
+        descriptor_set: Option<u32>,
+
+        #[schemars(description = "Byte offset into the buffer")]
+        offset: u32,
+
+        #[schemars(description = "Binding point of the buffer whose address is written")]
+        target_binding: u32,
+
+        #[schemars(description = "Descriptor set of the target buffer (default: 0)")]
+        target_descriptor_set: Option<u32>,
+    },
+
+    #[schemars(
+        description = "Declare the push constant range instead of relying on vkrunner's defaults (size of the pushed data, all stages)"
+    )]
+    PushRange {
+        #[schemars(
+            description = "Size in bytes; pushing beyond it is an error, as is exceeding the device's maxPushConstantsSize"
+        )]
+        size: Option<u32>,
+
+        #[schemars(
+            description = "Stages that can access the push constants (default: the SPIR-V passes that declare a push constant block)"
+        )]
+        stages: Option<Vec<ShaderStage>>,
+    },
+
     #[schemars(description = "Set memory layout for push constants")]
     PushLayout {
         #[schemars(description = "Layout specification (std140, std430)")]
@@ -380,18 +2948,85 @@ pub enum ShaderRunnerTest {
         z: u32,
     },
 
-    #[schemars(description = "Verify framebuffer or buffer contents match expected values")]
-    Probe {
-        #[schemars(description = "Probe type (all, rect, ssbo, etc.)")]
-        probe_type: String,
+    #[schemars(
+        description = "Bind a generated RGBA8 texture as a combined image sampler (sampler2D, sampler2DArray or samplerCube), sampled with linear filtering and repeat wrapping"
+    )]
+    Texture {
+        #[schemars(description = "Binding point in the shader")]
+        binding: u32,
 
-        #[schemars(description = "Component format (rgba, rgb, etc.)")]
-        format: String,
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
+        descriptor_set: Option<u32>,
 
-        #[schemars(description = "Parameters (coordinates, expected values)")]
+        #[schemars(description = "Width in texels")]
+        width: u32,
+
+        #[schemars(description = "Height in texels")]
+        height: u32,
+
+        #[schemars(description = "Texture type (default: Texture2D)")]
+        kind: Option<TextureKind>,
+
+        #[schemars(
+            description = "Generate a full mipmap chain down to 1x1, each level averaging 2x2 texel blocks of the level above (default: false)"
+        )]
+        mipmaps: Option<bool>,
+
+        #[schemars(
+            description = "Make a D32_SFLOAT depth texture from the red channel (0-1) of the source instead of an RGBA8 one; needed for compare_op (default: false)"
+        )]
+        depth: Option<bool>,
+
+        #[schemars(
+            description = "Sampler state (default: linear filtering, repeat wrapping, no LOD bias)"
+        )]
+        sampler: Option<SamplerOptions>,
+
+        #[schemars(
+            description = "How the texels are generated; a single source fills every layer or face"
+        )]
+        source: TextureSource,
+    },
+
+    #[schemars(description = "Verify framebuffer or buffer contents match expected values")]
+    Probe {
+        #[schemars(description = "Probe type (all, rect, ssbo, etc.)")]
+        probe_type: String,
+
+        #[schemars(description = "Component format (rgba, rgb, etc.)")]
+        format: String,
+
+        #[schemars(description = "Parameters (coordinates, expected values)")]
         args: Vec<String>,
     },
 
+    #[schemars(description = "Verify values in an SSBO after a draw or compute command")]
+    ProbeSsbo {
+        #[schemars(description = "Binding point in the shader")]
+        binding: u32,
+
+        #[schemars(
+            description = "Descriptor set number (default: 0); written as set:binding when given"
+        )]
+        descriptor_set: Option<u32>,
+
+        #[schemars(
+            description = "Data type as spelled in GLSL (float, vec4, uvec2, mat4, float16_t, bool, bvec2, etc.). bool and bvec values are written as 4-byte 1 or 0, as buffers store them"
+        )]
+        data_type: DataType,
+
+        #[schemars(description = "Byte offset into the buffer")]
+        offset: u32,
+
+        #[schemars(description = "How each buffer value is compared with the expected one")]
+        comparison: Comparison,
+
+        #[schemars(description = "Expected values (as strings)")]
+        values: Vec<String>,
+    },
+
     #[schemars(description = "Verify contents using normalized (0-1) coordinates")]
     RelativeProbe {
         #[schemars(description = "Probe type (rect, etc.)")]
@@ -443,34 +3078,15 @@ pub enum ShaderRunnerTest {
         mode: String,
     },
 
-    #[schemars(description = "Configure stencil operation for a face")]
-    StencilOp {
-        #[schemars(description = "Face (front, back)")]
-        face: String,
-
-        #[schemars(description = "Operation name (passOp, failOp, etc.)")]
-        op_name: String,
-
-        #[schemars(description = "Value (VK_STENCIL_OP_REPLACE, etc.)")]
-        value: String,
-    },
-
-    #[schemars(description = "Set reference value for stencil comparisons")]
-    StencilReference {
-        #[schemars(description = "Face (front, back)")]
-        face: String,
-
-        #[schemars(description = "Reference value")]
-        value: u32,
-    },
-
-    #[schemars(description = "Set stencil comparison function")]
-    StencilCompareOp {
-        #[schemars(description = "Face (front, back)")]
-        face: String,
+    #[schemars(
+        description = "Configure the stencil test for one or both faces; fields left out keep their current value"
+    )]
+    StencilState {
+        #[schemars(description = "Faces to configure (default: FrontAndBack)")]
+        face: Option<StencilFace>,
 
-        #[schemars(description = "Function (VK_COMPARE_OP_EQUAL, etc.)")]
-        op: String,
+        #[schemars(description = "Stencil operations, comparison, reference and masks")]
+        state: StencilFaceState,
     },
 
     #[schemars(description = "Control which color channels can be written")]
@@ -481,9 +3097,19 @@ pub enum ShaderRunnerTest {
         mask: String,
     },
 
+    #[schemars(
+        description = "Configure color blending; factors and operations left out take the straight-alpha over defaults"
+    )]
+    BlendState {
+        #[schemars(description = "Blend enable, factors and operations")]
+        state: blend::BlendState,
+    },
+
     #[schemars(description = "Enable/disable logical operations on colors")]
     LogicOpEnable {
-        #[schemars(description = "True to enable logic operations")]
+        #[schemars(
+            description = "True to enable logic operations; needs an integer or normalized framebuffer format and adds the logicOp requirement"
+        )]
         enable: bool,
     },
 
@@ -499,6 +3125,14 @@ pub enum ShaderRunnerTest {
         mode: String,
     },
 
+    #[schemars(
+        description = "Set how polygons are rasterized; modes other than fill add the fillModeNonSolid requirement"
+    )]
+    PolygonMode {
+        #[schemars(description = "Mode (VK_POLYGON_MODE_LINE, VK_POLYGON_MODE_POINT, etc.)")]
+        mode: String,
+    },
+
     #[schemars(description = "Set width for line primitives")]
     LineWidth {
         #[schemars(description = "Width in pixels")]
@@ -515,769 +3149,10029 @@ pub enum ShaderRunnerTest {
     },
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
-pub struct CompileRequest {
-    #[schemars(description = "The shader stage to compile (vert, frag, comp, geom, tesc, tese)")]
-    pub stage: ShaderStage,
-    #[schemars(description = "GLSL shader source code to compile")]
-    pub source: String,
-    #[schemars(description = "Path where compiled SPIR-V assembly (.spvasm) will be saved")]
-    pub tmp_output_path: String,
-}
-#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
-pub struct CompileShadersRequest {
-    #[schemars(description = "List of shader compile requests (produces SPIR-V assemblies)")]
-    pub requests: Vec<CompileRequest>,
-}
-
-#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
-pub struct CompileRunShadersRequest {
-    #[schemars(
-        description = "List of shader compile requests - each produces a SPIR-V assembly file"
-    )]
-    pub requests: Vec<CompileRequest>,
-    #[schemars(description = "Optional hardware/feature requirements needed for shader execution")]
-    pub requirements: Option<Vec<ShaderRunnerRequire>>,
-    #[schemars(
-        description = "Shader pipeline configuration (references compiled SPIR-V files by path)"
-    )]
-    pub passes: Vec<ShaderRunnerPass>,
-    #[schemars(description = "Optional vertex data for rendering geometry")]
-    pub vertex_data: Option<Vec<ShaderRunnerVertexData>>,
-    #[schemars(description = "Test commands to execute (drawing, compute, verification, etc.)")]
-    pub tests: Vec<ShaderRunnerTest>,
-    #[schemars(description = "Optional path to save output image (PNG format)")]
-    pub output_path: Option<String>,
-}
-#[derive(Clone)]
-pub struct ShadercVkrunnerMcp {}
-#[tool(tool_box)]
-impl ShadercVkrunnerMcp {
-    #[must_use]
-    pub fn new() -> Self {
-        Self {}
+impl ShaderRunnerTest {
+    /// An SSBO of `size` bytes at `binding` of set 0.
+    fn ssbo(binding: u32, size: usize) -> Self {
+        ShaderRunnerTest::SSBO {
+            binding,
+            size: Some(size as u32),
+            data: None,
+            descriptor_set: None,
+        }
     }
 
-    #[tool(
-        description = "REQUIRES: (1) Shader compile requests that produce SPIR-V assembly files, (2) Passes referencing these files by path, and (3) Test commands for drawing/computation. Workflow: First compile GLSL to SPIR-V, then reference compiled files in passes, then execute drawing commands in tests to render/compute. Tests MUST include draw/compute commands to produce visible output. Optional: requirements for hardware features, vertex data for geometry, output path for saving image. Every shader MUST have its compiled output referenced in passes."
-    )]
-    fn compile_run_shaders(
-        &self,
-        #[tool(aggr)] request: CompileRunShadersRequest,
-    ) -> Result<CallToolResult, McpError> {
-        use std::fs::File;
-        use std::io::{Read, Write};
-        use std::path::Path;
-        use std::process::{Command, Stdio}; // Still needed for vkrunner
-
-        fn io_err(e: std::io::Error) -> McpError {
-            McpError::internal_error("IO operation failed", Some(json!({"error": e.to_string()})))
+    /// Writes `values` to the start of the SSBO at `binding` of set 0.
+    fn ssbo_values(binding: u32, data_type: DataType, values: Vec<String>) -> Self {
+        ShaderRunnerTest::SSBOSubData {
+            binding,
+            data_type,
+            offset: 0,
+            values,
+            descriptor_set: None,
         }
+    }
 
-        for req in &request.requests {
-            let shader_kind = match req.stage {
-                ShaderStage::Vert => ShaderKind::Vertex,
-                ShaderStage::Frag => ShaderKind::Fragment,
-                ShaderStage::Tesc => ShaderKind::TessControl,
-                ShaderStage::Tese => ShaderKind::TessEvaluation,
-                ShaderStage::Geom => ShaderKind::Geometry,
-                ShaderStage::Comp => ShaderKind::Compute,
-            };
-
-            let stage_flag = match req.stage {
-                ShaderStage::Vert => "vert",
-                ShaderStage::Frag => "frag",
-                ShaderStage::Tesc => "tesc",
-                ShaderStage::Tese => "tese",
-                ShaderStage::Geom => "geom",
-                ShaderStage::Comp => "comp",
-            };
+    fn push(data_type: DataType, offset: u32, values: Vec<String>) -> Self {
+        ShaderRunnerTest::Push {
+            data_type,
+            offset,
+            values,
+        }
+    }
 
-            let tmp_output_path = if req.tmp_output_path.starts_with("/tmp") {
-                req.tmp_output_path.clone()
-            } else {
-                format!("/tmp/{}", req.tmp_output_path)
-            };
+    /// Draws a rectangle covering the whole framebuffer.
+    fn full_screen_rect() -> Self {
+        ShaderRunnerTest::DrawRect {
+            x: -1.0,
+            y: -1.0,
+            width: 2.0,
+            height: 2.0,
+        }
+    }
 
-            if let Some(parent) = Path::new(&tmp_output_path).parent() {
-                std::fs::create_dir_all(parent).map_err(|e| {
-                    McpError::internal_error(
-                        "Failed to create temporary output directory",
-                        Some(json!({"error": e.to_string()})),
-                    )
-                })?;
+    /// `(set, binding)` of the buffer or texture a command touches.
+    fn resource_binding(&self) -> Option<(u32, u32)> {
+        match self {
+            ShaderRunnerTest::SSBO {
+                binding,
+                descriptor_set,
+                ..
             }
+            | ShaderRunnerTest::SSBOSubData {
+                binding,
+                descriptor_set,
+                ..
+            }
+            | ShaderRunnerTest::UBO {
+                binding,
+                descriptor_set,
+                ..
+            }
+            | ShaderRunnerTest::UBOSubData {
+                binding,
+                descriptor_set,
+                ..
+            }
+            | ShaderRunnerTest::ProbeSsbo {
+                binding,
+                descriptor_set,
+                ..
+            }
+            | ShaderRunnerTest::SSBOAddress {
+                binding,
+                descriptor_set,
+                ..
+            }
+            | ShaderRunnerTest::UBOAddress {
+                binding,
+                descriptor_set,
+                ..
+            }
+            | ShaderRunnerTest::Texture {
+                binding,
+                descriptor_set,
+                ..
+            } => Some((descriptor_set.unwrap_or(0), *binding)),
+            _ => None,
+        }
+    }
 
-            // Create compiler and options
-            let mut compiler = Compiler::new().ok().ok_or_else(|| {
-                McpError::internal_error(
-                    "Failed to create shaderc compiler",
-                    Some(json!({"error": "Could not instantiate shaderc compiler"})),
-                )
-            })?;
-
-            let mut options = CompileOptions::new().ok().ok_or_else(|| {
-                McpError::internal_error(
-                    "Failed to create shaderc compile options",
-                    Some(json!({"error": "Could not create compiler options"})),
-                )
-            })?;
+    /// `(set, binding)` of the buffer whose device address a command takes.
+    fn address_target(&self) -> Option<(u32, u32)> {
+        match self {
+            ShaderRunnerTest::PushAddress {
+                binding,
+                descriptor_set,
+                ..
+            } => Some((descriptor_set.unwrap_or(0), *binding)),
+            ShaderRunnerTest::SSBOAddress {
+                target_binding,
+                target_descriptor_set,
+                ..
+            }
+            | ShaderRunnerTest::UBOAddress {
+                target_binding,
+                target_descriptor_set,
+                ..
+            } => Some((target_descriptor_set.unwrap_or(0), *target_binding)),
+            _ => None,
+        }
+    }
 
-            // Set options equivalent to the CLI flags
-            options.set_target_env(
-                shaderc::TargetEnv::Vulkan,
-                shaderc::EnvVersion::Vulkan1_4 as u32,
-            );
-            options.set_optimization_level(OptimizationLevel::Performance);
-            options.set_generate_debug_info();
-
-            // Compile to SPIR-V assembly
-            let artifact = match compiler.compile_into_spirv_assembly(
-                &req.source,
-                shader_kind,
-                "shader.glsl", // source name for error reporting
-                "main",        // entry point
-                Some(&options),
-            ) {
-                Ok(artifact) => artifact,
-                Err(e) => {
-                    let stage_name = match req.stage {
-                        ShaderStage::Vert => "Vertex",
-                        ShaderStage::Frag => "Fragment",
-                        ShaderStage::Tesc => "Tessellation Control",
-                        ShaderStage::Tese => "Tessellation Evaluation",
-                        ShaderStage::Geom => "Geometry",
-                        ShaderStage::Comp => "Compute",
-                    };
+    fn is_draw(&self) -> bool {
+        matches!(
+            self,
+            ShaderRunnerTest::DrawRect { .. }
+                | ShaderRunnerTest::DrawArrays { .. }
+                | ShaderRunnerTest::DrawArraysIndexed { .. }
+        )
+    }
+}
 
-                    let error_details = format!("{}", e);
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct TokenReplacement {
+    #[schemars(description = "Token to search for in the generated script")]
+    pub token: String,
+    #[schemars(description = "Text that replaces every occurrence of the token")]
+    pub replacement: String,
+}
 
-                    return Ok(CallToolResult::success(vec![Content::text(format!(
-                        "Shader compilation failed for {} shader:\n\nError:\n{}\n\nShader Source:\n{}\n",
-                        stage_name, error_details, req.source
-                    ))]));
-                }
-            };
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub enum TextureKind {
+    #[schemars(description = "A single 2D image (sampler2D)")]
+    Texture2D,
+    #[schemars(description = "A 2D array texture with this many layers (sampler2DArray)")]
+    Array { layers: u32 },
+    #[schemars(
+        description = "A cubemap with square faces (samplerCube), in the order +X, -X, +Y, -Y, +Z, -Z"
+    )]
+    Cube,
+}
 
-            // Write the compiled SPIR-V assembly to the output file, filtering unsupported lines
-            let spv_text = artifact.as_text();
-            let filtered_spv = spv_text
-                .lines()
-                .filter(|l| !l.trim_start().starts_with("OpModuleProcessed"))
-                .collect::<Vec<_>>()
-                .join("\n");
-            std::fs::write(&tmp_output_path, filtered_spv).map_err(|e| {
-                McpError::internal_error(
-                    "Failed to write compiled shader to file",
-                    Some(json!({"error": e.to_string()})),
-                )
-            })?;
+impl TextureKind {
+    fn layers(self) -> u32 {
+        match self {
+            TextureKind::Texture2D => 1,
+            TextureKind::Array { layers } => layers,
+            TextureKind::Cube => 6,
         }
+    }
 
-        let shader_test_path = "/tmp/vkrunner_test.shader_test";
-        let mut shader_test_file = File::create(shader_test_path).map_err(io_err)?;
+    /// Words inserted between the binding and the size of vkrunner's
+    /// texture command.
+    fn script_words(self) -> String {
+        match self {
+            TextureKind::Texture2D => String::new(),
+            TextureKind::Array { layers } => format!(" array {layers}"),
+            TextureKind::Cube => " cube".to_string(),
+        }
+    }
+}
 
-        if let Some(requirements) = &request.requirements {
-            if !requirements.is_empty() {
-                writeln!(shader_test_file, "[require]").map_err(io_err)?;
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
 
-                for req in requirements {
-                    match req {
-                        ShaderRunnerRequire::CooperativeMatrix {
-                            m,
-                            n,
-                            component_type,
-                        } => {
-                            writeln!(
-                                shader_test_file,
-                                "cooperative_matrix m={m} n={n} c={component_type}"
-                            )
-                            .map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::DepthStencil(format) => {
-                            writeln!(shader_test_file, "depthstencil {format}").map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::Framebuffer(format) => {
-                            writeln!(shader_test_file, "framebuffer {format}").map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::ShaderFloat64 => {
-                            writeln!(shader_test_file, "shaderFloat64").map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::GeometryShader => {
-                            writeln!(shader_test_file, "geometryShader").map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::WideLines => {
-                            writeln!(shader_test_file, "wideLines").map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::LogicOp => {
-                            writeln!(shader_test_file, "logicOp").map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::SubgroupSize(size) => {
-                            writeln!(shader_test_file, "subgroup_size {size}").map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::FragmentStoresAndAtomics => {
-                            writeln!(shader_test_file, "fragmentStoresAndAtomics")
-                                .map_err(io_err)?;
-                        }
-                        ShaderRunnerRequire::BufferDeviceAddress => {
-                            writeln!(shader_test_file, "bufferDeviceAddress").map_err(io_err)?;
-                        }
-                    }
-                }
+impl std::fmt::Display for TextureFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TextureFilter::Nearest => "nearest",
+            TextureFilter::Linear => "linear",
+        })
+    }
+}
 
-                writeln!(shader_test_file).map_err(io_err)?;
-            }
-        }
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum AddressMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    #[schemars(description = "Coordinates outside the texture return border_color")]
+    ClampToBorder,
+}
 
-        for pass in &request.passes {
-            match pass {
-                ShaderRunnerPass::VertPassthrough => {
-                    writeln!(shader_test_file, "[vertex shader passthrough]").map_err(io_err)?;
-                }
-                ShaderRunnerPass::VertSpirv { vert_spvasm_path } => {
-                    writeln!(shader_test_file, "[vertex shader spirv]").map_err(io_err)?;
+impl std::fmt::Display for AddressMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AddressMode::Repeat => "repeat",
+            AddressMode::MirroredRepeat => "mirrored_repeat",
+            AddressMode::ClampToEdge => "clamp_to_edge",
+            AddressMode::ClampToBorder => "clamp_to_border",
+        })
+    }
+}
 
-                    let mut spvasm = String::new();
-                    let path = if vert_spvasm_path.starts_with("/tmp") {
-                        vert_spvasm_path.clone()
-                    } else {
-                        format!("/tmp/{vert_spvasm_path}")
-                    };
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum BorderColor {
+    TransparentBlack,
+    OpaqueBlack,
+    OpaqueWhite,
+}
 
-                    File::open(&path)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                format!("Failed to open vertex shader SPIR-V file at {path}"),
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?
-                        .read_to_string(&mut spvasm)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                "Failed to read vertex shader SPIR-V file",
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?;
-                    writeln!(shader_test_file, "{spvasm}").map_err(io_err)?;
-                }
-                ShaderRunnerPass::FragSpirv { frag_spvasm_path } => {
-                    writeln!(shader_test_file, "[fragment shader spirv]").map_err(io_err)?;
+impl std::fmt::Display for BorderColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BorderColor::TransparentBlack => "transparent_black",
+            BorderColor::OpaqueBlack => "opaque_black",
+            BorderColor::OpaqueWhite => "opaque_white",
+        })
+    }
+}
 
-                    let mut spvasm = String::new();
-                    let path = if frag_spvasm_path.starts_with("/tmp") {
-                        frag_spvasm_path.clone()
-                    } else {
-                        format!("/tmp/{frag_spvasm_path}")
-                    };
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum CompareOp {
+    Never,
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
 
-                    File::open(&path)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                format!("Failed to open fragment shader SPIR-V file at {path}"),
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?
-                        .read_to_string(&mut spvasm)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                "Failed to read fragment shader SPIR-V file",
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?;
-                    writeln!(shader_test_file, "{spvasm}").map_err(io_err)?;
-                }
-                ShaderRunnerPass::CompSpirv { comp_spvasm_path } => {
-                    writeln!(shader_test_file, "[compute shader spirv]").map_err(io_err)?;
+impl std::fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CompareOp::Never => "never",
+            CompareOp::Less => "less",
+            CompareOp::Equal => "equal",
+            CompareOp::LessOrEqual => "less_or_equal",
+            CompareOp::Greater => "greater",
+            CompareOp::NotEqual => "not_equal",
+            CompareOp::GreaterOrEqual => "greater_or_equal",
+            CompareOp::Always => "always",
+        })
+    }
+}
 
-                    let mut spvasm = String::new();
-                    let path = if comp_spvasm_path.starts_with("/tmp") {
-                        comp_spvasm_path.clone()
-                    } else {
-                        format!("/tmp/{comp_spvasm_path}")
-                    };
+impl CompareOp {
+    /// The enum name that vkrunner's pipeline properties take.
+    fn vk_name(self) -> &'static str {
+        match self {
+            CompareOp::Never => "VK_COMPARE_OP_NEVER",
+            CompareOp::Less => "VK_COMPARE_OP_LESS",
+            CompareOp::Equal => "VK_COMPARE_OP_EQUAL",
+            CompareOp::LessOrEqual => "VK_COMPARE_OP_LESS_OR_EQUAL",
+            CompareOp::Greater => "VK_COMPARE_OP_GREATER",
+            CompareOp::NotEqual => "VK_COMPARE_OP_NOT_EQUAL",
+            CompareOp::GreaterOrEqual => "VK_COMPARE_OP_GREATER_OR_EQUAL",
+            CompareOp::Always => "VK_COMPARE_OP_ALWAYS",
+        }
+    }
+}
 
-                    File::open(&path)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                format!("Failed to open compute shader SPIR-V file at {path}"),
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?
-                        .read_to_string(&mut spvasm)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                "Failed to read compute shader SPIR-V file",
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?;
-                    writeln!(shader_test_file, "{spvasm}").map_err(io_err)?;
-                }
-                ShaderRunnerPass::GeomSpirv { geom_spvasm_path } => {
-                    writeln!(shader_test_file, "[geometry shader spirv]").map_err(io_err)?;
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    IncrementAndClamp,
+    DecrementAndClamp,
+    Invert,
+    IncrementAndWrap,
+    DecrementAndWrap,
+}
 
-                    let mut spvasm = String::new();
-                    let path = if geom_spvasm_path.starts_with("/tmp") {
-                        geom_spvasm_path.clone()
-                    } else {
-                        format!("/tmp/{geom_spvasm_path}")
-                    };
+impl std::fmt::Display for StencilOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StencilOp::Keep => "VK_STENCIL_OP_KEEP",
+            StencilOp::Zero => "VK_STENCIL_OP_ZERO",
+            StencilOp::Replace => "VK_STENCIL_OP_REPLACE",
+            StencilOp::IncrementAndClamp => "VK_STENCIL_OP_INCREMENT_AND_CLAMP",
+            StencilOp::DecrementAndClamp => "VK_STENCIL_OP_DECREMENT_AND_CLAMP",
+            StencilOp::Invert => "VK_STENCIL_OP_INVERT",
+            StencilOp::IncrementAndWrap => "VK_STENCIL_OP_INCREMENT_AND_WRAP",
+            StencilOp::DecrementAndWrap => "VK_STENCIL_OP_DECREMENT_AND_WRAP",
+        })
+    }
+}
 
-                    File::open(&path)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                format!("Failed to open geometry shader SPIR-V file at {path}"),
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?
-                        .read_to_string(&mut spvasm)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                "Failed to read geometry shader SPIR-V file",
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?;
-                    writeln!(shader_test_file, "{spvasm}").map_err(io_err)?;
-                }
-                ShaderRunnerPass::TescSpirv { tesc_spvasm_path } => {
-                    writeln!(shader_test_file, "[tessellation control shader spirv]")
-                        .map_err(io_err)?;
+#[derive(
+    Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub enum StencilFace {
+    Front,
+    Back,
+    #[default]
+    FrontAndBack,
+}
 
-                    let mut spvasm = String::new();
-                    let path = if tesc_spvasm_path.starts_with("/tmp") {
-                        tesc_spvasm_path.clone()
-                    } else {
-                        format!("/tmp/{tesc_spvasm_path}")
-                    };
+impl StencilFace {
+    /// Prefixes of the pipeline properties of the faces.
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            StencilFace::Front => &["front"],
+            StencilFace::Back => &["back"],
+            StencilFace::FrontAndBack => &["front", "back"],
+        }
+    }
+}
 
-                    File::open(&path)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                format!(
-                                    "Failed to open tessellation control shader SPIR-V file at {path}"
-                                ),
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?
-                        .read_to_string(&mut spvasm)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                "Failed to read tessellation control shader SPIR-V file",
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?;
-                    writeln!(shader_test_file, "{spvasm}").map_err(io_err)?;
-                }
-                ShaderRunnerPass::TeseSpirv { tese_spvasm_path } => {
-                    writeln!(shader_test_file, "[tessellation evaluation shader spirv]")
-                        .map_err(io_err)?;
+/// The stencil state of a face, as in `VkStencilOpState`.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct StencilFaceState {
+    #[schemars(description = "Operation when the stencil test fails (default: Keep)")]
+    pub fail_op: Option<StencilOp>,
+    #[schemars(
+        description = "Operation when both the stencil and depth tests pass (default: Keep)"
+    )]
+    pub pass_op: Option<StencilOp>,
+    #[schemars(
+        description = "Operation when the stencil test passes but the depth test fails (default: Keep)"
+    )]
+    pub depth_fail_op: Option<StencilOp>,
+    #[schemars(
+        description = "How the masked reference is compared with the masked stencil value (default: Always)"
+    )]
+    pub compare_op: Option<CompareOp>,
+    #[schemars(description = "Value compared with and written by Replace (default: 0)")]
+    pub reference: Option<u32>,
+    #[schemars(description = "Bits of the stencil value that are compared (default: all)")]
+    pub compare_mask: Option<u32>,
+    #[schemars(description = "Bits of the stencil value that are written (default: all)")]
+    pub write_mask: Option<u32>,
+}
 
-                    let mut spvasm = String::new();
-                    let path = if tese_spvasm_path.starts_with("/tmp") {
-                        tese_spvasm_path.clone()
-                    } else {
-                        format!("/tmp/{tese_spvasm_path}")
-                    };
+impl StencilFaceState {
+    /// The pipeline properties set for a face and their values.
+    /// vkrunner parses integer properties as i32, so masks and the
+    /// reference are written in that range.
+    fn properties(&self) -> Vec<(&'static str, String)> {
+        let ops = [
+            ("failOp", self.fail_op),
+            ("passOp", self.pass_op),
+            ("depthFailOp", self.depth_fail_op),
+        ];
+        let integers = [
+            ("reference", self.reference),
+            ("compareMask", self.compare_mask),
+            ("writeMask", self.write_mask),
+        ];
+
+        ops.into_iter()
+            .filter_map(|(property, op)| Some((property, op?.to_string())))
+            .chain(
+                self.compare_op
+                    .map(|op| ("compareOp", op.vk_name().to_string())),
+            )
+            .chain(
+                integers
+                    .into_iter()
+                    .filter_map(|(property, value)| Some((property, (value? as i32).to_string()))),
+            )
+            .collect()
+    }
+}
 
-                    File::open(&path)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                format!(
-                                    "Failed to open tessellation evaluation shader SPIR-V file at {path}"
-                                ),
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?
-                        .read_to_string(&mut spvasm)
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                "Failed to read tessellation evaluation shader SPIR-V file",
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?;
-                    writeln!(shader_test_file, "{spvasm}").map_err(io_err)?;
-                }
-            }
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct SamplerOptions {
+    #[schemars(description = "Filter when the texture is magnified (default: Linear)")]
+    pub mag_filter: Option<TextureFilter>,
+    #[schemars(description = "Filter when the texture is minified (default: Linear)")]
+    pub min_filter: Option<TextureFilter>,
+    #[schemars(
+        description = "Filter between mipmap levels; Linear with Linear min_filter is trilinear filtering (default: Nearest)"
+    )]
+    pub mipmap_filter: Option<TextureFilter>,
+    #[schemars(description = "Bias added to the computed level of detail (default: 0)")]
+    pub lod_bias: Option<f32>,
+    #[schemars(description = "Wrapping of the u coordinate (default: Repeat)")]
+    pub address_u: Option<AddressMode>,
+    #[schemars(description = "Wrapping of the v coordinate (default: Repeat)")]
+    pub address_v: Option<AddressMode>,
+    #[schemars(description = "Wrapping of the w coordinate, used by cubemaps (default: Repeat)")]
+    pub address_w: Option<AddressMode>,
+    #[schemars(description = "Color returned by ClampToBorder (default: TransparentBlack)")]
+    pub border_color: Option<BorderColor>,
+    #[schemars(
+        description = "Enable anisotropic filtering up to this many samples (at least 1); requires the samplerAnisotropy feature"
+    )]
+    pub max_anisotropy: Option<f32>,
+    #[schemars(
+        description = "Make a shadow sampler (sampler2DShadow) comparing the reference value with the depth; only for depth textures"
+    )]
+    pub compare_op: Option<CompareOp>,
+}
 
-            writeln!(shader_test_file).map_err(io_err)?;
+impl SamplerOptions {
+    /// Options of vkrunner's texture command for this sampler state.
+    fn script_words(&self) -> String {
+        let mut words = String::new();
+
+        if self.mag_filter.is_some() || self.min_filter.is_some() || self.mipmap_filter.is_some() {
+            words.push_str(&format!(
+                " filter {} {} {}",
+                self.mag_filter.unwrap_or(TextureFilter::Linear),
+                self.min_filter.unwrap_or(TextureFilter::Linear),
+                self.mipmap_filter.unwrap_or(TextureFilter::Nearest)
+            ));
+        }
+        if let Some(lod_bias) = self.lod_bias {
+            words.push_str(&format!(" lod_bias {lod_bias}"));
+        }
+        if self.address_u.is_some() || self.address_v.is_some() || self.address_w.is_some() {
+            let address = |mode: Option<AddressMode>| mode.unwrap_or(AddressMode::Repeat);
+            words.push_str(&format!(
+                " address {} {} {}",
+                address(self.address_u),
+                address(self.address_v),
+                address(self.address_w)
+            ));
+        }
+        if let Some(border_color) = self.border_color {
+            words.push_str(&format!(" border {border_color}"));
+        }
+        if let Some(max_anisotropy) = self.max_anisotropy {
+            words.push_str(&format!(" anisotropy {max_anisotropy}"));
+        }
+        if let Some(compare_op) = self.compare_op {
+            words.push_str(&format!(" compare {compare_op}"));
         }
 
-        if let Some(vertex_data) = &request.vertex_data {
-            writeln!(shader_test_file, "[vertex data]").map_err(io_err)?;
+        words
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum TextureSource {
+    #[schemars(
+        description = "Uniformly random RGB texels with opaque alpha; the same seed gives the same texture"
+    )]
+    WhiteNoise { seed: Option<u64> },
+    #[schemars(
+        description = "Grayscale Perlin noise with scale lattice cells across the texture (default: 8), summing octaves (default: 1, max 8) of doubling frequency. Tiles seamlessly"
+    )]
+    Perlin {
+        seed: Option<u64>,
+        scale: Option<u32>,
+        octaves: Option<u32>,
+    },
+    #[schemars(
+        description = "Red is the u texture coordinate and green is v at each texel center (v grows downwards), blue 0, alpha 1"
+    )]
+    UvGradient,
+    #[schemars(
+        description = "Seven vertical 75% color bars from left to right: white, yellow, cyan, green, magenta, red, blue"
+    )]
+    ColorBars,
+    #[schemars(
+        description = "Checkerboard of size x size texel squares, starting with first (default: white) at the top left; colors are R, G, B, A (0-1)"
+    )]
+    Checkerboard {
+        size: u32,
+        first: Option<[f64; 4]>,
+        second: Option<[f64; 4]>,
+    },
+    #[schemars(
+        description = "Image stored by upload_texture under this tex-... ID; width and height must match its size"
+    )]
+    Uploaded { texture_id: String },
+    #[schemars(
+        description = "Cubemap faces where each texel is the unit direction from the cube center through it, mapped from -1..1 to 0..1 like a normal map; alpha 1. Only for Cube textures"
+    )]
+    CubeDirections,
+    #[schemars(
+        description = "One source per layer of an array texture or per face of a cubemap (+X, -X, +Y, -Y, +Z, -Z); sources can't be Layers themselves"
+    )]
+    Layers { sources: Vec<TextureSource> },
+}
+
+/// Largest width or height of a generated texture.
+const MAX_TEXTURE_SIZE: u32 = 4096;
+
+/// Pixels of the largest image run_image_filter packs into a buffer.
+const MAX_FILTER_PIXELS: u32 = 1024 * 1024;
+
+/// Most layers of an array texture, the smallest `maxImageArrayLayers`
+/// a Vulkan implementation may report.
+const MAX_TEXTURE_LAYERS: u32 = 256;
+
+/// SplitMix64 step, the seeded generator behind the noise textures.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// 2D gradient noise in roughly -1..1 with `period` lattice cells per
+/// unit of `x` and `y` before it repeats.
+fn perlin_noise(x: f64, y: f64, period: u32, seed: u64) -> f64 {
+    let gradient = |ix: u32, iy: u32| {
+        let mut state = seed ^ (u64::from(ix % period) << 32 | u64::from(iy % period));
+        let angle =
+            (splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64 * std::f64::consts::TAU;
+        (angle.cos(), angle.sin())
+    };
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let corner = |dx: u32, dy: u32| {
+        let (gx, gy) = gradient(x0 as u32 + dx, y0 as u32 + dy);
+        gx * (fx - f64::from(dx)) + gy * (fy - f64::from(dy))
+    };
+
+    let (u, v) = (fade(fx), fade(fy));
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * u;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * u;
+    (top + (bottom - top) * v) * std::f64::consts::SQRT_2
+}
+
+/// Unit direction through the point `(s, t)` (both -1..1) of a cube
+/// face, the inverse of Vulkan's cube map face selection.
+fn cube_direction(face: u32, s: f64, t: f64) -> [f64; 3] {
+    let direction = match face {
+        0 => [1.0, -t, -s],
+        1 => [-1.0, -t, s],
+        2 => [s, 1.0, t],
+        3 => [s, -1.0, -t],
+        4 => [s, -t, 1.0],
+        _ => [-s, -t, -1.0],
+    };
+    let length = direction.iter().map(|c| c * c).sum::<f64>().sqrt();
+    direction.map(|c| c / length)
+}
+
+/// Checks a texture test's size and source before anything is written.
+fn check_texture(
+    context: &str,
+    width: u32,
+    height: u32,
+    kind: TextureKind,
+    depth: bool,
+    sampler: Option<&SamplerOptions>,
+    source: &TextureSource,
+) -> Result<(), McpError> {
+    let invalid = |message: String| McpError::invalid_params(format!("{context}: {message}"), None);
+
+    if !(1..=MAX_TEXTURE_SIZE).contains(&width) || !(1..=MAX_TEXTURE_SIZE).contains(&height) {
+        return Err(invalid(format!(
+            "{width}x{height} is not between 1x1 and {MAX_TEXTURE_SIZE}x{MAX_TEXTURE_SIZE}"
+        )));
+    }
+    if !(1..=MAX_TEXTURE_LAYERS).contains(&kind.layers()) {
+        return Err(invalid(format!(
+            "{} layers is not between 1 and {MAX_TEXTURE_LAYERS}",
+            kind.layers()
+        )));
+    }
+    if kind == TextureKind::Cube && width != height {
+        return Err(invalid(format!(
+            "cube faces must be square, not {width}x{height}"
+        )));
+    }
+    if let Some(sampler) = sampler {
+        if sampler.compare_op.is_some() && !depth {
+            return Err(invalid("compare_op needs a depth texture".to_string()));
+        }
+        if sampler.max_anisotropy.is_some_and(|max| max < 1.0) {
+            return Err(invalid("max_anisotropy must be at least 1".to_string()));
+        }
+    }
+
+    let sources = match source {
+        TextureSource::Layers { sources } if sources.len() != kind.layers() as usize => {
+            return Err(invalid(format!(
+                "{} layer sources for {} layers",
+                sources.len(),
+                kind.layers()
+            )));
+        }
+        TextureSource::Layers { sources } => sources.iter().collect(),
+        source => vec![source],
+    };
+
+    for source in sources {
+        match source {
+            TextureSource::Layers { .. } => {
+                return Err(invalid("Layers sources can't be nested".to_string()));
+            }
+            TextureSource::CubeDirections if kind != TextureKind::Cube => {
+                return Err(invalid(
+                    "CubeDirections is only for Cube textures".to_string(),
+                ));
+            }
+            TextureSource::Uploaded { texture_id } => {
+                let (uploaded_width, uploaded_height) = uploaded_texture(texture_id)?.dimensions();
+                if (uploaded_width, uploaded_height) != (width, height) {
+                    return Err(invalid(format!(
+                        "{texture_id} is {uploaded_width}x{uploaded_height}, not {width}x{height}"
+                    )));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+impl TextureSource {
+    /// RGBA8 texels of one layer (or cube face), row by row from the
+    /// top, as vkrunner's texture command reads them. Noise sources
+    /// give every layer different texels, and use `default_seed` unless
+    /// they set their own.
+    fn texels(
+        &self,
+        width: u32,
+        height: u32,
+        layer: u32,
+        default_seed: u64,
+    ) -> Result<Vec<u8>, McpError> {
+        let unorm = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let mut texels = Vec::with_capacity(width as usize * height as usize * 4);
+        let layer_seed = |seed: &Option<u64>| seed.unwrap_or(default_seed) ^ u64::from(layer) << 32;
+
+        match self {
+            TextureSource::Layers { sources } => {
+                return sources[layer as usize].texels(width, height, layer, default_seed);
+            }
+            TextureSource::WhiteNoise { seed } => {
+                let mut state = layer_seed(seed);
+                for _ in 0..width * height {
+                    let [r, g, b, ..] = splitmix64(&mut state).to_le_bytes();
+                    texels.extend([r, g, b, 255]);
+                }
+                return Ok(texels);
+            }
+            TextureSource::Uploaded { texture_id } => {
+                return Ok(uploaded_texture(texture_id)?.into_raw());
+            }
+            _ => {}
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let u = (f64::from(x) + 0.5) / f64::from(width);
+                let v = (f64::from(y) + 0.5) / f64::from(height);
+                let color = match self {
+                    TextureSource::WhiteNoise { .. }
+                    | TextureSource::Uploaded { .. }
+                    | TextureSource::Layers { .. } => unreachable!(),
+                    TextureSource::Perlin {
+                        seed,
+                        scale,
+                        octaves,
+                    } => {
+                        let scale = scale.unwrap_or(8).max(1);
+                        let (mut sum, mut total) = (0.0, 0.0);
+                        for octave in 0..octaves.unwrap_or(1).clamp(1, 8) {
+                            let period = scale << octave;
+                            let amplitude = 0.5f64.powi(octave as i32);
+                            sum += amplitude
+                                * perlin_noise(
+                                    u * f64::from(period),
+                                    v * f64::from(period),
+                                    period,
+                                    layer_seed(seed).wrapping_add(u64::from(octave)),
+                                );
+                            total += amplitude;
+                        }
+                        let gray = sum / total * 0.5 + 0.5;
+                        [gray, gray, gray, 1.0]
+                    }
+                    TextureSource::UvGradient => [u, v, 0.0, 1.0],
+                    TextureSource::CubeDirections => {
+                        let [x, y, z] = cube_direction(layer, u * 2.0 - 1.0, v * 2.0 - 1.0);
+                        [x * 0.5 + 0.5, y * 0.5 + 0.5, z * 0.5 + 0.5, 1.0]
+                    }
+                    TextureSource::ColorBars => {
+                        const BARS: [[f64; 3]; 7] = [
+                            [0.75, 0.75, 0.75],
+                            [0.75, 0.75, 0.0],
+                            [0.0, 0.75, 0.75],
+                            [0.0, 0.75, 0.0],
+                            [0.75, 0.0, 0.75],
+                            [0.75, 0.0, 0.0],
+                            [0.0, 0.0, 0.75],
+                        ];
+                        let [r, g, b] = BARS[(x as usize * BARS.len()) / width as usize];
+                        [r, g, b, 1.0]
+                    }
+                    TextureSource::Checkerboard {
+                        size,
+                        first,
+                        second,
+                    } => {
+                        let size = (*size).max(1);
+                        if (x / size + y / size) & 1 == 0 {
+                            first.unwrap_or([1.0; 4])
+                        } else {
+                            second.unwrap_or([0.0, 0.0, 0.0, 1.0])
+                        }
+                    }
+                };
+                texels.extend(color.map(unorm));
+            }
+        }
+
+        Ok(texels)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum ExpectedPattern {
+    #[schemars(description = "Every pixel has this R, G, B color (0-1)")]
+    Constant { color: [f64; 3] },
+    #[schemars(
+        description = "Linear ramp from the left (top if vertical) edge to the opposite one, sampled at pixel centers"
+    )]
+    Gradient {
+        from: [f64; 3],
+        to: [f64; 3],
+        vertical: Option<bool>,
+    },
+    #[schemars(
+        description = "Checkerboard of size x size pixel squares, starting with first at the top left"
+    )]
+    Checker {
+        size: u32,
+        first: [f64; 3],
+        second: [f64; 3],
+    },
+    #[schemars(
+        description = "Image file with the same dimensions, compared with the output image as it is saved"
+    )]
+    Reference { path: String },
+}
+
+impl ExpectedPattern {
+    /// The expected color of an analytic pattern at a pixel.
+    fn color_at(&self, x: u32, y: u32, width: u32, height: u32) -> Option<[f64; 3]> {
+        match self {
+            ExpectedPattern::Constant { color } => Some(*color),
+            ExpectedPattern::Gradient { from, to, vertical } => {
+                let t = if *vertical == Some(true) {
+                    (f64::from(y) + 0.5) / f64::from(height)
+                } else {
+                    (f64::from(x) + 0.5) / f64::from(width)
+                };
+                Some([0, 1, 2].map(|c| from[c] + (to[c] - from[c]) * t))
+            }
+            ExpectedPattern::Checker {
+                size,
+                first,
+                second,
+            } => {
+                let size = (*size).max(1);
+                Some(if (x / size + y / size) & 1 == 0 {
+                    *first
+                } else {
+                    *second
+                })
+            }
+            ExpectedPattern::Reference { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ExpectedImage {
+    #[schemars(
+        description = "What the output should look like. Analytic colors are in probe_color_space, like probe colors"
+    )]
+    pub pattern: ExpectedPattern,
+    #[schemars(
+        description = "Largest per-channel difference (0-1) that still counts as matching (default: 0.01)"
+    )]
+    pub tolerance: Option<f64>,
+}
+
+impl ExpectedImage {
+    /// Per-pixel deviation from the expectation, row by row: the largest
+    /// absolute channel difference in 0-1.
+    fn deviations(
+        &self,
+        img: &RgbImage,
+        color_space: ColorSpace,
+        probe_color_space: ColorSpace,
+    ) -> Result<Vec<f64>, String> {
+        let (width, height) = img.dimensions();
+        let channel = |value: u8| f64::from(value) / 255.0;
+
+        if let ExpectedPattern::Reference { path } = &self.pattern {
+            let reference = image::open(path)
+                .map_err(|e| format!("Failed to read reference image {path}: {e}"))?
+                .to_rgb8();
+            if reference.dimensions() != (width, height) {
+                return Err(format!(
+                    "Reference image {path} is {}x{} but the output is {width}x{height}",
+                    reference.width(),
+                    reference.height()
+                ));
+            }
+
+            return Ok(color_space
+                .to_display(img)
+                .pixels()
+                .zip(reference.pixels())
+                .map(|(observed, expected)| {
+                    (0..3)
+                        .map(|c| (channel(observed[c]) - channel(expected[c])).abs())
+                        .fold(0.0, f64::max)
+                })
+                .collect());
+        }
+
+        Ok(img
+            .enumerate_pixels()
+            .map(|(x, y, observed)| {
+                let expected = self
+                    .pattern
+                    .color_at(x, y, width, height)
+                    .unwrap_or_default();
+                (0..3)
+                    .map(|c| {
+                        let expected = probe_color_space.convert(expected[c], color_space);
+                        (channel(observed[c]) - expected).abs()
+                    })
+                    .fold(0.0, f64::max)
+            })
+            .collect())
+    }
+}
+
+/// False-color image of per-pixel deviations: black within `tolerance`,
+/// then blue through green and yellow to red at the largest deviation.
+fn deviation_heatmap(deviations: &[f64], width: u32, tolerance: f64) -> RgbImage {
+    const RAMP: [[f64; 3]; 4] = [
+        [0.0, 0.0, 255.0],
+        [0.0, 255.0, 0.0],
+        [255.0, 255.0, 0.0],
+        [255.0, 0.0, 0.0],
+    ];
+    let max = deviations.iter().copied().fold(0.0, f64::max);
+    let height = deviations.len() as u32 / width.max(1);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let deviation = deviations[(y * width + x) as usize];
+        if deviation <= tolerance {
+            return image::Rgb([0, 0, 0]);
+        }
+
+        let t = if max > tolerance {
+            (deviation - tolerance) / (max - tolerance) * (RAMP.len() - 1) as f64
+        } else {
+            0.0
+        };
+        let segment = (t as usize).min(RAMP.len() - 2);
+        let t = t - segment as f64;
+        image::Rgb([0, 1, 2].map(|c| {
+            (RAMP[segment][c] + (RAMP[segment + 1][c] - RAMP[segment][c]) * t).round() as u8
+        }))
+    })
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct PixelRegion {
+    #[schemars(description = "Left edge in pixels, from the left of the image")]
+    pub x: u32,
+    #[schemars(description = "Top edge in pixels, from the top of the image")]
+    pub y: u32,
+    #[schemars(description = "Width in pixels")]
+    pub width: u32,
+    #[schemars(description = "Height in pixels")]
+    pub height: u32,
+}
+
+impl PixelRegion {
+    /// Lists the region's pixels row by row as `R,G,B` byte triples.
+    fn describe(&self, img: &RgbImage) -> Result<String, String> {
+        let PixelRegion {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+
+        if width == 0
+            || height == 0
+            || x.saturating_add(width) > img.width()
+            || y.saturating_add(height) > img.height()
+        {
+            return Err(format!(
+                "crop region {width}x{height} at ({x}, {y}) is not inside the {}x{} image",
+                img.width(),
+                img.height()
+            ));
+        }
+
+        let mut text = format!(
+            "Pixels of the {width}x{height} region at ({x}, {y}) as R,G,B, one row per line:\n"
+        );
+        for row in y..y + height {
+            let pixels = (x..x + width)
+                .map(|column| {
+                    let [r, g, b] = img.get_pixel(column, row).0;
+                    format!("{r},{g},{b}")
+                })
+                .collect::<Vec<_>>();
+            text.push_str(&format!("y={row}: {}\n", pixels.join(" ")));
+        }
+
+        Ok(text)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct EnvironmentVariable {
+    #[schemars(
+        description = "Variable name; one of MESA_DEBUG, MESA_LOG_LEVEL, RADV_DEBUG, ACO_DEBUG, ANV_DEBUG, INTEL_DEBUG, NIR_DEBUG, LP_DEBUG, VK_LOADER_DEBUG, VK_INSTANCE_LAYERS, MVK_CONFIG_LOG_LEVEL"
+    )]
+    pub name: String,
+    #[schemars(description = "Value to set for this run only")]
+    pub value: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct BufferDump {
+    #[schemars(description = "Binding of the UBO/SSBO to dump (default: first buffer)")]
+    pub binding: Option<u32>,
+    #[schemars(description = "Path under /tmp where the raw buffer contents will be saved")]
+    pub path: String,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct VkrunnerOptions {
+    #[schemars(description = "Don't print non-error information (--quiet)")]
+    pub quiet: Option<bool>,
+    #[schemars(
+        description = "Print the SPIR-V disassembly of every shader in the output (--disasm)"
+    )]
+    pub show_disassembly: Option<bool>,
+    #[schemars(
+        description = "Create compute shaders as VK_EXT_shader_object objects instead of pipelines; the run is skipped on devices without the shaderObject feature (--separate-shader-objects)"
+    )]
+    pub separate_shader_objects: Option<bool>,
+    #[schemars(
+        description = "1-based index of the Vulkan device to run on, as listed by vulkaninfo (--device-id; default: the server's)"
+    )]
+    pub device_id: Option<u32>,
+    #[schemars(description = "Token replacements applied to the script (--replace TOK=REPL)")]
+    pub replacements: Option<Vec<TokenReplacement>>,
+    #[schemars(description = "Dump the final contents of a UBO/SSBO to a file (--buffer)")]
+    pub buffer_dump: Option<BufferDump>,
+    #[schemars(
+        description = "Driver debug environment variables for the vkrunner process; its stderr is then always included in the result"
+    )]
+    pub environment: Option<Vec<EnvironmentVariable>>,
+}
+
+impl VkrunnerOptions {
+    // The vkrunner switches for everything except the device, which
+    // falls back to the server's default
+    fn args(&self, buffer_dump_path: Option<&str>) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if self.quiet == Some(true) {
+            args.push("--quiet".to_string());
+        }
+        if self.show_disassembly == Some(true) {
+            args.push("--disasm".to_string());
+        }
+        if self.separate_shader_objects == Some(true) {
+            args.push("--separate-shader-objects".to_string());
+        }
+        for replacement in self.replacements.iter().flatten() {
+            args.push(format!(
+                "--replace={}={}",
+                replacement.token, replacement.replacement
+            ));
+        }
+        if let (Some(dump), Some(path)) = (&self.buffer_dump, buffer_dump_path) {
+            args.push(format!("--buffer={path}"));
+            if let Some(binding) = dump.binding {
+                args.push(format!("--binding={binding}"));
+            }
+        }
+
+        args
+    }
+
+    fn validate_environment(&self) -> Result<(), McpError> {
+        for variable in self.environment.iter().flatten() {
+            if !ALLOWED_ENV_VARS.contains(&variable.name.as_str()) {
+                return Err(ErrorCode::NotAllowed.invalid_params(
+                    format!("environment variable {} is not allowed", variable.name),
+                    Some(json!({"allowed": ALLOWED_ENV_VARS})),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub enum ShaderLanguage {
+    #[schemars(description = "OpenGL Shading Language (default)")]
+    Glsl,
+    #[schemars(description = "HLSL compiled through glslang's HLSL front end (like glslc -x hlsl)")]
+    Hlsl,
+}
+
+fn is_version_or_extension(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("#version") || line.starts_with("#extension")
+}
+
+/// Where a line of the source handed to the compiler came from, so that
+/// diagnostics can point at the lines the client wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineOrigin {
+    /// 1-based line of the request's source.
+    Source(usize),
+    /// 1-based line of the library with the given index.
+    Library(usize, usize),
+    /// Boilerplate added by the server.
+    Injected,
+}
+
+/// Source lines tagged with their origin.
+type TaggedLines = Vec<(LineOrigin, String)>;
+
+fn tag_lines(source: &str, origin: impl Fn(usize) -> LineOrigin) -> TaggedLines {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (origin(i + 1), line.to_string()))
+        .collect()
+}
+
+fn join_lines(lines: &[(LineOrigin, String)]) -> String {
+    lines
+        .iter()
+        .map(|(_, line)| line.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Rewrites `name:LINE:` locations in compiler output from lines of the
+/// compiled source to lines of the source or library they came from.
+fn map_diagnostics(output: &str, name: &str, origins: &[LineOrigin]) -> String {
+    output
+        .lines()
+        .map(|line| {
+            let Some((number, rest)) = line
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .and_then(|rest| rest.split_once(':'))
+            else {
+                return line.to_string();
+            };
+            let Some(origin) = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| origins.get(number.wrapping_sub(1)))
+            else {
+                return line.to_string();
+            };
+
+            match origin {
+                LineOrigin::Source(line) => format!("{name}:{line}:{rest}"),
+                LineOrigin::Library(index, line) => format!("library {index}:{line}:{rest}"),
+                LineOrigin::Injected => format!("{name}:{number} (added by the server):{rest}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Links helper translation units into a shader by concatenation. The
+/// libraries are placed after the shader's own directives; their
+/// `#extension` lines are hoisted and deduplicated and their `#version`
+/// lines are dropped.
+fn link_sources(source: &str, libraries: &[String]) -> TaggedLines {
+    let mut lines = tag_lines(source, LineOrigin::Source);
+    let split = lines
+        .iter()
+        .rposition(|(_, line)| is_version_or_extension(line))
+        .map_or(0, |line| line + 1);
+
+    let normalize = |line: &str| line.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut seen = lines[..split]
+        .iter()
+        .map(|(_, line)| normalize(line))
+        .collect::<std::collections::HashSet<_>>();
+
+    let mut directives = Vec::new();
+    let mut bodies = Vec::new();
+
+    for (i, library) in libraries.iter().enumerate() {
+        bodies.push((LineOrigin::Injected, format!("// library {i}")));
+
+        for (origin, line) in tag_lines(library, |line| LineOrigin::Library(i, line)) {
+            let trimmed = line.trim_start();
+
+            if trimmed.starts_with("#version") {
+                continue;
+            }
+
+            if trimmed.starts_with("#extension") {
+                if seen.insert(normalize(&line)) {
+                    directives.push((origin, line));
+                }
+                continue;
+            }
+
+            bodies.push((origin, line));
+        }
+    }
+
+    let rest = lines.split_off(split);
+    lines.extend(directives);
+    lines.extend(bodies);
+    lines.extend(rest);
+    lines
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct SourceHeader {
+    #[schemars(
+        description = "GLSL version to declare when the source has no #version line (default: 450)"
+    )]
+    pub version: Option<String>,
+    #[schemars(
+        description = "Extensions to enable with '#extension NAME : require' unless the source already mentions them"
+    )]
+    pub extensions: Option<Vec<String>>,
+    #[schemars(
+        description = "Declare 'precision highp float; precision highp int;' when the source has no precision statements"
+    )]
+    pub default_precision: Option<bool>,
+}
+
+impl SourceHeader {
+    /// Adds whatever boilerplate the source is missing right after its
+    /// `#version` line (or at the top if it has none).
+    fn apply(&self, lines: TaggedLines) -> TaggedLines {
+        let version_line = lines
+            .iter()
+            .position(|(_, line)| line.trim_start().starts_with("#version"));
+
+        let mut directives = Vec::new();
+
+        if version_line.is_none() {
+            directives.push((
+                LineOrigin::Injected,
+                format!("#version {}", self.version.as_deref().unwrap_or("450")),
+            ));
+        }
+
+        for extension in self.extensions.iter().flatten() {
+            let declared = lines.iter().any(|(_, line)| {
+                let mut tokens = line.split_whitespace();
+                tokens.next() == Some("#extension")
+                    && tokens
+                        .next()
+                        .is_some_and(|name| name.trim_end_matches(':') == extension.as_str())
+            });
+
+            if !declared {
+                directives.push((
+                    LineOrigin::Injected,
+                    format!("#extension {extension} : require"),
+                ));
+            }
+        }
+
+        let insert_at = version_line.map_or(0, |line| line + 1);
+        let mut result = lines;
+        result.splice(insert_at..insert_at, directives);
+
+        if self.default_precision == Some(true)
+            && !result
+                .iter()
+                .any(|(_, line)| line.trim_start().starts_with("precision "))
+        {
+            // Precision statements are declarations, so they have to
+            // follow every #extension directive.
+            let after_directives = result
+                .iter()
+                .rposition(|(_, line)| {
+                    let line = line.trim_start();
+                    line.starts_with("#extension") || line.starts_with("#version")
+                })
+                .map_or(0, |line| line + 1);
+            result.splice(
+                after_directives..after_directives,
+                [
+                    (LineOrigin::Injected, "precision highp float;".to_string()),
+                    (LineOrigin::Injected, "precision highp int;".to_string()),
+                ],
+            );
+        }
+
+        result
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct MacroDefinition {
+    #[schemars(description = "Macro name")]
+    pub name: String,
+    #[schemars(description = "Optional macro value (like glslc -DNAME=VALUE)")]
+    pub value: Option<String>,
+}
+
+impl std::fmt::Display for MacroDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.name, value),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct CompileRequest {
+    #[schemars(description = "The shader stage to compile (vert, frag, comp, geom, tesc, tese)")]
+    pub stage: ShaderStage,
+    #[schemars(description = "GLSL shader source code to compile")]
+    pub source: String,
+    #[schemars(
+        description = "Path where compiled SPIR-V assembly (.spvasm) will be saved. Omit it to let the server name the artifact by content hash: passes then reference it as request:<index> (request:<index>/<variant> for define variants) and later calls by the returned spv-... artifact ID"
+    )]
+    pub tmp_output_path: Option<String>,
+    #[schemars(
+        description = "Optional boilerplate (#version, #extension, precision) to add when the source lacks it"
+    )]
+    pub header: Option<SourceHeader>,
+    #[schemars(description = "Source language of the shader (default: Glsl)")]
+    pub language: Option<ShaderLanguage>,
+    #[schemars(
+        description = "Entry point function name (default: main, like glslc -fentry-point). Non-main entry points also need a matching *Entrypoint test command"
+    )]
+    pub entry_point: Option<String>,
+    #[schemars(
+        description = "Use HLSL register-based binding assignment (like glslc -fhlsl-iomap)"
+    )]
+    pub hlsl_io_mapping: Option<bool>,
+    #[schemars(
+        description = "Assign locations to inputs/outputs without explicit locations (like glslc -fauto-map-locations)"
+    )]
+    pub auto_map_locations: Option<bool>,
+    #[schemars(
+        description = "Assign bindings to resources without explicit bindings (like glslc -fauto-bind-uniforms)"
+    )]
+    pub auto_bind_uniforms: Option<bool>,
+    #[schemars(description = "Macros defined for every compilation of this shader")]
+    pub defines: Option<Vec<MacroDefinition>>,
+    #[schemars(
+        description = "Optional macro sets; each one produces a separate artifact named <stem>_variant<N>.spvasm (N is the index in this list) instead of tmp_output_path"
+    )]
+    pub define_variants: Option<Vec<Vec<MacroDefinition>>>,
+    #[schemars(
+        description = "Optional helper translation units (e.g. function libraries) linked into this shader. Their #version lines are dropped and #extension lines deduplicated and hoisted above all code"
+    )]
+    pub libraries: Option<Vec<String>>,
+    #[schemars(
+        description = "Include the SPIR-V assembly text in the response, for clients that cannot read the server's /tmp (default: false)"
+    )]
+    pub include_disassembly: Option<bool>,
+    /// The assertion helper, which check_assertions injects into
+    /// compute shaders.
+    #[serde(skip)]
+    pub assertions: Option<assertions::Assertions>,
+}
+impl CompileRequest {
+    /// Compiles GLSL `source` for `stage` with the default options.
+    fn new(stage: ShaderStage, source: String) -> Self {
+        CompileRequest {
+            stage,
+            source,
+            tmp_output_path: None,
+            header: None,
+            language: None,
+            entry_point: None,
+            hlsl_io_mapping: None,
+            auto_map_locations: None,
+            auto_bind_uniforms: None,
+            defines: None,
+            define_variants: None,
+            libraries: None,
+            include_disassembly: None,
+            assertions: None,
+        }
+    }
+
+    fn output_path(&self) -> Option<String> {
+        self.tmp_output_path.as_deref().map(tmp_path)
+    }
+
+    /// Lists every artifact this request produces along with the macros
+    /// it is compiled with. Variants are named `<stem>_variant<N>.<ext>`;
+    /// the path is `None` when the server names artifacts by content.
+    fn variant_outputs(&self) -> Vec<(Option<String>, Vec<MacroDefinition>)> {
+        let base_defines = self.defines.clone().unwrap_or_default();
+        let output_path = self.output_path();
+
+        let Some(variants) = &self.define_variants else {
+            return vec![(output_path, base_defines)];
+        };
+
+        let Some(output_path) = output_path else {
+            return variants
+                .iter()
+                .map(|variant| {
+                    let mut defines = base_defines.clone();
+                    defines.extend(variant.iter().cloned());
+                    (None, defines)
+                })
+                .collect();
+        };
+
+        let path = Path::new(&output_path);
+        let stem = path.with_extension("");
+        let extension = path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+
+        variants
+            .iter()
+            .enumerate()
+            .map(|(i, variant)| {
+                let mut defines = base_defines.clone();
+                defines.extend(variant.iter().cloned());
+                (
+                    Some(format!("{}_variant{i}{extension}", stem.display())),
+                    defines,
+                )
+            })
+            .collect()
+    }
+
+    /// The source as it is compiled: with libraries linked, the header
+    /// applied and any assertion helper added.
+    fn tagged_source(&self) -> TaggedLines {
+        let mut lines = match &self.libraries {
+            Some(libraries) => link_sources(&self.source, libraries),
+            None => tag_lines(&self.source, LineOrigin::Source),
+        };
+        // The boilerplate is GLSL-specific so it is never added to HLSL
+        if let (Some(header), ShaderLanguage::Glsl) =
+            (&self.header, self.language.unwrap_or(ShaderLanguage::Glsl))
+        {
+            lines = header.apply(lines);
+        }
+        if let (Some(assertions), ShaderLanguage::Glsl) = (
+            &self.assertions,
+            self.language.unwrap_or(ShaderLanguage::Glsl),
+        ) {
+            lines = assertions.inject(lines);
+        }
+        lines
+    }
+
+    /// File name the compiler reports diagnostics against.
+    fn source_name(&self) -> &'static str {
+        match self.language.unwrap_or(ShaderLanguage::Glsl) {
+            ShaderLanguage::Glsl => "shader.glsl",
+            ShaderLanguage::Hlsl => "shader.hlsl",
+        }
+    }
+
+    /// Analysis warnings for the compiled SPIR-V, in the format of
+    /// compiler diagnostics and with lines of the client's source.
+    fn analysis_warnings(&self, spvasm: &str) -> Vec<String> {
+        let source_name = self.source_name();
+        let origins = self
+            .tagged_source()
+            .iter()
+            .map(|(origin, _)| *origin)
+            .collect::<Vec<_>>();
+
+        analysis::warnings(&spirv::Module::parse(spvasm))
+            .into_iter()
+            .map(|warning| match warning.location {
+                Some(location) if location.file == source_name => map_diagnostics(
+                    &format!(
+                        "{source_name}:{}: warning: {}",
+                        location.line, warning.message
+                    ),
+                    source_name,
+                    &origins,
+                ),
+                _ => format!("warning: {}", warning.message),
+            })
+            .collect()
+    }
+
+    /// Compiles every variant with `compile`, saves the ones that
+    /// compiled and adds a line about each to `reports`. `compile` gets
+    /// the variant's index and defines and returns the compiler's result
+    /// with a note on how it was obtained, such as its timing. Returns
+    /// whether every variant compiled.
+    fn compile_variants(
+        &self,
+        reports: &mut Vec<String>,
+        mut compile: impl FnMut(
+            usize,
+            &[MacroDefinition],
+        ) -> Result<(Result<String, String>, Option<String>), McpError>,
+    ) -> Result<bool, McpError> {
+        let mut compiled = true;
+
+        for (variant, (output_path, defines)) in self.variant_outputs().iter().enumerate() {
+            let label = if self.define_variants.is_some() {
+                format!("- variant {variant}")
+            } else {
+                "-".to_string()
+            };
+
+            let (result, note) = compile(variant, defines)?;
+            let note = note.map(|note| format!(" ({note})")).unwrap_or_default();
+            let spvasm = match result {
+                Ok(spvasm) => spvasm,
+                Err(message) => {
+                    reports.push(format!("{label} failed{note}:\n{message}"));
+                    compiled = false;
+                    continue;
+                }
+            };
+
+            let (path, name) = match output_path {
+                Some(path) => (path.clone(), path.clone()),
+                None => {
+                    let id = artifact_id(&spvasm);
+                    let path = artifact_path(&id);
+                    let name = format!("artifact {id} ({path})");
+                    (path, name)
+                }
+            };
+            write_spvasm(&path, &spvasm)?;
+            reports.push(format!("{label} compiled to {name}{note}"));
+            for warning in self.analysis_warnings(&spvasm) {
+                reports.push(format!("  {warning}"));
+            }
+
+            if self.include_disassembly.unwrap_or(false) {
+                reports.push(format!("Disassembly of {path}:\n{spvasm}\n"));
+            }
+        }
+
+        Ok(compiled)
+    }
+
+    /// Compiles the shader to SPIR-V assembly. The inner error is a
+    /// report of the compiler diagnostics meant for the client.
+    fn compile(&self, defines: &[MacroDefinition]) -> Result<Result<String, String>, McpError> {
+        // Create compiler and options
+        let mut compiler = Compiler::new().ok().ok_or_else(|| {
+            McpError::internal_error(
+                "Failed to create shaderc compiler",
+                Some(json!({"error": "Could not instantiate shaderc compiler"})),
+            )
+        })?;
+
+        let mut options = CompileOptions::new().ok().ok_or_else(|| {
+            McpError::internal_error(
+                "Failed to create shaderc compile options",
+                Some(json!({"error": "Could not create compiler options"})),
+            )
+        })?;
+
+        // Set options equivalent to the CLI flags
+        options.set_target_env(
+            shaderc::TargetEnv::Vulkan,
+            shaderc::EnvVersion::Vulkan1_4 as u32,
+        );
+        options.set_optimization_level(OptimizationLevel::Performance);
+        options.set_generate_debug_info();
+
+        let language = self.language.unwrap_or(ShaderLanguage::Glsl);
+        if language == ShaderLanguage::Hlsl {
+            options.set_source_language(shaderc::SourceLanguage::HLSL);
+        }
+        if self.hlsl_io_mapping == Some(true) {
+            options.set_hlsl_io_mapping(true);
+        }
+        if self.auto_map_locations == Some(true) {
+            options.set_auto_map_locations(true);
+        }
+        if self.auto_bind_uniforms == Some(true) {
+            options.set_auto_bind_uniforms(true);
+        }
+        for define in defines {
+            options.add_macro_definition(&define.name, define.value.as_deref());
+        }
+
+        let lines = self.tagged_source();
+        let source = join_lines(&lines);
+        let entry_point = self.entry_point.as_deref().unwrap_or("main");
+        let source_name = self.source_name();
+
+        // Compile to SPIR-V assembly
+        let artifact = match compiler.compile_into_spirv_assembly(
+            &source,
+            self.stage.shader_kind(),
+            source_name, // source name for error reporting
+            entry_point,
+            Some(&options),
+        ) {
+            Ok(artifact) => artifact,
+            Err(e) => {
+                let defines = if defines.is_empty() {
+                    String::new()
+                } else {
+                    let defines = defines
+                        .iter()
+                        .map(MacroDefinition::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!(" (defines: {defines})")
+                };
+
+                // Line numbers refer to the client's source, so that is
+                // what gets shown
+                let origins = lines.iter().map(|(origin, _)| *origin).collect::<Vec<_>>();
+                return Ok(Err(format!(
+                    "Shader compilation failed for {} shader{}:\n\nError:\n{}\n\nShader Source:\n{}\n",
+                    self.stage.display_name(),
+                    defines,
+                    map_diagnostics(&e.to_string(), source_name, &origins),
+                    self.source
+                )));
+            }
+        };
+
+        // Filter out lines that the vkrunner assembler doesn't support
+        let spv_text = artifact.as_text();
+        Ok(Ok(spv_text
+            .lines()
+            .filter(|l| !l.trim_start().starts_with("OpModuleProcessed"))
+            .collect::<Vec<_>>()
+            .join("\n")))
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    schemars::JsonSchema,
+)]
+pub enum OrderingLint {
+    #[schemars(description = "Report ordering mistakes in the result but still run")]
+    #[default]
+    Warn,
+    #[schemars(description = "Reject the request when it has ordering mistakes")]
+    Fail,
+    #[schemars(description = "Skip the ordering checks")]
+    Off,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    schemars::JsonSchema,
+)]
+pub enum ColorSpace {
+    #[schemars(
+        description = "Values are sRGB-encoded, as stored by *_SRGB framebuffers or shaders that output display colors"
+    )]
+    #[default]
+    Srgb,
+    #[schemars(
+        description = "Values are linear light, as a shader writes them to a UNORM framebuffer"
+    )]
+    Linear,
+}
+
+impl ColorSpace {
+    /// Converts a value in this space to linear light.
+    fn decode(self, value: f64) -> f64 {
+        match self {
+            ColorSpace::Linear => value,
+            ColorSpace::Srgb if value <= 0.04045 => value / 12.92,
+            ColorSpace::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+        }
+    }
+
+    /// Converts a linear light value to this space.
+    fn encode(self, value: f64) -> f64 {
+        match self {
+            ColorSpace::Linear => value,
+            ColorSpace::Srgb if value <= 0.0031308 => value * 12.92,
+            ColorSpace::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+        }
+    }
+
+    fn convert(self, value: f64, to: ColorSpace) -> f64 {
+        to.encode(self.decode(value))
+    }
+
+    /// Re-encodes 8-bit framebuffer values as sRGB for viewing.
+    fn to_display(self, img: &RgbImage) -> RgbImage {
+        let mut display = img.clone();
+        if self != ColorSpace::Srgb {
+            for pixel in display.pixels_mut() {
+                pixel.0 = pixel.0.map(|c| {
+                    (self.convert(f64::from(c) / 255.0, ColorSpace::Srgb) * 255.0).round() as u8
+                });
+            }
+        }
+        display
+    }
+}
+
+/// Saves a captured framebuffer: EXR files get linear floats, other
+/// formats get sRGB-encoded 8-bit values. The metadata goes into the
+/// text chunks of PNG files and the header attributes of EXR files;
+/// other formats have no place for it.
+fn save_framebuffer(
+    img: &RgbImage,
+    path: &Path,
+    color_space: ColorSpace,
+    metadata: &[(&str, String)],
+) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("exr") => {
+            let channels =
+                exr::prelude::SpecificChannels::rgb(|position: exr::prelude::Vec2<usize>| {
+                    let [r, g, b] = img
+                        .get_pixel(position.x() as u32, position.y() as u32)
+                        .0
+                        .map(|c| color_space.decode(f64::from(c) / 255.0) as f32);
+                    (r, g, b)
+                });
+            let mut image = exr::prelude::Image::from_channels(
+                (img.width() as usize, img.height() as usize),
+                channels,
+            );
+            for (key, value) in metadata {
+                let (Some(key), Some(value)) = (
+                    exr::prelude::Text::new_or_none(key),
+                    exr::prelude::Text::new_or_none(value),
+                ) else {
+                    continue;
+                };
+                image
+                    .attributes
+                    .other
+                    .insert(key, exr::prelude::AttributeValue::Text(value));
+            }
+            exr::prelude::WritableImage::write(&image)
+                .to_file(path)
+                .map_err(|e| e.to_string())
+        }
+        Some("png") => {
+            let display = color_space.to_display(img);
+            let file = File::create(path).map_err(|e| e.to_string())?;
+            let mut encoder = png::Encoder::new(BufWriter::new(file), img.width(), img.height());
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            for (key, value) in metadata {
+                encoder
+                    .add_text_chunk(key.to_string(), value.clone())
+                    .map_err(|e| e.to_string())?;
+            }
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(display.as_raw()))
+                .map_err(|e| e.to_string())
+        }
+        _ => color_space
+            .to_display(img)
+            .save(path)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Where a saved output came from, so that golden-image tooling can
+/// tell later which request, device and driver produced it.
+struct RunMetadata {
+    request_hash: String,
+    device: Option<String>,
+    driver_version: Option<String>,
+    timestamp: String,
+    seed: u64,
+}
+
+impl RunMetadata {
+    /// Key-value pairs as written into the saved images.
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        let unknown = || "unknown".to_string();
+        vec![
+            ("Software", "shaderc-vkrunner-mcp".to_string()),
+            ("RequestHash", self.request_hash.clone()),
+            ("Device", self.device.clone().unwrap_or_else(unknown)),
+            (
+                "DriverVersion",
+                self.driver_version.clone().unwrap_or_else(unknown),
+            ),
+            ("Timestamp", self.timestamp.clone()),
+            ("Seed", self.seed.to_string()),
+        ]
+    }
+}
+
+/// The value of a `Name: value` line that vkrunner's --device-info
+/// prints.
+fn device_info_line(stdout: &str, name: &str) -> Option<String> {
+    stdout.lines().find_map(|line| {
+        line.strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix(": "))
+            .map(|value| value.trim().to_string())
+    })
+}
+
+/// The lines of vkrunner's --memory-usage report: the memory allocated
+/// for buffers and images, and each heap's usage against its budget
+/// (or why the budget is unknown).
+fn memory_usage_lines(stdout: &str) -> Vec<&str> {
+    stdout
+        .lines()
+        .filter(|line| {
+            ["Memory allocated: ", "Memory heap ", "Memory budget: "]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+        })
+        .collect()
+}
+
+/// `time` as an RFC 3339 UTC timestamp, e.g. 2025-01-31T12:00:00Z.
+fn rfc3339_utc(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since 1970-01-01 in the proleptic Gregorian
+    // calendar, counting 400-year eras from 0000-03-01
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60
+    )
+}
+
+/// Rewrites the expected color of a probe from `from` to `to`. The color
+/// is the last 3 or 4 numbers of the arguments; alpha is left alone.
+fn convert_probe_color(
+    format: &str,
+    args: &[String],
+    from: ColorSpace,
+    to: ColorSpace,
+) -> Vec<String> {
+    let numbers = args
+        .iter()
+        .flat_map(|arg| {
+            arg.split(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | ','))
+                .filter(|token| !token.is_empty())
+        })
+        .collect::<Vec<_>>();
+    let n_components = if format == "rgba" { 4 } else { 3 };
+
+    if from == to || numbers.len() < n_components {
+        return args.to_vec();
+    }
+
+    let (position, color) = numbers.split_at(numbers.len() - n_components);
+    let color = color
+        .iter()
+        .enumerate()
+        .map(|(i, value)| match value.parse::<f64>() {
+            Ok(value) if i < 3 => from.convert(value, to).to_string(),
+            _ => value.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    if position.is_empty() {
+        vec![color.join(" ")]
+    } else {
+        vec![
+            format!("({})", position.join(", ")),
+            format!("({})", color.join(", ")),
+        ]
+    }
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CompileShadersRequest {
+    #[schemars(description = "List of shader compile requests (produces SPIR-V assemblies)")]
+    pub requests: Vec<CompileRequest>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct IncrementalCompile {
+    #[schemars(
+        description = "ID of a source returned by an earlier compile_incremental call (src-...). When set, source is a unified diff against that source instead of the full text"
+    )]
+    pub base_source_id: Option<String>,
+    #[serde(flatten)]
+    pub request: CompileRequest,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CompileIncrementalRequest {
+    #[schemars(description = "Shaders to compile; each one is reported separately")]
+    pub shaders: Vec<IncrementalCompile>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct UploadTextureRequest {
+    #[schemars(
+        description = "Base64-encoded PNG or JPEG file; a data:image/...;base64, prefix is accepted"
+    )]
+    pub data: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct BisectShadersRequest {
+    #[schemars(
+        description = "The compile_run_shaders request to run every version against; its probes decide whether a version passes. Output images, image checks and scenes are not used"
+    )]
+    pub request: CompileRunShadersRequest,
+    #[schemars(description = "Index of the compile request whose shader varies (default: 0)")]
+    pub compile_request: Option<usize>,
+    #[schemars(
+        description = "Full sources of the shader in order, from a known-good version to a known-bad one"
+    )]
+    pub versions: Option<Vec<String>>,
+    #[schemars(
+        description = "Macros to define one more at a time instead of versions: version 0 defines none, version N the first N"
+    )]
+    pub flags: Option<Vec<String>>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct MutationTestRequest {
+    #[schemars(
+        description = "The compile_run_shaders request whose probes are tested; it must pass as is. Output images, image checks and scenes are not used"
+    )]
+    pub request: CompileRunShadersRequest,
+    #[schemars(
+        description = "Stage of the *Spirv pass to mutate (default: the first fragment or compute pass)"
+    )]
+    pub stage: Option<ShaderStage>,
+    #[schemars(description = "Most mutants to run, in instruction order (default: 32)")]
+    pub max_mutants: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ShaderCoverageRequest {
+    #[schemars(
+        description = "The compile_run_shaders request to measure; output images, image checks and scenes are not used"
+    )]
+    pub request: CompileRunShadersRequest,
+    #[schemars(
+        description = "Stage of the *Spirv pass to instrument (default: the first fragment or compute pass)"
+    )]
+    pub stage: Option<ShaderStage>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CheckAssertionsRequest {
+    #[schemars(
+        description = "The compile_run_shaders request whose GLSL compute shaders use the ASSERT macros; output images, image checks and scenes are not used"
+    )]
+    pub request: CompileRunShadersRequest,
+    #[schemars(
+        description = "Binding at set 0 of the buffer that records failures (default: one past the highest binding of the test commands)"
+    )]
+    pub binding: Option<u32>,
+    #[schemars(
+        description = "Failures recorded with their invocation and values; later ones are only counted (default: 64)"
+    )]
+    pub max_failures: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ClockStatisticsRequest {
+    #[schemars(
+        description = "The compile_run_shaders request whose shaders write clock deltas (clockARB or clockRealtimeEXT differences) to an SSBO, one 64-bit value or uvec2 (low, high) per invocation"
+    )]
+    pub request: CompileRunShadersRequest,
+    #[schemars(description = "Binding of the SSBO holding the clock values")]
+    pub binding: u32,
+    #[schemars(description = "Byte offset of the first value in the buffer (default: 0)")]
+    pub offset: Option<usize>,
+    #[schemars(
+        description = "Each invocation writes a start and an end clock instead of their difference (default: false)"
+    )]
+    pub start_end_pairs: Option<bool>,
+    #[schemars(description = "Number of slowest invocations to list (default: 5)")]
+    pub slowest: Option<usize>,
+}
+
+/// Summary of per-invocation clock deltas, in clock ticks.
+fn clock_statistics(deltas: &[u64]) -> Vec<String> {
+    let mut sorted = deltas.to_vec();
+    sorted.sort_unstable();
+    let count = sorted.len();
+    let percentile = |p: usize| sorted[((count - 1) * p).div_ceil(100)];
+    let mean = sorted.iter().map(|&delta| delta as f64).sum::<f64>() / count as f64;
+    let variance = sorted
+        .iter()
+        .map(|&delta| (delta as f64 - mean).powi(2))
+        .sum::<f64>()
+        / count as f64;
+
+    vec![
+        format!("- min: {}", sorted[0]),
+        format!("- median: {}", percentile(50)),
+        format!("- mean: {mean:.1}"),
+        format!("- p90: {}", percentile(90)),
+        format!("- p99: {}", percentile(99)),
+        format!("- max: {}", sorted[count - 1]),
+        format!("- standard deviation: {:.1}", variance.sqrt()),
+    ]
+}
+
+/// The `line N: ...` errors vkrunner reported in a run's result, each
+/// with its indented continuation lines (such as a probe's expected and
+/// observed colors).
+fn vkrunner_errors(result: &str) -> Vec<String> {
+    let output = result
+        .split_once("\nShader Test File Contents:")
+        .map_or(result, |(output, _)| output);
+    let mut errors = Vec::new();
+    let mut in_error = false;
+
+    for line in output.lines() {
+        in_error = line.starts_with("line ") || (in_error && line.starts_with("  "));
+        if in_error {
+            errors.push(line.to_string());
+        }
+    }
+
+    errors
+}
+
+/// How a run of bisect_shaders or mutation_test came out: whether its
+/// probes passed, and the error lines that explain a failure.
+struct RunVerdict {
+    passed: bool,
+    errors: Vec<String>,
+}
+
+/// The report lines of a suite test: PASS or FAIL with its tags and
+/// duration, then the errors of a failure indented.
+fn suite_test_lines(
+    test: &suite::SuiteTest,
+    verdict: &RunVerdict,
+    elapsed: Duration,
+) -> Vec<String> {
+    let tag_list = if test.tags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", test.tags.join(", "))
+    };
+    let status = if verdict.passed { "PASS" } else { "FAIL" };
+    let milliseconds = elapsed.as_secs_f64() * 1000.0;
+
+    let mut lines = vec![format!(
+        "{status} {}{tag_list} ({milliseconds:.0} ms)",
+        test.name
+    )];
+    if !verdict.passed {
+        lines.extend(verdict.errors.iter().map(|error| format!("  {error}")));
+    }
+    lines
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunSuiteRequest {
+    #[schemars(
+        description = "Path of the suite file under /tmp: YAML, or JSON if it ends in .json. It has an optional name and a list of tests, each with a name, optional tags and either a request (a compile_run_shaders request) or a shader_test (a vkrunner script path relative to the suite file). A request may list source_files, GLSL files relative to the suite file that replace the sources of its compile requests in order"
+    )]
+    pub path: String,
+    #[schemars(description = "Only run tests with at least one of these tags (default: all)")]
+    pub tags: Option<Vec<String>>,
+    #[schemars(description = "Skip tests with any of these tags")]
+    pub exclude_tags: Option<Vec<String>>,
+    #[schemars(description = "Stop at the first failing test (default: false)")]
+    pub fail_fast: Option<bool>,
+    #[schemars(
+        description = "Also write a table of the results under /tmp, one row per selected test with its tags, status (PASS, FAIL or NOT RUN), duration_ms and errors: Parquet if the path ends in .parquet, otherwise CSV"
+    )]
+    pub export_path: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SnapshotWorkspaceRequest {
+    #[schemars(description = "Path of the snapshot file to write, under /tmp")]
+    pub path: String,
+    #[schemars(
+        description = "Further files to include, such as golden images, output images or buffer dumps; paths are under /tmp. The artifact store (spv-... and tex-... IDs) and the compile_incremental sources are always included"
+    )]
+    pub files: Option<Vec<String>>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RestoreWorkspaceRequest {
+    #[schemars(description = "Path of a file written by snapshot_workspace, under /tmp")]
+    pub path: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RegisterShaderRequest {
+    #[schemars(
+        description = "Name that passes reference the shader by, as lib:<name>; letters, digits, '-' and '_'. Registering an existing name replaces it"
+    )]
+    pub name: String,
+    #[schemars(
+        description = "How to compile the shader; tmp_output_path and define_variants are not used"
+    )]
+    pub request: CompileRequest,
+    #[schemars(description = "What the shader does, shown by list_shaders")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct InstantiateTemplateRequest {
+    #[schemars(description = "Template name, as listed by list_templates")]
+    pub name: String,
+    #[schemars(
+        description = "Also run the request with compile_run_shaders and return its result after the request (default: false)"
+    )]
+    pub run: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct UnregisterShaderRequest {
+    #[schemars(description = "Name the shader was registered under")]
+    pub name: String,
+}
+
+/// A shader of the library that register_shader builds up.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct LibraryShader {
+    stage: ShaderStage,
+    /// `spv-...` ID of the compiled module.
+    artifact_id: String,
+    description: Option<String>,
+}
+
+/// Prefix of pass references to library shaders.
+const LIBRARY_PREFIX: &str = "lib:";
+
+/// Version of the snapshot_workspace format, bumped when it changes
+/// incompatibly.
+const WORKSPACE_SNAPSHOT_VERSION: u32 = 1;
+
+/// What snapshot_workspace archives: the files of a session and the
+/// in-memory compile state, which otherwise dies with the process.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WorkspaceSnapshot {
+    version: u32,
+    /// Full sources by `src-...` ID, oldest first.
+    sources: Vec<(String, String)>,
+    /// Compiler outputs by cache key, oldest first.
+    results: Vec<(String, Result<String, String>)>,
+    /// Base64 contents by absolute path.
+    files: Vec<(String, String)>,
+    /// Library shaders by name.
+    #[serde(default)]
+    library: Vec<(String, LibraryShader)>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ListEntrypointsRequest {
+    #[schemars(description = "Path to a compiled SPIR-V assembly (.spvasm) file")]
+    pub spvasm_path: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SpecConstantValue {
+    #[schemars(description = "constant_id of the specialization constant")]
+    pub spec_id: u32,
+    #[schemars(description = "Value to assume (booleans: 0 or 1)")]
+    pub value: f64,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct PushConstantValue {
+    #[schemars(description = "Index of the member in the push constant block")]
+    pub member: u32,
+    #[schemars(description = "Components of the member, e.g. 4 numbers for a vec4")]
+    pub values: Vec<f64>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct EvaluateConstantExpressionsRequest {
+    #[schemars(
+        description = "Path or spv-... artifact ID of a compiled SPIR-V assembly (.spvasm) file"
+    )]
+    pub spvasm_path: String,
+    #[schemars(description = "Specialization constant values replacing their defaults")]
+    pub spec_constants: Option<Vec<SpecConstantValue>>,
+    #[schemars(
+        description = "Push constant values to assume; members without one are runtime inputs"
+    )]
+    pub push_constants: Option<Vec<PushConstantValue>>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct AnalyzeMemoryAccessRequest {
+    #[schemars(
+        description = "Path or spv-... artifact ID of a compiled compute shader's SPIR-V assembly (.spvasm) file"
+    )]
+    pub spvasm_path: String,
+    #[schemars(
+        description = "Invocations that access memory together: 32 for NVIDIA and most mobile GPUs, 64 for AMD GCN (default: 32)"
+    )]
+    pub subgroup_size: Option<u32>,
+    #[schemars(description = "Shared memory banks (default: 32)")]
+    pub banks: Option<u32>,
+    #[schemars(description = "Bytes each shared memory bank serves per cycle (default: 4)")]
+    pub bank_width: Option<u32>,
+    #[schemars(
+        description = "Bytes of storage buffer memory fetched together, such as a cache line (default: 128)"
+    )]
+    pub segment_size: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct TestSubgroupOpsRequest {
+    #[schemars(
+        description = "Subgroup sizes to require, powers of two up to 128 (default: all of them); sizes the device doesn't support are reported as skipped. The device's default size is always tested"
+    )]
+    pub subgroup_sizes: Option<Vec<u32>>,
+    #[schemars(description = "Groups of operations to test (default: all)")]
+    pub categories: Option<Vec<subgroups::Category>>,
+    #[schemars(
+        description = "Vulkan driver to test: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CharacterizeFpRequest {
+    #[schemars(
+        description = "Float types to characterize (default: all); types the device doesn't support are reported as skipped"
+    )]
+    pub types: Option<Vec<fp::FloatType>>,
+    #[schemars(
+        description = "Vulkan driver to characterize: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunCoopmatMatmulRequest {
+    #[schemars(
+        description = "Rows, columns and inner dimension of the product, rounded up to whole tiles (default: 256, at most 512)"
+    )]
+    pub size: Option<u32>,
+    #[schemars(description = "Tile rows M to use (default: any the device supports)")]
+    pub m: Option<u32>,
+    #[schemars(description = "Tile columns N to use (default: any the device supports)")]
+    pub n: Option<u32>,
+    #[schemars(description = "Tile inner dimension K to use (default: any the device supports)")]
+    pub k: Option<u32>,
+    #[schemars(description = "Component type of A and B (default: any the device supports)")]
+    pub input_type: Option<coopmat::Component>,
+    #[schemars(
+        description = "Component type of the accumulator and C (default: any the device supports)"
+    )]
+    pub accumulator_type: Option<coopmat::Component>,
+    #[schemars(description = "Times the product is dispatched for timing (default: 5)")]
+    pub repetitions: Option<u32>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct VerifyReductionScanRequest {
+    #[schemars(description = "Reduction, inclusive scan or exclusive scan")]
+    pub pattern: scan::Pattern,
+    #[schemars(description = "Operator to combine elements with")]
+    pub operator: scan::Operator,
+    #[schemars(description = "Element type: uint, int or float")]
+    pub element: scan::Element,
+    #[schemars(
+        description = "Element counts to verify, 1 to 65536 (default: 1, 7, 256, 257, 1000 and 4099, covering partial and multiple chunks)"
+    )]
+    pub sizes: Option<Vec<u32>>,
+    #[schemars(description = "Seed of the random inputs (default: 1)")]
+    pub seed: Option<u64>,
+    #[schemars(
+        description = "GLSL compute shader to verify instead of the built-in kernel. It reads the elements from `readonly buffer { T data_in[]; }` at binding 0, writes the results to a buffer at binding 1 (one element for a reduction) and gets the element count as a uint push constant at offset 0"
+    )]
+    pub source: Option<String>,
+    #[schemars(
+        description = "Elements each workgroup of source handles; ceil(size / items_per_workgroup) workgroups are dispatched (default: one workgroup)"
+    )]
+    pub items_per_workgroup: Option<u32>,
+    #[schemars(
+        description = "Once the built-in kernel passes, register it in the shader library under this name so multi-pass pipelines can reference it as lib:<name>"
+    )]
+    pub register_as: Option<String>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct VerifyHistogramSortRequest {
+    #[schemars(description = "Kernel to verify: Histogram or Sort")]
+    pub algorithm: sort::Algorithm,
+    #[schemars(
+        description = "Element counts to verify, 1 to 65536 (default: 1, 7, 256, 257, 1000 and 4099)"
+    )]
+    pub sizes: Option<Vec<u32>>,
+    #[schemars(description = "Bins of a histogram, 1 to 4096 (default: 64)")]
+    pub bins: Option<u32>,
+    #[schemars(description = "Seed of the random inputs (default: 1)")]
+    pub seed: Option<u64>,
+    #[schemars(
+        description = "GLSL compute shader to verify instead of the built-in kernel. A histogram reads uint bin indices from binding 0 and counts them into the zeroed uint bins at binding 1, with the element count and bin count as uint push constants at offsets 0 and 4. A sort sorts the uint keys at binding 0 ascending in place, with the element count as a uint push constant at offset 0"
+    )]
+    pub source: Option<String>,
+    #[schemars(
+        description = "Elements each workgroup of source handles; ceil(size / items_per_workgroup) workgroups are dispatched (default: one workgroup)"
+    )]
+    pub items_per_workgroup: Option<u32>,
+    #[schemars(
+        description = "Once the built-in kernel passes, register it in the shader library under this name so multi-pass pipelines can reference it as lib:<name>"
+    )]
+    pub register_as: Option<String>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunImageFilterRequest {
+    #[schemars(description = "ID of the input image, as returned by upload_texture")]
+    pub input: String,
+    #[schemars(description = "Built-in filter to run when no source is given")]
+    pub preset: Option<filter::Preset>,
+    #[schemars(
+        description = "GLSL compute shader filtering the image. Pixels are row-major uints packed as packUnorm4x8 does, read from `readonly buffer { uint pixels_in[]; }` at binding 0 and written to `writeonly buffer { uint pixels_out[]; }` at binding 1; the image size is `uniform { uint width; uint height; }` in push constants"
+    )]
+    pub source: Option<String>,
+    #[schemars(
+        description = "Workgroup size of source as [x, y]; one invocation is dispatched per pixel, rounded up to whole workgroups (default: [8, 8])"
+    )]
+    pub local_size: Option<[u32; 2]>,
+    #[schemars(
+        description = "Expected output to compute the PSNR against: an upload_texture ID or a PNG/JPEG path, the size of the input"
+    )]
+    pub reference: Option<String>,
+    #[schemars(
+        description = "Where to save the output image as PNG (default: a new /tmp/image_filter_<id>.png)"
+    )]
+    pub output_path: Option<String>,
+    #[schemars(
+        description = "Larger side in pixels of the output image embedded in the response (default: 512)"
+    )]
+    pub return_image_max_dim: Option<u32>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunRaymarcherRequest {
+    #[schemars(
+        description = "GLSL of the scene, placed after a header declaring the push constants `vec3 eye; float time; vec3 target; float fov_y; vec2 resolution;`, `out vec4 frag_color` and `vec3 camera_ray(vec2 frag_coord)`. Define `float map(vec3 p)` to use the built-in sphere tracer (hits shaded from 0.2 to 1, misses black), or write your own main"
+    )]
+    pub source: String,
+    #[schemars(description = "Camera position (default: [0, 0, 3])")]
+    pub eye: Option<[f64; 3]>,
+    #[schemars(description = "Point the camera looks at, with +Y up (default: [0, 0, 0])")]
+    pub target: Option<[f64; 3]>,
+    #[schemars(description = "Vertical field of view in degrees (default: 60)")]
+    pub fov_y: Option<f64>,
+    #[schemars(description = "Frame time in seconds, pushed as time (default: 0)")]
+    pub time: Option<f64>,
+    #[schemars(
+        description = "Spheres the scene contains, whose analytic silhouettes the rendered coverage (pixels differing from background) is checked against"
+    )]
+    pub spheres: Option<Vec<raymarch::Sphere>>,
+    #[schemars(
+        description = "Largest difference between the expected and rendered coverage, in percentage points of the frame (default: 1)"
+    )]
+    pub coverage_tolerance: Option<f64>,
+    #[schemars(description = "R, G, B (0-1) of pixels that miss the scene (default: black)")]
+    pub background: Option<[f64; 3]>,
+    #[schemars(description = "Where to save the frame (default: a new /tmp/raymarch_<id>.png)")]
+    pub output_path: Option<String>,
+    #[schemars(
+        description = "Embed the frame in the response, downscaled so its larger side is at most this many pixels"
+    )]
+    pub return_image_max_dim: Option<u32>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct AnalyzeNoiseRequest {
+    #[schemars(
+        description = "GLSL defining `float noise(vec2 p)` and anything it needs; it is sampled by a compute kernel into a float buffer, so derivatives and textures are unavailable"
+    )]
+    pub source: String,
+    #[schemars(
+        description = "Samples per side of one period, a power of two from 16 to 1024 (default: 256)"
+    )]
+    pub size: Option<u32>,
+    #[schemars(
+        description = "Side of the square of p values sampled, from 0 to period, and the period tileable noise repeats with (default: 8)"
+    )]
+    pub period: Option<f64>,
+    #[schemars(description = "Range every value must lie in, as [min, max]")]
+    pub expected_range: Option<[f64; 2]>,
+    #[schemars(description = "Expected mean of the values")]
+    pub expected_mean: Option<f64>,
+    #[schemars(description = "Expected variance of the values")]
+    pub expected_variance: Option<f64>,
+    #[schemars(
+        description = "Largest difference from expected_mean and expected_variance, relative to the expected variance (default: 0.1)"
+    )]
+    pub tolerance: Option<f64>,
+    #[schemars(
+        description = "Highest frequency in cycles per period the noise should contain; fails when more than 5% of the energy lies above it"
+    )]
+    pub max_frequency: Option<u32>,
+    #[schemars(
+        description = "Check that the noise tiles with the period: values on opposite borders must match within 1e-3 (default: false)"
+    )]
+    pub tileable: Option<bool>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CheckDerivativesRequest {
+    #[schemars(
+        description = "GLSL defining `float value(vec2 p)`; it must also compile in a compute shader, so it can't use derivatives itself"
+    )]
+    pub source: String,
+    #[schemars(
+        description = "GLSL defining `vec2 derivatives(vec2 p)`, the fragment shader code under test returning the change of value per pixel along x and y, e.g. with dFdx/dFdy inside branches (default: dFdx and dFdy of value(p))"
+    )]
+    pub derivatives: Option<String>,
+    #[schemars(
+        description = "Size of a pixel in p: p = gl_FragCoord.xy * pixel_size (default: 1/250, so p spans [0, 1] over the 250x250 frame)"
+    )]
+    pub pixel_size: Option<f64>,
+    #[schemars(
+        description = "Subdivisions of a pixel the reference differences are taken at (default: 4)"
+    )]
+    pub refine: Option<u32>,
+    #[schemars(
+        description = "Largest error of a derivative relative to the larger of its reference and the reference's RMS (default: 0.05)"
+    )]
+    pub tolerance: Option<f64>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct VerifyBlendingRequest {
+    #[schemars(
+        description = "R, G, B, A (0-1) written over the whole frame, unblended, before the quads (default: opaque black)"
+    )]
+    pub background: Option<[f64; 4]>,
+    #[schemars(
+        description = "R, G, B, A (0-1) of the first quad, drawn over the upper left (default: half-transparent red)"
+    )]
+    pub first_color: Option<[f64; 4]>,
+    #[schemars(
+        description = "R, G, B, A (0-1) of the second quad, drawn after the first over the lower right, overlapping it in the center (default: half-transparent blue)"
+    )]
+    pub second_color: Option<[f64; 4]>,
+    #[schemars(description = "Blend state of both quads (default: straight-alpha over)")]
+    pub blend: Option<blend::BlendState>,
+    #[schemars(
+        description = "Where to save the composite (default: a new /tmp/blending_<id>.png)"
+    )]
+    pub output_path: Option<String>,
+    #[schemars(
+        description = "Embed the composite in the response, downscaled so its larger side is at most this many pixels"
+    )]
+    pub return_image_max_dim: Option<u32>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct AnalyzeDepthPrecisionRequest {
+    #[schemars(description = "Distance of the near plane (default: 0.1)")]
+    pub near: Option<f64>,
+    #[schemars(description = "Distance of the far plane (default: 1000)")]
+    pub far: Option<f64>,
+    #[schemars(
+        description = "Distance between the two surfaces of each row: a fraction of the row's view distance, or world units with relative_separation false (default: 0.001)"
+    )]
+    pub separation: Option<f64>,
+    #[schemars(
+        description = "Whether separation is relative to the view distance (default: true)"
+    )]
+    pub relative_separation: Option<bool>,
+    #[schemars(
+        description = "Depth formats to measure, such as D32_SFLOAT, D24_UNORM_S8_UINT and D16_UNORM (default: those three); unsupported ones are reported as failed"
+    )]
+    pub formats: Option<Vec<String>>,
+    #[schemars(
+        description = "Also measure reversed Z (near plane at depth 1, GREATER test) next to the standard mapping (default: true)"
+    )]
+    pub reversed_z: Option<bool>,
+    #[schemars(
+        description = "Directory to save each measurement's image in, as depth_<format>_<standard|reversed>.png (default: /tmp)"
+    )]
+    pub output_dir: Option<String>,
+    #[schemars(
+        description = "Vulkan driver to run on: 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows (default: the server's)"
+    )]
+    pub icd: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct PlotResultsRequest {
+    #[schemars(description = "Series to plot, each with a name and [x, y] points")]
+    pub series: Option<Vec<plot::Series>>,
+    #[schemars(
+        description = "A CSV file under /tmp with a header row to plot columns of, such as a table written by export_path; rows whose values aren't numbers are skipped"
+    )]
+    pub csv_path: Option<String>,
+    #[schemars(description = "Column of csv_path with the x values")]
+    pub x_column: Option<String>,
+    #[schemars(description = "Columns of csv_path to plot against x_column, one series each")]
+    pub y_columns: Option<Vec<String>>,
+    #[schemars(
+        description = "Line joins the points in order of x; Scatter only marks them (default: Line)"
+    )]
+    pub style: Option<plot::Style>,
+    #[schemars(description = "Title drawn above the chart")]
+    pub title: Option<String>,
+    #[schemars(description = "Label of the x axis (default: x_column)")]
+    pub x_label: Option<String>,
+    #[schemars(description = "Label of the y axis (default: the y column, when there is one)")]
+    pub y_label: Option<String>,
+    #[schemars(
+        description = "Logarithmic x axis, e.g. for sizes or sample counts; points with x <= 0 are left out (default: false)"
+    )]
+    pub log_x: Option<bool>,
+    #[schemars(
+        description = "Logarithmic y axis, e.g. for errors; points with y <= 0 are left out (default: false)"
+    )]
+    pub log_y: Option<bool>,
+    #[schemars(
+        description = "Path of the PNG to write under /tmp (default: a new /tmp/plot_<id>.png)"
+    )]
+    pub output_path: Option<String>,
+    #[schemars(description = "Width of the chart in pixels (default: 640)")]
+    pub width: Option<u32>,
+    #[schemars(description = "Height of the chart in pixels (default: 400)")]
+    pub height: Option<u32>,
+    #[schemars(
+        description = "Downscale the embedded chart so its larger side is at most this many pixels; the saved file keeps full resolution"
+    )]
+    pub return_image_max_dim: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct DiffSpirvRequest {
+    #[schemars(
+        description = "Path or spv-... artifact ID of the compiled SPIR-V assembly (.spvasm) file to compare from"
+    )]
+    pub before_path: String,
+    #[schemars(
+        description = "Path or spv-... artifact ID of the compiled SPIR-V assembly (.spvasm) file to compare to"
+    )]
+    pub after_path: String,
+    #[schemars(
+        description = "Most added or removed instructions to list per function; more are only counted (default: 40)"
+    )]
+    pub max_instruction_changes: Option<usize>,
+}
+
+/// The shaders of a compile_run_shaders request, compiled before
+/// anything runs.
+#[derive(Default)]
+struct CompiledShaders {
+    /// Output paths of each compile request, one per define variant.
+    paths: Vec<Vec<String>>,
+    /// Whether each shader compiled, and where to.
+    statuses: Vec<String>,
+    /// The compiler's messages of the shaders that failed.
+    failures: Vec<String>,
+    timings: Vec<(String, Duration)>,
+    analysis_warnings: Vec<String>,
+    /// The artifact IDs of outputs the requests didn't name.
+    artifact_notes: Vec<String>,
+    /// The defines of each variant's output.
+    variant_notes: Vec<String>,
+    disassemblies: Vec<String>,
+}
+
+impl CompiledShaders {
+    /// The report of a run stopped by shaders that failed to compile, if
+    /// any did.
+    fn failure_report(&self) -> Option<String> {
+        if self.failures.is_empty() {
+            return None;
+        }
+
+        let succeeded = self.statuses.len() - self.failures.len();
+        let mut message = format!(
+            "Compiled {succeeded} of {} shaders, so nothing was run.\n\nShaders:\n{}\n",
+            self.statuses.len(),
+            self.statuses.join("\n")
+        );
+        if succeeded > 0 {
+            message.push_str("Reference the compiled shaders by path or artifact ID in the passes of the next run, and only send the failed ones as compile requests again.\n");
+        }
+        for failure in &self.failures {
+            message.push('\n');
+            message.push_str(failure);
+        }
+        Some(message)
+    }
+}
+
+/// What writing a run's vkrunner script found out along the way.
+#[derive(Default)]
+struct ShaderTestScript {
+    /// `(stage, path, spvasm)` of each SPIR-V pass.
+    modules: Vec<(String, String, String)>,
+    /// How dispatches over the budget were split.
+    dispatch_notes: Vec<String>,
+    /// The `[require]` section, with the inferred requirements.
+    require_lines: Vec<String>,
+}
+
+impl ShaderTestScript {
+    /// The requirements that portability drivers usually lack, whether
+    /// the request listed them or they were inferred from its shaders.
+    fn portability_gaps(&self) -> Vec<&'static str> {
+        self.require_lines
+            .iter()
+            .filter_map(|line| portability_gap(line))
+            .collect()
+    }
+}
+
+/// How the dispatches of a run are bounded.
+struct DispatchPlan<'a> {
+    /// The GPU watchdog the run is under, unless it runs on a software
+    /// driver.
+    watchdog: Option<&'a watchdog::Watchdog>,
+    local_size: [u32; 3],
+    /// Identifies the compute shader across runs, to learn its rate.
+    compute_key: Option<String>,
+    /// Most invocations one dispatch may run.
+    budget: Option<u64>,
+    /// Most workgroups of a split dispatch, if any is over the budget.
+    chunk_workgroups: Option<u64>,
+    watchdog_notes: Vec<String>,
+}
+
+/// How vkrunner ran a request's script.
+struct ScriptExecution {
+    output: Output,
+    /// The driver it ran on, if one was pinned or fallen back to.
+    icd: Option<PathBuf>,
+    /// The software driver it ran on, if it did.
+    software_icd: Option<String>,
+    fell_back_to_software: bool,
+}
+
+/// What reruns of a script after the main run share with it.
+struct FollowUp<'a> {
+    /// The options of the main run that affect rendering.
+    args: Vec<String>,
+    icd: Option<&'a Path>,
+    env: &'a [EnvironmentVariable],
+    scratch: &'a ScratchDir,
+}
+
+/// How the output image compared with expected_image.
+struct ImageComparison {
+    /// The largest per-pixel deviation.
+    max: f64,
+    mean: f64,
+    /// Whether any pixel deviated by more than the tolerance.
+    mismatch: bool,
+}
+
+/// How one compile_run_shaders run came out, before it becomes the
+/// tool's result.
+#[derive(Clone)]
+struct ShaderRun {
+    /// The report returned as text, without its error_code line.
+    report: String,
+    /// Output, heatmap and snapshot images embedded after the report.
+    images: Vec<Content>,
+    /// Why the run failed or skipped its test, if it did.
+    error_code: Option<ErrorCode>,
+    /// Whether vkrunner exited successfully, which a skipped test also
+    /// does.
+    succeeded: bool,
+    /// Whether vkrunner skipped the test for missing device support.
+    skipped: bool,
+    /// What vkrunner printed, stdout then stderr.
+    output: String,
+    /// The `line N: ...` errors vkrunner reported.
+    errors: Vec<String>,
+    /// The features the device lacks for the shaders, explained.
+    missing_features: Vec<String>,
+    /// The max and mean deviation of the output image from
+    /// expected_image, if they were compared.
+    deviation: Option<(f64, f64)>,
+    /// The cache key of the identical earlier run this was reused from.
+    cached: Option<String>,
+}
+
+impl ShaderRun {
+    /// A run that stopped before vkrunner, reported by `report`.
+    fn stopped(error_code: ErrorCode, report: String) -> Self {
+        ShaderRun {
+            report,
+            images: Vec::new(),
+            error_code: Some(error_code),
+            succeeded: false,
+            skipped: false,
+            output: String::new(),
+            errors: Vec::new(),
+            missing_features: Vec::new(),
+            deviation: None,
+            cached: None,
+        }
+    }
+
+    /// Whether vkrunner ran the test and every probe passed.
+    fn passed(&self) -> bool {
+        self.succeeded && !self.skipped
+    }
+
+    /// The line that best explains why the run failed or was skipped.
+    fn failure_reason(&self) -> String {
+        const MARKERS: &[&str] = &[
+            "error:",
+            "Required subgroup size",
+            "Missing",
+            "not supported",
+        ];
+
+        self.missing_features
+            .first()
+            .map(String::as_str)
+            .or_else(|| {
+                self.output
+                    .lines()
+                    .chain(self.report.lines())
+                    .find(|line| MARKERS.iter().any(|marker| line.contains(marker)))
+            })
+            .or_else(|| self.errors.first().map(String::as_str))
+            .or_else(|| self.report.lines().next())
+            .unwrap_or("no output")
+            .trim()
+            .to_string()
+    }
+
+    /// The tool result: the report, prefixed with its error_code line,
+    /// then the images.
+    fn contents(self) -> Vec<Content> {
+        let mut contents = Vec::new();
+        if let Some(key) = &self.cached {
+            contents.push(Content::text(format!(
+                "cached: true\nReturning the result of an identical earlier run (key {key}); the files it reports are where that run saved them."
+            )));
+        }
+        let report = match self.error_code {
+            Some(code) => format!("error_code: {code}\n{}", self.report),
+            None => self.report,
+        };
+        contents.push(Content::text(report));
+        contents.extend(self.images);
+        contents
+    }
+}
+
+/// How one run of a built-in kernel, such as the subgroup operation
+/// battery, came out.
+enum KernelRun {
+    /// The dumped buffer and what vkrunner printed.
+    Dump(Vec<u8>, String),
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Scene {
+    #[schemars(
+        description = "Name reported with this scene's results and used to name its image (letters, digits, '-' and '_')"
+    )]
+    pub name: String,
+    #[schemars(description = "Shader pipeline of this scene, replacing the request's passes")]
+    pub passes: Vec<ShaderRunnerPass>,
+    #[schemars(description = "Vertex data of this scene (default: the request's vertex_data)")]
+    pub vertex_data: Option<Vec<ShaderRunnerVertexData>>,
+    #[schemars(
+        description = "Per-instance data of this scene (default: the request's instance_data)"
+    )]
+    pub instance_data: Option<Vec<ShaderRunnerVertexData>>,
+    #[schemars(description = "Test commands of this scene, replacing the request's tests")]
+    pub tests: Vec<ShaderRunnerTest>,
+    #[schemars(
+        description = "Path to save this scene's image; defaults to <stem>_<name>.<ext> next to the request's output_path"
+    )]
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct CompileRunShadersRequest {
+    #[schemars(
+        description = "List of shader compile requests - each produces a SPIR-V assembly file. If any fails to compile, the others are still compiled and saved, nothing runs, and the result lists the status of each so the next run can reference the compiled ones"
+    )]
+    pub requests: Vec<CompileRequest>,
+    #[schemars(description = "Optional hardware/feature requirements needed for shader execution")]
+    pub requirements: Option<Vec<ShaderRunnerRequire>>,
+    #[schemars(
+        description = "Shader pipeline configuration (references compiled SPIR-V files by path)"
+    )]
+    pub passes: Vec<ShaderRunnerPass>,
+    #[schemars(
+        description = "Optional vertex data for rendering geometry: AttributeFormat entries first, then one row per vertex (use GenericComponents when a row spans several attributes)"
+    )]
+    pub vertex_data: Option<Vec<ShaderRunnerVertexData>>,
+    #[schemars(
+        description = "Optional per-instance vertex attributes, laid out like vertex_data: AttributeFormat entries first, then one row per instance (e.g. a per-instance offset or color). Locations must differ from those in vertex_data; draw with an instance count to use them"
+    )]
+    pub instance_data: Option<Vec<ShaderRunnerVertexData>>,
+    #[schemars(
+        description = "Optional number of instances that share each instance_data row (default 1). Values other than 1 need VK_EXT_vertex_attribute_divisor, and 0 gives every instance the first row"
+    )]
+    pub instance_divisor: Option<u32>,
+    #[schemars(description = "Test commands to execute (drawing, compute, verification, etc.)")]
+    pub tests: Vec<ShaderRunnerTest>,
+    #[schemars(description = "Optional path to save output image (PNG format)")]
+    pub output_path: Option<String>,
+    #[schemars(
+        description = "Optional Vulkan driver to run on: the shorthands 'lavapipe'/'swiftshader' or a path to an ICD manifest (.json) the server allows. Sets VK_ICD_FILENAMES/VK_DRIVER_FILES for this run only, overriding the server default"
+    )]
+    pub icd: Option<String>,
+    #[schemars(description = "Optional switches passed through to the vkrunner command line")]
+    pub vkrunner_options: Option<VkrunnerOptions>,
+    #[schemars(
+        description = "How to treat probes before any draw/compute, Compute without a compute pass and entrypoint commands no draw/compute follows (default: Warn)"
+    )]
+    pub ordering_lint: Option<OrderingLint>,
+    #[schemars(
+        description = "Also capture the framebuffer after each draw command, saved next to output_path as <stem>_draw<N>.png (N counts draws from 0) and returned as image resources. Re-runs the test up to each draw with probes removed (default: false)"
+    )]
+    pub snapshot_draws: Option<bool>,
+    #[schemars(
+        description = "Embed the output image in the response, downscaled so its larger side is at most this many pixels; also caps embedded snapshots. The saved files keep full resolution"
+    )]
+    pub return_image_max_dim: Option<u32>,
+    #[schemars(
+        description = "Report the exact pixel values of this rectangle of the output image; an embedded image (return_image_max_dim) is cropped to it too. Needs output_path"
+    )]
+    pub crop: Option<PixelRegion>,
+    #[schemars(
+        description = "How to interpret the framebuffer values when writing images (default: Srgb, written unchanged). Linear values are sRGB-encoded for PNG; EXR output (.exr output_path) always gets linear floats"
+    )]
+    pub color_space: Option<ColorSpace>,
+    #[schemars(
+        description = "Color space the expected colors of Probe/RelativeProbe are written in (default: color_space); they are converted to the framebuffer's space before probing, e.g. sRGB color-picker values against a linear framebuffer"
+    )]
+    pub probe_color_space: Option<ColorSpace>,
+    #[schemars(
+        description = "Add the requirements that the compiled SPIR-V capabilities (e.g. shaderFloat64 for Float64, storageBuffer16BitAccess), the test data and commands (wideLines, a depthstencil format, logicOp) and the passes (geometryShader, fragmentStoresAndAtomics for storage writes) need, and report them (default: true)"
+    )]
+    pub infer_requirements: Option<bool>,
+    #[schemars(
+        description = "When probes fail, re-run them at increasing tolerances (0.02 up to 1.0) and report the smallest tolerance each failing probe passes at, to tell precision drift from real bugs (default: false)"
+    )]
+    pub suggest_tolerances: Option<bool>,
+    #[schemars(
+        description = "Compare the output image with an analytic pattern or reference image and save a false-color map of the per-pixel error next to output_path as <stem>_error.png, also returned as an image resource. Needs output_path"
+    )]
+    pub expected_image: Option<ExpectedImage>,
+    #[schemars(
+        description = "Independent scenes run one after another in a single call, e.g. to compare two techniques side by side. Each scene replaces passes and tests (and optionally vertex_data and output_path) while sharing everything else, including the compile requests; the request's own passes and tests are then ignored. The output image and buffer dump of each scene are named <stem>_<scene name>.<ext> after the request's"
+    )]
+    pub scenes: Option<Vec<Scene>>,
+    #[schemars(
+        description = "Return the stored result of an earlier successful run of an identical request (same shaders, data, tests, options and driver) instead of running again; such results start with 'cached: true'. The output_path, label and return_image_max_dim count too, since the result names the saved image and embeds it (default: false)"
+    )]
+    pub cache: Option<bool>,
+    #[schemars(
+        description = "Also render the geometry with these built-in debug fragment shaders and return each result after the real one, saved as <stem>_<view>.<ext> next to output_path (uv, normals, depth, wireframe). Probes and image checks only apply to the real output. Not combinable with scenes"
+    )]
+    pub debug_views: Option<Vec<DebugView>>,
+    #[schemars(
+        description = "Seed of the WhiteNoise and Perlin textures that don't set their own (default: 0). Saved images record it with the request hash, device, driver version and time in their PNG text chunks or EXR header, and the result lists the same run metadata"
+    )]
+    pub seed: Option<u64>,
+    #[schemars(
+        description = "Text drawn in the top-left corner of the saved and embedded output image, such as a run id and parameter values. {status} becomes PASS, or MISMATCH when the image differs from expected_image, and {scene} the scene's name; \\n starts a new line"
+    )]
+    pub label: Option<String>,
+    #[schemars(
+        description = "With scenes, also assemble the scenes' output images into one grid saved at this path under /tmp, each captioned with its scene name and PASS, MISMATCH, FAIL or SKIP, and return it as an image resource (downscaled to return_image_max_dim if set)"
+    )]
+    pub montage_path: Option<String>,
+    #[schemars(
+        description = "Number of columns of the montage grid (default: about the square root of the number of scenes)"
+    )]
+    pub montage_columns: Option<u32>,
+    #[schemars(
+        description = "With scenes, also write a table of their results under /tmp, one row per scene with its status, duration_ms, output_path, the max_deviation and mean_deviation from expected_image and vkrunner's errors: Parquet if the path ends in .parquet, otherwise CSV"
+    )]
+    pub export_path: Option<String>,
+    #[schemars(
+        description = "Byte offset of a uvec3 push constant that the compute shader adds to gl_WorkGroupID. Setting it lets the server split a compute dispatch over its max_dispatch_invocations budget into smaller dispatches, pushing each one's first workgroup there; it is zero outside split dispatches. Without it such a dispatch is refused"
+    )]
+    pub dispatch_offset_push: Option<u32>,
+}
+impl CompileRunShadersRequest {
+    /// A request of a built-in tool that runs `tests` on `source`, the
+    /// only shader, compiled for `stage`: a compute shader, or a
+    /// fragment shader behind the passthrough vertex shader.
+    fn single_shader(
+        stage: ShaderStage,
+        source: String,
+        tests: Vec<ShaderRunnerTest>,
+        icd: Option<String>,
+    ) -> Self {
+        let spvasm_path = "request:0".to_string();
+        let passes = match stage {
+            ShaderStage::Comp => vec![ShaderRunnerPass::CompSpirv {
+                comp_spvasm_path: spvasm_path,
+                entrypoint: None,
+            }],
+            _ => vec![
+                ShaderRunnerPass::VertPassthrough,
+                ShaderRunnerPass::FragSpirv {
+                    frag_spvasm_path: spvasm_path,
+                    entrypoint: None,
+                },
+            ],
+        };
+        CompileRunShadersRequest {
+            requests: vec![CompileRequest::new(stage, source)],
+            passes,
+            tests,
+            icd,
+            ..Default::default()
+        }
+    }
+
+    /// Finds test commands whose position makes them ineffective, which
+    /// otherwise only shows up as blank images or untouched buffers.
+    fn ordering_issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let has_compute_pass = self.passes.iter().any(|pass| {
+            matches!(
+                pass,
+                ShaderRunnerPass::CompSpirv { .. } | ShaderRunnerPass::CompGlsl { .. }
+            )
+        });
+        let mut dispatched = false;
+
+        for (i, test) in self.tests.iter().enumerate() {
+            let later = &self.tests[i + 1..];
+
+            match test {
+                ShaderRunnerTest::Probe { .. }
+                | ShaderRunnerTest::ProbeSsbo { .. }
+                | ShaderRunnerTest::RelativeProbe { .. }
+                    if !dispatched =>
+                {
+                    issues.push(format!(
+                        "test {i}: probe comes before any draw or compute command"
+                    ));
+                }
+                ShaderRunnerTest::Compute { .. } if !has_compute_pass => {
+                    issues.push(format!(
+                        "test {i}: Compute is used but no compute shader pass is configured"
+                    ));
+                }
+                ShaderRunnerTest::VertexEntrypoint { .. }
+                | ShaderRunnerTest::FragmentEntrypoint { .. }
+                | ShaderRunnerTest::GeometryEntrypoint { .. }
+                    if !later.iter().any(ShaderRunnerTest::is_draw) =>
+                {
+                    issues.push(format!(
+                        "test {i}: entrypoint command has no effect because no draw command follows it"
+                    ));
+                }
+                ShaderRunnerTest::ComputeEntrypoint { .. }
+                    if !later
+                        .iter()
+                        .any(|test| matches!(test, ShaderRunnerTest::Compute { .. })) =>
+                {
+                    issues.push(format!(
+                        "test {i}: entrypoint command has no effect because no Compute command follows it"
+                    ));
+                }
+                _ => {}
+            }
+
+            dispatched |= test.is_draw() || matches!(test, ShaderRunnerTest::Compute { .. });
+        }
+
+        issues
+    }
+
+    /// Checks stringly numeric values before any script is generated, so
+    /// mistakes surface as precise errors instead of vkrunner parse errors.
+    fn validate_values(&self) -> Result<(), McpError> {
+        for (section, rows) in [
+            ("vertex data", &self.vertex_data),
+            ("instance data", &self.instance_data),
+        ] {
+            if let Some(rows) = rows {
+                check_hex_rows(section, rows)?;
+            }
+        }
+
+        for test in &self.tests {
+            match test {
+                ShaderRunnerTest::SSBOSubData {
+                    binding,
+                    data_type,
+                    offset,
+                    values,
+                    descriptor_set,
+                } => check_data(
+                    *data_type,
+                    *offset,
+                    values,
+                    &format!(
+                        "ssbo {} subdata {data_type} at offset {offset}",
+                        binding_ref(*descriptor_set, *binding)
+                    ),
+                )?,
+                ShaderRunnerTest::UBOSubData {
+                    binding,
+                    data_type,
+                    offset,
+                    values,
+                    descriptor_set,
+                } => check_data(
+                    *data_type,
+                    *offset,
+                    values,
+                    &format!(
+                        "ubo {} subdata {data_type} at offset {offset}",
+                        binding_ref(*descriptor_set, *binding)
+                    ),
+                )?,
+                ShaderRunnerTest::ProbeSsbo {
+                    binding,
+                    descriptor_set,
+                    data_type,
+                    offset,
+                    values,
+                    ..
+                } => check_data(
+                    *data_type,
+                    *offset,
+                    values,
+                    &format!(
+                        "probe ssbo {data_type} {} at offset {offset}",
+                        binding_ref(*descriptor_set, *binding)
+                    ),
+                )?,
+                ShaderRunnerTest::Push {
+                    data_type,
+                    offset,
+                    values,
+                } => check_data(
+                    *data_type,
+                    *offset,
+                    values,
+                    &format!("push {data_type} at offset {offset}"),
+                )?,
+                ShaderRunnerTest::Probe {
+                    probe_type,
+                    format,
+                    args,
+                } => check_probe_args(&format!("probe {probe_type} {format}"), args)?,
+                ShaderRunnerTest::RelativeProbe {
+                    probe_type,
+                    format,
+                    args,
+                } => check_probe_args(&format!("relative probe {probe_type} {format}"), args)?,
+                ShaderRunnerTest::Texture {
+                    binding,
+                    descriptor_set,
+                    width,
+                    height,
+                    kind,
+                    depth,
+                    sampler,
+                    source,
+                    ..
+                } => check_texture(
+                    &format!("texture {}", binding_ref(*descriptor_set, *binding)),
+                    *width,
+                    *height,
+                    kind.unwrap_or(TextureKind::Texture2D),
+                    depth.unwrap_or(false),
+                    sampler.as_ref(),
+                    source,
+                )?,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The *Spirv pass of `stage` (by default the first fragment or
+    /// compute pass) with its index and assembly, for tools that run the
+    /// test again with a changed copy of that shader.
+    fn spirv_pass(
+        &self,
+        stage: Option<ShaderStage>,
+    ) -> Result<(usize, ShaderStage, String), McpError> {
+        let pass_index = self
+            .passes
+            .iter()
+            .position(|pass| match (pass.spirv_input(), stage) {
+                (Some((stage, _)), Some(wanted)) => stage == wanted,
+                (Some((stage, _)), None) => matches!(stage, ShaderStage::Frag | ShaderStage::Comp),
+                (None, _) => false,
+            })
+            .ok_or_else(|| {
+                McpError::invalid_params(
+                    "The request has no *Spirv pass of that stage; GLSL passes can't be changed",
+                    None,
+                )
+            })?;
+        let Some((stage, reference)) = self.passes[pass_index].spirv_input() else {
+            unreachable!("the pass was found by its SPIR-V input");
+        };
+
+        let spvasm = match self.locate_output(reference)? {
+            Some((index, variant)) => {
+                let shader = &self.requests[index];
+                shader
+                    .compile(&shader.variant_outputs()[variant].1)?
+                    .map_err(|message| {
+                        ErrorCode::CompileError.invalid_params(
+                            format!("Failed to compile {reference}:\n{message}"),
+                            None,
+                        )
+                    })?
+            }
+            None => {
+                let path = self.resolve_spvasm_path(reference, &[])?;
+                std::fs::read_to_string(&path).map_err(|e| {
+                    ErrorCode::BadRequestPath
+                        .invalid_params(format!("Failed to read {path}: {e}"), None)
+                })?
+            }
+        };
+
+        Ok((pass_index, stage, spvasm))
+    }
+
+    /// Finds the compile request output (request index, variant index) a
+    /// pass reference names, either as `request:N[/K]` or by its path.
+    fn locate_output(&self, reference: &str) -> Result<Option<(usize, usize)>, McpError> {
+        let Some(spec) = reference.strip_prefix("request:") else {
+            let path = tmp_path(reference);
+            return Ok(self.requests.iter().enumerate().find_map(|(index, req)| {
+                req.variant_outputs()
+                    .iter()
+                    .position(|(output, _)| output.as_deref() == Some(path.as_str()))
+                    .map(|variant| (index, variant))
+            }));
+        };
+
+        let (index, variant) = spec.split_once('/').unwrap_or((spec, "0"));
+        let located = index
+            .parse::<usize>()
+            .ok()
+            .zip(variant.parse::<usize>().ok())
+            .filter(|(index, variant)| {
+                self.requests
+                    .get(*index)
+                    .is_some_and(|req| *variant < req.variant_outputs().len())
+            });
+
+        match located {
+            Some(located) => Ok(Some(located)),
+            None => Err(ErrorCode::UnknownReference.invalid_params(
+                format!("{reference} does not name a compile request output of this call"),
+                None,
+            )),
+        }
+    }
+
+    /// Turns a pass reference into the file to load: an output of this
+    /// call, a `spv-...` artifact ID from an earlier one, or a /tmp path.
+    fn resolve_spvasm_path(
+        &self,
+        reference: &str,
+        compiled: &[Vec<String>],
+    ) -> Result<String, McpError> {
+        if let Some((index, variant)) = self.locate_output(reference)? {
+            return Ok(compiled[index][variant].clone());
+        }
+
+        if reference.starts_with("spv-") {
+            return Ok(artifact_path(reference));
+        }
+
+        Ok(tmp_path(reference))
+    }
+
+    /// Stages of the SPIR-V passes that declare a push constant block, or
+    /// `None` when a GLSL pass hides that information.
+    fn reflected_push_stages(
+        &self,
+        compiled: &[Vec<String>],
+    ) -> Result<Option<Vec<ShaderStage>>, McpError> {
+        let mut stages = Vec::new();
+
+        for pass in &self.passes {
+            let Some((stage, reference)) = pass.spirv_input() else {
+                if matches!(pass, ShaderRunnerPass::VertPassthrough) {
+                    continue;
+                }
+                return Ok(None);
+            };
+
+            let path = self.resolve_spvasm_path(reference, compiled)?;
+            let Ok(spvasm) = std::fs::read_to_string(&path) else {
+                return Ok(None);
+            };
+
+            if spirv::Module::parse(&spvasm).has_push_constants() && !stages.contains(&stage) {
+                stages.push(stage);
+            }
+        }
+
+        Ok(Some(stages))
+    }
+
+    /// Workgroup size of the compute shader, which every dispatch runs.
+    /// vkrunner links the GLSL compute passes into one shader, so a size
+    /// declared in any of them counts, while a SPIR-V pass has its own.
+    /// Dimensions no pass declares are 1, as in GLSL.
+    fn compute_local_size(&self, compiled: &[Vec<String>]) -> Result<[u32; 3], McpError> {
+        let mut local_size = [None; 3];
+        for pass in &self.passes {
+            let declared = match (pass, pass.spirv_input()) {
+                (_, Some((ShaderStage::Comp, reference))) => {
+                    let path = self.resolve_spvasm_path(reference, compiled)?;
+                    std::fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|spvasm| spirv::Module::parse(&spvasm).local_size())
+                        .map_or([None; 3], |size| size.map(Some))
+                }
+                (ShaderRunnerPass::CompGlsl { source }, _) => glsl_local_size(source),
+                _ => continue,
+            };
+            for (size, declared) in local_size.iter_mut().zip(declared) {
+                *size = declared.or(*size);
+            }
+        }
+        Ok(local_size.map(|size| size.unwrap_or(1)))
+    }
+
+    /// Hash of the compute pass's SPIR-V assembly or GLSL source, which
+    /// identifies it across runs.
+    fn compute_shader_key(&self, compiled: &[Vec<String>]) -> Result<Option<String>, McpError> {
+        for pass in &self.passes {
+            match (pass, pass.spirv_input()) {
+                (_, Some((ShaderStage::Comp, reference))) => {
+                    let path = self.resolve_spvasm_path(reference, compiled)?;
+                    return Ok(std::fs::read_to_string(&path).ok().map(sha256_hex));
+                }
+                (ShaderRunnerPass::CompGlsl { source }, _) => return Ok(Some(sha256_hex(source))),
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Each compute test with its index and its number of invocations.
+    fn dispatch_invocations(&self, local_size: [u32; 3]) -> Vec<(usize, [u32; 3], u64)> {
+        let group_invocations = local_size.iter().map(|&n| u64::from(n)).product::<u64>();
+        self.tests
+            .iter()
+            .enumerate()
+            .filter_map(|(i, test)| match test {
+                ShaderRunnerTest::Compute { x, y, z } => Some((
+                    i,
+                    [*x, *y, *z],
+                    u64::from(*x) * u64::from(*y) * u64::from(*z) * group_invocations,
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Most workgroups one dispatch may run under `max_invocations`, or
+    /// `None` when every dispatch fits. Refuses dispatches over the budget
+    /// unless `dispatch_offset_push` lets them be split.
+    fn dispatch_chunk_workgroups(
+        &self,
+        local_size: [u32; 3],
+        max_invocations: Option<u64>,
+    ) -> Result<Option<u64>, McpError> {
+        let Some(max_invocations) = max_invocations else {
+            return Ok(None);
+        };
+        let group_invocations = local_size.iter().map(|&n| u64::from(n)).product::<u64>();
+        let [lx, ly, lz] = local_size;
+
+        let mut oversized = false;
+        for (i, [x, y, z], invocations) in self.dispatch_invocations(local_size) {
+            if invocations <= max_invocations {
+                continue;
+            }
+            if self.dispatch_offset_push.is_none() {
+                return Err(ErrorCode::BudgetExceeded.invalid_params(
+                    format!(
+                        "test {i}: compute {x} {y} {z} with workgroups of {lx}x{ly}x{lz} runs {invocations} invocations, over the server's budget of {max_invocations} per dispatch. Dispatch fewer workgroups, or add a uvec3 push constant that the shader adds to gl_WorkGroupID and set dispatch_offset_push to its offset so the dispatch can be split"
+                    ),
+                    None,
+                ));
+            }
+            if group_invocations > max_invocations {
+                return Err(ErrorCode::BudgetExceeded.invalid_params(
+                    format!(
+                        "test {i}: a single workgroup of {lx}x{ly}x{lz} runs more than the server's budget of {max_invocations} invocations per dispatch, so the dispatch can't be split"
+                    ),
+                    None,
+                ));
+            }
+            oversized = true;
+        }
+
+        Ok(oversized.then(|| max_invocations / group_invocations.max(1)))
+    }
+
+    /// Invocations per dispatch that keep each one well inside the GPU
+    /// watchdog's timeout at the rate measured in an earlier run, and
+    /// notes on the dispatches estimated to come close to it. Dispatches
+    /// estimated to outlast the timeout are refused unless
+    /// `dispatch_offset_push` lets them be split.
+    fn watchdog_dispatch_budget(
+        &self,
+        local_size: [u32; 3],
+        watchdog: &watchdog::Watchdog,
+        seconds_per_invocation: f64,
+    ) -> Result<(Option<u64>, Vec<String>), McpError> {
+        let group_invocations = local_size.iter().map(|&n| u64::from(n)).product::<u64>();
+        let chunk_duration = watchdog.margin() / 2;
+        let mut budget = None;
+        let mut notes = Vec::new();
+
+        for (i, [x, y, z], invocations) in self.dispatch_invocations(local_size) {
+            let estimated = watchdog::estimate(invocations, seconds_per_invocation);
+            if estimated <= watchdog.margin() {
+                continue;
+            }
+            let timing = format!(
+                "test {i}: compute {x} {y} {z} is estimated at {:.2} s from an earlier run of this shader, against the {:.2} s timeout",
+                estimated.as_secs_f64(),
+                watchdog.timeout.as_secs_f64()
+            );
+            if self.dispatch_offset_push.is_some() {
+                budget = Some(
+                    watchdog::invocations_within(chunk_duration, seconds_per_invocation)
+                        .max(group_invocations),
+                );
+                notes.push(format!(
+                    "- {timing}; split into dispatches of about {:.2} s",
+                    chunk_duration.as_secs_f64()
+                ));
+            } else if estimated > watchdog.timeout {
+                return Err(ErrorCode::Timeout.invalid_params(
+                    format!(
+                        "{timing} of the {}, so running it would likely reset the GPU. Dispatch fewer workgroups, or add a uvec3 push constant that the shader adds to gl_WorkGroupID and set dispatch_offset_push to its offset so the dispatch can be split",
+                        watchdog.source
+                    ),
+                    None,
+                ));
+            } else {
+                notes.push(format!(
+                    "- {timing}; set dispatch_offset_push to let it be split"
+                ));
+            }
+        }
+
+        Ok((budget, notes))
+    }
+
+    /// Refuses the request if a SPIR-V pass or a dispatch is over one of
+    /// the server's budgets. GLSL passes, which vkrunner compiles
+    /// itself, only have their dispatches checked.
+    fn check_budgets(
+        &self,
+        compiled: &[Vec<String>],
+        budgets: &ComplexityBudgets,
+    ) -> Result<(), McpError> {
+        for pass in &self.passes {
+            let Some((stage, reference)) = pass.spirv_input() else {
+                continue;
+            };
+            let path = self.resolve_spvasm_path(reference, compiled)?;
+            let Ok(spvasm) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let module = spirv::Module::parse(&spvasm);
+            let stage = stage.display_name();
+
+            let instructions = module.instructions.len();
+            ComplexityBudgets::check(
+                "max_spirv_instructions",
+                budgets.max_spirv_instructions,
+                instructions,
+                || format!("the {stage} shader {reference} has {instructions} instructions"),
+            )?;
+            let loop_depth = module.loop_depth();
+            ComplexityBudgets::check(
+                "max_loop_nesting",
+                budgets.max_loop_nesting,
+                loop_depth,
+                || format!("the {stage} shader {reference} nests loops {loop_depth} deep"),
+            )?;
+        }
+
+        for (i, [x, y, z], workgroups) in self.dispatch_invocations([1, 1, 1]) {
+            ComplexityBudgets::check(
+                "max_dispatch_workgroups",
+                budgets.max_dispatch_workgroups,
+                workgroups,
+                || format!("test {i}: compute {x} {y} {z} dispatches {workgroups} workgroups"),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Images the request renders: one per scene (or one without
+    /// scenes), one per debug view and one per draw with snapshot_draws.
+    fn frame_count(&self) -> usize {
+        let renders = self.scenes.as_ref().map_or(1, Vec::len);
+        let debug_views = self.debug_views.as_ref().map_or(0, Vec::len);
+        let snapshots = if self.snapshot_draws == Some(true) {
+            let tests = match &self.scenes {
+                Some(scenes) => scenes.iter().flat_map(|scene| &scene.tests).collect(),
+                None => self.tests.iter().collect::<Vec<_>>(),
+            };
+            tests.iter().filter(|test| test.is_draw()).count()
+        } else {
+            0
+        };
+        renders + debug_views + snapshots
+    }
+
+    /// Lists the capabilities each compiled SPIR-V pass declares.
+    fn capability_demands(
+        &self,
+        compiled: &[Vec<String>],
+    ) -> Result<Vec<CapabilityDemand>, McpError> {
+        let mut demands = Vec::new();
+
+        for pass in &self.passes {
+            let Some((_, reference)) = pass.spirv_input() else {
+                continue;
+            };
+
+            let path = self.resolve_spvasm_path(reference, compiled)?;
+            let Ok(spvasm) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            let module = spirv::Module::parse(&spvasm);
+            for capability in module.capabilities() {
+                // The clock capability covers both scopes
+                let feature = match capability {
+                    "ShaderClockKHR" if module.reads_device_clock() => Some("shaderDeviceClock"),
+                    capability => capability_feature(capability),
+                };
+                demands.push(CapabilityDemand {
+                    capability: capability.to_string(),
+                    feature,
+                    reference: reference.to_string(),
+                });
+            }
+        }
+
+        Ok(demands)
+    }
+
+    /// The stores-and-atomics feature each graphics pass that writes to
+    /// storage buffers or images needs, with the pass's reference.
+    fn storage_write_features(
+        &self,
+        compiled: &[Vec<String>],
+    ) -> Result<Vec<(&'static str, String)>, McpError> {
+        let mut features = Vec::new();
+
+        for pass in &self.passes {
+            let (feature, reference) = match pass.spirv_input() {
+                Some((ShaderStage::Comp, _)) | None => continue,
+                Some((ShaderStage::Frag, reference)) => ("fragmentStoresAndAtomics", reference),
+                Some((_, reference)) => ("vertexPipelineStoresAndAtomics", reference),
+            };
+
+            let path = self.resolve_spvasm_path(reference, compiled)?;
+            let Ok(spvasm) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            if spirv::Module::parse(&spvasm).writes_storage() {
+                features.push((feature, reference.to_string()));
+            }
+        }
+
+        Ok(features)
+    }
+
+    /// Device features the test data, commands and passes imply, with
+    /// what implies each: shaders only read doubles with shaderFloat64
+    /// and half floats in a buffer or push constants with the matching
+    /// 16-bit storage feature, logic ops need logicOp, wide lines
+    /// wideLines, wireframes fillModeNonSolid, and GLSL passes of the
+    /// optional stages their stage feature.
+    fn implied_features(&self) -> Vec<(&'static str, &'static str)> {
+        let vertex_doubles = [&self.vertex_data, &self.instance_data]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|data| match data {
+                ShaderRunnerVertexData::AttributeFormat { format, .. } => {
+                    format.contains("64_SFLOAT") || format.starts_with("double/")
+                }
+                ShaderRunnerVertexData::Dvec2 { .. }
+                | ShaderRunnerVertexData::Dvec3 { .. }
+                | ShaderRunnerVertexData::Dvec4 { .. } => true,
+                _ => false,
+            });
+
+        let mut features = Vec::new();
+        if vertex_doubles {
+            features.push(("shaderFloat64", "double-precision values in the test data"));
+        }
+        for test in &self.tests {
+            let (data_type, half_feature) = match test {
+                ShaderRunnerTest::SSBOSubData { data_type, .. }
+                | ShaderRunnerTest::ProbeSsbo { data_type, .. } => (
+                    data_type,
+                    ("storageBuffer16BitAccess", "half-float SSBO data"),
+                ),
+                ShaderRunnerTest::UBOSubData { data_type, .. } => (
+                    data_type,
+                    ("uniformAndStorageBuffer16BitAccess", "half-float UBO data"),
+                ),
+                ShaderRunnerTest::Push { data_type, .. } => (
+                    data_type,
+                    ("storagePushConstant16", "half-float push constants"),
+                ),
+                _ => continue,
+            };
+            let feature = if data_type.is_double() {
+                ("shaderFloat64", "double-precision values in the test data")
+            } else if data_type.is_half() {
+                half_feature
+            } else {
+                continue;
+            };
+            if !features.contains(&feature) {
+                features.push(feature);
+            }
+        }
+        if self.enables_logic_op() {
+            features.push(("logicOp", "LogicOpEnable in the tests"));
+        }
+        if self
+            .tests
+            .iter()
+            .any(|test| matches!(test, ShaderRunnerTest::LineWidth { width } if *width > 1.0))
+        {
+            features.push(("wideLines", "LineWidth above 1 in the tests"));
+        }
+        if self.tests.iter().any(
+            |test| matches!(test, ShaderRunnerTest::PolygonMode { mode } if !mode.ends_with("FILL")),
+        ) {
+            features.push(("fillModeNonSolid", "a line or point PolygonMode in the tests"));
+        }
+        for pass in &self.passes {
+            let feature = match pass {
+                ShaderRunnerPass::GeomGlsl { .. } => ("geometryShader", "a GeomGlsl pass"),
+                ShaderRunnerPass::TescGlsl { .. } => ("tessellationShader", "a TescGlsl pass"),
+                ShaderRunnerPass::TeseGlsl { .. } => ("tessellationShader", "a TeseGlsl pass"),
+                _ => continue,
+            };
+            if !features.iter().any(|(line, _)| *line == feature.0) {
+                features.push(feature);
+            }
+        }
+
+        features
+    }
+
+    /// The depth/stencil formats to pick from, best first, when depth or
+    /// stencil testing is enabled without a `DepthStencil` requirement,
+    /// and what enables it.
+    fn depth_stencil_candidates(&self) -> Option<(&'static [&'static str], &'static str)> {
+        let declared = self
+            .requirements
+            .iter()
+            .flatten()
+            .any(|requirement| matches!(requirement, ShaderRunnerRequire::DepthStencil(_)));
+        let enables = |enabled: fn(&ShaderRunnerTest) -> bool| self.tests.iter().any(enabled);
+
+        if declared {
+            None
+        } else if enables(|test| {
+            matches!(test, ShaderRunnerTest::StencilTestEnable { enable: true })
+        }) {
+            Some((STENCIL_FORMATS, "StencilTestEnable in the tests"))
+        } else if enables(|test| matches!(test, ShaderRunnerTest::DepthTestEnable { enable: true }))
+        {
+            Some((DEPTH_FORMATS, "DepthTestEnable in the tests"))
+        } else {
+            None
+        }
+    }
+
+    fn enables_logic_op(&self) -> bool {
+        self.tests
+            .iter()
+            .any(|test| matches!(test, ShaderRunnerTest::LogicOpEnable { enable: true }))
+    }
+
+    /// Finds device features the capabilities need that the requirements
+    /// don't already list. Returns the `[require]` lines to add and a note
+    /// per feature (or unsupported check) for the result.
+    fn infer_requirements(
+        &self,
+        demands: &[CapabilityDemand],
+        storage_writes: &[(&'static str, String)],
+    ) -> (Vec<String>, Vec<String>) {
+        let mut lines = self
+            .requirements
+            .iter()
+            .flatten()
+            .map(ShaderRunnerRequire::require_line)
+            .collect::<Vec<_>>();
+        let declared = lines.len();
+        let mut notes = Vec::new();
+
+        for CapabilityDemand {
+            capability,
+            feature,
+            reference,
+        } in demands
+        {
+            if let Some(feature) = feature {
+                if !lines.iter().any(|line| line == feature) {
+                    lines.push(feature.to_string());
+                    notes.push(format!(
+                        "- {feature} (OpCapability {capability} in {reference})"
+                    ));
+                }
+            } else if capability.starts_with("GroupNonUniform") && capability != "GroupNonUniform" {
+                // Supported subgroup operations are a device property
+                // that [require] cannot express
+                notes.push(format!(
+                    "- not checked: OpCapability {capability} in {reference} needs subgroup operation support, which cannot be required"
+                ));
+            }
+        }
+
+        for (feature, reference) in storage_writes {
+            if !lines.iter().any(|line| line == feature) {
+                lines.push(feature.to_string());
+                notes.push(format!(
+                    "- {feature} (storage buffer or image writes in {reference})"
+                ));
+            }
+        }
+
+        // Double and half data is only useful to shaders that can read
+        // it, and logic ops to devices that have them
+        for (feature, reason) in self.implied_features() {
+            if !lines.iter().any(|line| line == feature) {
+                lines.push(feature.to_string());
+                notes.push(format!("- {feature} ({reason})"));
+            }
+        }
+
+        // The best format first; run_shaders falls back to the others if
+        // the device lacks it
+        if let Some((formats, reason)) = self.depth_stencil_candidates() {
+            lines.push(format!("depthstencil {}", formats[0]));
+            notes.push(format!("- depthstencil {} ({reason})", formats[0]));
+        }
+
+        notes.dedup();
+        (lines.split_off(declared), notes)
+    }
+
+    /// Checks buffer and texture commands against the descriptor bindings the SPIR-V
+    /// passes declare. Skipped when a GLSL pass is present, since its
+    /// bindings are only known once vkrunner compiles it.
+    fn validate_bindings(&self, compiled: &[Vec<String>]) -> Result<(), McpError> {
+        let mut declared = std::collections::BTreeSet::new();
+
+        for pass in &self.passes {
+            let Some((_, reference)) = pass.spirv_input() else {
+                if matches!(pass, ShaderRunnerPass::VertPassthrough) {
+                    continue;
+                }
+                return Ok(());
+            };
+
+            let path = self.resolve_spvasm_path(reference, compiled)?;
+            let Ok(spvasm) = std::fs::read_to_string(&path) else {
+                return Ok(());
+            };
+            declared.extend(spirv::Module::parse(&spvasm).resource_bindings());
+        }
+
+        // Buffers reached through a device address don't need a
+        // descriptor in any shader.
+        declared.extend(
+            self.tests
+                .iter()
+                .filter_map(ShaderRunnerTest::address_target),
+        );
+
+        for (i, test) in self.tests.iter().enumerate() {
+            let Some((set, binding)) = test.resource_binding() else {
+                continue;
+            };
+
+            if !declared.contains(&(set, binding)) {
+                let available = declared
+                    .iter()
+                    .map(|(set, binding)| format!("{set}:{binding}"))
+                    .collect::<Vec<_>>();
+                return Err(McpError::invalid_params(
+                    format!(
+                        "test {i}: no shader declares a resource at set {set}, binding {binding}"
+                    ),
+                    Some(json!({"declared": available})),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that compiled artifacts and passes line up: each artifact is
+    /// loaded by at most one pass of its own stage, each compile request is
+    /// used (only one of its define variants needs to be), and draws have a
+    /// vertex stage.
+    fn validate_pipeline(&self) -> Result<(), McpError> {
+        let mut referenced = std::collections::HashSet::new();
+
+        for pass in &self.passes {
+            let Some((stage, reference)) = pass.spirv_input() else {
+                continue;
+            };
+
+            let Some((index, variant)) = self.locate_output(reference)? else {
+                continue;
+            };
+
+            if !referenced.insert((index, variant)) {
+                return Err(McpError::invalid_params(
+                    format!("{reference} is referenced by more than one pass"),
+                    None,
+                ));
+            }
+
+            let req = &self.requests[index];
+
+            if req.stage != stage {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "{reference} was compiled as a {} shader but is used by a {} pass",
+                        req.stage.display_name(),
+                        stage.display_name()
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        for (index, req) in self.requests.iter().enumerate() {
+            if !referenced
+                .iter()
+                .any(|(referenced, _)| *referenced == index)
+            {
+                let name = req
+                    .output_path()
+                    .unwrap_or_else(|| format!("request:{index}"));
+                return Err(McpError::invalid_params(
+                    format!("{name} is compiled but no pass references it"),
+                    Some(json!({"stage": req.stage.display_name()})),
+                ));
+            }
+        }
+
+        let draws = self.tests.iter().any(ShaderRunnerTest::is_draw);
+
+        if draws && !self.passes.iter().any(ShaderRunnerPass::is_vertex_stage) {
+            return Err(McpError::invalid_params(
+                "draw commands need a vertex stage: add a VertPassthrough, VertSpirv or VertGlsl pass",
+                None,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Key of the run cache for this request after its shaders compiled
+    /// to `compiled`: the SHA-256 of the [`RunCacheKey`] of the request,
+    /// run on `icd` with `portability`.
+    fn run_cache_key(
+        &self,
+        compiled: &[Vec<String>],
+        icd: Option<&Path>,
+        portability: bool,
+    ) -> Result<String, McpError> {
+        // SPIR-V files can change on disk under the same path
+        let shaders = self
+            .passes
+            .iter()
+            .filter_map(ShaderRunnerPass::spirv_input)
+            .map(|(_, reference)| {
+                let path = self.resolve_spvasm_path(reference, compiled)?;
+                Ok(std::fs::read_to_string(path).ok())
+            })
+            .collect::<Result<Vec<_>, McpError>>()?;
+        let key = RunCacheKey {
+            requests: &self.requests,
+            requirements: &self.requirements,
+            passes: &self.passes,
+            vertex_data: &self.vertex_data,
+            instance_data: &self.instance_data,
+            instance_divisor: self.instance_divisor,
+            tests: &self.tests,
+            vkrunner_options: &self.vkrunner_options,
+            ordering_lint: &self.ordering_lint,
+            snapshot_draws: self.snapshot_draws,
+            crop: &self.crop,
+            color_space: &self.color_space,
+            probe_color_space: &self.probe_color_space,
+            infer_requirements: self.infer_requirements,
+            suggest_tolerances: self.suggest_tolerances,
+            expected_image: &self.expected_image,
+            debug_views: &self.debug_views,
+            seed: self.seed,
+            dispatch_offset_push: self.dispatch_offset_push,
+            output_path: &self.output_path,
+            label: &self.label,
+            return_image_max_dim: self.return_image_max_dim,
+            shaders,
+            icd,
+            driver_env: ["VK_ICD_FILENAMES", "VK_DRIVER_FILES"]
+                .map(|name| std::env::var(name).ok()),
+            portability,
+        };
+        let key = serde_json::to_string(&key).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize the cache key",
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+        Ok(sha256_hex(key))
+    }
+
+    /// A copy of the request to change for another run.
+    fn duplicate(&self) -> Result<CompileRunShadersRequest, McpError> {
+        serde_json::to_value(self)
+            .and_then(serde_json::from_value)
+            .map_err(|e| {
+                McpError::internal_error(
+                    "Failed to copy the request",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })
+    }
+
+    /// Drops the probes and image checks, for runs whose images are
+    /// looked at rather than checked. Failing probes would also stop
+    /// vkrunner before it writes the image.
+    fn without_checks(&mut self) {
+        self.tests.retain(|test| {
+            !matches!(
+                test,
+                ShaderRunnerTest::Probe { .. }
+                    | ShaderRunnerTest::RelativeProbe { .. }
+                    | ShaderRunnerTest::ProbeSsbo { .. }
+            )
+        });
+        self.expected_image = None;
+        self.crop = None;
+        self.snapshot_draws = None;
+        self.debug_views = None;
+    }
+
+    /// Makes `state` the only command setting its pipeline state, ahead of
+    /// every draw.
+    fn set_state(&mut self, state: ShaderRunnerTest) {
+        let kind = std::mem::discriminant(&state);
+        self.tests
+            .retain(|test| std::mem::discriminant(test) != kind);
+        self.tests.insert(0, state);
+    }
+
+    /// Replaces the shader of every vertex or fragment pass with GLSL
+    /// `source`. A compile request of this call gets the new source, so
+    /// the references to it stay valid.
+    fn replace_shader(&mut self, stage: ShaderStage, source: &str) -> Result<(), McpError> {
+        for i in 0..self.passes.len() {
+            let reference = match self.passes[i].spirv_input() {
+                Some((pass_stage, reference)) if pass_stage == stage => reference.to_string(),
+                _ => continue,
+            };
+
+            let pass = match self.locate_output(&reference)? {
+                Some((index, _)) => {
+                    let request = &mut self.requests[index];
+                    request.source = source.to_string();
+                    request.language = None;
+                    request.entry_point = None;
+                    request.header = None;
+                    request.libraries = None;
+                    match stage {
+                        ShaderStage::Vert => ShaderRunnerPass::VertSpirv {
+                            vert_spvasm_path: reference,
+                            entrypoint: None,
+                        },
+                        _ => ShaderRunnerPass::FragSpirv {
+                            frag_spvasm_path: reference,
+                            entrypoint: None,
+                        },
+                    }
+                }
+                None => match stage {
+                    ShaderStage::Vert => ShaderRunnerPass::VertGlsl {
+                        source: source.to_string(),
+                    },
+                    _ => ShaderRunnerPass::FragGlsl {
+                        source: source.to_string(),
+                    },
+                },
+            };
+            self.passes[i] = pass;
+        }
+
+        for pass in &mut self.passes {
+            match (stage, pass) {
+                (ShaderStage::Vert, ShaderRunnerPass::VertGlsl { source: glsl })
+                | (ShaderStage::Frag, ShaderRunnerPass::FragGlsl { source: glsl }) => {
+                    *glsl = source.to_string();
+                }
+                _ => {}
+            }
+        }
+
+        self.tests.retain(|test| match test {
+            ShaderRunnerTest::VertexEntrypoint { .. } => stage != ShaderStage::Vert,
+            ShaderRunnerTest::FragmentEntrypoint { .. } => stage != ShaderStage::Frag,
+            _ => true,
+        });
+        Ok(())
+    }
+
+    /// Checks that logic ops, when enabled, apply to the framebuffer:
+    /// Vulkan silently skips them for float and sRGB attachments, so the
+    /// probes would see plain blending results instead.
+    fn validate_logic_op(&self) -> Result<(), McpError> {
+        if !self.enables_logic_op() {
+            return Ok(());
+        }
+
+        let format = self
+            .requirements
+            .iter()
+            .flatten()
+            .filter_map(|requirement| match requirement {
+                ShaderRunnerRequire::Framebuffer(format) => Some(format.as_str()),
+                _ => None,
+            })
+            .next_back()
+            .unwrap_or("B8G8R8A8_UNORM");
+        let name = format.strip_prefix("VK_FORMAT_").unwrap_or(format);
+        let logic_op_format = name.split('_').any(|part| {
+            matches!(
+                part,
+                "UNORM" | "SNORM" | "UINT" | "SINT" | "USCALED" | "SSCALED"
+            )
+        });
+
+        if !logic_op_format {
+            return Err(McpError::invalid_params(
+                format!(
+                    "LogicOpEnable has no effect on the {name} framebuffer: Vulkan only applies logic ops to integer and normalized (UNORM, SNORM, UINT, SINT) color attachments and ignores them for float and sRGB ones. Require a framebuffer such as R8G8B8A8_UNORM or R32G32B32A32_UINT"
+                ),
+                Some(json!({"framebuffer": name})),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the vertex and instance data rows, and that the two sections
+    /// never declare the same attribute location.
+    fn validate_vertex_data(&self) -> Result<(), McpError> {
+        if let Some(rows) = &self.vertex_data {
+            check_vertex_rows("vertex data", rows)?;
+        }
+
+        let Some(instance_data) = &self.instance_data else {
+            if self.instance_divisor.is_some() {
+                return Err(McpError::invalid_params(
+                    "instance_divisor needs instance_data",
+                    None,
+                ));
+            }
+            return Ok(());
+        };
+
+        check_vertex_rows("instance data", instance_data)?;
+
+        let instance_locations = attribute_locations(instance_data);
+        if let Some(location) = attribute_locations(self.vertex_data.iter().flatten())
+            .into_iter()
+            .find(|location| instance_locations.contains(location))
+        {
+            return Err(McpError::invalid_params(
+                format!(
+                    "attribute location {location} is used in both vertex_data and instance_data"
+                ),
+                Some(json!({"location": location})),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks what a run can be checked for before its shaders compile.
+    fn validate_run(&self) -> Result<(), McpError> {
+        self.validate_values()?;
+        self.validate_vertex_data()?;
+        self.validate_pipeline()?;
+        self.validate_logic_op()?;
+        if let Some(options) = &self.vkrunner_options {
+            options.validate_environment()?;
+        }
+
+        if self.output_path.is_none() {
+            let needs_image = [
+                (
+                    self.snapshot_draws == Some(true),
+                    "snapshot_draws needs an output_path to name the snapshots after",
+                ),
+                (
+                    self.expected_image.is_some(),
+                    "expected_image needs an output_path so that an image is captured",
+                ),
+                (
+                    self.crop.is_some(),
+                    "crop needs an output_path so that an image is captured",
+                ),
+            ];
+            if let Some((_, message)) = needs_image.into_iter().find(|(set, _)| *set) {
+                return Err(McpError::invalid_params(message, None));
+            }
+        }
+        Ok(())
+    }
+
+    /// The ordering issues to warn about, or an error listing them when
+    /// ordering_lint fails the run on them.
+    fn lint_ordering(&self) -> Result<Vec<String>, McpError> {
+        match self.ordering_lint.unwrap_or_default() {
+            OrderingLint::Off => Ok(Vec::new()),
+            OrderingLint::Warn => Ok(self.ordering_issues()),
+            OrderingLint::Fail => {
+                let issues = self.ordering_issues();
+                if !issues.is_empty() {
+                    return Err(McpError::invalid_params(
+                        "Test commands are out of order",
+                        Some(json!({"issues": issues})),
+                    ));
+                }
+                Ok(issues)
+            }
+        }
+    }
+
+    /// Compiles every shader of the request. Every shader is compiled
+    /// even after one fails, so that the good ones can be referenced by
+    /// the next run.
+    fn compile_requests(&self) -> Result<CompiledShaders, McpError> {
+        let mut shaders = CompiledShaders::default();
+
+        for (index, req) in self.requests.iter().enumerate() {
+            let mut paths = Vec::new();
+
+            for (variant, (output_path, defines)) in req.variant_outputs().iter().enumerate() {
+                let reference = if req.define_variants.is_some() {
+                    format!("request:{index}/{variant}")
+                } else {
+                    format!("request:{index}")
+                };
+
+                let started = Instant::now();
+                let spvasm = match req.compile(defines)? {
+                    Ok(spvasm) => spvasm,
+                    Err(message) => {
+                        shaders.statuses.push(format!(
+                            "- {reference} ({}): failed",
+                            req.stage.display_name()
+                        ));
+                        shaders.failures.push(message);
+                        continue;
+                    }
+                };
+                shaders
+                    .timings
+                    .push((format!("compile {reference}"), started.elapsed()));
+                shaders.analysis_warnings.extend(
+                    req.analysis_warnings(&spvasm)
+                        .into_iter()
+                        .map(|warning| format!("- {reference}: {warning}")),
+                );
+
+                let (tmp_output_path, name) = match output_path {
+                    Some(path) => (path.clone(), path.clone()),
+                    None => {
+                        let id = artifact_id(&spvasm);
+                        shaders.artifact_notes.push(format!(
+                            "- {reference} ({}): {id}",
+                            req.stage.display_name()
+                        ));
+                        (artifact_path(&id), format!("artifact {id}"))
+                    }
+                };
+
+                write_spvasm(&tmp_output_path, &spvasm)?;
+                shaders.statuses.push(format!(
+                    "- {reference} ({}): compiled to {name}",
+                    req.stage.display_name()
+                ));
+
+                if req.include_disassembly.unwrap_or(false) {
+                    shaders
+                        .disassemblies
+                        .push(format!("Disassembly of {tmp_output_path}:\n{spvasm}\n"));
+                }
+
+                paths.push(tmp_output_path);
+            }
+
+            if req.define_variants.is_some() {
+                let name = req
+                    .tmp_output_path
+                    .clone()
+                    .unwrap_or_else(|| format!("request:{index}"));
+                shaders.variant_notes.push(format!("Variants of {name}:"));
+                for (path, (_, defines)) in paths.iter().zip(req.variant_outputs()) {
+                    let defines = defines
+                        .iter()
+                        .map(MacroDefinition::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    shaders.variant_notes.push(format!("- {path}: {defines}"));
+                }
+            }
+
+            shaders.paths.push(paths);
+        }
+
+        Ok(shaders)
+    }
+
+    /// Writes the vkrunner script of the run to `path`: the requirements
+    /// with `inferred_requirements` added, the passes, the vertex data and
+    /// the test commands, with dispatches over the budget split into
+    /// chunks of `chunk_workgroups`.
+    fn write_shader_test(
+        &self,
+        path: &str,
+        compiled: &[Vec<String>],
+        inferred_requirements: Vec<String>,
+        chunk_workgroups: Option<u64>,
+        scratch: &ScratchDir,
+    ) -> Result<ShaderTestScript, McpError> {
+        use std::io::Write;
+
+        let mut file = File::create(path).map_err(io_err)?;
+
+        let require_lines = self
+            .requirements
+            .iter()
+            .flatten()
+            .map(ShaderRunnerRequire::require_line)
+            .chain(inferred_requirements)
+            .collect::<Vec<_>>();
+        if !require_lines.is_empty() {
+            writeln!(file, "[require]").map_err(io_err)?;
+            for line in &require_lines {
+                writeln!(file, "{line}").map_err(io_err)?;
+            }
+            writeln!(file).map_err(io_err)?;
+        }
+        let mut script = ShaderTestScript {
+            require_lines,
+            ..Default::default()
+        };
+
+        let mut entrypoints = Vec::new();
+        for pass in &self.passes {
+            writeln!(file, "{}", pass.script_header()).map_err(io_err)?;
+
+            if let Some((stage, reference)) = pass.spirv_input() {
+                let path = self.resolve_spvasm_path(reference, compiled)?;
+                let spvasm = std::fs::read_to_string(&path).map_err(|e| {
+                    ErrorCode::BadRequestPath.internal_error(
+                        format!(
+                            "Failed to read {} shader SPIR-V file at {path}",
+                            stage.script_name()
+                        ),
+                        Some(json!({"error": e.to_string()})),
+                    )
+                })?;
+                if let Some(name) = pass.entrypoint() {
+                    check_entrypoint(&spvasm, name, &path)?;
+                    entrypoints.push((stage, name));
+                }
+                writeln!(file, "{spvasm}").map_err(io_err)?;
+                script
+                    .modules
+                    .push((format!("{} shader", stage.script_name()), path, spvasm));
+            } else if let Some((_, source)) = pass.glsl_input() {
+                writeln!(file, "{source}").map_err(io_err)?;
+            }
+
+            writeln!(file).map_err(io_err)?;
+        }
+
+        if let Some(vertex_data) = &self.vertex_data {
+            write_vertex_section(&mut file, "[vertex data]", vertex_data).map_err(io_err)?;
+        }
+
+        if let Some(instance_data) = &self.instance_data {
+            let header = match self.instance_divisor.unwrap_or(1) {
+                1 => "[instance data]".to_string(),
+                divisor => format!("[instance data divisor {divisor}]"),
+            };
+            write_vertex_section(&mut file, &header, instance_data).map_err(io_err)?;
+        }
+
+        writeln!(file, "[test]").map_err(io_err)?;
+
+        for (stage, name) in &entrypoints {
+            writeln!(file, "{} entrypoint {name}", stage.script_name()).map_err(io_err)?;
+        }
+
+        if let Some(offset) = self.dispatch_offset_push {
+            writeln!(file, "push uvec3 {offset} 0 0 0").map_err(io_err)?;
+        }
+
+        let push_stages = self.reflected_push_stages(compiled)?;
+        for test in &self.tests {
+            self.write_test_command(
+                &mut file,
+                test,
+                scratch,
+                &push_stages,
+                chunk_workgroups,
+                &mut script.dispatch_notes,
+            )?;
+        }
+
+        file.flush().map_err(io_err)?;
+        Ok(script)
+    }
+
+    /// Writes the script lines of one test command. Textures are written
+    /// to files in `scratch`, push ranges without stages get
+    /// `push_stages`, and the split of an oversized dispatch is noted in
+    /// `dispatch_notes`.
+    fn write_test_command(
+        &self,
+        file: &mut File,
+        test: &ShaderRunnerTest,
+        scratch: &ScratchDir,
+        push_stages: &Option<Vec<ShaderStage>>,
+        chunk_workgroups: Option<u64>,
+        dispatch_notes: &mut Vec<String>,
+    ) -> Result<(), McpError> {
+        use std::io::Write;
+
+        let color_space = self.color_space.unwrap_or_default();
+        let probe_color_space = self.probe_color_space.unwrap_or(color_space);
+
+        match test {
+            ShaderRunnerTest::FragmentEntrypoint { name } => {
+                writeln!(file, "fragment entrypoint {name}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::VertexEntrypoint { name } => {
+                writeln!(file, "vertex entrypoint {name}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::ComputeEntrypoint { name } => {
+                writeln!(file, "compute entrypoint {name}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::GeometryEntrypoint { name } => {
+                writeln!(file, "geometry entrypoint {name}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::DrawRect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                writeln!(file, "draw rect {x} {y} {width} {height}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::DrawArrays {
+                primitive_type,
+                first,
+                count,
+                instance_count,
+            } => match instance_count {
+                Some(instances) => writeln!(
+                    file,
+                    "draw arrays instanced {primitive_type} {first} {count} {instances}"
+                )
+                .map_err(io_err)?,
+                None => writeln!(file, "draw arrays {primitive_type} {first} {count}")
+                    .map_err(io_err)?,
+            },
+            ShaderRunnerTest::DrawArraysIndexed {
+                primitive_type,
+                first,
+                count,
+                instance_count,
+            } => match instance_count {
+                Some(instances) => writeln!(
+                    file,
+                    "draw arrays indexed instanced {primitive_type} {first} {count} {instances}"
+                )
+                .map_err(io_err)?,
+                None => writeln!(file, "draw arrays indexed {primitive_type} {first} {count}")
+                    .map_err(io_err)?,
+            },
+            ShaderRunnerTest::Texture {
+                binding,
+                descriptor_set,
+                width,
+                height,
+                kind,
+                mipmaps,
+                depth,
+                sampler,
+                source,
+            } => {
+                let kind = kind.unwrap_or(TextureKind::Texture2D);
+                let texture_path = scratch.path(&format!(
+                    "texture_{}_{binding}.rgba",
+                    descriptor_set.unwrap_or(0)
+                ));
+                let mut texels = Vec::new();
+                for layer in 0..kind.layers() {
+                    texels.extend(source.texels(*width, *height, layer, self.seed.unwrap_or(0))?);
+                }
+                let depth = depth.unwrap_or(false);
+                if depth {
+                    // One float per texel from the red channel, the
+                    // same size as the RGBA8 texels
+                    texels = texels
+                        .chunks(4)
+                        .flat_map(|texel| (f32::from(texel[0]) / 255.0).to_le_bytes())
+                        .collect();
+                }
+                std::fs::write(&texture_path, texels).map_err(io_err)?;
+
+                let binding = binding_ref(*descriptor_set, *binding);
+                let mipmaps = if mipmaps.unwrap_or(false) {
+                    " mipmaps"
+                } else {
+                    ""
+                };
+                let depth = if depth { " depth" } else { "" };
+                let sampler = sampler
+                    .as_ref()
+                    .map(SamplerOptions::script_words)
+                    .unwrap_or_default();
+                writeln!(
+                    file,
+                    "texture {binding}{}{mipmaps}{depth}{sampler} {width} {height} {texture_path}",
+                    kind.script_words()
+                )
+                .map_err(io_err)?;
+            }
+            ShaderRunnerTest::SSBO {
+                binding,
+                size,
+                data,
+                descriptor_set,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+
+                if let Some(size) = size {
+                    writeln!(file, "ssbo {binding} {size}").map_err(io_err)?;
+                } else if let Some(_data) = data {
+                    writeln!(file, "ssbo {binding} data").map_err(io_err)?;
+                }
+            }
+            ShaderRunnerTest::SSBOSubData {
+                binding,
+                data_type,
+                offset,
+                values,
+                descriptor_set,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+
+                write!(
+                    file,
+                    "ssbo {binding} subdata {} {offset}",
+                    data_type.script_name()
+                )
+                .map_err(io_err)?;
+                write_script_values(file, *data_type, values)?;
+            }
+            ShaderRunnerTest::UBO {
+                binding,
+                data: _,
+                descriptor_set,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+
+                writeln!(file, "ubo {binding} data").map_err(io_err)?;
+            }
+            ShaderRunnerTest::UBOSubData {
+                binding,
+                data_type,
+                offset,
+                values,
+                descriptor_set,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+
+                write!(
+                    file,
+                    "ubo {binding} subdata {} {offset}",
+                    data_type.script_name()
+                )
+                .map_err(io_err)?;
+                write_script_values(file, *data_type, values)?;
+            }
+            ShaderRunnerTest::BufferLayout {
+                buffer_type,
+                layout_type,
+            } => {
+                writeln!(file, "{buffer_type} layout {layout_type}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::Push {
+                data_type,
+                offset,
+                values,
+            } => {
+                write!(file, "push {} {offset}", data_type.script_name()).map_err(io_err)?;
+                write_script_values(file, *data_type, values)?;
+            }
+            ShaderRunnerTest::PushAddress {
+                offset,
+                binding,
+                descriptor_set,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+
+                writeln!(file, "push address {offset} {binding}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::SSBOAddress {
+                binding,
+                descriptor_set,
+                offset,
+                target_binding,
+                target_descriptor_set,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+                let target = binding_ref(*target_descriptor_set, *target_binding);
+
+                writeln!(file, "ssbo {binding} address {offset} {target}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::UBOAddress {
+                binding,
+                descriptor_set,
+                offset,
+                target_binding,
+                target_descriptor_set,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+                let target = binding_ref(*target_descriptor_set, *target_binding);
+
+                writeln!(file, "ubo {binding} address {offset} {target}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::PushRange { size, stages } => {
+                if let Some(size) = size {
+                    writeln!(file, "push size {size}").map_err(io_err)?;
+                }
+
+                let stages = stages.clone().or_else(|| push_stages.clone());
+                if let Some(stages) = stages.filter(|stages| !stages.is_empty()) {
+                    let stages = stages
+                        .iter()
+                        .map(|stage| stage.display_name().to_lowercase())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    writeln!(file, "push stages {stages}").map_err(io_err)?;
+                }
+            }
+            ShaderRunnerTest::PushLayout { layout_type } => {
+                writeln!(file, "push layout {layout_type}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::Compute { x, y, z } => {
+                match (chunk_workgroups, self.dispatch_offset_push) {
+                    (Some(max), Some(offset))
+                        if u64::from(*x) * u64::from(*y) * u64::from(*z) > max =>
+                    {
+                        let chunks = dispatch_chunks([*x, *y, *z], max);
+                        for ([ox, oy, oz], [cx, cy, cz]) in &chunks {
+                            writeln!(file, "push uvec3 {offset} {ox} {oy} {oz}").map_err(io_err)?;
+                            writeln!(file, "compute {cx} {cy} {cz}").map_err(io_err)?;
+                        }
+                        writeln!(file, "push uvec3 {offset} 0 0 0").map_err(io_err)?;
+                        let [cx, cy, cz] = chunks[0].1;
+                        dispatch_notes.push(format!(
+                            "- compute {x} {y} {z}: {} dispatches of up to {cx}x{cy}x{cz} workgroups",
+                            chunks.len()
+                        ));
+                    }
+                    _ => {
+                        writeln!(file, "compute {x} {y} {z}").map_err(io_err)?;
+                    }
+                }
+            }
+            ShaderRunnerTest::Probe {
+                probe_type,
+                format,
+                args,
+            } => {
+                write!(file, "probe {probe_type} {format}").map_err(io_err)?;
+                let args = convert_probe_color(format, args, probe_color_space, color_space);
+                for arg in &args {
+                    write!(file, " {arg}").map_err(io_err)?;
+                }
+                writeln!(file).map_err(io_err)?;
+            }
+            ShaderRunnerTest::ProbeSsbo {
+                binding,
+                descriptor_set,
+                data_type,
+                offset,
+                comparison,
+                values,
+            } => {
+                let binding = binding_ref(*descriptor_set, *binding);
+
+                write!(
+                    file,
+                    "probe ssbo {} {binding} {offset} {comparison}",
+                    data_type.script_name()
+                )
+                .map_err(io_err)?;
+                write_script_values(file, *data_type, values)?;
+            }
+            ShaderRunnerTest::RelativeProbe {
+                probe_type,
+                format,
+                args,
+            } => {
+                write!(file, "relative probe {probe_type} {format}").map_err(io_err)?;
+                let args = convert_probe_color(format, args, probe_color_space, color_space);
+                for arg in &args {
+                    write!(file, " {arg}").map_err(io_err)?;
+                }
+                writeln!(file).map_err(io_err)?;
+            }
+            ShaderRunnerTest::Tolerance { values } => {
+                write!(file, "tolerance").map_err(io_err)?;
+                for value in values {
+                    write!(file, " {value}").map_err(io_err)?;
+                }
+                writeln!(file).map_err(io_err)?;
+            }
+            ShaderRunnerTest::Clear => {
+                writeln!(file, "clear").map_err(io_err)?;
+            }
+            ShaderRunnerTest::DepthTestEnable { enable } => {
+                writeln!(file, "depthTestEnable {enable}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::DepthWriteEnable { enable } => {
+                writeln!(file, "depthWriteEnable {enable}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::DepthCompareOp { op } => {
+                writeln!(file, "depthCompareOp {op}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::StencilTestEnable { enable } => {
+                writeln!(file, "stencilTestEnable {enable}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::FrontFace { mode } => {
+                writeln!(file, "frontFace {mode}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::StencilState { face, state } => {
+                for face in face.unwrap_or_default().names() {
+                    for (property, value) in state.properties() {
+                        writeln!(file, "{face}.{property} {value}").map_err(io_err)?;
+                    }
+                }
+            }
+            ShaderRunnerTest::ColorWriteMask { mask } => {
+                writeln!(file, "colorWriteMask {mask}",).map_err(io_err)?;
+            }
+            ShaderRunnerTest::BlendState { state } => {
+                for (property, value) in state.properties() {
+                    writeln!(file, "{property} {value}").map_err(io_err)?;
+                }
+            }
+            ShaderRunnerTest::LogicOpEnable { enable } => {
+                writeln!(file, "logicOpEnable {enable}",).map_err(io_err)?;
+            }
+            ShaderRunnerTest::LogicOp { op } => {
+                writeln!(file, "logicOp {op}",).map_err(io_err)?;
+            }
+            ShaderRunnerTest::CullMode { mode } => {
+                writeln!(file, "cullMode {mode}",).map_err(io_err)?;
+            }
+            ShaderRunnerTest::PolygonMode { mode } => {
+                writeln!(file, "polygonMode {mode}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::LineWidth { width } => {
+                writeln!(file, "lineWidth {width}").map_err(io_err)?;
+            }
+            ShaderRunnerTest::Require {
+                feature,
+                parameters,
+            } => {
+                write!(file, "require {feature}").map_err(io_err)?;
+                for param in parameters {
+                    write!(file, " {param}").map_err(io_err)?;
+                }
+                writeln!(file).map_err(io_err)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the image vkrunner rendered to `image_path` at
+    /// `output_path`, after describing the crop region and comparing it
+    /// with expected_image, and embeds it when the request asks to.
+    fn save_output_image(
+        &self,
+        output_path: &str,
+        image_path: &str,
+        metadata: &[(&str, String)],
+        report: &mut String,
+        images: &mut Vec<Content>,
+    ) -> Result<Option<ImageComparison>, McpError> {
+        if !Path::new(image_path).exists() {
+            report.push_str("No output image was generated by VkRunner.\n");
+            return Ok(None);
+        }
+        let img = match read_and_decode_ppm_file(image_path) {
+            Ok(img) => img,
+            Err(e) => {
+                report.push_str(&format!("Failed to convert output image: {e}\n"));
+                return Ok(None);
+            }
+        };
+        let color_space = self.color_space.unwrap_or_default();
+
+        if let Some(parent) = Path::new(output_path)
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                McpError::internal_error(
+                    "Failed to create output directory",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?;
+        }
+
+        if let Some(region) = &self.crop {
+            match region.describe(&img) {
+                Ok(pixels) => report.push_str(&pixels),
+                Err(e) => {
+                    return Err(McpError::invalid_params(
+                        e,
+                        Some(json!({"width": img.width(), "height": img.height()})),
+                    ));
+                }
+            }
+        }
+
+        let comparison = self
+            .expected_image
+            .as_ref()
+            .map(|expected| self.compare_to_expected(expected, &img, output_path, report, images))
+            .transpose()?;
+
+        // Labels only go on the images written out, after the checks
+        // have seen the rendered pixels
+        let labeled = self.label.as_deref().map(|text| {
+            let status = match &comparison {
+                Some(comparison) if comparison.mismatch => "MISMATCH",
+                _ => "PASS",
+            };
+            let mut labeled = img.clone();
+            label::draw(&mut labeled, &text.replace("{status}", status), 1);
+            labeled
+        });
+        save_framebuffer(
+            labeled.as_ref().unwrap_or(&img),
+            Path::new(output_path),
+            color_space,
+            metadata,
+        )
+        .map_err(|e| {
+            McpError::internal_error("Failed to save output image", Some(json!({"error": e})))
+        })?;
+
+        report.push_str(&format!("Image saved to: {output_path}\n"));
+
+        if let Some(max_dim) = self.return_image_max_dim {
+            // The region was checked above, so an embedded image only
+            // shows the requested pixels.
+            let img = match &self.crop {
+                Some(region) => {
+                    image::imageops::crop_imm(&img, region.x, region.y, region.width, region.height)
+                        .to_image()
+                }
+                None => labeled.unwrap_or(img),
+            };
+            match image_resource(&color_space.to_display(&img), output_path, Some(max_dim)) {
+                Ok(resource) => images.push(resource),
+                Err(e) => report.push_str(&format!("Failed to embed output image: {e}\n")),
+            }
+        }
+
+        Ok(comparison)
+    }
+
+    /// Compares the rendered `img` with `expected`, reporting the
+    /// deviation and saving a heatmap of it next to `output_path`.
+    fn compare_to_expected(
+        &self,
+        expected: &ExpectedImage,
+        img: &RgbImage,
+        output_path: &str,
+        report: &mut String,
+        images: &mut Vec<Content>,
+    ) -> Result<ImageComparison, McpError> {
+        let color_space = self.color_space.unwrap_or_default();
+        let probe_color_space = self.probe_color_space.unwrap_or(color_space);
+        let deviations = expected
+            .deviations(img, color_space, probe_color_space)
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        let tolerance = expected.tolerance.unwrap_or(0.01);
+        let (max_index, max) =
+            deviations
+                .iter()
+                .copied()
+                .enumerate()
+                .fold((0, 0.0), |worst, (i, deviation)| {
+                    if deviation > worst.1 {
+                        (i, deviation)
+                    } else {
+                        worst
+                    }
+                });
+        let mean = deviations.iter().sum::<f64>() / deviations.len() as f64;
+        let above = deviations
+            .iter()
+            .filter(|deviation| **deviation > tolerance)
+            .count();
+        report.push_str(&format!(
+            "Expected image: {} (max deviation {max:.4} at ({}, {}), mean {mean:.4}, {above} of {} pixels above tolerance {tolerance})\n",
+            if above == 0 { "match" } else { "MISMATCH" },
+            max_index as u32 % img.width(),
+            max_index as u32 / img.width(),
+            deviations.len()
+        ));
+
+        let heatmap = deviation_heatmap(&deviations, img.width(), tolerance);
+        let output_path = Path::new(output_path);
+        let stem = output_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "output".to_string());
+        let heatmap_path = output_path
+            .with_file_name(format!("{stem}_error.png"))
+            .display()
+            .to_string();
+        let saved = heatmap
+            .save(&heatmap_path)
+            .map(|()| image_resource(&heatmap, &heatmap_path, self.return_image_max_dim));
+        match saved {
+            Ok(Ok(resource)) => {
+                report.push_str(&format!(
+                    "Error heatmap (black within tolerance, blue to red up to the max deviation) saved to: {heatmap_path}\n"
+                ));
+                images.push(resource);
+            }
+            Ok(Err(e)) | Err(e) => {
+                report.push_str(&format!("Failed to write the error heatmap: {e}\n"))
+            }
+        }
+
+        Ok(ImageComparison {
+            max,
+            mean,
+            mismatch: above > 0,
+        })
+    }
+}
+
+/// The `local_size_x`, `_y` and `_z` a GLSL compute shader declares,
+/// outside comments. Sizes set by specialization constants
+/// (`local_size_x_id`) aren't read.
+fn glsl_local_size(source: &str) -> [Option<u32>; 3] {
+    let mut code = String::with_capacity(source.len());
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+            code.push(' ');
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            code.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    ["local_size_x", "local_size_y", "local_size_z"].map(|name| {
+        code.match_indices(name).find_map(|(index, _)| {
+            let value = code[index + name.len()..].trim_start().strip_prefix('=')?;
+            let digits = value.trim_start();
+            let end = digits
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(digits.len());
+            let literal = digits[..end].trim_end_matches(['u', 'U']);
+            match literal
+                .strip_prefix("0x")
+                .or_else(|| literal.strip_prefix("0X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => literal.parse().ok(),
+            }
+        })
+    })
+}
+
+fn io_err(e: std::io::Error) -> McpError {
+    McpError::internal_error("IO operation failed", Some(json!({"error": e.to_string()})))
+}
+
+/// Ends the script line of a typed command with its values as vkrunner
+/// reads them.
+fn write_script_values(
+    file: &mut impl std::io::Write,
+    data_type: DataType,
+    values: &[String],
+) -> Result<(), McpError> {
+    let values = script_values(data_type, values)
+        .map_err(|message| McpError::invalid_params(message, None))?;
+    values
+        .iter()
+        .try_for_each(|value| write!(file, " {value}"))
+        .and_then(|()| writeln!(file))
+        .map_err(|e| {
+            McpError::internal_error("IO operation failed", Some(json!({"error": e.to_string()})))
+        })
+}
+
+fn attribute_locations<'a>(rows: impl IntoIterator<Item = &'a ShaderRunnerVertexData>) -> Vec<u32> {
+    rows.into_iter()
+        .filter_map(|data| match data {
+            ShaderRunnerVertexData::AttributeFormat { location, .. } => Some(*location),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Writes a vertex data style section: its header line, the attribute
+/// formats on one line and then one line per data row.
+fn write_vertex_section(
+    file: &mut impl std::io::Write,
+    header: &str,
+    rows: &[ShaderRunnerVertexData],
+) -> std::io::Result<()> {
+    writeln!(file, "{header}")?;
+
+    let formats = rows
+        .iter()
+        .filter_map(|data| match data {
+            ShaderRunnerVertexData::AttributeFormat { location, format } => {
+                Some(format!("{location}/{format}"))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    writeln!(file, "{}", formats.join(" "))?;
+
+    for values in rows.iter().filter_map(ShaderRunnerVertexData::row_values) {
+        writeln!(file, "{}", values.join(" "))?;
+    }
+
+    writeln!(file)
+}
+
+/// Checks that attribute formats come first and that every data row
+/// supplies the values they declare, with the right kind.
+fn check_vertex_rows(section: &str, rows: &[ShaderRunnerVertexData]) -> Result<(), McpError> {
+    let formats = rows
+        .iter()
+        .take_while(|data| data.row_values().is_none())
+        .filter_map(|data| match data {
+            ShaderRunnerVertexData::AttributeFormat { location, format } => {
+                Some(format!("{location}/{format}"))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if formats.is_empty() {
+        return Err(McpError::invalid_params(
+            format!("{section} must start with AttributeFormat entries"),
+            None,
+        ));
+    }
+
+    let kinds = rows[..formats.len()]
+        .iter()
+        .map(|data| match data {
+            ShaderRunnerVertexData::AttributeFormat { format, .. } => attribute_layout(format),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|layouts| {
+            layouts
+                .into_iter()
+                .flat_map(|(components, kind)| std::iter::repeat_n(kind, components))
+                .collect::<Vec<_>>()
+        });
+
+    for (row, data) in rows.iter().enumerate().skip(formats.len()) {
+        let Some(values) = data.row_values() else {
+            return Err(McpError::invalid_params(
+                format!("{section} row {row}: AttributeFormat must appear before all data rows"),
+                Some(json!({"row": row, "formats": formats})),
+            ));
+        };
+
+        let Some(kinds) = &kinds else {
+            continue;
+        };
+
+        let problem = if values.len() != kinds.len() {
+            Some(format!(
+                "has {} values but the attribute formats declare {}",
+                values.len(),
+                kinds.len()
+            ))
+        } else {
+            kinds
+                .iter()
+                .zip(&values)
+                .position(|(kind, value)| !kind.accepts(value))
+                .map(|i| format!("value {i} is not {}", kinds[i].description()))
+        };
+
+        if let Some(problem) = problem {
+            return Err(McpError::invalid_params(
+                format!("{section} row {row} {problem}"),
+                Some(json!({"row": row, "values": values, "formats": formats})),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Grace period for in-flight tool calls when neither the command line
+/// nor the configuration file sets one.
+const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 30;
+
+/// Seconds an idle vkrunner worker keeps its Vulkan device when neither
+/// the command line nor the configuration file sets it.
+const DEFAULT_VKRUNNER_IDLE_SECS: u64 = 60;
+
+/// Environment variable with the token of the client the server serves,
+/// set by whoever launches the server for that client.
+const CLIENT_TOKEN_ENV: &str = "SHADERC_VKRUNNER_MCP_TOKEN";
+
+/// Server settings. A `--config` TOML file uses the same field names;
+/// command line flags override it.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerOptions {
+    /// Directory to change into before serving.
+    pub work_dir: Option<PathBuf>,
+    /// Let vkrunner enumerate portability drivers (MoltenVK) and
+    /// report known feature gaps in results.
+    pub portability: bool,
+    /// Never retry on lavapipe/SwiftShader when no hardware device is found.
+    pub no_software_fallback: bool,
+    /// Driver manifest (or `lavapipe`/`swiftshader`) used when a request doesn't pick one.
+    pub icd: Option<String>,
+    /// Driver manifests a request may pick with its `icd` field, on top
+    /// of `icd` and the `lavapipe`/`swiftshader` shorthands. A manifest
+    /// names a library for vkrunner to load, so others are refused.
+    pub allowed_icds: Vec<String>,
+    /// 1-based index of the Vulkan device used when a request doesn't pick one.
+    pub device_id: Option<u32>,
+    /// vkrunner executable, a path or a name looked up on `PATH`
+    /// (default `vkrunner`).
+    pub vkrunner_path: Option<PathBuf>,
+    /// Directory of the spv-... and tex-... artifacts
+    /// (default [`DEFAULT_ARTIFACT_DIR`]).
+    pub artifact_dir: Option<PathBuf>,
+    /// Number of requests allowed to execute on the GPU at once; 0 is treated as 1.
+    pub max_concurrent_runs: usize,
+    /// Seconds a vkrunner process stays up for the next run after one
+    /// ends, keeping its Vulkan device (default
+    /// [`DEFAULT_VKRUNNER_IDLE_SECS`]); 0 starts a process per run.
+    pub vkrunner_idle_timeout_secs: Option<u64>,
+    /// Seconds to let in-flight tool calls finish on shutdown
+    /// (default [`DEFAULT_SHUTDOWN_GRACE_SECS`]).
+    pub shutdown_grace_secs: Option<u64>,
+    /// Largest number of invocations a single compute dispatch may run;
+    /// bigger ones are split or refused. Unlimited if unset.
+    pub max_dispatch_invocations: Option<u64>,
+    /// GPU watchdog timeout in milliseconds to plan runs around instead
+    /// of the detected one; 0 turns watchdog handling off.
+    pub watchdog_timeout_ms: Option<u64>,
+    /// Tools no client may call unless its policy allows them explicitly.
+    pub denied_tools: Vec<String>,
+    /// Directory of suite files and GLSL sources whose tests rerun when
+    /// one of them changes.
+    pub watch: Option<PathBuf>,
+    /// Limits on the shaders and work of a request.
+    pub budgets: ComplexityBudgets,
+    /// Tool access of individual clients. Once there is one, a client
+    /// without a policy may call no tools.
+    pub clients: Vec<ClientPolicy>,
+    /// Token of the client this server serves, from [`CLIENT_TOKEN_ENV`].
+    /// Never read from or written to the configuration file.
+    #[serde(skip)]
+    pub client_token: Option<String>,
+}
+
+/// Caps on what a request may ask of the GPU, set in the `[budgets]`
+/// table of the configuration file. A request over one is refused with
+/// the budget's name before anything runs.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComplexityBudgets {
+    /// Instructions in one SPIR-V module.
+    pub max_spirv_instructions: Option<usize>,
+    /// Depth of nested loops in one SPIR-V module, through function calls.
+    pub max_loop_nesting: Option<usize>,
+    /// Workgroups in one Compute command, before any splitting.
+    pub max_dispatch_workgroups: Option<u64>,
+    /// Images one compile_run_shaders call renders: one per scene, debug
+    /// view and draw snapshot.
+    pub max_frames: Option<usize>,
+}
+
+impl ComplexityBudgets {
+    /// Refuses a request whose `actual` value is over the budget `name`.
+    fn check<T: PartialOrd + std::fmt::Display + serde::Serialize>(
+        name: &str,
+        limit: Option<T>,
+        actual: T,
+        what: impl FnOnce() -> String,
+    ) -> Result<(), McpError> {
+        match limit {
+            Some(limit) if actual > limit => Err(ErrorCode::BudgetExceeded.invalid_params(
+                format!("Over the server's {name} budget of {limit}: {}", what()),
+                Some(json!({"budget": name, "limit": limit, "actual": actual})),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Tool access of the client holding a token. Over stdio the server
+/// serves the one client that launched it, which hands the token over in
+/// [`CLIENT_TOKEN_ENV`] rather than claiming an identity in the protocol,
+/// so a client cannot take another's policy without its token.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientPolicy {
+    /// Names the client in refusals.
+    pub name: String,
+    /// Hex SHA-256 digest of the client's token, so the file holds no
+    /// secret.
+    pub token_sha256: String,
+    /// Only these tools may be called, if set; overrides `denied_tools`.
+    pub allowed_tools: Option<Vec<String>>,
+    /// Tools this client may not call, on top of the server-wide list.
+    pub denied_tools: Vec<String>,
+}
+
+impl ServerOptions {
+    /// The policy of the served client, found by the digest of its token.
+    fn client_policy(&self) -> Option<&ClientPolicy> {
+        let digest = sha256_hex(self.client_token.as_deref()?);
+        self.clients
+            .iter()
+            .find(|policy| policy.token_sha256.eq_ignore_ascii_case(&digest))
+    }
+
+    /// Whether the served client may call `tool`. With client policies
+    /// configured, a client without a known token is refused everything
+    /// rather than let through.
+    fn tool_allowed(&self, tool: &str) -> bool {
+        let denied = |list: &[String]| list.iter().any(|denied| denied == tool);
+
+        match self.client_policy() {
+            Some(ClientPolicy {
+                allowed_tools: Some(allowed),
+                denied_tools,
+                ..
+            }) => allowed.iter().any(|allowed| allowed == tool) && !denied(denied_tools),
+            Some(policy) => !denied(&policy.denied_tools) && !denied(&self.denied_tools),
+            None => self.clients.is_empty() && !denied(&self.denied_tools),
+        }
+    }
+
+    /// Resolves the driver a request picked, if it may pick it.
+    fn request_icd(&self, icd: &str) -> Result<PathBuf, McpError> {
+        let allowed = matches!(icd, "lavapipe" | "swiftshader")
+            || self.icd.as_deref() == Some(icd)
+            || self.allowed_icds.iter().any(|allowed| allowed == icd);
+        if !allowed {
+            let choices = ["lavapipe", "swiftshader"]
+                .into_iter()
+                .chain(self.icd.as_deref())
+                .chain(self.allowed_icds.iter().map(String::as_str))
+                .collect::<Vec<_>>();
+            return Err(ErrorCode::NotAllowed.invalid_params(
+                format!("Vulkan driver {icd} is not one the server allows"),
+                Some(json!({"allowed": choices})),
+            ));
+        }
+        resolve_icd(icd)
+    }
+
+    /// The vkrunner executable to run.
+    fn vkrunner(&self) -> &Path {
+        self.vkrunner_path
+            .as_deref()
+            .unwrap_or(Path::new("vkrunner"))
+    }
+
+    /// The options with the defaults of unset paths filled in, as they
+    /// are in effect.
+    fn effective(&self) -> Self {
+        let mut options = self.clone();
+        options.vkrunner_path = Some(self.vkrunner().to_path_buf());
+        options
+            .artifact_dir
+            .get_or_insert_with(|| PathBuf::from(DEFAULT_ARTIFACT_DIR));
+        options
+    }
+
+    /// Reads a TOML configuration file.
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {e}", path.display()))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config file {}: {e}", path.display()))
+    }
+
+    /// Applies command line flags on top of these options.
+    fn override_with(mut self, args: Args) -> Self {
+        self.work_dir = args.work_dir.or(self.work_dir);
+        self.portability |= args.portability;
+        self.no_software_fallback |= args.no_software_fallback;
+        self.icd = args.icd.or(self.icd);
+        if !args.allowed_icds.is_empty() {
+            self.allowed_icds = args.allowed_icds;
+        }
+        self.device_id = args.device_id.or(self.device_id);
+        self.vkrunner_path = args.vkrunner_path.or(self.vkrunner_path);
+        self.artifact_dir = args.artifact_dir.or(self.artifact_dir);
+        self.max_concurrent_runs = args
+            .max_concurrent_runs
+            .unwrap_or(self.max_concurrent_runs)
+            .max(1);
+        self.shutdown_grace_secs = args.shutdown_grace_secs.or(self.shutdown_grace_secs);
+        self.vkrunner_idle_timeout_secs = args
+            .vkrunner_idle_timeout_secs
+            .or(self.vkrunner_idle_timeout_secs);
+        self.max_dispatch_invocations = args
+            .max_dispatch_invocations
+            .or(self.max_dispatch_invocations);
+        self.watchdog_timeout_ms = args.watchdog_timeout_ms.or(self.watchdog_timeout_ms);
+        self.watch = args.watch.or(self.watch);
+        self
+    }
+}
+
+/// FIFO limit on concurrent vkrunner executions. Requests are admitted in
+/// arrival order so a burst of large runs cannot starve a small one.
+#[derive(Debug)]
+pub struct RunQueue {
+    limit: usize,
+    slots: Arc<tokio::sync::Semaphore>,
+    next_ticket: std::sync::atomic::AtomicU64,
+    /// Number of tickets admitted so far, watched by the queued requests
+    /// to follow their position.
+    admitted: tokio::sync::watch::Sender<u64>,
+}
+
+/// Held while a request uses the GPU.
+pub struct RunPermit {
+    _slot: tokio::sync::OwnedSemaphorePermit,
+    /// Requests that were running or queued ahead when this one arrived.
+    pub position: usize,
+    pub waited: Duration,
+}
+
+impl RunQueue {
+    pub fn new(limit: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            limit,
+            slots: Arc::new(tokio::sync::Semaphore::new(limit)),
+            next_ticket: Default::default(),
+            admitted: tokio::sync::watch::Sender::new(0),
+        }
+    }
+
+    /// Waits for a free slot, calling `queued` with the number of requests
+    /// ahead whenever it changes while this one waits. The worker thread is
+    /// handed back to the runtime meanwhile, so notifications still go out.
+    pub fn acquire(&self, queued: impl Fn(u64)) -> RunPermit {
+        let started = Instant::now();
+        let ticket = self.next_ticket.fetch_add(1, Ordering::SeqCst);
+        let mut admitted = self.admitted.subscribe();
+        let ahead = ticket.saturating_sub(*admitted.borrow_and_update());
+        let running = self.limit - self.slots.available_permits().min(self.limit);
+        let position = running + ahead as usize;
+
+        let wait = async {
+            let slot = self.slots.clone().acquire_owned();
+            tokio::pin!(slot);
+            if self.slots.available_permits() == 0 || ahead > 0 {
+                queued(ahead);
+            }
+            let mut reported = ahead;
+            loop {
+                tokio::select! {
+                    slot = &mut slot => break slot,
+                    Ok(()) = admitted.changed() => {
+                        let ahead = ticket.saturating_sub(*admitted.borrow_and_update());
+                        if ahead != reported {
+                            reported = ahead;
+                            queued(ahead);
+                        }
+                    }
+                }
+            }
+        };
+        let multi_thread = tokio::runtime::Handle::try_current().is_ok_and(|handle| {
+            handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread
+        });
+        let slot = if multi_thread {
+            tokio::task::block_in_place(|| futures::executor::block_on(wait))
+        } else {
+            futures::executor::block_on(wait)
+        }
+        .expect("the run queue semaphore is never closed");
+        self.admitted.send_modify(|admitted| *admitted += 1);
+
+        RunPermit {
+            _slot: slot,
+            position,
+            waited: started.elapsed(),
+        }
+    }
+}
+
+/// Directory of the intermediate files of one run, such as the script,
+/// the raw image and buffer dumps, so that concurrent runs never share a
+/// path. It is removed with its contents when dropped.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn new() -> Result<Self, McpError> {
+        let path = PathBuf::from(format!("/tmp/vkrunner_runs/{}", Self::next_id()));
+        std::fs::create_dir_all(&path).map_err(|e| {
+            McpError::internal_error(format!("Failed to create {}: {e}", path.display()), None)
+        })?;
+        Ok(Self(path))
+    }
+
+    /// Path of the file `name` in the directory.
+    pub fn path(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().to_string()
+    }
+
+    /// Path `/tmp/<stem>_<pid>-<n>.<extension>` for an output the caller
+    /// didn't name. It is unique like the directories but outlives the
+    /// call, since the response reports where it was saved.
+    pub fn output_path(stem: &str, extension: &str) -> String {
+        format!("/tmp/{stem}_{}.{extension}", Self::next_id())
+    }
+
+    fn next_id() -> String {
+        static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{id}", std::process::id())
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+const COMPILE_CACHE_LIMIT: usize = 256;
+
+/// Run results hold images, so fewer of them are kept.
+const RUN_CACHE_LIMIT: usize = 32;
+
+/// The parts of a compile_run_shaders request that decide its result,
+/// hashed into the run cache key. The result names the saved image and
+/// embeds it labeled and downscaled, so output_path, label and
+/// return_image_max_dim are part of it; scenes never get this far.
+#[derive(serde::Serialize)]
+struct RunCacheKey<'a> {
+    requests: &'a [CompileRequest],
+    requirements: &'a Option<Vec<ShaderRunnerRequire>>,
+    passes: &'a [ShaderRunnerPass],
+    vertex_data: &'a Option<Vec<ShaderRunnerVertexData>>,
+    instance_data: &'a Option<Vec<ShaderRunnerVertexData>>,
+    instance_divisor: Option<u32>,
+    tests: &'a [ShaderRunnerTest],
+    vkrunner_options: &'a Option<VkrunnerOptions>,
+    ordering_lint: &'a Option<OrderingLint>,
+    snapshot_draws: Option<bool>,
+    crop: &'a Option<PixelRegion>,
+    color_space: &'a Option<ColorSpace>,
+    probe_color_space: &'a Option<ColorSpace>,
+    infer_requirements: Option<bool>,
+    suggest_tolerances: Option<bool>,
+    expected_image: &'a Option<ExpectedImage>,
+    debug_views: &'a Option<Vec<DebugView>>,
+    seed: Option<u64>,
+    dispatch_offset_push: Option<u32>,
+    output_path: &'a Option<String>,
+    label: &'a Option<String>,
+    return_image_max_dim: Option<u32>,
+    /// Contents of the SPIR-V files the passes load
+    shaders: Vec<Option<String>>,
+    icd: Option<&'a Path>,
+    driver_env: [Option<String>; 2],
+    portability: bool,
+}
+
+/// Map that forgets its oldest entries beyond a limit, by default
+/// [`COMPILE_CACHE_LIMIT`].
+#[derive(Debug)]
+struct BoundedCache<V> {
+    entries: std::collections::HashMap<String, V>,
+    order: std::collections::VecDeque<String>,
+    limit: usize,
+}
+
+impl<V> Default for BoundedCache<V> {
+    fn default() -> Self {
+        Self::with_limit(COMPILE_CACHE_LIMIT)
+    }
+}
+
+impl<V> BoundedCache<V> {
+    fn with_limit(limit: usize) -> Self {
+        Self {
+            entries: std::collections::HashMap::new(),
+            order: std::collections::VecDeque::new(),
+            limit,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&V> {
+        self.entries.get(key)
+    }
+
+    /// Entries from the oldest to the newest.
+    fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.order
+            .iter()
+            .filter_map(|key| self.entries.get_key_value(key))
+    }
+
+    fn insert(&mut self, key: String, value: V) {
+        if self.entries.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.limit {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Tool calls in flight, so that a shutdown can refuse new calls and
+/// wait for the running ones.
+#[derive(Debug, Default)]
+struct Shutdown {
+    draining: std::sync::atomic::AtomicBool,
+    in_flight: std::sync::atomic::AtomicUsize,
+}
+
+/// Counts a tool call as in flight until dropped.
+struct InFlight(Arc<Shutdown>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Shutdown {
+    /// Registers a tool call, or returns `None` once draining started.
+    fn enter(self: &Arc<Self>) -> Option<InFlight> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight(self.clone());
+        (!self.draining.load(Ordering::SeqCst)).then_some(guard)
+    }
+
+    /// Refuses new tool calls and waits up to `grace` for the running
+    /// ones; returns whether they all finished.
+    async fn drain(self: &Arc<Self>, grace: Duration) -> bool {
+        self.draining.store(true, Ordering::SeqCst);
+        let shutdown = self.clone();
+        // Tool calls block their worker, so poll from a blocking thread
+        tokio::task::spawn_blocking(move || {
+            let deadline = Instant::now() + grace;
+            while shutdown.in_flight.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            shutdown.in_flight.load(Ordering::SeqCst) == 0
+        })
+        .await
+        .unwrap_or(false)
+    }
+}
+
+/// State kept between compile_incremental calls.
+#[derive(Debug, Default)]
+struct CompileCache {
+    /// Full sources by `src-...` ID, the bases that patches apply to.
+    sources: BoundedCache<String>,
+    /// Compiler output (SPIR-V assembly or diagnostics) by a hash of the
+    /// source and every option that affects it.
+    results: BoundedCache<Result<String, String>>,
+}
+
+#[derive(Clone)]
+pub struct ShadercVkrunnerMcp {
+    options: Arc<ServerOptions>,
+    run_queue: Arc<RunQueue>,
+    /// vkrunner processes that keep their Vulkan device between runs.
+    vkrunner_pool: Arc<pool::WorkerPool>,
+    compile_cache: Arc<std::sync::Mutex<CompileCache>>,
+    /// Results of compile_run_shaders runs that asked to be cached, by a
+    /// hash of the request, the generated script and the driver.
+    run_cache: Arc<std::sync::Mutex<BoundedCache<ShaderRun>>>,
+    /// Seconds per invocation measured for compute shaders, by a hash of
+    /// their code, for estimating dispatch times against the watchdog.
+    dispatch_rates: Arc<std::sync::Mutex<BoundedCache<f64>>>,
+    /// GPU watchdog of the hardware drivers, unless turned off.
+    watchdog: Option<watchdog::Watchdog>,
+    /// Shaders registered with register_shader, by name.
+    shader_library: Arc<std::sync::Mutex<std::collections::BTreeMap<String, LibraryShader>>>,
+    /// Results of watch mode, if a directory is watched.
+    watch: Option<Arc<watch::WatchState>>,
+    peer: Option<Peer<RoleServer>>,
+    /// Progress tokens recorded from the client's messages.
+    progress_tokens: ProgressTokens,
+    /// The progress token of the tool call this copy serves, if any.
+    progress_token: Option<ProgressToken>,
+    /// Minimum level of logging notifications, from `logging/setLevel`.
+    log_level: Arc<std::sync::Mutex<Option<LoggingLevel>>>,
+    shutdown: Arc<Shutdown>,
+}
+#[tool(tool_box)]
+impl ShadercVkrunnerMcp {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_options(ServerOptions::default())
+    }
+
+    #[must_use]
+    pub fn with_options(options: ServerOptions) -> Self {
+        Self {
+            run_queue: Arc::new(RunQueue::new(options.max_concurrent_runs)),
+            vkrunner_pool: pool::WorkerPool::new(
+                Duration::from_secs(
+                    options
+                        .vkrunner_idle_timeout_secs
+                        .unwrap_or(DEFAULT_VKRUNNER_IDLE_SECS),
+                ),
+                options.max_concurrent_runs,
+            ),
+            compile_cache: Arc::default(),
+            run_cache: Arc::new(std::sync::Mutex::new(BoundedCache::with_limit(
+                RUN_CACHE_LIMIT,
+            ))),
+            dispatch_rates: Arc::default(),
+            watchdog: match options.watchdog_timeout_ms {
+                Some(0) => None,
+                Some(milliseconds) => Some(watchdog::Watchdog {
+                    timeout: Duration::from_millis(milliseconds),
+                    source: "configured watchdog_timeout_ms".to_string(),
+                }),
+                None => watchdog::detect(),
+            },
+            shader_library: Arc::default(),
+            watch: options
+                .watch
+                .clone()
+                .map(|dir| Arc::new(watch::WatchState::new(dir))),
+            peer: None,
+            progress_tokens: Arc::default(),
+            progress_token: None,
+            log_level: Arc::default(),
+            shutdown: Arc::default(),
+            options: Arc::new(options),
+        }
+    }
+
+    /// Waits for a turn on the GPU, telling the client its place in the
+    /// queue while it waits: as progress notifications if the call has a
+    /// progress token, and as logging messages otherwise.
+    fn acquire_run_slot(&self) -> RunPermit {
+        let progress = self.progress_notifier();
+        let forwarder = self.stderr_forwarder();
+        let first_ahead = std::cell::Cell::new(None);
+        self.run_queue.acquire(|ahead| {
+            if let Some(progress) = &progress {
+                // Counts the runs that went ahead out of those that were
+                // ahead on arrival, plus this one's own turn
+                let first = first_ahead.get().unwrap_or(ahead);
+                first_ahead.set(Some(first));
+                let count = |runs: u64| u32::try_from(runs).unwrap_or(u32::MAX);
+                progress.send(count(first - ahead), Some(count(first + 1)));
+                return;
+            }
+
+            let line = match ahead {
+                0 => "Queued for the GPU: next in line".to_string(),
+                ahead => format!("Queued for the GPU: {ahead} run(s) ahead"),
+            };
+            if let Some(forwarder) = &forwarder {
+                forwarder.send_as("run_queue", line);
+            }
+        })
+    }
+
+    #[tool(
+        description = "REQUIRES: (1) Shader compile requests that produce SPIR-V assembly files, (2) Passes referencing these files by path, and (3) Test commands for drawing/computation. Workflow: First compile GLSL to SPIR-V, then reference compiled files in passes, then execute drawing commands in tests to render/compute. Tests MUST include draw/compute commands to produce visible output. Optional: requirements for hardware features, vertex data for geometry, output path for saving image. Every shader MUST have its compiled output referenced in passes. Alternatively, *Glsl passes embed GLSL source that VkRunner compiles itself, without a compile request."
+    )]
+    fn compile_run_shaders(
+        &self,
+        #[tool(aggr)] request: CompileRunShadersRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut request = request;
+        self.resolve_library_references(&mut request.passes)?;
+        for scene in request.scenes.iter_mut().flatten() {
+            self.resolve_library_references(&mut scene.passes)?;
+        }
+        let frames = request.frame_count();
+        ComplexityBudgets::check(
+            "max_frames",
+            self.options.budgets.max_frames,
+            frames,
+            || format!("the request renders {frames} images"),
+        )?;
+        let debug_views = request.debug_views.take();
+        if debug_views.is_some() && request.scenes.is_some() {
+            return Err(McpError::invalid_params(
+                "debug_views apply to a single render; leave out scenes",
+                None,
+            ));
+        }
+        if request.montage_path.is_some() && request.scenes.is_none() {
+            return Err(McpError::invalid_params(
+                "montage_path assembles the images of scenes; add scenes",
+                None,
+            ));
+        }
+        let montage_path = request
+            .montage_path
+            .as_deref()
+            .map(|path| confined_tmp_path("montage_path", path))
+            .transpose()?;
+        if request.export_path.is_some() && request.scenes.is_none() {
+            return Err(McpError::invalid_params(
+                "export_path writes a table of the results of scenes; add scenes",
+                None,
+            ));
+        }
+        let export_path = request
+            .export_path
+            .as_deref()
+            .map(|path| confined_tmp_path("export_path", path))
+            .transpose()?;
+        let Some(scenes) = request.scenes.take() else {
+            let mut result = self.run_shaders(&request)?;
+            for view in debug_views.into_iter().flatten() {
+                result
+                    .content
+                    .extend(self.render_debug_view(&request, view)?);
+            }
+            return Ok(result);
+        };
+
+        let mut names = std::collections::HashSet::new();
+        for scene in &scenes {
+            let valid = !scene.name.is_empty()
+                && scene
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid || !names.insert(scene.name.as_str()) {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "Scene name {:?} must be unique and only use letters, digits, '-' and '_'",
+                        scene.name
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        let default_output = request.output_path.clone();
+        let default_label = request.label.clone();
+        let mut tiles = Vec::new();
+        let mut table = export::Table::new(&[
+            "scene",
+            "status",
+            "duration_ms",
+            "output_path",
+            "max_deviation",
+            "mean_deviation",
+            "errors",
+        ]);
+        let default_dump = request
+            .vkrunner_options
+            .as_ref()
+            .and_then(|options| options.buffer_dump.as_ref())
+            .map(|dump| dump.path.clone());
+        let mut contents = Vec::new();
+
+        for scene in scenes {
+            request.passes = scene.passes;
+            request.tests = scene.tests;
+            if scene.vertex_data.is_some() {
+                request.vertex_data = scene.vertex_data;
+            }
+            if scene.instance_data.is_some() {
+                request.instance_data = scene.instance_data;
+            }
+            request.output_path = scene.output_path.or_else(|| {
+                default_output
+                    .as_deref()
+                    .map(|path| scene_path(path, &scene.name))
+            });
+            request.label = default_label
+                .as_deref()
+                .map(|label| label.replace("{scene}", &scene.name));
+            if let (Some(dump), Some(path)) = (
+                request
+                    .vkrunner_options
+                    .as_mut()
+                    .and_then(|options| options.buffer_dump.as_mut()),
+                &default_dump,
+            ) {
+                dump.path = scene_path(path, &scene.name);
+            }
+
+            contents.push(Content::text(format!("=== Scene {} ===", scene.name)));
+            let started = Instant::now();
+            let (status, deviation, errors) = match self.run_shaders_inner(&request) {
+                Ok(run) => {
+                    let status = if run.skipped {
+                        "SKIP"
+                    } else if !run.succeeded {
+                        "FAIL"
+                    } else if run.error_code == Some(ErrorCode::ProbeFailed) {
+                        "MISMATCH"
+                    } else {
+                        "PASS"
+                    };
+                    let (deviation, errors) = (run.deviation, run.errors.clone());
+                    contents.extend(run.contents());
+                    (status, deviation, errors)
+                }
+                Err(e) => {
+                    contents.push(Content::text(format!(
+                        "Scene {} failed: {}",
+                        scene.name, e.message
+                    )));
+                    ("FAIL", None, vkrunner_errors(&e.message))
+                }
+            };
+            table.push(vec![
+                scene.name.as_str().into(),
+                status.into(),
+                (started.elapsed().as_secs_f64() * 1000.0).into(),
+                request.output_path.clone().into(),
+                deviation.map(|(max, _)| max).into(),
+                deviation.map(|(_, mean)| mean).into(),
+                (!errors.is_empty()).then(|| errors.join("\n")).into(),
+            ]);
+            // A failed run leaves any earlier image at the path in place
+            let img = request
+                .output_path
+                .as_ref()
+                .filter(|_| matches!(status, "PASS" | "MISMATCH"))
+                .and_then(|path| image::open(path).ok())
+                .map(|img| img.to_rgb8());
+            tiles.push((img, format!("{}: {status}", scene.name)));
+        }
+
+        if let Some(export_path) = &export_path {
+            contents.push(Content::text(match table.write(Path::new(&export_path)) {
+                Ok(()) => format!(
+                    "Results of {} scenes exported to: {export_path}",
+                    table.rows.len()
+                ),
+                Err(e) => format!("Failed to export the results: {e}"),
+            }));
+        }
+
+        if let Some(montage_path) = &montage_path {
+            let columns = request
+                .montage_columns
+                .unwrap_or_else(|| (tiles.len() as f64).sqrt().ceil() as u32);
+            let sheet = label::montage(&tiles, columns);
+            let saved = sheet
+                .save(montage_path)
+                .map(|()| image_resource(&sheet, montage_path, request.return_image_max_dim));
+            match saved {
+                Ok(Ok(resource)) => {
+                    contents.push(Content::text(format!(
+                        "Montage of {} scenes saved to: {montage_path}",
+                        tiles.len()
+                    )));
+                    contents.push(resource);
+                }
+                Ok(Err(e)) | Err(e) => {
+                    contents.push(Content::text(format!("Failed to write the montage: {e}")))
+                }
+            }
+        }
+
+        Ok(CallToolResult::success(contents))
+    }
+
+    #[tool(
+        description = "Find out why a render comes out blank (one flat color, usually black). Runs the compile_run_shaders request as given and then step by step with back-face culling disabled, the depth test off, every fragment shader replaced by solid magenta, and finally a full-screen rectangle through a pass-through vertex shader. Each step keeps the earlier changes; probes are left out. Reports the drawn pixels and the colors at the NDC corners for each step, and what the first step that draws pixels says about the cause."
+    )]
+    fn diagnose_black_output(
+        &self,
+        #[tool(aggr)] request: CompileRunShadersRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut request = request;
+        self.resolve_library_references(&mut request.passes)?;
+        if request.scenes.is_some() {
+            return Err(McpError::invalid_params(
+                "diagnose_black_output runs a single render; leave out scenes",
+                None,
+            ));
+        }
+        if !request.tests.iter().any(ShaderRunnerTest::is_draw) {
+            return Err(McpError::invalid_params(
+                "diagnose_black_output needs a request with draw commands",
+                None,
+            ));
+        }
+
+        let mut step = request.duplicate()?;
+        step.without_checks();
+        // A cached result doesn't write the image
+        step.cache = None;
+
+        let mut lines = Vec::new();
+        let mut background = None;
+        let mut cause = None;
+        let changes = std::iter::once(None).chain(BLACK_OUTPUT_STEPS.iter().map(Some));
+        let scratch = ScratchDir::new()?;
+
+        for (i, change) in changes.enumerate() {
+            if let Some(change) = change {
+                (change.apply)(&mut step)?;
+            }
+            let name = change.map_or("as given", |change| change.change);
+            let path = scratch.path(&format!("step{i}.png"));
+            let _ = std::fs::remove_file(&path);
+            step.output_path = Some(path.clone());
+
+            let result = self.run_shaders(&step);
+            let image = image::open(&path).map(|image| image.to_rgb8());
+            let Ok(image) = image else {
+                let reason = match result {
+                    Ok(_) => "vkrunner failed; run this step's changes with compile_run_shaders for its output".to_string(),
+                    Err(e) => e.message.to_string(),
+                };
+                lines.push(format!("{i}. {name}: no image ({reason})"));
+                continue;
+            };
+
+            // The flat color of the unchanged render, or its most common
+            // one if it isn't flat
+            let background = *background.get_or_insert_with(|| {
+                let mut counts = std::collections::HashMap::new();
+                for pixel in image.pixels() {
+                    *counts.entry(*pixel).or_insert(0usize) += 1;
+                }
+                counts
+                    .into_iter()
+                    .max_by_key(|(_, count)| *count)
+                    .map_or(image::Rgb([0, 0, 0]), |(pixel, _)| pixel)
+            });
+            let drawn = image.pixels().any(|pixel| *pixel != background);
+            lines.push(format!(
+                "{i}. {name}: {}",
+                describe_drawn_pixels(&image, background)
+            ));
+
+            if drawn {
+                cause = Some(change.map_or(
+                    "The unchanged render already draws pixels that differ from its most common color, so it is not blank; compare the drawn pixels with the expected ones instead.",
+                    |change| change.cause,
+                ));
+                break;
+            }
+        }
+
+        let mut report = String::new();
+        if let Some(background) = background {
+            report.push_str(&format!(
+                "Background: {} (most common color of the unchanged render)
+",
+                hex_color(background)
+            ));
+        }
+        report.push_str(&lines.join("\n"));
+        report.push_str("\n\n");
+        report.push_str(cause.unwrap_or(
+            "Even a solid full-screen rectangle stays blank. Check the framebuffer format, ColorWriteMask, blending and logic ops, and that the draws come after the clears.",
+        ));
+
+        Ok(CallToolResult::success(vec![Content::text(report)]))
+    }
+
+    /// Whether the client wants logging notifications of `level`.
+    fn logs(&self, level: &LoggingLevel) -> bool {
+        let minimum = self.log_level.lock().unwrap_or_else(|e| e.into_inner());
+        minimum
+            .as_ref()
+            .is_none_or(|minimum| logging_severity(level) >= logging_severity(minimum))
+    }
+
+    /// Forwards vkrunner's stderr unless the client asked for less than
+    /// informational logging or isn't connected.
+    fn stderr_forwarder(&self) -> Option<StderrForwarder> {
+        if !self.logs(&LoggingLevel::Info) {
+            return None;
+        }
+
+        Some(StderrForwarder {
+            peer: self.peer.clone()?,
+            runtime: tokio::runtime::Handle::try_current().ok()?,
+        })
+    }
+
+    /// Reports the progress of the tool call this copy serves, if the
+    /// client sent a progress token with it.
+    fn progress_notifier(&self) -> Option<ProgressNotifier> {
+        Some(ProgressNotifier {
+            peer: self.peer.clone()?,
+            runtime: tokio::runtime::Handle::try_current().ok()?,
+            token: self.progress_token.clone()?,
+        })
+    }
+
+    /// Runs version `version` of a bisect_shaders request.
+    fn run_bisect_version(
+        &self,
+        request: &BisectShadersRequest,
+        index: usize,
+        version: usize,
+    ) -> Result<RunVerdict, McpError> {
+        let mut copy = request.request.duplicate()?;
+        let shader = &mut copy.requests[index];
+        match (&request.versions, &request.flags) {
+            (Some(versions), _) => shader.source = versions[version].clone(),
+            (None, Some(flags)) => {
+                shader
+                    .defines
+                    .get_or_insert_with(Vec::new)
+                    .extend(flags[..version].iter().map(|flag| MacroDefinition {
+                        name: flag.clone(),
+                        value: None,
+                    }))
+            }
+            (None, None) => {}
+        }
+
+        Ok(self.run_verdict(copy))
+    }
+
+    /// Runs one test of a suite in `suite_dir`, with its source_files
+    /// read into the request.
+    fn run_suite_test(
+        &self,
+        test: &suite::SuiteTest,
+        suite_dir: &Path,
+    ) -> Result<RunVerdict, McpError> {
+        let test_request = match test.check(suite_dir) {
+            suite::Check::Request(test_request) => test_request,
+            suite::Check::ShaderTest(script) => return self.run_shader_test_file(&script),
+        };
+        let mut copy = test_request.duplicate()?;
+        if copy.scenes.is_some() {
+            return Ok(RunVerdict {
+                passed: false,
+                errors: vec![
+                    "Suite requests run a single test; split the scenes into tests".to_string(),
+                ],
+            });
+        }
+        for (shader, file) in copy.requests.iter_mut().zip(&test.source_files) {
+            let path = suite::resolve(suite_dir, file);
+            match std::fs::read_to_string(&path) {
+                Ok(source) => shader.source = source,
+                Err(e) => {
+                    return Ok(RunVerdict {
+                        passed: false,
+                        errors: vec![format!("Failed to read {}: {e}", path.display())],
+                    });
+                }
+            }
+        }
+        self.resolve_library_references(&mut copy.passes)?;
+
+        Ok(self.run_verdict(copy))
+    }
+
+    /// Runs a vkrunner script file as it is and reports whether it passed.
+    fn run_shader_test_file(&self, script: &Path) -> Result<RunVerdict, McpError> {
+        if !script.is_file() {
+            return Ok(RunVerdict {
+                passed: false,
+                errors: vec![format!("Script {} not found", script.display())],
+            });
+        }
+
+        let mut args = vec![script.display().to_string()];
+        if self.options.portability {
+            args.push("--portability".to_string());
+        }
+        if let Some(device_id) = self.options.device_id {
+            args.push(format!("--device-id={device_id}"));
+        }
+        let icd = self.options.icd.as_deref().map(resolve_icd).transpose()?;
+
+        let _permit = self.acquire_run_slot();
+        let output = run_vkrunner(
+            &self.vkrunner_pool,
+            self.options.vkrunner(),
+            &args,
+            icd.as_deref(),
+            &[],
+            self.stderr_forwarder().as_ref(),
+        )?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let passed = output.status.success() && !stdout.contains("\"result\": \"skip\"");
+
+        let mut errors = vkrunner_errors(&stdout);
+        if !passed && errors.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            errors.extend(
+                format!("{stdout}\n{stderr}")
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(str::to_string),
+            );
+        }
+
+        Ok(RunVerdict { passed, errors })
+    }
+
+    /// Runs a request only to find out whether its probes pass, without
+    /// writing images or using the run cache.
+    fn run_verdict(&self, mut request: CompileRunShadersRequest) -> RunVerdict {
+        request.output_path = None;
+        request.expected_image = None;
+        request.crop = None;
+        request.snapshot_draws = None;
+        request.debug_views = None;
+        request.cache = None;
+
+        match self.run_shaders_inner(&request) {
+            Ok(run) => RunVerdict {
+                passed: run.passed(),
+                errors: run.errors,
+            },
+            Err(e) => RunVerdict {
+                passed: false,
+                errors: e.message.lines().map(str::to_string).collect(),
+            },
+        }
+    }
+
+    /// Runs the subgroup operations of `categories`, requiring `size` if
+    /// given, and reads back their records.
+    fn run_subgroup_ops(
+        &self,
+        size: Option<u32>,
+        categories: &[subgroups::Category],
+        icd: Option<&String>,
+    ) -> KernelRun {
+        // Enough subgroups per workgroup to see them differ, without
+        // exceeding how many a workgroup may have
+        let local_size = size.map_or(64, |size| (size * 4).clamp(16, 128));
+        let scratch = match ScratchDir::new() {
+            Ok(scratch) => scratch,
+            Err(e) => return KernelRun::Failed(e.message.to_string()),
+        };
+        let dump_path = scratch.path("subgroup_ops.bin");
+
+        let mut request = CompileRunShadersRequest::single_shader(
+            ShaderStage::Comp,
+            subgroups::shader(categories, local_size),
+            vec![
+                ShaderRunnerTest::ssbo(
+                    0,
+                    subgroups::INVOCATIONS as usize * subgroups::RECORD_WORDS * 4,
+                ),
+                ShaderRunnerTest::Compute {
+                    x: subgroups::INVOCATIONS / local_size,
+                    y: 1,
+                    z: 1,
+                },
+            ],
+            icd.cloned(),
+        );
+        request.requirements = size.map(|size| vec![ShaderRunnerRequire::SubgroupSize(size)]);
+        self.run_kernel(request, 0, &dump_path)
+    }
+
+    /// Runs `request` of a built-in kernel and reads back the buffer at
+    /// `binding` it dumps to `dump_path`. A run the device lacks a
+    /// feature for is skipped.
+    fn run_kernel(
+        &self,
+        mut request: CompileRunShadersRequest,
+        binding: u32,
+        dump_path: &str,
+    ) -> KernelRun {
+        let _ = std::fs::remove_file(dump_path);
+        request.vkrunner_options = Some(VkrunnerOptions {
+            buffer_dump: Some(BufferDump {
+                binding: Some(binding),
+                path: dump_path.to_string(),
+            }),
+            ..Default::default()
+        });
+
+        let run = match self.run_shaders_inner(&request) {
+            Ok(run) => run,
+            Err(e) => return KernelRun::Failed(e.message.to_string()),
+        };
+        match run.error_code {
+            Some(ErrorCode::UnsupportedFeature) => KernelRun::Skipped(run.failure_reason()),
+            Some(_) => KernelRun::Failed(run.failure_reason()),
+            None => match std::fs::read(dump_path) {
+                Ok(dump) => KernelRun::Dump(dump, run.output),
+                Err(_) => KernelRun::Failed(run.failure_reason()),
+            },
+        }
+    }
+
+    /// Replaces `lib:<name>` pass references with the artifact IDs of the
+    /// registered shaders.
+    fn resolve_library_references(&self, passes: &mut [ShaderRunnerPass]) -> Result<(), McpError> {
+        let library = self
+            .shader_library
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        for pass in passes {
+            let Some((stage, reference)) = pass.spirv_input() else {
+                continue;
+            };
+            let Some(name) = reference.strip_prefix(LIBRARY_PREFIX) else {
+                continue;
+            };
+            let shader = library.get(name).ok_or_else(|| {
+                ErrorCode::UnknownReference.invalid_params(
+                    format!("No shader is registered as {name}; see list_shaders"),
+                    None,
+                )
+            })?;
+            if shader.stage != stage {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "{reference} is a {} shader, not {}",
+                        shader.stage.display_name(),
+                        stage.display_name()
+                    ),
+                    None,
+                ));
+            }
+            let artifact_id = shader.artifact_id.clone();
+            if let Some(reference) = pass.spirv_input_mut() {
+                *reference = artifact_id;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the request again with the view's fragment shader.
+    fn render_debug_view(
+        &self,
+        request: &CompileRunShadersRequest,
+        view: DebugView,
+    ) -> Result<Vec<Content>, McpError> {
+        let Some(output_path) = &request.output_path else {
+            return Err(McpError::invalid_params(
+                "debug_views need an output_path to name their images after",
+                None,
+            ));
+        };
+
+        let mut copy = request.duplicate()?;
+        copy.without_checks();
+        copy.cache = None;
+        copy.output_path = Some(scene_path(output_path, view.name()));
+        copy.replace_shader(ShaderStage::Frag, view.fragment_shader())?;
+        if view == DebugView::Wireframe {
+            copy.set_state(ShaderRunnerTest::PolygonMode {
+                mode: "VK_POLYGON_MODE_LINE".to_string(),
+            });
+        }
+
+        let mut contents = vec![Content::text(format!("=== Debug view {} ===", view.name()))];
+        match self.run_shaders(&copy) {
+            Ok(result) => contents.extend(result.content),
+            Err(e) => contents.push(Content::text(format!(
+                "Debug view {} failed: {}",
+                view.name(),
+                e.message
+            ))),
+        }
+        Ok(contents)
+    }
+
+    fn run_shaders(&self, request: &CompileRunShadersRequest) -> Result<CallToolResult, McpError> {
+        let run = self.run_shaders_inner(request)?;
+        Ok(CallToolResult::success(run.contents()))
+    }
+
+    /// Compiles and runs `request`, returning how the run came out for
+    /// callers that act on it rather than on its report.
+    fn run_shaders_inner(&self, request: &CompileRunShadersRequest) -> Result<ShaderRun, McpError> {
+        request.validate_run()?;
+        let buffer_dump_path = request
+            .vkrunner_options
+            .as_ref()
+            .and_then(|options| options.buffer_dump.as_ref())
+            .map(|dump| confined_tmp_path("buffer_dump path", &dump.path))
+            .transpose()?;
+        let ordering_issues = request.lint_ordering()?;
+
+        let shaders = request.compile_requests()?;
+        if let Some(report) = shaders.failure_report() {
+            return Ok(ShaderRun::stopped(ErrorCode::CompileError, report));
+        }
+        let compiled = &shaders.paths;
+        request.check_budgets(compiled, &self.options.budgets)?;
+
+        let pinned_icd = match request.icd.as_deref() {
+            Some(icd) => Some(self.options.request_icd(icd)?),
+            None => self.options.icd.as_deref().map(resolve_icd).transpose()?,
+        };
+
+        // Looked up before queueing, so that a cached result never waits
+        // for the GPU
+        let cache_key = if request.cache == Some(true) {
+            let key =
+                request.run_cache_key(compiled, pinned_icd.as_deref(), self.options.portability)?;
+            let cached = self
+                .run_cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(&key)
+                .cloned();
+            if let Some(mut cached) = cached {
+                cached.cached = Some(key);
+                return Ok(cached);
+            }
+            Some(key)
+        } else {
+            None
+        };
+
+        let mut timings = shaders.timings.clone();
+        let permit = self.acquire_run_slot();
+        if permit.position > 0 {
+            timings.push((
+                format!("GPU queue wait (position {})", permit.position),
+                permit.waited,
+            ));
+        }
+
+        let started = Instant::now();
+        request.validate_bindings(compiled)?;
+        let capability_demands = request.capability_demands(compiled)?;
+        let (inferred_requirements, mut requirement_notes) =
+            if request.infer_requirements == Some(false) {
+                (Vec::new(), Vec::new())
+            } else {
+                let storage_writes = request.storage_write_features(compiled)?;
+                request.infer_requirements(&capability_demands, &storage_writes)
+            };
+        let mut plan = self.plan_dispatches(request, compiled)?;
+
+        let scratch = ScratchDir::new()?;
+        let shader_test_path = &scratch.path("test.shader_test");
+        let depth_stencil_candidates = request.depth_stencil_candidates().filter(|_| {
+            inferred_requirements
+                .iter()
+                .any(|line| line.starts_with("depthstencil "))
+        });
+        let script = request.write_shader_test(
+            shader_test_path,
+            compiled,
+            inferred_requirements,
+            plan.chunk_workgroups,
+            &scratch,
+        )?;
+        timings.push(("script generation".to_string(), started.elapsed()));
+
+        let tmp_image_path = &scratch.path("output.ppm");
+        let vkrunner_args = self.vkrunner_args(
+            request,
+            shader_test_path,
+            tmp_image_path,
+            buffer_dump_path.as_deref(),
+            plan.watchdog.is_some(),
+        );
+        let env = request
+            .vkrunner_options
+            .as_ref()
+            .and_then(|options| options.environment.as_deref())
+            .unwrap_or_default();
+
+        let started = Instant::now();
+        let mut execution = self.execute_script(&vkrunner_args, pinned_icd.as_deref(), env)?;
+        if let Some((formats, reason)) = depth_stencil_candidates {
+            let rejected = self.retry_depth_stencil(
+                &mut execution,
+                shader_test_path,
+                &vkrunner_args,
+                env,
+                formats,
+            )?;
+            if !rejected.is_empty() {
+                let chosen = formats[rejected.len()];
+                for note in &mut requirement_notes {
+                    if note.starts_with("- depthstencil ") {
+                        *note = format!(
+                            "- depthstencil {chosen} ({reason}; the device does not support {} as a depth/stencil attachment)",
+                            rejected.join(" or ")
+                        );
+                    }
+                }
+            }
+        }
+        timings.push(("vkrunner execution".to_string(), started.elapsed()));
+
+        let succeeded = execution.output.status.success();
+        let stdout = String::from_utf8_lossy(&execution.output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&execution.output.stderr).to_string();
+        let output = format!("{stdout}\n{stderr}");
+
+        // Each draw and dispatch is its own submission under a watchdog,
+        // so the longest one bounds the time of the largest dispatch
+        let longest_submission = watchdog::longest_submission(&stdout);
+        if let Some(longest) = longest_submission {
+            self.record_dispatch_rate(request, &mut plan, &execution, longest);
+        }
+
+        let shader_test = std::fs::read_to_string(shader_test_path);
+        let metadata = RunMetadata {
+            request_hash: sha256_hex(format!(
+                "{request:?}\n{}",
+                shader_test.as_deref().unwrap_or_default()
+            )),
+            device: device_info_line(&stdout, "Device"),
+            driver_version: device_info_line(&stdout, "Driver version"),
+            timestamp: rfc3339_utc(SystemTime::now()),
+            seed: request.seed.unwrap_or(0),
+        }
+        .entries();
+
+        let missing_features = explain_missing_features(&output, &capability_demands);
+        let mut result_message = self.execution_report(
+            &script,
+            &execution,
+            pinned_icd.as_deref(),
+            env,
+            &missing_features,
+        );
+
+        let mut images = Vec::new();
+        let started = Instant::now();
+        let comparison = match &request.output_path {
+            Some(output_path) if succeeded => request.save_output_image(
+                output_path,
+                tmp_image_path,
+                &metadata,
+                &mut result_message,
+                &mut images,
+            )?,
+            _ => None,
+        };
+        if request.output_path.is_some() {
+            timings.push(("image conversion".to_string(), started.elapsed()));
+        }
+
+        // Only the options that affect rendering carry over to snapshots
+        // and tolerance reruns; a buffer dump would overwrite the one
+        // from the full run.
+        let follow_up = FollowUp {
+            args: vkrunner_args
+                .iter()
+                .filter(|arg| {
+                    *arg == "--portability"
+                        || arg.starts_with("--device-id=")
+                        || arg.starts_with("--replace=")
+                })
+                .cloned()
+                .collect(),
+            icd: execution.icd.as_deref(),
+            env,
+            scratch: &scratch,
+        };
+        let script_text = || {
+            shader_test.as_deref().map_err(|e| {
+                McpError::internal_error(
+                    "IO operation failed",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })
+        };
+
+        if request.snapshot_draws == Some(true) {
+            let started = Instant::now();
+            if let Some(output_path) = request.output_path.as_ref().filter(|_| succeeded) {
+                self.capture_snapshots(
+                    request,
+                    output_path,
+                    script_text()?,
+                    &follow_up,
+                    &metadata,
+                    &mut result_message,
+                    &mut images,
+                )?;
+            }
+            timings.push(("snapshots".to_string(), started.elapsed()));
+        }
+
+        let failed_probes = failed_probe_lines(&output);
+        if request.suggest_tolerances == Some(true) && !failed_probes.is_empty() {
+            let started = Instant::now();
+            result_message.push_str(&self.suggest_tolerances(
+                script_text()?,
+                &failed_probes,
+                &follow_up,
+            )?);
+            timings.push(("tolerance search".to_string(), started.elapsed()));
+        }
+
+        result_message.push_str("Timings:\n");
+        for (phase, duration) in &timings {
+            result_message.push_str(&format!(
+                "- {phase}: {:.1} ms\n",
+                duration.as_secs_f64() * 1000.0
+            ));
+        }
+        result_message.push('\n');
+
+        if !succeeded {
+            push_report_section(
+                &mut result_message,
+                "Source lines of the SPIR-V IDs in the error",
+                &error_source_locations(&output, &script.modules),
+            );
+        }
+        if let Some(watchdog) = plan.watchdog {
+            result_message.push_str(&format!(
+                "GPU watchdog: {:.2} s timeout ({})",
+                watchdog.timeout.as_secs_f64(),
+                watchdog.source
+            ));
+            if let Some(longest) = longest_submission {
+                result_message.push_str(&format!(
+                    ", longest submission {:.1} ms",
+                    longest.as_secs_f64() * 1000.0
+                ));
+            }
+            result_message.push('\n');
+            for note in &plan.watchdog_notes {
+                result_message.push_str(note);
+                result_message.push('\n');
+            }
+            result_message.push('\n');
+        }
+        push_report_section(
+            &mut result_message,
+            &format!(
+                "Split dispatches (budget {} invocations)",
+                plan.budget.unwrap_or_default()
+            ),
+            &script.dispatch_notes,
+        );
+        push_report_section(
+            &mut result_message,
+            "Inferred requirements",
+            &requirement_notes,
+        );
+        push_report_section(
+            &mut result_message,
+            "Shader analysis warnings",
+            &shaders.analysis_warnings,
+        );
+        push_report_section(
+            &mut result_message,
+            "Ordering warnings",
+            &ordering_issues
+                .iter()
+                .map(|issue| format!("- {issue}"))
+                .collect::<Vec<_>>(),
+        );
+        push_report_section(
+            &mut result_message,
+            "Artifacts (reference these IDs in *_spvasm_path of later calls)",
+            &shaders.artifact_notes,
+        );
+        if !shaders.variant_notes.is_empty() {
+            result_message.push_str(&shaders.variant_notes.join("\n"));
+            result_message.push_str("\n\n");
+        }
+        push_report_section(
+            &mut result_message,
+            "Device memory",
+            &memory_usage_lines(&stdout)
+                .into_iter()
+                .map(|line| format!("- {line}"))
+                .collect::<Vec<_>>(),
+        );
+
+        if let Some(path) = &buffer_dump_path {
+            match std::fs::metadata(path) {
+                Ok(metadata) if succeeded => {
+                    result_message.push_str(&format!(
+                        "Buffer dumped to: {path} ({} bytes)\n",
+                        metadata.len()
+                    ));
+                }
+                _ => result_message.push_str("No buffer was dumped by VkRunner.\n"),
+            }
+        }
+
+        result_message.push_str("Run metadata:\n");
+        for (key, value) in &metadata {
+            result_message.push_str(&format!("- {key}: {value}\n"));
+        }
+
+        result_message.push_str("SHA-256 hashes:\n");
+        for (stage, path, spvasm) in &script.modules {
+            result_message.push_str(&format!("- {stage} {path}: {}\n", sha256_hex(spvasm)));
+        }
+        if let Ok(script) = &shader_test {
+            result_message.push_str(&format!("- shader_test: {}\n", sha256_hex(script)));
+        }
+
+        for disassembly in &shaders.disassemblies {
+            result_message.push('\n');
+            result_message.push_str(disassembly);
+        }
+
+        result_message.push_str("\nShader Test File Contents:\n");
+        result_message.push_str(
+            shader_test
+                .as_deref()
+                .unwrap_or("Failed to read shader test file"),
+        );
+
+        let mismatch = comparison
+            .as_ref()
+            .is_some_and(|comparison| comparison.mismatch);
+        let run = ShaderRun {
+            report: result_message,
+            images,
+            error_code: vkrunner_failure_code(&execution.output)
+                .or(mismatch.then_some(ErrorCode::ProbeFailed)),
+            succeeded,
+            skipped: output.contains("\"result\": \"skip\""),
+            errors: vkrunner_errors(&output),
+            output,
+            missing_features,
+            deviation: comparison.map(|comparison| (comparison.max, comparison.mean)),
+            cached: None,
+        };
+
+        // Failed runs are often transient (no device, lost device), so
+        // only successes are reused
+        if let Some(key) = cache_key.filter(|_| run.succeeded) {
+            self.run_cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(key, run.clone());
+        }
+
+        Ok(run)
+    }
+
+    /// Runs vkrunner with `args` on the `icd` driver, forwarding its
+    /// stderr to the client.
+    fn run_script(
+        &self,
+        args: &[String],
+        icd: Option<&Path>,
+        env: &[EnvironmentVariable],
+    ) -> Result<Output, McpError> {
+        run_vkrunner(
+            &self.vkrunner_pool,
+            self.options.vkrunner(),
+            args,
+            icd,
+            env,
+            self.stderr_forwarder().as_ref(),
+        )
+    }
+
+    /// Finds the dispatch budget of a run from the server's limit and,
+    /// under a GPU watchdog, the rate an earlier run of the same compute
+    /// shader was measured at.
+    fn plan_dispatches(
+        &self,
+        request: &CompileRunShadersRequest,
+        compiled: &[Vec<String>],
+    ) -> Result<DispatchPlan<'_>, McpError> {
+        // Software drivers run on the CPU, out of reach of GPU watchdogs
+        let software_selected = request
+            .icd
+            .as_deref()
+            .or(self.options.icd.as_deref())
+            .map_or(software_icd_from_env().is_some(), |icd| {
+                matches!(icd, "lavapipe" | "swiftshader") || is_software_icd(icd)
+            });
+        let watchdog = self.watchdog.as_ref().filter(|_| !software_selected);
+        let local_size = request.compute_local_size(compiled)?;
+        let compute_key = request.compute_shader_key(compiled)?;
+        let seconds_per_invocation = compute_key.as_ref().and_then(|key| {
+            self.dispatch_rates
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(key)
+                .copied()
+        });
+        let (watchdog_budget, watchdog_notes) = match (watchdog, seconds_per_invocation) {
+            (Some(watchdog), Some(rate)) => {
+                request.watchdog_dispatch_budget(local_size, watchdog, rate)?
+            }
+            _ => (None, Vec::new()),
+        };
+        let budget = match (self.options.max_dispatch_invocations, watchdog_budget) {
+            (Some(configured), Some(watchdog)) => Some(configured.min(watchdog)),
+            (configured, watchdog) => configured.or(watchdog),
+        };
+
+        Ok(DispatchPlan {
+            watchdog,
+            local_size,
+            compute_key,
+            budget,
+            chunk_workgroups: request.dispatch_chunk_workgroups(local_size, budget)?,
+            watchdog_notes,
+        })
+    }
+
+    /// Learns how long an invocation of the run's compute shader takes
+    /// from its `longest` submission, and warns when that came close to
+    /// the watchdog's timeout.
+    fn record_dispatch_rate(
+        &self,
+        request: &CompileRunShadersRequest,
+        plan: &mut DispatchPlan,
+        execution: &ScriptExecution,
+        longest: Duration,
+    ) {
+        let Some(watchdog) = plan.watchdog else {
+            return;
+        };
+        let group_invocations = plan
+            .local_size
+            .iter()
+            .map(|&n| u64::from(n))
+            .product::<u64>();
+        let largest_dispatch = request
+            .dispatch_invocations(plan.local_size)
+            .into_iter()
+            .map(|(.., invocations)| invocations)
+            .max()
+            .map(|largest| match plan.chunk_workgroups {
+                Some(workgroups) => largest.min(workgroups * group_invocations),
+                None => largest,
+            });
+        match (&plan.compute_key, largest_dispatch) {
+            (Some(key), Some(largest)) if execution.software_icd.is_none() && largest > 0 => {
+                self.dispatch_rates
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(key.clone(), longest.as_secs_f64() / largest as f64);
+            }
+            _ => {}
+        }
+        if longest > watchdog.margin() {
+            plan.watchdog_notes.push(format!(
+                "- The longest submission took {:.2} s, over half of the {:.2} s timeout; shrink the largest draw or dispatch, or set dispatch_offset_push so later runs of this compute shader are split",
+                longest.as_secs_f64(),
+                watchdog.timeout.as_secs_f64()
+            ));
+        }
+    }
+
+    /// The command line of a request's vkrunner run.
+    fn vkrunner_args(
+        &self,
+        request: &CompileRunShadersRequest,
+        shader_test_path: &str,
+        image_path: &str,
+        buffer_dump_path: Option<&str>,
+        separate_submissions: bool,
+    ) -> Vec<String> {
+        let mut args = vec![shader_test_path.to_string()];
+
+        if request.output_path.is_some() {
+            args.push("--image".to_string());
+            args.push(image_path.to_string());
+        }
+
+        if self.options.portability {
+            args.push("--portability".to_string());
+        }
+        args.push("--device-info".to_string());
+        args.push("--memory-usage".to_string());
+        args.push("--submission-times".to_string());
+        if separate_submissions {
+            args.push("--separate-submissions".to_string());
+        }
+
+        let vkrunner_options = request.vkrunner_options.as_ref();
+        if let Some(device_id) = vkrunner_options
+            .and_then(|options| options.device_id)
+            .or(self.options.device_id)
+        {
+            args.push(format!("--device-id={device_id}"));
+        }
+
+        if let Some(options) = vkrunner_options {
+            args.extend(options.args(buffer_dump_path));
+        }
+        args
+    }
+
+    /// Runs a request's script, retrying on a software driver when no
+    /// Vulkan device was found and none was pinned.
+    fn execute_script(
+        &self,
+        args: &[String],
+        pinned_icd: Option<&Path>,
+        env: &[EnvironmentVariable],
+    ) -> Result<ScriptExecution, McpError> {
+        let mut execution = ScriptExecution {
+            output: self.run_script(args, pinned_icd, env)?,
+            icd: pinned_icd.map(Path::to_path_buf),
+            software_icd: match pinned_icd {
+                Some(icd) => Some(icd.display().to_string()).filter(|icd| is_software_icd(icd)),
+                None => software_icd_from_env(),
+            },
+            fell_back_to_software: false,
+        };
+
+        if pinned_icd.is_none()
+            && !execution.output.status.success()
+            && reports_no_vulkan_device(&execution.output)
+        {
+            if self.options.no_software_fallback {
+                tracing::warn!("No Vulkan device found and software fallback is disabled");
+            } else if let Some(icd) = find_software_icd() {
+                tracing::info!("No Vulkan device found, retrying with {}", icd.display());
+                execution.output = self.run_script(args, Some(&icd), env)?;
+                execution.software_icd = Some(icd.display().to_string());
+                execution.fell_back_to_software = true;
+                execution.icd = Some(icd);
+            }
+        }
+        Ok(execution)
+    }
+
+    /// Moves an inferred depth/stencil format on to the next of
+    /// `formats` for as long as vkrunner skips the test because the
+    /// device can't attach it, and returns the formats it rejected.
+    fn retry_depth_stencil(
+        &self,
+        execution: &mut ScriptExecution,
+        shader_test_path: &str,
+        args: &[String],
+        env: &[EnvironmentVariable],
+        formats: &'static [&'static str],
+    ) -> Result<Vec<&'static str>, McpError> {
+        let mut rejected = Vec::new();
+        for pair in formats.windows(2) {
+            let output = format!(
+                "{}\n{}",
+                String::from_utf8_lossy(&execution.output.stdout),
+                String::from_utf8_lossy(&execution.output.stderr)
+            );
+            let unsupported = format!(
+                "Format {} is not supported as a depth/stencil attachment",
+                pair[0]
+            );
+            if !output.contains(&unsupported) {
+                break;
+            }
+
+            let script = std::fs::read_to_string(shader_test_path).map_err(io_err)?;
+            let script = script.replacen(
+                &format!("depthstencil {}\n", pair[0]),
+                &format!("depthstencil {}\n", pair[1]),
+                1,
+            );
+            std::fs::write(shader_test_path, script).map_err(io_err)?;
+            tracing::info!("{unsupported}, retrying with {}", pair[1]);
+            execution.output = self.run_script(args, execution.icd.as_deref(), env)?;
+            rejected.push(pair[0]);
+        }
+        Ok(rejected)
+    }
+
+    /// The start of a run's report: whether vkrunner succeeded with its
+    /// output, then the driver it ran on and what the device lacks.
+    fn execution_report(
+        &self,
+        script: &ShaderTestScript,
+        execution: &ScriptExecution,
+        pinned_icd: Option<&Path>,
+        env: &[EnvironmentVariable],
+        missing_features: &[String],
+    ) -> String {
+        let stdout = String::from_utf8_lossy(&execution.output.stdout);
+        let stderr = String::from_utf8_lossy(&execution.output.stderr);
+        let succeeded = execution.output.status.success();
+
+        let mut report = if succeeded {
+            format!(
+                "Shader compilation successful using shaderc-rs.\nVkRunner execution successful.\n\nOutput:\n{stdout}\n\n"
+            )
+        } else {
+            format!(
+                "Shader compilation successful using shaderc-rs.\nVkRunner execution failed.\n\nOutput:\n{stdout}\n\nError:\n{stderr}\n\n",
+            )
+        };
+
+        if succeeded && !env.is_empty() {
+            let names = env
+                .iter()
+                .map(|variable| format!("{}={}", variable.name, variable.value))
+                .collect::<Vec<_>>()
+                .join(" ");
+            report.push_str(&format!("Driver debug output ({names}):\n{stderr}\n\n"));
+        }
+
+        if execution.fell_back_to_software {
+            report.push_str(
+                "No hardware Vulkan device was available, so the test fell back to a software driver.\n",
+            );
+        } else if self.options.no_software_fallback && reports_no_vulkan_device(&execution.output) {
+            report.push_str(
+                "No Vulkan device was available and the software fallback is disabled on this server.\n",
+            );
+        }
+        if let Some(icd) = pinned_icd {
+            report.push_str(&format!("Vulkan driver pinned to: {}\n", icd.display()));
+        }
+        if let Some(icd) = &execution.software_icd {
+            report.push_str(&format!(
+                "Device: software rasterizer ({icd}); precision and performance may differ from hardware.\n\n"
+            ));
+        }
+
+        push_report_section(&mut report, "Missing device support", missing_features);
+
+        if self.options.portability {
+            let gaps = script.portability_gaps();
+
+            if stdout.contains("\"result\": \"skip\"") {
+                report.push_str(
+                    "Portability mode: VkRunner skipped the test because the device does not support all of the requirements.\n",
+                );
+            }
+
+            if !gaps.is_empty() {
+                report
+                    .push_str("Portability mode: these requirements are usually unavailable on portability drivers and cause a skip:\n");
+                for gap in gaps {
+                    report.push_str(&format!("- {gap}\n"));
+                }
+                report.push('\n');
+            }
+        }
+        report
+    }
+
+    /// Renders the script up to each draw on its own and saves the images
+    /// next to `output_path`, so the draw that went wrong can be seen.
+    #[allow(clippy::too_many_arguments)]
+    fn capture_snapshots(
+        &self,
+        request: &CompileRunShadersRequest,
+        output_path: &str,
+        script: &str,
+        follow_up: &FollowUp,
+        metadata: &[(&str, String)],
+        report: &mut String,
+        images: &mut Vec<Content>,
+    ) -> Result<(), McpError> {
+        let snapshot_test_path = &follow_up.scratch.path("snapshot.shader_test");
+        let snapshot_image_path = &follow_up.scratch.path("snapshot.ppm");
+        let color_space = request.color_space.unwrap_or_default();
+        let output_path = Path::new(output_path);
+        let stem = output_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "output".to_string());
+
+        let mut args = vec![
+            snapshot_test_path.to_string(),
+            "--image".to_string(),
+            snapshot_image_path.to_string(),
+        ];
+        args.extend(follow_up.args.iter().cloned());
+
+        for (draw, snapshot_script) in snapshot_scripts(script).iter().enumerate() {
+            std::fs::write(snapshot_test_path, snapshot_script).map_err(io_err)?;
+            let _ = std::fs::remove_file(snapshot_image_path);
+
+            let output = self.run_script(&args, follow_up.icd, follow_up.env)?;
+            let snapshot_path = output_path.with_file_name(format!("{stem}_draw{draw}.png"));
+
+            let snapshot_path = snapshot_path.display().to_string();
+            let resource = read_and_decode_ppm_file(snapshot_image_path)
+                .ok()
+                .filter(|img| {
+                    output.status.success()
+                        && save_framebuffer(img, Path::new(&snapshot_path), color_space, metadata)
+                            .is_ok()
+                })
+                .and_then(|img| {
+                    image_resource(
+                        &color_space.to_display(&img),
+                        &snapshot_path,
+                        request.return_image_max_dim,
+                    )
+                    .ok()
+                });
+
+            let Some(resource) = resource else {
+                report.push_str(&format!(
+                    "Failed to capture a snapshot after draw {draw}.\n"
+                ));
+                continue;
+            };
+
+            report.push_str(&format!(
+                "Snapshot after draw {draw} saved to: {snapshot_path}\n"
+            ));
+            images.push(resource);
+        }
+        Ok(())
+    }
+
+    /// Reruns the script with looser tolerances on the `failed_probes`
+    /// lines and reports the tolerance each one passes at.
+    fn suggest_tolerances(
+        &self,
+        script: &str,
+        failed_probes: &[usize],
+        follow_up: &FollowUp,
+    ) -> Result<String, McpError> {
+        let script_lines = script.lines().collect::<Vec<_>>();
+        let tolerance_test_path = &follow_up.scratch.path("tolerance.shader_test");
+
+        let mut args = vec![tolerance_test_path.to_string()];
+        args.extend(follow_up.args.iter().cloned());
+
+        let mut remaining = failed_probes.to_vec();
+        let mut passed_at = Vec::new();
+        for tolerance in TOLERANCE_LADDER {
+            if remaining.is_empty() {
+                break;
+            }
+
+            let (tolerance_script, origins) = with_probe_tolerance(script, &remaining, tolerance);
+            std::fs::write(tolerance_test_path, tolerance_script).map_err(io_err)?;
+            let output = self.run_script(&args, follow_up.icd, follow_up.env)?;
+            let output = format!(
+                "{}\n{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            let still_failing = failed_probe_lines(&output)
+                .into_iter()
+                .filter_map(|line| origins.get(line.wrapping_sub(1)).copied())
+                .collect::<Vec<_>>();
+            remaining.retain(|line| {
+                let passed = !still_failing.contains(line);
+                if passed {
+                    passed_at.push((*line, tolerance));
+                }
+                !passed
+            });
+        }
+
+        let mut report = "Tolerance suggestions for failing probes:\n".to_string();
+        for line in failed_probes {
+            let probe = line
+                .checked_sub(1)
+                .and_then(|index| script_lines.get(index))
+                .map(|probe| probe.trim())
+                .unwrap_or_default();
+            match passed_at.iter().find(|(passed, _)| passed == line) {
+                Some((_, tolerance)) => report.push_str(&format!(
+                    "- line {line} ({probe}): passes at tolerance {tolerance}\n"
+                )),
+                None => report.push_str(&format!(
+                    "- line {line} ({probe}): still fails at tolerance {}, likely a real bug\n",
+                    TOLERANCE_LADDER[TOLERANCE_LADDER.len() - 1]
+                )),
+            }
+        }
+        report.push('\n');
+        Ok(report)
+    }
+
+    #[tool(
+        description = "Only compile shaders, to check that GLSL compiles without building a pipeline and tests. Reports for each shader (and each define variant) whether it compiled, with the compiler's diagnostics when it didn't and the path of the SPIR-V assembly when it did. The outputs are saved like compile_run_shaders requests, so later passes can reference them."
+    )]
+    fn compile_shaders(
+        &self,
+        #[tool(aggr)] request: CompileShadersRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut reports = Vec::new();
+        let mut failed = false;
+
+        for (index, req) in request.requests.iter().enumerate() {
+            reports.push(format!("Shader {index} ({}):", req.stage.display_name()));
+            failed |= !req
+                .compile_variants(&mut reports, |_, defines| Ok((req.compile(defines)?, None)))?;
+        }
+
+        if failed {
+            reports.insert(0, format!("error_code: {}", ErrorCode::CompileError));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            reports.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Compile shaders without running them, for tight edit-compile loops. Each shader's full source is cached and returned as a src-... ID; later calls can send only a unified diff against that ID. Unchanged sources reuse the previous compiler output. Successful compilations are saved like compile_run_shaders requests, so their paths or spv-... artifact IDs can be used in later passes."
+    )]
+    fn compile_incremental(
+        &self,
+        #[tool(aggr)] request: CompileIncrementalRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut reports = Vec::new();
+        let mut failed = false;
+        let lock_cache = || self.compile_cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        for (index, shader) in request.shaders.into_iter().enumerate() {
+            let mut req = shader.request;
+
+            if let Some(base_id) = &shader.base_source_id {
+                let cache = lock_cache();
+                let base = cache.sources.get(base_id).ok_or_else(|| {
+                    ErrorCode::UnknownReference.invalid_params(
+                        format!("Unknown source ID {base_id}; send the full source again"),
+                        Some(json!({"shader": index})),
+                    )
+                })?;
+                req.source = apply_patch(base, &req.source).map_err(|e| {
+                    McpError::invalid_params(
+                        format!("Failed to apply the patch for shader {index} to {base_id}: {e}"),
+                        Some(json!({"shader": index})),
+                    )
+                })?;
+            }
+
+            let source_id = format!("src-{}", sha256_hex(&req.source));
+            lock_cache()
+                .sources
+                .insert(source_id.clone(), req.source.clone());
+            reports.push(format!(
+                "Shader {index} ({}): source {source_id}",
+                req.stage.display_name()
+            ));
+
+            // Every option is part of the key, so only an identical
+            // request reuses a result
+            let request_hash = serde_json::to_string(&req).map(sha256_hex).map_err(|e| {
+                McpError::internal_error(
+                    "Failed to hash compile request",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?;
+
+            failed |= !req.compile_variants(&mut reports, |variant, defines| {
+                let key = format!("{request_hash}/{variant}");
+                if let Some(result) = lock_cache().results.get(&key).cloned() {
+                    return Ok((result, Some("cached".to_string())));
+                }
+
+                let started = Instant::now();
+                let result = req.compile(defines)?;
+                lock_cache().results.insert(key, result.clone());
+                let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+                Ok((result, Some(format!("{elapsed:.1} ms"))))
+            })?;
+        }
+
+        if failed {
+            reports.insert(0, format!("error_code: {}", ErrorCode::CompileError));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            reports.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Upload a base64-encoded PNG or JPEG image for use as a texture. It is decoded to RGBA8 and stored on the server under a tex-... ID, which Texture tests reference with an Uploaded source. Reports the ID and the image size that the Texture test must use."
+    )]
+    fn upload_texture(
+        &self,
+        #[tool(aggr)] request: UploadTextureRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let encoded = match request.data.split_once(";base64,") {
+            Some((prefix, encoded)) if prefix.starts_with("data:") => encoded,
+            _ => &request.data,
+        };
+        let bytes = base64_decode(encoded).map_err(|e| {
+            McpError::invalid_params(
+                "Texture data is not valid base64",
+                Some(json!({"error": e})),
+            )
+        })?;
+
+        let format = image::guess_format(&bytes).map_err(|e| {
+            McpError::invalid_params(
+                "Unrecognized texture image format",
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+        if !matches!(format, image::ImageFormat::Png | image::ImageFormat::Jpeg) {
+            return Err(McpError::invalid_params(
+                format!("Texture images must be PNG or JPEG, not {format:?}"),
+                None,
+            ));
+        }
+
+        let img = image::load_from_memory_with_format(&bytes, format)
+            .map_err(|e| {
+                McpError::invalid_params(
+                    "Failed to decode texture image",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?
+            .to_rgba8();
+        let (width, height) = img.dimensions();
+        if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Texture image is {width}x{height}, larger than {MAX_TEXTURE_SIZE}x{MAX_TEXTURE_SIZE}"
+                ),
+                None,
+            ));
+        }
+
+        let mut hashed = img.as_raw().clone();
+        hashed.extend(width.to_le_bytes());
+        let id = format!("tex-{}", sha256_hex(&hashed));
+        let path = texture_artifact_path(&id);
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .and_then(|()| {
+                std::fs::create_dir_all(artifact_dir())
+                    .and_then(|()| write_atomically(&path, &png))
+                    .map_err(ImageError::IoError)
+            })
+            .map_err(|e| {
+                McpError::internal_error(
+                    "Failed to store uploaded texture",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Uploaded {format:?} texture {id} ({width}x{height}, stored at {path})"
+        ))]))
+    }
+
+    #[tool(
+        description = "List the built-in starter requests: known-good compile_run_shaders requests that instantiate_template returns as a baseline to modify"
+    )]
+    fn list_templates(&self) -> Result<CallToolResult, McpError> {
+        let lines = REQUEST_TEMPLATES
+            .iter()
+            .map(|template| format!("- {}: {}", template.name, template.description))
+            .collect::<Vec<_>>();
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Return the compile_run_shaders request of a built-in template as JSON, to modify and send. Starting from a working baseline avoids most first-attempt failures; set run to check it on this device first."
+    )]
+    fn instantiate_template(
+        &self,
+        #[tool(aggr)] request: InstantiateTemplateRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let template = REQUEST_TEMPLATES
+            .iter()
+            .find(|template| template.name == request.name)
+            .ok_or_else(|| {
+                ErrorCode::UnknownReference.invalid_params(
+                    format!("Unknown template {}; see list_templates", request.name),
+                    Some(json!({
+                        "templates": REQUEST_TEMPLATES.iter().map(|template| template.name).collect::<Vec<_>>()
+                    })),
+                )
+            })?;
+
+        let value = (template.request)();
+        let run_request = serde_json::from_value::<CompileRunShadersRequest>(value.clone())
+            .map_err(|e| {
+                McpError::internal_error(
+                    format!("Template {} is not a valid request", template.name),
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?;
+        let json = serde_json::to_string_pretty(&value).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize the template",
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+
+        let mut contents = vec![Content::text(format!(
+            "Template {}: {}\n\n{json}",
+            template.name, template.description
+        ))];
+        if request.run == Some(true) {
+            contents.push(Content::text("=== Run ===".to_string()));
+            contents.extend(self.compile_run_shaders(run_request)?.content);
+        }
+
+        Ok(CallToolResult::success(contents))
+    }
+
+    #[tool(
+        description = "Find the first of a series of shader versions that breaks a test: runs a compile_run_shaders request with each version of one shader, either full sources or macros defined one more at a time, and bisects for the first version whose probes fail. The first version must pass and the last fail."
+    )]
+    fn bisect_shaders(
+        &self,
+        #[tool(aggr)] request: BisectShadersRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut request = request;
+        if request.request.scenes.is_some() {
+            return Err(McpError::invalid_params(
+                "bisect_shaders runs a single test; leave out scenes",
+                None,
+            ));
+        }
+        self.resolve_library_references(&mut request.request.passes)?;
+
+        let index = request.compile_request.unwrap_or(0);
+        let Some(shader) = request.request.requests.get(index) else {
+            return Err(ErrorCode::UnknownReference
+                .invalid_params(format!("The request has no compile request {index}"), None));
+        };
+        if shader.define_variants.is_some() {
+            return Err(McpError::invalid_params(
+                "The bisected compile request can't have define_variants",
+                None,
+            ));
+        }
+        let count = match (&request.versions, &request.flags) {
+            (Some(versions), None) => versions.len(),
+            (None, Some(flags)) => flags.len() + 1,
+            _ => {
+                return Err(McpError::invalid_params(
+                    "Give either versions or flags",
+                    None,
+                ));
+            }
+        };
+        if count < 2 {
+            return Err(McpError::invalid_params(
+                "Bisecting needs at least two versions",
+                None,
+            ));
+        }
+
+        let mut lines = Vec::new();
+        let mut report = |version: usize, run: &RunVerdict| {
+            lines.push(format!(
+                "Version {version}: {}",
+                if run.passed { "pass" } else { "FAIL" }
+            ));
+        };
+
+        let first = self.run_bisect_version(&request, index, 0)?;
+        report(0, &first);
+        if !first.passed {
+            lines.push(
+                "The first version already fails, so there is nothing to bisect:".to_string(),
+            );
+            lines.extend(first.errors);
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        }
+
+        let last = self.run_bisect_version(&request, index, count - 1)?;
+        report(count - 1, &last);
+        if last.passed {
+            lines.push("The last version passes too; no version breaks the test.".to_string());
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        }
+
+        // Invariant: version good passes and version bad fails
+        let (mut good, mut bad, mut bad_run) = (0, count - 1, last);
+        while bad - good > 1 {
+            let middle = good + (bad - good) / 2;
+            let run = self.run_bisect_version(&request, index, middle)?;
+            report(middle, &run);
+            if run.passed {
+                good = middle;
+            } else {
+                (bad, bad_run) = (middle, run);
+            }
+        }
+
+        match &request.flags {
+            Some(flags) => lines.push(format!(
+                "First failing version: {bad}, which adds the flag {}",
+                flags[bad - 1]
+            )),
+            None => lines.push(format!("First failing version: {bad}")),
+        }
+        lines.extend(bad_run.errors);
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Check how well a test's probes cover a shader: applies small deliberate mutations to the SPIR-V of one pass (swapped arithmetic and comparison opcodes, swapped operands, zeroed float constants), reruns the test with each and reports the mutants no probe detects, with their source lines. The test must pass unmutated."
+    )]
+    fn mutation_test(
+        &self,
+        #[tool(aggr)] request: MutationTestRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut request = request;
+        if request.request.scenes.is_some() {
+            return Err(McpError::invalid_params(
+                "mutation_test runs a single test; leave out scenes",
+                None,
+            ));
+        }
+        self.resolve_library_references(&mut request.request.passes)?;
+
+        let (pass_index, stage, spvasm) = request.request.spirv_pass(request.stage)?;
+
+        let baseline = self.run_verdict(request.request.duplicate()?);
+        if !baseline.passed {
+            let mut lines = vec![
+                "The unmutated test already fails, so mutants can't be told apart:".to_string(),
+            ];
+            lines.extend(baseline.errors);
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        }
+
+        let mutants = mutation::mutants(&spvasm);
+        let total = mutants.len();
+        let mutants = &mutants[..total.min(request.max_mutants.unwrap_or(32))];
+
+        let mut survivors = Vec::new();
+        for mutant in mutants {
+            let id = artifact_id(&mutant.text);
+            write_spvasm(&artifact_path(&id), &mutant.text)?;
+            let mut copy = request.request.duplicate()?;
+            if let Some(reference) = copy.passes[pass_index].spirv_input_mut() {
+                *reference = id;
+            }
+            if self.run_verdict(copy).passed {
+                survivors.push(mutant);
+            }
+        }
+
+        let mut lines = vec![format!(
+            "{} of {} mutants of the {} shader killed",
+            mutants.len() - survivors.len(),
+            mutants.len(),
+            stage.display_name()
+        )];
+        if mutants.len() < total {
+            lines.push(format!(
+                "Only the first {} of {total} mutants ran; raise max_mutants for the rest",
+                mutants.len()
+            ));
+        }
+        if !survivors.is_empty() {
+            lines.push("Mutants no probe detects:".to_string());
+        }
+        for mutant in survivors {
+            let location = match &mutant.location {
+                Some(location) => match &location.text {
+                    Some(text) => format!("{}:{}: {}", location.file, location.line, text.trim()),
+                    None => format!("{}:{}", location.file, location.line),
+                },
+                None => "no source location".to_string(),
+            };
+            lines.push(format!(
+                "  %{}: {} ({location})",
+                mutant.result_id, mutant.description
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Report which code paths of a shader a test actually executed: instruments the SPIR-V of one pass with an atomic counter per basic block, runs the test and lists each block with its source line and how many invocations entered it. Uses a storage buffer at the next free binding of set 0 and, for graphics stages, the stores-and-atomics feature."
+    )]
+    fn shader_coverage(
+        &self,
+        #[tool(aggr)] request: ShaderCoverageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut request = request;
+        if request.request.scenes.is_some() {
+            return Err(McpError::invalid_params(
+                "shader_coverage runs a single test; leave out scenes",
+                None,
+            ));
+        }
+        self.resolve_library_references(&mut request.request.passes)?;
+
+        let (pass_index, stage, spvasm) = request.request.spirv_pass(request.stage)?;
+        let binding = request
+            .request
+            .tests
+            .iter()
+            .filter_map(ShaderRunnerTest::resource_binding)
+            .chain(spirv::Module::parse(&spvasm).resource_bindings())
+            .map(|(_, binding)| binding + 1)
+            .max()
+            .unwrap_or(0);
+        let instrumented = coverage::instrument(&spvasm, binding).map_err(|message| {
+            McpError::invalid_params(format!("Can't instrument the shader: {message}"), None)
+        })?;
+
+        let id = artifact_id(&instrumented.text);
+        write_spvasm(&artifact_path(&id), &instrumented.text)?;
+        let scratch = ScratchDir::new()?;
+        let dump_path = scratch.path("coverage.bin");
+        let _ = std::fs::remove_file(&dump_path);
+
+        let mut copy = request.request.duplicate()?;
+        if let Some(reference) = copy.passes[pass_index].spirv_input_mut() {
+            *reference = id;
+        }
+        copy.tests.insert(
+            0,
+            ShaderRunnerTest::SSBO {
+                binding,
+                size: Some(4 * instrumented.blocks.len() as u32),
+                data: None,
+                descriptor_set: None,
+            },
+        );
+        copy.vkrunner_options
+            .get_or_insert_with(VkrunnerOptions::default)
+            .buffer_dump = Some(BufferDump {
+            binding: Some(binding),
+            path: dump_path.clone(),
+        });
+
+        let run = self.run_verdict(copy);
+        let Ok(dump) = std::fs::read(&dump_path) else {
+            let mut lines = vec!["The instrumented test produced no counters:".to_string()];
+            lines.extend(run.errors);
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        };
+        let counts = dump
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<_>>();
+
+        let executed = counts.iter().filter(|count| **count > 0).count();
+        let mut lines = vec![format!(
+            "{executed} of {} blocks of the {} shader executed; the test {}",
+            instrumented.blocks.len(),
+            stage.display_name(),
+            if run.passed { "passed" } else { "FAILED" }
+        )];
+        lines.extend(run.errors);
+        for (block, count) in instrumented.blocks.iter().zip(&counts) {
+            let location = match &block.location {
+                Some(location) => match &location.text {
+                    Some(text) => format!("{}:{}: {}", location.file, location.line, text.trim()),
+                    None => format!("{}:{}", location.file, location.line),
+                },
+                None => "no source location".to_string(),
+            };
+            let count = match count {
+                0 => "NEVER EXECUTED".to_string(),
+                count => format!("{count} invocations"),
+            };
+            lines.push(format!("  %{} ({location}): {count}", block.label));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Run compute shaders with in-shader assertions and report where they failed. GLSL compute shaders of the request can use ASSERT(cond), ASSERT_VALUE(cond, value) and ASSERT_EQ/NE/LT/LE/GT/GE(a, b) on scalars; each failure records the source line, gl_GlobalInvocationID and the values into a storage buffer the server adds at set 0. Lists the failed assertions by line with how often they failed and the lowest failing invocation."
+    )]
+    fn check_assertions(
+        &self,
+        #[tool(aggr)] request: CheckAssertionsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut request = request;
+        if request.request.scenes.is_some() {
+            return Err(McpError::invalid_params(
+                "check_assertions runs a single test; leave out scenes",
+                None,
+            ));
+        }
+        self.resolve_library_references(&mut request.request.passes)?;
+
+        let binding = request.binding.unwrap_or_else(|| {
+            request
+                .request
+                .tests
+                .iter()
+                .filter_map(ShaderRunnerTest::resource_binding)
+                .map(|(_, binding)| binding + 1)
+                .max()
+                .unwrap_or(0)
+        });
+        let capacity = request.max_failures.unwrap_or(64);
+
+        let mut copy = request.request.duplicate()?;
+        for (shader, req) in copy.requests.iter_mut().enumerate() {
+            if req.stage == ShaderStage::Comp
+                && req.language.unwrap_or(ShaderLanguage::Glsl) == ShaderLanguage::Glsl
+            {
+                req.assertions = Some(assertions::Assertions {
+                    binding,
+                    capacity,
+                    shader,
+                });
+            }
+        }
+        let Some(helper) = copy.requests.iter().find_map(|req| req.assertions) else {
+            return Err(McpError::invalid_params(
+                "check_assertions needs a GLSL compute shader compile request",
+                None,
+            ));
+        };
+
+        let scratch = ScratchDir::new()?;
+        let dump_path = scratch.path("assertions.bin");
+        let _ = std::fs::remove_file(&dump_path);
+        copy.tests.insert(
+            0,
+            ShaderRunnerTest::SSBO {
+                binding,
+                size: Some(helper.buffer_size()),
+                data: None,
+                descriptor_set: None,
+            },
+        );
+        copy.vkrunner_options
+            .get_or_insert_with(VkrunnerOptions::default)
+            .buffer_dump = Some(BufferDump {
+            binding: Some(binding),
+            path: dump_path.clone(),
+        });
+
+        // Failures name lines of the sources as compiled, helper included
+        let sources = copy
+            .requests
+            .iter()
+            .map(CompileRequest::tagged_source)
+            .collect::<Vec<_>>();
+        let run = self.run_verdict(copy);
+        let Ok(dump) = std::fs::read(&dump_path) else {
+            let mut lines = vec!["The run recorded no assertions:".to_string()];
+            lines.extend(run.errors);
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        };
+
+        let (count, failures) = assertions::decode(&dump);
+        let outcome = if run.passed { "passed" } else { "FAILED" };
+        let mut lines = vec![if count == 0 {
+            format!("No assertion failed; the test {outcome}")
+        } else {
+            format!("{count} assertion failures; the test {outcome}")
+        }];
+        if count as usize > failures.len() {
+            lines.push(format!(
+                "Only the first {} failures were recorded; raise max_failures to see more.",
+                failures.len()
+            ));
+        }
+        lines.extend(run.errors);
+
+        let mut by_line = std::collections::BTreeMap::<_, Vec<&assertions::Failure>>::new();
+        for failure in &failures {
+            by_line
+                .entry((failure.shader, failure.line))
+                .or_default()
+                .push(failure);
+        }
+        for ((shader, line), group) in by_line {
+            let location = sources
+                .get(shader)
+                .and_then(|lines| {
+                    let (origin, text) = lines.get(line.checked_sub(1)?)?;
+                    Some(match origin {
+                        LineOrigin::Source(line) => format!("line {line}: {}", text.trim()),
+                        LineOrigin::Library(index, line) => {
+                            format!("library {index} line {line}: {}", text.trim())
+                        }
+                        LineOrigin::Injected => format!("line {line} added by the server"),
+                    })
+                })
+                .unwrap_or_else(|| format!("line {line}"));
+            let Some(lowest) = group.iter().min_by_key(|failure| {
+                let [x, y, z] = failure.invocation;
+                [z, y, x]
+            }) else {
+                continue;
+            };
+
+            let [x, y, z] = lowest.invocation;
+            let mut first = format!("lowest invocation ({x}, {y}, {z})");
+            if !lowest.values.is_empty() {
+                first.push_str(&format!(" with {}", lowest.values.join(", ")));
+            }
+            lines.push(format!(
+                "- request:{shader} {location} failed {} times, {first}",
+                group.len()
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Profile inside a shader: runs a compile_run_shaders request whose shaders write per-invocation clock deltas (GL_ARB_shader_clock or GL_EXT_shader_realtime_clock) to an SSBO, and reports timing statistics over the invocations plus the slowest ones. Values left 0 count as invocations that didn't write. Clock ticks are device-specific units."
+    )]
+    fn clock_statistics(
+        &self,
+        #[tool(aggr)] request: ClockStatisticsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut request = request;
+        if request.request.scenes.is_some() {
+            return Err(McpError::invalid_params(
+                "clock_statistics runs a single test; leave out scenes",
+                None,
+            ));
+        }
+        self.resolve_library_references(&mut request.request.passes)?;
+
+        let scratch = ScratchDir::new()?;
+        let dump_path = scratch.path("clock.bin");
+        let _ = std::fs::remove_file(&dump_path);
+        let mut copy = request.request.duplicate()?;
+        copy.vkrunner_options
+            .get_or_insert_with(VkrunnerOptions::default)
+            .buffer_dump = Some(BufferDump {
+            binding: Some(request.binding),
+            path: dump_path.clone(),
+        });
+
+        let run = self.run_verdict(copy);
+        let Ok(dump) = std::fs::read(&dump_path) else {
+            let mut lines = vec![format!(
+                "The run dumped no buffer at binding {}:",
+                request.binding
+            )];
+            lines.extend(run.errors);
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        };
+
+        let values = dump
+            .get(request.offset.unwrap_or(0)..)
+            .unwrap_or_default()
+            .chunks_exact(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        let deltas = if request.start_end_pairs == Some(true) {
+            values
+                .chunks_exact(2)
+                .map(|pair| match pair {
+                    [start, end] if *start != 0 => end.wrapping_sub(*start),
+                    _ => 0,
+                })
+                .collect::<Vec<_>>()
+        } else {
+            values
+        };
+        let timed = deltas
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, delta)| *delta != 0)
+            .collect::<Vec<_>>();
+
+        let mut lines = vec![format!(
+            "{} of {} invocation slots hold a clock delta; the test {}",
+            timed.len(),
+            deltas.len(),
+            if run.passed { "passed" } else { "FAILED" }
+        )];
+        lines.extend(run.errors);
+        if timed.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        }
+
+        lines.push("Clock ticks per invocation:".to_string());
+        lines.extend(clock_statistics(
+            &timed.iter().map(|(_, delta)| *delta).collect::<Vec<_>>(),
+        ));
+
+        let mut slowest = timed;
+        slowest.sort_by_key(|(index, delta)| (std::cmp::Reverse(*delta), *index));
+        slowest.truncate(request.slowest.unwrap_or(5));
+        lines.push("Slowest invocations:".to_string());
+        for (index, delta) in slowest {
+            lines.push(format!("- invocation {index}: {delta}"));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Run a regression suite file of named, tagged tests, each a compile_run_shaders request or a vkrunner shader_test script, and report which passed with the errors of those that failed. Filter by tags, or stop at the first failure with fail_fast. Requests run without writing images or using the run cache."
+    )]
+    fn run_suite(
+        &self,
+        #[tool(aggr)] request: RunSuiteRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let export_path = request
+            .export_path
+            .as_deref()
+            .map(|path| confined_tmp_path("export_path", path))
+            .transpose()?;
+        let path = PathBuf::from(tmp_path(&request.path));
+        let code = if path.is_file() {
+            ErrorCode::BadRequest
+        } else {
+            ErrorCode::BadRequestPath
+        };
+        let loaded = suite::Suite::load(&path).map_err(|e| code.invalid_params(e, None))?;
+        let suite_dir = path.parent().unwrap_or(Path::new("/tmp"));
+        let tags = request.tags.unwrap_or_default();
+        let excluded = request.exclude_tags.unwrap_or_default();
+
+        let selected = loaded
+            .tests
+            .iter()
+            .filter(|test| test.selected(&tags, &excluded))
+            .collect::<Vec<_>>();
+        let mut lines = Vec::new();
+        let (mut passed, mut failed) = (0, 0);
+        let mut table = export::Table::new(&["test", "tags", "status", "duration_ms", "errors"]);
+        let tags_of = |test: &suite::SuiteTest| test.tags.join(";");
+
+        for (index, test) in selected.iter().enumerate() {
+            let started = Instant::now();
+            let verdict = self.run_suite_test(test, suite_dir)?;
+            lines.extend(suite_test_lines(test, &verdict, started.elapsed()));
+            table.push(vec![
+                test.name.as_str().into(),
+                tags_of(test).into(),
+                if verdict.passed { "PASS" } else { "FAIL" }.into(),
+                (started.elapsed().as_secs_f64() * 1000.0).into(),
+                (!verdict.errors.is_empty())
+                    .then(|| verdict.errors.join("\n"))
+                    .into(),
+            ]);
+            if verdict.passed {
+                passed += 1;
+                continue;
+            }
+
+            failed += 1;
+            if request.fail_fast == Some(true) {
+                for skipped in &selected[index + 1..] {
+                    lines.push(format!("NOT RUN {}", skipped.name));
+                    table.push(vec![
+                        skipped.name.as_str().into(),
+                        tags_of(skipped).into(),
+                        "NOT RUN".into(),
+                        export::Value::Missing,
+                        export::Value::Missing,
+                    ]);
+                }
+                break;
+            }
+        }
+
+        let name = loaded
+            .name
+            .clone()
+            .unwrap_or_else(|| path.display().to_string());
+        let filtered = loaded.tests.len() - selected.len();
+        let mut summary = format!(
+            "Suite {name}: {passed} passed, {failed} failed of {} selected tests",
+            selected.len()
+        );
+        if filtered > 0 {
+            summary.push_str(&format!(" ({filtered} filtered out by tags)"));
+        }
+        lines.insert(0, summary);
+
+        if let Some(export_path) = &export_path {
+            lines.push(match table.write(Path::new(&export_path)) {
+                Ok(()) => format!("Results exported to: {export_path}"),
+                Err(e) => format!("Failed to export the results: {e}"),
+            });
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Compile a shader once and keep it in this session's library under a name, for helper kernels and shaders reused across many runs. *Spirv passes then reference it as lib:<name> instead of a path or compile request."
+    )]
+    fn register_shader(
+        &self,
+        #[tool(aggr)] request: RegisterShaderRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let name = request.name;
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(McpError::invalid_params(
+                format!("Shader name {name:?} must only use letters, digits, '-' and '_'"),
+                None,
+            ));
+        }
+
+        let req = request.request;
+        let spvasm = req
+            .compile(req.defines.as_deref().unwrap_or_default())?
+            .map_err(|message| {
+                ErrorCode::CompileError
+                    .invalid_params(format!("Failed to compile shader {name}:\n{message}"), None)
+            })?;
+        let artifact_id = artifact_id(&spvasm);
+        write_spvasm(&artifact_path(&artifact_id), &spvasm)?;
+
+        let mut report = format!(
+            "Registered {} shader {name} as {artifact_id}; reference it as {LIBRARY_PREFIX}{name}",
+            req.stage.display_name()
+        );
+        for warning in req.analysis_warnings(&spvasm) {
+            report.push_str(&format!("\n  {warning}"));
+        }
+
+        let replaced = self
+            .shader_library
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                name,
+                LibraryShader {
+                    stage: req.stage,
+                    artifact_id,
+                    description: request.description,
+                },
+            );
+        if let Some(replaced) = replaced {
+            report.push_str(&format!("\nReplaced the earlier {}", replaced.artifact_id));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(report)]))
+    }
+
+    #[tool(description = "List the shaders registered with register_shader")]
+    fn list_shaders(&self) -> Result<CallToolResult, McpError> {
+        let library = self
+            .shader_library
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if library.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(
+                "No shaders are registered",
+            )]));
+        }
+
+        let lines = library
+            .iter()
+            .map(|(name, shader)| {
+                let mut line = format!(
+                    "- {LIBRARY_PREFIX}{name}: {} shader {}",
+                    shader.stage.display_name(),
+                    shader.artifact_id
+                );
+                if let Some(description) = &shader.description {
+                    line.push_str(&format!(" ({description})"));
+                }
+                line
+            })
+            .collect::<Vec<_>>();
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(description = "Remove a shader from the library; its spv-... artifact stays available")]
+    fn unregister_shader(
+        &self,
+        #[tool(aggr)] request: UnregisterShaderRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let removed = self
+            .shader_library
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&request.name);
+
+        match removed {
+            Some(shader) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "Unregistered {} ({})",
+                request.name, shader.artifact_id
+            ))])),
+            None => Err(ErrorCode::UnknownReference
+                .invalid_params(format!("No shader is registered as {}", request.name), None)),
+        }
+    }
+
+    #[tool(
+        description = "Archive the session's state into one file so that a later server (e.g. after a restart) can restore it with restore_workspace: the stored artifacts (spv-... shaders and tex-... textures), the compile_incremental sources and cached compiler outputs, the register_shader library, and any further files such as golden images."
+    )]
+    fn snapshot_workspace(
+        &self,
+        #[tool(aggr)] request: SnapshotWorkspaceRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let path = tmp_path(&request.path);
+        if !is_tmp_path(&path) {
+            return Err(ErrorCode::BadRequestPath.invalid_params(
+                format!("Snapshot path {path} is outside /tmp"),
+                Some(json!({"path": path})),
+            ));
+        }
+
+        let io_err = |path: &str, e: std::io::Error| {
+            ErrorCode::BadRequestPath.invalid_params(
+                format!("Failed to read {path}"),
+                Some(json!({"error": e.to_string()})),
+            )
+        };
+
+        let mut paths = match std::fs::read_dir(artifact_dir()) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "spvasm" || extension == "png")
+                })
+                .map(|path| path.display().to_string())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+        for path in request.files.iter().flatten() {
+            let path = tmp_path(path);
+            if !is_tmp_path(&path) {
+                return Err(ErrorCode::BadRequestPath.invalid_params(
+                    format!("Snapshot file {path} is outside /tmp"),
+                    Some(json!({"path": path})),
+                ));
+            }
+            paths.push(path);
+        }
+
+        let mut files = Vec::new();
+        for path in paths {
+            let contents = std::fs::read(&path).map_err(|e| io_err(&path, e))?;
+            files.push((path, BASE64_STANDARD.encode(&contents)));
+        }
+
+        let snapshot = {
+            let cache = self.compile_cache.lock().unwrap_or_else(|e| e.into_inner());
+            WorkspaceSnapshot {
+                version: WORKSPACE_SNAPSHOT_VERSION,
+                sources: cache
+                    .sources
+                    .iter()
+                    .map(|(id, source)| (id.clone(), source.clone()))
+                    .collect(),
+                results: cache
+                    .results
+                    .iter()
+                    .map(|(key, result)| (key.clone(), result.clone()))
+                    .collect(),
+                files,
+                library: self
+                    .shader_library
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .iter()
+                    .map(|(name, shader)| (name.clone(), shader.clone()))
+                    .collect(),
+            }
+        };
+
+        let json = serde_json::to_vec(&snapshot).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize the workspace",
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+        if let Some(parent) = Path::new(&path).parent() {
+            std::fs::create_dir_all(parent).map_err(|e| io_err(&path, e))?;
+        }
+        write_atomically(&path, &json).map_err(|e| {
+            McpError::internal_error(
+                "Failed to write the workspace snapshot",
+                Some(json!({"error": e.to_string(), "path": path})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Saved {} files, {} sources, {} compiler outputs and {} library shaders to {path} ({} bytes)",
+            snapshot.files.len(),
+            snapshot.sources.len(),
+            snapshot.results.len(),
+            snapshot.library.len(),
+            json.len()
+        ))]))
+    }
+
+    #[tool(
+        description = "Restore a session archived by snapshot_workspace: rewrites its files at their original paths and adds its compile_incremental sources, compiler outputs and library shaders to this server's, so earlier spv-..., tex-..., src-... and lib:... references and file paths work again."
+    )]
+    fn restore_workspace(
+        &self,
+        #[tool(aggr)] request: RestoreWorkspaceRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let path = tmp_path(&request.path);
+        if !is_tmp_path(&path) {
+            return Err(ErrorCode::BadRequestPath.invalid_params(
+                format!("Snapshot path {path} is outside /tmp"),
+                Some(json!({"path": path})),
+            ));
+        }
+        let json = std::fs::read(&path).map_err(|e| {
+            ErrorCode::BadRequestPath.invalid_params(
+                format!("Failed to read workspace snapshot {path}"),
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+        let snapshot = serde_json::from_slice::<WorkspaceSnapshot>(&json).map_err(|e| {
+            McpError::invalid_params(
+                format!("{path} is not a workspace snapshot"),
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+        if snapshot.version != WORKSPACE_SNAPSHOT_VERSION {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Workspace snapshot version {} is not supported (expected {WORKSPACE_SNAPSHOT_VERSION})",
+                    snapshot.version
+                ),
+                None,
+            ));
+        }
+
+        // Check everything before writing anything, so a bad snapshot
+        // leaves the workspace as it was
+        let mut files = Vec::new();
+        for (file, encoded) in &snapshot.files {
+            // Artifacts may live outside /tmp when the configuration says so
+            let artifact = Path::new(file).parent() == Some(artifact_dir());
+            if !is_tmp_path(file) && !artifact {
+                return Err(ErrorCode::BadRequestPath.invalid_params(
+                    format!("Workspace snapshot file {file} is outside /tmp"),
+                    None,
+                ));
+            }
+            let contents = base64_decode(encoded).map_err(|e| {
+                McpError::invalid_params(
+                    format!("Workspace snapshot file {file} is not valid base64"),
+                    Some(json!({"error": e})),
+                )
+            })?;
+            files.push((file, contents));
+        }
+
+        for (file, contents) in &files {
+            Path::new(file)
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| write_atomically(file, contents))
+                .map_err(|e| {
+                    McpError::internal_error(
+                        format!("Failed to restore {file}"),
+                        Some(json!({"error": e.to_string()})),
+                    )
+                })?;
+        }
+
+        let mut cache = self.compile_cache.lock().unwrap_or_else(|e| e.into_inner());
+        for (id, source) in snapshot.sources.iter().cloned() {
+            cache.sources.insert(id, source);
+        }
+        for (key, result) in snapshot.results.iter().cloned() {
+            cache.results.insert(key, result);
+        }
+        drop(cache);
+        self.shader_library
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(snapshot.library.iter().cloned());
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Restored {} files, {} sources, {} compiler outputs and {} library shaders from {path}",
+            files.len(),
+            snapshot.sources.len(),
+            snapshot.results.len(),
+            snapshot.library.len()
+        ))]))
+    }
+
+    #[tool(
+        description = "Show the server's effective configuration: its configuration file merged with command line flags"
+    )]
+    fn get_config(&self) -> Result<CallToolResult, McpError> {
+        let config = toml::to_string(&self.options.effective()).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize the configuration",
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(config)]))
+    }
+
+    #[tool(
+        description = "Partially evaluate a compiled SPIR-V module with the given spec constant and push constant values and report which output stores are statically determined, e.g. a fragment color that is provably vec4(0, 0, 0, 1). Catches shaders that are constant by construction before running them; values that depend on other inputs, memory or control flow are reported as runtime-dependent."
+    )]
+    fn evaluate_constant_expressions(
+        &self,
+        #[tool(aggr)] request: EvaluateConstantExpressionsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let path = if request.spvasm_path.starts_with("spv-") {
+            artifact_path(&request.spvasm_path)
+        } else {
+            tmp_path(&request.spvasm_path)
+        };
+
+        let spvasm = std::fs::read_to_string(&path).map_err(|e| {
+            ErrorCode::BadRequestPath.invalid_params(
+                format!("Failed to read SPIR-V file at {path}"),
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+
+        let inputs = evaluate::Inputs {
+            spec_constants: request
+                .spec_constants
+                .iter()
+                .flatten()
+                .map(|constant| (constant.spec_id, constant.value))
+                .collect(),
+            push_constants: request
+                .push_constants
+                .into_iter()
+                .flatten()
+                .map(|constant| (constant.member, constant.values))
+                .collect(),
+        };
+        let stores = evaluate::evaluate_outputs(&spirv::Module::parse(&spvasm), &inputs);
+
+        if stores.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "{path} stores no outputs"
+            ))]));
+        }
+
+        let report = stores
+            .iter()
+            .map(|store| {
+                let location = store
+                    .location
+                    .map(|location| format!(" (location {location})"))
+                    .unwrap_or_default();
+                match &store.value {
+                    Some(value) => format!("- {}{location} is provably {value}", store.output),
+                    None => format!("- {}{location} depends on runtime values", store.output),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Output stores in {path}:\n{report}"
+        ))]))
+    }
+
+    #[tool(
+        description = "Statically analyze how a compiled compute shader accesses shared and storage buffer memory. Follows each address as a function of the invocation ID to find the stride between consecutive invocations of a subgroup, and reports likely shared memory bank conflicts, uncoalesced storage buffer accesses and addresses it can't follow, with their source lines. The results are hypotheses to confirm with timings, not measurements."
+    )]
+    fn analyze_memory_access(
+        &self,
+        #[tool(aggr)] request: AnalyzeMemoryAccessRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let path = if request.spvasm_path.starts_with("spv-") {
+            artifact_path(&request.spvasm_path)
+        } else {
+            tmp_path(&request.spvasm_path)
+        };
+
+        let spvasm = std::fs::read_to_string(&path).map_err(|e| {
+            ErrorCode::BadRequestPath.invalid_params(
+                format!("Failed to read SPIR-V file at {path}"),
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+
+        let hardware = access::Hardware {
+            subgroup_size: request.subgroup_size.unwrap_or(32),
+            banks: request.banks.unwrap_or(32),
+            bank_width: request.bank_width.unwrap_or(4),
+            segment_size: request.segment_size.unwrap_or(128),
+        };
+        let sizes = [
+            hardware.subgroup_size,
+            hardware.banks,
+            hardware.bank_width,
+            hardware.segment_size,
+        ];
+        if sizes.iter().any(|size| !(1..=4096).contains(size)) {
+            return Err(ErrorCode::BadRequest.invalid_params(
+                "subgroup_size, banks, bank_width and segment_size must be between 1 and 4096",
+                None,
+            ));
+        }
+
+        let module = spirv::Module::parse(&spvasm);
+        let is_compute = module
+            .entry_points()
+            .iter()
+            .any(|entry_point| entry_point.execution_model == "GLCompute");
+        if !is_compute {
+            return Err(ErrorCode::BadRequest
+                .invalid_params(format!("{path} is not a compute shader"), None));
+        }
+
+        let accesses = access::accesses(&module, &hardware);
+        let mut lines = vec![format!(
+            "{} shared and storage buffer accesses in {path}, for subgroups of {} consecutive invocations along x, {} banks of {} bytes and {}-byte segments",
+            accesses.len(),
+            hardware.subgroup_size,
+            hardware.banks,
+            hardware.bank_width,
+            hardware.segment_size
+        )];
+        match module.local_size() {
+            Some([x, ..]) if x < hardware.subgroup_size => lines.push(format!(
+                "The workgroup is only {x} invocations wide, so a subgroup also spans y and z, which this analysis doesn't model"
+            )),
+            _ => {}
+        }
+
+        let problems = accesses
+            .iter()
+            .filter(|access| access.problem.is_some())
+            .collect::<Vec<_>>();
+        if problems.is_empty() {
+            lines.push(
+                "No likely bank conflicts or uncoalesced accesses: every access is conflict-free, coalesced or the same address for the whole subgroup".to_string(),
+            );
+        } else {
+            lines.push("Likely problems:".to_string());
+        }
+        for access in &problems {
+            let location = match &access.location {
+                Some(location) => match &location.text {
+                    Some(text) => format!("{}:{}: {}", location.file, location.line, text.trim()),
+                    None => format!("{}:{}", location.file, location.line),
+                },
+                None => "no source location".to_string(),
+            };
+            let stride = match access.stride {
+                Some(stride) => format!(", {stride} bytes apart"),
+                None => String::new(),
+            };
+            lines.push(format!(
+                "- {}-byte {} of {} {}{stride} ({location}): {}",
+                access.width,
+                access.kind,
+                if access.shared { "shared" } else { "buffer" },
+                access.variable,
+                access.problem.as_deref().unwrap_or_default()
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Check the device's subgroup operations: runs a battery of compute kernels (elect, vote, ballot, arithmetic and scans, shuffles, clustered and quad operations) at the device's default subgroup size and every supported required size, compares each invocation's result with a CPU reference computed from the subgroups the device formed, and reports the operations that differ. Use it to validate a driver or before building on a subgroup primitive."
+    )]
+    fn test_subgroup_ops(
+        &self,
+        #[tool(aggr)] request: TestSubgroupOpsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut categories = request
+            .categories
+            .unwrap_or_else(|| subgroups::Category::ALL.to_vec());
+        categories.sort_unstable();
+        categories.dedup();
+        if categories.is_empty() {
+            return Err(McpError::invalid_params(
+                "categories names no operations to test",
+                None,
+            ));
+        }
+
+        let sizes = request
+            .subgroup_sizes
+            .unwrap_or_else(|| (0..8).map(|shift| 1 << shift).collect());
+        if let Some(size) = sizes
+            .iter()
+            .find(|size| !size.is_power_of_two() || **size > 128)
+        {
+            return Err(McpError::invalid_params(
+                format!("Subgroup size {size} is not a power of two up to 128"),
+                None,
+            ));
+        }
+
+        let mut lines = Vec::new();
+        let mut anomaly_count = 0;
+        let mut tested = 0;
+        for size in std::iter::once(None).chain(sizes.into_iter().map(Some)) {
+            let label = match size {
+                Some(size) => format!("Subgroup size {size}"),
+                None => "Default subgroup size".to_string(),
+            };
+
+            // Run every category at once, and each on its own when that
+            // fails so one unsupported category doesn't hide the others
+            let mut runs = vec![(
+                categories.clone(),
+                self.run_subgroup_ops(size, &categories, request.icd.as_ref()),
+            )];
+            let combined_failed = matches!(runs.as_slice(), [(_, KernelRun::Failed(_))]);
+            if combined_failed && categories.len() > 1 {
+                runs = categories
+                    .iter()
+                    .map(|category| {
+                        let run = self.run_subgroup_ops(size, &[*category], request.icd.as_ref());
+                        (vec![*category], run)
+                    })
+                    .collect();
+            }
+            if let [(_, KernelRun::Skipped(reason))] = runs.as_slice() {
+                lines.push(format!("- {label}: skipped, {reason}"));
+                continue;
+            }
+            tested += 1;
+
+            let mut details = Vec::new();
+            let mut reported_sizes = Vec::new();
+            for (run_categories, run) in &runs {
+                let dump = match run {
+                    KernelRun::Dump(dump, _) => dump,
+                    KernelRun::Skipped(reason) | KernelRun::Failed(reason) => {
+                        anomaly_count += 1;
+                        details.push(format!(
+                            "  - {:?} operations didn't run: {reason}",
+                            run_categories
+                        ));
+                        continue;
+                    }
+                };
+                reported_sizes.extend(subgroups::subgroup_sizes(dump));
+                for anomaly in subgroups::check(dump, run_categories) {
+                    anomaly_count += 1;
+                    details.push(format!(
+                        "  - {}: {} of {} invocations differ; {}",
+                        anomaly.operation, anomaly.mismatches, anomaly.checked, anomaly.first
+                    ));
+                }
+            }
+            reported_sizes.sort_unstable();
+            reported_sizes.dedup();
+            if let (Some(size), false) = (
+                size,
+                reported_sizes
+                    .iter()
+                    .all(|reported| Some(*reported) == size),
+            ) {
+                anomaly_count += 1;
+                details.push(format!(
+                    "  - gl_SubgroupSize is {reported_sizes:?}, not the required {size}"
+                ));
+            }
+
+            let reported = reported_sizes
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            if details.is_empty() {
+                lines.push(format!(
+                    "- {label} (gl_SubgroupSize {reported}): every operation matches"
+                ));
+            } else {
+                lines.push(format!(
+                    "- {label} (gl_SubgroupSize {reported}): {} anomalies",
+                    details.len()
+                ));
+                lines.extend(details);
+            }
+        }
+
+        let operations = subgroups::OPERATIONS
+            .iter()
+            .filter(|operation| categories.contains(&operation.category))
+            .count();
+        let summary = if anomaly_count == 0 {
+            format!(
+                "All {operations} subgroup operations match their CPU references at {tested} subgroup sizes"
+            )
+        } else {
+            format!(
+                "{anomaly_count} anomalies in {operations} subgroup operations at {tested} subgroup sizes"
+            )
+        };
+        lines.insert(0, summary);
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Characterize the device's floating-point environment. For 16-, 32- and 64-bit floats, runs kernels on inputs the compiler cannot fold that probe denormal operands and results, contraction of a * b + c into an FMA, whether precise and fma() are honored, rounding of halfway results, overflow, NaN and signed zeros, and measures the error of division, sqrt and the transcendental functions against CPU references, flagging 32-bit results outside Vulkan's required precision. Use it to choose tolerances for numerical tests on this device."
+    )]
+    fn characterize_fp(
+        &self,
+        #[tool(aggr)] request: CharacterizeFpRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut types = request.types.unwrap_or_else(|| fp::FloatType::ALL.to_vec());
+        types.sort_unstable();
+        types.dedup();
+        if types.is_empty() {
+            return Err(McpError::invalid_params(
+                "types names no float types to characterize",
+                None,
+            ));
+        }
+
+        let scratch = ScratchDir::new()?;
+        let mut lines = Vec::new();
+        for float in types {
+            let dump_path = scratch.path(&format!("characterize_fp_{float:?}.bin"));
+            let words = fp::buffer(float);
+            let run_request = CompileRunShadersRequest::single_shader(
+                ShaderStage::Comp,
+                fp::shader(float),
+                vec![
+                    ShaderRunnerTest::ssbo(0, words.len() * 4),
+                    ShaderRunnerTest::ssbo_values(
+                        0,
+                        DataType::Uint,
+                        words.iter().map(u32::to_string).collect(),
+                    ),
+                    ShaderRunnerTest::Compute { x: 1, y: 1, z: 1 },
+                ],
+                request.icd.clone(),
+            );
+
+            let label = format!("{} ({float:?})", float.glsl());
+            match self.run_kernel(run_request, 0, &dump_path) {
+                KernelRun::Dump(dump, _) => {
+                    lines.push(format!("{label}:"));
+                    lines.extend(
+                        fp::report(float, &dump)
+                            .into_iter()
+                            .map(|line| format!("  {line}")),
+                    );
+                }
+                KernelRun::Skipped(reason) => lines.push(format!("{label}: skipped, {reason}")),
+                KernelRun::Failed(reason) => {
+                    lines.push(format!("{label}: the kernel FAILED to run, {reason}"))
+                }
+            }
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Run a matrix multiplication on cooperative matrices (VK_KHR_cooperative_matrix) and verify it. Picks the first configuration of tile sizes and component types the device supports (or the one given), generates a kernel computing C = A x B with coopMatLoad/coopMatMulAdd/coopMatStore, checks C against a CPU GEMM within the accumulator's rounding error, and reports the largest error, mismatching elements and throughput."
+    )]
+    fn run_coopmat_matmul(
+        &self,
+        #[tool(aggr)] request: RunCoopmatMatmulRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let size = request.size.unwrap_or(256);
+        if !(1..=512).contains(&size) {
+            return Err(McpError::invalid_params(
+                format!("size {size} is not between 1 and 512"),
+                None,
+            ));
+        }
+        let repetitions = request.repetitions.unwrap_or(5).max(1);
+
+        let candidates = match (
+            request.m,
+            request.n,
+            request.k,
+            request.input_type,
+            request.accumulator_type,
+        ) {
+            (Some(m), Some(n), Some(k), Some(input), Some(accumulator)) => vec![coopmat::Config {
+                m,
+                n,
+                k,
+                input,
+                accumulator,
+            }],
+            _ => coopmat::CANDIDATES
+                .iter()
+                .filter(|config| {
+                    request.m.is_none_or(|m| m == config.m)
+                        && request.n.is_none_or(|n| n == config.n)
+                        && request.k.is_none_or(|k| k == config.k)
+                        && request.input_type.is_none_or(|input| input == config.input)
+                        && request
+                            .accumulator_type
+                            .is_none_or(|accumulator| accumulator == config.accumulator)
+                })
+                .copied()
+                .collect(),
+        };
+        if candidates.is_empty() {
+            return Err(McpError::invalid_params(
+                "No known cooperative matrix configuration matches; give m, n, k, input_type and accumulator_type to try another one",
+                Some(json!({
+                    "configurations": coopmat::CANDIDATES.iter().map(ToString::to_string).collect::<Vec<_>>()
+                })),
+            ));
+        }
+        if let Some(config) = candidates
+            .iter()
+            .find(|config| config.m == 0 || config.n == 0 || config.k == 0)
+        {
+            return Err(McpError::invalid_params(
+                format!("Tile sizes of {config} must not be 0"),
+                None,
+            ));
+        }
+
+        let mut skipped = Vec::new();
+        let scratch = ScratchDir::new()?;
+        for config in candidates {
+            let problem = coopmat::Problem::new(size, &config);
+            let dump_path = &scratch.path("coopmat_matmul.bin");
+            let mut tests = vec![
+                ShaderRunnerTest::ssbo_values(
+                    0,
+                    config.input.data_type(),
+                    coopmat::inputs(0, &problem, &config),
+                ),
+                ShaderRunnerTest::ssbo_values(
+                    1,
+                    config.input.data_type(),
+                    coopmat::inputs(1, &problem, &config),
+                ),
+                ShaderRunnerTest::ssbo(2, problem.result_bytes(&config)),
+            ];
+            tests.extend((0..repetitions).map(|_| ShaderRunnerTest::Compute {
+                x: problem.tiles(&config).min(65535),
+                y: 1,
+                z: 1,
+            }));
+            let mut run_request = CompileRunShadersRequest::single_shader(
+                ShaderStage::Comp,
+                coopmat::shader(&problem, &config),
+                tests,
+                request.icd.clone(),
+            );
+            run_request.requirements = Some(vec![ShaderRunnerRequire::CooperativeMatrix {
+                m: config.m,
+                n: config.n,
+                component_type: config.accumulator.glsl().to_string(),
+                k: Some(config.k),
+                ab_type: Some(config.input.glsl().to_string()),
+                result_type: Some(config.accumulator.glsl().to_string()),
+            }]);
+
+            let (dump, output) = match self.run_kernel(run_request, 2, dump_path) {
+                KernelRun::Dump(dump, output) => (dump, output),
+                KernelRun::Skipped(_) => {
+                    skipped.push(config.to_string());
+                    continue;
+                }
+                KernelRun::Failed(reason) => {
+                    return Ok(CallToolResult::success(vec![Content::text(format!(
+                        "The cooperative matrix kernel for {config} FAILED to run: {reason}"
+                    ))]));
+                }
+            };
+
+            let verification = coopmat::verify(&dump, &problem, &config);
+            let mut lines = vec![format!(
+                "Cooperative matrix matmul with {config} tiles, C ({} x {}) = A ({} x {}) x B ({} x {}):",
+                problem.m, problem.n, problem.m, problem.k, problem.k, problem.n
+            )];
+            lines.push(match verification.first {
+                None => format!(
+                    "- PASS: largest error {:e} ({:e} relative), every element within the accumulator's rounding",
+                    verification.max_error, verification.max_relative_error
+                ),
+                Some((row, col, actual, expected)) => format!(
+                    "- FAIL: {} of {} elements are off by more than the accumulator's rounding, first C[{row}][{col}] = {actual}, expected {expected}; largest error {:e} ({:e} relative)",
+                    verification.mismatches,
+                    problem.m * problem.n,
+                    verification.max_error,
+                    verification.max_relative_error
+                ),
+            });
+            let operations = if config.accumulator.is_integer() {
+                "GOP/s"
+            } else {
+                "GFLOP/s"
+            };
+            lines.push(match watchdog::total_submission_time(&output) {
+                Some(total) if !total.is_zero() => {
+                    let seconds = total.as_secs_f64() / f64::from(repetitions);
+                    format!(
+                        "- Throughput: {:.1} {operations}, {:.3} ms per product over {repetitions} dispatches",
+                        problem.operations() / seconds / 1e9,
+                        seconds * 1000.0
+                    )
+                }
+                _ => "- Throughput: not measured, vkrunner reported no submission times".to_string(),
+            });
+            if !skipped.is_empty() {
+                lines.push(format!("- Unsupported: {}", skipped.join(", ")));
+            }
+            return Ok(CallToolResult::success(vec![Content::text(
+                lines.join("\n"),
+            )]));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "The device supports none of the cooperative matrix configurations tried: {}",
+            skipped.join(", ")
+        ))]))
+    }
+
+    #[tool(
+        description = "Verify a parallel reduction or prefix scan (inclusive or exclusive) with add, min or max over uint, int or float against a CPU reference, on random inputs of sizes around the workgroup and chunk boundaries where such kernels usually break. Without a source it verifies the built-in known-good kernel, whose GLSL it returns and can register in the shader library as a building block. Float sums pass within the rounding error of any summation order."
+    )]
+    fn verify_reduction_scan(
+        &self,
+        #[tool(aggr)] request: VerifyReductionScanRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let kernel = scan::Kernel {
+            pattern: request.pattern,
+            operator: request.operator,
+            element: request.element,
+        };
+        let sizes = request
+            .sizes
+            .unwrap_or_else(|| vec![1, 7, 256, 257, 1000, 4099]);
+        if let Some(size) = sizes.iter().find(|size| !(1..=65536).contains(*size)) {
+            return Err(McpError::invalid_params(
+                format!("size {size} is not between 1 and 65536"),
+                None,
+            ));
+        }
+        if request.items_per_workgroup == Some(0) {
+            return Err(McpError::invalid_params(
+                "items_per_workgroup must be at least 1",
+                None,
+            ));
+        }
+        let built_in = request.source.is_none();
+        let source = request.source.unwrap_or_else(|| kernel.shader());
+        let seed = request.seed.unwrap_or(1);
+
+        let mut lines = Vec::new();
+        let mut failures = 0;
+        let scratch = ScratchDir::new()?;
+        for &size in &sizes {
+            let dump_path = scratch.path(&format!("{}_{size}.bin", kernel.name()));
+            let inputs = kernel.inputs(size, seed);
+            let workgroups = request
+                .items_per_workgroup
+                .map_or(1, |items| size.div_ceil(items));
+            let run_request = CompileRunShadersRequest::single_shader(
+                ShaderStage::Comp,
+                source.clone(),
+                vec![
+                    ShaderRunnerTest::ssbo(0, size as usize * 4),
+                    ShaderRunnerTest::ssbo_values(
+                        0,
+                        kernel.element.data_type(),
+                        kernel.input_values(&inputs),
+                    ),
+                    ShaderRunnerTest::ssbo(1, kernel.output_len(size) as usize * 4),
+                    ShaderRunnerTest::push(DataType::Uint, 0, vec![size.to_string()]),
+                    ShaderRunnerTest::Compute {
+                        x: workgroups,
+                        y: 1,
+                        z: 1,
+                    },
+                ],
+                request.icd.clone(),
+            );
+
+            match self.run_kernel(run_request, 1, &dump_path) {
+                KernelRun::Dump(dump, _) => {
+                    let comparison = kernel.compare(&inputs, &dump);
+                    match comparison.first {
+                        None => lines.push(format!(
+                            "- {size} elements: PASS ({} results)",
+                            comparison.checked
+                        )),
+                        Some((index, actual, expected)) => {
+                            failures += 1;
+                            lines.push(format!(
+                                "- {size} elements: FAIL, {} of {} results differ; first at index {index}: {actual} instead of {expected}",
+                                comparison.mismatches, comparison.checked
+                            ));
+                        }
+                    }
+                }
+                KernelRun::Skipped(reason) => {
+                    lines.push(format!("- {size} elements: skipped, {reason}"))
+                }
+                KernelRun::Failed(reason) => {
+                    failures += 1;
+                    lines.push(format!("- {size} elements: FAILED to run, {reason}"));
+                }
+            }
+        }
+
+        let what = if built_in {
+            format!("Built-in {}", kernel.name())
+        } else {
+            format!("Shader under test ({})", kernel.name())
+        };
+        lines.insert(
+            0,
+            format!(
+                "{what}: {} of {} sizes passed",
+                sizes.len() - failures,
+                sizes.len()
+            ),
+        );
+
+        if let Some(name) = request.register_as {
+            if !built_in {
+                lines.push(
+                    "Not registered: register_as only registers the built-in kernel".to_string(),
+                );
+            } else if failures > 0 {
+                lines.push(format!(
+                    "Not registered as {name}: the kernel failed on this device"
+                ));
+            } else {
+                let register = serde_json::from_value::<RegisterShaderRequest>(json!({
+                    "name": name,
+                    "request": {"stage": "Comp", "source": source},
+                    "description": format!(
+                        "Built-in {}: input at binding 0, output at binding 1, uint count push constant; dispatch one workgroup",
+                        kernel.name()
+                    ),
+                }))
+                .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+                let registered = self.register_shader(register)?;
+                lines.extend(
+                    registered
+                        .content
+                        .iter()
+                        .filter_map(|content| content.as_text())
+                        .map(|content| content.text.clone()),
+                );
+            }
+        }
+        if built_in {
+            lines.push(format!("\nShader:\n{source}"));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Verify a GPU histogram or sort (bitonic, radix or any other) against a CPU reference on seeded random inputs of several sizes: skewed bin indices that make atomics contend, and sort keys spanning all 32 bits with many duplicates. Reports mismatching elements and the first divergent index for each size. Without a source it verifies the built-in known-good kernel, whose GLSL it returns and can register in the shader library."
+    )]
+    fn verify_histogram_sort(
+        &self,
+        #[tool(aggr)] request: VerifyHistogramSortRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let algorithm = request.algorithm;
+        let sizes = request
+            .sizes
+            .unwrap_or_else(|| vec![1, 7, 256, 257, 1000, 4099]);
+        if let Some(size) = sizes.iter().find(|size| !(1..=65536).contains(*size)) {
+            return Err(McpError::invalid_params(
+                format!("size {size} is not between 1 and 65536"),
+                None,
+            ));
+        }
+        let bins = request.bins.unwrap_or(64);
+        if !(1..=4096).contains(&bins) {
+            return Err(McpError::invalid_params(
+                format!("bins {bins} is not between 1 and 4096"),
+                None,
+            ));
+        }
+        if request.items_per_workgroup == Some(0) {
+            return Err(McpError::invalid_params(
+                "items_per_workgroup must be at least 1",
+                None,
+            ));
+        }
+        let built_in = request.source.is_none();
+        let source = request.source.unwrap_or_else(|| algorithm.shader());
+        let seed = request.seed.unwrap_or(1);
+
+        let mut lines = Vec::new();
+        let mut failures = 0;
+        let scratch = ScratchDir::new()?;
+        for &size in &sizes {
+            let dump_path = scratch.path(&format!("{}_{size}.bin", algorithm.name()));
+            let inputs = algorithm.inputs(size, bins, seed);
+            let workgroups = request
+                .items_per_workgroup
+                .map_or(1, |items| size.div_ceil(items));
+            let mut tests = vec![
+                ShaderRunnerTest::ssbo(0, size as usize * 4),
+                ShaderRunnerTest::ssbo_values(
+                    0,
+                    DataType::Uint,
+                    inputs.iter().map(u32::to_string).collect(),
+                ),
+            ];
+            let mut push = vec![size.to_string()];
+            if algorithm == sort::Algorithm::Histogram {
+                tests.push(ShaderRunnerTest::ssbo(1, bins as usize * 4));
+                tests.push(ShaderRunnerTest::ssbo_values(
+                    1,
+                    DataType::Uint,
+                    vec!["0".to_string(); bins as usize],
+                ));
+                push.push(bins.to_string());
+            }
+            tests.push(ShaderRunnerTest::push(DataType::Uint, 0, push));
+            tests.push(ShaderRunnerTest::Compute {
+                x: workgroups,
+                y: 1,
+                z: 1,
+            });
+            let run_request = CompileRunShadersRequest::single_shader(
+                ShaderStage::Comp,
+                source.clone(),
+                tests,
+                request.icd.clone(),
+            );
+
+            match self.run_kernel(run_request, algorithm.output_binding(), &dump_path) {
+                KernelRun::Dump(dump, _) => {
+                    let comparison = algorithm.compare(&inputs, bins, &dump);
+                    let mut line = match comparison.first {
+                        None => format!("- {size} elements: PASS"),
+                        Some((index, actual, expected)) => {
+                            failures += 1;
+                            let actual =
+                                actual.map_or("nothing".to_string(), |actual| actual.to_string());
+                            format!(
+                                "- {size} elements: FAIL, {} of {} {} differ; first at index {index}: {actual} instead of {expected}",
+                                comparison.mismatches,
+                                comparison.checked,
+                                match algorithm {
+                                    sort::Algorithm::Histogram => "bins",
+                                    sort::Algorithm::Sort => "keys",
+                                }
+                            )
+                        }
+                    };
+                    if algorithm == sort::Algorithm::Histogram
+                        && comparison.total != u64::from(size)
+                    {
+                        line.push_str(&format!(" (the bins count {} elements)", comparison.total));
+                    }
+                    lines.push(line);
+                }
+                KernelRun::Skipped(reason) => {
+                    lines.push(format!("- {size} elements: skipped, {reason}"))
+                }
+                KernelRun::Failed(reason) => {
+                    failures += 1;
+                    lines.push(format!("- {size} elements: FAILED to run, {reason}"));
+                }
+            }
+        }
+
+        let what = if built_in {
+            format!("Built-in {}", algorithm.name())
+        } else {
+            format!("Shader under test ({})", algorithm.name())
+        };
+        lines.insert(
+            0,
+            format!(
+                "{what}: {} of {} sizes passed",
+                sizes.len() - failures,
+                sizes.len()
+            ),
+        );
+
+        if let Some(name) = request.register_as {
+            if !built_in {
+                lines.push(
+                    "Not registered: register_as only registers the built-in kernel".to_string(),
+                );
+            } else if failures > 0 {
+                lines.push(format!(
+                    "Not registered as {name}: the kernel failed on this device"
+                ));
+            } else {
+                let register = serde_json::from_value::<RegisterShaderRequest>(json!({
+                    "name": name,
+                    "request": {"stage": "Comp", "source": source},
+                    "description": format!(
+                        "Built-in {}; dispatch one workgroup",
+                        algorithm.name()
+                    ),
+                }))
+                .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+                let registered = self.register_shader(register)?;
+                lines.extend(
+                    registered
+                        .content
+                        .iter()
+                        .filter_map(|content| content.as_text())
+                        .map(|content| content.text.clone()),
+                );
+            }
+        }
+        if built_in {
+            lines.push(format!("\nShader:\n{source}"));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Run a compute filter over an uploaded image and return the result: the image is packed into a storage buffer one uint per pixel, the filter (a built-in preset or your GLSL) writes the output buffer, which is unpacked, saved as PNG and embedded. With a reference image, also reports the PSNR, the largest channel difference and how many pixels differ."
+    )]
+    fn run_image_filter(
+        &self,
+        #[tool(aggr)] request: RunImageFilterRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let input = uploaded_texture(&request.input)?;
+        let (width, height) = input.dimensions();
+        if width * height > MAX_FILTER_PIXELS {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Input image is {width}x{height}, more than the {MAX_FILTER_PIXELS} pixels a filter can run on"
+                ),
+                None,
+            ));
+        }
+        let source = match (request.source, request.preset) {
+            (Some(source), None) => source,
+            (None, Some(preset)) => preset.shader(),
+            _ => {
+                return Err(McpError::invalid_params(
+                    "Give exactly one of source and preset",
+                    None,
+                ));
+            }
+        };
+        let [local_x, local_y] = request.local_size.unwrap_or([8, 8]);
+        if local_x == 0 || local_y == 0 {
+            return Err(McpError::invalid_params(
+                "local_size must be at least 1 in both dimensions",
+                None,
+            ));
+        }
+        let reference = match &request.reference {
+            Some(id) if id.starts_with("tex-") => Some(uploaded_texture(id)?),
+            Some(path) => Some(
+                image::open(path)
+                    .map_err(|e| {
+                        McpError::invalid_params(
+                            format!("Failed to read reference image {path}"),
+                            Some(json!({"error": e.to_string()})),
+                        )
+                    })?
+                    .to_rgba8(),
+            ),
+            None => None,
+        };
+        if let Some(reference) = reference
+            .as_ref()
+            .filter(|reference| reference.dimensions() != (width, height))
+        {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Reference image is {}x{} but the input is {width}x{height}",
+                    reference.width(),
+                    reference.height()
+                ),
+                None,
+            ));
+        }
+        let output_path = request
+            .output_path
+            .unwrap_or_else(|| ScratchDir::output_path("image_filter", "png"));
+
+        let scratch = ScratchDir::new()?;
+        let dump_path = &scratch.path("image_filter.bin");
+        let size = (width * height) as usize * 4;
+        let run_request = CompileRunShadersRequest::single_shader(
+            ShaderStage::Comp,
+            source,
+            vec![
+                ShaderRunnerTest::ssbo(0, size),
+                ShaderRunnerTest::ssbo_values(0, DataType::Uint, filter::pack(&input)),
+                ShaderRunnerTest::ssbo(1, size),
+                ShaderRunnerTest::push(
+                    DataType::Uint,
+                    0,
+                    vec![width.to_string(), height.to_string()],
+                ),
+                ShaderRunnerTest::Compute {
+                    x: width.div_ceil(local_x),
+                    y: height.div_ceil(local_y),
+                    z: 1,
+                },
+            ],
+            request.icd.clone(),
+        );
+
+        let dump = match self.run_kernel(run_request, 1, dump_path) {
+            KernelRun::Dump(dump, _) => dump,
+            KernelRun::Skipped(reason) | KernelRun::Failed(reason) => {
+                return Ok(CallToolResult::success(vec![Content::text(format!(
+                    "The filter FAILED to run: {reason}"
+                ))]));
+            }
+        };
+        let output = filter::unpack(&dump, width, height).ok_or_else(|| {
+            McpError::internal_error(
+                format!(
+                    "The output buffer holds {} bytes, less than a {width}x{height} image",
+                    dump.len()
+                ),
+                None,
+            )
+        })?;
+        output.save(&output_path).map_err(|e| {
+            McpError::internal_error(
+                format!("Failed to write output image {output_path}"),
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
+
+        let mut lines = vec![format!(
+            "Filtered {width}x{height} image {} saved to: {output_path}",
+            request.input
+        )];
+        if let Some(reference) = &reference {
+            let difference = filter::difference(&output, reference);
+            lines.push(format!(
+                "Against the reference: PSNR {:.2} dB, max channel difference {}, {} of {} pixels differ",
+                difference.psnr,
+                difference.max,
+                difference.differing,
+                width * height
+            ));
+        }
+
+        let mut contents = vec![Content::text(lines.join("\n"))];
+        let preview = DynamicImage::ImageRgba8(output).to_rgb8();
+        match image_resource(
+            &preview,
+            &output_path,
+            Some(request.return_image_max_dim.unwrap_or(512)),
+        ) {
+            Ok(resource) => contents.push(resource),
+            Err(e) => contents.push(Content::text(format!("Failed to embed output image: {e}"))),
+        }
+        Ok(CallToolResult::success(contents))
+    }
+
+    #[tool(
+        description = "Render a signed-distance-field raymarcher on a full-screen rectangle with standard camera push constants (eye, target, fov_y, resolution, time). A scene that only defines map(p) gets a built-in sphere tracer. Reports the fraction of the frame the scene covers and, given the spheres it contains, compares that coverage and the silhouettes pixel by pixel with the analytic ones."
+    )]
+    fn run_raymarcher(
+        &self,
+        #[tool(aggr)] request: RunRaymarcherRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let camera = raymarch::Camera {
+            eye: request.eye.unwrap_or([0.0, 0.0, 3.0]),
+            target: request.target.unwrap_or([0.0, 0.0, 0.0]),
+            fov_y: request.fov_y.unwrap_or(60.0).to_radians(),
+        };
+        if camera.eye == camera.target {
+            return Err(McpError::invalid_params("eye and target must differ", None));
+        }
+        if !(camera.fov_y > 0.0 && camera.fov_y < std::f64::consts::PI) {
+            return Err(McpError::invalid_params(
+                "fov_y must be between 0 and 180 degrees",
+                None,
+            ));
+        }
+        let output_path = request
+            .output_path
+            .unwrap_or_else(|| ScratchDir::output_path("raymarch", "png"));
+
+        let mut tests = camera.push_constants(request.time.unwrap_or(0.0));
+        tests.push(ShaderRunnerTest::full_screen_rect());
+        let mut run_request = CompileRunShadersRequest::single_shader(
+            ShaderStage::Frag,
+            raymarch::shader(&request.source),
+            tests,
+            request.icd,
+        );
+        run_request.output_path = Some(output_path.clone());
+        run_request.return_image_max_dim = request.return_image_max_dim;
+
+        let _ = std::fs::remove_file(&output_path);
+        let mut contents = self.run_shaders_inner(&run_request)?.contents();
+        let Ok(frame) = image::open(&output_path).map(|frame| frame.to_rgb8()) else {
+            return Ok(CallToolResult::success(contents));
+        };
 
-            for data in vertex_data {
-                if let ShaderRunnerVertexData::AttributeFormat { location, format } = data {
-                    writeln!(shader_test_file, "{location}/{format}").map_err(io_err)?;
+        let rendered = raymarch::rendered_coverage(&frame, request.background.unwrap_or_default());
+        let mut lines = vec![format!(
+            "Raymarcher coverage: {:.2}% of the frame",
+            raymarch::percentage(&rendered)
+        )];
+        if let Some(spheres) = request.spheres.filter(|spheres| !spheres.is_empty()) {
+            let expected = raymarch::expected_coverage(&spheres, &camera);
+            let tolerance = request.coverage_tolerance.unwrap_or(1.0);
+            let difference = raymarch::percentage(&rendered) - raymarch::percentage(&expected);
+            let missed = expected
+                .iter()
+                .zip(&rendered)
+                .filter(|(expected, rendered)| **expected && !**rendered)
+                .count();
+            let extra = expected
+                .iter()
+                .zip(&rendered)
+                .filter(|(expected, rendered)| !**expected && **rendered)
+                .count();
+            lines.push(format!(
+                "Analytic coverage of {} sphere(s): {:.2}%, {} ({difference:+.2} points, tolerance {tolerance})",
+                spheres.len(),
+                raymarch::percentage(&expected),
+                if difference.abs() <= tolerance {
+                    "PASS"
                 } else {
-                    match data {
-                        ShaderRunnerVertexData::Vec2 { x, y } => {
-                            write!(shader_test_file, "{x} {y}").map_err(io_err)?;
-                        }
-                        ShaderRunnerVertexData::Vec3 { x, y, z } => {
-                            write!(shader_test_file, "{x} {y} {z}").map_err(io_err)?;
-                        }
-                        ShaderRunnerVertexData::Vec4 { x, y, z, w } => {
-                            write!(shader_test_file, "{x} {y} {z} {w}").map_err(io_err)?;
-                        }
-                        ShaderRunnerVertexData::RGB { r, g, b } => {
-                            write!(shader_test_file, "{r} {g} {b}").map_err(io_err)?;
-                        }
-                        ShaderRunnerVertexData::Hex { value } => {
-                            write!(shader_test_file, "{value}").map_err(io_err)?;
-                        }
-                        ShaderRunnerVertexData::GenericComponents { components } => {
-                            for component in components {
-                                write!(shader_test_file, "{component} ").map_err(io_err)?;
-                            }
-                        }
-                        _ => {}
-                    }
-                    writeln!(shader_test_file).map_err(io_err)?;
+                    "FAIL"
                 }
+            ));
+            lines.push(format!(
+                "Silhouettes: {missed} expected pixels not covered, {extra} covered pixels outside them"
+            ));
+        }
+        contents.push(Content::text(lines.join("\n")));
+        Ok(CallToolResult::success(contents))
+    }
+
+    #[tool(
+        description = "Verify a procedural noise function statistically, since point probes can't: samples noise(p) over one period on a grid into a float buffer, then reports the value range, mean and variance, the energy in each octave of frequencies from a 2D FFT, and the mismatch across opposite borders. Optional expectations for the range, moments, highest frequency and tileability turn these into PASS/FAIL checks."
+    )]
+    fn analyze_noise(
+        &self,
+        #[tool(aggr)] request: AnalyzeNoiseRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let size = request.size.unwrap_or(256);
+        if !size.is_power_of_two() || !(16..=1024).contains(&size) {
+            return Err(McpError::invalid_params(
+                format!("size {size} is not a power of two from 16 to 1024"),
+                None,
+            ));
+        }
+        let period = request.period.unwrap_or(8.0);
+        if !(period.is_finite() && period > 0.0) {
+            return Err(McpError::invalid_params("period must be positive", None));
+        }
+
+        let samples = size + 1;
+        let scratch = ScratchDir::new()?;
+        let dump_path = &scratch.path("analyze_noise.bin");
+        let run_request = CompileRunShadersRequest::single_shader(
+            ShaderStage::Comp,
+            noise::shader(&request.source, size, period),
+            vec![
+                ShaderRunnerTest::ssbo(0, (samples * samples) as usize * 4),
+                ShaderRunnerTest::Compute {
+                    x: samples.div_ceil(8),
+                    y: samples.div_ceil(8),
+                    z: 1,
+                },
+            ],
+            request.icd.clone(),
+        );
+        let values = match self.run_kernel(run_request, 0, dump_path) {
+            KernelRun::Dump(dump, _) => noise::read(&dump, size).ok_or_else(|| {
+                McpError::internal_error(
+                    format!("The noise buffer holds {} bytes, too few", dump.len()),
+                    None,
+                )
+            })?,
+            KernelRun::Skipped(reason) | KernelRun::Failed(reason) => {
+                return Ok(CallToolResult::success(vec![Content::text(format!(
+                    "The noise kernel FAILED to run: {reason}"
+                ))]));
             }
+        };
 
-            writeln!(shader_test_file).map_err(io_err)?;
+        let one_period = noise::period(&values, size);
+        let stats = noise::statistics(&one_period);
+        let mut checks = Vec::new();
+        let mut lines = vec![
+            format!("Sampled noise(p) on a {size}x{size} grid over [0, {period}]^2"),
+            format!(
+                "- Range: [{:.6}, {:.6}], mean {:.6}, variance {:.6} (standard deviation {:.6})",
+                stats.min,
+                stats.max,
+                stats.mean,
+                stats.variance,
+                stats.variance.sqrt()
+            ),
+        ];
+        if stats.non_finite > 0 {
+            checks.push(format!(
+                "FAIL finite values: {} samples are NaN or infinite",
+                stats.non_finite
+            ));
+        }
+        if let Some([min, max]) = request.expected_range {
+            let inside = stats.min >= min && stats.max <= max;
+            checks.push(format!(
+                "{} range within [{min}, {max}]",
+                if inside { "PASS" } else { "FAIL" }
+            ));
+        }
+        let tolerance = request.tolerance.unwrap_or(0.1);
+        let scale = request
+            .expected_variance
+            .unwrap_or(stats.variance)
+            .max(f64::EPSILON);
+        if let Some(mean) = request.expected_mean {
+            let pass = (stats.mean - mean).abs() <= tolerance * scale.sqrt();
+            checks.push(format!(
+                "{} mean {:.6} vs expected {mean}",
+                if pass { "PASS" } else { "FAIL" },
+                stats.mean
+            ));
+        }
+        if let Some(variance) = request.expected_variance {
+            let pass = (stats.variance - variance).abs() <= tolerance * scale;
+            checks.push(format!(
+                "{} variance {:.6} vs expected {variance}",
+                if pass { "PASS" } else { "FAIL" },
+                stats.variance
+            ));
         }
 
-        writeln!(shader_test_file, "[test]").map_err(io_err)?;
+        if stats.non_finite == 0 {
+            let spectrum = noise::spectrum(&one_period, size);
+            lines.push("- Energy by frequency (cycles per period):".to_string());
+            for (low, high, fraction) in noise::octave_bands(&spectrum) {
+                lines.push(format!("  {low}-{high}: {:.1}%", fraction * 100.0));
+            }
+            let peak = (1..spectrum.len())
+                .max_by(|a, b| spectrum[*a].total_cmp(&spectrum[*b]))
+                .unwrap_or(1);
+            lines.push(format!(
+                "- Peak frequency: {peak} cycles per period, {:.3} cycles per unit",
+                peak as f64 / period
+            ));
+            if let Some(max_frequency) = request.max_frequency {
+                let total = spectrum.iter().skip(1).sum::<f64>().max(f64::MIN_POSITIVE);
+                let above = spectrum
+                    .iter()
+                    .skip(max_frequency as usize + 1)
+                    .sum::<f64>()
+                    / total;
+                checks.push(format!(
+                    "{} {:.1}% of the energy above {max_frequency} cycles per period",
+                    if above <= 0.05 { "PASS" } else { "FAIL" },
+                    above * 100.0
+                ));
+            }
+        }
 
-        for test_cmd in &request.tests {
-            match test_cmd {
-                ShaderRunnerTest::FragmentEntrypoint { name } => {
-                    writeln!(shader_test_file, "fragment entrypoint {name}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::VertexEntrypoint { name } => {
-                    writeln!(shader_test_file, "vertex entrypoint {name}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::ComputeEntrypoint { name } => {
-                    writeln!(shader_test_file, "compute entrypoint {name}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::GeometryEntrypoint { name } => {
-                    writeln!(shader_test_file, "geometry entrypoint {name}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::DrawRect {
-                    x,
-                    y,
-                    width,
-                    height,
-                } => {
-                    writeln!(shader_test_file, "draw rect {x} {y} {width} {height}")
-                        .map_err(io_err)?;
-                }
-                ShaderRunnerTest::DrawArrays {
-                    primitive_type,
-                    first,
-                    count,
-                } => {
-                    writeln!(
-                        shader_test_file,
-                        "draw arrays {primitive_type} {first} {count}"
-                    )
-                    .map_err(io_err)?;
-                }
-                ShaderRunnerTest::DrawArraysIndexed {
-                    primitive_type,
-                    first,
-                    count,
-                } => {
-                    writeln!(
-                        shader_test_file,
-                        "draw arrays indexed {primitive_type} {first} {count}"
-                    )
-                    .map_err(io_err)?;
-                }
-                ShaderRunnerTest::SSBO {
-                    binding,
-                    size,
-                    data,
-                    descriptor_set,
-                } => {
-                    let set_prefix = if let Some(set) = descriptor_set {
-                        format!("{set}:")
-                    } else {
-                        String::new()
-                    };
+        let seam = noise::seam(&values, size);
+        lines.push(format!(
+            "- Border mismatch: {seam:.6} ({:.2} standard deviations)",
+            seam / stats.variance.sqrt().max(f64::EPSILON)
+        ));
+        if request.tileable == Some(true) {
+            checks.push(format!(
+                "{} tiles with period {period}",
+                if seam <= 1e-3 { "PASS" } else { "FAIL" }
+            ));
+        }
 
-                    if let Some(size) = size {
-                        writeln!(shader_test_file, "ssbo {set_prefix}{binding} {size}")
-                            .map_err(io_err)?;
-                    } else if let Some(_data) = data {
-                        writeln!(shader_test_file, "ssbo {set_prefix}{binding} data")
-                            .map_err(io_err)?;
-                    }
-                }
-                ShaderRunnerTest::SSBOSubData {
-                    binding,
-                    data_type,
-                    offset,
-                    values,
-                    descriptor_set,
-                } => {
-                    let set_prefix = if let Some(set) = descriptor_set {
-                        format!("{set}:")
-                    } else {
-                        String::new()
-                    };
+        if !checks.is_empty() {
+            let failed = checks
+                .iter()
+                .filter(|check| check.starts_with("FAIL"))
+                .count();
+            lines.push(format!(
+                "Checks: {} of {} passed",
+                checks.len() - failed,
+                checks.len()
+            ));
+            lines.extend(checks.into_iter().map(|check| format!("- {check}")));
+        }
 
-                    write!(
-                        shader_test_file,
-                        "ssbo {set_prefix}{binding} subdata {data_type} {offset}"
-                    )
-                    .map_err(io_err)?;
-                    for value in values {
-                        write!(shader_test_file, " {value}").map_err(io_err)?;
-                    }
-                    writeln!(shader_test_file).map_err(io_err)?;
-                }
-                ShaderRunnerTest::UBO {
-                    binding,
-                    data: _,
-                    descriptor_set,
-                } => {
-                    let set_prefix = if let Some(set) = descriptor_set {
-                        format!("{set}:")
-                    } else {
-                        String::new()
-                    };
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
 
-                    writeln!(shader_test_file, "ubo {set_prefix}{binding} data").map_err(io_err)?;
-                }
-                ShaderRunnerTest::UBOSubData {
-                    binding,
-                    data_type,
-                    offset,
-                    values,
-                    descriptor_set,
-                } => {
-                    let set_prefix = if let Some(set) = descriptor_set {
-                        format!("{set}:")
-                    } else {
-                        String::new()
-                    };
+    #[tool(
+        description = "Check screen-space derivatives against finite differences. A fragment shader stores the dFdx/dFdy of value(p) (or your derivatives(p) code) for every pixel, a compute kernel stores central differences of value(p) at a fraction of a pixel, and the two are compared: mismatching pixels and their bounding box, RMS and mean error, and the best-fit scale, which exposes flipped signs, wrong scales and derivatives taken in non-uniform control flow."
+    )]
+    fn check_derivatives(
+        &self,
+        #[tool(aggr)] request: CheckDerivativesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let pixel_size = request
+            .pixel_size
+            .unwrap_or(1.0 / f64::from(derivatives::RESOLUTION));
+        if !(pixel_size.is_finite() && pixel_size > 0.0) {
+            return Err(McpError::invalid_params(
+                "pixel_size must be positive",
+                None,
+            ));
+        }
+        let refine = request.refine.unwrap_or(4);
+        if !(1..=64).contains(&refine) {
+            return Err(McpError::invalid_params(
+                format!("refine {refine} is not between 1 and 64"),
+                None,
+            ));
+        }
+        let tolerance = request.tolerance.unwrap_or(0.05);
+        let size = (derivatives::RESOLUTION * derivatives::RESOLUTION) as usize * 16;
+
+        let scratch = ScratchDir::new()?;
+        let shader_path = &scratch.path("check_derivatives_shader.bin");
+        let mut shader_run = CompileRunShadersRequest::single_shader(
+            ShaderStage::Frag,
+            derivatives::fragment_shader(
+                &request.source,
+                request.derivatives.as_deref(),
+                pixel_size,
+            ),
+            vec![
+                ShaderRunnerTest::ssbo(0, size),
+                ShaderRunnerTest::full_screen_rect(),
+            ],
+            request.icd.clone(),
+        );
+        shader_run.requirements = Some(vec![ShaderRunnerRequire::FragmentStoresAndAtomics]);
+        let reference_path = &scratch.path("check_derivatives_reference.bin");
+        let groups = derivatives::RESOLUTION.div_ceil(8);
+        let reference_run = CompileRunShadersRequest::single_shader(
+            ShaderStage::Comp,
+            derivatives::reference_shader(&request.source, pixel_size, refine),
+            vec![
+                ShaderRunnerTest::ssbo(0, size),
+                ShaderRunnerTest::Compute {
+                    x: groups,
+                    y: groups,
+                    z: 1,
+                },
+            ],
+            request.icd.clone(),
+        );
 
-                    write!(
-                        shader_test_file,
-                        "ubo {set_prefix}{binding} subdata {data_type} {offset}"
-                    )
-                    .map_err(io_err)?;
-                    for value in values {
-                        write!(shader_test_file, " {value}").map_err(io_err)?;
-                    }
-                    writeln!(shader_test_file).map_err(io_err)?;
-                }
-                ShaderRunnerTest::BufferLayout {
-                    buffer_type,
-                    layout_type,
-                } => {
-                    writeln!(shader_test_file, "{buffer_type} layout {layout_type}")
-                        .map_err(io_err)?;
-                }
-                ShaderRunnerTest::Push {
-                    data_type,
-                    offset,
-                    values,
-                } => {
-                    write!(shader_test_file, "push {data_type} {offset}").map_err(io_err)?;
-                    for value in values {
-                        write!(shader_test_file, " {value}").map_err(io_err)?;
-                    }
-                    writeln!(shader_test_file).map_err(io_err)?;
-                }
-                ShaderRunnerTest::PushLayout { layout_type } => {
-                    writeln!(shader_test_file, "push layout {layout_type}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::Compute { x, y, z } => {
-                    writeln!(shader_test_file, "compute {x} {y} {z}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::Probe {
-                    probe_type,
-                    format,
-                    args,
-                } => {
-                    write!(shader_test_file, "probe {probe_type} {format}").map_err(io_err)?;
-                    for arg in args {
-                        write!(shader_test_file, " {arg}").map_err(io_err)?;
-                    }
-                    writeln!(shader_test_file).map_err(io_err)?;
+        let mut dumps = Vec::new();
+        for (name, run_request, path) in [
+            ("fragment shader", shader_run, shader_path),
+            ("reference kernel", reference_run, reference_path),
+        ] {
+            match self.run_kernel(run_request, 0, path) {
+                KernelRun::Dump(dump, _) => dumps.push(derivatives::read(&dump)),
+                KernelRun::Skipped(reason) | KernelRun::Failed(reason) => {
+                    return Ok(CallToolResult::success(vec![Content::text(format!(
+                        "The {name} FAILED to run: {reason}"
+                    ))]));
                 }
-                ShaderRunnerTest::RelativeProbe {
-                    probe_type,
-                    format,
-                    args,
-                } => {
-                    write!(shader_test_file, "relative probe {probe_type} {format}")
-                        .map_err(io_err)?;
-                    for arg in args {
-                        write!(shader_test_file, " {arg}").map_err(io_err)?;
-                    }
-                    writeln!(shader_test_file).map_err(io_err)?;
+            }
+        }
+        let (shader, reference) = (&dumps[0], &dumps[1]);
+
+        let unwritten = shader.iter().filter(|pixel| pixel.is_none()).count();
+        let mut lines = vec![format!(
+            "Compared dFdx/dFdy on a {0}x{0} frame with central differences at 1/{refine} pixel{1}",
+            derivatives::RESOLUTION,
+            if unwritten > 0 {
+                format!("; {unwritten} pixels were not written by the fragment shader")
+            } else {
+                String::new()
+            }
+        )];
+        let mut failed = false;
+        for (axis, name) in [(1, "dFdx"), (2, "dFdy")] {
+            let comparison = derivatives::compare(shader, reference, axis, tolerance);
+            failed |= comparison.mismatches > 0;
+            lines.push(format!(
+                "{name}: {} ({} of {} pixels beyond tolerance {tolerance})",
+                if comparison.mismatches == 0 {
+                    "PASS"
+                } else {
+                    "FAIL"
+                },
+                comparison.mismatches,
+                comparison.compared
+            ));
+            lines.push(format!(
+                "- RMS error {:.3e} against a reference RMS of {:.3e}, mean error {:+.3e}, best-fit scale {:.4}",
+                comparison.rms_error,
+                comparison.reference_rms,
+                comparison.mean_error,
+                comparison.scale
+            ));
+            if let Some(((x, y), actual, expected)) = comparison.first {
+                lines.push(format!(
+                    "- First mismatch at pixel ({x}, {y}): {actual:.6} instead of {expected:.6}"
+                ));
+            }
+            if let Some((min_x, min_y, max_x, max_y)) = comparison.bounds {
+                lines.push(format!(
+                    "- Mismatches lie within ({min_x}, {min_y}) to ({max_x}, {max_y})"
+                ));
+            }
+            if comparison.mismatches > 0 {
+                if (comparison.scale + 1.0).abs() < 0.05 {
+                    lines.push("- The sign is flipped throughout".to_string());
+                } else if (comparison.scale - 1.0).abs() > 0.05 && comparison.scale.abs() > 0.05 {
+                    lines.push(format!(
+                        "- Derivatives are scaled by about {:.3}",
+                        comparison.scale
+                    ));
                 }
-                ShaderRunnerTest::Tolerance { values } => {
-                    write!(shader_test_file, "tolerance").map_err(io_err)?;
-                    for value in values {
-                        write!(shader_test_file, " {value}").map_err(io_err)?;
+            }
+        }
+        if failed {
+            lines.push(
+                "Errors confined to some regions usually mean derivatives taken in non-uniform control flow, where helper or inactive invocations leave the quad's values undefined; take them before branching."
+                    .to_string(),
+            );
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "Verify color blending numerically: draws a background, then two overlapping translucent quads with the given blend state, and probes the RGBA of the background, each quad alone and their overlap against composites computed on the CPU with the blend equations and 8-bit rounding after each draw. Returns the expected colors with the run's probe results and image."
+    )]
+    fn verify_blending(
+        &self,
+        #[tool(aggr)] request: VerifyBlendingRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let background = request.background.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        let first = request.first_color.unwrap_or([1.0, 0.0, 0.0, 0.5]);
+        let second = request.second_color.unwrap_or([0.0, 0.0, 1.0, 0.5]);
+        let state = request.blend.unwrap_or(blend::BlendState::OVER);
+        let unblended = blend::BlendState {
+            enable: false,
+            ..blend::BlendState::OVER
+        };
+
+        // The quads cover pixels 31-156 and 94-219 of the 250x250 frame
+        let base = unblended.composite(background, [0.0; 4]);
+        let over_first = state.composite(first, base);
+        let regions = [
+            ("background", (5, 5), base),
+            ("first quad", (45, 45), over_first),
+            ("second quad", (195, 195), state.composite(second, base)),
+            ("overlap", (120, 120), state.composite(second, over_first)),
+        ];
+
+        let color =
+            |color: [f64; 4]| format!("{} {} {} {}", color[0], color[1], color[2], color[3]);
+        let quad = |corner: f32| ShaderRunnerTest::DrawRect {
+            x: corner,
+            y: corner,
+            width: 1.0,
+            height: 1.0,
+        };
+        let mut tests = vec![
+            ShaderRunnerTest::BlendState { state: unblended },
+            ShaderRunnerTest::push(DataType::Vec4, 0, vec![color(background)]),
+            ShaderRunnerTest::full_screen_rect(),
+            ShaderRunnerTest::BlendState { state },
+            ShaderRunnerTest::push(DataType::Vec4, 0, vec![color(first)]),
+            quad(-0.75),
+            ShaderRunnerTest::push(DataType::Vec4, 0, vec![color(second)]),
+            quad(-0.25),
+            ShaderRunnerTest::Tolerance {
+                values: vec![0.01; 4],
+            },
+        ];
+        let mut lines = vec!["Expected composites (RGBA, after 8-bit rounding):".to_string()];
+        for (name, (x, y), expected) in regions {
+            let expected_text = format!(
+                "({:.4}, {:.4}, {:.4}, {:.4})",
+                expected[0], expected[1], expected[2], expected[3]
+            );
+            lines.push(format!(
+                "- {name}, probed at ({x}, {y}, 10, 10): {expected_text}"
+            ));
+            tests.push(ShaderRunnerTest::Probe {
+                probe_type: "rect".to_string(),
+                format: "rgba".to_string(),
+                args: vec![format!("({x}, {y}, 10, 10)"), expected_text],
+            });
+        }
+
+        let mut run_request = CompileRunShadersRequest::single_shader(
+            ShaderStage::Frag,
+            "#version 450
+layout(push_constant) uniform Quad { vec4 color; };
+layout(location = 0) out vec4 frag_color;
+void main()
+{
+    frag_color = color;
+}
+"
+            .to_string(),
+            tests,
+            request.icd,
+        );
+        run_request.output_path = Some(
+            request
+                .output_path
+                .unwrap_or_else(|| ScratchDir::output_path("blending", "png")),
+        );
+        run_request.return_image_max_dim = request.return_image_max_dim;
+
+        let mut contents = vec![Content::text(lines.join("\n"))];
+        contents.extend(self.run_shaders_inner(&run_request)?.contents());
+        Ok(CallToolResult::success(contents))
+    }
+
+    #[tool(
+        description = "Measure depth-buffer precision on the device. Each row of the frame draws two surfaces a small separation apart at one view distance, log-spaced from the near to the far plane; rows where the back surface shows through are z-fighting. Reports for each depth format, with standard and reversed Z, how much of the range fights, where fighting starts and the distance beyond which the surfaces are never resolved."
+    )]
+    fn analyze_depth_precision(
+        &self,
+        #[tool(aggr)] request: AnalyzeDepthPrecisionRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let near = request.near.unwrap_or(0.1);
+        let far = request.far.unwrap_or(1000.0);
+        if !(near > 0.0 && far > near && far.is_finite()) {
+            return Err(McpError::invalid_params(
+                format!("near {near} and far {far} must satisfy 0 < near < far"),
+                None,
+            ));
+        }
+        let separation = request.separation.unwrap_or(0.001);
+        let relative = request.relative_separation.unwrap_or(true);
+        if !(separation.is_finite() && separation > 0.0) {
+            return Err(McpError::invalid_params(
+                "separation must be positive",
+                None,
+            ));
+        }
+        let formats = request.formats.unwrap_or_else(|| {
+            DEPTH_FORMATS
+                .iter()
+                .map(|format| format.to_string())
+                .collect()
+        });
+        let mut modes = vec![("standard", 0)];
+        if request.reversed_z.unwrap_or(true) {
+            modes.push(("reversed", depth::REVERSED));
+        }
+        let output_dir = request.output_dir.unwrap_or_else(|| "/tmp".to_string());
+        let flags = if relative { depth::RELATIVE } else { 0 };
+
+        let mut lines = vec![format!(
+            "Surfaces {separation}{} apart at distances {near} to {far}, one distance per row",
+            if relative {
+                " of the distance"
+            } else {
+                " units"
+            }
+        )];
+        for format in &formats {
+            for &(mode, mode_flags) in &modes {
+                let output_path = format!("{output_dir}/depth_{format}_{mode}.png");
+                let push = |color: &str, offset: f64, flags: u32| {
+                    [
+                        ShaderRunnerTest::push(DataType::Vec4, 0, vec![color.to_string()]),
+                        ShaderRunnerTest::push(
+                            DataType::Float,
+                            16,
+                            vec![near.to_string(), far.to_string(), offset.to_string()],
+                        ),
+                        ShaderRunnerTest::push(DataType::Uint, 28, vec![flags.to_string()]),
+                        ShaderRunnerTest::full_screen_rect(),
+                    ]
+                };
+                let compare = if mode_flags & depth::REVERSED != 0 {
+                    "VK_COMPARE_OP_GREATER"
+                } else {
+                    "VK_COMPARE_OP_LESS"
+                };
+                let mut tests = vec![
+                    ShaderRunnerTest::DepthTestEnable { enable: true },
+                    ShaderRunnerTest::DepthWriteEnable { enable: true },
+                    ShaderRunnerTest::DepthCompareOp {
+                        op: "VK_COMPARE_OP_ALWAYS".to_string(),
+                    },
+                ];
+                tests.extend(push("0 0 0 1", 0.0, mode_flags | depth::BACKGROUND));
+                tests.push(ShaderRunnerTest::DepthCompareOp {
+                    op: compare.to_string(),
+                });
+                tests.extend(push("0 1 0 1", separation, mode_flags | flags));
+                tests.extend(push("1 0 0 1", 0.0, mode_flags | flags));
+
+                let mut run_request = CompileRunShadersRequest::single_shader(
+                    ShaderStage::Frag,
+                    depth::SHADER.to_string(),
+                    tests,
+                    request.icd.clone(),
+                );
+                run_request.requirements =
+                    Some(vec![ShaderRunnerRequire::DepthStencil(format.clone())]);
+                run_request.output_path = Some(output_path.clone());
+
+                let _ = std::fs::remove_file(&output_path);
+                let run = self.run_shaders_inner(&run_request)?;
+                let label = format!("{format}, {mode} Z");
+                let Ok(img) = image::open(&output_path).map(|img| img.to_rgb8()) else {
+                    if run.skipped {
+                        lines.push(format!(
+                            "- {label}: skipped, the device doesn't support it as a depth attachment"
+                        ));
+                    } else {
+                        lines.push(format!(
+                            "- {label}: FAILED to render, {}",
+                            run.failure_reason()
+                        ));
                     }
-                    writeln!(shader_test_file).map_err(io_err)?;
+                    continue;
+                };
+
+                let fighting = depth::fighting_rows(&img);
+                let count = fighting.iter().filter(|fighting| **fighting).count();
+                let mut line = format!(
+                    "- {label}: {:.1}% of the distance range z-fights",
+                    100.0 * count as f64 / fighting.len().max(1) as f64
+                );
+                if let Some(row) = fighting.iter().position(|fighting| *fighting) {
+                    line.push_str(&format!(
+                        ", first at distance {:.4}",
+                        depth::distance(row as u32, near, far)
+                    ));
                 }
-                ShaderRunnerTest::Clear => {
-                    writeln!(shader_test_file, "clear").map_err(io_err)?;
-                }
-                ShaderRunnerTest::DepthTestEnable { enable } => {
-                    writeln!(shader_test_file, "depthTestEnable {enable}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::DepthWriteEnable { enable } => {
-                    writeln!(shader_test_file, "depthWriteEnable {enable}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::DepthCompareOp { op } => {
-                    writeln!(shader_test_file, "depthCompareOp {op}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::StencilTestEnable { enable } => {
-                    writeln!(shader_test_file, "stencilTestEnable {enable}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::FrontFace { mode } => {
-                    writeln!(shader_test_file, "frontFace {mode}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::StencilOp {
-                    face,
-                    op_name,
-                    value,
-                } => {
-                    writeln!(shader_test_file, "{face}.{op_name} {value}",).map_err(io_err)?;
-                }
-                ShaderRunnerTest::StencilReference { face, value } => {
-                    writeln!(shader_test_file, "{face}.reference {value}",).map_err(io_err)?;
-                }
-                ShaderRunnerTest::StencilCompareOp { face, op } => {
-                    writeln!(shader_test_file, "{face}.compareOp {op}",).map_err(io_err)?;
-                }
-                ShaderRunnerTest::ColorWriteMask { mask } => {
-                    writeln!(shader_test_file, "colorWriteMask {mask}",).map_err(io_err)?;
-                }
-                ShaderRunnerTest::LogicOpEnable { enable } => {
-                    writeln!(shader_test_file, "logicOpEnable {enable}",).map_err(io_err)?;
-                }
-                ShaderRunnerTest::LogicOp { op } => {
-                    writeln!(shader_test_file, "logicOp {op}",).map_err(io_err)?;
-                }
-                ShaderRunnerTest::CullMode { mode } => {
-                    writeln!(shader_test_file, "cullMode {mode}",).map_err(io_err)?;
-                }
-                ShaderRunnerTest::LineWidth { width } => {
-                    writeln!(shader_test_file, "lineWidth {width}").map_err(io_err)?;
-                }
-                ShaderRunnerTest::Require {
-                    feature,
-                    parameters,
-                } => {
-                    write!(shader_test_file, "require {feature}").map_err(io_err)?;
-                    for param in parameters {
-                        write!(shader_test_file, " {param}").map_err(io_err)?;
+                match depth::resolved_until(&fighting, near, far) {
+                    None => line.push_str(", resolved at the far plane"),
+                    Some(distance) => {
+                        line.push_str(&format!(", never resolved beyond {distance:.4}"))
                     }
-                    writeln!(shader_test_file).map_err(io_err)?;
                 }
+                line.push_str(&format!(" ({output_path})"));
+                lines.push(line);
             }
         }
+        lines.push(
+            "Rows are red where the front surface wins and green where the depth buffer can't separate the surfaces."
+                .to_string(),
+        );
 
-        shader_test_file.flush().map_err(io_err)?;
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
 
-        let tmp_image_path = "/tmp/vkrunner_output.ppm";
-        if let Some(output_path) = &request.output_path {
-            if output_path.starts_with("/tmp") {
-                tmp_image_path.to_string()
-            } else {
-                format!("/tmp/{output_path}")
+    #[tool(
+        description = "Plot numeric results as a line or scatter chart saved as a PNG and returned as an image, such as an error against a swept parameter or the time against the workgroup size. Takes series of points, or columns of a CSV table such as one written by export_path. Axes can be logarithmic."
+    )]
+    fn plot_results(
+        &self,
+        #[tool(aggr)] request: PlotResultsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let width = request.width.unwrap_or(640);
+        let height = request.height.unwrap_or(400);
+        if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return Err(McpError::invalid_params(
+                format!("Charts are at most {MAX_TEXTURE_SIZE} pixels on a side"),
+                None,
+            ));
+        }
+
+        let mut series = request.series.unwrap_or_default();
+        let mut y_columns = request.y_columns.unwrap_or_default();
+        if let Some(csv_path) = &request.csv_path {
+            let path = tmp_path(csv_path);
+            let text = std::fs::read_to_string(&path).map_err(|e| {
+                ErrorCode::BadRequestPath
+                    .invalid_params(format!("Failed to read {path}: {e}"), None)
+            })?;
+            let (header, rows) = export::read_csv(&text)
+                .map_err(|e| McpError::invalid_params(format!("Invalid CSV {path}: {e}"), None))?;
+            let column = |name: &str| {
+                header
+                    .iter()
+                    .position(|column| column == name)
+                    .ok_or_else(|| {
+                        McpError::invalid_params(
+                            format!(
+                                "{path} has no column {name:?}; its columns are {}",
+                                header.join(", ")
+                            ),
+                            None,
+                        )
+                    })
+            };
+            let Some(x_column) = &request.x_column else {
+                return Err(McpError::invalid_params("csv_path needs x_column", None));
+            };
+            if y_columns.is_empty() {
+                return Err(McpError::invalid_params("csv_path needs y_columns", None));
+            }
+            let x = column(x_column)?;
+            for name in &y_columns {
+                let y = column(name)?;
+                let points = rows
+                    .iter()
+                    .filter_map(|row| {
+                        Some([
+                            row[x].trim().parse::<f64>().ok()?,
+                            row[y].trim().parse::<f64>().ok()?,
+                        ])
+                    })
+                    .collect();
+                series.push(plot::Series {
+                    name: name.clone(),
+                    points,
+                });
             }
         } else {
-            tmp_image_path.to_string()
+            y_columns.clear();
+        }
+        if series.is_empty() {
+            return Err(McpError::invalid_params(
+                "Give series, or csv_path with x_column and y_columns",
+                None,
+            ));
+        }
+        let output_path = match &request.output_path {
+            Some(path) => confined_tmp_path("output_path", path)?,
+            None => ScratchDir::output_path("plot", "png"),
         };
-        let mut vkrunner_args = vec![shader_test_path];
 
-        if request.output_path.is_some() {
-            vkrunner_args.push("--image");
-            vkrunner_args.push(tmp_image_path);
-        }
+        let chart = plot::Chart {
+            title: request.title,
+            x_label: request.x_label.or(request.x_column),
+            y_label: request
+                .y_label
+                .or_else(|| (y_columns.len() == 1).then(|| y_columns[0].clone())),
+            style: request.style.unwrap_or_default(),
+            log_x: request.log_x.unwrap_or(false),
+            log_y: request.log_y.unwrap_or(false),
+            series,
+        };
+        let img = chart
+            .render(width, height)
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        img.save(&output_path).map_err(|e| {
+            McpError::internal_error(
+                "Failed to save the chart",
+                Some(json!({"error": e.to_string()})),
+            )
+        })?;
 
-        let vkrunner_output = Command::new("vkrunner")
-            .args(&vkrunner_args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .map_err(|e| {
+        let mut lines = vec![format!("Chart saved to: {output_path}")];
+        for series in &chart.series {
+            let ys = series
+                .points
+                .iter()
+                .map(|[_, y]| *y)
+                .filter(|y| y.is_finite())
+                .collect::<Vec<_>>();
+            let min = ys.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = ys.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            lines.push(if ys.is_empty() {
+                format!("- {}: no points", series.name)
+            } else {
+                format!(
+                    "- {}: {} points, y from {min} to {max}",
+                    series.name,
+                    series.points.len()
+                )
+            });
+        }
+        let image =
+            image_resource(&img, &output_path, request.return_image_max_dim).map_err(|e| {
                 McpError::internal_error(
-                    "Failed to run vkrunner",
+                    "Failed to embed the chart",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?;
+
+        Ok(CallToolResult::success(vec![
+            Content::text(lines.join("\n")),
+            image,
+        ]))
+    }
+
+    #[tool(
+        description = "Compare two compiled SPIR-V modules semantically instead of as text: added or removed capabilities and extensions, entry points and execution modes such as the local size, resources with their bindings and block layouts, changed constants and spec constant defaults, and the instructions added or removed in each function. Result ids are matched regardless of numbering, and debug info such as names and line numbers is ignored, so recompiling after an edit only shows what the edit changed."
+    )]
+    fn diff_spirv(
+        &self,
+        #[tool(aggr)] request: DiffSpirvRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let read = |path: &str| {
+            let path = if path.starts_with("spv-") {
+                artifact_path(path)
+            } else {
+                tmp_path(path)
+            };
+            let spvasm = std::fs::read_to_string(&path).map_err(|e| {
+                ErrorCode::BadRequestPath.invalid_params(
+                    format!("Failed to read SPIR-V file at {path}"),
                     Some(json!({"error": e.to_string()})),
                 )
             })?;
+            Ok::<_, McpError>((path, spirv::Module::parse(&spvasm)))
+        };
+        let (before_path, before) = read(&request.before_path)?;
+        let (after_path, after) = read(&request.after_path)?;
 
-        let stdout = String::from_utf8_lossy(&vkrunner_output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&vkrunner_output.stderr).to_string();
+        let sections = diff::compare(
+            &before,
+            &after,
+            request.max_instruction_changes.unwrap_or(40),
+        );
 
-        let mut result_message = if vkrunner_output.status.success() {
+        let mut lines = vec![
             format!(
-                "Shader compilation successful using shaderc-rs.\nVkRunner execution successful.\n\nOutput:\n{stdout}\n\n"
-            )
-        } else {
+                "Before: {before_path} ({} instructions)",
+                before.instructions.len()
+            ),
             format!(
-                "Shader compilation successful using shaderc-rs.\nVkRunner execution failed.\n\nOutput:\n{stdout}\n\nError:\n{stderr}\n\n",
+                "After: {after_path} ({} instructions)",
+                after.instructions.len()
+            ),
+        ];
+        if sections.is_empty() {
+            lines.push("The modules are equivalent up to id numbering and debug info".to_string());
+        }
+        for section in sections {
+            lines.push(String::new());
+            lines.push(format!("{}:", section.title));
+            lines.extend(section.changes);
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            lines.join("\n"),
+        )]))
+    }
+
+    #[tool(
+        description = "List the entry points of a compiled SPIR-V assembly file with their execution models. Any of them can be selected with the entrypoint field of the matching *Spirv pass."
+    )]
+    fn list_entrypoints(
+        &self,
+        #[tool(aggr)] request: ListEntrypointsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let path = tmp_path(&request.spvasm_path);
+
+        let spvasm = std::fs::read_to_string(&path).map_err(|e| {
+            ErrorCode::BadRequestPath.invalid_params(
+                format!("Failed to read SPIR-V file at {path}"),
+                Some(json!({"error": e.to_string()})),
             )
+        })?;
+
+        let entry_points = spirv::Module::parse(&spvasm).entry_points();
+
+        if entry_points.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "No entry points found in {path}"
+            ))]));
+        }
+
+        let listing = entry_points
+            .iter()
+            .map(|entry_point| format!("{} ({})", entry_point.name, entry_point.execution_model))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Entry points in {path}:\n{listing}"
+        ))]))
+    }
+}
+
+impl Default for ShadercVkrunnerMcp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const_string!(Echo = "echo");
+impl ServerHandler for ShadercVkrunnerMcp {
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let tools = Self::tool_box()
+            .list()
+            .into_iter()
+            .filter(|tool| self.options.tool_allowed(&tool.name))
+            .collect();
+
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools,
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let progress_token = self
+            .progress_tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&context.id);
+
+        if !self.options.tool_allowed(&request.name) {
+            let client = self
+                .options
+                .client_policy()
+                .map_or("without a known token".to_string(), |policy| {
+                    format!("{:?}", policy.name)
+                });
+            return Err(ErrorCode::NotAllowed.invalid_request(
+                format!("Client {client} is not allowed to call {}", request.name),
+                Some(json!({"tool": request.name})),
+            ));
+        }
+
+        let Some(_in_flight) = self.shutdown.enter() else {
+            return Err(ErrorCode::ShuttingDown.internal_error(
+                "The server is shutting down and accepts no new tool calls",
+                None,
+            ));
+        };
+
+        let server = Self {
+            progress_token,
+            ..self.clone()
         };
+        let context = ToolCallContext::new(&server, request, context);
+        Self::tool_box()
+            .call(context)
+            .await
+            .map_err(errors::with_code)
+    }
+
+    fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> impl Future<Output = Result<(), McpError>> + Send + '_ {
+        *self.log_level.lock().unwrap_or_else(|e| e.into_inner()) = Some(request.level);
+        std::future::ready(Ok(()))
+    }
 
-        if let Some(output_path) = &request.output_path {
-            if vkrunner_output.status.success() && Path::new(tmp_image_path).exists() {
-                match read_and_decode_ppm_file(tmp_image_path) {
-                    Ok(img) => {
-                        if let Some(parent) = Path::new(output_path).parent() {
-                            if !parent.as_os_str().is_empty() {
-                                std::fs::create_dir_all(parent).map_err(|e| {
-                                    McpError::internal_error(
-                                        "Failed to create output directory",
-                                        Some(json!({"error": e.to_string()})),
-                                    )
-                                })?;
-                            }
-                        }
+    async fn list_resources(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        Ok(ListResourcesResult {
+            next_cursor: None,
+            resources: self
+                .watch
+                .as_ref()
+                .map(|watch| watch.resources())
+                .unwrap_or_default(),
+        })
+    }
 
-                        img.save(output_path).map_err(|e| {
-                            McpError::internal_error(
-                                "Failed to save output image",
-                                Some(json!({"error": e.to_string()})),
-                            )
-                        })?;
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        let report = self
+            .watch
+            .as_ref()
+            .and_then(|watch| watch.read(&request.uri));
+        let Some(report) = report else {
+            return Err(McpError::resource_not_found(
+                format!("No resource {}", request.uri),
+                Some(json!({"uri": request.uri})),
+            ));
+        };
 
-                        result_message.push_str(&format!("Image saved to: {output_path}\n"));
-                    }
-                    Err(e) => {
-                        result_message.push_str(&format!("Failed to convert output image: {e}\n"));
-                    }
-                }
-            } else if vkrunner_output.status.success() {
-                result_message.push_str("No output image was generated by VkRunner.\n");
-            }
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::text(report, request.uri)],
+        })
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        if let Some(watch) = &self.watch {
+            watch.subscribe(request.uri, true);
         }
+        Ok(())
+    }
 
-        result_message.push_str("\nShader Test File Contents:\n");
-        result_message.push_str(
-            &std::fs::read_to_string(shader_test_path)
-                .unwrap_or_else(|_| "Failed to read shader test file".to_string()),
-        );
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        if let Some(watch) = &self.watch {
+            watch.subscribe(request.uri, false);
+        }
+        Ok(())
+    }
 
-        Ok(CallToolResult::success(vec![Content::text(result_message)]))
+    fn get_peer(&self) -> Option<Peer<RoleServer>> {
+        self.peer.clone()
     }
-}
 
-impl Default for ShadercVkrunnerMcp {
-    fn default() -> Self {
-        Self::new()
+    fn set_peer(&mut self, peer: Peer<RoleServer>) {
+        self.peer = Some(peer);
     }
-}
 
-const_string!(Echo = "echo");
-#[tool(tool_box)]
-impl ServerHandler for ShadercVkrunnerMcp {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::V_2024_11_05,
             capabilities: ServerCapabilities::builder()
                 .enable_tools()
+                .enable_logging()
+                .enable_resources()
+                .enable_resources_list_changed()
+                .enable_resources_subscribe()
                 .build(),
             server_info: Implementation::from_build_env(),
             instructions: Some("This server provides tools for compiling and running GLSL shaders using Vulkan infrastructure. The typical workflow is:
@@ -1297,14 +13191,98 @@ impl ServerHandler for ShadercVkrunnerMcp {
 struct Args {
     #[clap(short, long, value_parser)]
     work_dir: Option<PathBuf>,
+
+    /// Enumerate portability drivers such as MoltenVK (macOS)
+    #[clap(long)]
+    portability: bool,
+
+    /// Fail instead of falling back to a software driver when no device is found
+    #[clap(long)]
+    no_software_fallback: bool,
+
+    /// Default Vulkan driver manifest, or `lavapipe`/`swiftshader`
+    #[clap(long)]
+    icd: Option<String>,
+
+    /// Driver manifests a request may pick besides `--icd` and the
+    /// software drivers, separated by commas
+    #[clap(long, value_delimiter = ',')]
+    allowed_icds: Vec<String>,
+
+    /// 1-based index of the Vulkan device to run on when a request doesn't pick one
+    #[clap(long)]
+    device_id: Option<u32>,
+
+    /// vkrunner executable to run (default: `vkrunner` on PATH)
+    #[clap(long)]
+    vkrunner_path: Option<PathBuf>,
+
+    /// Directory to store compiled and uploaded artifacts in (default /tmp/artifacts)
+    #[clap(long)]
+    artifact_dir: Option<PathBuf>,
+
+    /// Number of requests allowed to run on the GPU at once; others wait in order (default 1)
+    #[clap(long)]
+    max_concurrent_runs: Option<usize>,
+
+    /// Seconds to let in-flight tool calls finish after SIGTERM/SIGINT (default 30)
+    #[clap(long)]
+    shutdown_grace_secs: Option<u64>,
+
+    /// Seconds an idle vkrunner process keeps its Vulkan device for the next run (default 60); 0 starts a process per run
+    #[clap(long)]
+    vkrunner_idle_timeout_secs: Option<u64>,
+
+    /// Largest number of invocations one compute dispatch may run; larger dispatches are split when the request opts in, otherwise refused
+    #[clap(long)]
+    max_dispatch_invocations: Option<u64>,
+
+    /// GPU watchdog timeout in milliseconds, overriding the one detected from Windows TDR or amdgpu settings; 0 turns watchdog handling off
+    #[clap(long)]
+    watchdog_timeout_ms: Option<u64>,
+
+    /// Directory of suite files and GLSL sources to rerun tests from on every change, publishing results as resources
+    #[clap(long)]
+    watch: Option<PathBuf>,
+
+    /// TOML file with server settings; command line flags override it
+    #[clap(long)]
+    config: Option<PathBuf>,
+}
+
+/// Resolves with the name of the first SIGINT or SIGTERM received.
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            return tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = terminate.recv() => "SIGTERM",
+            };
+        }
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+    "SIGINT"
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let mut options = match args.config.take() {
+        Some(path) => ServerOptions::load(&path).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }),
+        None => ServerOptions::default(),
+    }
+    .override_with(args);
+    options.client_token = std::env::var(CLIENT_TOKEN_ENV).ok();
 
-    if let Some(work_dir) = args.work_dir {
-        std::env::set_current_dir(&work_dir)
+    if let Some(work_dir) = &options.work_dir {
+        std::env::set_current_dir(work_dir)
             .map_err(|e| {
                 eprintln!("Failed to set working directory to {work_dir:?}: {e}");
                 std::process::exit(1);
@@ -1312,6 +13290,15 @@ async fn main() -> Result<()> {
             .unwrap();
     }
 
+    if let Some(dir) = &options.artifact_dir {
+        let _ = ARTIFACT_DIR.set(dir.clone());
+    }
+
+    if let Some(dir) = options.watch.as_ref().filter(|dir| !dir.is_dir()) {
+        eprintln!("Watch directory {dir:?} does not exist");
+        std::process::exit(1);
+    }
+
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive(tracing::Level::DEBUG.into()))
         .with_writer(std::io::stderr)
@@ -1320,13 +13307,1157 @@ async fn main() -> Result<()> {
 
     tracing::info!("Starting MCP server");
 
-    let service = ShadercVkrunnerMcp::new()
-        .serve(stdio())
+    let server = ShadercVkrunnerMcp::with_options(options.clone());
+    let shutdown = server.shutdown.clone();
+    let watcher = server.clone();
+    let stdin = stdin_recording_progress(server.progress_tokens.clone());
+    let service = server
+        .serve((stdin, tokio::io::stdout()))
         .await
         .inspect_err(|e| {
             tracing::error!("serving error: {:?}", e);
         })?;
+    if let Some(state) = watcher.watch.clone() {
+        tracing::info!("Watching {}", state.dir.display());
+        watch::spawn(watcher, state, service.peer().clone());
+    }
 
-    service.waiting().await?;
+    let waiting = service.waiting();
+    tokio::pin!(waiting);
+    let signal = tokio::select! {
+        result = &mut waiting => {
+            result?;
+            return Ok(());
+        }
+        signal = shutdown_signal() => signal,
+    };
+
+    // Keep serving while draining so in-flight calls still get their results
+    tracing::info!("Received {signal}, finishing in-flight tool calls");
+    let grace = Duration::from_secs(
+        options
+            .shutdown_grace_secs
+            .unwrap_or(DEFAULT_SHUTDOWN_GRACE_SECS),
+    );
+    tokio::select! {
+        result = &mut waiting => {
+            result?;
+        }
+        drained = shutdown.drain(grace) => {
+            if !drained {
+                tracing::warn!("Tool calls still running after {grace:?}, exiting anyway");
+            }
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_icd() {
+        let manifest = format!("/tmp/test_request_icd_{}.json", std::process::id());
+        std::fs::write(&manifest, "{}").unwrap();
+        let mut options = ServerOptions::default();
+
+        let error = options.request_icd(&manifest).unwrap_err();
+        assert!(error.message.contains("is not one the server allows"));
+
+        options.allowed_icds = vec![manifest.clone()];
+        assert_eq!(
+            options.request_icd(&manifest).unwrap(),
+            Path::new(&manifest)
+        );
+        // Only the listed spelling is accepted
+        let dotted = manifest.replace("/tmp/", "/tmp/./");
+        assert!(options.request_icd(&dotted).is_err());
+
+        // The server's own driver may always be picked
+        let options = ServerOptions {
+            icd: Some(manifest.clone()),
+            ..Default::default()
+        };
+        assert!(options.request_icd(&manifest).is_ok());
+        let _ = std::fs::remove_file(&manifest);
+    }
+
+    #[test]
+    fn test_confined_tmp_path() {
+        assert_eq!(
+            confined_tmp_path("buffer_dump path", "dump.bin").unwrap(),
+            "/tmp/dump.bin"
+        );
+        assert_eq!(
+            confined_tmp_path("buffer_dump path", "/tmp/runs/dump.bin").unwrap(),
+            "/tmp/runs/dump.bin"
+        );
+        for path in [
+            "../etc/passwd",
+            "/tmp/runs/../../etc/passwd",
+            "/tmpfile.bin",
+        ] {
+            let error = confined_tmp_path("buffer_dump path", path).unwrap_err();
+            assert!(error.message.contains("is outside /tmp"), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        // FIPS 180-4 examples
+        assert_eq!(
+            sha256_hex(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256_hex("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256_hex("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha256_hex("a".repeat(1_000_000)),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+
+        assert_eq!(
+            artifact_id("abc"),
+            "spv-ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        // RFC 4648 section 10
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (decoded, encoded) in vectors {
+            assert_eq!(BASE64_STANDARD.encode(decoded), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), decoded.as_bytes());
+        }
+
+        // Every byte value, with each padding length
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        for length in 253..=256 {
+            let encoded = BASE64_STANDARD.encode(&bytes[..length]);
+            assert_eq!(encoded.len(), length.div_ceil(3) * 4);
+            assert_eq!(base64_decode(&encoded).unwrap(), &bytes[..length]);
+        }
+
+        // Unpadded, URL-safe and wrapped input
+        assert_eq!(base64_decode("Zm9vYg").unwrap(), b"foob");
+        assert_eq!(base64_decode("-_-_").unwrap(), [0xfb, 0xff, 0xbf]);
+        assert_eq!(base64_decode("Zm9v\r\nYmFy").unwrap(), b"foobar");
+
+        assert!(base64_decode("Zm9vY").is_err());
+        assert!(base64_decode("Zm9*").is_err());
+    }
+
+    #[test]
+    fn test_run_cache_key() {
+        let shader = format!("/tmp/test_run_cache_key_{}.spvasm", std::process::id());
+        std::fs::write(&shader, "; first").unwrap();
+        let request = |extra: serde_json::Value| {
+            let mut value = json!({
+                "requests": [],
+                "passes": [{"CompSpirv": {"comp_spvasm_path": shader}}],
+                "tests": [{"Compute": {"x": 1, "y": 1, "z": 1}}],
+            });
+            value
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::from_value::<CompileRunShadersRequest>(value).unwrap()
+        };
+        let key =
+            |request: &CompileRunShadersRequest| request.run_cache_key(&[], None, false).unwrap();
+
+        let base = key(&request(json!({})));
+        assert_eq!(base.len(), 64);
+        // The result names the saved image and embeds it
+        let saved = key(&request(json!({"output_path": "/tmp/a.png"})));
+        assert_ne!(saved, base);
+        assert_ne!(key(&request(json!({"output_path": "/tmp/b.png"}))), saved);
+        assert_ne!(key(&request(json!({"label": "a"}))), base);
+        assert_ne!(key(&request(json!({"return_image_max_dim": 64}))), base);
+
+        assert_ne!(key(&request(json!({"seed": 2}))), base);
+        assert_ne!(
+            request(json!({})).run_cache_key(&[], None, true).unwrap(),
+            base
+        );
+        assert_ne!(
+            request(json!({}))
+                .run_cache_key(&[], Some(Path::new("/tmp/icd.json")), false)
+                .unwrap(),
+            base
+        );
+
+        // A shader changed on disk under the same path
+        std::fs::write(&shader, "; second").unwrap();
+        assert_ne!(key(&request(json!({}))), base);
+        let _ = std::fs::remove_file(&shader);
+    }
+
+    #[test]
+    fn test_tool_allowed() {
+        let mut options = ServerOptions {
+            denied_tools: vec!["upload_texture".to_string()],
+            ..Default::default()
+        };
+        assert!(options.tool_allowed("compile_run_shaders"));
+        assert!(!options.tool_allowed("upload_texture"));
+
+        let policy = |token: &str| ClientPolicy {
+            name: token.to_string(),
+            token_sha256: sha256_hex(token),
+            ..Default::default()
+        };
+        options.clients = vec![
+            ClientPolicy {
+                allowed_tools: Some(vec!["list_entrypoints".to_string()]),
+                ..policy("restricted")
+            },
+            ClientPolicy {
+                // Digests in the file may be upper case
+                token_sha256: sha256_hex("trusted").to_uppercase(),
+                allowed_tools: Some(vec!["upload_texture".to_string()]),
+                denied_tools: vec!["get_config".to_string()],
+                ..policy("trusted")
+            },
+            ClientPolicy {
+                denied_tools: vec!["get_config".to_string()],
+                ..policy("open")
+            },
+        ];
+        let serving = |token: &str| ServerOptions {
+            client_token: Some(token.to_string()),
+            ..options.clone()
+        };
+        assert!(serving("restricted").tool_allowed("list_entrypoints"));
+        assert!(!serving("restricted").tool_allowed("compile_run_shaders"));
+        assert!(serving("trusted").tool_allowed("upload_texture"));
+        assert!(!serving("trusted").tool_allowed("get_config"));
+        assert!(serving("open").tool_allowed("compile_run_shaders"));
+        assert!(!serving("open").tool_allowed("get_config"));
+        assert!(!serving("open").tool_allowed("upload_texture"));
+
+        // Clients without a known token fail closed
+        assert!(!serving("unknown").tool_allowed("compile_run_shaders"));
+        assert!(!options.tool_allowed("list_entrypoints"));
+    }
+
+    #[test]
+    fn test_call_progress_token() {
+        let call = r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"_meta":{"progressToken":"run-1"},"name":"get_config","arguments":{}}}"#;
+        assert_eq!(
+            call_progress_token(call),
+            Some((
+                NumberOrString::Number(7),
+                NumberOrString::String("run-1".into())
+            ))
+        );
+
+        let without =
+            r#"{"jsonrpc":"2.0","id":8,"method":"tools/call","params":{"name":"get_config"}}"#;
+        assert_eq!(call_progress_token(without), None);
+        let list = r#"{"jsonrpc":"2.0","id":9,"method":"tools/list","params":{"_meta":{"progressToken":1}}}"#;
+        assert_eq!(call_progress_token(list), None);
+        assert_eq!(call_progress_token("not json"), None);
+    }
+
+    #[test]
+    fn test_portability_gaps() {
+        let scratch = ScratchDir::new().unwrap();
+        let request = serde_json::from_value::<CompileRunShadersRequest>(json!({
+            "requests": [],
+            "passes": [],
+            "tests": [],
+            "requirements": ["WideLines"],
+        }))
+        .unwrap();
+        let inferred = ["shaderFloat64", "depthstencil D32_SFLOAT"].map(str::to_string);
+        let script = request
+            .write_shader_test(
+                &scratch.path("test.shader_test"),
+                &[],
+                inferred.to_vec(),
+                None,
+                &scratch,
+            )
+            .unwrap();
+
+        assert_eq!(
+            script.portability_gaps(),
+            [
+                "wideLines: Metal only rasterizes lines 1 pixel wide",
+                "shaderFloat64: Metal has no 64-bit float support",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_path_confined() {
+        let server = ShadercVkrunnerMcp::new();
+        let outside = "/tmp/../home/results.csv";
+        let refused = |error: McpError| {
+            assert!(
+                error
+                    .message
+                    .contains("export_path /tmp/../home/results.csv is outside /tmp"),
+                "{}",
+                error.message
+            );
+        };
+
+        refused(
+            server
+                .run_suite(RunSuiteRequest {
+                    path: "suite.yaml".to_string(),
+                    tags: None,
+                    exclude_tags: None,
+                    fail_fast: None,
+                    export_path: Some(outside.to_string()),
+                })
+                .unwrap_err(),
+        );
+
+        let request = serde_json::from_value::<CompileRunShadersRequest>(json!({
+            "requests": [],
+            "passes": [],
+            "tests": [],
+            "scenes": [{"name": "a", "passes": [], "tests": []}],
+            "export_path": outside,
+        }))
+        .unwrap();
+        refused(server.compile_run_shaders(request).unwrap_err());
+    }
+
+    #[test]
+    fn test_write_script_values() {
+        let write = |data_type, values: &[&str]| {
+            let values = values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            let mut line = b"push".to_vec();
+            write_script_values(&mut line, data_type, &values)
+                .map(|()| String::from_utf8(line).unwrap())
+        };
+        assert_eq!(
+            write(DataType::Bvec2, &["true false"]).unwrap(),
+            "push 1 0\n"
+        );
+        assert_eq!(
+            write(DataType::Uint, &["1:4|3:4", "7"]).unwrap(),
+            "push 0x31 7\n"
+        );
+        assert_eq!(write(DataType::Float, &[]).unwrap(), "push\n");
+
+        // A value that can't be converted is an error, not written as is
+        let error = write(DataType::Uint, &["16:4"]).unwrap_err();
+        assert!(
+            error.message.contains("does not fit in 4 bits"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn test_glsl_local_size() {
+        assert_eq!(
+            glsl_local_size("layout(local_size_x = 64, local_size_y=0x2u) in;"),
+            [Some(64), Some(2), None]
+        );
+        assert_eq!(
+            glsl_local_size(
+                "// layout(local_size_x = 1) in;\n/* local_size_y = 3 */\nlayout(local_size_x_id = 0, local_size_z = 4) in;"
+            ),
+            [None, None, Some(4)]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_budget_local_size() {
+        let spvasm = format!("/tmp/test_dispatch_budget_{}.spvasm", std::process::id());
+        std::fs::write(
+            &spvasm,
+            "OpEntryPoint GLCompute %main \"main\"\nOpExecutionMode %main LocalSize 16 4 1\n",
+        )
+        .unwrap();
+        let request = |passes: serde_json::Value| {
+            serde_json::from_value::<CompileRunShadersRequest>(json!({
+                "requests": [],
+                "passes": passes,
+                "tests": [
+                    {"Compute": {"x": 4, "y": 2, "z": 1}},
+                    {"Compute": {"x": 1, "y": 1, "z": 1}},
+                ],
+            }))
+            .unwrap()
+        };
+        let invocations = |request: &CompileRunShadersRequest| {
+            let local_size = request.compute_local_size(&[]).unwrap();
+            request
+                .dispatch_invocations(local_size)
+                .into_iter()
+                .map(|(_, _, invocations)| invocations)
+                .collect::<Vec<_>>()
+        };
+
+        // GLSL sections are linked, so the size may be declared in any
+        let glsl = request(json!([
+            {"CompGlsl": {"source": "#version 450\nlayout(local_size_x = 64) in;\n"}},
+            {"CompGlsl": {"source": "layout(local_size_y = 2) in;\nvoid main() {}\n"}},
+        ]));
+        assert_eq!(invocations(&glsl), [4 * 2 * 64 * 2, 64 * 2]);
+        let error = glsl
+            .dispatch_chunk_workgroups([64, 2, 1], Some(512))
+            .unwrap_err();
+        assert!(
+            error.message.contains("runs 1024 invocations"),
+            "{}",
+            error.message
+        );
+
+        let spirv = request(json!([{"CompSpirv": {"comp_spvasm_path": spvasm}}]));
+        assert_eq!(invocations(&spirv), [4 * 2 * 64, 64]);
+        let _ = std::fs::remove_file(&spvasm);
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let base = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+
+        // Two hunks, the second one's new-side lines offset by the first
+        let patch = "--- a/shader.glsl\n+++ b/shader.glsl\n\
+                     @@ -1,2 +1,3 @@\n one\n+one and a half\n two\n\
+                     @@ -5,3 +6,2 @@\n five\n-six\n seven\n";
+        assert_eq!(
+            apply_patch(base, patch).unwrap(),
+            "one\none and a half\ntwo\nthree\nfour\nfive\nseven\n"
+        );
+
+        // A hunk placed where its context isn't is refused, not moved
+        let misplaced = "@@ -2,2 +2,2 @@\n three\n-four\n+4\n";
+        let error = apply_patch(base, misplaced).unwrap_err();
+        assert!(
+            error.contains("line 2 of the base source is \"two\""),
+            "{error}"
+        );
+
+        // Context that doesn't match the base
+        let mismatch = "@@ -3,2 +3,2 @@\n three\n-for\n+4\n";
+        let error = apply_patch(base, mismatch).unwrap_err();
+        assert!(error.contains("does not apply"), "{error}");
+
+        // Hunks out of order
+        let unordered = "@@ -5,1 +5,1 @@\n-five\n+5\n@@ -1,1 +1,1 @@\n-one\n+1\n";
+        assert!(apply_patch(base, unordered).is_err());
+
+        // Dropping and adding the newline at the end of the file
+        let drop_newline = "@@ -7 +7 @@\n-seven\n+seven\n\\ No newline at end of file\n";
+        assert_eq!(
+            apply_patch(base, drop_newline).unwrap(),
+            "one\ntwo\nthree\nfour\nfive\nsix\nseven"
+        );
+        let unterminated = base.trim_end();
+        let add_newline = "@@ -7 +7 @@\n-seven\n\\ No newline at end of file\n+seven\n";
+        assert_eq!(apply_patch(unterminated, add_newline).unwrap(), base);
+        let keep_missing = "@@ -6,2 +6,2 @@\n-six\n+6\n seven\n\\ No newline at end of file\n";
+        assert_eq!(
+            apply_patch(unterminated, keep_missing).unwrap(),
+            "one\ntwo\nthree\nfour\nfive\n6\nseven"
+        );
+
+        assert!(apply_patch(base, "no hunks here").is_err());
+        assert!(apply_patch(base, "@@ -1,2 +1,2 @@\n one\n").is_err());
+    }
+
+    #[test]
+    fn test_vkrunner_options_args() {
+        assert!(VkrunnerOptions::default().args(None).is_empty());
+
+        let options: VkrunnerOptions = serde_json::from_value(json!({
+            "quiet": true,
+            "show_disassembly": false,
+            "separate_shader_objects": true,
+            "replacements": [{"token": "N", "replacement": "64"}],
+            "buffer_dump": {"binding": 2, "path": "out.bin"},
+        }))
+        .unwrap();
+        assert_eq!(
+            options.args(Some("/tmp/out.bin")),
+            [
+                "--quiet",
+                "--separate-shader-objects",
+                "--replace=N=64",
+                "--buffer=/tmp/out.bin",
+                "--binding=2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_header() {
+        let header =
+            |json: serde_json::Value| serde_json::from_value::<SourceHeader>(json).unwrap();
+        let apply = |header: &SourceHeader, source: &str| {
+            join_lines(&header.apply(tag_lines(source, LineOrigin::Source)))
+        };
+
+        let full = header(json!({
+            "version": "460",
+            "extensions": ["GL_EXT_nonuniform_qualifier"],
+            "default_precision": true,
+        }));
+        assert_eq!(
+            apply(&full, "void main() {}"),
+            "#version 460\n#extension GL_EXT_nonuniform_qualifier : require\nprecision highp float;\nprecision highp int;\nvoid main() {}"
+        );
+
+        // Whatever the source already has is neither repeated nor moved
+        let complete = "#version 450\n#extension GL_EXT_nonuniform_qualifier: enable\nprecision mediump float;\nvoid main() {}";
+        assert_eq!(apply(&full, complete), complete);
+
+        let extensions = header(json!({"extensions": ["GL_EXT_shader_16bit_storage"]}));
+        assert_eq!(
+            apply(&extensions, "#version 450\nvoid main() {}"),
+            "#version 450\n#extension GL_EXT_shader_16bit_storage : require\nvoid main() {}"
+        );
+        assert_eq!(
+            apply(&header(json!({})), "void main() {}"),
+            "#version 450\nvoid main() {}"
+        );
+    }
+
+    #[test]
+    fn test_data_type() {
+        let push = |data_type: &str| {
+            serde_json::from_value::<ShaderRunnerTest>(json!({
+                "Push": {"data_type": data_type, "offset": 0, "values": ["1"]}
+            }))
+        };
+        assert!(matches!(
+            push("f16vec2").unwrap(),
+            ShaderRunnerTest::Push {
+                data_type: DataType::F16vec2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            push("dmat3x4").unwrap(),
+            ShaderRunnerTest::Push {
+                data_type: DataType::Dmat3x4,
+                ..
+            }
+        ));
+
+        // A typo fails when the arguments are parsed, before any run
+        let error = push("vec_4").unwrap_err().to_string();
+        assert!(error.contains("unknown variant `vec_4`"), "{error}");
+
+        let schema = serde_json::to_string(&schemars::schema_for!(DataType)).unwrap();
+        assert!(schema.contains("\"u16vec3\"") && schema.contains("\"mat4x2\""));
+        assert_eq!(DataType::Mat2x3.to_string(), "mat2x3");
+    }
+
+    #[test]
+    fn test_validate_values() {
+        let server = ShadercVkrunnerMcp::new();
+        let refusal = |vertex_data: serde_json::Value, tests: serde_json::Value| {
+            let request = serde_json::from_value::<CompileRunShadersRequest>(json!({
+                "requests": [],
+                "passes": [],
+                "vertex_data": vertex_data,
+                "tests": tests,
+            }))
+            .unwrap();
+            server
+                .compile_run_shaders(request)
+                .unwrap_err()
+                .message
+                .to_string()
+        };
+
+        assert_eq!(
+            refusal(
+                json!(null),
+                json!([{"Push": {"data_type": "vec4", "offset": 16, "values": ["1.0 2.5", "-3e2 abc"]}}])
+            ),
+            "value 3 of push vec4 at offset 16 is not a float: \"abc\""
+        );
+        assert_eq!(
+            refusal(
+                json!(null),
+                json!([{"SSBOSubData": {"binding": 1, "data_type": "uint", "offset": 0, "values": ["1 -2"]}}])
+            ),
+            "value 1 of ssbo 1 subdata uint at offset 0 is not an unsigned integer: \"-2\""
+        );
+        assert_eq!(
+            refusal(
+                json!(null),
+                json!([{"Probe": {"probe_type": "all", "format": "rgba", "args": ["1 0 O 1"]}}])
+            ),
+            "argument 2 of probe all rgba is not a number: \"O\""
+        );
+        assert_eq!(
+            refusal(
+                json!([
+                    {"AttributeFormat": {"location": 0, "format": "A8B8G8R8_UNORM_PACK32"}},
+                    {"Hex": {"value": "ff0000ff"}},
+                ]),
+                json!([])
+            ),
+            "vertex data row 1: \"ff0000ff\" is not a 0x-prefixed hex number"
+        );
+    }
+
+    #[test]
+    fn test_validate_vertex_data() {
+        let request = |vertex_data: serde_json::Value| {
+            serde_json::from_value::<CompileRunShadersRequest>(json!({
+                "requests": [],
+                "passes": [],
+                "vertex_data": vertex_data,
+                "tests": [],
+            }))
+            .unwrap()
+        };
+        let server = ShadercVkrunnerMcp::new();
+        let refusal = |vertex_data: serde_json::Value| {
+            server
+                .compile_run_shaders(request(vertex_data))
+                .unwrap_err()
+                .message
+                .to_string()
+        };
+
+        assert!(
+            request(json!([
+                {"AttributeFormat": {"location": 0, "format": "R32G32_SFLOAT"}},
+                {"AttributeFormat": {"location": 1, "format": "R8G8B8A8_UINT"}},
+                {"GenericComponents": {"components": ["-1 1", "255 0 0 255"]}},
+            ]))
+            .validate_vertex_data()
+            .is_ok()
+        );
+        assert_eq!(
+            refusal(json!([{"Vec2": {"x": 0.0, "y": 1.0}}])),
+            "vertex data must start with AttributeFormat entries"
+        );
+        assert_eq!(
+            refusal(json!([
+                {"AttributeFormat": {"location": 0, "format": "R32G32B32_SFLOAT"}},
+                {"Vec2": {"x": 0.0, "y": 1.0}},
+            ])),
+            "vertex data row 1 has 2 values but the attribute formats declare 3"
+        );
+        assert_eq!(
+            refusal(json!([
+                {"AttributeFormat": {"location": 0, "format": "R8G8B8A8_UINT"}},
+                {"GenericComponents": {"components": ["1 2 -3 4"]}},
+            ])),
+            "vertex data row 1 value 2 is not an unsigned integer"
+        );
+        assert_eq!(
+            refusal(json!([
+                {"AttributeFormat": {"location": 0, "format": "R32G32_SFLOAT"}},
+                {"Vec2": {"x": 0.0, "y": 1.0}},
+                {"AttributeFormat": {"location": 1, "format": "R32_SFLOAT"}},
+            ])),
+            "vertex data row 2: AttributeFormat must appear before all data rows"
+        );
+    }
+
+    #[test]
+    fn test_validate_pipeline() {
+        let server = ShadercVkrunnerMcp::new();
+        let refusal =
+            |requests: serde_json::Value, passes: serde_json::Value, tests: serde_json::Value| {
+                let request = serde_json::from_value::<CompileRunShadersRequest>(json!({
+                    "requests": requests,
+                    "passes": passes,
+                    "tests": tests,
+                }))
+                .unwrap();
+                server
+                    .compile_run_shaders(request)
+                    .unwrap_err()
+                    .message
+                    .to_string()
+            };
+        let frag = json!([{
+            "stage": "Frag",
+            "source": "void main() {}",
+            "tmp_output_path": "/tmp/test_validate_pipeline.spvasm",
+        }]);
+        let draw = json!([{"DrawRect": {"x": -1.0, "y": -1.0, "width": 2.0, "height": 2.0}}]);
+
+        assert_eq!(
+            refusal(
+                frag.clone(),
+                json!([{"VertSpirv": {"vert_spvasm_path": "/tmp/test_validate_pipeline.spvasm"}}]),
+                draw.clone()
+            ),
+            "/tmp/test_validate_pipeline.spvasm was compiled as a Fragment shader but is used by a Vertex pass"
+        );
+        assert_eq!(
+            refusal(
+                frag.clone(),
+                json!([
+                    "VertPassthrough",
+                    {"FragSpirv": {"frag_spvasm_path": "/tmp/test_validate_pipeline.spvasm"}},
+                    {"FragSpirv": {"frag_spvasm_path": "/tmp/test_validate_pipeline.spvasm"}},
+                ]),
+                draw.clone()
+            ),
+            "/tmp/test_validate_pipeline.spvasm is referenced by more than one pass"
+        );
+        assert_eq!(
+            refusal(frag, json!(["VertPassthrough"]), draw.clone()),
+            "/tmp/test_validate_pipeline.spvasm is compiled but no pass references it"
+        );
+        assert_eq!(
+            refusal(
+                json!([]),
+                json!([{"FragGlsl": {"source": "void main() {}"}}]),
+                draw
+            ),
+            "draw commands need a vertex stage: add a VertPassthrough, VertSpirv or VertGlsl pass"
+        );
+    }
+
+    #[test]
+    fn test_ordering_lint() {
+        let request = |ordering_lint: &str| {
+            serde_json::from_value::<CompileRunShadersRequest>(json!({
+                "requests": [],
+                "passes": ["VertPassthrough", {"FragGlsl": {"source": "void main() {}"}}],
+                "tests": [
+                    {"Probe": {"probe_type": "all", "format": "rgba", "args": ["1 0 0 1"]}},
+                    {"DrawRect": {"x": -1.0, "y": -1.0, "width": 2.0, "height": 2.0}},
+                    {"FragmentEntrypoint": {"name": "main"}},
+                    {"Compute": {"x": 1, "y": 1, "z": 1}},
+                ],
+                "ordering_lint": ordering_lint,
+            }))
+            .unwrap()
+        };
+        let issues = [
+            "test 0: probe comes before any draw or compute command",
+            "test 2: entrypoint command has no effect because no draw command follows it",
+            "test 3: Compute is used but no compute shader pass is configured",
+        ];
+        assert_eq!(request("Warn").ordering_issues(), issues);
+
+        let error = ShadercVkrunnerMcp::new()
+            .compile_run_shaders(request("Fail"))
+            .unwrap_err();
+        assert_eq!(error.message, "Test commands are out of order");
+        assert_eq!(error.data.unwrap()["issues"], json!(issues));
+    }
+
+    #[test]
+    fn test_expected_image() {
+        let expected = |pattern: serde_json::Value| {
+            serde_json::from_value::<ExpectedImage>(json!({"pattern": pattern})).unwrap()
+        };
+        let img = RgbImage::from_fn(4, 2, |x, _| image::Rgb([x as u8 * 85, 0, 0]));
+        let deviations = |pattern: serde_json::Value| {
+            expected(pattern)
+                .deviations(&img, ColorSpace::Linear, ColorSpace::Linear)
+                .unwrap()
+                .into_iter()
+                .map(|deviation| (deviation * 1000.0).round() / 1000.0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            deviations(json!({"Constant": {"color": [0.0, 0.0, 0.0]}})),
+            [0.0, 0.333, 0.667, 1.0, 0.0, 0.333, 0.667, 1.0]
+        );
+        // The gradient is sampled at pixel centers
+        assert_eq!(
+            deviations(json!({"Gradient": {"from": [0.0, 0.0, 0.0], "to": [1.0, 0.0, 0.0]}})),
+            [0.125, 0.042, 0.042, 0.125, 0.125, 0.042, 0.042, 0.125]
+        );
+
+        // Within tolerance is black, the largest deviation red
+        let heatmap = deviation_heatmap(&[0.0, 0.005, 0.5, 1.0], 2, 0.01);
+        assert_eq!(heatmap.dimensions(), (2, 2));
+        assert_eq!(heatmap.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(heatmap.get_pixel(1, 0).0, [0, 0, 0]);
+        assert_eq!(heatmap.get_pixel(0, 1).0, [124, 255, 0]);
+        assert_eq!(heatmap.get_pixel(1, 1).0, [255, 0, 0]);
+
+        let request = serde_json::from_value::<CompileRunShadersRequest>(json!({
+            "requests": [],
+            "passes": [],
+            "tests": [],
+            "expected_image": {"pattern": {"Constant": {"color": [0.0, 0.0, 0.0]}}},
+        }))
+        .unwrap();
+        let error = ShadercVkrunnerMcp::new()
+            .compile_run_shaders(request)
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "expected_image needs an output_path so that an image is captured"
+        );
+    }
+
+    #[test]
+    fn test_texture_sources() {
+        let texels = |source: serde_json::Value, width, height| {
+            serde_json::from_value::<TextureSource>(source)
+                .unwrap()
+                .texels(width, height, 0, 0)
+                .unwrap()
+        };
+
+        assert_eq!(
+            texels(json!("UvGradient"), 2, 2),
+            [
+                64, 64, 0, 255, 191, 64, 0, 255, 64, 191, 0, 255, 191, 191, 0, 255
+            ]
+        );
+        assert_eq!(
+            texels(json!({"Checkerboard": {"size": 1}}), 2, 1),
+            [255, 255, 255, 255, 0, 0, 0, 255]
+        );
+        let bars = texels(json!("ColorBars"), 7, 1);
+        assert_eq!(bars[..4], [191, 191, 191, 255]);
+        assert_eq!(bars[24..], [0, 0, 191, 255]);
+
+        // Noise is reproducible from its seed and opaque
+        let noise = texels(json!({"WhiteNoise": {"seed": 7}}), 4, 4);
+        assert_eq!(noise, texels(json!({"WhiteNoise": {"seed": 7}}), 4, 4));
+        assert_ne!(noise, texels(json!({"WhiteNoise": {"seed": 8}}), 4, 4));
+        assert!(noise.chunks(4).all(|texel| texel[3] == 255));
+        let perlin = texels(json!({"Perlin": {"seed": 7, "octaves": 3}}), 8, 8);
+        assert!(
+            perlin
+                .chunks(4)
+                .all(|texel| texel[0] == texel[1] && texel[1] == texel[2])
+        );
+
+        let request = serde_json::from_value::<CompileRunShadersRequest>(json!({
+            "requests": [],
+            "passes": [],
+            "tests": [{"Texture": {"binding": 0, "width": 0, "height": 4, "source": "UvGradient"}}],
+        }))
+        .unwrap();
+        let error = ShadercVkrunnerMcp::new()
+            .compile_run_shaders(request)
+            .unwrap_err();
+        assert!(
+            error.message.contains("0x4 is not between 1x1"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn test_map_diagnostics() {
+        let origins = [
+            LineOrigin::Injected,
+            LineOrigin::Source(1),
+            LineOrigin::Library(0, 3),
+        ];
+        assert_eq!(
+            map_diagnostics(
+                "shader.glsl:1: error: a\nshader.glsl:2: warning: b\nshader.glsl:3: error: c\nshader.glsl:9: error: d\n1 error generated.",
+                "shader.glsl",
+                &origins
+            ),
+            "shader.glsl:1 (added by the server): error: a\nshader.glsl:1: warning: b\nlibrary 0:3: error: c\nshader.glsl:9: error: d\n1 error generated."
+        );
+
+        // The injected #version line shifts what the compiler reports
+        let request = serde_json::from_value::<CompileRequest>(json!({
+            "stage": "Frag",
+            "source": "layout(location = 0) out vec4 color;\n\nvoid broken() {\n    undeclared = 1;\n}\n",
+            "header": {"version": "450"},
+        }))
+        .unwrap();
+        let report = request.compile(&[]).unwrap().unwrap_err();
+        assert!(report.contains("shader.glsl:4: error"), "{report}");
+    }
+
+    #[test]
+    fn test_packed_hex_values() {
+        let pack = |value, packing, byte_order| packed_hex_values(value, packing, byte_order);
+
+        assert_eq!(
+            pack("0xF800", HexPacking::R5G6B5, ByteOrder::BigEndian).unwrap(),
+            ["0xf800"]
+        );
+        assert_eq!(
+            pack("0x00F8", HexPacking::R5G6B5, ByteOrder::LittleEndian).unwrap(),
+            ["0xf800"]
+        );
+        assert_eq!(
+            pack("0x40003c00", HexPacking::HalfPair, ByteOrder::BigEndian).unwrap(),
+            ["0x3c00", "0x4000"]
+        );
+        assert_eq!(
+            pack("0x003c0040", HexPacking::HalfPair, ByteOrder::LittleEndian).unwrap(),
+            ["0x3c00", "0x4000"]
+        );
+        assert_eq!(
+            pack("0x3", HexPacking::A2B10G10R10, ByteOrder::BigEndian).unwrap(),
+            ["0x00000003"]
+        );
+        assert!(pack("0x12345", HexPacking::R5G6B5, ByteOrder::BigEndian).is_err());
+        // Every byte has to be given when they are reversed
+        assert!(pack("0xF8", HexPacking::R5G6B5, ByteOrder::LittleEndian).is_err());
+
+        let request = serde_json::from_value::<CompileRunShadersRequest>(json!({
+            "requests": [],
+            "passes": [],
+            "vertex_data": [
+                {"AttributeFormat": {"location": 0, "format": "R8G8B8A8_UNORM"}},
+                {"Hex": {"value": "0xf800", "packing": "R5G6B5"}},
+            ],
+            "tests": [],
+        }))
+        .unwrap();
+        let error = ShadercVkrunnerMcp::new()
+            .compile_run_shaders(request)
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "vertex data row 1 is packed as R5G6B5, which needs the R5G6B5_UNORM_PACK16 attribute format, not R8G8B8A8_UNORM"
+        );
+    }
+
+    #[test]
+    fn test_double_data() {
+        let request = |vertex_data: serde_json::Value, tests: serde_json::Value| {
+            serde_json::from_value::<CompileRunShadersRequest>(json!({
+                "requests": [],
+                "passes": [],
+                "vertex_data": vertex_data,
+                "tests": tests,
+            }))
+            .unwrap()
+        };
+        let infers_float64 = |request: &CompileRunShadersRequest| {
+            let (lines, _) = request.infer_requirements(&[], &[]);
+            lines.iter().any(|line| line == "shaderFloat64")
+        };
+
+        let dvec = request(
+            json!([
+                {"AttributeFormat": {"location": 0, "format": "R64G64_SFLOAT"}},
+                {"Dvec2": {"x": 0.5, "y": 1e-300}},
+            ]),
+            json!([]),
+        );
+        assert!(dvec.validate_vertex_data().is_ok());
+        assert!(infers_float64(&dvec));
+
+        let ssbo = |offset: u32| {
+            request(
+                json!(null),
+                json!([{"SSBOSubData": {"binding": 0, "data_type": "dvec2", "offset": offset, "values": ["1 2"]}}]),
+            )
+        };
+        assert!(infers_float64(&ssbo(16)));
+        assert!(!infers_float64(&request(
+            json!(null),
+            json!([{"Push": {"data_type": "vec2", "offset": 0, "values": ["1 2"]}}])
+        )));
+
+        let error = ShadercVkrunnerMcp::new()
+            .compile_run_shaders(ssbo(4))
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "ssbo 0 subdata dvec2 at offset 4: dvec2 components are 8 bytes, so the offset must be a multiple of 8"
+        );
+    }
+
+    #[test]
+    fn test_stencil_state() {
+        let test = serde_json::from_value::<ShaderRunnerTest>(json!({"StencilState": {
+            "face": "Back",
+            "state": {
+                "fail_op": "Zero",
+                "pass_op": "Replace",
+                "compare_op": "Equal",
+                "reference": 1,
+                "write_mask": 0xffffffffu32,
+            },
+        }}))
+        .unwrap();
+        let ShaderRunnerTest::StencilState { face, state } = test else {
+            panic!("not a StencilState");
+        };
+        assert_eq!(face.unwrap_or_default().names(), ["back"]);
+        assert_eq!(StencilFace::default().names(), ["front", "back"]);
+        assert_eq!(
+            state.properties(),
+            [
+                ("failOp", "VK_STENCIL_OP_ZERO".to_string()),
+                ("passOp", "VK_STENCIL_OP_REPLACE".to_string()),
+                ("compareOp", "VK_COMPARE_OP_EQUAL".to_string()),
+                ("reference", "1".to_string()),
+                // vkrunner reads integer properties as i32
+                ("writeMask", "-1".to_string()),
+            ]
+        );
+        assert!(StencilFaceState::default().properties().is_empty());
+    }
+
+    #[test]
+    fn test_shader_run() {
+        let text = |content: &Content| content.as_text().unwrap().text.clone();
+
+        let mut run = ShaderRun::stopped(
+            ErrorCode::CompileError,
+            "Compiled 0 of 1 shaders, so nothing was run.\n\nshader.comp:3: error: 'x' : undeclared identifier\n".to_string(),
+        );
+        assert!(!run.passed());
+        assert_eq!(
+            run.failure_reason(),
+            "shader.comp:3: error: 'x' : undeclared identifier"
+        );
+        let contents = run.clone().contents();
+        assert_eq!(contents.len(), 1);
+        assert!(text(&contents[0]).starts_with("error_code: COMPILE_ERROR\nCompiled 0 of 1"));
+
+        run.error_code = None;
+        run.succeeded = true;
+        run.cached = Some("abc".to_string());
+        assert!(run.passed());
+        let contents = run.clone().contents();
+        assert!(text(&contents[0]).starts_with("cached: true\n"));
+        assert!(text(&contents[0]).contains("(key abc)"));
+        assert!(text(&contents[1]).starts_with("Compiled 0 of 1"));
+
+        run.skipped = true;
+        run.missing_features = vec!["- shaderFloat64 for dvec2 in shader.comp".to_string()];
+        assert!(!run.passed());
+        assert_eq!(
+            run.failure_reason(),
+            "- shaderFloat64 for dvec2 in shader.comp"
+        );
+
+        run.missing_features.clear();
+        run.report = "VkRunner execution failed.".to_string();
+        run.errors = vec!["line 12: Probe color at (0, 0)".to_string()];
+        assert_eq!(run.failure_reason(), "line 12: Probe color at (0, 0)");
+        run.errors.clear();
+        assert_eq!(run.failure_reason(), "VkRunner execution failed.");
+    }
+
+    #[test]
+    fn test_single_shader_request() {
+        let request = CompileRunShadersRequest::single_shader(
+            ShaderStage::Frag,
+            "void main() {}".to_string(),
+            vec![ShaderRunnerTest::full_screen_rect()],
+            Some("lavapipe".to_string()),
+        );
+        assert_eq!(request.requests.len(), 1);
+        assert_eq!(request.requests[0].stage, ShaderStage::Frag);
+        assert!(matches!(
+            request.passes.as_slice(),
+            [
+                ShaderRunnerPass::VertPassthrough,
+                ShaderRunnerPass::FragSpirv { frag_spvasm_path, entrypoint: None },
+            ] if frag_spvasm_path == "request:0"
+        ));
+        assert_eq!(request.icd.as_deref(), Some("lavapipe"));
+        assert!(request.output_path.is_none());
+
+        let request = CompileRunShadersRequest::single_shader(
+            ShaderStage::Comp,
+            "void main() {}".to_string(),
+            vec![ShaderRunnerTest::Compute { x: 1, y: 1, z: 1 }],
+            None,
+        );
+        assert!(matches!(
+            request.passes.as_slice(),
+            [ShaderRunnerPass::CompSpirv { comp_spvasm_path, .. }] if comp_spvasm_path == "request:0"
+        ));
+    }
+
+    #[test]
+    fn test_compile_shaders() {
+        let scratch = ScratchDir::new().unwrap();
+        let server = ShadercVkrunnerMcp::new();
+        let compile = |request: CompileRequest| {
+            let result = server
+                .compile_shaders(CompileShadersRequest {
+                    requests: vec![request],
+                })
+                .unwrap();
+            result.content[0].as_text().unwrap().text.clone()
+        };
+        let source = "#version 450\nlayout(local_size_x = 1) in;\nvoid main() {\n#ifdef BROKEN\n    undeclared = 1;\n#endif\n}\n";
+
+        let mut request = CompileRequest::new(ShaderStage::Comp, source.to_string());
+        request.tmp_output_path = Some(scratch.path("shader.spvasm"));
+        let report = compile(request);
+        assert!(report.starts_with(&format!(
+            "Shader 0 (Compute):\n- compiled to {}",
+            scratch.path("shader.spvasm")
+        )));
+        let spvasm = std::fs::read_to_string(scratch.path("shader.spvasm")).unwrap();
+        assert!(spvasm.contains("OpEntryPoint GLCompute %main \"main\""));
+
+        let mut request = CompileRequest::new(ShaderStage::Comp, source.to_string());
+        request.tmp_output_path = Some(scratch.path("variant.spvasm"));
+        request.define_variants = Some(vec![
+            vec![],
+            vec![MacroDefinition {
+                name: "BROKEN".to_string(),
+                value: None,
+            }],
+        ]);
+        let report = compile(request);
+        assert!(report.starts_with("error_code: COMPILE_ERROR\nShader 0 (Compute):\n"));
+        assert!(report.contains(&format!(
+            "- variant 0 compiled to {}",
+            scratch.path("variant_variant0.spvasm")
+        )));
+        assert!(report.contains(
+            "- variant 1 failed:\nShader compilation failed for Compute shader (defines: BROKEN)"
+        ));
+        assert!(report.contains("undeclared identifier"));
+        assert!(!Path::new(&scratch.path("variant_variant1.spvasm")).exists());
+
+        // compile_incremental reports the same way, with how each result
+        // was obtained
+        let incremental = || {
+            let mut request = CompileRequest::new(ShaderStage::Comp, source.to_string());
+            request.tmp_output_path = Some(scratch.path("incremental.spvasm"));
+            let result = server
+                .compile_incremental(CompileIncrementalRequest {
+                    shaders: vec![IncrementalCompile {
+                        base_source_id: None,
+                        request,
+                    }],
+                })
+                .unwrap();
+            result.content[0].as_text().unwrap().text.clone()
+        };
+        let compiled = format!("- compiled to {}", scratch.path("incremental.spvasm"));
+        let report = incremental();
+        assert!(report.contains(&compiled) && report.contains(" ms)"));
+        assert!(incremental().contains(&format!("{compiled} (cached)")));
+    }
+}
@@ -0,0 +1,175 @@
+//! Small deliberate changes to SPIR-V assembly for mutation testing: a
+//! test whose probes still pass with a mutated shader does not check
+//! what the mutated instruction computes.
+
+use crate::spirv::{Module, SourceLocation};
+use std::collections::HashSet;
+
+/// A copy of the module with one instruction changed.
+#[derive(Debug, Clone)]
+pub struct Mutant {
+    /// What was changed, e.g. `OpFAdd -> OpFSub`.
+    pub description: String,
+    /// Result id of the changed instruction, without the leading `%`.
+    pub result_id: String,
+    pub location: Option<SourceLocation>,
+    /// The whole mutated module.
+    pub text: String,
+}
+
+/// Opcodes replaced by one with the same operands and result type but a
+/// different meaning. Comparisons turn into their negation.
+const OPCODE_SWAPS: &[(&str, &str)] = &[
+    ("OpFAdd", "OpFSub"),
+    ("OpFSub", "OpFAdd"),
+    ("OpFMul", "OpFDiv"),
+    ("OpFDiv", "OpFMul"),
+    ("OpIAdd", "OpISub"),
+    ("OpISub", "OpIAdd"),
+    ("OpIMul", "OpIAdd"),
+    ("OpFOrdLessThan", "OpFOrdGreaterThanEqual"),
+    ("OpFOrdGreaterThanEqual", "OpFOrdLessThan"),
+    ("OpFOrdGreaterThan", "OpFOrdLessThanEqual"),
+    ("OpFOrdLessThanEqual", "OpFOrdGreaterThan"),
+    ("OpFOrdEqual", "OpFUnordNotEqual"),
+    ("OpFUnordNotEqual", "OpFOrdEqual"),
+    ("OpSLessThan", "OpSGreaterThanEqual"),
+    ("OpSGreaterThanEqual", "OpSLessThan"),
+    ("OpSGreaterThan", "OpSLessThanEqual"),
+    ("OpSLessThanEqual", "OpSGreaterThan"),
+    ("OpULessThan", "OpUGreaterThanEqual"),
+    ("OpUGreaterThanEqual", "OpULessThan"),
+    ("OpUGreaterThan", "OpULessThanEqual"),
+    ("OpULessThanEqual", "OpUGreaterThan"),
+    ("OpIEqual", "OpINotEqual"),
+    ("OpINotEqual", "OpIEqual"),
+    ("OpLogicalAnd", "OpLogicalOr"),
+    ("OpLogicalOr", "OpLogicalAnd"),
+];
+
+/// Non-commutative opcodes whose two operands are swapped.
+const OPERAND_SWAPS: &[&str] = &["OpFSub", "OpFDiv", "OpISub", "OpSDiv", "OpUDiv"];
+
+/// Every mutant of `text`, in the order of the instructions they change:
+/// swapped opcodes and operands, and float constants set to 0 (or 1 when
+/// they are 0). Integer constants are left alone because they also size
+/// arrays, where changing them makes the module invalid.
+pub fn mutants(text: &str) -> Vec<Mutant> {
+    let module = Module::parse(text);
+    let locations = module.source_locations();
+    let float_types = module
+        .with_opcode("OpTypeFloat")
+        .filter_map(|instruction| instruction.result_id.as_deref())
+        .collect::<HashSet<_>>();
+
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut mutants = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        let [id, "=", opcode, operands @ ..] = tokens.as_slice() else {
+            continue;
+        };
+        let Some(result_id) = id.strip_prefix('%') else {
+            continue;
+        };
+
+        let mut changes = Vec::new();
+        if let Some((_, to)) = OPCODE_SWAPS.iter().find(|(from, _)| from == opcode) {
+            changes.push((
+                format!("{opcode} -> {to}"),
+                format!("{id} = {to} {}", operands.join(" ")),
+            ));
+        }
+        if let ([result_type, a, b], true) = (operands, OPERAND_SWAPS.contains(opcode)) {
+            changes.push((
+                format!("{opcode} operands swapped"),
+                format!("{id} = {opcode} {result_type} {b} {a}"),
+            ));
+        }
+        if let ("OpConstant", [result_type, value]) = (*opcode, operands) {
+            let is_float = result_type
+                .strip_prefix('%')
+                .is_some_and(|result_type| float_types.contains(result_type));
+            if is_float {
+                let mutated = if value.parse::<f64>() == Ok(0.0) {
+                    "1"
+                } else {
+                    "0"
+                };
+                changes.push((
+                    format!("constant {value} -> {mutated}"),
+                    format!("{id} = OpConstant {result_type} {mutated}"),
+                ));
+            }
+        }
+
+        for (description, replacement) in changes {
+            let mut mutated = lines.clone();
+            mutated[index] = &replacement;
+            mutants.push(Mutant {
+                description,
+                result_id: result_id.to_string(),
+                location: locations.get(result_id).cloned(),
+                text: mutated.join("\n"),
+            });
+        }
+    }
+
+    mutants
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mutants() {
+        let text = "%file = OpString \"test.frag\"
+%float = OpTypeFloat 32
+%int = OpTypeInt 32 1
+%half = OpConstant %float 0.5
+%zero = OpConstant %float 0
+%size = OpConstant %int 4
+%main = OpFunction %void None %fn
+%entry = OpLabel
+OpLine %file 4 0
+%diff = OpFSub %float %half %zero
+%sum = OpFAdd %float %diff %half
+OpReturn
+OpFunctionEnd";
+        let mutants = mutants(text);
+        assert_eq!(
+            mutants
+                .iter()
+                .map(|mutant| (mutant.result_id.as_str(), mutant.description.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("half", "constant 0.5 -> 0"),
+                ("zero", "constant 0 -> 1"),
+                ("diff", "OpFSub -> OpFAdd"),
+                ("diff", "OpFSub operands swapped"),
+                ("sum", "OpFAdd -> OpFSub"),
+            ]
+        );
+
+        // Each mutant changes its one line
+        let swapped = &mutants[3];
+        assert_eq!(
+            swapped.location.as_ref().map(|location| location.line),
+            Some(4)
+        );
+        let changed = text
+            .lines()
+            .zip(swapped.text.lines())
+            .filter(|(original, mutated)| original != mutated)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changed,
+            [(
+                "%diff = OpFSub %float %half %zero",
+                "%diff = OpFSub %float %zero %half"
+            )]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Statistical checks of procedural noise: a compute kernel samples a
+//! `float noise(vec2 p)` function on a grid into a float buffer, and the
+//! values are checked for their distribution, frequency content (through
+//! a 2D FFT) and continuity across the borders of one period.
+
+/// The kernel sampling `source` at the (size + 1)² grid points spanning
+/// [0, period]², row by row, into the float buffer at binding 0. The last
+/// row and column repeat the first ones for tileable noise.
+pub fn shader(source: &str, size: u32, period: f64) -> String {
+    let samples = size + 1;
+    format!(
+        "#version 450
+layout(local_size_x = 8, local_size_y = 8) in;
+layout(std430, binding = 0) writeonly buffer Values {{ float values[]; }};
+{source}
+void main()
+{{
+    uvec2 id = gl_GlobalInvocationID.xy;
+    if (id.x >= {samples}u || id.y >= {samples}u)
+        return;
+    values[id.y * {samples}u + id.x] = noise(vec2(id) * float({period:?} / {size}.0));
+}}
+"
+    )
+}
+
+/// The (size + 1)² values in a dumped buffer, if it holds them all.
+pub fn read(dump: &[u8], size: u32) -> Option<Vec<f64>> {
+    let count = ((size + 1) * (size + 1)) as usize;
+    let values = dump
+        .get(..count * 4)?
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).into())
+        .collect();
+    Some(values)
+}
+
+/// The size² values of one period, leaving out the repeated last row and
+/// column.
+pub fn period(values: &[f64], size: u32) -> Vec<f64> {
+    let samples = size as usize + 1;
+    values
+        .chunks_exact(samples)
+        .take(size as usize)
+        .flat_map(|row| &row[..size as usize])
+        .copied()
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    pub mean: f64,
+    pub variance: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Values that are NaN or infinite, left out of the others.
+    pub non_finite: usize,
+}
+
+pub fn statistics(values: &[f64]) -> Statistics {
+    let finite = values
+        .iter()
+        .copied()
+        .filter(|value| value.is_finite())
+        .collect::<Vec<_>>();
+    let count = finite.len().max(1) as f64;
+    let mean = finite.iter().sum::<f64>() / count;
+    Statistics {
+        mean,
+        variance: finite
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / count,
+        min: finite.iter().copied().fold(f64::INFINITY, f64::min),
+        max: finite.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        non_finite: values.len() - finite.len(),
+    }
+}
+
+/// In-place radix-2 FFT of `re` + i`im`, whose length is a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * std::f64::consts::PI / length as f64;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + length / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+/// Radially averaged power of one period of the size² `values` (size a
+/// power of two) without its mean: element r is the power at about r
+/// cycles per period, for r from 0 to size / 2.
+pub fn spectrum(values: &[f64], size: u32) -> Vec<f64> {
+    let n = size as usize;
+    let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+    let mut re = values.iter().map(|value| value - mean).collect::<Vec<_>>();
+    let mut im = vec![0.0; re.len()];
+
+    for row in 0..n {
+        fft(
+            &mut re[row * n..(row + 1) * n],
+            &mut im[row * n..(row + 1) * n],
+        );
+    }
+    let mut column_re = vec![0.0; n];
+    let mut column_im = vec![0.0; n];
+    for column in 0..n {
+        for row in 0..n {
+            column_re[row] = re[row * n + column];
+            column_im[row] = im[row * n + column];
+        }
+        fft(&mut column_re, &mut column_im);
+        for row in 0..n {
+            re[row * n + column] = column_re[row];
+            im[row * n + column] = column_im[row];
+        }
+    }
+
+    let mut power = vec![0.0; n / 2 + 1];
+    let mut counts = vec![0usize; n / 2 + 1];
+    let frequency = |index: usize| index.min(n - index) as f64;
+    for row in 0..n {
+        for column in 0..n {
+            let radius = frequency(row).hypot(frequency(column)).round() as usize;
+            if radius <= n / 2 {
+                let index = row * n + column;
+                power[radius] += re[index] * re[index] + im[index] * im[index];
+                counts[radius] += 1;
+            }
+        }
+    }
+    power
+        .iter()
+        .zip(&counts)
+        .map(|(power, count)| power / (*count).max(1) as f64)
+        .collect()
+}
+
+/// Fraction of the energy of `spectrum` in each octave band of
+/// frequencies, as (lowest, highest, fraction) with [1, 1], [2, 3],
+/// [4, 7] and so on.
+pub fn octave_bands(spectrum: &[f64]) -> Vec<(usize, usize, f64)> {
+    let total = spectrum.iter().skip(1).sum::<f64>().max(f64::MIN_POSITIVE);
+    let mut bands = Vec::new();
+    let mut low = 1;
+    while low < spectrum.len() {
+        let high = (2 * low - 1).min(spectrum.len() - 1);
+        bands.push((low, high, spectrum[low..=high].iter().sum::<f64>() / total));
+        low *= 2;
+    }
+    bands
+}
+
+/// The largest difference between the first and the repeated last row
+/// or column: zero for noise that tiles with the period.
+pub fn seam(values: &[f64], size: u32) -> f64 {
+    let samples = size as usize + 1;
+    let at = |x: usize, y: usize| values[y * samples + x];
+    (0..samples)
+        .map(|i| {
+            let horizontal = (at(0, i) - at(size as usize, i)).abs();
+            let vertical = (at(i, 0) - at(i, size as usize)).abs();
+            horizontal.max(vertical)
+        })
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_spectrum() {
+        let size = 64;
+        let grid = |f: &dyn Fn(f64, f64) -> f64| {
+            (0..=size)
+                .flat_map(|y| (0..=size).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    f(
+                        f64::from(x) / f64::from(size),
+                        f64::from(y) / f64::from(size),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let wave = |x: f64, _: f64| (2.0 * std::f64::consts::PI * 8.0 * x).sin();
+
+        let values = grid(&wave);
+        assert!(seam(&values, size) < 1e-9);
+        let stats = statistics(&period(&values, size));
+        assert!(stats.mean.abs() < 1e-9);
+        assert!((stats.variance - 0.5).abs() < 1e-9);
+
+        let spectrum = spectrum(&period(&values, size), size);
+        let peak = (0..spectrum.len())
+            .max_by(|a, b| spectrum[*a].total_cmp(&spectrum[*b]))
+            .unwrap();
+        assert_eq!(peak, 8);
+        let bands = octave_bands(&spectrum);
+        assert_eq!((bands[3].0, bands[3].1), (8, 15));
+        assert!(bands[3].2 > 0.999);
+
+        // Not periodic: the last column differs from the first
+        let ramp = grid(&|x, _| x);
+        assert!((seam(&ramp, size) - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,514 @@
+//! Line and scatter charts of numeric results, such as an error against a
+//! swept parameter or the time against the workgroup size, drawn with
+//! the built-in bitmap font of `label`.
+
+use crate::label;
+use image::{Rgb, RgbImage};
+use rmcp::schemars::{self, JsonSchema};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, JsonSchema)]
+pub enum Style {
+    /// Points joined in order of x, with a marker at each.
+    #[default]
+    Line,
+    /// Unconnected markers.
+    Scatter,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, JsonSchema)]
+pub struct Series {
+    #[schemars(description = "Name shown in the legend")]
+    pub name: String,
+    #[schemars(description = "The points as [x, y] pairs")]
+    pub points: Vec<[f64; 2]>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Chart {
+    pub title: Option<String>,
+    pub x_label: Option<String>,
+    pub y_label: Option<String>,
+    pub style: Style,
+    pub log_x: bool,
+    pub log_y: bool,
+    pub series: Vec<Series>,
+}
+
+const PALETTE: [[u8; 3]; 8] = [
+    [31, 119, 180],
+    [255, 127, 14],
+    [44, 160, 44],
+    [214, 39, 40],
+    [148, 103, 189],
+    [140, 86, 75],
+    [227, 119, 194],
+    [127, 127, 127],
+];
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const GRID: Rgb<u8> = Rgb([225, 225, 225]);
+const MARGIN: u32 = 8;
+
+/// One axis: the range of its values, in log10 for a log axis, widened to
+/// whole tick steps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Axis {
+    min: f64,
+    max: f64,
+    step: f64,
+    log: bool,
+}
+
+/// The value as it is laid out: log10 on a log axis.
+fn transform(value: f64, log: bool) -> f64 {
+    if log { value.log10() } else { value }
+}
+
+/// The 1, 2 or 5 times a power of ten that splits `range` into about five
+/// steps.
+fn nice_step(range: f64) -> f64 {
+    let raw = range / 5.0;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let fraction = raw / magnitude;
+    let nice = if fraction <= 1.0 {
+        1.0
+    } else if fraction <= 2.0 {
+        2.0
+    } else if fraction <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+impl Axis {
+    fn new(values: &[f64], log: bool) -> Axis {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        if log {
+            let (min, max) = (min.floor(), max.ceil());
+            let max = if max > min { max } else { min + 1.0 };
+            let step = ((max - min) / 8.0).ceil().max(1.0);
+            return Axis {
+                min,
+                max: min + ((max - min) / step).ceil() * step,
+                step,
+                log,
+            };
+        }
+
+        let (min, max) = if max > min {
+            (min, max)
+        } else {
+            let pad = (min.abs() * 0.1).max(0.5);
+            (min - pad, max + pad)
+        };
+        let step = nice_step(max - min);
+        Axis {
+            min: (min / step).floor() * step,
+            max: (max / step).ceil() * step,
+            step,
+            log,
+        }
+    }
+
+    /// Where `value` (already transformed) falls, from 0 at min to 1 at
+    /// max.
+    fn fraction(&self, value: f64) -> f64 {
+        (value - self.min) / (self.max - self.min)
+    }
+
+    fn ticks(&self) -> Vec<f64> {
+        let count = ((self.max - self.min) / self.step).round() as usize;
+        (0..=count)
+            .map(|i| self.min + i as f64 * self.step)
+            .collect()
+    }
+
+    fn tick_label(&self, tick: f64) -> String {
+        if self.log {
+            let value = 10f64.powf(tick.round());
+            return if (1e-3..1e5).contains(&value) {
+                format!("{value}")
+            } else {
+                format!("1e{}", tick.round())
+            };
+        }
+        let tick = if tick.abs() < self.step * 1e-9 {
+            0.0
+        } else {
+            tick
+        };
+        if tick != 0.0 && !(1e-4..1e6).contains(&tick.abs()) {
+            // Only the digits the step resolves, which drops the rounding
+            // error of adding up steps
+            let digits = (tick.abs().log10().floor() - self.step.log10().floor()).max(0.0) as usize;
+            let text = format!("{tick:.digits$e}");
+            match text.split_once('e') {
+                Some((mantissa, exponent)) if mantissa.contains('.') => format!(
+                    "{}e{exponent}",
+                    mantissa.trim_end_matches('0').trim_end_matches('.')
+                ),
+                _ => text,
+            }
+        } else {
+            let decimals = (-self.step.log10().floor()).max(0.0) as usize;
+            format!("{tick:.decimals$}")
+        }
+    }
+}
+
+/// Draws a line of 3 × 3 pixel dots from `from` to `to`.
+fn line(img: &mut RgbImage, from: (i64, i64), to: (i64, i64), color: Rgb<u8>) {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y, mut error) = (from.0, from.1, dx + dy);
+    loop {
+        dot(img, (x, y), 1, color);
+        if (x, y) == to {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Fills the square of `radius` around `center`, within `img`.
+fn dot(img: &mut RgbImage, center: (i64, i64), radius: i64, color: Rgb<u8>) {
+    for y in center.1 - radius..=center.1 + radius {
+        for x in center.0 - radius..=center.0 + radius {
+            if x >= 0 && y >= 0 && x < i64::from(img.width()) && y < i64::from(img.height()) {
+                img.put_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
+
+impl Chart {
+    /// The chart as a `width` × `height` image. Points that aren't finite,
+    /// or not positive on a log axis, are left out.
+    pub fn render(&self, width: u32, height: u32) -> Result<RgbImage, String> {
+        let series = self
+            .series
+            .iter()
+            .map(|series| {
+                let mut points = series
+                    .points
+                    .iter()
+                    .filter(|[x, y]| {
+                        x.is_finite()
+                            && y.is_finite()
+                            && (!self.log_x || *x > 0.0)
+                            && (!self.log_y || *y > 0.0)
+                    })
+                    .copied()
+                    .collect::<Vec<_>>();
+                points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+                (series.name.as_str(), points)
+            })
+            .collect::<Vec<_>>();
+        let all = series.iter().flat_map(|(_, points)| points);
+        if all.clone().next().is_none() {
+            return Err(format!(
+                "No point to plot{}",
+                if self.log_x || self.log_y {
+                    " (log axes need positive values)"
+                } else {
+                    ""
+                }
+            ));
+        }
+        let x_axis = Axis::new(
+            &all.clone()
+                .map(|[x, _]| transform(*x, self.log_x))
+                .collect::<Vec<_>>(),
+            self.log_x,
+        );
+        let y_axis = Axis::new(
+            &all.map(|[_, y]| transform(*y, self.log_y))
+                .collect::<Vec<_>>(),
+            self.log_y,
+        );
+
+        // Room for the title and y label above the plot, the tick labels
+        // left of and below it and the x label under those
+        let x_ticks = x_axis.ticks();
+        let y_ticks = y_axis.ticks();
+        let y_tick_width = y_ticks
+            .iter()
+            .map(|tick| label::text_width(&y_axis.tick_label(*tick), 1))
+            .max()
+            .unwrap_or(0);
+        let top_lines = u32::from(self.title.is_some()) + u32::from(self.y_label.is_some());
+        let left = MARGIN + y_tick_width + 6;
+        let top = MARGIN + top_lines * label::LINE_HEIGHT + 6;
+        let right = width.saturating_sub(MARGIN + 8);
+        let bottom = height.saturating_sub(
+            MARGIN + label::LINE_HEIGHT * (1 + u32::from(self.x_label.is_some())) + 4,
+        );
+        if right <= left + 16 || bottom <= top + 16 {
+            return Err(format!("{width} × {height} is too small for the chart"));
+        }
+
+        let mut img = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+        let to_x = |x: f64| {
+            (f64::from(left) + x_axis.fraction(x) * f64::from(right - left)).round() as i64
+        };
+        let to_y = |y: f64| {
+            (f64::from(bottom) - y_axis.fraction(y) * f64::from(bottom - top)).round() as i64
+        };
+
+        for tick in &x_ticks {
+            let x = to_x(*tick);
+            line(&mut img, (x, i64::from(top)), (x, i64::from(bottom)), GRID);
+            let text = x_axis.tick_label(*tick);
+            let text_left = (x - i64::from(label::text_width(&text, 1) / 2)).max(0) as u32;
+            label::write(&mut img, text_left, bottom + 6, &text, 1, BLACK);
+        }
+        for tick in &y_ticks {
+            let y = to_y(*tick);
+            line(&mut img, (i64::from(left), y), (i64::from(right), y), GRID);
+            let text = y_axis.tick_label(*tick);
+            let text_left = left - 6 - label::text_width(&text, 1);
+            label::write(&mut img, text_left, (y - 3).max(0) as u32, &text, 1, BLACK);
+        }
+        let corners = [
+            (i64::from(left), i64::from(top)),
+            (i64::from(left), i64::from(bottom)),
+            (i64::from(right), i64::from(bottom)),
+        ];
+        line(&mut img, corners[0], corners[1], BLACK);
+        line(&mut img, corners[1], corners[2], BLACK);
+
+        let mut text_top = MARGIN;
+        if let Some(title) = &self.title {
+            let text_left = (width.saturating_sub(label::text_width(title, 1))) / 2;
+            label::write(&mut img, text_left, text_top, title, 1, BLACK);
+            text_top += label::LINE_HEIGHT;
+        }
+        if let Some(y_label) = &self.y_label {
+            label::write(&mut img, MARGIN, text_top, y_label, 1, BLACK);
+        }
+        if let Some(x_label) = &self.x_label {
+            let text_left = (left + right).saturating_sub(label::text_width(x_label, 1)) / 2;
+            let text_top = bottom + 6 + label::LINE_HEIGHT;
+            label::write(&mut img, text_left, text_top, x_label, 1, BLACK);
+        }
+
+        for (index, (_, points)) in series.iter().enumerate() {
+            let color = Rgb(PALETTE[index % PALETTE.len()]);
+            let pixels = points
+                .iter()
+                .map(|[x, y]| {
+                    (
+                        to_x(transform(*x, self.log_x)),
+                        to_y(transform(*y, self.log_y)),
+                    )
+                })
+                .collect::<Vec<_>>();
+            if self.style == Style::Line {
+                for pair in pixels.windows(2) {
+                    line(&mut img, pair[0], pair[1], color);
+                }
+            }
+            for pixel in pixels {
+                dot(&mut img, pixel, 2, color);
+            }
+        }
+
+        // The legend, in the top-right corner of the plot
+        if series.len() > 1 || series.iter().any(|(name, _)| !name.is_empty()) {
+            let text_width = series
+                .iter()
+                .map(|(name, _)| label::text_width(name, 1))
+                .max()
+                .unwrap_or(0);
+            let legend_width = text_width + 26;
+            let legend_height = series.len() as u32 * label::LINE_HEIGHT + 6;
+            let legend_left = right.saturating_sub(legend_width + 4);
+            let legend_top = top + 4;
+            for y in legend_top..(legend_top + legend_height).min(height) {
+                for x in legend_left..(legend_left + legend_width).min(width) {
+                    let border = y == legend_top
+                        || y + 1 == legend_top + legend_height
+                        || x == legend_left
+                        || x + 1 == legend_left + legend_width;
+                    img.put_pixel(x, y, if border { BLACK } else { Rgb([255, 255, 255]) });
+                }
+            }
+            for (index, (name, _)) in series.iter().enumerate() {
+                let color = Rgb(PALETTE[index % PALETTE.len()]);
+                let row_top = legend_top + 4 + index as u32 * label::LINE_HEIGHT;
+                let middle = i64::from(row_top + 3);
+                let swatch = i64::from(legend_left + 4);
+                line(&mut img, (swatch, middle), (swatch + 12, middle), color);
+                label::write(&mut img, legend_left + 22, row_top, name, 1, BLACK);
+            }
+        }
+
+        Ok(img)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_axis() {
+        let axis = Axis::new(&[0.13, 0.92], false);
+        assert_eq!((axis.step, axis.ticks().len()), (0.2, 6));
+        assert!((axis.max - 1.0).abs() < 1e-12);
+        assert_eq!(axis.tick_label(axis.ticks()[2]), "0.4");
+
+        let axis = Axis::new(&[32f64.log10(), 1024f64.log10()], true);
+        assert_eq!((axis.min, axis.max), (1.0, 4.0));
+        assert_eq!(axis.tick_label(3.0), "1000");
+        assert_eq!(axis.tick_label(-6.0), "1e-6");
+
+        // A single value still gets a range around it
+        let axis = Axis::new(&[3.0, 3.0], false);
+        assert!(axis.min < 3.0 && axis.max > 3.0);
+    }
+
+    #[test]
+    fn test_nice_step() {
+        assert_eq!(nice_step(1.0), 0.2);
+        assert_eq!(nice_step(10.0), 2.0);
+        assert_eq!(nice_step(20.0), 5.0);
+        assert_eq!(nice_step(30.0), 10.0);
+        assert!((nice_step(0.003) - 0.001).abs() < 1e-15);
+    }
+
+    #[test]
+    fn test_axis_scaling() {
+        // The range widens to whole steps around negative values
+        let axis = Axis::new(&[-7.0, 3.0], false);
+        assert_eq!((axis.min, axis.max, axis.step), (-8.0, 4.0, 2.0));
+        assert_eq!(axis.fraction(-8.0), 0.0);
+        assert_eq!(axis.fraction(4.0), 1.0);
+        assert_eq!(axis.fraction(-2.0), 0.5);
+        assert_eq!(axis.tick_label(axis.ticks()[0]), "-8");
+
+        // Large and small magnitudes switch to exponents
+        let axis = Axis::new(&[0.0, 3e7], false);
+        assert_eq!(axis.step, 1e7);
+        assert_eq!(axis.tick_label(2e7), "2e7");
+        let axis = Axis::new(&[1e7, 2e7], false);
+        assert_eq!(axis.tick_label(1.2e7), "1.2e7");
+        assert_eq!(axis.tick_label(1.4e7 + 1.0), "1.4e7");
+        let axis = Axis::new(&[0.0, 3e-5], false);
+        assert_eq!(axis.tick_label(axis.ticks()[1]), "1e-5");
+
+        // A log axis spans whole decades, one tick each up to eight
+        let axis = Axis::new(&[0.5f64.log10(), 20f64.log10()], true);
+        assert_eq!((axis.min, axis.max, axis.step), (-1.0, 2.0, 1.0));
+        assert_eq!(axis.tick_label(-1.0), "0.1");
+        // Over eight decades, the steps take several and the range
+        // widens so the last tick is at the end
+        let axis = Axis::new(&[0.0, 20.0], true);
+        assert_eq!((axis.max, axis.step), (21.0, 3.0));
+        assert_eq!(axis.ticks().last(), Some(&21.0));
+
+        // A single decade still gets a range
+        let axis = Axis::new(&[1.0, 1.0], true);
+        assert_eq!((axis.min, axis.max), (1.0, 2.0));
+    }
+
+    #[test]
+    fn test_render_empty_series() {
+        let none = Chart::default();
+        assert_eq!(none.render(320, 200).unwrap_err(), "No point to plot");
+
+        let empty = Chart {
+            series: vec![Series {
+                name: "empty".to_string(),
+                points: Vec::new(),
+            }],
+            ..Chart::default()
+        };
+        assert!(empty.render(320, 200).is_err());
+
+        // An empty series next to one with points is only in the legend
+        let chart = Chart {
+            series: vec![
+                Series {
+                    name: "empty".to_string(),
+                    points: Vec::new(),
+                },
+                Series {
+                    name: "full".to_string(),
+                    points: vec![[0.0, 0.0], [1.0, 1.0]],
+                },
+            ],
+            ..Chart::default()
+        };
+        let img = chart.render(320, 200).unwrap();
+        let count = |color: [u8; 3]| img.pixels().filter(|pixel| pixel.0 == color).count();
+        // The first series' color only appears as its legend swatch
+        assert_eq!(count(PALETTE[0]), 15 * 3);
+        assert!(count(PALETTE[1]) > 15 * 3);
+    }
+
+    #[test]
+    fn test_render_non_finite() {
+        let points = vec![[0.0, 0.0], [1.0, 2.0], [2.0, 1.0]];
+        let chart = |points: Vec<[f64; 2]>| Chart {
+            series: vec![Series {
+                name: "values".to_string(),
+                points,
+            }],
+            ..Chart::default()
+        };
+        let expected = chart(points.clone()).render(320, 200).unwrap();
+
+        // Points with NaN or infinite coordinates are left out without
+        // stretching the axes
+        let mut with_non_finite = points;
+        with_non_finite.extend([
+            [f64::NAN, 5.0],
+            [3.0, f64::NAN],
+            [f64::INFINITY, 1.0],
+            [1.5, f64::NEG_INFINITY],
+        ]);
+        assert_eq!(chart(with_non_finite).render(320, 200).unwrap(), expected);
+
+        let only_nan = chart(vec![[f64::NAN, f64::NAN]]);
+        assert!(only_nan.render(320, 200).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let chart = Chart {
+            title: Some("error vs samples".to_string()),
+            log_x: true,
+            series: vec![Series {
+                name: "rms".to_string(),
+                points: vec![[16.0, 0.5], [64.0, 0.25], [256.0, 0.125], [-1.0, 0.0]],
+            }],
+            ..Chart::default()
+        };
+        let img = chart.render(320, 200).unwrap();
+        let color = Rgb(PALETTE[0]);
+        let drawn = img.pixels().filter(|pixel| **pixel == color).count();
+        assert!(drawn > 100, "{drawn}");
+
+        let empty = Chart {
+            log_y: true,
+            series: vec![Series {
+                name: String::new(),
+                points: vec![[1.0, 0.0]],
+            }],
+            ..Chart::default()
+        };
+        assert!(empty.render(320, 200).is_err());
+        assert!(chart.render(40, 30).is_err());
+    }
+}
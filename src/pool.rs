@@ -0,0 +1,416 @@
+//! vkrunner processes kept running in serve mode between runs, so that
+//! the Vulkan instance and device they create are reused instead of set
+//! up again for every run. Workers that sit idle past the timeout exit,
+//! so the GPU memory they hold is given back on a shared server.
+
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex, Weak, mpsc};
+use std::time::{Duration, Instant};
+
+/// Line that vkrunner ends the output of every run with in serve mode,
+/// on both stdout and stderr, followed by the run's exit code.
+const SERVE_DONE: &str = "VKRUNNER-SERVE-DONE";
+
+/// What a worker is started with: runs with a different key need a
+/// worker of their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Key {
+    program: PathBuf,
+    env: Vec<(String, String)>,
+    /// Arguments that select the Vulkan device.
+    device: Vec<String>,
+}
+
+impl Key {
+    fn new(program: &Path, args: &[String], env: &[(String, String)]) -> Self {
+        Self {
+            program: program.to_path_buf(),
+            env: env.to_vec(),
+            device: args
+                .iter()
+                .filter(|arg| *arg == "--portability" || arg.starts_with("--device-id"))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+enum Line {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+/// A vkrunner process in serve mode. It is killed when dropped.
+struct Worker {
+    key: Key,
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines of both output streams; disconnected once the process has
+    /// closed them, e.g. when it exits.
+    lines: mpsc::Receiver<Line>,
+    idle_since: Instant,
+}
+
+impl Worker {
+    fn spawn(key: Key) -> std::io::Result<Self> {
+        let mut child = Command::new(&key.program)
+            .arg("--serve")
+            .envs(key.env.iter().map(|(name, value)| (name, value)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (sender, lines) = mpsc::channel();
+        let forward = |stream: Box<dyn Read + Send>, wrap: fn(Vec<u8>) -> Line| {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let mut stream = BufReader::new(stream);
+                loop {
+                    let mut line = Vec::new();
+                    match stream.read_until(b'\n', &mut line) {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) => {
+                            if sender.send(wrap(line)).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            });
+        };
+        if let Some(stdout) = child.stdout.take() {
+            forward(Box::new(stdout), Line::Stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward(Box::new(stderr), Line::Stderr);
+        }
+        let stdin = child.stdin.take().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "vkrunner has no stdin")
+        })?;
+
+        Ok(Self {
+            key,
+            child,
+            stdin,
+            lines,
+            idle_since: Instant::now(),
+        })
+    }
+
+    fn exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The exit code of a serve mode end line.
+fn done_code(line: &[u8]) -> Option<i32> {
+    let line = std::str::from_utf8(line).ok()?.trim_end();
+    line.strip_prefix(SERVE_DONE)?.trim().parse().ok()
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+}
+
+/// Idle workers by key, at most `capacity` of them.
+pub struct WorkerPool {
+    idle_timeout: Duration,
+    capacity: usize,
+    idle: Mutex<Vec<Worker>>,
+    /// Programs that exited instead of serving, such as a vkrunner built
+    /// before serve mode; they start a process per run.
+    unsupported: Mutex<HashSet<PathBuf>>,
+}
+
+impl WorkerPool {
+    /// A pool keeping up to `capacity` idle workers for `idle_timeout`
+    /// each, or none at all for a zero timeout.
+    pub fn new(idle_timeout: Duration, capacity: usize) -> Arc<Self> {
+        let pool = Arc::new(Self {
+            idle_timeout,
+            capacity: capacity.max(1),
+            idle: Mutex::default(),
+            unsupported: Mutex::default(),
+        });
+
+        if !idle_timeout.is_zero() {
+            let pool = Arc::downgrade(&pool);
+            let interval = (idle_timeout / 2).max(Duration::from_millis(10));
+            std::thread::spawn(move || evict_until_dropped(pool, interval));
+        }
+        pool
+    }
+
+    /// Whether a run of `program` with `args` can go to a worker: each
+    /// run is sent as one line of tab-separated arguments.
+    pub fn accepts(&self, program: &Path, args: &[String]) -> bool {
+        !self.idle_timeout.is_zero()
+            && args.iter().all(|arg| !arg.contains(['\t', '\n', '\r']))
+            && !self
+                .unsupported
+                .lock()
+                .unwrap()
+                .contains(&program.to_path_buf())
+    }
+
+    /// Number of workers waiting for a run.
+    #[cfg(test)]
+    pub fn idle_workers(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Stops the workers idle for longer than the timeout.
+    pub fn evict_idle(&self) {
+        let expired = {
+            let mut idle = self.idle.lock().unwrap();
+            let (expired, kept) = std::mem::take(&mut *idle)
+                .into_iter()
+                .partition(|worker| worker.idle_since.elapsed() >= self.idle_timeout);
+            *idle = kept;
+            expired
+        };
+        // Killing and reaping happens outside the lock
+        drop::<Vec<Worker>>(expired);
+    }
+
+    fn take(&self, key: &Key) -> Option<Worker> {
+        self.evict_idle();
+        let mut idle = self.idle.lock().unwrap();
+        let index = idle.iter().rposition(|worker| worker.key == *key)?;
+        Some(idle.remove(index))
+    }
+
+    fn put(&self, worker: Worker) {
+        let evicted = {
+            let mut idle = self.idle.lock().unwrap();
+            idle.push(worker);
+            let excess = idle.len().saturating_sub(self.capacity);
+            idle.drain(..excess).collect::<Vec<_>>()
+        };
+        drop(evicted);
+    }
+
+    /// Runs vkrunner with `args` on an idle worker for the same program,
+    /// environment and device, or on a new one, passing each stderr line
+    /// to `on_stderr` as it arrives. `None` if `program` has no serve
+    /// mode, in which case it has to be run directly.
+    pub fn run(
+        &self,
+        program: &Path,
+        args: &[String],
+        env: &[(String, String)],
+        on_stderr: impl Fn(&str),
+    ) -> std::io::Result<Option<Output>> {
+        let key = Key::new(program, args, env);
+        let request = format!("{}\n", args.join("\t"));
+
+        // An idle worker may have exited meanwhile; a new one gets the run
+        let send = |worker: &mut Worker| {
+            worker.stdin.write_all(request.as_bytes())?;
+            worker.stdin.flush()
+        };
+        let idle = self.take(&key).and_then(|mut worker| {
+            (!worker.exited() && send(&mut worker).is_ok()).then_some(worker)
+        });
+        let mut worker = match idle {
+            Some(worker) => worker,
+            None => {
+                let mut worker = Worker::spawn(key)?;
+                // A new worker that can't take the run has exited, which
+                // its output and exit status below tell about
+                let _ = send(&mut worker);
+                worker
+            }
+        };
+
+        let mut output = Output {
+            status: ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        let (mut stdout_code, mut stderr_code) = (None, None);
+        while stdout_code.is_none() || stderr_code.is_none() {
+            match worker.lines.recv() {
+                Ok(Line::Stdout(line)) => match done_code(&line) {
+                    Some(code) => stdout_code = Some(code),
+                    None => output.stdout.extend_from_slice(&line),
+                },
+                Ok(Line::Stderr(line)) => match done_code(&line) {
+                    Some(code) => stderr_code = Some(code),
+                    None => {
+                        on_stderr(String::from_utf8_lossy(&line).trim_end());
+                        output.stderr.extend_from_slice(&line);
+                    }
+                },
+                // The worker exited during the run, e.g. when the driver
+                // crashed, so the run failed the way a process of its own
+                // would have
+                Err(_) => {
+                    output.status = worker.child.wait()?;
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    if stderr.contains("Unknown option: --serve") {
+                        self.unsupported
+                            .lock()
+                            .unwrap()
+                            .insert(program.to_path_buf());
+                        return Ok(None);
+                    }
+                    return Ok(Some(output));
+                }
+            }
+        }
+
+        output.status = exit_status(stdout_code.unwrap_or(1));
+        worker.idle_since = Instant::now();
+        self.put(worker);
+        Ok(Some(output))
+    }
+}
+
+fn evict_until_dropped(pool: Weak<WorkerPool>, interval: Duration) {
+    loop {
+        std::thread::sleep(interval);
+        match pool.upgrade() {
+            Some(pool) => pool.evict_idle(),
+            None => break,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    /// A stand-in for vkrunner's serve mode that echoes its arguments
+    /// and process ID, fails runs of `fail` and exits on `crash`.
+    fn fake_vkrunner(name: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("pool_test_{name}_{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "#!/bin/sh\n\
+             [ \"$1\" = --serve ] || { echo 'Unknown option: --serve' >&2; exit 1; }\n\
+             while IFS= read -r line; do\n\
+             \x20 [ \"$line\" = crash ] && exit 3\n\
+             \x20 echo \"ran $line in $$ on ${DEVICE:-none}\"\n\
+             \x20 echo \"progress $line\" >&2\n\
+             \x20 code=0; [ \"$line\" = fail ] && code=1\n\
+             \x20 echo \"VKRUNNER-SERVE-DONE $code\"\n\
+             \x20 echo \"VKRUNNER-SERVE-DONE $code\" >&2\n\
+             done\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn run(pool: &WorkerPool, program: &Path, args: &[&str], env: &[(&str, &str)]) -> Output {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let env = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        pool.run(program, &args, &env, |_| {}).unwrap().unwrap()
+    }
+
+    fn pid(output: &Output) -> String {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout.split_whitespace().nth(3).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_reuse() {
+        let program = fake_vkrunner("reuse");
+        let pool = WorkerPool::new(Duration::from_secs(60), 2);
+
+        let lines = Mutex::new(Vec::new());
+        let first = pool
+            .run(&program, &["a.shader_test".to_string()], &[], |line| {
+                lines.lock().unwrap().push(line.to_string())
+            })
+            .unwrap()
+            .unwrap();
+        assert!(first.status.success());
+        assert_eq!(lines.into_inner().unwrap(), ["progress a.shader_test"]);
+        assert_eq!(first.stderr, b"progress a.shader_test\n");
+
+        // The same worker takes the next run, and a failed run keeps it
+        let failed = run(&pool, &program, &["fail"], &[]);
+        assert_eq!(failed.status.code(), Some(1));
+        assert_eq!(pid(&failed), pid(&first));
+        assert_eq!(pid(&run(&pool, &program, &["b"], &[])), pid(&first));
+        assert_eq!(pool.idle_workers(), 1);
+
+        // Another driver environment or device needs another worker
+        let other = run(&pool, &program, &["b"], &[("DEVICE", "lavapipe")]);
+        assert!(String::from_utf8_lossy(&other.stdout).ends_with("on lavapipe\n"));
+        assert_ne!(pid(&other), pid(&first));
+        let device = run(&pool, &program, &["b", "--device-id=2"], &[]);
+        assert_ne!(pid(&device), pid(&first));
+        assert_eq!(pool.idle_workers(), 2);
+
+        // A crash fails the run with the worker's exit status, and the
+        // next run starts over
+        let crashed = run(&pool, &program, &["crash"], &[]);
+        assert_eq!(crashed.status.code(), Some(3));
+        assert!(run(&pool, &program, &["c"], &[]).status.success());
+
+        assert!(!pool.accepts(&program, &["a\tb".to_string()]));
+        std::fs::remove_file(&program).unwrap();
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let program = fake_vkrunner("idle");
+        let pool = WorkerPool::new(Duration::from_millis(50), 1);
+
+        let first = run(&pool, &program, &["a"], &[]);
+        assert_eq!(pool.idle_workers(), 1);
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(pool.idle_workers(), 0);
+        assert_ne!(pid(&run(&pool, &program, &["a"], &[])), pid(&first));
+
+        assert!(!WorkerPool::new(Duration::ZERO, 1).accepts(&program, &[]));
+        std::fs::remove_file(&program).unwrap();
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let program = std::env::temp_dir().join(format!("pool_test_old_{}", std::process::id()));
+        std::fs::write(
+            &program,
+            "#!/bin/sh\necho 'Unknown option: --serve' >&2\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(
+            &program,
+            std::os::unix::fs::PermissionsExt::from_mode(0o755),
+        )
+        .unwrap();
+
+        let pool = WorkerPool::new(Duration::from_secs(60), 1);
+        let args = ["a".to_string()];
+        assert!(pool.accepts(&program, &args));
+        assert!(pool.run(&program, &args, &[], |_| {}).unwrap().is_none());
+        assert!(!pool.accepts(&program, &args));
+        std::fs::remove_file(&program).unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+//! Harness for signed-distance-field raymarchers: a fragment shader
+//! header with standard camera push constants, a built-in sphere-tracing
+//! main for scenes that only define `map`, and the analytic silhouettes of
+//! spheres to check the rendered coverage against.
+
+use crate::{DataType, ShaderRunnerTest};
+use image::RgbImage;
+use rmcp::schemars::{self, JsonSchema};
+
+/// vkrunner's default framebuffer size, which the harness renders at.
+pub const RESOLUTION: u32 = 250;
+
+/// Declarations prepended to every raymarcher. The push constants are
+/// laid out as std430: eye at 0, time at 12, target at 16, fov_y at 28
+/// and resolution at 32.
+const HEADER: &str = "#version 450
+layout(push_constant) uniform Camera {
+    vec3 eye;
+    float time;
+    vec3 target;
+    float fov_y;
+    vec2 resolution;
+};
+layout(location = 0) out vec4 frag_color;
+
+// Direction of the ray from eye through the pixel at frag_coord, with y
+// up and the vertical field of view fov_y in radians
+vec3 camera_ray(vec2 frag_coord)
+{
+    vec3 forward = normalize(target - eye);
+    vec3 right = normalize(cross(forward, vec3(0.0, 1.0, 0.0)));
+    vec3 up = cross(right, forward);
+    vec2 ndc = (2.0 * frag_coord - resolution) / resolution.y;
+    return normalize(forward + tan(0.5 * fov_y) * (ndc.x * right - ndc.y * up));
+}
+";
+
+/// Sphere tracing of `map` with Lambert shading: hits are never darker
+/// than 0.2 and misses are black, so coverage can be read off the image.
+const MAIN: &str = "
+void main()
+{
+    vec3 direction = camera_ray(gl_FragCoord.xy);
+    float t = 0.0;
+    for (int i = 0; i < 256 && t < 100.0; i++) {
+        vec3 p = eye + t * direction;
+        float d = map(p);
+        if (d < 1e-4 * max(t, 1.0)) {
+            const vec2 e = vec2(1e-3, 0.0);
+            vec3 normal = normalize(vec3(
+                map(p + e.xyy) - map(p - e.xyy),
+                map(p + e.yxy) - map(p - e.yxy),
+                map(p + e.yyx) - map(p - e.yyx)));
+            float light = max(dot(normal, normalize(vec3(1.0, 2.0, 3.0))), 0.0);
+            frag_color = vec4(vec3(0.2 + 0.8 * light), 1.0);
+            return;
+        }
+        t += d;
+    }
+    frag_color = vec4(0.0, 0.0, 0.0, 1.0);
+}
+";
+
+/// The full fragment shader for `source`: the header, then the source,
+/// then the built-in main unless the source has its own.
+pub fn shader(source: &str) -> String {
+    let main = if source.contains("void main") {
+        ""
+    } else {
+        MAIN
+    };
+    format!("{HEADER}\n{source}\n{main}")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub eye: [f64; 3],
+    pub target: [f64; 3],
+    /// Vertical field of view in radians.
+    pub fov_y: f64,
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f64; 3]) -> [f64; 3] {
+    let length = dot(a, a).sqrt();
+    a.map(|c| c / length)
+}
+
+impl Camera {
+    /// The push constant commands setting up this camera at `time`.
+    pub fn push_constants(&self, time: f64) -> Vec<ShaderRunnerTest> {
+        let vec3 = |v: [f64; 3]| format!("{} {} {}", v[0], v[1], v[2]);
+        let push = |data_type, offset, value| ShaderRunnerTest::Push {
+            data_type,
+            offset,
+            values: vec![value],
+        };
+        vec![
+            push(DataType::Vec3, 0, vec3(self.eye)),
+            push(DataType::Float, 12, time.to_string()),
+            push(DataType::Vec3, 16, vec3(self.target)),
+            push(DataType::Float, 28, self.fov_y.to_string()),
+            push(DataType::Vec2, 32, format!("{RESOLUTION} {RESOLUTION}")),
+        ]
+    }
+
+    /// The ray through the center of pixel (x, y) of the frame, as
+    /// camera_ray computes it.
+    fn ray(&self, x: u32, y: u32) -> [f64; 3] {
+        let forward = normalize(sub(self.target, self.eye));
+        let right = normalize(cross(forward, [0.0, 1.0, 0.0]));
+        let up = cross(right, forward);
+        let size = f64::from(RESOLUTION);
+        let ndc_x = (2.0 * (f64::from(x) + 0.5) - size) / size;
+        let ndc_y = (2.0 * (f64::from(y) + 0.5) - size) / size;
+        let scale = (0.5 * self.fov_y).tan();
+        normalize([0, 1, 2].map(|c| forward[c] + scale * (ndc_x * right[c] - ndc_y * up[c])))
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, JsonSchema)]
+pub struct Sphere {
+    pub center: [f64; 3],
+    pub radius: f64,
+}
+
+impl Sphere {
+    fn hit(&self, origin: [f64; 3], direction: [f64; 3]) -> bool {
+        let offset = sub(origin, self.center);
+        let b = dot(offset, direction);
+        let c = dot(offset, offset) - self.radius * self.radius;
+        // Hit when the nearer root exists and is in front of the eye
+        b * b - c >= 0.0 && (c < 0.0 || b < 0.0)
+    }
+}
+
+/// Which pixels of the frame, row by row, see any of `spheres`.
+pub fn expected_coverage(spheres: &[Sphere], camera: &Camera) -> Vec<bool> {
+    (0..RESOLUTION)
+        .flat_map(|y| (0..RESOLUTION).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let direction = camera.ray(x, y);
+            spheres
+                .iter()
+                .any(|sphere| sphere.hit(camera.eye, direction))
+        })
+        .collect()
+}
+
+/// Which pixels of `img`, row by row, differ from `background` by more
+/// than 2/255 in any channel.
+pub fn rendered_coverage(img: &RgbImage, background: [f64; 3]) -> Vec<bool> {
+    let background = background.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    img.pixels()
+        .map(|pixel| (0..3).any(|c| pixel[c].abs_diff(background[c]) > 2))
+        .collect()
+}
+
+/// Percentage of covered pixels.
+pub fn percentage(coverage: &[bool]) -> f64 {
+    let covered = coverage.iter().filter(|covered| **covered).count();
+    100.0 * covered as f64 / coverage.len().max(1) as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expected_coverage() {
+        let camera = Camera {
+            eye: [0.0, 0.0, 3.0],
+            target: [0.0, 0.0, 0.0],
+            fov_y: 60f64.to_radians(),
+        };
+        let sphere = Sphere {
+            center: [0.0, 0.0, 0.0],
+            radius: 1.0,
+        };
+
+        // The silhouette is a disc whose angular radius is asin(r / d)
+        let radius = (1f64 / 3.0).asin().tan() / (0.5 * camera.fov_y).tan();
+        let disc = 100.0 * std::f64::consts::PI * radius * radius / 4.0;
+        let coverage = percentage(&expected_coverage(&[sphere], &camera));
+        assert!((coverage - disc).abs() < 0.5, "{coverage} vs {disc}");
+
+        // A sphere behind the eye is not seen
+        let behind = Sphere {
+            center: [0.0, 0.0, 5.0],
+            radius: 1.0,
+        };
+        assert_eq!(percentage(&expected_coverage(&[behind], &camera)), 0.0);
+
+        // Raised spheres show in the top rows, which come first
+        let raised = Sphere {
+            center: [0.0, 1.0, 0.0],
+            radius: 0.3,
+        };
+        let coverage = expected_coverage(&[raised], &camera);
+        let first = coverage.iter().position(|covered| *covered).unwrap();
+        assert!(first < (RESOLUTION * RESOLUTION / 2) as usize);
+    }
+}
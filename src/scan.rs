@@ -0,0 +1,357 @@
+//! Known-good parallel reduction and prefix scan kernels and their CPU
+//! references. The built-in kernels run as a single workgroup that walks
+//! the array in chunks, so they are correct for any size and serve as
+//! building blocks; shaders under test use the same interface.
+
+use rmcp::schemars::{self, JsonSchema};
+
+/// Threads of the built-in kernels' workgroup and the elements of one
+/// chunk.
+const WORKGROUP_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, JsonSchema)]
+pub enum Pattern {
+    /// One value combining every element, written to element 0.
+    Reduce,
+    /// Element i combines elements 0 to i.
+    InclusiveScan,
+    /// Element i combines elements 0 to i - 1; element 0 is the identity.
+    ExclusiveScan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, JsonSchema)]
+pub enum Operator {
+    Add,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, JsonSchema)]
+pub enum Element {
+    #[serde(rename = "uint")]
+    Uint,
+    #[serde(rename = "int")]
+    Int,
+    #[serde(rename = "float")]
+    Float,
+}
+
+impl Element {
+    pub fn glsl(self) -> &'static str {
+        match self {
+            Element::Uint => "uint",
+            Element::Int => "int",
+            Element::Float => "float",
+        }
+    }
+
+    /// The type of an element in vkrunner's subdata commands.
+    pub fn data_type(self) -> crate::DataType {
+        match self {
+            Element::Uint => crate::DataType::Uint,
+            Element::Int => crate::DataType::Int,
+            Element::Float => crate::DataType::Float,
+        }
+    }
+
+    fn value(self, bits: u32) -> f64 {
+        match self {
+            Element::Uint => bits.into(),
+            Element::Int => (bits as i32).into(),
+            Element::Float => f32::from_bits(bits).into(),
+        }
+    }
+}
+
+/// A reduction or scan of one operator over one element type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Kernel {
+    pub pattern: Pattern,
+    pub operator: Operator,
+    pub element: Element,
+}
+
+impl Kernel {
+    /// Name of the built-in kernel, such as `inclusive_scan_add_uint`.
+    pub fn name(&self) -> String {
+        let pattern = match self.pattern {
+            Pattern::Reduce => "reduce",
+            Pattern::InclusiveScan => "inclusive_scan",
+            Pattern::ExclusiveScan => "exclusive_scan",
+        };
+        let operator = match self.operator {
+            Operator::Add => "add",
+            Operator::Min => "min",
+            Operator::Max => "max",
+        };
+        format!("{pattern}_{operator}_{}", self.element.glsl())
+    }
+
+    /// The GLSL identity of the operator.
+    fn identity(&self) -> &'static str {
+        match (self.operator, self.element) {
+            (Operator::Add, Element::Uint) => "0u",
+            (Operator::Add, Element::Int) => "0",
+            (Operator::Add, Element::Float) => "0.0",
+            (Operator::Min, Element::Uint) => "0xffffffffu",
+            (Operator::Min, Element::Int) => "0x7fffffff",
+            (Operator::Min, Element::Float) => "uintBitsToFloat(0x7f800000u)",
+            (Operator::Max, Element::Uint) => "0u",
+            (Operator::Max, Element::Int) => "int(0x80000000u)",
+            (Operator::Max, Element::Float) => "uintBitsToFloat(0xff800000u)",
+        }
+    }
+
+    /// Elements of the output buffer for `count` inputs.
+    pub fn output_len(&self, count: u32) -> u32 {
+        match self.pattern {
+            Pattern::Reduce => 1,
+            Pattern::InclusiveScan | Pattern::ExclusiveScan => count,
+        }
+    }
+
+    /// The built-in kernel: the input at binding 0, the output at
+    /// binding 1 and the element count in a `uint` push constant.
+    pub fn shader(&self) -> String {
+        let operator = match self.operator {
+            Operator::Add => "((a) + (b))",
+            Operator::Min => "min(a, b)",
+            Operator::Max => "max(a, b)",
+        };
+        let body = match self.pattern {
+            Pattern::Reduce => {
+                "    T value = IDENTITY;
+    for (uint i = lane; i < count; i += WORKGROUP_SIZE)
+        value = OP(value, data_in[i]);
+    partial[lane] = value;
+    barrier();
+    for (uint width = WORKGROUP_SIZE / 2u; width > 0u; width >>= 1u) {
+        if (lane < width)
+            partial[lane] = OP(partial[lane], partial[lane + width]);
+        barrier();
+    }
+    if (lane == 0u)
+        data_out[0] = partial[0];"
+            }
+            Pattern::InclusiveScan | Pattern::ExclusiveScan => {
+                "    T carry = IDENTITY;
+    for (uint base = 0u; base < count; base += WORKGROUP_SIZE) {
+        uint i = base + lane;
+        partial[lane] = i < count ? data_in[i] : IDENTITY;
+        barrier();
+        for (uint offset = 1u; offset < WORKGROUP_SIZE; offset <<= 1u) {
+            T other = lane >= offset ? partial[lane - offset] : IDENTITY;
+            barrier();
+            partial[lane] = OP(partial[lane], other);
+            barrier();
+        }
+        if (i < count)
+            data_out[i] = RESULT;
+        T total = partial[WORKGROUP_SIZE - 1u];
+        barrier();
+        carry = OP(carry, total);
+    }"
+            }
+        };
+        let result = match self.pattern {
+            Pattern::ExclusiveScan => "(lane == 0u ? carry : OP(carry, partial[lane - 1u]))",
+            _ => "OP(carry, partial[lane])",
+        };
+        format!(
+            "#version 450
+#define T {element}
+#define IDENTITY {identity}
+#define OP(a, b) {operator}
+#define RESULT {result}
+#define WORKGROUP_SIZE {WORKGROUP_SIZE}u
+layout(local_size_x = {WORKGROUP_SIZE}) in;
+layout(std430, binding = 0) readonly buffer Input {{ T data_in[]; }};
+layout(std430, binding = 1) writeonly buffer Output {{ T data_out[]; }};
+layout(push_constant) uniform Count {{ uint count; }};
+shared T partial[WORKGROUP_SIZE];
+void main()
+{{
+    uint lane = gl_LocalInvocationID.x;
+{body}
+}}
+",
+            element = self.element.glsl(),
+            identity = self.identity(),
+        )
+    }
+
+    /// `count` seeded random inputs as bits: 16-bit unsigned values,
+    /// signed values in [-1000, 1000] or floats in [-1, 1] in steps of
+    /// 1/1024.
+    pub fn inputs(&self, count: u32, seed: u64) -> Vec<u32> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                let random = crate::splitmix64(&mut state);
+                match self.element {
+                    Element::Uint => (random & 0xffff) as u32,
+                    Element::Int => ((random % 2001) as i32 - 1000) as u32,
+                    Element::Float => (((random % 2049) as f32 - 1024.0) / 1024.0).to_bits(),
+                }
+            })
+            .collect()
+    }
+
+    /// The inputs as SSBO subdata values.
+    pub fn input_values(&self, inputs: &[u32]) -> Vec<String> {
+        inputs
+            .iter()
+            .map(|&bits| self.element.value(bits).to_string())
+            .collect()
+    }
+
+    fn combine(&self, a: u32, b: u32) -> u32 {
+        match (self.operator, self.element) {
+            (Operator::Add, Element::Uint | Element::Int) => a.wrapping_add(b),
+            (Operator::Add, Element::Float) => (f32::from_bits(a) + f32::from_bits(b)).to_bits(),
+            (Operator::Min, Element::Uint) => a.min(b),
+            (Operator::Max, Element::Uint) => a.max(b),
+            (Operator::Min, Element::Int) => (a as i32).min(b as i32) as u32,
+            (Operator::Max, Element::Int) => (a as i32).max(b as i32) as u32,
+            (Operator::Min, Element::Float) => f32::from_bits(a).min(f32::from_bits(b)).to_bits(),
+            (Operator::Max, Element::Float) => f32::from_bits(a).max(f32::from_bits(b)).to_bits(),
+        }
+    }
+
+    fn identity_bits(&self) -> u32 {
+        match (self.operator, self.element) {
+            (Operator::Add, _) | (Operator::Max, Element::Uint) => 0,
+            (Operator::Min, Element::Uint) => u32::MAX,
+            (Operator::Min, Element::Int) => i32::MAX as u32,
+            (Operator::Max, Element::Int) => i32::MIN as u32,
+            (Operator::Min, Element::Float) => f32::INFINITY.to_bits(),
+            (Operator::Max, Element::Float) => f32::NEG_INFINITY.to_bits(),
+        }
+    }
+
+    /// The expected outputs with, for float sums, the sum of the
+    /// magnitudes each one adds up; its rounding bounds the error of any
+    /// summation order.
+    fn reference(&self, inputs: &[u32]) -> Vec<(f64, f64)> {
+        let float_sum = self.operator == Operator::Add && self.element == Element::Float;
+        let mut bits = self.identity_bits();
+        let (mut sum, mut magnitude) = (0.0, 0.0);
+        let current = |bits: u32, sum: f64, magnitude: f64| match float_sum {
+            true => (sum, magnitude),
+            false => (self.element.value(bits), 0.0),
+        };
+        let mut outputs = Vec::new();
+        for &input in inputs {
+            if self.pattern == Pattern::ExclusiveScan {
+                outputs.push(current(bits, sum, magnitude));
+            }
+            bits = self.combine(bits, input);
+            sum += self.element.value(input);
+            magnitude += self.element.value(input).abs();
+            if self.pattern == Pattern::InclusiveScan {
+                outputs.push(current(bits, sum, magnitude));
+            }
+        }
+        if self.pattern == Pattern::Reduce {
+            outputs.push(current(bits, sum, magnitude));
+        }
+        outputs
+    }
+
+    /// Compares the dumped output of a run on `inputs` with the reference.
+    pub fn compare(&self, inputs: &[u32], dump: &[u8]) -> Comparison {
+        let outputs = dump
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<_>>();
+        let reference = self.reference(inputs);
+
+        let mut comparison = Comparison {
+            checked: reference.len(),
+            mismatches: 0,
+            first: None,
+        };
+        for (index, (expected, magnitude)) in reference.into_iter().enumerate() {
+            let actual = outputs
+                .get(index)
+                .map_or(f64::NAN, |&bits| self.element.value(bits));
+            let bound = magnitude * inputs.len() as f64 * 2f64.powi(-24);
+            // Infinite identities of float min and max compare equal
+            let error = (actual - expected).abs();
+            if actual != expected && (error.is_nan() || error > bound) {
+                comparison.mismatches += 1;
+                comparison.first.get_or_insert((index, actual, expected));
+            }
+        }
+        comparison
+    }
+}
+
+/// How the output of a run compares with the reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub checked: usize,
+    pub mismatches: usize,
+    /// Index, result and expected value of the first mismatch.
+    pub first: Option<(usize, f64, f64)>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dump(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_reference() {
+        let kernel = Kernel {
+            pattern: Pattern::ExclusiveScan,
+            operator: Operator::Add,
+            element: Element::Uint,
+        };
+        let inputs = [3, 1, 4, 1, 5];
+        assert!(
+            kernel
+                .compare(&inputs, &dump(&[0, 3, 4, 8, 9]))
+                .first
+                .is_none()
+        );
+        assert_eq!(
+            kernel.compare(&inputs, &dump(&[0, 3, 4, 9, 9])).first,
+            Some((3, 9.0, 8.0))
+        );
+
+        let kernel = Kernel {
+            pattern: Pattern::InclusiveScan,
+            operator: Operator::Min,
+            element: Element::Int,
+        };
+        let inputs = [5, -2i32 as u32, 7, -3i32 as u32];
+        let expected = [5, -2i32 as u32, -2i32 as u32, -3i32 as u32];
+        assert_eq!(kernel.compare(&inputs, &dump(&expected)).mismatches, 0);
+
+        // Float sums may round differently in any order
+        let kernel = Kernel {
+            pattern: Pattern::Reduce,
+            operator: Operator::Add,
+            element: Element::Float,
+        };
+        let inputs = kernel.inputs(1000, 7);
+        let sum = inputs.iter().map(|&bits| f32::from_bits(bits)).sum::<f32>();
+        assert_eq!(
+            kernel.compare(&inputs, &dump(&[sum.to_bits()])).mismatches,
+            0
+        );
+        assert_eq!(
+            kernel
+                .compare(&inputs, &dump(&[(sum + 1.0).to_bits()]))
+                .mismatches,
+            1
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Known-good histogram and sort kernels and their CPU references. Like
+//! the reduction and scan kernels, the built-in ones run as a single
+//! workgroup so they are correct for any size, and shaders under test use
+//! the same interface.
+
+use rmcp::schemars::{self, JsonSchema};
+
+const WORKGROUP_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, JsonSchema)]
+pub enum Algorithm {
+    /// Counts the uint bin indices at binding 0 into the zeroed uint bins
+    /// at binding 1; the element count and bin count are uint push
+    /// constants at offsets 0 and 4.
+    Histogram,
+    /// Sorts the uint keys at binding 0 ascending in place; the element
+    /// count is a uint push constant at offset 0.
+    Sort,
+}
+
+impl Algorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Histogram => "histogram",
+            Algorithm::Sort => "sort",
+        }
+    }
+
+    /// Binding of the buffer the results are read back from.
+    pub fn output_binding(self) -> u32 {
+        match self {
+            Algorithm::Histogram => 1,
+            Algorithm::Sort => 0,
+        }
+    }
+
+    /// The built-in kernel. The histogram adds with global atomics; the
+    /// sort is a bitonic network whose comparators all put the smaller
+    /// key first, so keys past the end behave as infinite padding.
+    pub fn shader(self) -> String {
+        let body = match self {
+            Algorithm::Histogram => {
+                "layout(std430, binding = 0) readonly buffer Input { uint data_in[]; };
+layout(std430, binding = 1) buffer Bins { uint bins[]; };
+layout(push_constant) uniform Count { uint count; uint bin_count; };
+void main()
+{
+    for (uint i = gl_LocalInvocationID.x; i < count; i += WORKGROUP_SIZE)
+        atomicAdd(bins[data_in[i] % bin_count], 1u);
+}"
+            }
+            Algorithm::Sort => {
+                "layout(std430, binding = 0) coherent buffer Data { uint data[]; };
+layout(push_constant) uniform Count { uint count; };
+void main()
+{
+    uint size = 1u;
+    while (size < count)
+        size <<= 1u;
+    for (uint k = 2u; k <= size; k <<= 1u) {
+        for (uint j = k >> 1u; j > 0u; j >>= 1u) {
+            for (uint i = gl_LocalInvocationID.x; i < size; i += WORKGROUP_SIZE) {
+                uint partner = j == (k >> 1u) ? i ^ (k - 1u) : i ^ j;
+                if (partner > i && partner < count) {
+                    uint a = data[i];
+                    uint b = data[partner];
+                    if (a > b) {
+                        data[i] = b;
+                        data[partner] = a;
+                    }
+                }
+            }
+            memoryBarrierBuffer();
+            barrier();
+        }
+    }
+}"
+            }
+        };
+        format!(
+            "#version 450
+#define WORKGROUP_SIZE {WORKGROUP_SIZE}u
+layout(local_size_x = {WORKGROUP_SIZE}) in;
+{body}
+"
+        )
+    }
+
+    /// `count` seeded random inputs. Histogram bins are skewed towards
+    /// the first ones so that atomics contend; sort keys mix the full
+    /// 32-bit range, which exercises every radix digit, with many
+    /// duplicates of small keys.
+    pub fn inputs(self, count: u32, bins: u32, seed: u64) -> Vec<u32> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                let random = crate::splitmix64(&mut state);
+                match self {
+                    Algorithm::Histogram => {
+                        let a = (random as u32) % bins;
+                        let b = ((random >> 32) as u32) % bins;
+                        a.min(b)
+                    }
+                    Algorithm::Sort if random & 1 == 0 => (random >> 32) as u32,
+                    Algorithm::Sort => ((random >> 32) % 64) as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// The expected contents of the output buffer.
+    fn reference(self, inputs: &[u32], bins: u32) -> Vec<u32> {
+        match self {
+            Algorithm::Histogram => {
+                let mut counts = vec![0; bins as usize];
+                for &input in inputs {
+                    counts[(input % bins) as usize] += 1;
+                }
+                counts
+            }
+            Algorithm::Sort => {
+                let mut sorted = inputs.to_vec();
+                sorted.sort_unstable();
+                sorted
+            }
+        }
+    }
+
+    /// Compares the dumped output of a run on `inputs` with the reference.
+    pub fn compare(self, inputs: &[u32], bins: u32, dump: &[u8]) -> Comparison {
+        let outputs = dump
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<_>>();
+        let reference = self.reference(inputs, bins);
+
+        let mut comparison = Comparison {
+            checked: reference.len(),
+            mismatches: 0,
+            first: None,
+            total: outputs
+                .iter()
+                .take(reference.len())
+                .map(|&count| u64::from(count))
+                .sum(),
+        };
+        for (index, expected) in reference.into_iter().enumerate() {
+            let actual = outputs.get(index).copied();
+            if actual != Some(expected) {
+                comparison.mismatches += 1;
+                comparison.first.get_or_insert((index, actual, expected));
+            }
+        }
+        comparison
+    }
+}
+
+/// How the output of a run compares with the reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub checked: usize,
+    pub mismatches: usize,
+    /// Index, result (none past the end of the dump) and expected value
+    /// of the first mismatch.
+    pub first: Option<(usize, Option<u32>, u32)>,
+    /// Sum of the outputs, which for a histogram must be the element
+    /// count.
+    pub total: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dump(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_compare() {
+        let inputs = [2, 0, 2, 3, 2];
+        let comparison = Algorithm::Histogram.compare(&inputs, 4, &dump(&[1, 0, 3, 1]));
+        assert_eq!((comparison.mismatches, comparison.total), (0, 5));
+        let comparison = Algorithm::Histogram.compare(&inputs, 4, &dump(&[1, 0, 2, 1]));
+        assert_eq!(comparison.first, Some((2, Some(2), 3)));
+        assert_eq!(comparison.total, 4);
+
+        let inputs = Algorithm::Sort.inputs(100, 0, 3);
+        let mut sorted = inputs.clone();
+        sorted.sort_unstable();
+        assert_eq!(
+            Algorithm::Sort
+                .compare(&inputs, 0, &dump(&sorted))
+                .mismatches,
+            0
+        );
+        let comparison = Algorithm::Sort.compare(&inputs, 0, &dump(&sorted[..99]));
+        assert_eq!(comparison.first, Some((99, None, sorted[99])));
+    }
+}
@@ -0,0 +1,640 @@
+//! Minimal reader for the SPIR-V assembly text that shaderc produces.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// Result id without the leading `%`, if the instruction has one.
+    pub result_id: Option<String>,
+    pub opcode: String,
+    /// Raw operand tokens; string literals keep their quotes.
+    pub operands: Vec<String>,
+}
+
+impl Instruction {
+    /// Returns operand `index` with surrounding quotes removed.
+    pub fn string_operand(&self, index: usize) -> Option<&str> {
+        self.operands
+            .get(index)
+            .map(|operand| operand.trim_matches('"'))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EntryPoint {
+    pub execution_model: String,
+    pub name: String,
+}
+
+/// Source position recorded by `OpLine` debug info.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// File name from the `OpString` that `OpLine` refers to.
+    pub file: String,
+    pub line: u32,
+    /// Text of the line, when `OpSource` embeds the source.
+    pub text: Option<String>,
+}
+
+/// Loops of a function for `Module::loop_depth`: how deep its own loops
+/// nest, and the functions it calls with the depth at each call.
+#[derive(Debug, Default)]
+struct FunctionLoops<'a> {
+    depth: usize,
+    calls: Vec<(usize, &'a str)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub instructions: Vec<Instruction>,
+}
+
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        if in_string {
+            current.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+            current.push(c);
+        } else if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+/// Splits assembly into instructions per line, except that string
+/// literals (such as the source embedded in `OpSource`) may span lines.
+fn logical_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut in_comment = false;
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '\n' {
+            lines.push(&text[start..i]);
+            start = i + 1;
+            in_comment = false;
+        } else if c == ';' {
+            in_comment = true;
+        } else if c == '"' && !in_comment {
+            in_string = true;
+        }
+    }
+
+    lines.push(&text[start..]);
+    lines
+}
+
+/// Removes the quotes and escapes of a string literal operand.
+fn unquote(literal: &str) -> String {
+    let inner = literal
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .unwrap_or(literal);
+    let mut text = String::new();
+    let mut escaped = false;
+
+    for c in inner.chars() {
+        if escaped || c != '\\' {
+            text.push(c);
+            escaped = false;
+        } else {
+            escaped = true;
+        }
+    }
+
+    text
+}
+
+impl Module {
+    pub fn parse(text: &str) -> Module {
+        let instructions = logical_lines(text)
+            .into_iter()
+            .filter_map(|line| {
+                let mut tokens = tokenize(line).into_iter();
+                let first = tokens.next()?;
+
+                let (result_id, opcode) = if let Some(id) = first.strip_prefix('%') {
+                    if tokens.next().as_deref() != Some("=") {
+                        return None;
+                    }
+                    (Some(id.to_string()), tokens.next()?)
+                } else {
+                    (None, first)
+                };
+
+                Some(Instruction {
+                    result_id,
+                    opcode,
+                    operands: tokens.collect(),
+                })
+            })
+            .collect();
+
+        Module { instructions }
+    }
+
+    pub fn with_opcode<'a>(&'a self, opcode: &'a str) -> impl Iterator<Item = &'a Instruction> {
+        self.instructions
+            .iter()
+            .filter(move |instruction| instruction.opcode == opcode)
+    }
+
+    /// `OpEntryPoint ExecutionModel %id "name" interface...`
+    pub fn entry_points(&self) -> Vec<EntryPoint> {
+        self.with_opcode("OpEntryPoint")
+            .filter_map(|instruction| {
+                Some(EntryPoint {
+                    execution_model: instruction.operands.first()?.clone(),
+                    name: instruction.string_operand(2)?.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// `(set, binding)` pairs from `OpDecorate ... DescriptorSet/Binding`.
+    /// A binding without a set decoration is in set 0.
+    pub fn resource_bindings(&self) -> Vec<(u32, u32)> {
+        let mut sets = std::collections::HashMap::new();
+        let mut bindings = Vec::new();
+
+        for instruction in self.with_opcode("OpDecorate") {
+            let (Some(target), Some(decoration), Some(value)) = (
+                instruction.operands.first(),
+                instruction.operands.get(1),
+                instruction
+                    .operands
+                    .get(2)
+                    .and_then(|v| v.parse::<u32>().ok()),
+            ) else {
+                continue;
+            };
+
+            match decoration.as_str() {
+                "DescriptorSet" => {
+                    sets.insert(target.clone(), value);
+                }
+                "Binding" => bindings.push((target.clone(), value)),
+                _ => {}
+            }
+        }
+
+        bindings
+            .into_iter()
+            .map(|(target, binding)| (sets.get(&target).copied().unwrap_or(0), binding))
+            .collect()
+    }
+
+    /// `OpCapability Name`
+    pub fn capabilities(&self) -> Vec<&str> {
+        self.with_opcode("OpCapability")
+            .filter_map(|instruction| instruction.operands.first())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Source location of the instruction defining each result id: the
+    /// closest `OpLine` before it in the same function.
+    pub fn source_locations(&self) -> std::collections::HashMap<String, SourceLocation> {
+        self.instructions
+            .iter()
+            .zip(self.instruction_locations())
+            .filter_map(|(instruction, location)| Some((instruction.result_id.clone()?, location?)))
+            .collect()
+    }
+
+    /// Source location of each instruction, in the same order as
+    /// `instructions`.
+    pub fn instruction_locations(&self) -> Vec<Option<SourceLocation>> {
+        let mut files = std::collections::HashMap::new();
+        let mut sources = std::collections::HashMap::<String, String>::new();
+        let mut last_source = None;
+
+        for instruction in &self.instructions {
+            match instruction.opcode.as_str() {
+                "OpString" => {
+                    if let (Some(id), Some(name)) =
+                        (&instruction.result_id, instruction.operands.first())
+                    {
+                        files.insert(id.clone(), unquote(name));
+                    }
+                }
+                // `OpSource Language Version %file "text"`
+                "OpSource" => {
+                    if let (Some(file), Some(text)) =
+                        (instruction.operands.get(2), instruction.operands.get(3))
+                    {
+                        sources.insert(file.clone(), unquote(text));
+                        last_source = Some(file.clone());
+                    }
+                }
+                "OpSourceContinued" => {
+                    if let (Some(file), Some(text)) = (&last_source, instruction.operands.first()) {
+                        sources
+                            .entry(file.clone())
+                            .or_default()
+                            .push_str(&unquote(text));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // glslang prefixes the source with comments and a `#line 1`
+        let source_lines = sources
+            .iter()
+            .map(|(file, text)| {
+                let lines = text.lines().collect::<Vec<_>>();
+                let first = lines
+                    .iter()
+                    .position(|line| line.trim() == "#line 1")
+                    .map_or(0, |line| line + 1);
+                (file.as_str(), lines[first..].to_vec())
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+
+        let mut locations = Vec::new();
+        let mut current: Option<(&str, u32)> = None;
+
+        for instruction in &self.instructions {
+            match instruction.opcode.as_str() {
+                "OpLine" => {
+                    let file = instruction
+                        .operands
+                        .first()
+                        .and_then(|id| id.strip_prefix('%'));
+                    let line = instruction
+                        .operands
+                        .get(1)
+                        .and_then(|line| line.parse::<u32>().ok());
+                    current = file.zip(line);
+                }
+                "OpNoLine" | "OpFunctionEnd" => current = None,
+                _ => {}
+            }
+
+            locations.push(current.map(|(file, line)| {
+                SourceLocation {
+                    file: files
+                        .get(file)
+                        .cloned()
+                        .unwrap_or_else(|| format!("%{file}")),
+                    line,
+                    text: source_lines
+                        .get(format!("%{file}").as_str())
+                        .and_then(|lines| lines.get((line as usize).wrapping_sub(1)))
+                        .map(|text| (*text).to_string()),
+                }
+            }));
+        }
+
+        locations
+    }
+
+    /// Whether the module writes to storage buffers or images through
+    /// stores, atomics other than loads or image writes. Outside compute
+    /// shaders these need fragmentStoresAndAtomics or
+    /// vertexPipelineStoresAndAtomics.
+    pub fn writes_storage(&self) -> bool {
+        fn id(operand: Option<&String>) -> Option<&str> {
+            operand?.strip_prefix('%')
+        }
+
+        // `OpDecorate %type BufferBlock` marks storage buffers declared
+        // in the Uniform storage class before SPIR-V 1.3
+        let buffer_blocks = self
+            .with_opcode("OpDecorate")
+            .filter(|instruction| {
+                instruction.operands.get(1).map(String::as_str) == Some("BufferBlock")
+            })
+            .filter_map(|instruction| id(instruction.operands.first()))
+            .collect::<std::collections::HashSet<_>>();
+        // `%pointer = OpTypePointer Class %pointee`
+        let pointees = self
+            .with_opcode("OpTypePointer")
+            .filter_map(|instruction| {
+                Some((
+                    instruction.result_id.as_deref()?,
+                    id(instruction.operands.get(1))?,
+                ))
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+
+        let mut storage = std::collections::HashSet::new();
+        for instruction in &self.instructions {
+            let Some(result) = instruction.result_id.as_deref() else {
+                continue;
+            };
+            let operands = &instruction.operands;
+            let is_storage = match instruction.opcode.as_str() {
+                // `OpVariable %pointer Class`
+                "OpVariable" => match operands.get(1).map(String::as_str) {
+                    Some("StorageBuffer") => true,
+                    Some("Uniform") => id(operands.first())
+                        .and_then(|pointer| pointees.get(pointer))
+                        .is_some_and(|pointee| buffer_blocks.contains(pointee)),
+                    _ => false,
+                },
+                "OpAccessChain"
+                | "OpInBoundsAccessChain"
+                | "OpPtrAccessChain"
+                | "OpInBoundsPtrAccessChain"
+                | "OpCopyObject" => id(operands.get(1)).is_some_and(|base| storage.contains(base)),
+                "OpImageTexelPointer" => true,
+                _ => false,
+            };
+            if is_storage {
+                storage.insert(result);
+            }
+        }
+
+        self.instructions.iter().any(|instruction| {
+            let operands = &instruction.operands;
+            let pointer = match instruction.opcode.as_str() {
+                "OpImageWrite" => return true,
+                "OpStore" | "OpCopyMemory" | "OpAtomicStore" => operands.first(),
+                "OpAtomicLoad" => return false,
+                // `%result = OpAtomicIAdd %type %pointer ...`
+                opcode if opcode.starts_with("OpAtomic") => operands.get(1),
+                _ => return false,
+            };
+            id(pointer).is_some_and(|pointer| storage.contains(pointer))
+        })
+    }
+
+    /// Whether an `OpReadClockKHR` reads the device clock: its scope
+    /// operand is a constant 1 (Device) rather than 3 (Subgroup).
+    pub fn reads_device_clock(&self) -> bool {
+        let constants = self
+            .with_opcode("OpConstant")
+            .filter_map(|instruction| {
+                Some((
+                    instruction.result_id.as_deref()?,
+                    instruction.operands.get(1)?.as_str(),
+                ))
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+
+        self.with_opcode("OpReadClockKHR").any(|instruction| {
+            instruction
+                .operands
+                .get(1)
+                .and_then(|scope| scope.strip_prefix('%'))
+                .and_then(|scope| constants.get(scope))
+                == Some(&"1")
+        })
+    }
+
+    /// Workgroup size from `OpExecutionMode %entry LocalSize x y z`.
+    /// `LocalSizeId` sizes set by specialization constants aren't read.
+    pub fn local_size(&self) -> Option<[u32; 3]> {
+        self.with_opcode("OpExecutionMode").find_map(|instruction| {
+            let [_, mode, x, y, z] = instruction.operands.as_slice() else {
+                return None;
+            };
+            if mode != "LocalSize" {
+                return None;
+            }
+            Some([x.parse().ok()?, y.parse().ok()?, z.parse().ok()?])
+        })
+    }
+
+    /// Deepest nesting of structured loops (`OpLoopMerge`), where a loop
+    /// in a called function nests inside the loops around the call.
+    /// Relies on the blocks of a loop coming before its merge block, as
+    /// compilers emit them.
+    pub fn loop_depth(&self) -> usize {
+        let mut functions = std::collections::HashMap::<&str, FunctionLoops>::new();
+        let mut current = None;
+        let mut merges = Vec::<&str>::new();
+
+        for instruction in &self.instructions {
+            let operand = |index: usize| {
+                instruction
+                    .operands
+                    .get(index)
+                    .and_then(|operand| operand.strip_prefix('%'))
+            };
+            match (
+                instruction.opcode.as_str(),
+                instruction.result_id.as_deref(),
+            ) {
+                ("OpFunction", Some(id)) => {
+                    current = Some(id);
+                    merges.clear();
+                    functions.entry(id).or_default();
+                }
+                ("OpLabel", Some(label)) => {
+                    while merges.last() == Some(&label) {
+                        merges.pop();
+                    }
+                }
+                ("OpLoopMerge", _) => {
+                    if let (Some(function), Some(merge)) = (current, operand(0)) {
+                        merges.push(merge);
+                        let loops = functions.entry(function).or_default();
+                        loops.depth = loops.depth.max(merges.len());
+                    }
+                }
+                // `%result = OpFunctionCall %type %function arguments...`
+                ("OpFunctionCall", _) => {
+                    if let (Some(function), Some(callee)) = (current, operand(1)) {
+                        let loops = functions.entry(function).or_default();
+                        loops.calls.push((merges.len(), callee));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        fn depth<'a>(
+            function: &'a str,
+            functions: &std::collections::HashMap<&'a str, FunctionLoops<'a>>,
+            visiting: &mut Vec<&'a str>,
+        ) -> usize {
+            // Shaders can't recurse, but a malformed module shouldn't hang
+            let Some(loops) = functions.get(function) else {
+                return 0;
+            };
+            if visiting.contains(&function) {
+                return loops.depth;
+            }
+            visiting.push(function);
+            let deepest_call = loops
+                .calls
+                .iter()
+                .map(|(at, callee)| at + depth(callee, functions, visiting))
+                .max()
+                .unwrap_or(0);
+            visiting.pop();
+            loops.depth.max(deepest_call)
+        }
+
+        functions
+            .keys()
+            .map(|function| depth(function, &functions, &mut Vec::new()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// `OpVariable %type PushConstant`
+    pub fn has_push_constants(&self) -> bool {
+        self.with_opcode("OpVariable").any(|instruction| {
+            instruction.operands.get(1).map(String::as_str) == Some("PushConstant")
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let module = Module::parse(
+            "; SPIR-V
+               OpCapability Shader
+               OpCapability Int64 ; a comment
+          %1 = OpString \"test.comp\"
+               OpSource GLSL 450 %1 \"// OpModuleProcessed
+#line 1
+#version 450
+void main() {}\"
+               OpEntryPoint GLCompute %main \"main\" %gl_GlobalInvocationID
+               OpExecutionMode %main LocalSize 8 4 1
+               OpDecorate %buf DescriptorSet 1
+               OpDecorate %buf Binding 2
+               OpDecorate %img Binding 3
+       %main = OpFunction %void None %fn
+      %entry = OpLabel
+               OpLine %1 2 0
+          %x = OpLoad %uint %buf
+               OpReturn
+               OpFunctionEnd",
+        );
+
+        assert_eq!(module.capabilities(), ["Shader", "Int64"]);
+        assert_eq!(
+            module.entry_points(),
+            [EntryPoint {
+                execution_model: "GLCompute".to_string(),
+                name: "main".to_string(),
+            }]
+        );
+        assert_eq!(module.resource_bindings(), [(1, 2), (0, 3)]);
+        assert_eq!(module.local_size(), Some([8, 4, 1]));
+        assert_eq!(
+            module.source_locations().get("x"),
+            Some(&SourceLocation {
+                file: "test.comp".to_string(),
+                line: 2,
+                text: Some("void main() {}".to_string()),
+            })
+        );
+        assert!(!module.source_locations().contains_key("main"));
+    }
+
+    #[test]
+    fn test_loop_depth() {
+        // `outer` has two nested loops and calls `inner`, with one loop,
+        // from inside the first
+        let module = Module::parse(
+            "%outer = OpFunction %void None %fn
+%a = OpLabel
+OpLoopMerge %a_merge %a_continue None
+%b = OpLabel
+%call = OpFunctionCall %void %inner
+OpLoopMerge %b_merge %b_continue None
+%b_merge = OpLabel
+%a_merge = OpLabel
+OpFunctionEnd
+%inner = OpFunction %void None %fn
+%c = OpLabel
+OpLoopMerge %c_merge %c_continue None
+%c_merge = OpLabel
+OpFunctionEnd",
+        );
+        assert_eq!(module.loop_depth(), 2);
+        assert_eq!(Module::parse("OpCapability Shader").loop_depth(), 0);
+    }
+
+    #[test]
+    fn test_writes_storage() {
+        let storage = "%ptr = OpTypePointer StorageBuffer %block
+%buf = OpVariable %ptr StorageBuffer
+%element = OpAccessChain %uint_ptr %buf %zero";
+        let writes = |body: &str| Module::parse(&format!("{storage}\n{body}")).writes_storage();
+
+        assert!(writes("OpStore %element %one"));
+        assert!(writes(
+            "%old = OpAtomicIAdd %uint %element %scope %semantics %one"
+        ));
+        assert!(!writes(
+            "%old = OpAtomicLoad %uint %element %scope %semantics"
+        ));
+        assert!(!writes(
+            "%value = OpLoad %uint %element\nOpStore %local %value"
+        ));
+
+        // Storage buffers declared as Uniform BufferBlocks
+        let module = Module::parse(
+            "OpDecorate %block BufferBlock
+%ptr = OpTypePointer Uniform %block
+%buf = OpVariable %ptr Uniform
+%element = OpAccessChain %uint_ptr %buf %zero
+OpStore %element %one",
+        );
+        assert!(module.writes_storage());
+    }
+
+    #[test]
+    fn test_queries() {
+        let module = Module::parse(
+            "%device = OpConstant %uint 1
+%subgroup = OpConstant %uint 3
+%push = OpVariable %push_ptr PushConstant
+%time = OpReadClockKHR %ulong %subgroup",
+        );
+        assert!(module.has_push_constants());
+        assert!(!module.reads_device_clock());
+
+        let module = Module::parse(
+            "%device = OpConstant %uint 1
+%time = OpReadClockKHR %ulong %device",
+        );
+        assert!(!module.has_push_constants());
+        assert!(module.reads_device_clock());
+    }
+}
@@ -0,0 +1,540 @@
+//! A battery of subgroup operations checked against CPU references: one
+//! compute shader records, for every invocation, its place in its
+//! subgroup and the result of each operation, and the references are
+//! computed from the subgroups the device actually formed.
+
+use rmcp::schemars::{self, JsonSchema};
+use std::collections::BTreeMap;
+
+/// Groups of subgroup operations, each needing its own extension and
+/// device support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, JsonSchema)]
+pub enum Category {
+    Basic,
+    Vote,
+    Ballot,
+    Arithmetic,
+    Shuffle,
+    ShuffleRelative,
+    Clustered,
+    Quad,
+}
+
+impl Category {
+    pub const ALL: &[Category] = &[
+        Category::Basic,
+        Category::Vote,
+        Category::Ballot,
+        Category::Arithmetic,
+        Category::Shuffle,
+        Category::ShuffleRelative,
+        Category::Clustered,
+        Category::Quad,
+    ];
+
+    fn extension(self) -> &'static str {
+        match self {
+            Category::Basic => "GL_KHR_shader_subgroup_basic",
+            Category::Vote => "GL_KHR_shader_subgroup_vote",
+            Category::Ballot => "GL_KHR_shader_subgroup_ballot",
+            Category::Arithmetic => "GL_KHR_shader_subgroup_arithmetic",
+            Category::Shuffle => "GL_KHR_shader_subgroup_shuffle",
+            Category::ShuffleRelative => "GL_KHR_shader_subgroup_shuffle_relative",
+            Category::Clustered => "GL_KHR_shader_subgroup_clustered",
+            Category::Quad => "GL_KHR_shader_subgroup_quad",
+        }
+    }
+}
+
+/// The invocations of one subgroup: the input value of each, by
+/// `gl_SubgroupInvocationID`.
+pub struct Subgroup {
+    pub size: u32,
+    pub values: BTreeMap<u32, u32>,
+}
+
+impl Subgroup {
+    fn get(&self, lane: u32) -> Option<u32> {
+        self.values.get(&lane).copied()
+    }
+
+    fn first(&self) -> Option<u32> {
+        self.values.values().next().copied()
+    }
+
+    fn fold(&self, lanes: impl Fn(u32) -> bool, init: u32, f: fn(u32, u32) -> u32) -> u32 {
+        self.values
+            .iter()
+            .filter(|(lane, _)| lanes(**lane))
+            .fold(init, |acc, (_, value)| f(acc, *value))
+    }
+
+    /// The lanes of the cluster of `size` invocations that holds `lane`.
+    fn cluster(lane: u32, size: u32) -> impl Fn(u32) -> bool {
+        move |other| other / size == lane / size
+    }
+}
+
+/// One operation: the GLSL expression that computes it from the value
+/// `v` of each invocation, and its expected result for an invocation,
+/// if the operation defines one there.
+pub struct Operation {
+    pub name: &'static str,
+    pub category: Category,
+    expression: &'static str,
+    reference: fn(&Subgroup, u32) -> Option<u32>,
+}
+
+fn is_odd(value: &u32) -> bool {
+    value % 2 == 1
+}
+
+/// Reads another lane in the same quad, for subgroups of whole quads.
+fn quad(subgroup: &Subgroup, lane: u32) -> Option<u32> {
+    (subgroup.size >= 4).then_some(())?;
+    subgroup.get(lane)
+}
+
+pub const OPERATIONS: &[Operation] = &[
+    Operation {
+        name: "subgroupElect",
+        category: Category::Basic,
+        expression: "uint(subgroupElect())",
+        reference: |subgroup, lane| Some(u32::from(subgroup.values.keys().next() == Some(&lane))),
+    },
+    Operation {
+        name: "subgroupAll",
+        category: Category::Vote,
+        expression: "uint(subgroupAll((v & 1u) == 1u))",
+        reference: |subgroup, _| Some(u32::from(subgroup.values.values().all(is_odd))),
+    },
+    Operation {
+        name: "subgroupAny",
+        category: Category::Vote,
+        expression: "uint(subgroupAny(v % 7u == 0u))",
+        reference: |subgroup, _| {
+            Some(u32::from(
+                subgroup.values.values().any(|value| value % 7 == 0),
+            ))
+        },
+    },
+    Operation {
+        name: "subgroupAllEqual",
+        category: Category::Vote,
+        expression: "uint(subgroupAllEqual(v >> 15u))",
+        reference: |subgroup, _| {
+            let first = subgroup.first()? >> 15;
+            Some(u32::from(
+                subgroup.values.values().all(|value| value >> 15 == first),
+            ))
+        },
+    },
+    Operation {
+        name: "subgroupBroadcastFirst",
+        category: Category::Ballot,
+        expression: "subgroupBroadcastFirst(v)",
+        reference: |subgroup, _| subgroup.first(),
+    },
+    Operation {
+        name: "subgroupBallotBitCount",
+        category: Category::Ballot,
+        expression: "subgroupBallotBitCount(subgroupBallot((v & 1u) == 1u))",
+        reference: |subgroup, _| {
+            Some(
+                subgroup
+                    .values
+                    .values()
+                    .filter(|value| is_odd(value))
+                    .count() as u32,
+            )
+        },
+    },
+    Operation {
+        name: "subgroupBallotExclusiveBitCount",
+        category: Category::Ballot,
+        expression: "subgroupBallotExclusiveBitCount(subgroupBallot((v & 1u) == 1u))",
+        reference: |subgroup, lane| {
+            Some(
+                subgroup
+                    .values
+                    .range(..lane)
+                    .filter(|(_, value)| is_odd(value))
+                    .count() as u32,
+            )
+        },
+    },
+    Operation {
+        name: "subgroupBallotFindLSB",
+        category: Category::Ballot,
+        expression: "subgroupBallotFindLSB(subgroupBallot((v & 1u) == 1u))",
+        reference: |subgroup, _| {
+            subgroup
+                .values
+                .iter()
+                .find(|(_, value)| is_odd(value))
+                .map(|(lane, _)| *lane)
+        },
+    },
+    Operation {
+        name: "subgroupAdd",
+        category: Category::Arithmetic,
+        expression: "subgroupAdd(v)",
+        reference: |subgroup, _| Some(subgroup.fold(|_| true, 0, u32::wrapping_add)),
+    },
+    Operation {
+        name: "subgroupMin",
+        category: Category::Arithmetic,
+        expression: "subgroupMin(v)",
+        reference: |subgroup, _| Some(subgroup.fold(|_| true, u32::MAX, u32::min)),
+    },
+    Operation {
+        name: "subgroupMax",
+        category: Category::Arithmetic,
+        expression: "subgroupMax(v)",
+        reference: |subgroup, _| Some(subgroup.fold(|_| true, 0, u32::max)),
+    },
+    Operation {
+        name: "subgroupAnd",
+        category: Category::Arithmetic,
+        expression: "subgroupAnd(v)",
+        reference: |subgroup, _| Some(subgroup.fold(|_| true, u32::MAX, |a, b| a & b)),
+    },
+    Operation {
+        name: "subgroupOr",
+        category: Category::Arithmetic,
+        expression: "subgroupOr(v)",
+        reference: |subgroup, _| Some(subgroup.fold(|_| true, 0, |a, b| a | b)),
+    },
+    Operation {
+        name: "subgroupXor",
+        category: Category::Arithmetic,
+        expression: "subgroupXor(v)",
+        reference: |subgroup, _| Some(subgroup.fold(|_| true, 0, |a, b| a ^ b)),
+    },
+    Operation {
+        name: "subgroupInclusiveAdd",
+        category: Category::Arithmetic,
+        expression: "subgroupInclusiveAdd(v)",
+        reference: |subgroup, lane| {
+            Some(subgroup.fold(|other| other <= lane, 0, u32::wrapping_add))
+        },
+    },
+    Operation {
+        name: "subgroupExclusiveAdd",
+        category: Category::Arithmetic,
+        expression: "subgroupExclusiveAdd(v)",
+        reference: |subgroup, lane| Some(subgroup.fold(|other| other < lane, 0, u32::wrapping_add)),
+    },
+    Operation {
+        name: "subgroupExclusiveMin",
+        category: Category::Arithmetic,
+        expression: "subgroupExclusiveMin(v)",
+        reference: |subgroup, lane| Some(subgroup.fold(|other| other < lane, u32::MAX, u32::min)),
+    },
+    Operation {
+        name: "subgroupShuffle",
+        category: Category::Shuffle,
+        expression: "subgroupShuffle(v, (gl_SubgroupInvocationID + 1u) % gl_SubgroupSize)",
+        reference: |subgroup, lane| subgroup.get((lane + 1) % subgroup.size),
+    },
+    Operation {
+        name: "subgroupShuffleXor",
+        category: Category::Shuffle,
+        expression: "subgroupShuffleXor(v, 1u)",
+        reference: |subgroup, lane| subgroup.get(lane ^ 1),
+    },
+    Operation {
+        name: "subgroupShuffleUp",
+        category: Category::ShuffleRelative,
+        expression: "subgroupShuffleUp(v, 1u)",
+        reference: |subgroup, lane| subgroup.get(lane.checked_sub(1)?),
+    },
+    Operation {
+        name: "subgroupShuffleDown",
+        category: Category::ShuffleRelative,
+        expression: "subgroupShuffleDown(v, 1u)",
+        reference: |subgroup, lane| subgroup.get(lane + 1),
+    },
+    Operation {
+        name: "subgroupClusteredAdd",
+        category: Category::Clustered,
+        expression: "subgroupClusteredAdd(v, 4u)",
+        reference: |subgroup, lane| {
+            (subgroup.size >= 4).then_some(())?;
+            Some(subgroup.fold(Subgroup::cluster(lane, 4), 0, u32::wrapping_add))
+        },
+    },
+    Operation {
+        name: "subgroupClusteredMax",
+        category: Category::Clustered,
+        expression: "subgroupClusteredMax(v, 4u)",
+        reference: |subgroup, lane| {
+            (subgroup.size >= 4).then_some(())?;
+            Some(subgroup.fold(Subgroup::cluster(lane, 4), 0, u32::max))
+        },
+    },
+    Operation {
+        name: "subgroupQuadBroadcast",
+        category: Category::Quad,
+        expression: "subgroupQuadBroadcast(v, 0u)",
+        reference: |subgroup, lane| quad(subgroup, lane & !3),
+    },
+    Operation {
+        name: "subgroupQuadSwapHorizontal",
+        category: Category::Quad,
+        expression: "subgroupQuadSwapHorizontal(v)",
+        reference: |subgroup, lane| quad(subgroup, lane ^ 1),
+    },
+    Operation {
+        name: "subgroupQuadSwapVertical",
+        category: Category::Quad,
+        expression: "subgroupQuadSwapVertical(v)",
+        reference: |subgroup, lane| quad(subgroup, lane ^ 2),
+    },
+    Operation {
+        name: "subgroupQuadSwapDiagonal",
+        category: Category::Quad,
+        expression: "subgroupQuadSwapDiagonal(v)",
+        reference: |subgroup, lane| quad(subgroup, lane ^ 3),
+    },
+];
+
+/// Words before the results in each invocation's record: its subgroup
+/// size, subgroup invocation ID, subgroup ID and workgroup.
+const HEADER_WORDS: usize = 4;
+
+/// Words per invocation record.
+pub const RECORD_WORDS: usize = HEADER_WORDS + OPERATIONS.len();
+
+/// Invocations every run dispatches.
+pub const INVOCATIONS: u32 = 256;
+
+/// The input value of an invocation, as the shader computes it.
+fn value(invocation: u32) -> u32 {
+    (invocation.wrapping_mul(2_654_435_761) >> 8) & 0xffff
+}
+
+/// Compute shader running the operations of `categories` with
+/// `local_size` invocations per workgroup.
+pub fn shader(categories: &[Category], local_size: u32) -> String {
+    let mut lines = vec!["#version 450".to_string()];
+    lines.extend(
+        std::iter::once(Category::Basic)
+            .chain(categories.iter().copied())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .map(|category| format!("#extension {} : require", category.extension())),
+    );
+    lines.push(format!("layout(local_size_x = {local_size}) in;"));
+    lines.push("layout(std430, binding = 0) buffer Records { uint records[]; };".to_string());
+    lines.push("void main()\n{".to_string());
+    lines.push("    uint index = gl_GlobalInvocationID.x;".to_string());
+    lines.push("    uint v = ((index * 2654435761u) >> 8u) & 0xffffu;".to_string());
+    lines.push(format!("    uint base = index * {RECORD_WORDS}u;"));
+    lines.push("    records[base] = gl_SubgroupSize;".to_string());
+    lines.push("    records[base + 1u] = gl_SubgroupInvocationID;".to_string());
+    lines.push("    records[base + 2u] = gl_SubgroupID;".to_string());
+    lines.push("    records[base + 3u] = gl_WorkGroupID.x;".to_string());
+    for (index, operation) in OPERATIONS.iter().enumerate() {
+        if categories.contains(&operation.category) {
+            lines.push(format!(
+                "    records[base + {}u] = {};",
+                HEADER_WORDS + index,
+                operation.expression
+            ));
+        }
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// An operation whose results differ from the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+    pub operation: String,
+    /// Invocations whose result differs.
+    pub mismatches: usize,
+    /// Invocations where the operation defines a result.
+    pub checked: usize,
+    /// What went wrong at the first differing invocation.
+    pub first: String,
+}
+
+/// Records of a dump, one slice of `RECORD_WORDS` per invocation.
+fn records(dump: &[u8]) -> Vec<Vec<u32>> {
+    let words = dump
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect::<Vec<_>>();
+    words
+        .chunks_exact(RECORD_WORDS)
+        .take(INVOCATIONS as usize)
+        .map(<[u32]>::to_vec)
+        .collect()
+}
+
+/// The subgroup sizes the invocations of a dump report.
+pub fn subgroup_sizes(dump: &[u8]) -> Vec<u32> {
+    let mut sizes = records(dump)
+        .iter()
+        .map(|record| record[0])
+        .collect::<Vec<_>>();
+    sizes.sort_unstable();
+    sizes.dedup();
+    sizes
+}
+
+/// Compares the results of the operations of `categories` in a dump
+/// with their references.
+pub fn check(dump: &[u8], categories: &[Category]) -> Vec<Anomaly> {
+    let records = records(dump);
+
+    let mut subgroups = BTreeMap::<(u32, u32), Subgroup>::new();
+    // Invocations whose subgroup invocation ID is out of range or taken
+    let mut misplaced = Vec::new();
+    for (invocation, record) in (0..).zip(&records) {
+        let subgroup = subgroups
+            .entry((record[3], record[2]))
+            .or_insert_with(|| Subgroup {
+                size: record[0],
+                values: BTreeMap::new(),
+            });
+        let taken = subgroup.values.insert(record[1], value(invocation));
+        if record[1] >= subgroup.size || taken.is_some() {
+            misplaced.push(format!(
+                "invocation {invocation} reports ID {} in subgroup {} of size {}, which is out of range or shared",
+                record[1], record[2], subgroup.size
+            ));
+        }
+    }
+
+    let mut anomalies = Vec::new();
+    if let Some(first) = misplaced.first() {
+        anomalies.push(Anomaly {
+            operation: "gl_SubgroupInvocationID".to_string(),
+            mismatches: misplaced.len(),
+            checked: records.len(),
+            first: first.clone(),
+        });
+    }
+    for (index, operation) in OPERATIONS.iter().enumerate() {
+        if !categories.contains(&operation.category) {
+            continue;
+        }
+        let mut checked = 0;
+        let mut mismatches = Vec::new();
+        for (invocation, record) in (0..).zip(&records) {
+            let Some(subgroup) = subgroups.get(&(record[3], record[2])) else {
+                continue;
+            };
+            let Some(expected) = (operation.reference)(subgroup, record[1]) else {
+                continue;
+            };
+            checked += 1;
+            let actual = record[HEADER_WORDS + index];
+            if actual != expected {
+                mismatches.push(format!(
+                    "invocation {invocation} (ID {} of {}) got {actual}, expected {expected}",
+                    record[1], record[0]
+                ));
+            }
+        }
+        if let Some(first) = mismatches.first() {
+            anomalies.push(Anomaly {
+                operation: operation.name.to_string(),
+                mismatches: mismatches.len(),
+                checked,
+                first: first.clone(),
+            });
+        }
+    }
+    anomalies
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The dump of a device with subgroups of `size` in workgroups of 64
+    /// that computes every operation as the references do.
+    fn dump(size: u32) -> Vec<u32> {
+        let place = |invocation: u32| (invocation / 64, invocation % 64 / size, invocation % size);
+        let mut subgroups = BTreeMap::<(u32, u32), Subgroup>::new();
+        for invocation in 0..INVOCATIONS {
+            let (workgroup, id, lane) = place(invocation);
+            subgroups
+                .entry((workgroup, id))
+                .or_insert_with(|| Subgroup {
+                    size,
+                    values: BTreeMap::new(),
+                })
+                .values
+                .insert(lane, value(invocation));
+        }
+
+        let mut words = Vec::new();
+        for invocation in 0..INVOCATIONS {
+            let (workgroup, id, lane) = place(invocation);
+            let subgroup = &subgroups[&(workgroup, id)];
+            words.extend([size, lane, id, workgroup]);
+            words.extend(
+                OPERATIONS
+                    .iter()
+                    .map(|operation| (operation.reference)(subgroup, lane).unwrap_or(0)),
+            );
+        }
+        words
+    }
+
+    fn bytes(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let words = dump(32);
+        assert_eq!(subgroup_sizes(&bytes(&words)), [32]);
+        assert_eq!(check(&bytes(&words), Category::ALL), []);
+
+        // A wrong subgroupAdd on invocation 5
+        let add = OPERATIONS
+            .iter()
+            .position(|operation| operation.name == "subgroupAdd")
+            .unwrap();
+        let mut wrong = words.clone();
+        wrong[5 * RECORD_WORDS + HEADER_WORDS + add] += 1;
+        let anomalies = check(&bytes(&wrong), Category::ALL);
+        assert_eq!(
+            anomalies
+                .iter()
+                .map(|anomaly| (anomaly.operation.as_str(), anomaly.mismatches))
+                .collect::<Vec<_>>(),
+            [("subgroupAdd", 1)]
+        );
+        assert!(anomalies[0].first.starts_with("invocation 5 (ID 5 of 32)"));
+        // Only the categories asked for are checked
+        assert_eq!(check(&bytes(&wrong), &[Category::Vote]), []);
+
+        // Two invocations claiming the same ID
+        let mut shared = words;
+        shared[RECORD_WORDS + 1] = 0;
+        assert_eq!(
+            check(&bytes(&shared), &[])
+                .iter()
+                .map(|anomaly| (anomaly.operation.as_str(), anomaly.mismatches))
+                .collect::<Vec<_>>(),
+            [("gl_SubgroupInvocationID", 1)]
+        );
+    }
+
+    #[test]
+    fn test_shader() {
+        let shader = shader(&[Category::Quad], 64);
+        assert!(shader.contains("#extension GL_KHR_shader_subgroup_basic : require"));
+        assert!(shader.contains("#extension GL_KHR_shader_subgroup_quad : require"));
+        assert!(!shader.contains("GL_KHR_shader_subgroup_vote"));
+        assert!(shader.contains("layout(local_size_x = 64) in;"));
+        assert!(shader.contains("subgroupQuadSwapDiagonal(v)"));
+        assert!(!shader.contains("subgroupAdd(v)"));
+    }
+}
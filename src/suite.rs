@@ -0,0 +1,207 @@
+//! Suite files: named compile_run_shaders requests and shader_test
+//! scripts with tags, kept next to a project as a regression suite.
+//!
+//! ```yaml
+//! name: lighting
+//! tests:
+//!   - name: red-triangle
+//!     tags: [smoke]
+//!     request: { requests: [...], passes: [...], tests: [...] }
+//!     source_files: [triangle.vert, red.frag]
+//!   - name: legacy-blend
+//!     shader_test: blend.shader_test
+//! ```
+
+use crate::CompileRunShadersRequest;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    /// Shown in the report instead of the file name.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub tests: Vec<SuiteTest>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteTest {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A compile_run_shaders request.
+    #[serde(default)]
+    pub request: Option<CompileRunShadersRequest>,
+    /// A vkrunner script, relative to the suite file.
+    #[serde(default)]
+    pub shader_test: Option<String>,
+    /// GLSL files, relative to the suite file, whose contents replace the
+    /// sources of the request's compile requests in order.
+    #[serde(default)]
+    pub source_files: Vec<String>,
+}
+
+/// What a suite test runs.
+pub enum Check<'a> {
+    Request(&'a CompileRunShadersRequest),
+    ShaderTest(PathBuf),
+}
+
+impl Suite {
+    /// Reads a suite from YAML, or from JSON for `.json` files, and
+    /// checks that test names are unique and that each test has either
+    /// a request or a shader_test.
+    pub fn load(path: &Path) -> Result<Suite, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read suite {}: {e}", path.display()))?;
+        // Through a JSON value, so that enums take the single-key map form
+        // of tool requests rather than YAML tags
+        let suite = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            serde_json::from_str::<Suite>(&text).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_str::<serde_json::Value>(&text)
+                .map_err(|e| e.to_string())
+                .and_then(|value| serde_json::from_value::<Suite>(value).map_err(|e| e.to_string()))
+        }
+        .map_err(|e| format!("Invalid suite {}: {e}", path.display()))?;
+
+        let mut names = std::collections::HashSet::new();
+        for test in &suite.tests {
+            if !names.insert(test.name.as_str()) {
+                return Err(format!("Suite test name {:?} is used twice", test.name));
+            }
+            if test.request.is_some() == test.shader_test.is_some() {
+                return Err(format!(
+                    "Suite test {:?} needs exactly one of request and shader_test",
+                    test.name
+                ));
+            }
+            let compiles = test
+                .request
+                .as_ref()
+                .map_or(0, |request| request.requests.len());
+            if test.source_files.len() > compiles {
+                return Err(format!(
+                    "Suite test {:?} has {} source_files for {compiles} compile requests",
+                    test.name,
+                    test.source_files.len()
+                ));
+            }
+        }
+
+        Ok(suite)
+    }
+}
+
+impl SuiteTest {
+    /// Whether the filters select the test: it has one of `tags`, or
+    /// `tags` is empty, and none of `excluded`.
+    pub fn selected(&self, tags: &[String], excluded: &[String]) -> bool {
+        (tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag)))
+            && !self.tags.iter().any(|tag| excluded.contains(tag))
+    }
+
+    /// The request or script to run; scripts are found relative to
+    /// `suite_dir`, and absolute ones under /tmp.
+    pub fn check(&self, suite_dir: &Path) -> Check<'_> {
+        match (&self.request, &self.shader_test) {
+            (Some(request), _) => Check::Request(request),
+            (None, script) => {
+                Check::ShaderTest(resolve(suite_dir, script.as_deref().unwrap_or_default()))
+            }
+        }
+    }
+
+    /// The files the test reads besides the suite: its source_files or
+    /// its script.
+    pub fn inputs(&self, suite_dir: &Path) -> Vec<PathBuf> {
+        self.source_files
+            .iter()
+            .chain(&self.shader_test)
+            .map(|file| resolve(suite_dir, file))
+            .collect()
+    }
+}
+
+/// A file named in a suite: relative to `suite_dir`, or under /tmp if
+/// absolute.
+pub fn resolve(suite_dir: &Path, file: &str) -> PathBuf {
+    if Path::new(file).is_absolute() {
+        PathBuf::from(crate::tmp_path(file))
+    } else {
+        suite_dir.join(file)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SUITE: &str = "name: lighting
+tests:
+  - name: red
+    tags: [smoke]
+    request: { requests: [{ stage: Frag, source: '' }], passes: [VertPassthrough], tests: [] }
+    source_files: [red.frag]
+  - name: legacy
+    tags: [slow]
+    shader_test: legacy.shader_test
+";
+
+    fn load(name: &str, text: &str) -> Result<Suite, String> {
+        let scratch = crate::ScratchDir::new().map_err(|e| e.to_string())?;
+        let path = PathBuf::from(scratch.path(name));
+        std::fs::write(&path, text).map_err(|e| e.to_string())?;
+        Suite::load(&path)
+    }
+
+    #[test]
+    fn test_load() {
+        let suite = load("suite.yaml", SUITE).unwrap();
+        assert_eq!(suite.name.as_deref(), Some("lighting"));
+        let [red, legacy] = &suite.tests[..] else {
+            panic!("expected two tests, got {}", suite.tests.len());
+        };
+
+        let dir = Path::new("/project");
+        assert!(matches!(red.check(dir), Check::Request(request) if request.requests.len() == 1));
+        assert_eq!(red.inputs(dir), [PathBuf::from("/project/red.frag")]);
+        assert!(matches!(
+            legacy.check(dir),
+            Check::ShaderTest(path) if path == Path::new("/project/legacy.shader_test")
+        ));
+        assert_eq!(
+            resolve(dir, "/abs/legacy.shader_test"),
+            PathBuf::from("/tmp/abs/legacy.shader_test")
+        );
+
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        assert!(red.selected(&[], &[]));
+        assert!(red.selected(&tags(&["smoke"]), &[]));
+        assert!(!legacy.selected(&tags(&["smoke"]), &[]));
+        assert!(!red.selected(&[], &tags(&["smoke"])));
+
+        let json = r#"{"tests": [{"name": "legacy", "shader_test": "a.shader_test"}]}"#;
+        assert_eq!(load("suite.json", json).unwrap().tests.len(), 1);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let error = |text: &str| load("suite.yaml", text).err().unwrap_or_default();
+
+        assert!(error(&SUITE.replace("name: legacy", "name: red")).contains("is used twice"));
+        assert!(
+            error("tests:\n  - name: empty\n")
+                .contains("needs exactly one of request and shader_test")
+        );
+        assert!(
+            error(&SUITE.replace("[red.frag]", "[red.frag, blue.frag]"))
+                .contains("has 2 source_files for 1 compile requests")
+        );
+        assert!(error("tests: []\nunknown: 1\n").starts_with("Invalid suite"));
+    }
+}
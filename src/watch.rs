@@ -0,0 +1,407 @@
+//! Watch mode: suite files in a directory and the GLSL sources their
+//! tests read, rerun whenever one of them changes. Each suite's latest
+//! results are a `watch://` resource, and every rerun is also sent as a
+//! logging notification.
+
+use crate::ShadercVkrunnerMcp;
+use crate::suite::Suite;
+use rmcp::RoleServer;
+use rmcp::model::{
+    AnnotateAble, LoggingLevel, LoggingMessageNotificationParam, RawResource, Resource,
+    ResourceUpdatedNotificationParam,
+};
+use rmcp::service::Peer;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the directory is scanned for modified files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const GLSL_EXTENSIONS: &[&str] = &["vert", "tesc", "tese", "geom", "frag", "comp", "glsl"];
+
+const SUITE_EXTENSIONS: &[&str] = &["yaml", "yml", "json"];
+
+/// Latest results of one suite file.
+#[derive(Debug, Default)]
+struct SuiteResults {
+    name: String,
+    /// Why the suite could not be loaded, instead of test results.
+    error: Option<String>,
+    /// Report lines of each test, in suite order.
+    tests: Vec<(String, bool, Vec<String>)>,
+}
+
+impl SuiteResults {
+    fn report(&self) -> String {
+        if let Some(error) = &self.error {
+            return format!("Suite {}: {error}", self.name);
+        }
+
+        let passed = self.tests.iter().filter(|(_, passed, _)| *passed).count();
+        let mut lines = vec![format!(
+            "Suite {}: {passed} passed, {} failed of {} tests",
+            self.name,
+            self.tests.len() - passed,
+            self.tests.len()
+        )];
+        lines.extend(
+            self.tests
+                .iter()
+                .flat_map(|(_, _, test_lines)| test_lines.clone()),
+        );
+        lines.join("\n")
+    }
+}
+
+/// What the watcher shares with request handlers.
+#[derive(Debug)]
+pub struct WatchState {
+    pub dir: PathBuf,
+    /// Results by resource URI.
+    results: Mutex<BTreeMap<String, SuiteResults>>,
+    /// Resource URIs the client subscribed to.
+    subscribed: Mutex<HashSet<String>>,
+}
+
+impl WatchState {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            results: Mutex::default(),
+            subscribed: Mutex::default(),
+        }
+    }
+
+    /// `watch://` followed by the suite's path in the watched directory.
+    fn uri(&self, suite: &Path) -> String {
+        let relative = suite.strip_prefix(&self.dir).unwrap_or(suite);
+        format!("watch://{}", relative.display())
+    }
+
+    pub fn resources(&self) -> Vec<Resource> {
+        let results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        results
+            .iter()
+            .map(|(uri, suite)| {
+                let mut resource = RawResource::new(uri.clone(), suite.name.clone());
+                resource.description = Some("Latest watch mode results of a suite".to_string());
+                resource.mime_type = Some("text/plain".to_string());
+                resource.no_annotation()
+            })
+            .collect()
+    }
+
+    pub fn read(&self, uri: &str) -> Option<String> {
+        let results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        results.get(uri).map(SuiteResults::report)
+    }
+
+    pub fn subscribe(&self, uri: String, subscribed: bool) {
+        let mut subscriptions = self.subscribed.lock().unwrap_or_else(|e| e.into_inner());
+        if subscribed {
+            subscriptions.insert(uri);
+        } else {
+            subscriptions.remove(&uri);
+        }
+    }
+}
+
+/// Modification times of the GLSL and suite files under `dir`.
+fn scan(dir: &Path, files: &mut BTreeMap<PathBuf, SystemTime>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            scan(&path, files);
+            continue;
+        }
+        let watched = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                GLSL_EXTENSIONS.contains(&extension) || SUITE_EXTENSIONS.contains(&extension)
+            });
+        if let (true, Ok(modified)) = (watched, metadata.modified()) {
+            files.insert(path, modified);
+        }
+    }
+}
+
+fn is_suite(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| SUITE_EXTENSIONS.contains(&extension))
+}
+
+/// Starts the watcher thread, which runs every suite once and then the
+/// tests whose suite or inputs change, until the server shuts down.
+pub fn spawn(
+    server: ShadercVkrunnerMcp,
+    state: std::sync::Arc<WatchState>,
+    peer: Peer<RoleServer>,
+) {
+    let watcher = Watcher {
+        server,
+        state,
+        peer,
+        runtime: tokio::runtime::Handle::current(),
+        suites: BTreeMap::new(),
+    };
+    std::thread::spawn(move || watcher.run());
+}
+
+struct Watcher {
+    server: ShadercVkrunnerMcp,
+    state: std::sync::Arc<WatchState>,
+    peer: Peer<RoleServer>,
+    runtime: tokio::runtime::Handle,
+    /// Suites that loaded, by path.
+    suites: BTreeMap<PathBuf, Suite>,
+}
+
+impl Watcher {
+    fn run(mut self) {
+        let mut seen = BTreeMap::new();
+        while !self.server.shutdown.draining.load(Ordering::SeqCst) {
+            let mut files = BTreeMap::new();
+            scan(&self.state.dir, &mut files);
+
+            let changed = files
+                .iter()
+                .filter(|(path, modified)| seen.get(*path) != Some(*modified))
+                .map(|(path, _)| path.clone())
+                .chain(
+                    seen.keys()
+                        .filter(|path| !files.contains_key(*path))
+                        .cloned(),
+                )
+                .collect::<HashSet<_>>();
+            if !changed.is_empty() {
+                self.rerun(&files, &changed);
+            }
+
+            seen = files;
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Reloads changed suites and reruns the tests that `changed` affects.
+    fn rerun(&mut self, files: &BTreeMap<PathBuf, SystemTime>, changed: &HashSet<PathBuf>) {
+        let mut list_changed = false;
+
+        for path in changed.iter().filter(|path| is_suite(path)) {
+            let uri = self.state.uri(path);
+            if !files.contains_key(path) {
+                self.suites.remove(path);
+                list_changed |= self.results().remove(&uri).is_some();
+                continue;
+            }
+
+            let name = path.display().to_string();
+            match Suite::load(path) {
+                Ok(suite) => {
+                    let results = SuiteResults {
+                        name: suite.name.clone().unwrap_or(name),
+                        ..Default::default()
+                    };
+                    list_changed |= self.results().insert(uri, results).is_none();
+                    self.suites.insert(path.clone(), suite);
+                }
+                Err(error) => {
+                    self.log(LoggingLevel::Warning, error.clone());
+                    self.suites.remove(path);
+                    let results = SuiteResults {
+                        name,
+                        error: Some(error),
+                        ..Default::default()
+                    };
+                    list_changed |= self.results().insert(uri.clone(), results).is_none();
+                    self.updated(uri);
+                }
+            }
+        }
+        if list_changed {
+            let peer = self.peer.clone();
+            self.runtime.spawn(async move {
+                let _ = peer.notify_resource_list_changed().await;
+            });
+        }
+
+        for (path, suite) in &self.suites {
+            let suite_dir = path.parent().unwrap_or(Path::new("."));
+            let suite_changed = changed.contains(path);
+            let affected = suite
+                .tests
+                .iter()
+                .filter(|test| {
+                    suite_changed
+                        || test
+                            .inputs(suite_dir)
+                            .iter()
+                            .any(|input| changed.contains(input))
+                })
+                .collect::<Vec<_>>();
+            if affected.is_empty() && !suite_changed {
+                continue;
+            }
+
+            let uri = self.state.uri(path);
+            for test in affected {
+                let Some(_in_flight) = self.server.shutdown.enter() else {
+                    return;
+                };
+                let started = Instant::now();
+                let verdict = self
+                    .server
+                    .run_suite_test(test, suite_dir)
+                    .unwrap_or_else(|e| crate::RunVerdict {
+                        passed: false,
+                        errors: e.message.lines().map(str::to_string).collect(),
+                    });
+                let lines = crate::suite_test_lines(test, &verdict, started.elapsed());
+
+                let level = if verdict.passed {
+                    LoggingLevel::Info
+                } else {
+                    LoggingLevel::Warning
+                };
+                self.log(level, format!("{uri}: {}", lines.join("\n")));
+
+                let mut results = self.results();
+                let Some(suite_results) = results.get_mut(&uri) else {
+                    continue;
+                };
+                let entry = (test.name.clone(), verdict.passed, lines);
+                match suite_results
+                    .tests
+                    .iter_mut()
+                    .find(|(name, ..)| *name == test.name)
+                {
+                    Some(existing) => *existing = entry,
+                    None => suite_results.tests.push(entry),
+                }
+            }
+
+            if let Some(suite_results) = self.results().get_mut(&uri) {
+                suite_results.tests.sort_by_key(|(name, ..)| {
+                    suite.tests.iter().position(|test| test.name == *name)
+                });
+            }
+            self.updated(uri);
+        }
+    }
+
+    fn results(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, SuiteResults>> {
+        self.state.results.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sends a logging notification if the client wants that level.
+    fn log(&self, level: LoggingLevel, text: String) {
+        if !self.server.logs(&level) {
+            return;
+        }
+        let peer = self.peer.clone();
+        self.runtime.spawn(async move {
+            let _ = peer
+                .notify_logging_message(LoggingMessageNotificationParam {
+                    level,
+                    logger: Some("watch".to_string()),
+                    data: serde_json::Value::String(text),
+                })
+                .await;
+        });
+    }
+
+    /// Tells the client that `uri` changed, if it subscribed to it.
+    fn updated(&self, uri: String) {
+        let subscribed = self
+            .state
+            .subscribed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if !subscribed.contains(&uri) {
+            return;
+        }
+        let peer = self.peer.clone();
+        self.runtime.spawn(async move {
+            let _ = peer
+                .notify_resource_updated(ResourceUpdatedNotificationParam { uri })
+                .await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let scratch = crate::ScratchDir::new().unwrap();
+        let dir = PathBuf::from(scratch.path("watched"));
+        std::fs::create_dir_all(dir.join("shaders")).unwrap();
+        for file in [
+            "suite.yaml",
+            "notes.txt",
+            "shaders/red.frag",
+            "shaders/a.spvasm",
+        ] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        let mut files = BTreeMap::new();
+        scan(&dir, &mut files);
+        assert_eq!(
+            files.keys().cloned().collect::<Vec<_>>(),
+            [dir.join("shaders/red.frag"), dir.join("suite.yaml")]
+        );
+        assert!(is_suite(&dir.join("suite.yaml")));
+        assert!(!is_suite(&dir.join("shaders/red.frag")));
+    }
+
+    #[test]
+    fn test_results() {
+        let state = WatchState::new(PathBuf::from("/tmp/project"));
+        let uri = state.uri(Path::new("/tmp/project/suites/lighting.yaml"));
+        assert_eq!(uri, "watch://suites/lighting.yaml");
+
+        state.results.lock().unwrap().insert(
+            uri.clone(),
+            SuiteResults {
+                name: "lighting".to_string(),
+                error: None,
+                tests: vec![
+                    ("red".to_string(), true, vec!["- red: passed".to_string()]),
+                    (
+                        "blue".to_string(),
+                        false,
+                        vec!["- blue: failed".to_string()],
+                    ),
+                ],
+            },
+        );
+        assert_eq!(
+            state.read(&uri).as_deref(),
+            Some("Suite lighting: 1 passed, 1 failed of 2 tests\n- red: passed\n- blue: failed")
+        );
+        assert_eq!(state.read("watch://other.yaml"), None);
+        let resources = state.resources();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].raw.uri, uri);
+
+        let broken = SuiteResults {
+            name: "broken".to_string(),
+            error: Some("Invalid suite".to_string()),
+            tests: Vec::new(),
+        };
+        assert_eq!(broken.report(), "Suite broken: Invalid suite");
+    }
+}
@@ -0,0 +1,181 @@
+//! Timeouts after which the platform resets a GPU that stays busy with
+//! one piece of work, and estimates of how long dispatches take.
+
+use std::time::Duration;
+
+/// amdgpu's default timeout for jobs on the graphics queue.
+const AMDGPU_DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+/// Windows resets the GPU after this long unless `TdrDelay` is set.
+const WINDOWS_DEFAULT_TDR_SECS: u64 = 2;
+
+const WINDOWS_GRAPHICS_DRIVERS_KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Control\GraphicsDrivers";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchdog {
+    pub timeout: Duration,
+    /// Where the timeout came from, e.g. `Windows TdrDelay`.
+    pub source: String,
+}
+
+impl Watchdog {
+    /// Work estimated to take longer than this is split or warned about;
+    /// estimates from earlier runs are rough.
+    pub fn margin(&self) -> Duration {
+        self.timeout / 2
+    }
+}
+
+/// The watchdog of the hardware drivers on this machine, if a known one
+/// is enabled.
+pub fn detect() -> Option<Watchdog> {
+    if cfg!(windows) {
+        windows_tdr()
+    } else {
+        let text = std::fs::read_to_string("/sys/module/amdgpu/parameters/lockup_timeout").ok()?;
+        parse_lockup_timeout(&text)
+    }
+}
+
+/// Windows Timeout Detection and Recovery, turned off by `TdrLevel` 0.
+fn windows_tdr() -> Option<Watchdog> {
+    let query = |value: &str| {
+        let output = std::process::Command::new("reg")
+            .args(["query", WINDOWS_GRAPHICS_DRIVERS_KEY, "/v", value])
+            .output()
+            .ok()?;
+        parse_reg_dword(&String::from_utf8_lossy(&output.stdout))
+    };
+
+    if query("TdrLevel") == Some(0) {
+        return None;
+    }
+    Some(match query("TdrDelay") {
+        Some(secs) => Watchdog {
+            timeout: Duration::from_secs(secs),
+            source: "Windows TdrDelay".to_string(),
+        },
+        None => Watchdog {
+            timeout: Duration::from_secs(WINDOWS_DEFAULT_TDR_SECS),
+            source: "Windows TDR default".to_string(),
+        },
+    })
+}
+
+/// The value of `reg query` output such as `    TdrDelay    REG_DWORD    0x8`.
+fn parse_reg_dword(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let [_, "REG_DWORD", value] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        u64::from_str_radix(value.strip_prefix("0x")?, 16).ok()
+    })
+}
+
+/// amdgpu's `lockup_timeout` parameter: milliseconds for every queue, or
+/// a comma-separated list whose first entry is the graphics queue that
+/// vkrunner submits to. 0 selects the default and a negative value
+/// disables the timeout.
+fn parse_lockup_timeout(text: &str) -> Option<Watchdog> {
+    let first = text.trim().split(',').next()?.trim().parse::<i64>().ok()?;
+    let milliseconds = match first {
+        0 => AMDGPU_DEFAULT_TIMEOUT_MS,
+        milliseconds if milliseconds < 0 => return None,
+        milliseconds => milliseconds as u64,
+    };
+
+    Some(Watchdog {
+        timeout: Duration::from_millis(milliseconds),
+        source: "amdgpu lockup_timeout".to_string(),
+    })
+}
+
+/// The longest submission from vkrunner's `--submission-times` line,
+/// `Submissions: 3, longest 12.500 ms, total 20.000 ms`.
+pub fn longest_submission(stdout: &str) -> Option<Duration> {
+    submission_time(stdout, "longest ")
+}
+
+/// The time of all submissions together from the same line.
+pub fn total_submission_time(stdout: &str) -> Option<Duration> {
+    submission_time(stdout, "total ")
+}
+
+fn submission_time(stdout: &str, field: &str) -> Option<Duration> {
+    stdout.lines().find_map(|line| {
+        let milliseconds = line
+            .strip_prefix("Submissions: ")?
+            .split(", ")
+            .find_map(|part| part.strip_prefix(field))?
+            .strip_suffix(" ms")?
+            .parse::<f64>()
+            .ok()?;
+        Some(Duration::from_secs_f64(milliseconds / 1000.0))
+    })
+}
+
+/// Estimated duration of `invocations` at `seconds_per_invocation`.
+pub fn estimate(invocations: u64, seconds_per_invocation: f64) -> Duration {
+    Duration::try_from_secs_f64(invocations as f64 * seconds_per_invocation)
+        .unwrap_or(Duration::MAX)
+}
+
+/// Invocations that fit in `duration` at `seconds_per_invocation`, at
+/// least 1.
+pub fn invocations_within(duration: Duration, seconds_per_invocation: f64) -> u64 {
+    if seconds_per_invocation <= 0.0 {
+        return u64::MAX;
+    }
+    ((duration.as_secs_f64() / seconds_per_invocation) as u64).max(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_timeouts() {
+        let amdgpu = parse_lockup_timeout("5000,10000,10000\n").unwrap();
+        assert_eq!(amdgpu.timeout, Duration::from_secs(5));
+        assert_eq!(amdgpu.margin(), Duration::from_millis(2500));
+        assert_eq!(amdgpu.source, "amdgpu lockup_timeout");
+        assert_eq!(
+            parse_lockup_timeout("0").unwrap().timeout,
+            Duration::from_millis(AMDGPU_DEFAULT_TIMEOUT_MS)
+        );
+        assert_eq!(parse_lockup_timeout("-1"), None);
+        assert_eq!(parse_lockup_timeout("unset"), None);
+
+        let reg = format!("\n{WINDOWS_GRAPHICS_DRIVERS_KEY}\n    TdrDelay    REG_DWORD    0x8\n");
+        assert_eq!(parse_reg_dword(&reg), Some(8));
+        assert_eq!(
+            parse_reg_dword(
+                "ERROR: The system was unable to find the specified registry key or value."
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_submission_times() {
+        let stdout = "PIPELINE CREATION\nSubmissions: 3, longest 12.500 ms, total 20.000 ms\n";
+        assert_eq!(
+            longest_submission(stdout),
+            Some(Duration::from_micros(12_500))
+        );
+        assert_eq!(
+            total_submission_time(stdout),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(longest_submission("Command buffer was submitted\n"), None);
+    }
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(estimate(1_000, 0.001), Duration::from_secs(1));
+        assert_eq!(estimate(u64::MAX, f64::MAX), Duration::MAX);
+        assert_eq!(invocations_within(Duration::from_secs(2), 0.001), 2_000);
+        assert_eq!(invocations_within(Duration::from_millis(1), 1.0), 1);
+        assert_eq!(invocations_within(Duration::from_secs(1), 0.0), u64::MAX);
+    }
+}
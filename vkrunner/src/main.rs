@@ -30,7 +30,7 @@ use std::ptr;
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::ffi::c_void;
-use std::io::{self, BufWriter};
+use std::io::{self, BufRead, BufWriter, Write};
 use std::fs::File;
 extern crate vkrunner;
 use vkrunner::{Config, Executor, Source, inspect, result};
@@ -170,8 +170,20 @@ static DISASM_OPTION: &'static str = "disasm";
 static REPLACE_OPTION: &'static str = "replace";
 static QUIET_OPTION: &'static str = "quiet";
 static DEVICE_ID_OPTION: &'static str = "device-id";
-
-static OPTIONS: [Opt; 8] = [
+static PORTABILITY_OPTION: &'static str = "portability";
+static DEVICE_INFO_OPTION: &'static str = "device-info";
+static MEMORY_USAGE_OPTION: &'static str = "memory-usage";
+static SEPARATE_SUBMISSIONS_OPTION: &'static str = "separate-submissions";
+static SUBMISSION_TIMES_OPTION: &'static str = "submission-times";
+static SEPARATE_SHADER_OBJECTS_OPTION: &'static str =
+    "separate-shader-objects";
+static SERVE_OPTION: &'static str = "serve";
+
+// Line that ends the output of each run in serve mode on both the
+// stdout and the stderr, followed by the exit code of the run
+static SERVE_DONE: &'static str = "VKRUNNER-SERVE-DONE";
+
+static OPTIONS: [Opt; 15] = [
     Opt {
         short: Some('h'),
         long: HELP_OPTION,
@@ -229,6 +241,56 @@ static OPTIONS: [Opt; 8] = [
         argument_name: Some("DEVID"),
         argument_type: ArgumentType::Integer,
     },
+    Opt {
+        short: None,
+        long: PORTABILITY_OPTION,
+        help: "Also enumerate portability drivers such as MoltenVK",
+        argument_name: None,
+        argument_type: ArgumentType::Flag,
+    },
+    Opt {
+        short: None,
+        long: DEVICE_INFO_OPTION,
+        help: "Show the name and driver version of the device",
+        argument_name: None,
+        argument_type: ArgumentType::Flag,
+    },
+    Opt {
+        short: None,
+        long: MEMORY_USAGE_OPTION,
+        help: "Show the device memory allocated and the memory budget",
+        argument_name: None,
+        argument_type: ArgumentType::Flag,
+    },
+    Opt {
+        short: None,
+        long: SEPARATE_SUBMISSIONS_OPTION,
+        help: "Submit each draw and dispatch to the queue on its own",
+        argument_name: None,
+        argument_type: ArgumentType::Flag,
+    },
+    Opt {
+        short: None,
+        long: SUBMISSION_TIMES_OPTION,
+        help: "Show the number of queue submissions and the longest one",
+        argument_name: None,
+        argument_type: ArgumentType::Flag,
+    },
+    Opt {
+        short: None,
+        long: SEPARATE_SHADER_OBJECTS_OPTION,
+        help: "Create compute shaders as VK_EXT_shader_object objects",
+        argument_name: None,
+        argument_type: ArgumentType::Flag,
+    },
+    Opt {
+        short: None,
+        long: SERVE_OPTION,
+        help: "Run the tab-separated arguments on each line of stdin, \
+               keeping the Vulkan device between runs",
+        argument_name: None,
+        argument_type: ArgumentType::Flag,
+    },
 ];
 
 fn format_help(f: &mut fmt::Formatter) -> fmt::Result {
@@ -503,8 +565,6 @@ fn write_ppm(
 ) -> Result<(), Error> {
     let mut file = BufWriter::new(File::create(filename)?);
 
-    use std::io::Write;
-
     write!(
         &mut file,
         "P6\n\
@@ -572,8 +632,6 @@ fn write_buffer(
         )
     };
 
-    use std::io::Write;
-
     file.write_all(data)?;
 
     Ok(())
@@ -626,6 +684,36 @@ fn set_up_config(
         config.set_show_disassembly(true);
     }
 
+    if let Some(ArgumentValue::Flag) = options.values.get(PORTABILITY_OPTION) {
+        config.set_portability(true);
+    }
+
+    if let Some(ArgumentValue::Flag) = options.values.get(DEVICE_INFO_OPTION) {
+        config.set_show_device_info(true);
+    }
+
+    if let Some(ArgumentValue::Flag) = options.values.get(MEMORY_USAGE_OPTION) {
+        config.set_show_memory_usage(true);
+    }
+
+    if let Some(ArgumentValue::Flag) =
+        options.values.get(SEPARATE_SUBMISSIONS_OPTION)
+    {
+        config.set_separate_submissions(true);
+    }
+
+    if let Some(ArgumentValue::Flag) =
+        options.values.get(SUBMISSION_TIMES_OPTION)
+    {
+        config.set_show_submission_times(true);
+    }
+
+    if let Some(ArgumentValue::Flag) =
+        options.values.get(SEPARATE_SHADER_OBJECTS_OPTION)
+    {
+        config.set_separate_shader_objects(true);
+    }
+
     Ok(())
 }
 
@@ -645,23 +733,17 @@ fn add_token_replacements(
     }
 }
 
-fn run() -> Result<(), Error> {
-    let options = parse_options(std::env::args_os())?;
-
-    if options.values.contains_key(HELP_OPTION) || options.scripts.is_empty() {
-        return Err(Error::ShowHelp);
-    }
-
-    let config = Rc::new(RefCell::new(Config::new()));
-
-    let mut inspect_data = InspectData::new(&options);
+fn run_scripts(
+    options: &Options,
+    config: &Rc<RefCell<Config>>,
+    executor: &mut Executor,
+) -> Result<(), Error> {
+    let mut inspect_data = InspectData::new(options);
 
-    set_up_config(&config, &options, &mut inspect_data)?;
+    set_up_config(config, options, &mut inspect_data)?;
 
     let token_replacements = get_token_replacements(&options.values)?;
 
-    let mut executor = Executor::new(Rc::clone(&config));
-
     let mut overall_result = result::Result::Skip;
 
     for script_filename in options.scripts.iter() {
@@ -680,6 +762,13 @@ fn run() -> Result<(), Error> {
         overall_result = overall_result.merge(result);
     }
 
+    // The executor may outlive the inspect data in serve mode
+    {
+        let mut config = config.borrow_mut();
+        config.set_inspect_cb(None);
+        config.set_user_data(ptr::null_mut());
+    }
+
     if inspect_data.failed {
         overall_result = overall_result.merge(result::Result::Fail);
     }
@@ -696,6 +785,88 @@ fn run() -> Result<(), Error> {
     }
 }
 
+// The options that the executor creates its context with, so a run
+// that changes them needs a new executor
+fn device_selection(options: &Options) -> (Option<u32>, bool) {
+    let device_id = match options.values.get(DEVICE_ID_OPTION) {
+        Some(&ArgumentValue::Integer(device_id)) => Some(device_id),
+        _ => None,
+    };
+
+    (device_id, options.values.contains_key(PORTABILITY_OPTION))
+}
+
+// Runs the arguments on each line of stdin as if they were given on the
+// command line, with one executor for all of them so that the Vulkan
+// context is only created again when a script has other requirements.
+// The output of each run ends with a SERVE_DONE line on both streams.
+fn serve() -> Result<(), Error> {
+    let config = Rc::new(RefCell::new(Config::new()));
+    let mut executor = Executor::new(Rc::clone(&config));
+    let mut selection = None;
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let args = std::iter::once(OsString::from("vkrunner"))
+            .chain(line.split('\t').map(OsString::from));
+
+        let res = parse_options(args)
+            .map_err(Error::from)
+            .and_then(|options| {
+                if options.scripts.is_empty() {
+                    return Err(Error::ShowHelp);
+                }
+
+                let options_selection = device_selection(&options);
+
+                if selection != Some(options_selection) {
+                    executor = Executor::new(Rc::clone(&config));
+                    selection = Some(options_selection);
+                }
+
+                *config.borrow_mut() = Config::new();
+
+                run_scripts(&options, &config, &mut executor)
+            });
+
+        let code = match res {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("{}", e);
+                1
+            },
+        };
+
+        println!("{} {}", SERVE_DONE, code);
+        io::stdout().flush()?;
+        eprintln!("{} {}", SERVE_DONE, code);
+    }
+
+    Ok(())
+}
+
+fn run() -> Result<(), Error> {
+    let options = parse_options(std::env::args_os())?;
+
+    if options.values.contains_key(HELP_OPTION) {
+        return Err(Error::ShowHelp);
+    }
+
+    if options.values.contains_key(SERVE_OPTION) {
+        return serve();
+    }
+
+    if options.scripts.is_empty() {
+        return Err(Error::ShowHelp);
+    }
+
+    let config = Rc::new(RefCell::new(Config::new()));
+
+    let mut executor = Executor::new(Rc::clone(&config));
+
+    run_scripts(&options, &config, &mut executor)
+}
+
 fn main() -> ExitCode {
     match run() {
         Ok(()) => ExitCode::SUCCESS,
@@ -852,6 +1023,25 @@ mod test {
         );
     }
 
+    #[test]
+    fn serve_device_selection() {
+        let args = vec![
+            "vkrunner".into(),
+            "--device-id=2".into(),
+            "--portability".into(),
+            "script.shader_test".into(),
+        ];
+
+        let options = parse_options(args.into_iter()).unwrap();
+        assert_eq!(device_selection(&options), (Some(2), true));
+
+        let args = vec!["vkrunner".into(), "--serve".into()];
+
+        let options = parse_options(args.into_iter()).unwrap();
+        assert!(options.values.contains_key(SERVE_OPTION));
+        assert_eq!(device_selection(&options), (None, false));
+    }
+
     #[test]
     fn argument_for_flag() {
         let args = vec!["vkrunner".into(), "--quiet=yes".into()].into_iter();
@@ -55,6 +55,7 @@ fn allocate_memory(
     context: &Context,
     reqs: &vk::VkMemoryRequirements,
     memory_type_flags: vk::VkMemoryPropertyFlags,
+    allocate_flags: vk::VkMemoryAllocateFlags,
 ) -> Result<(vk::VkDeviceMemory, u32), String> {
     let memory_type_index = find_memory_type(
         context,
@@ -64,9 +65,22 @@ fn allocate_memory(
 
     let mut memory = vk::null_handle();
 
+    let flags_info = vk::VkMemoryAllocateFlagsInfo {
+        sType: vk::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_FLAGS_INFO,
+        pNext: ptr::null(),
+        flags: allocate_flags,
+        deviceMask: 0,
+    };
+
     let allocate_info = vk::VkMemoryAllocateInfo {
         sType: vk::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
-        pNext: ptr::null(),
+        // Only chain the flags struct when it is needed so that
+        // drivers without Vulkan 1.1 never see it.
+        pNext: if allocate_flags == 0 {
+            ptr::null()
+        } else {
+            ptr::addr_of!(flags_info).cast()
+        },
         allocationSize: reqs.size,
         memoryTypeIndex: memory_type_index,
     };
@@ -88,15 +102,20 @@ fn allocate_memory(
 /// Allocate Vulkan device memory for the given buffer. It will pick
 /// the right memory type by querying the device for the memory
 /// requirements of the buffer. You can also limit the memory types
-/// further by specifying extra flags in `memory_type_flags`. A handle
-/// to the newly allocate device memory will be returned along with an
-/// index representing the chosen memory type.
+/// further by specifying extra flags in `memory_type_flags`.
+/// `allocate_flags` is passed to the allocation in a
+/// `VkMemoryAllocateFlagsInfo` struct, for example to request
+/// `VK_MEMORY_ALLOCATE_DEVICE_ADDRESS_BIT`. It can be zero if no
+/// flags are needed. A handle to the newly allocate device memory will
+/// be returned along with an index representing the chosen memory
+/// type.
 ///
 /// If the allocation fails a `String` will be returned describing the
 /// error.
 pub fn allocate_buffer(
     context: &Context,
     memory_type_flags: vk::VkMemoryPropertyFlags,
+    allocate_flags: vk::VkMemoryAllocateFlags,
     buffer: vk::VkBuffer,
 ) -> Result<(vk::VkDeviceMemory, u32), String> {
     let vkdev = context.device();
@@ -112,7 +131,9 @@ pub fn allocate_buffer(
     }
 
     let (memory, memory_type_index) =
-        allocate_memory(context, &reqs, memory_type_flags)?;
+        allocate_memory(context, &reqs, memory_type_flags, allocate_flags)?;
+
+    context.record_buffer_allocation(reqs.size);
 
     unsafe {
         vkdev.vkBindBufferMemory.unwrap()(
@@ -153,7 +174,9 @@ pub fn allocate_image(
     }
 
     let (memory, memory_type_index) =
-        allocate_memory(context, &reqs, memory_type_flags)?;
+        allocate_memory(context, &reqs, memory_type_flags, 0)?;
+
+    context.record_image_allocation(reqs.size);
 
     unsafe {
         vkdev.vkBindImageMemory.unwrap()(
@@ -185,7 +208,7 @@ mod test {
             }
         );
 
-        let res = allocate_buffer(context, memory_type_flags, buffer);
+        let res = allocate_buffer(context, memory_type_flags, 0, buffer);
 
         fake_vulkan.get_handle_mut(buffer).freed = true;
 
@@ -267,6 +290,9 @@ mod test {
         // allowed for the buffer requirements.
         assert_eq!(memory_type, 3);
 
+        // The allocation counts towards the memory report
+        assert_eq!(context.allocations().buffers, 1);
+
         unsafe {
             context.device().vkFreeMemory.unwrap()(
                 context.vk_device(),
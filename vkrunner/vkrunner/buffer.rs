@@ -124,11 +124,13 @@ impl DeviceMemory {
     pub fn new_buffer(
         context: Rc<Context>,
         memory_type_flags: vk::VkMemoryPropertyFlags,
+        allocate_flags: vk::VkMemoryAllocateFlags,
         buffer: vk::VkBuffer,
     ) -> Result<DeviceMemory, Error> {
         let res = allocate_store::allocate_buffer(
             context.as_ref(),
             memory_type_flags,
+            allocate_flags,
             buffer,
         );
         DeviceMemory::new_from_result(context, res)
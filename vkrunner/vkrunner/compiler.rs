@@ -28,6 +28,7 @@ use crate::logger::Logger;
 use crate::context::Context;
 use crate::script::{Script, Shader};
 use crate::temp_file;
+use crate::reflect;
 use crate::requirements::extract_version;
 use std::fmt;
 use std::mem;
@@ -46,7 +47,7 @@ use fake_process::{Command, Output};
 use std::process::{Command, Output};
 use std::process::Stdio;
 
-/// An error that can be returned by [build_stage].
+/// An error that can be returned by [build_stage] or [build_stage_code].
 #[derive(Debug)]
 pub enum Error {
     /// There were no shaders for this stage in the script
@@ -62,6 +63,12 @@ pub enum Error {
     /// The generated shader binary didn’t have the right SPIR-V magic
     /// number or wasn’t a multiple of 32-bit integers.
     InvalidShaderBinary,
+    /// The shader declares more workgroup shared memory than the
+    /// device allows
+    SharedMemoryTooLarge { size: u64, max: u32 },
+    /// The shader declares a push constant block bigger than the
+    /// device allows
+    PushConstantsTooLarge { size: u64, max: u32 },
 }
 
 impl fmt::Display for Error {
@@ -86,6 +93,23 @@ impl fmt::Display for Error {
                 f,
                 "The compiler or assembler generated an invalid SPIR-V binary",
             ),
+            Error::SharedMemoryTooLarge { size, max } => write!(
+                f,
+                "The shader declares {} bytes of shared memory, {} more \
+                 than the device allows ({}, maxComputeSharedMemorySize)",
+                size,
+                size - *max as u64,
+                max,
+            ),
+            Error::PushConstantsTooLarge { size, max } => write!(
+                f,
+                "The shader declares {} bytes of push constants but the \
+                 device only allows {} (maxPushConstantsSize). Move the \
+                 larger members into a uniform buffer and keep only small, \
+                 frequently changing values in push constants.",
+                size,
+                max,
+            ),
         }
     }
 }
@@ -146,6 +170,47 @@ fn show_disassembly_from_file(
     )
 }
 
+fn device_limits(context: &Context) -> vk::VkPhysicalDeviceLimits {
+    let mut props = vk::VkPhysicalDeviceProperties::default();
+
+    unsafe {
+        context.instance().vkGetPhysicalDeviceProperties.unwrap()(
+            context.physical_device(),
+            &mut props as *mut vk::VkPhysicalDeviceProperties,
+        );
+    }
+
+    props.limits
+}
+
+// Checking the declared sizes against the limits here gives a better
+// message than the pipeline creation failing
+fn check_footprint(context: &Context, data: &[u32]) -> Result<(), Error> {
+    let footprint = reflect::footprint(data);
+
+    if footprint == reflect::Footprint::default() {
+        return Ok(());
+    }
+
+    let limits = device_limits(context);
+
+    if footprint.workgroup > limits.maxComputeSharedMemorySize as u64 {
+        return Err(Error::SharedMemoryTooLarge {
+            size: footprint.workgroup,
+            max: limits.maxComputeSharedMemorySize,
+        });
+    }
+
+    if footprint.push_constants > limits.maxPushConstantsSize as u64 {
+        return Err(Error::PushConstantsTooLarge {
+            size: footprint.push_constants,
+            max: limits.maxPushConstantsSize,
+        });
+    }
+
+    Ok(())
+}
+
 fn create_shader_from_binary(
     context: &Context,
     data: &[u32],
@@ -176,10 +241,7 @@ fn create_shader_from_binary(
     }
 }
 
-fn create_shader_from_binary_file(
-    context: &Context,
-    file: &mut File,
-) -> Result<vk::VkShaderModule, Error> {
+fn read_binary_file(file: &mut File) -> Result<Vec<u32>, Error> {
     let mut data = Vec::<u32>::new();
     let mut buf = vec![0u8; 512];
     let mut buf_length = 0usize;
@@ -227,7 +289,7 @@ fn create_shader_from_binary_file(
     if buf_length != 0 {
         Err(Error::InvalidShaderBinary)
     } else {
-        create_shader_from_binary(context, &data)
+        Ok(data)
     }
 }
 
@@ -249,11 +311,10 @@ fn version_string(version: u32) -> String {
 
 fn compile_glsl(
     logger: &mut Logger,
-    context: &Context,
     script: &Script,
     stage: shader_stage::Stage,
     show_disassembly: bool,
-) -> Result<vk::VkShaderModule, Error> {
+) -> Result<Vec<u32>, Error> {
     let mut shader_files = Vec::new();
 
     for shader in script.shaders(stage) {
@@ -295,16 +356,15 @@ fn compile_glsl(
         show_disassembly_from_file(logger, module_file.filename())?;
     }
 
-    create_shader_from_binary_file(context, module_file.file().unwrap())
+    read_binary_file(module_file.file().unwrap())
 }
 
 fn assemble_spirv(
     logger: &mut Logger,
-    context: &Context,
     script: &Script,
     source: &str,
     show_disassembly: bool,
-) -> Result<vk::VkShaderModule, Error> {
+) -> Result<Vec<u32>, Error> {
     let version_str = version_string(script.requirements().version());
 
     let mut module_file = temp_file::TempFile::new()?;
@@ -333,15 +393,14 @@ fn assemble_spirv(
         show_disassembly_from_file(logger, module_file.filename())?;
     }
 
-    create_shader_from_binary_file(context, module_file.file().unwrap())
+    read_binary_file(module_file.file().unwrap())
 }
 
 fn load_binary_stage(
     logger: &mut Logger,
-    context: &Context,
     data: &[u32],
     show_disassembly: bool,
-) -> Result<vk::VkShaderModule, Error> {
+) -> Result<Vec<u32>, Error> {
     if show_disassembly {
         let mut temp_file = temp_file::TempFile::new()?;
         let mut writer = BufWriter::new(temp_file.file().unwrap());
@@ -359,23 +418,25 @@ fn load_binary_stage(
         show_disassembly_from_file(logger, temp_file.filename())?;
     }
 
-    create_shader_from_binary(context, data)
+    Ok(data.to_vec())
 }
 
-pub fn build_stage(
+/// Compiles or assembles the shaders for `stage` and returns the
+/// SPIR-V binary. This is used directly for shader objects, which
+/// take the code instead of a `VkShaderModule`.
+pub fn build_stage_code(
     logger: &mut Logger,
     context: &Context,
     script: &Script,
     stage: shader_stage::Stage,
     show_disassembly: bool,
-) -> Result<vk::VkShaderModule, Error> {
+) -> Result<Vec<u32>, Error> {
     let shaders = script.shaders(stage);
 
-    match shaders.get(0) {
+    let code = match shaders.get(0) {
         None => Err(Error::MissingStageShaders(stage)),
         Some(Shader::Glsl(_)) => compile_glsl(
             logger,
-            context,
             script,
             stage,
             show_disassembly,
@@ -386,7 +447,6 @@ pub fn build_stage(
             assert_eq!(shaders.len(), 1);
             assemble_spirv(
                 logger,
-                context,
                 script,
                 source,
                 show_disassembly
@@ -398,12 +458,33 @@ pub fn build_stage(
             assert_eq!(shaders.len(), 1);
             load_binary_stage(
                 logger,
-                context,
                 data,
                 show_disassembly
             )
         },
-    }
+    }?;
+
+    check_footprint(context, &code)?;
+
+    Ok(code)
+}
+
+pub fn build_stage(
+    logger: &mut Logger,
+    context: &Context,
+    script: &Script,
+    stage: shader_stage::Stage,
+    show_disassembly: bool,
+) -> Result<vk::VkShaderModule, Error> {
+    let code = build_stage_code(
+        logger,
+        context,
+        script,
+        stage,
+        show_disassembly,
+    )?;
+
+    create_shader_from_binary(context, &code)
 }
 
 #[cfg(test)]
@@ -660,6 +741,62 @@ mod test {
         );
     }
 
+    #[test]
+    fn shared_memory_too_large() {
+        let CompileOutput { result, .. } = compile_script(
+            "[compute shader binary]\n\
+             07230203 00010000 00000000 00000064 00000000\n\
+             # %1 = OpTypeFloat 32\n\
+             00030016 00000001 00000020\n\
+             # %2 = OpConstant %1 8192\n\
+             0004002b 00000001 00000002 00002000\n\
+             # %3 = OpTypeArray %1 %2\n\
+             0004001c 00000003 00000001 00000002\n\
+             # %4 = OpTypePointer Workgroup %3\n\
+             00040020 00000004 00000004 00000003\n\
+             # %5 = OpVariable %4 Workgroup\n\
+             0004003b 00000004 00000005 00000004\n",
+            shader_stage::Stage::Compute,
+            false, // show_disassembly
+        );
+        assert_eq!(
+            &result.unwrap_err().to_string(),
+            "The shader declares 32768 bytes of shared memory, 16384 more \
+             than the device allows (16384, maxComputeSharedMemorySize)",
+        );
+    }
+
+    #[test]
+    fn push_constants_too_large() {
+        let CompileOutput { result, .. } = compile_script(
+            "[vertex shader binary]\n\
+             07230203 00010000 00000000 00000064 00000000\n\
+             # OpMemberDecorate %4 0 Offset 0\n\
+             00050048 00000004 00000000 00000023 00000000\n\
+             # %1 = OpTypeFloat 32\n\
+             00030016 00000001 00000020\n\
+             # %2 = OpConstant %1 64\n\
+             0004002b 00000001 00000002 00000040\n\
+             # %3 = OpTypeArray %1 %2\n\
+             0004001c 00000003 00000001 00000002\n\
+             # %4 = OpTypeStruct %3\n\
+             0003001e 00000004 00000003\n\
+             # %5 = OpTypePointer PushConstant %4\n\
+             00040020 00000005 00000009 00000004\n\
+             # %6 = OpVariable %5 PushConstant\n\
+             0004003b 00000005 00000006 00000009\n",
+            shader_stage::Stage::Vertex,
+            false, // show_disassembly
+        );
+        assert_eq!(
+            &result.unwrap_err().to_string(),
+            "The shader declares 256 bytes of push constants but the device \
+             only allows 128 (maxPushConstantsSize). Move the larger members \
+             into a uniform buffer and keep only small, frequently changing \
+             values in push constants.",
+        );
+    }
+
     #[test]
     fn invalid_magic() {
         let CompileOutput { result, .. } = compile_script(
@@ -34,7 +34,13 @@ pub type ErrorCallback = logger::WriteCallback;
 
 pub struct Config {
     show_disassembly: bool,
+    show_device_info: bool,
+    show_memory_usage: bool,
+    separate_submissions: bool,
+    show_submission_times: bool,
+    separate_shader_objects: bool,
     device_id: Option<usize>,
+    portability: bool,
 
     error_cb: Option<logger::WriteCallback>,
     inspect_cb: Option<inspect::Callback>,
@@ -47,7 +53,13 @@ impl Config {
     pub fn new() -> Config {
         Config {
             show_disassembly: false,
+            show_device_info: false,
+            show_memory_usage: false,
+            separate_submissions: false,
+            show_submission_times: false,
+            separate_shader_objects: false,
             device_id: None,
+            portability: false,
             error_cb: None,
             inspect_cb: None,
             user_data: ptr::null_mut(),
@@ -66,6 +78,54 @@ impl Config {
         self.show_disassembly = show_disassembly;
     }
 
+    /// Sets whether the name and driver version of the device should
+    /// be reported whenever a context is created for a script. They
+    /// are written the same way as the disassembly, one `Device:`,
+    /// `Driver version:` and `Vulkan version:` line each.
+    pub fn set_show_device_info(&mut self, show_device_info: bool) {
+        self.show_device_info = show_device_info;
+    }
+
+    /// Sets whether to report the device memory that each script
+    /// allocates for its buffers and images, and the budget and usage
+    /// of the memory heaps before and after it runs when the device
+    /// supports `VK_EXT_memory_budget`. The report is written after
+    /// the script runs, even when it fails, as lines starting with
+    /// `Memory`.
+    pub fn set_show_memory_usage(&mut self, show_memory_usage: bool) {
+        self.show_memory_usage = show_memory_usage;
+    }
+
+    /// Sets whether each draw and dispatch should be submitted to the
+    /// queue on its own, waiting for it to finish before recording the
+    /// next command. This keeps long scripts from piling up work in a
+    /// single submission, which GPU watchdogs such as Windows TDR may
+    /// time as a whole.
+    pub fn set_separate_submissions(&mut self, separate_submissions: bool) {
+        self.separate_submissions = separate_submissions;
+    }
+
+    /// Sets whether to report the number of queue submissions each
+    /// script makes and the time until the longest one finished. The
+    /// report is written after the script runs, even when it fails, as
+    /// a line starting with `Submissions`.
+    pub fn set_show_submission_times(&mut self, show_submission_times: bool) {
+        self.show_submission_times = show_submission_times;
+    }
+
+    /// Sets whether compute shaders should be created as separate
+    /// shader objects with `VK_EXT_shader_object` instead of compute
+    /// pipelines. The `shaderObject` feature is then required by
+    /// every script that has a compute shader. Graphics stages still
+    /// use pipelines because shader objects can only be drawn with
+    /// dynamic rendering.
+    pub fn set_separate_shader_objects(
+        &mut self,
+        separate_shader_objects: bool,
+    ) {
+        self.separate_shader_objects = separate_shader_objects;
+    }
+
     /// Sets or removes a callback that will receive error messages
     /// generated during the script execution. The callback will be
     /// invoked one line at a time without the trailing newline
@@ -102,6 +162,15 @@ impl Config {
         self.device_id = device_id;
     }
 
+    /// Sets whether the Vulkan instance should be created with the
+    /// `VK_KHR_portability_enumeration` extension so that
+    /// non-conformant portability implementations such as MoltenVK
+    /// are included in the device list. The extension is only
+    /// enabled if the loader advertises it.
+    pub fn set_portability(&mut self, portability: bool) {
+        self.portability = portability;
+    }
+
     /// Get a logger that will write to the current `error_cb` of the
     /// `Config`. The logger will be shared between calls to this
     /// until the callback is changed.
@@ -123,10 +192,34 @@ impl Config {
         self.show_disassembly
     }
 
+    pub(crate) fn show_device_info(&self) -> bool {
+        self.show_device_info
+    }
+
+    pub(crate) fn show_memory_usage(&self) -> bool {
+        self.show_memory_usage
+    }
+
+    pub(crate) fn separate_submissions(&self) -> bool {
+        self.separate_submissions
+    }
+
+    pub(crate) fn show_submission_times(&self) -> bool {
+        self.show_submission_times
+    }
+
+    pub(crate) fn separate_shader_objects(&self) -> bool {
+        self.separate_shader_objects
+    }
+
     pub(crate) fn device_id(&self) -> Option<usize> {
         self.device_id
     }
 
+    pub(crate) fn portability(&self) -> bool {
+        self.portability
+    }
+
     fn reset_logger(&self) {
         // Reset the logger back to None so that it will be
         // reconstructed the next time it is requested.
@@ -140,7 +233,10 @@ impl fmt::Debug for Config {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Config")
             .field("show_disassembly", &self.show_disassembly)
+            .field("show_device_info", &self.show_device_info)
+            .field("show_memory_usage", &self.show_memory_usage)
             .field("device_id", &self.device_id)
+            .field("portability", &self.portability)
             .field("user_data", &self.user_data)
             .finish()
     }
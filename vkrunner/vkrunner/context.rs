@@ -27,10 +27,14 @@ use crate::requirements::{self, Requirements};
 use crate::vulkan_funcs;
 use crate::util::env_var_as_boolean;
 use crate::result;
+use crate::memory_usage::Allocations;
+use std::cell::Cell;
 use std::ffi::{c_char, c_void, CStr};
 use std::fmt;
 use std::ptr;
 
+const PORTABILITY_SUBSET_EXTENSION: &CStr = c"VK_KHR_portability_subset";
+
 /// Struct containing the VkDevice and accessories such as a
 /// VkCommandPool, VkQueue and the function pointers from
 /// [vulkan_funcs].
@@ -55,6 +59,8 @@ pub struct Context {
     queue: vk::VkQueue,
 
     always_flush_memory: bool,
+
+    allocations: Cell<Allocations>,
 }
 
 /// Error returned by [Context::new]
@@ -279,7 +285,8 @@ struct InstancePair {
 impl InstancePair {
     fn new(
         vklib: &vulkan_funcs::Library,
-        requirements: &Requirements
+        requirements: &Requirements,
+        portability: bool,
     ) -> Result<InstancePair, Error> {
         let application_info = vk::VkApplicationInfo {
             sType: vk::VK_STRUCTURE_TYPE_APPLICATION_INFO,
@@ -312,6 +319,25 @@ impl InstancePair {
             enabled_extensions.push(ext.as_ptr().cast());
         }
 
+        // Portability implementations are only enumerated if the
+        // extension is enabled together with the create flag. If the
+        // loader doesn’t know about the extension then there can’t be
+        // any portability drivers to find anyway so it is silently
+        // skipped.
+        if portability {
+            let ext = vk::VK_KHR_PORTABILITY_ENUMERATION_EXTENSION_NAME;
+
+            match check_instance_extension(vklib, ext) {
+                Ok(()) => {
+                    enabled_extensions.push(ext.as_ptr().cast());
+                    instance_create_info.flags |=
+                        vk::VK_INSTANCE_CREATE_ENUMERATE_PORTABILITY_BIT_KHR;
+                },
+                Err(Error::MissingInstanceExtension(_)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
         instance_create_info.enabledExtensionCount =
             enabled_extensions.len() as u32;
         instance_create_info.ppEnabledExtensionNames =
@@ -400,10 +426,27 @@ impl DevicePair {
         requirements: &Requirements,
         physical_device: vk::VkPhysicalDevice,
         queue_family: u32,
+        portability: bool,
     ) -> Result<DevicePair, Error> {
         let structures = requirements.c_structures();
         let base_features = requirements.c_base_features();
-        let extensions = requirements.c_extensions();
+        let mut extensions = requirements.c_extensions().to_vec();
+
+        // A portability implementation must have the subset
+        // extension enabled if it advertises it. The extension is
+        // only in the beta headers so the name isn’t in the bindings.
+        if portability {
+            let available = Requirements::get_device_extensions(
+                &instance_pair.vkinst,
+                physical_device,
+            )?;
+
+            let ext = PORTABILITY_SUBSET_EXTENSION;
+
+            if available.contains(ext.to_str().unwrap()) {
+                extensions.push(ext.as_ptr().cast());
+            }
+        }
 
         let queue_priorities = [1.0f32];
 
@@ -836,7 +879,8 @@ impl Context {
             always_flush_memory: env_var_as_boolean(
                 "VKRUNNER_ALWAYS_FLUSH_MEMORY",
                 false
-            )
+            ),
+            allocations: Cell::new(Allocations::default()),
         })
     }
 
@@ -849,10 +893,26 @@ impl Context {
     pub fn new(
         requirements: &Requirements,
         device_id: Option<usize>,
+    ) -> Result<Context, Error> {
+        Context::new_with_portability(requirements, device_id, false)
+    }
+
+    /// Same as [Context::new] but if `portability` is true then the
+    /// instance will also enumerate portability implementations such
+    /// as MoltenVK when the loader supports
+    /// `VK_KHR_portability_enumeration`.
+    pub fn new_with_portability(
+        requirements: &Requirements,
+        device_id: Option<usize>,
+        portability: bool,
     ) -> Result<Context, Error> {
         let vklib = Box::new(vulkan_funcs::Library::new()?);
 
-        let instance_pair = InstancePair::new(vklib.as_ref(), requirements)?;
+        let instance_pair = InstancePair::new(
+            vklib.as_ref(),
+            requirements,
+            portability,
+        )?;
 
         let (physical_device, queue_family) = find_physical_device(
             &instance_pair,
@@ -865,6 +925,7 @@ impl Context {
             requirements,
             physical_device,
             queue_family,
+            portability,
         )?;
 
         Context::new_internal(
@@ -977,6 +1038,28 @@ impl Context {
     pub fn always_flush_memory(&self) -> bool {
         self.always_flush_memory
     }
+
+    /// Get the totals of the device memory allocated for buffers and
+    /// images through this context so far. Freed memory isn’t
+    /// subtracted.
+    #[inline]
+    pub(crate) fn allocations(&self) -> Allocations {
+        self.allocations.get()
+    }
+
+    pub(crate) fn record_buffer_allocation(&self, size: vk::VkDeviceSize) {
+        let mut allocations = self.allocations.get();
+        allocations.buffers += 1;
+        allocations.buffer_bytes += size;
+        self.allocations.set(allocations);
+    }
+
+    pub(crate) fn record_image_allocation(&self, size: vk::VkDeviceSize) {
+        let mut allocations = self.allocations.get();
+        allocations.images += 1;
+        allocations.image_bytes += size;
+        self.allocations.set(allocations);
+    }
 }
 
 impl Drop for Context {
@@ -1088,6 +1171,67 @@ mod test {
         );
     }
 
+    #[test]
+    fn portability_enumeration() {
+        let mut fake_vulkan = FakeVulkan::new();
+        fake_vulkan.physical_devices.push(Default::default());
+
+        // Without the extension the instance should still be created
+        fake_vulkan.set_override();
+        Context::new_with_portability(&Requirements::new(), None, true)
+            .unwrap();
+
+        fake_vulkan.add_instance_extension("VK_KHR_portability_enumeration");
+        fake_vulkan.set_override();
+        Context::new_with_portability(&Requirements::new(), None, true)
+            .unwrap();
+
+        // Other errors while checking the extension are still reported
+        fake_vulkan.queue_result(
+            "vkEnumerateInstanceExtensionProperties".to_string(),
+            vk::VK_ERROR_UNKNOWN,
+        );
+        fake_vulkan.set_override();
+        let err = Context::new_with_portability(
+            &Requirements::new(),
+            None,
+            true,
+        ).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "vkEnumerateInstanceExtensionProperties failed",
+        );
+    }
+
+    #[test]
+    fn portability_subset() {
+        let mut fake_vulkan = FakeVulkan::new();
+        fake_vulkan.physical_devices.push(Default::default());
+        fake_vulkan.physical_devices[0].add_extension(
+            "VK_KHR_portability_subset"
+        );
+
+        // The extension is left alone unless portability is requested
+        fake_vulkan.set_override();
+        Context::new(&Requirements::new(), None).unwrap();
+        assert!(fake_vulkan.device_extensions.is_empty());
+
+        fake_vulkan.set_override();
+        Context::new_with_portability(&Requirements::new(), None, true)
+            .unwrap();
+        assert_eq!(
+            fake_vulkan.device_extensions,
+            ["VK_KHR_portability_subset"],
+        );
+
+        // Devices that don’t advertise it don’t get it enabled
+        fake_vulkan.physical_devices[0].extensions.clear();
+        fake_vulkan.set_override();
+        Context::new_with_portability(&Requirements::new(), None, true)
+            .unwrap();
+        assert!(fake_vulkan.device_extensions.is_empty());
+    }
+
     #[test]
     fn extension_feature() {
         let mut fake_vulkan = FakeVulkan::new();
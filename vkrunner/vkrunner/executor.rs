@@ -27,12 +27,14 @@ use crate::context::{self, Context};
 use crate::window::{Window, WindowError};
 use crate::config::Config;
 use crate::script::Script;
+use crate::shader_stage::Stage;
 use crate::source::Source;
 use crate::result;
 use crate::vk;
 use crate::requirements::Requirements;
 use crate::pipeline_set::{self, PipelineSet};
 use crate::tester;
+use crate::memory_usage;
 use crate::requirements;
 use std::ffi::c_void;
 use std::fmt;
@@ -96,6 +98,48 @@ impl fmt::Display for Error {
     }
 }
 
+// NVIDIA packs its driver version into 10, 8, 8 and 6 bits instead of
+// the layout of a Vulkan version
+const NVIDIA_VENDOR_ID: u32 = 0x10de;
+
+fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        (version >> 22) & 0x7f,
+        (version >> 12) & 0x3ff,
+        version & 0xfff,
+    )
+}
+
+fn device_info(props: &vk::VkPhysicalDeviceProperties) -> String {
+    let name = props.deviceName
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect::<Vec<u8>>();
+
+    let driver_version = if props.vendorID == NVIDIA_VENDOR_ID {
+        format!(
+            "{}.{}.{}.{}",
+            props.driverVersion >> 22,
+            (props.driverVersion >> 14) & 0xff,
+            (props.driverVersion >> 6) & 0xff,
+            props.driverVersion & 0x3f,
+        )
+    } else {
+        format_version(props.driverVersion)
+    };
+
+    format!(
+        "Device: {}\n\
+         Driver version: {}\n\
+         Vulkan version: {}",
+        String::from_utf8_lossy(&name),
+        driver_version,
+        format_version(props.apiVersion),
+    )
+}
+
 #[derive(Debug)]
 struct ExternalData {
     get_instance_proc_cb: vulkan_funcs::GetInstanceProcFunc,
@@ -129,7 +173,23 @@ impl Executor {
         self.context = None;
     }
 
-    fn context_is_compatible(&self, script: &Script) -> bool {
+    // The requirements of the script plus the ones that the config
+    // adds on top of it
+    fn script_requirements(&self, script: &Script) -> Requirements {
+        let mut requirements = script.requirements().clone();
+
+        if self.config.borrow().separate_shader_objects()
+            && !script.shaders(Stage::Compute).is_empty()
+        {
+            // VK_EXT_shader_object depends on VK_KHR_dynamic_rendering
+            requirements.add("VK_KHR_dynamic_rendering");
+            requirements.add("shaderObject");
+        }
+
+        requirements
+    }
+
+    fn context_is_compatible(&self, requirements: &Requirements) -> bool {
         // If the device is created externally then it’s up to the
         // caller to ensure the device has all the necessary features
         // enabled.
@@ -138,7 +198,7 @@ impl Executor {
         }
 
         match self.context {
-            Some(_) => self.requirements.eq(script.requirements()),
+            Some(_) => self.requirements.eq(requirements),
             None => false,
         }
     }
@@ -187,10 +247,15 @@ impl Executor {
                     e.vk_device,
                 )?)
             },
-            None => Ok(Context::new(
-                requirements,
-                self.config.borrow().device_id()
-            )?),
+            None => {
+                let config = self.config.borrow();
+
+                Ok(Context::new_with_portability(
+                    requirements,
+                    config.device_id(),
+                    config.portability(),
+                )?)
+            },
         }
     }
 
@@ -198,22 +263,46 @@ impl Executor {
         &mut self,
         script: &Script,
     ) -> Result<Rc<Context>, Error> {
+        let requirements = self.script_requirements(script);
+
         // Recreate the context if the features or extensions have changed
-        if !self.context_is_compatible(script) {
+        if !self.context_is_compatible(&requirements) {
             self.reset_context();
         }
 
         match &self.context {
             Some(c) => Ok(Rc::clone(c)),
             None => {
-                self.requirements.clone_from(script.requirements());
+                self.requirements = requirements;
                 let context = self.create_context(&self.requirements)?;
 
+                if self.config.borrow().show_device_info() {
+                    self.log_device_info(&context);
+                }
+
                 Ok(Rc::clone(self.context.insert(Rc::new(context))))
             }
         }
     }
 
+    fn log_device_info(&self, context: &Context) {
+        let mut props = vk::VkPhysicalDeviceProperties::default();
+
+        unsafe {
+            context.instance().vkGetPhysicalDeviceProperties.unwrap()(
+                context.physical_device(),
+                &mut props as *mut vk::VkPhysicalDeviceProperties,
+            );
+        }
+
+        use std::fmt::Write;
+        let _ = writeln!(
+            self.config.borrow().logger().borrow_mut(),
+            "{}",
+            device_info(&props),
+        );
+    }
+
     fn window_for_script(
         &mut self,
         script: &Script,
@@ -253,14 +342,41 @@ impl Executor {
         }
     }
 
+    fn log_memory_usage(&self, before: &memory_usage::Snapshot, context: &Context) {
+        let after = memory_usage::Snapshot::take(context);
+
+        use std::fmt::Write;
+        let _ = writeln!(
+            self.config.borrow().logger().borrow_mut(),
+            "{}",
+            memory_usage::report(before, &after),
+        );
+    }
+
     fn execute_script_or_error(
         &mut self,
         script: &Script
     ) -> Result<(), Error> {
         let context = self.context_for_script(script)?;
 
+        if !self.config.borrow().show_memory_usage() {
+            return self.execute_script_with_context(script, context);
+        }
+
+        let before = memory_usage::Snapshot::take(&context);
+        let res = self.execute_script_with_context(script, Rc::clone(&context));
+        self.log_memory_usage(&before, &context);
+
+        res
+    }
+
+    fn execute_script_with_context(
+        &mut self,
+        script: &Script,
+        context: Rc<Context>,
+    ) -> Result<(), Error> {
         if self.external.is_some() {
-            if let Err(e) = script.requirements().check(
+            if let Err(e) = self.script_requirements(script).check(
                 context.instance(),
                 context.physical_device(),
             ) {
@@ -275,14 +391,32 @@ impl Executor {
             Rc::clone(&window),
             script,
             self.config.borrow().show_disassembly(),
+            self.config.borrow().separate_shader_objects(),
         )?;
 
-        tester::run(
+        let mut submissions = tester::Submissions {
+            separate: self.config.borrow().separate_submissions(),
+            ..Default::default()
+        };
+
+        let res = tester::run(
             window.as_ref(),
             &pipeline_set,
             script,
             self.config.borrow().inspector().clone(),
-        )?;
+            &mut submissions,
+        );
+
+        if self.config.borrow().show_submission_times() {
+            use std::fmt::Write;
+            let _ = writeln!(
+                self.config.borrow().logger().borrow_mut(),
+                "{}",
+                submissions.report(),
+            );
+        }
+
+        res?;
 
         Ok(())
     }
@@ -310,7 +444,7 @@ impl Executor {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::fake_vulkan::FakeVulkan;
+    use crate::fake_vulkan::{FakeVulkan, Command, HandleType};
     use crate::config::Config;
     use std::ffi::c_char;
 
@@ -416,6 +550,83 @@ mod test {
         assert!(!Rc::ptr_eq(&window, executor.window.as_ref().unwrap()));
     }
 
+    #[test]
+    fn separate_shader_objects() {
+        let mut fake_vulkan = create_fake_vulkan();
+        fake_vulkan.add_instance_extension(
+            "VK_KHR_get_physical_device_properties2"
+        );
+        let device = &mut fake_vulkan.physical_devices[0];
+        device.add_extension("VK_KHR_dynamic_rendering");
+        device.add_extension("VK_EXT_shader_object");
+        device.shader_object.shaderObject = vk::VK_TRUE;
+
+        let config = Rc::new(RefCell::new(Config::new()));
+        config.borrow_mut().set_separate_shader_objects(true);
+
+        let mut executor = Executor::new(Rc::clone(&config));
+
+        let compute_source = Source::from_string(
+            "[compute shader]\n\
+             03 02 23 07\n\
+             [test]\n\
+             ssbo 0 1024\n\
+             push float 0 1.0\n\
+             compute 1 2 3".to_string()
+        );
+
+        fake_vulkan.set_override();
+        assert_eq!(executor.execute(&compute_source), result::Result::Pass);
+
+        // The shader object is bound instead of a compute pipeline
+        assert!(!fake_vulkan.commands.iter().any(|command| {
+            matches!(command, Command::BindPipeline { .. })
+        }));
+
+        let mut commands = fake_vulkan.commands.iter().skip_while(|command| {
+            !matches!(command, Command::BindShaders { .. })
+        });
+
+        let Command::BindShaders { stages, shaders } = commands.next().unwrap()
+        else { unreachable!("Bad command"); };
+
+        assert_eq!(stages, &[vk::VK_SHADER_STAGE_COMPUTE_BIT]);
+        assert_eq!(shaders.len(), 1);
+
+        let HandleType::Shader(ref info) =
+            fake_vulkan.get_freed_handle(shaders[0]).data
+        else { unreachable!("Mismatched handle"); };
+
+        assert_eq!(info.create_info.stage, vk::VK_SHADER_STAGE_COMPUTE_BIT);
+        assert_eq!(info.code, [0x07230203]);
+        assert_eq!(info.entrypoint, "main");
+        // These need to match the pipeline layout
+        assert_eq!(info.layouts.len(), 1);
+        assert_eq!(info.push_constant_ranges.len(), 1);
+        assert_eq!(info.push_constant_ranges[0].size, 4);
+
+        assert!(matches!(
+            commands.next(),
+            Some(Command::Dispatch { x: 1, y: 2, z: 3 }),
+        ));
+
+        // Scripts without a compute shader don’t need the feature
+        fake_vulkan.physical_devices[0].shader_object.shaderObject =
+            vk::VK_FALSE;
+        fake_vulkan.set_override();
+        assert_eq!(
+            executor.execute(&Source::from_string(
+                "[test]\n\
+                 draw rect -1 -1 2 2".to_string()
+            )),
+            result::Result::Pass,
+        );
+
+        // The others are skipped when the device doesn’t support it
+        fake_vulkan.set_override();
+        assert_eq!(executor.execute(&compute_source), result::Result::Skip);
+    }
+
     extern "C" fn get_instance_proc_cb(
         func_name: *const c_char,
         user_data: *const c_void,
@@ -495,6 +706,29 @@ mod test {
         );
     }
 
+    #[test]
+    fn device_info_versions() {
+        let mut props = vk::VkPhysicalDeviceProperties::default();
+
+        for (c, &b) in props.deviceName.iter_mut().zip(b"llvmpipe") {
+            *c = b as c_char;
+        }
+        props.driverVersion = requirements::make_version(24, 2, 8);
+        props.apiVersion = requirements::make_version(1, 3, 289);
+
+        assert_eq!(
+            device_info(&props),
+            "Device: llvmpipe\n\
+             Driver version: 24.2.8\n\
+             Vulkan version: 1.3.289",
+        );
+
+        props.vendorID = NVIDIA_VENDOR_ID;
+        props.driverVersion = (565 << 22) | (57 << 14) | (1 << 6);
+
+        assert!(device_info(&props).contains("Driver version: 565.57.1.0\n"));
+    }
+
     #[test]
     fn context_error() {
         let fake_vulkan = create_fake_vulkan();
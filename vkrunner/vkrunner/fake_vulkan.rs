@@ -71,6 +71,10 @@ pub struct PhysicalDeviceInfo {
     // Two random extension feature sets to report when asked
     pub shader_atomic: vk::VkPhysicalDeviceShaderAtomicInt64FeaturesKHR,
     pub multiview: vk::VkPhysicalDeviceMultiviewFeaturesKHR,
+    // Needed for the separate shader objects
+    pub shader_object: vk::VkPhysicalDeviceShaderObjectFeaturesEXT,
+    // Reported through vkGetPhysicalDeviceMemoryProperties2
+    pub memory_budget: vk::VkPhysicalDeviceMemoryBudgetPropertiesEXT,
 }
 
 impl PhysicalDeviceInfo {
@@ -90,7 +94,14 @@ impl Default for PhysicalDeviceInfo {
                 deviceType: vk::VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU,
                 deviceName: [0; 256],
                 pipelineCacheUUID: *b"fakevulkan123456",
-                limits: Default::default(),
+                limits: vk::VkPhysicalDeviceLimits {
+                    // The minimum required by the spec
+                    maxPushConstantsSize: 128,
+                    maxComputeSharedMemorySize: 16384,
+                    maxVertexInputAttributes: 16,
+                    maxVertexInputBindingStride: 2048,
+                    ..Default::default()
+                },
                 sparseProperties: Default::default(),
             },
             memory_properties: Default::default(),
@@ -105,6 +116,8 @@ impl Default for PhysicalDeviceInfo {
             extensions: Vec::new(),
             shader_atomic: Default::default(),
             multiview: Default::default(),
+            shader_object: Default::default(),
+            memory_budget: Default::default(),
         }
     }
 }
@@ -113,12 +126,15 @@ const ATOMIC_TYPE: vk::VkStructureType =
     vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_SHADER_ATOMIC_INT64_FEATURES_KHR;
 const MULTIVIEW_TYPE: vk::VkStructureType =
     vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MULTIVIEW_FEATURES_KHR;
+const SHADER_OBJECT_TYPE: vk::VkStructureType =
+    vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_SHADER_OBJECT_FEATURES_EXT;
 
 #[derive(Debug, Clone)]
 pub struct GraphicsPipelineCreateInfo {
     pub create_info: vk::VkGraphicsPipelineCreateInfo,
     pub bindings: Vec<vk::VkVertexInputBindingDescription>,
     pub attribs: Vec<vk::VkVertexInputAttributeDescription>,
+    pub divisors: Vec<vk::VkVertexInputBindingDivisorDescriptionEXT>,
 }
 
 impl GraphicsPipelineCreateInfo {
@@ -137,10 +153,31 @@ impl GraphicsPipelineCreateInfo {
             vertex_input_state.vertexAttributeDescriptionCount as usize,
         );
 
+        // The only struct that vkrunner chains onto the vertex input
+        // state is the divisor state
+        let divisors = if vertex_input_state.pNext.is_null() {
+            Vec::new()
+        } else {
+            let divisor_state = unsafe {
+                &*vertex_input_state.pNext.cast::<
+                    vk::VkPipelineVertexInputDivisorStateCreateInfoEXT
+                >()
+            };
+            assert_eq!(
+                divisor_state.sType,
+                vk::VK_STRUCTURE_TYPE_PIPELINE_VERTEX_INPUT_DIVISOR_STATE_CREATE_INFO_EXT,
+            );
+            vec_from_raw_parts(
+                divisor_state.pVertexBindingDivisors,
+                divisor_state.vertexBindingDivisorCount as usize,
+            )
+        };
+
         GraphicsPipelineCreateInfo {
             create_info: create_info.clone(),
             bindings,
             attribs,
+            divisors,
         }
     }
 }
@@ -158,6 +195,15 @@ pub struct PipelineLayoutCreateInfo {
     pub layouts: Vec<vk::VkDescriptorSetLayout>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ShaderCreateInfo {
+    pub create_info: vk::VkShaderCreateInfoEXT,
+    pub code: Vec<u32>,
+    pub entrypoint: String,
+    pub push_constant_ranges: Vec<vk::VkPushConstantRange>,
+    pub layouts: Vec<vk::VkDescriptorSetLayout>,
+}
+
 #[derive(Debug)]
 // It would be nice to just store the VkClearAttachment directly but
 // that can’t derive Debug because it is a union and it would be
@@ -182,6 +228,10 @@ pub enum Command {
         bind_point: vk::VkPipelineBindPoint,
         pipeline: vk::VkPipeline,
     },
+    BindShaders {
+        stages: Vec<vk::VkShaderStageFlagBits>,
+        shaders: Vec<vk::VkShaderEXT>,
+    },
     BindVertexBuffers {
         first_binding: u32,
         buffers: Vec<vk::VkBuffer>,
@@ -228,6 +278,12 @@ pub enum Command {
         dst_buffer: vk::VkBuffer,
         regions: Vec<vk::VkBufferImageCopy>,
     },
+    CopyBufferToImage {
+        src_buffer: vk::VkBuffer,
+        dst_image: vk::VkImage,
+        dst_image_layout: vk::VkImageLayout,
+        regions: Vec<vk::VkBufferImageCopy>,
+    },
     PushConstants {
         layout: vk::VkPipelineLayout,
         stage_flags: vk::VkShaderStageFlags,
@@ -263,6 +319,7 @@ pub enum HandleType {
     RenderPass { attachments: Vec<vk::VkAttachmentDescription> },
     Image,
     ImageView,
+    Sampler { create_info: vk::VkSamplerCreateInfo },
     Buffer {
         create_info: vk::VkBufferCreateInfo,
         memory: Option<vk::VkDeviceMemory>,
@@ -274,6 +331,7 @@ pub enum HandleType {
     DescriptorSetLayout { bindings: Vec<vk::VkDescriptorSetLayoutBinding> },
     PipelineLayout(PipelineLayoutCreateInfo),
     Pipeline(PipelineCreateInfo),
+    Shader(ShaderCreateInfo),
     DescriptorSet { bindings: HashMap<u32, Binding> },
 }
 
@@ -281,6 +339,7 @@ pub enum HandleType {
 pub struct Binding {
     pub descriptor_type: vk::VkDescriptorType,
     pub info: vk::VkDescriptorBufferInfo,
+    pub image_info: vk::VkDescriptorImageInfo,
 }
 
 #[derive(Debug)]
@@ -322,6 +381,8 @@ pub struct FakeVulkan {
     pub memory_flushes: Vec<vk::VkMappedMemoryRange>,
     /// Log of calls to vkInvalidateMappedMemoryRanges
     pub memory_invalidations: Vec<vk::VkMappedMemoryRange>,
+    /// Extensions enabled by the last call to vkCreateDevice
+    pub device_extensions: Vec<String>,
 
     /// All of the commands from command queues that were submitted
     /// with vkQueueSubmit
@@ -346,6 +407,7 @@ impl FakeVulkan {
             result_queue: HashMap::new(),
             memory_flushes: Vec::new(),
             memory_invalidations: Vec::new(),
+            device_extensions: Vec::new(),
             commands: Vec::new(),
         });
 
@@ -425,6 +487,11 @@ impl FakeVulkan {
                     Some(FakeVulkan::get_physical_device_memory_properties)
                 )
             },
+            "vkGetPhysicalDeviceMemoryProperties2" => unsafe {
+                transmute::<vk::PFN_vkGetPhysicalDeviceMemoryProperties2, _>(
+                    Some(FakeVulkan::get_physical_device_memory_properties2)
+                )
+            },
             "vkGetPhysicalDeviceFormatProperties" => unsafe {
                 transmute::<vk::PFN_vkGetPhysicalDeviceFormatProperties, _>(
                     Some(FakeVulkan::get_physical_device_format_properties)
@@ -531,6 +598,16 @@ impl FakeVulkan {
                     Some(FakeVulkan::destroy_image_view)
                 )
             },
+            "vkCreateSampler" => unsafe {
+                transmute::<vk::PFN_vkCreateSampler, _>(
+                    Some(FakeVulkan::create_sampler)
+                )
+            },
+            "vkDestroySampler" => unsafe {
+                transmute::<vk::PFN_vkDestroySampler, _>(
+                    Some(FakeVulkan::destroy_sampler)
+                )
+            },
             "vkCreateImage" => unsafe {
                 transmute::<vk::PFN_vkCreateImage, _>(
                     Some(FakeVulkan::create_image)
@@ -585,6 +662,11 @@ impl FakeVulkan {
                     Some(FakeVulkan::get_buffer_memory_requirements)
                 )
             },
+            "vkGetBufferDeviceAddress" => unsafe {
+                transmute::<vk::PFN_vkGetBufferDeviceAddress, _>(
+                    Some(FakeVulkan::get_buffer_device_address)
+                )
+            },
             "vkBindBufferMemory" => unsafe {
                 transmute::<vk::PFN_vkBindBufferMemory, _>(
                     Some(FakeVulkan::bind_buffer_memory)
@@ -625,6 +707,16 @@ impl FakeVulkan {
                     Some(FakeVulkan::destroy_shader_module)
                 )
             },
+            "vkCreateShadersEXT" => unsafe {
+                transmute::<vk::PFN_vkCreateShadersEXT, _>(
+                    Some(FakeVulkan::create_shaders)
+                )
+            },
+            "vkDestroyShaderEXT" => unsafe {
+                transmute::<vk::PFN_vkDestroyShaderEXT, _>(
+                    Some(FakeVulkan::destroy_shader)
+                )
+            },
             "vkCreatePipelineCache" => unsafe {
                 transmute::<vk::PFN_vkCreatePipelineCache, _>(
                     Some(FakeVulkan::create_pipeline_cache)
@@ -735,6 +827,11 @@ impl FakeVulkan {
                     Some(FakeVulkan::bind_pipeline)
                 )
             },
+            "vkCmdBindShadersEXT" => unsafe {
+                transmute::<vk::PFN_vkCmdBindShadersEXT, _>(
+                    Some(FakeVulkan::bind_shaders)
+                )
+            },
             "vkCmdBindVertexBuffers" => unsafe {
                 transmute::<vk::PFN_vkCmdBindVertexBuffers, _>(
                     Some(FakeVulkan::bind_vertex_buffers)
@@ -775,6 +872,11 @@ impl FakeVulkan {
                     Some(FakeVulkan::copy_image_to_buffer)
                 )
             },
+            "vkCmdCopyBufferToImage" => unsafe {
+                transmute::<vk::PFN_vkCmdCopyBufferToImage, _>(
+                    Some(FakeVulkan::copy_buffer_to_image)
+                )
+            },
             "vkCmdPushConstants" => unsafe {
                 transmute::<vk::PFN_vkCmdPushConstants, _>(
                     Some(FakeVulkan::push_constants)
@@ -917,6 +1019,48 @@ impl FakeVulkan {
         }
     }
 
+    extern "C" fn get_physical_device_memory_properties2(
+        physical_device: vk::VkPhysicalDevice,
+        memory_properties_out: *mut vk::VkPhysicalDeviceMemoryProperties2,
+    ) {
+        let fake_vulkan = FakeVulkan::current();
+
+        let device_num = fake_vulkan.physical_device_to_index(physical_device);
+        let device = &fake_vulkan.physical_devices[device_num];
+
+        let (struct_type, mut struct_ptr) =
+            FakeVulkan::extract_struct_data(memory_properties_out.cast());
+
+        assert_eq!(
+            struct_type,
+            vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2
+        );
+
+        unsafe {
+            (*memory_properties_out).memoryProperties =
+                device.memory_properties.clone();
+        }
+
+        while !struct_ptr.is_null() {
+            let (struct_type, next_ptr) =
+                FakeVulkan::extract_struct_data(struct_ptr);
+
+            assert_eq!(
+                struct_type,
+                vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT
+            );
+
+            unsafe {
+                let budget = struct_ptr
+                    .cast::<vk::VkPhysicalDeviceMemoryBudgetPropertiesEXT>();
+                (*budget).heapBudget = device.memory_budget.heapBudget;
+                (*budget).heapUsage = device.memory_budget.heapUsage;
+            }
+
+            struct_ptr = next_ptr;
+        }
+    }
+
     extern "C" fn get_physical_device_properties(
         physical_device: vk::VkPhysicalDevice,
         properties_out: *mut vk::VkPhysicalDeviceProperties,
@@ -974,8 +1118,19 @@ impl FakeVulkan {
             fake_vulkan.physical_device_to_index(physical_device);
         let device = &fake_vulkan.physical_devices[device_num];
 
+        // Formats that the test didn’t set up can only be used for
+        // vertex buffers
+        let format_properties = device
+            .format_properties
+            .get(&format)
+            .copied()
+            .unwrap_or(vk::VkFormatProperties {
+                bufferFeatures: vk::VK_FORMAT_FEATURE_VERTEX_BUFFER_BIT,
+                ..Default::default()
+            });
+
         unsafe {
-            *properties = device.format_properties[&format];
+            *properties = format_properties;
         }
     }
 
@@ -1032,6 +1187,9 @@ impl FakeVulkan {
                     device.multiview.multiviewGeometryShader,
                     device.multiview.multiviewTessellationShader,
                 ],
+                SHADER_OBJECT_TYPE => vec![
+                    device.shader_object.shaderObject,
+                ],
                 _ => unreachable!("unexpected struct type {}", struct_type),
             };
 
@@ -1326,7 +1484,7 @@ impl FakeVulkan {
 
     extern "C" fn create_device(
         _physical_device: vk::VkPhysicalDevice,
-        _create_info: *const vk::VkDeviceCreateInfo,
+        create_info: *const vk::VkDeviceCreateInfo,
         _allocator: *const vk::VkAllocationCallbacks,
         device_out: *mut vk::VkDevice,
     ) -> vk::VkResult {
@@ -1338,6 +1496,15 @@ impl FakeVulkan {
             return res;
         }
 
+        fake_vulkan.device_extensions = unsafe {
+            let create_info = &*create_info;
+
+            (0..create_info.enabledExtensionCount as usize).map(|i| {
+                let name = *create_info.ppEnabledExtensionNames.add(i);
+                CStr::from_ptr(name).to_str().unwrap().to_string()
+            }).collect()
+        };
+
         unsafe {
             *device_out =
                 fake_vulkan.add_dispatchable_handle(HandleType::Device);
@@ -1599,6 +1766,47 @@ impl FakeVulkan {
         handle.freed = true;
     }
 
+    extern "C" fn create_sampler(
+        device: vk::VkDevice,
+        create_info: *const vk::VkSamplerCreateInfo,
+        _allocator: *const vk::VkAllocationCallbacks,
+        sampler_out: *mut vk::VkSampler,
+    ) -> vk::VkResult {
+        let fake_vulkan = FakeVulkan::current();
+
+        let res = fake_vulkan.next_result("vkCreateSampler");
+
+        if res != vk::VK_SUCCESS {
+            return res;
+        }
+
+        fake_vulkan.check_device(device);
+
+        let create_info = unsafe { &*create_info }.clone();
+
+        unsafe {
+            *sampler_out = fake_vulkan.add_handle(
+                HandleType::Sampler { create_info }
+            );
+        }
+
+        res
+    }
+
+    extern "C" fn destroy_sampler(
+        device: vk::VkDevice,
+        sampler: vk::VkSampler,
+        _allocator: *const vk::VkAllocationCallbacks,
+    ) {
+        let fake_vulkan = FakeVulkan::current();
+
+        fake_vulkan.check_device(device);
+
+        let handle = fake_vulkan.get_handle_mut(sampler);
+        assert!(matches!(handle.data, HandleType::Sampler { .. }));
+        handle.freed = true;
+    }
+
     extern "C" fn create_image(
         device: vk::VkDevice,
         _create_info: *const vk::VkImageCreateInfo,
@@ -1757,6 +1965,34 @@ impl FakeVulkan {
         }
     }
 
+    /// Returns a made-up address that is derived from the buffer
+    /// handle so that tests can predict it with
+    /// [FakeVulkan::buffer_device_address].
+    extern "C" fn get_buffer_device_address(
+        device: vk::VkDevice,
+        info: *const vk::VkBufferDeviceAddressInfo,
+    ) -> vk::VkDeviceAddress {
+        let fake_vulkan = FakeVulkan::current();
+
+        fake_vulkan.check_device(device);
+
+        let buffer = unsafe { (*info).buffer };
+
+        assert!(matches!(
+            fake_vulkan.get_handle(buffer).data,
+            HandleType::Buffer { .. },
+        ));
+
+        FakeVulkan::buffer_device_address(buffer)
+    }
+
+    pub fn buffer_device_address(
+        buffer: vk::VkBuffer
+    ) -> vk::VkDeviceAddress {
+        (FakeVulkan::handle_to_index(buffer) as vk::VkDeviceAddress + 1)
+            << 32
+    }
+
     extern "C" fn get_image_memory_requirements(
         device: vk::VkDevice,
         image: vk::VkImage,
@@ -1957,6 +2193,75 @@ impl FakeVulkan {
         handle.freed = true;
     }
 
+    extern "C" fn create_shaders(
+        device: vk::VkDevice,
+        create_info_count: u32,
+        create_infos: *const vk::VkShaderCreateInfoEXT,
+        _allocator: *const vk::VkAllocationCallbacks,
+        shaders_out: *mut vk::VkShaderEXT,
+    ) -> vk::VkResult {
+        let fake_vulkan = FakeVulkan::current();
+
+        let res = fake_vulkan.next_result("vkCreateShadersEXT");
+
+        if res != vk::VK_SUCCESS {
+            return res;
+        }
+
+        fake_vulkan.check_device(device);
+
+        for i in 0..create_info_count as usize {
+            unsafe {
+                let create_info = &*create_infos.add(i);
+
+                assert_eq!(
+                    create_info.codeType,
+                    vk::VK_SHADER_CODE_TYPE_SPIRV_EXT,
+                );
+                assert_eq!(create_info.codeSize % (u32::BITS as usize / 8), 0);
+
+                let info = ShaderCreateInfo {
+                    create_info: create_info.clone(),
+                    code: vec_from_raw_parts(
+                        create_info.pCode.cast::<u32>(),
+                        create_info.codeSize / (u32::BITS as usize / 8),
+                    ),
+                    entrypoint: CStr::from_ptr(create_info.pName)
+                        .to_str()
+                        .unwrap()
+                        .to_string(),
+                    push_constant_ranges: vec_from_raw_parts(
+                        create_info.pPushConstantRanges,
+                        create_info.pushConstantRangeCount as usize,
+                    ),
+                    layouts: vec_from_raw_parts(
+                        create_info.pSetLayouts,
+                        create_info.setLayoutCount as usize,
+                    ),
+                };
+
+                *shaders_out.add(i) =
+                    fake_vulkan.add_handle(HandleType::Shader(info));
+            }
+        }
+
+        res
+    }
+
+    extern "C" fn destroy_shader(
+        device: vk::VkDevice,
+        shader: vk::VkShaderEXT,
+        _allocator: *const vk::VkAllocationCallbacks,
+    ) {
+        let fake_vulkan = FakeVulkan::current();
+
+        fake_vulkan.check_device(device);
+
+        let handle = fake_vulkan.get_handle_mut(shader);
+        assert!(matches!(handle.data, HandleType::Shader { .. }));
+        handle.freed = true;
+    }
+
     extern "C" fn create_pipeline_cache(
         device: vk::VkDevice,
         _create_info: *const vk::VkPipelineCacheCreateInfo,
@@ -2411,13 +2716,21 @@ impl FakeVulkan {
                 fake_vulkan.get_handle_mut(write.dstSet).data
             else { unreachable!("mismatched handle type"); };
 
-            bindings.insert(
-                write.dstBinding,
+            let binding = if write.pImageInfo.is_null() {
                 Binding {
                     descriptor_type: write.descriptorType,
                     info: unsafe { &*write.pBufferInfo }.clone(),
-                },
-            );
+                    image_info: Default::default(),
+                }
+            } else {
+                Binding {
+                    descriptor_type: write.descriptorType,
+                    info: Default::default(),
+                    image_info: unsafe { &*write.pImageInfo }.clone(),
+                }
+            };
+
+            bindings.insert(write.dstBinding, binding);
         }
     }
 
@@ -2522,6 +2835,28 @@ impl FakeVulkan {
         );
     }
 
+    extern "C" fn bind_shaders(
+        command_buffer: vk::VkCommandBuffer,
+        stage_count: u32,
+        stages: *const vk::VkShaderStageFlagBits,
+        shaders: *const vk::VkShaderEXT,
+    ) {
+        let fake_vulkan = FakeVulkan::current();
+
+        let stages = vec_from_raw_parts(stages, stage_count as usize);
+        let shaders = vec_from_raw_parts(shaders, stage_count as usize);
+
+        for &shader in shaders.iter() {
+            let handle = fake_vulkan.get_handle(shader);
+            assert!(matches!(handle.data, HandleType::Shader { .. }));
+        }
+
+        fake_vulkan.add_command(
+            command_buffer,
+            Command::BindShaders { stages, shaders },
+        );
+    }
+
     extern "C" fn bind_vertex_buffers(
         command_buffer: vk::VkCommandBuffer,
         first_binding: u32,
@@ -2763,6 +3098,35 @@ impl FakeVulkan {
         );
     }
 
+    extern "C" fn copy_buffer_to_image(
+        command_buffer: vk::VkCommandBuffer,
+        src_buffer: vk::VkBuffer,
+        dst_image: vk::VkImage,
+        dst_image_layout: vk::VkImageLayout,
+        region_count: u32,
+        regions: *const vk::VkBufferImageCopy,
+    ) {
+        let fake_vulkan = FakeVulkan::current();
+
+        fake_vulkan.check_buffer(src_buffer);
+        fake_vulkan.check_image(dst_image);
+
+        let regions = vec_from_raw_parts(
+            regions,
+            region_count as usize,
+        );
+
+        fake_vulkan.add_command(
+            command_buffer,
+            Command::CopyBufferToImage {
+                src_buffer,
+                dst_image,
+                dst_image_layout,
+                regions,
+            },
+        );
+    }
+
     extern "C" fn push_constants(
         command_buffer: vk::VkCommandBuffer,
         layout: vk::VkPipelineLayout,
@@ -1,6 +1,6 @@
 // Automatically generated by make-features.py
 
-static EXTENSIONS: [Extension; 29] = [
+static EXTENSIONS: [Extension; 31] = [
     Extension {
         name_bytes: vk::VK_KHR_16BIT_STORAGE_EXTENSION_NAME,
         struct_size: mem::size_of::<vk::VkPhysicalDevice16BitStorageFeaturesKHR>(),
@@ -277,6 +277,23 @@ static EXTENSIONS: [Extension; 29] = [
             "computeFullSubgroups",
         ],
     },
+    Extension {
+        name_bytes: vk::VK_KHR_SHADER_CLOCK_EXTENSION_NAME,
+        struct_size: mem::size_of::<vk::VkPhysicalDeviceShaderClockFeaturesKHR>(),
+        struct_type: vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_SHADER_CLOCK_FEATURES_KHR,
+        features: &[
+            "shaderSubgroupClock",
+            "shaderDeviceClock",
+        ],
+    },
+    Extension {
+        name_bytes: vk::VK_EXT_SHADER_OBJECT_EXTENSION_NAME,
+        struct_size: mem::size_of::<vk::VkPhysicalDeviceShaderObjectFeaturesEXT>(),
+        struct_type: vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_SHADER_OBJECT_FEATURES_EXT,
+        features: &[
+            "shaderObject",
+        ],
+    },
 ];
 
 const N_BASE_FEATURES: usize = 55;
@@ -22,78 +22,53 @@
 
 use crate::small_float;
 
-// Convert a 4-byte float to a 2-byte half float.
-// Based on code from:
-// http://www.opengl.org/discussion_boards/ubb/Forum3/HTML/008786.html
-//
-// Taken over from Piglit which took it from Mesa.
+// Shifts `value` right by `shift` bits, rounding to the nearest
+// integer and to even on ties.
+fn shift_round_even(value: u32, shift: u32) -> u32 {
+    let truncated = value >> shift;
+    let remainder = value & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+
+    if remainder > half || (remainder == half && truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+// Convert a 4-byte float to a 2-byte half float, rounding to the
+// nearest representable value (ties to even) like the conversions in
+// shaders do. Values too big for a half float become infinity and NaN
+// stays NaN.
 pub fn from_f32(val: f32) -> u16 {
     let fi = val.to_bits();
-    let flt_m = fi & 0x7fffff;
-    let flt_e = (fi >> 23) & 0xff;
-    // sign bit
-    let flt_s = (fi >> 31) & 0x1;
-
-    let e;
-    let m;
+    let sign = ((fi >> 16) & 0x8000) as u16;
+    let abs = fi & 0x7fffffff;
 
-    // handle special cases
-    if flt_e == 0 && flt_m == 0 {
-        // zero
-        m = 0;
-        e = 0;
-    } else if (flt_e == 0) && (flt_m != 0) {
-        // denorm -- denorm float maps to 0 half
-        m = 0;
-        e = 0;
-    } else if (flt_e == 0xff) && (flt_m == 0) {
-        // infinity
-        m = 0;
-        e = 31;
-    } else if (flt_e == 0xff) && (flt_m != 0) {
+    let magnitude = if abs > 0x7f800000 {
         // NaN
-        m = 1;
-        e = 31;
+        0x7c01
+    } else if abs >= 0x477ff000 {
+        // Infinity, or at least 65520, which rounds up to it
+        0x7c00
+    } else if abs >= 0x38800000 {
+        // Normal half float: rebias the exponent from 127 to 15 and
+        // drop 13 bits of mantissa. A carry out of the mantissa
+        // correctly bumps the exponent.
+        shift_round_even(abs - ((127 - 15) << 23), 13)
     } else {
-        // regular number
-        let new_exp = flt_e as i32 - 127;
-        if new_exp < -24 {
-            // this maps to 0
-            m = 0;
-            e = 0;
-        } else if new_exp < -14 {
-            // this maps to a denorm
-            // 2^-exp_val
-            let exp_val = (-14 - new_exp) as u32;
+        // Denormal half float in units of 2⁻²⁴
+        let exponent = abs >> 23;
+        let shift = 126u32.saturating_sub(exponent);
 
-            e = 0;
-
-            match exp_val {
-                0 => m = 0,
-                1 => m = 512 + (flt_m >> 14),
-                2 => m = 256 + (flt_m >> 15),
-                3 => m = 128 + (flt_m >> 16),
-                4 => m = 64 + (flt_m >> 17),
-                5 => m = 32 + (flt_m >> 18),
-                6 => m = 16 + (flt_m >> 19),
-                7 => m = 8 + (flt_m >> 20),
-                8 => m = 4 + (flt_m >> 21),
-                9 => m = 2 + (flt_m >> 22),
-                10 => m = 1,
-                _ => unreachable!(),
-            }
-        } else if new_exp > 15 {
-            // map this value to infinity
-            m = 0;
-            e = 31;
+        if exponent == 0 || shift > 24 {
+            0
         } else {
-            /* regular */
-            e = (new_exp + 15) as u32;
-            m = flt_m >> 13;
+            shift_round_even((abs & 0x7fffff) | 0x800000, shift)
         }
-    }
+    };
 
-    ((flt_s << 15) | (e << 10) | m) as u16
+    sign | magnitude as u16
 }
 
 pub fn to_f64(half: u16) -> f64 {
@@ -115,6 +90,34 @@ mod test {
         assert_eq!(from_f32(f32::MAX), 0x7c00);
     }
 
+    #[test]
+    fn test_from_f32_rounding() {
+        // Halfway between 0x3c00 and 0x3c01 rounds to the even one
+        assert_eq!(from_f32(f32::from_bits(0x3f801000)), 0x3c00);
+        // Halfway between 0x3c01 and 0x3c02
+        assert_eq!(from_f32(f32::from_bits(0x3f803000)), 0x3c02);
+        // Just above halfway rounds up
+        assert_eq!(from_f32(f32::from_bits(0x3f801001)), 0x3c01);
+        assert_eq!(from_f32(0.1), 0x2e66);
+        assert_eq!(from_f32(-2.0), 0xc000);
+
+        // The largest half float and the values around it
+        assert_eq!(from_f32(65504.0), 0x7bff);
+        assert_eq!(from_f32(65519.0), 0x7bff);
+        assert_eq!(from_f32(65520.0), 0x7c00);
+
+        // Denormals
+        assert_eq!(from_f32(2.0f32.powi(-24)), 0x0001);
+        assert_eq!(from_f32(2.0f32.powi(-25)), 0x0000);
+        assert_eq!(from_f32(1.5 * 2.0f32.powi(-25)), 0x0001);
+        assert_eq!(from_f32(3.0 * 2.0f32.powi(-25)), 0x0002);
+        assert_eq!(from_f32(2.0f32.powi(-15)), 0x0200);
+        assert_eq!(from_f32(-(2.0f32.powi(-20))), 0x8010);
+        // The largest denormal rounds up to the smallest normal
+        assert_eq!(from_f32(f32::from_bits(0x387ff000)), 0x0400);
+        assert_eq!(from_f32(f32::MIN_POSITIVE), 0x0000);
+    }
+
     fn assert_float_equal(a: f64, b: f64) {
         assert!(
             (a - b).abs() < 0.001,
@@ -20,11 +20,14 @@ mod stream;
 mod script;
 mod context;
 mod buffer;
+mod texture;
 mod window;
 mod allocate_store;
+mod memory_usage;
 mod executor;
 mod temp_file;
 mod logger;
+mod reflect;
 mod compiler;
 mod pipeline_set;
 mod flush_memory;
@@ -73,6 +73,8 @@ EXTENSIONS = [
     "KHR_VULKAN_MEMORY_MODEL",
     "KHR_COOPERATIVE_MATRIX",
     "EXT_SUBGROUP_SIZE_CONTROL",
+    "KHR_SHADER_CLOCK",
+    "EXT_SHADER_OBJECT",
 ]
 
 
@@ -51,6 +51,7 @@ INSTANCE_FUNCS = [
     "vkGetPhysicalDeviceFeatures2KHR",
     "vkGetPhysicalDeviceFormatProperties",
     "vkGetPhysicalDeviceMemoryProperties",
+    "vkGetPhysicalDeviceMemoryProperties2",
     "vkGetPhysicalDeviceProperties",
     "vkGetPhysicalDeviceProperties2",
     "vkGetPhysicalDeviceQueueFamilyProperties",
@@ -69,6 +70,7 @@ DEVICE_FUNCS = [
     "vkCmdBindDescriptorSets",
     "vkCmdBindIndexBuffer",
     "vkCmdBindPipeline",
+    "vkCmdBindShadersEXT",
     "vkCmdBindVertexBuffers",
     "vkCmdClearAttachments",
     "vkCmdCopyBufferToImage",
@@ -98,6 +100,7 @@ DEVICE_FUNCS = [
     "vkCreateSampler",
     "vkCreateSemaphore",
     "vkCreateShaderModule",
+    "vkCreateShadersEXT",
     "vkDestroyBuffer",
     "vkDestroyCommandPool",
     "vkDestroyDescriptorPool",
@@ -113,12 +116,14 @@ DEVICE_FUNCS = [
     "vkDestroyRenderPass",
     "vkDestroySampler",
     "vkDestroySemaphore",
+    "vkDestroyShaderEXT",
     "vkDestroyShaderModule",
     "vkEndCommandBuffer",
     "vkFlushMappedMemoryRanges",
     "vkFreeCommandBuffers",
     "vkFreeDescriptorSets",
     "vkFreeMemory",
+    "vkGetBufferDeviceAddress",
     "vkGetBufferMemoryRequirements",
     "vkGetDeviceQueue",
     "vkGetImageMemoryRequirements",
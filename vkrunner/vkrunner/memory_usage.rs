@@ -0,0 +1,280 @@
+// vkrunner
+//
+// Copyright 2026 The shaderc-vkrunner-mcp contributors
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next
+// paragraph) shall be included in all copies or substantial portions of the
+// Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+// THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Reports the device memory a script allocates for its buffers and
+//! images and, when the device supports `VK_EXT_memory_budget`, the
+//! budget and usage of each memory heap before and after the script
+//! runs.
+
+use crate::context::Context;
+use crate::requirements::Requirements;
+use crate::vk;
+use std::fmt::Write;
+use std::ptr;
+
+const MEMORY_BUDGET_EXTENSION: &str = "VK_EXT_memory_budget";
+
+/// Running totals of the memory allocated through a [Context].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Allocations {
+    pub buffers: usize,
+    pub buffer_bytes: vk::VkDeviceSize,
+    pub images: usize,
+    pub image_bytes: vk::VkDeviceSize,
+}
+
+impl Allocations {
+    fn since(&self, earlier: &Allocations) -> Allocations {
+        Allocations {
+            buffers: self.buffers - earlier.buffers,
+            buffer_bytes: self.buffer_bytes - earlier.buffer_bytes,
+            images: self.images - earlier.images,
+            image_bytes: self.image_bytes - earlier.image_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapBudget {
+    pub size: vk::VkDeviceSize,
+    pub device_local: bool,
+    /// How much of the heap the process can use, as estimated by the
+    /// driver.
+    pub budget: vk::VkDeviceSize,
+    /// How much of the heap the process currently uses.
+    pub usage: vk::VkDeviceSize,
+}
+
+/// The memory state at one point of a run.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    allocations: Allocations,
+    /// `None` if the device doesn’t support `VK_EXT_memory_budget`.
+    heaps: Option<Vec<HeapBudget>>,
+}
+
+impl Snapshot {
+    pub fn take(context: &Context) -> Snapshot {
+        Snapshot {
+            allocations: context.allocations(),
+            heaps: heap_budgets(context),
+        }
+    }
+}
+
+fn heap_budgets(context: &Context) -> Option<Vec<HeapBudget>> {
+    let get_properties =
+        context.instance().vkGetPhysicalDeviceMemoryProperties2?;
+
+    let extensions = Requirements::get_device_extensions(
+        context.instance(),
+        context.physical_device(),
+    ).ok()?;
+
+    if !extensions.contains(MEMORY_BUDGET_EXTENSION) {
+        return None;
+    }
+
+    let mut budget = vk::VkPhysicalDeviceMemoryBudgetPropertiesEXT {
+        sType:
+        vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT,
+        ..Default::default()
+    };
+    let mut properties = vk::VkPhysicalDeviceMemoryProperties2 {
+        sType: vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2,
+        pNext: ptr::addr_of_mut!(budget).cast(),
+        ..Default::default()
+    };
+
+    unsafe {
+        get_properties(
+            context.physical_device(),
+            ptr::addr_of_mut!(properties),
+        );
+    }
+
+    let memory = &properties.memoryProperties;
+
+    Some(
+        memory.memoryHeaps[..memory.memoryHeapCount as usize]
+            .iter()
+            .enumerate()
+            .map(|(i, heap)| HeapBudget {
+                size: heap.size,
+                device_local:
+                heap.flags & vk::VK_MEMORY_HEAP_DEVICE_LOCAL_BIT != 0,
+                budget: budget.heapBudget[i],
+                usage: budget.heapUsage[i],
+            })
+            .collect()
+    )
+}
+
+fn format_bytes(bytes: vk::VkDeviceSize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Describes what changed between two snapshots, one line starting
+/// with `Memory` each: the allocations and then each heap’s usage and
+/// budget.
+pub fn report(before: &Snapshot, after: &Snapshot) -> String {
+    let allocated = after.allocations.since(&before.allocations);
+
+    let mut report = format!(
+        "Memory allocated: {} buffers ({}), {} images ({})",
+        allocated.buffers,
+        format_bytes(allocated.buffer_bytes),
+        allocated.images,
+        format_bytes(allocated.image_bytes),
+    );
+
+    match (&before.heaps, &after.heaps) {
+        (Some(before), Some(after)) => {
+            for (i, (before, after)) in before.iter().zip(after).enumerate() {
+                let _ = write!(
+                    report,
+                    "\nMemory heap {}{}: {} used before the run, {} after, \
+                     of a {} budget ({}%; heap size {})",
+                    i,
+                    if after.device_local { " (device local)" } else { "" },
+                    format_bytes(before.usage),
+                    format_bytes(after.usage),
+                    format_bytes(after.budget),
+                    after.usage * 100 / after.budget.max(1),
+                    format_bytes(after.size),
+                );
+            }
+        },
+        _ => {
+            let _ = write!(
+                report,
+                "\nMemory budget: unknown, the device doesn’t support {}",
+                MEMORY_BUDGET_EXTENSION,
+            );
+        },
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fake_vulkan::FakeVulkan;
+
+    fn heap(usage: vk::VkDeviceSize) -> HeapBudget {
+        HeapBudget {
+            size: 4 << 30,
+            device_local: true,
+            budget: 3 << 30,
+            usage,
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(64 << 20), "64.0 MiB");
+        assert_eq!(format_bytes(3 << 30), "3.0 GiB");
+    }
+
+    #[test]
+    fn report_lines() {
+        let before = Snapshot {
+            allocations: Allocations {
+                buffers: 1,
+                buffer_bytes: 256,
+                images: 1,
+                image_bytes: 1 << 20,
+            },
+            heaps: Some(vec![heap(10 << 20)]),
+        };
+        let after = Snapshot {
+            allocations: Allocations {
+                buffers: 3,
+                buffer_bytes: (512 << 20) + 256,
+                images: 2,
+                image_bytes: 2 << 20,
+            },
+            heaps: Some(vec![heap(523 << 20)]),
+        };
+
+        assert_eq!(
+            report(&before, &after),
+            "Memory allocated: 2 buffers (512.0 MiB), 1 images (1.0 MiB)\n\
+             Memory heap 0 (device local): 10.0 MiB used before the run, \
+             523.0 MiB after, of a 3.0 GiB budget (17%; heap size 4.0 GiB)",
+        );
+
+        let after = Snapshot { heaps: None, ..after };
+        assert!(report(&before, &after).ends_with(
+            "\nMemory budget: unknown, the device doesn’t support \
+             VK_EXT_memory_budget"
+        ));
+    }
+
+    #[test]
+    fn query_budget() {
+        let mut fake_vulkan = FakeVulkan::new();
+        fake_vulkan.physical_devices.push(Default::default());
+
+        let device = &mut fake_vulkan.physical_devices[0];
+        device.memory_properties.memoryHeapCount = 1;
+        device.memory_properties.memoryHeaps[0] = vk::VkMemoryHeap {
+            size: 4 << 30,
+            flags: vk::VK_MEMORY_HEAP_DEVICE_LOCAL_BIT,
+        };
+        device.memory_budget.heapBudget[0] = 3 << 30;
+        device.memory_budget.heapUsage[0] = 10 << 20;
+
+        fake_vulkan.set_override();
+        let context = Context::new(&Requirements::new(), None).unwrap();
+
+        // Without the extension the budget is unknown
+        assert!(Snapshot::take(&context).heaps.is_none());
+
+        fake_vulkan.physical_devices[0].add_extension(MEMORY_BUDGET_EXTENSION);
+
+        assert_eq!(
+            Snapshot::take(&context).heaps,
+            Some(vec![heap(10 << 20)]),
+        );
+    }
+}
@@ -654,6 +654,38 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_stencil_masks() {
+        let mut key = Key::default();
+
+        assert!(key.set("front.compareMask", "0xf0").is_ok());
+        assert!(key.set("front.writeMask", "0x0f").is_ok());
+        assert!(key.set("front.reference", "0x80").is_ok());
+        // Masks with the top bit set can only be written as negative
+        // numbers
+        assert!(key.set("back.compareMask", "-256").is_ok());
+        assert!(key.set("back.writeMask", "0").is_ok());
+        assert!(
+            key.set("front.passOp", "VK_STENCIL_OP_REPLACE").is_ok()
+        );
+
+        let s = key.to_create_info();
+        let create_info = unsafe {
+            mem::transmute::<_, &vk::VkGraphicsPipelineCreateInfo>(
+                s.as_ptr()
+            )
+        };
+        let depth_stencil = unsafe { &*create_info.pDepthStencilState };
+
+        assert_eq!(depth_stencil.front.compareMask, 0xf0);
+        assert_eq!(depth_stencil.front.writeMask, 0x0f);
+        assert_eq!(depth_stencil.front.reference, 0x80);
+        assert_eq!(depth_stencil.front.passOp, vk::VK_STENCIL_OP_REPLACE);
+        assert_eq!(depth_stencil.back.compareMask, 0xffffff00);
+        assert_eq!(depth_stencil.back.writeMask, 0);
+        assert_eq!(depth_stencil.back.reference, 0);
+    }
+
     fn check_float_prop(value: &str) -> f32 {
         let mut key = Key::default();
 
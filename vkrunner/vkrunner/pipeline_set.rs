@@ -26,7 +26,7 @@ use crate::window::Window;
 use crate::compiler;
 use crate::shader_stage;
 use crate::vk;
-use crate::script::{Script, Buffer, BufferType, Operation};
+use crate::script::{Script, BufferType};
 use crate::pipeline_key;
 use crate::logger::Logger;
 use crate::vbo::Vbo;
@@ -34,13 +34,15 @@ use std::rc::Rc;
 use std::ptr;
 use std::mem;
 use std::fmt;
+use std::ffi::c_void;
 
 #[derive(Debug)]
 pub struct PipelineSet {
     pipelines: PipelineVec,
+    shader_objects: ShaderObjectVec,
     layout: PipelineLayout,
-    // The descriptor data is only created if there are buffers in the
-    // script
+    // The descriptor data is only created if there are buffers or
+    // textures in the script
     descriptor_data: Option<DescriptorData>,
     stages: vk::VkShaderStageFlagBits,
     // These are never read but they should probably be kept alive
@@ -70,8 +72,20 @@ pub enum Error {
     CreateDescriptorSetLayoutFailed,
     /// vkCreatePipelineLayout failed
     CreatePipelineLayoutFailed,
+    /// The push constant range is bigger than the device allows
+    PushConstantsTooLarge { size: u32, max: u32 },
+    /// A vertex attribute location is beyond the number of attributes
+    /// the device allows
+    TooManyVertexAttributes { location: u32, max: u32 },
+    /// The stride of a vertex data section is bigger than the device
+    /// allows
+    VertexStrideTooLarge { section: &'static str, stride: u32, max: u32 },
+    /// The device can’t read a vertex attribute’s format from a buffer
+    UnsupportedVertexFormat { location: u32, format: &'static str },
     /// vkCreatePipeline failed
     CreatePipelineFailed,
+    /// vkCreateShadersEXT failed
+    CreateShaderObjectFailed,
 }
 
 impl From<compiler::Error> for Error {
@@ -96,9 +110,50 @@ impl fmt::Display for Error {
             Error::CreatePipelineLayoutFailed => {
                 write!(f, "vkCreatePipelineLayout failed")
             },
+            Error::PushConstantsTooLarge { size, max } => {
+                write!(
+                    f,
+                    "The push constants need {} bytes but the device only \
+                     allows {} (maxPushConstantsSize). Move the larger data \
+                     into a uniform buffer.",
+                    size,
+                    max,
+                )
+            },
+            Error::TooManyVertexAttributes { location, max } => {
+                write!(
+                    f,
+                    "The vertex attribute at location {} is beyond the {} \
+                     attributes the device allows (maxVertexInputAttributes)",
+                    location,
+                    max,
+                )
+            },
+            Error::VertexStrideTooLarge { section, stride, max } => {
+                write!(
+                    f,
+                    "The {} section has a stride of {} bytes but the device \
+                     only allows {} (maxVertexInputBindingStride)",
+                    section,
+                    stride,
+                    max,
+                )
+            },
+            Error::UnsupportedVertexFormat { location, format } => {
+                write!(
+                    f,
+                    "The device can’t use {} as a vertex format, used by the \
+                     attribute at location {}",
+                    format,
+                    location,
+                )
+            },
             Error::CreatePipelineFailed => {
                 write!(f, "Pipeline creation function failed")
             },
+            Error::CreateShaderObjectFailed => {
+                write!(f, "vkCreateShadersEXT failed")
+            },
         }
     }
 }
@@ -170,6 +225,42 @@ impl PipelineCache {
     }
 }
 
+/// A buffer or texture binding point in a descriptor set layout.
+#[derive(Debug, Clone, Copy)]
+struct DescriptorBinding {
+    desc_set: u32,
+    binding: u32,
+    descriptor_type: vk::VkDescriptorType,
+}
+
+// Returns the binding points of all of the buffers and textures in
+// the script sorted by descriptor set and binding.
+fn descriptor_bindings(script: &Script) -> Vec<DescriptorBinding> {
+    let buffers = script.buffers().iter().map(|buffer| DescriptorBinding {
+        desc_set: buffer.desc_set,
+        binding: buffer.binding,
+        descriptor_type: match buffer.buffer_type {
+            BufferType::Ubo => vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+            BufferType::Ssbo => vk::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+        },
+    });
+    let textures = script.textures().iter().map(|texture| DescriptorBinding {
+        desc_set: texture.desc_set,
+        binding: texture.binding,
+        descriptor_type: vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+    });
+
+    let mut bindings = buffers.chain(textures).collect::<Vec<_>>();
+
+    bindings.sort_by(|a, b| {
+        a.desc_set
+            .cmp(&b.desc_set)
+            .then_with(|| a.binding.cmp(&b.binding))
+    });
+
+    bindings
+}
+
 #[derive(Debug)]
 struct DescriptorPool {
     handle: vk::VkDescriptorPool,
@@ -192,42 +283,31 @@ impl Drop for DescriptorPool {
 impl DescriptorPool {
     fn new(
         window: Rc<Window>,
-        buffers: &[Buffer],
+        bindings: &[DescriptorBinding],
     ) -> Result<DescriptorPool, Error> {
-        let mut n_ubos = 0;
-        let mut n_ssbos = 0;
-
-        for buffer in buffers {
-            match buffer.buffer_type {
-                BufferType::Ubo => n_ubos += 1,
-                BufferType::Ssbo => n_ssbos += 1,
-            }
-        }
-
         let mut pool_sizes = Vec::<vk::VkDescriptorPoolSize>::new();
 
-        if n_ubos > 0 {
-            pool_sizes.push(vk::VkDescriptorPoolSize {
-                type_: vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
-                descriptorCount: n_ubos,
-            });
-        }
-        if n_ssbos > 0 {
-            pool_sizes.push(vk::VkDescriptorPoolSize {
-                type_: vk::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
-                descriptorCount: n_ssbos,
-            });
+        for binding in bindings {
+            match pool_sizes.iter_mut().find(|size| {
+                size.type_ == binding.descriptor_type
+            }) {
+                Some(size) => size.descriptorCount += 1,
+                None => pool_sizes.push(vk::VkDescriptorPoolSize {
+                    type_: binding.descriptor_type,
+                    descriptorCount: 1,
+                }),
+            }
         }
 
         // The descriptor pool shouldn’t have been created if there
-        // were no buffers
+        // were no buffers or textures
         assert!(!pool_sizes.is_empty());
 
         let create_info = vk::VkDescriptorPoolCreateInfo {
             sType: vk::VK_STRUCTURE_TYPE_DESCRIPTOR_POOL_CREATE_INFO,
             flags: vk::VK_DESCRIPTOR_POOL_CREATE_FREE_DESCRIPTOR_SET_BIT,
             pNext: ptr::null(),
-            maxSets: n_desc_sets(buffers) as u32,
+            maxSets: n_desc_sets(bindings) as u32,
             poolSizeCount: pool_sizes.len() as u32,
             pPoolSizes: pool_sizes.as_ptr(),
         };
@@ -319,34 +399,31 @@ impl DescriptorSetLayoutVec {
 
 fn create_descriptor_set_layouts(
     window: Rc<Window>,
-    buffers: &[Buffer],
+    descriptor_bindings: &[DescriptorBinding],
     stages: vk::VkShaderStageFlags,
 ) -> Result<DescriptorSetLayoutVec, Error> {
-    let n_desc_sets = n_desc_sets(buffers);
+    let n_desc_sets = n_desc_sets(descriptor_bindings);
     let mut layouts = DescriptorSetLayoutVec::new(window);
     let mut bindings = Vec::new();
-    let mut buffer_num = 0;
+    let mut binding_num = 0;
 
     for desc_set in 0..n_desc_sets {
         bindings.clear();
 
-        while buffer_num < buffers.len()
-            && buffers[buffer_num].desc_set as usize == desc_set
+        while binding_num < descriptor_bindings.len()
+            && descriptor_bindings[binding_num].desc_set as usize == desc_set
         {
-            let buffer = &buffers[buffer_num];
+            let binding = &descriptor_bindings[binding_num];
 
             bindings.push(vk::VkDescriptorSetLayoutBinding {
-                binding: buffer.binding,
-                descriptorType: match buffer.buffer_type {
-                    BufferType::Ubo => vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
-                    BufferType::Ssbo => vk::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
-                },
+                binding: binding.binding,
+                descriptorType: binding.descriptor_type,
                 descriptorCount: 1,
                 stageFlags: stages,
                 pImmutableSamplers: ptr::null(),
             });
 
-            buffer_num += 1;
+            binding_num += 1;
         }
 
         layouts.add(&bindings)?;
@@ -357,10 +434,10 @@ fn create_descriptor_set_layouts(
     Ok(layouts)
 }
 
-fn n_desc_sets(buffers: &[Buffer]) -> usize {
-    match buffers.last() {
+fn n_desc_sets(bindings: &[DescriptorBinding]) -> usize {
+    match bindings.last() {
         // The number of descriptor sets is the highest used
-        // descriptor set index + 1. The buffers are in order so the
+        // descriptor set index + 1. The bindings are in order so the
         // highest one should be the last one.
         Some(last) => last.desc_set as usize + 1,
         None => 0,
@@ -376,17 +453,17 @@ struct DescriptorData {
 impl DescriptorData {
     fn new(
         window: &Rc<Window>,
-        buffers: &[Buffer],
+        bindings: &[DescriptorBinding],
         stages: vk::VkShaderStageFlagBits,
     ) -> Result<DescriptorData, Error> {
         let pool = DescriptorPool::new(
             Rc::clone(&window),
-            buffers,
+            bindings,
         )?;
 
         let layouts = create_descriptor_set_layouts(
             Rc::clone(&window),
-            buffers,
+            bindings,
             stages,
         )?;
 
@@ -399,6 +476,7 @@ fn compile_shaders(
     window: &Rc<Window>,
     script: &Script,
     show_disassembly: bool,
+    separate_shader_objects: bool,
 ) -> Result<[Option<ShaderModule>; shader_stage::N_STAGES], Error> {
     let mut modules: [Option<ShaderModule>; shader_stage::N_STAGES] =
         Default::default();
@@ -408,6 +486,11 @@ fn compile_shaders(
             continue;
         }
 
+        // Shader objects are created from the code instead
+        if separate_shader_objects && stage == shader_stage::Stage::Compute {
+            continue;
+        }
+
         modules[stage as usize] = Some(ShaderModule {
             handle: compiler::build_stage(
                 logger,
@@ -436,16 +519,105 @@ fn stage_flags(script: &Script) -> vk::VkShaderStageFlagBits {
 }
 
 fn push_constant_size(script: &Script) -> usize {
+    if let Some(size) = script.push_constant_size() {
+        return size;
+    }
+
     script.commands()
         .iter()
-        .map(|command| match &command.op {
-            Operation::SetPushCommand { offset, data } => offset + data.len(),
-            _ => 0,
-        })
+        .filter_map(|command| command.op.push_constant_end())
         .max()
         .unwrap_or(0)
 }
 
+fn push_constant_range(
+    script: &Script,
+    stages: vk::VkShaderStageFlagBits,
+) -> vk::VkPushConstantRange {
+    vk::VkPushConstantRange {
+        stageFlags: script.push_constant_stages().unwrap_or(stages),
+        offset: 0,
+        size: push_constant_size(script) as u32,
+    }
+}
+
+fn device_limits(window: &Window) -> vk::VkPhysicalDeviceLimits {
+    let context = window.context();
+    let mut props = vk::VkPhysicalDeviceProperties::default();
+
+    unsafe {
+        context.instance().vkGetPhysicalDeviceProperties.unwrap()(
+            context.physical_device(),
+            &mut props as *mut vk::VkPhysicalDeviceProperties,
+        );
+    }
+
+    props.limits
+}
+
+fn supports_vertex_format(window: &Window, format: vk::VkFormat) -> bool {
+    let context = window.context();
+    let mut props = vk::VkFormatProperties::default();
+
+    unsafe {
+        context.instance().vkGetPhysicalDeviceFormatProperties.unwrap()(
+            context.physical_device(),
+            format,
+            &mut props as *mut vk::VkFormatProperties,
+        );
+    }
+
+    props.bufferFeatures & vk::VK_FORMAT_FEATURE_VERTEX_BUFFER_BIT != 0
+}
+
+// Checks the vertex and instance data against the device limits so
+// that the error can say which attribute is the problem instead of
+// the pipeline creation failing
+fn check_vertex_input(window: &Window, script: &Script) -> Result<(), Error> {
+    let sections = [
+        ("vertex data", script.vertex_data()),
+        ("instance data", script.instance_data()),
+    ];
+    let limits = device_limits(window);
+    let mut n_attribs = 0;
+
+    for (section, vbo) in sections {
+        let Some(vbo) = vbo else { continue; };
+
+        let stride = vbo.stride() as u32;
+
+        if stride > limits.maxVertexInputBindingStride {
+            return Err(Error::VertexStrideTooLarge {
+                section,
+                stride,
+                max: limits.maxVertexInputBindingStride,
+            });
+        }
+
+        for attrib in vbo.attribs() {
+            n_attribs += 1;
+
+            if attrib.location() >= limits.maxVertexInputAttributes
+                || n_attribs > limits.maxVertexInputAttributes
+            {
+                return Err(Error::TooManyVertexAttributes {
+                    location: attrib.location(),
+                    max: limits.maxVertexInputAttributes,
+                });
+            }
+
+            if !supports_vertex_format(window, attrib.format().vk_format) {
+                return Err(Error::UnsupportedVertexFormat {
+                    location: attrib.location(),
+                    format: attrib.format().name,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 struct PipelineLayout {
     handle: vk::VkPipelineLayout,
@@ -474,11 +646,18 @@ impl PipelineLayout {
     ) -> Result<PipelineLayout, Error> {
         let mut handle = vk::null_handle();
 
-        let push_constant_range = vk::VkPushConstantRange {
-            stageFlags: stages,
-            offset: 0,
-            size: push_constant_size(script) as u32,
-        };
+        let push_constant_range = push_constant_range(script, stages);
+
+        if push_constant_range.size > 0 {
+            let max = device_limits(&window).maxPushConstantsSize;
+
+            if push_constant_range.size > max {
+                return Err(Error::PushConstantsTooLarge {
+                    size: push_constant_range.size,
+                    max,
+                });
+            }
+        }
 
         let mut create_info = vk::VkPipelineLayoutCreateInfo::default();
 
@@ -519,12 +698,27 @@ struct VertexInputState {
     // them so they need to be kept alive
     _input_bindings: Vec::<vk::VkVertexInputBindingDescription>,
     _attribs: Vec::<vk::VkVertexInputAttributeDescription>,
+    _divisor: Option<Box<DivisorState>>,
+}
+
+// The divisor description and the struct that points to it, kept in
+// a box so that the pointers stay valid when the VertexInputState
+// moves
+#[derive(Debug)]
+struct DivisorState {
+    create_info: vk::VkPipelineVertexInputDivisorStateCreateInfoEXT,
+    description: vk::VkVertexInputBindingDivisorDescriptionEXT,
 }
 
+// Binding of the per-instance attributes from the instance data
+// section. The vertex data section uses binding 0.
+pub(crate) const INSTANCE_DATA_BINDING: u32 = 1;
+
 impl VertexInputState {
     fn new(script: &Script, key: &pipeline_key::Key) -> VertexInputState {
         let mut input_bindings = Vec::new();
         let mut attribs = Vec::new();
+        let mut divisor = None;
 
         match key.source() {
             pipeline_key::Source::Rectangle => {
@@ -545,18 +739,42 @@ impl VertexInputState {
                     VertexInputState::set_up_vertex_data_attribs(
                         &mut input_bindings,
                         &mut attribs,
-                        vbo
+                        vbo,
+                        0, // binding
+                        vk::VK_VERTEX_INPUT_RATE_VERTEX,
                     );
                 }
+                if let Some(vbo) = script.instance_data() {
+                    VertexInputState::set_up_vertex_data_attribs(
+                        &mut input_bindings,
+                        &mut attribs,
+                        vbo,
+                        INSTANCE_DATA_BINDING,
+                        vk::VK_VERTEX_INPUT_RATE_INSTANCE,
+                    );
+
+                    if script.instance_divisor() != 1 {
+                        divisor = Some(VertexInputState::divisor_state(
+                            script.instance_divisor()
+                        ));
+                    }
+                }
             }
         }
 
+        let p_next = match divisor {
+            Some(ref divisor) => {
+                ptr::addr_of!(divisor.create_info).cast::<c_void>()
+            },
+            None => ptr::null(),
+        };
+
         VertexInputState {
             create_info: vk::VkPipelineVertexInputStateCreateInfo {
                 sType:
                 vk::VK_STRUCTURE_TYPE_PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
                 flags: 0,
-                pNext: ptr::null(),
+                pNext: p_next,
                 vertexBindingDescriptionCount: input_bindings.len() as u32,
                 pVertexBindingDescriptions: input_bindings.as_ptr(),
                 vertexAttributeDescriptionCount: attribs.len() as u32,
@@ -564,24 +782,47 @@ impl VertexInputState {
             },
             _input_bindings: input_bindings,
             _attribs: attribs,
+            _divisor: divisor,
         }
     }
 
+    fn divisor_state(divisor: u32) -> Box<DivisorState> {
+        let mut state = Box::new(DivisorState {
+            create_info: vk::VkPipelineVertexInputDivisorStateCreateInfoEXT {
+                sType: vk::VK_STRUCTURE_TYPE_PIPELINE_VERTEX_INPUT_DIVISOR_STATE_CREATE_INFO_EXT,
+                pNext: ptr::null(),
+                vertexBindingDivisorCount: 1,
+                pVertexBindingDivisors: ptr::null(),
+            },
+            description: vk::VkVertexInputBindingDivisorDescriptionEXT {
+                binding: INSTANCE_DATA_BINDING,
+                divisor,
+            },
+        });
+
+        state.create_info.pVertexBindingDivisors =
+            ptr::addr_of!(state.description);
+
+        state
+    }
+
     fn set_up_vertex_data_attribs(
         input_bindings: &mut Vec::<vk::VkVertexInputBindingDescription>,
         attribs: &mut Vec::<vk::VkVertexInputAttributeDescription>,
         vbo: &Vbo,
+        binding: u32,
+        input_rate: vk::VkVertexInputRate,
     ) {
         input_bindings.push(vk::VkVertexInputBindingDescription {
-            binding: 0,
+            binding,
             stride: vbo.stride() as u32,
-            inputRate: vk::VK_VERTEX_INPUT_RATE_VERTEX,
+            inputRate: input_rate,
         });
 
         for attrib in vbo.attribs().iter() {
             attribs.push(vk::VkVertexInputAttributeDescription {
                 location: attrib.location(),
-                binding: 0,
+                binding,
                 format: attrib.format().vk_format,
                 offset: attrib.offset() as u32,
             });
@@ -599,6 +840,11 @@ struct PipelineVec {
 impl Drop for PipelineVec {
     fn drop(&mut self) {
         for &handle in self.handles.iter() {
+            // Compute keys have no pipeline when shader objects are used
+            if handle == vk::null_handle() {
+                continue;
+            }
+
             unsafe {
                 self.window.device().vkDestroyPipeline.unwrap()(
                     self.window.vk_device(),
@@ -617,6 +863,7 @@ impl PipelineVec {
         pipeline_cache: vk::VkPipelineCache,
         layout: vk::VkPipelineLayout,
         modules: &[Option<ShaderModule>],
+        separate_shader_objects: bool,
     ) -> Result<PipelineVec, Error> {
         let mut vec = PipelineVec { window, handles: Vec::new() };
 
@@ -644,6 +891,9 @@ impl PipelineVec {
 
                     pipeline
                 },
+                pipeline_key::Type::Compute if separate_shader_objects => {
+                    vk::null_handle()
+                },
                 pipeline_key::Type::Compute => {
                     PipelineVec::create_compute_pipeline(
                         &vec.window,
@@ -890,28 +1140,177 @@ impl PipelineVec {
     }
 }
 
+// Compute shaders created with VK_EXT_shader_object, indexed by
+// pipeline key like the pipelines. The graphics keys have a null
+// handle.
+#[derive(Debug)]
+struct ShaderObjectVec {
+    handles: Vec<vk::VkShaderEXT>,
+    // needed for the destructor
+    window: Rc<Window>,
+}
+
+impl Drop for ShaderObjectVec {
+    fn drop(&mut self) {
+        for &handle in self.handles.iter() {
+            if handle == vk::null_handle() {
+                continue;
+            }
+
+            unsafe {
+                self.window.device().vkDestroyShaderEXT.unwrap()(
+                    self.window.vk_device(),
+                    handle,
+                    ptr::null(), // allocator
+                );
+            }
+        }
+    }
+}
+
+impl ShaderObjectVec {
+    fn new(
+        window: Rc<Window>,
+        script: &Script,
+        stages: vk::VkShaderStageFlagBits,
+        set_layouts: &[vk::VkDescriptorSetLayout],
+        code: Option<&[u32]>,
+    ) -> Result<ShaderObjectVec, Error> {
+        let mut vec = ShaderObjectVec { window, handles: Vec::new() };
+
+        for key in script.pipeline_keys().iter() {
+            let handle = match (key.pipeline_type(), code) {
+                (pipeline_key::Type::Compute, Some(code)) => {
+                    ShaderObjectVec::create_compute_shader(
+                        &vec.window,
+                        script,
+                        key,
+                        stages,
+                        set_layouts,
+                        code,
+                    )?
+                },
+                _ => vk::null_handle(),
+            };
+
+            vec.handles.push(handle);
+        }
+
+        Ok(vec)
+    }
+
+    fn create_compute_shader(
+        window: &Window,
+        script: &Script,
+        key: &pipeline_key::Key,
+        stages: vk::VkShaderStageFlagBits,
+        set_layouts: &[vk::VkDescriptorSetLayout],
+        code: &[u32],
+    ) -> Result<vk::VkShaderEXT, Error> {
+        let entrypoint = PipelineVec::null_terminated_entrypoint(
+            key,
+            shader_stage::Stage::Compute,
+        );
+
+        // The set layouts and push constants have to match the
+        // pipeline layout that the descriptor sets and push constants
+        // are bound with
+        let push_constant_range = push_constant_range(script, stages);
+
+        let rss_info = script.requirements().required_subgroup_size.map(
+            |size| vk::VkShaderRequiredSubgroupSizeCreateInfoEXT {
+                sType: vk::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_REQUIRED_SUBGROUP_SIZE_CREATE_INFO,
+                pNext: ptr::null_mut(),
+                requiredSubgroupSize: size,
+            });
+
+        let mut create_info = vk::VkShaderCreateInfoEXT {
+            sType: vk::VK_STRUCTURE_TYPE_SHADER_CREATE_INFO_EXT,
+            pNext: if let Some(ref rss_info) = rss_info {
+                ptr::addr_of!(*rss_info).cast()
+            } else {
+                ptr::null()
+            },
+            flags: 0,
+            stage: vk::VK_SHADER_STAGE_COMPUTE_BIT,
+            nextStage: 0,
+            codeType: vk::VK_SHADER_CODE_TYPE_SPIRV_EXT,
+            codeSize: code.len() * mem::size_of::<u32>(),
+            pCode: code.as_ptr().cast(),
+            pName: entrypoint.as_ptr().cast(),
+            setLayoutCount: set_layouts.len() as u32,
+            pSetLayouts: set_layouts.as_ptr(),
+            pushConstantRangeCount: 0,
+            pPushConstantRanges: ptr::null(),
+            pSpecializationInfo: ptr::null(),
+        };
+
+        if push_constant_range.size > 0 {
+            create_info.pushConstantRangeCount = 1;
+            create_info.pPushConstantRanges =
+                ptr::addr_of!(push_constant_range);
+        }
+
+        let mut handle = vk::null_handle();
+
+        let res = unsafe {
+            window.device().vkCreateShadersEXT.unwrap()(
+                window.vk_device(),
+                1, // createInfoCount
+                ptr::addr_of!(create_info),
+                ptr::null(), // allocator
+                ptr::addr_of_mut!(handle),
+            )
+        };
+
+        if res == vk::VK_SUCCESS {
+            Ok(handle)
+        } else {
+            Err(Error::CreateShaderObjectFailed)
+        }
+    }
+}
+
 impl PipelineSet {
     pub fn new(
         logger: &mut Logger,
         window: Rc<Window>,
         script: &Script,
         show_disassembly: bool,
+        separate_shader_objects: bool,
     ) -> Result<PipelineSet, Error> {
         let modules = compile_shaders(
             logger,
             &window,
             script,
-            show_disassembly
+            show_disassembly,
+            separate_shader_objects,
         )?;
 
+        let compute_code = if separate_shader_objects
+            && !script.shaders(shader_stage::Stage::Compute).is_empty()
+        {
+            Some(compiler::build_stage_code(
+                logger,
+                window.context(),
+                script,
+                shader_stage::Stage::Compute,
+                show_disassembly,
+            )?)
+        } else {
+            None
+        };
+
         let pipeline_cache = PipelineCache::new(Rc::clone(&window))?;
 
         let stages = stage_flags(script);
 
-        let descriptor_data = if script.buffers().is_empty() {
+        let bindings = descriptor_bindings(script);
+
+        let descriptor_data = if bindings.is_empty() {
             None
         } else {
-            Some(DescriptorData::new(&window, script.buffers(), stages)?)
+            Some(DescriptorData::new(&window, &bindings, stages)?)
         };
 
         let layout = PipelineLayout::new(
@@ -921,12 +1320,26 @@ impl PipelineSet {
             descriptor_data.as_ref(),
         )?;
 
+        check_vertex_input(&window, script)?;
+
         let pipelines = PipelineVec::new(
             Rc::clone(&window),
             script,
             pipeline_cache.handle,
             layout.handle,
             &modules,
+            separate_shader_objects,
+        )?;
+
+        let shader_objects = ShaderObjectVec::new(
+            Rc::clone(&window),
+            script,
+            stages,
+            descriptor_data
+                .as_ref()
+                .map(|data| &data.layouts.handles[..])
+                .unwrap_or(&[]),
+            compute_code.as_deref(),
         )?;
 
         Ok(PipelineSet {
@@ -936,6 +1349,7 @@ impl PipelineSet {
             descriptor_data,
             layout,
             pipelines,
+            shader_objects,
         })
     }
 
@@ -958,6 +1372,12 @@ impl PipelineSet {
         &self.pipelines.handles
     }
 
+    /// The compute shader objects, indexed like [PipelineSet::pipelines].
+    /// The handle is null for keys that use a pipeline instead.
+    pub fn shader_objects(&self) -> &[vk::VkShaderEXT] {
+        &self.shader_objects.handles
+    }
+
     pub fn descriptor_pool(&self) -> Option<vk::VkDescriptorPool> {
         self.descriptor_data.as_ref().map(|data| data.pool.handle)
     }
@@ -1030,6 +1450,7 @@ mod test {
                 Rc::clone(&window),
                 &script,
                 false, // show_disassembly
+                false, // separate_shader_objects
             )?;
 
             Ok(TestData { fake_vulkan, window, pipeline_set })
@@ -1374,6 +1795,190 @@ mod test {
         );
     }
 
+    #[test]
+    fn textures() {
+        let mut texels = crate::temp_file::TempFile::new().unwrap();
+        std::io::Write::write_all(texels.file().unwrap(), &[0; 4]).unwrap();
+        texels.close();
+        let path = texels.filename().to_str().unwrap();
+
+        let mut test_data = TestData::new(&format!(
+            "[vertex shader passthrough]\n\
+             [fragment shader]\n\
+             03 02 23 07\n\
+             fe ca fe ca\n\
+             [test]\n\
+             ubo 2 1024\n\
+             texture 1 1 1 {}\n\
+             texture 1:0 1 1 {}\n\
+             draw rect 0 0 1 1\n",
+            path,
+            path,
+        )).unwrap();
+
+        assert_eq!(test_data.pipeline_set.descriptor_set_layouts().len(), 2);
+
+        let bindings = test_data.descriptor_set_layout_bindings(0);
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(bindings[0].binding, 1);
+        assert_eq!(
+            bindings[0].descriptorType,
+            vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER
+        );
+        assert_eq!(bindings[1].binding, 2);
+        assert_eq!(
+            bindings[1].descriptorType,
+            vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER
+        );
+
+        let bindings = test_data.descriptor_set_layout_bindings(1);
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].binding, 0);
+        assert_eq!(
+            bindings[0].descriptorType,
+            vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER
+        );
+        assert_eq!(
+            bindings[0].stageFlags,
+            vk::VK_SHADER_STAGE_VERTEX_BIT
+                | vk::VK_SHADER_STAGE_FRAGMENT_BIT,
+        );
+    }
+
+    #[test]
+    fn instance_data() {
+        let mut test_data = TestData::new(
+            "[vertex shader passthrough]\n\
+             [fragment shader]\n\
+             03 02 23 07\n\
+             fe ca fe ca\n\
+             [vertex data]\n\
+             0/R32G32_SFLOAT\n\
+             -0.5 -0.5\n\
+             [instance data]\n\
+             1/R32_SFLOAT 2/R8_UNORM\n\
+             1.5 3\n\
+             [test]\n\
+             draw arrays instanced TRIANGLE_LIST 0 3 2\n"
+        ).unwrap();
+
+        let create_data = test_data.graphics_create_info(0);
+
+        assert_eq!(create_data.bindings.len(), 2);
+        assert_eq!(create_data.bindings[0].binding, 0);
+        assert_eq!(
+            create_data.bindings[0].inputRate,
+            vk::VK_VERTEX_INPUT_RATE_VERTEX,
+        );
+        assert_eq!(create_data.bindings[1].binding, INSTANCE_DATA_BINDING);
+        assert_eq!(create_data.bindings[1].stride, 8);
+        assert_eq!(
+            create_data.bindings[1].inputRate,
+            vk::VK_VERTEX_INPUT_RATE_INSTANCE,
+        );
+
+        assert_eq!(create_data.attribs.len(), 3);
+        assert_eq!(create_data.attribs[0].binding, 0);
+        assert_eq!(create_data.attribs[1].location, 1);
+        assert_eq!(create_data.attribs[1].binding, INSTANCE_DATA_BINDING);
+        assert_eq!(create_data.attribs[2].location, 2);
+        assert_eq!(create_data.attribs[2].binding, INSTANCE_DATA_BINDING);
+        assert_eq!(create_data.attribs[2].offset, 4);
+
+        // A divisor of 1 is the default so it doesn’t need the extension
+        assert!(create_data.divisors.is_empty());
+
+        drop(test_data);
+
+        let mut test_data = TestData::new(
+            "[vertex shader passthrough]\n\
+             [fragment shader]\n\
+             03 02 23 07\n\
+             fe ca fe ca\n\
+             [instance data divisor 4]\n\
+             0/R32G32_SFLOAT\n\
+             -0.5 -0.5\n\
+             [test]\n\
+             draw arrays instanced TRIANGLE_LIST 0 3 8\n"
+        ).unwrap();
+
+        let create_data = test_data.graphics_create_info(0);
+        assert_eq!(create_data.bindings.len(), 1);
+        assert_eq!(create_data.bindings[0].binding, INSTANCE_DATA_BINDING);
+        assert_eq!(create_data.divisors.len(), 1);
+        assert_eq!(create_data.divisors[0].binding, INSTANCE_DATA_BINDING);
+        assert_eq!(create_data.divisors[0].divisor, 4);
+    }
+
+    #[test]
+    fn vertex_input_limits() {
+        let error = TestData::new_with_errors(
+            "[vertex shader passthrough]\n\
+             [fragment shader]\n\
+             03 02 23 07\n\
+             fe ca fe ca\n\
+             [vertex data]\n\
+             0/R32G32_SFLOAT 1/R32_SFLOAT 2/R32_SFLOAT\n\
+             -0.5 -0.5 1 2\n",
+            |fake_vulkan| {
+                fake_vulkan.physical_devices[0]
+                    .properties
+                    .limits
+                    .maxVertexInputAttributes = 2;
+            },
+        ).unwrap_err();
+
+        assert_eq!(
+            &error.to_string(),
+            "The vertex attribute at location 2 is beyond the 2 attributes \
+             the device allows (maxVertexInputAttributes)",
+        );
+
+        let error = TestData::new_with_errors(
+            "[vertex shader passthrough]\n\
+             [fragment shader]\n\
+             03 02 23 07\n\
+             fe ca fe ca\n\
+             [instance data]\n\
+             0/R32G32B32A32_SFLOAT 1/R32G32B32A32_SFLOAT\n\
+             1 2 3 4 5 6 7 8\n",
+            |fake_vulkan| {
+                fake_vulkan.physical_devices[0]
+                    .properties
+                    .limits
+                    .maxVertexInputBindingStride = 16;
+            },
+        ).unwrap_err();
+
+        assert_eq!(
+            &error.to_string(),
+            "The instance data section has a stride of 32 bytes but the \
+             device only allows 16 (maxVertexInputBindingStride)",
+        );
+
+        let error = TestData::new_with_errors(
+            "[vertex shader passthrough]\n\
+             [fragment shader]\n\
+             03 02 23 07\n\
+             fe ca fe ca\n\
+             [vertex data]\n\
+             0/R32G32_SFLOAT 3/R64_SFLOAT\n\
+             -0.5 -0.5 1\n",
+            |fake_vulkan| {
+                fake_vulkan.physical_devices[0].format_properties.insert(
+                    vk::VK_FORMAT_R64_SFLOAT,
+                    Default::default(),
+                );
+            },
+        ).unwrap_err();
+
+        assert_eq!(
+            &error.to_string(),
+            "The device can’t use R64_SFLOAT as a vertex format, used by the \
+             attribute at location 3",
+        );
+    }
+
     #[test]
     fn compile_error() {
         let error = TestData::new(
@@ -1440,6 +2045,44 @@ mod test {
         }
     }
 
+    #[test]
+    fn push_constant_range() {
+        let mut test_data = TestData::new(
+            "[compute shader]\n\
+             03 02 23 07\n\
+             ca fe ca fe\n\
+             [test]\n\
+             push size 64\n\
+             push stages compute, fragment\n\
+             push float 0 42.0\n\
+             compute 1 1 1\n"
+        ).unwrap();
+
+        let layout = test_data.pipeline_layout_create_info();
+        assert_eq!(layout.push_constant_ranges.len(), 1);
+        assert_eq!(
+            layout.push_constant_ranges[0].stageFlags,
+            vk::VK_SHADER_STAGE_COMPUTE_BIT | vk::VK_SHADER_STAGE_FRAGMENT_BIT,
+        );
+        assert_eq!(layout.push_constant_ranges[0].size, 64);
+
+        // Only one fake Vulkan instance can exist at a time
+        drop(test_data);
+
+        let error = TestData::new(
+            "[test]\n\
+             push size 256\n\
+             draw rect 0 0 1 1\n"
+        ).unwrap_err();
+
+        assert_eq!(
+            &error.to_string(),
+            "The push constants need 256 bytes but the device only allows \
+             128 (maxPushConstantsSize). Move the larger data into a uniform \
+             buffer.",
+        );
+    }
+
     #[test]
     fn no_buffers() {
         let test_data = TestData::new("").unwrap();
@@ -0,0 +1,366 @@
+// vkrunner
+//
+// Copyright 2026 The shaderc-vkrunner-mcp contributors
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next
+// paragraph) shall be included in all copies or substantial portions of the
+// Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+// THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Computes how much workgroup shared memory and push constant space a
+//! SPIR-V module declares, so that a shader that needs more than the
+//! device allows can be reported before the pipeline is created.
+
+use std::collections::{HashMap, HashSet};
+
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_VARIABLE: u32 = 59;
+
+const DECORATION_ROW_MAJOR: u32 = 4;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_CLASS_WORKGROUP: u32 = 4;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+
+// Words in the module header before the first instruction
+const HEADER_SIZE: usize = 5;
+
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    size: u64,
+    align: u64,
+}
+
+fn round_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align.max(1)) * align.max(1)
+}
+
+/// Sizes in bytes of the memory that a module declares.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Footprint {
+    /// Total size of the variables in the Workgroup storage class
+    pub workgroup: u64,
+    /// Size of the push constant block, up to the end of its last
+    /// member
+    pub push_constants: u64,
+}
+
+/// Computes the memory footprint of a module. Types with explicit
+/// layout decorations use them. Other types are laid out like std430,
+/// where a three-component vector is aligned like a four-component
+/// one. Array lengths given by spec constants use the default value.
+pub fn footprint(code: &[u32]) -> Footprint {
+    let mut types = HashMap::<u32, Layout>::new();
+    let mut constants = HashMap::<u32, u64>::new();
+    let mut pointers = HashMap::<u32, u32>::new();
+    // Columns and rows of each matrix type
+    let mut matrices = HashMap::<u32, (u64, u64)>::new();
+    let mut vector_sizes = HashMap::<u32, u64>::new();
+    let mut array_strides = HashMap::<u32, u64>::new();
+    let mut offsets = HashMap::<(u32, u32), u64>::new();
+    let mut matrix_strides = HashMap::<(u32, u32), u64>::new();
+    let mut row_major = HashSet::<(u32, u32)>::new();
+    let mut result = Footprint::default();
+
+    let mut pos = HEADER_SIZE;
+
+    while pos < code.len() {
+        let word_count = (code[pos] >> 16) as usize;
+        let opcode = code[pos] & 0xffff;
+
+        if word_count == 0 || pos + word_count > code.len() {
+            break;
+        }
+
+        let args = &code[pos + 1..pos + word_count];
+
+        match (opcode, args) {
+            (OP_DECORATE, &[id, DECORATION_ARRAY_STRIDE, stride]) => {
+                array_strides.insert(id, stride as u64);
+            },
+            (OP_MEMBER_DECORATE, &[id, member, decoration, ref values @ ..]) => {
+                match (decoration, values) {
+                    (DECORATION_OFFSET, &[offset]) => {
+                        offsets.insert((id, member), offset as u64);
+                    },
+                    (DECORATION_MATRIX_STRIDE, &[stride]) => {
+                        matrix_strides.insert((id, member), stride as u64);
+                    },
+                    (DECORATION_ROW_MAJOR, &[]) => {
+                        row_major.insert((id, member));
+                    },
+                    _ => (),
+                }
+            },
+            (OP_TYPE_BOOL, &[id]) => {
+                types.insert(id, Layout { size: 4, align: 4 });
+            },
+            (OP_TYPE_INT | OP_TYPE_FLOAT, &[id, width, ..]) => {
+                let size = width as u64 / 8;
+                types.insert(id, Layout { size, align: size });
+            },
+            (OP_TYPE_VECTOR, &[id, component, count]) => {
+                if let Some(component) = types.get(&component).copied() {
+                    let count = count as u64;
+                    let aligned_count = if count == 3 { 4 } else { count };
+                    types.insert(id, Layout {
+                        size: component.size * count,
+                        align: component.align * aligned_count,
+                    });
+                    vector_sizes.insert(id, count);
+                }
+            },
+            (OP_TYPE_MATRIX, &[id, column, count]) => {
+                if let Some(column_layout) = types.get(&column).copied() {
+                    let stride = round_up(
+                        column_layout.size,
+                        column_layout.align,
+                    );
+                    types.insert(id, Layout {
+                        size: stride * count as u64,
+                        align: column_layout.align,
+                    });
+                    matrices.insert(id, (
+                        count as u64,
+                        vector_sizes.get(&column).copied().unwrap_or(1),
+                    ));
+                }
+            },
+            (OP_TYPE_ARRAY, &[id, element, length]) => {
+                if let (Some(element), Some(&length)) = (
+                    types.get(&element).copied(),
+                    constants.get(&length),
+                ) {
+                    let stride = array_strides
+                        .get(&id)
+                        .copied()
+                        .unwrap_or(round_up(element.size, element.align));
+                    types.insert(id, Layout {
+                        size: stride * length,
+                        align: element.align,
+                    });
+                }
+            },
+            (OP_TYPE_STRUCT, &[id, ref members @ ..]) => {
+                let mut size = 0;
+                let mut align = 1;
+                let mut complete = true;
+
+                for (index, &member_type) in members.iter().enumerate() {
+                    let Some(mut member) = types.get(&member_type).copied()
+                    else {
+                        complete = false;
+                        break;
+                    };
+                    let key = (id, index as u32);
+
+                    if let (Some(&(columns, rows)), Some(&stride)) = (
+                        matrices.get(&member_type),
+                        matrix_strides.get(&key),
+                    ) {
+                        let count = if row_major.contains(&key) {
+                            rows
+                        } else {
+                            columns
+                        };
+                        member.size = stride * count;
+                    }
+
+                    size = match offsets.get(&key) {
+                        Some(&offset) => size.max(offset + member.size),
+                        None => round_up(size, member.align) + member.size,
+                    };
+                    align = align.max(member.align);
+                }
+
+                // Explicitly laid out blocks end at their last member
+                let explicit = offsets.contains_key(&(id, 0));
+
+                if complete {
+                    types.insert(id, Layout {
+                        size: if explicit { size } else { round_up(size, align) },
+                        align,
+                    });
+                }
+            },
+            (OP_TYPE_POINTER, &[id, _, pointee]) => {
+                pointers.insert(id, pointee);
+            },
+            (OP_CONSTANT | OP_SPEC_CONSTANT, &[_, id, value, ..]) => {
+                constants.insert(id, value as u64);
+            },
+            (OP_VARIABLE, &[pointer, _, storage_class, ..]) => {
+                let layout = pointers
+                    .get(&pointer)
+                    .and_then(|pointee| types.get(pointee).copied());
+
+                match (storage_class, layout) {
+                    (STORAGE_CLASS_WORKGROUP, Some(layout)) => {
+                        result.workgroup = round_up(
+                            result.workgroup,
+                            layout.align,
+                        ) + layout.size;
+                    },
+                    (STORAGE_CLASS_PUSH_CONSTANT, Some(layout)) => {
+                        result.push_constants =
+                            result.push_constants.max(layout.size);
+                    },
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+
+        pos += word_count;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn instruction(opcode: u32, args: &[u32]) -> Vec<u32> {
+        let mut words = vec![((args.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(args);
+        words
+    }
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut code = vec![0x07230203, 0x00010000, 0, 100, 0];
+        for instruction in instructions {
+            code.extend_from_slice(instruction);
+        }
+        code
+    }
+
+    #[test]
+    fn no_shared_memory() {
+        assert_eq!(footprint(&[]), Footprint::default());
+
+        let code = module(&[
+            instruction(OP_TYPE_FLOAT, &[1, 32]),
+            instruction(OP_TYPE_POINTER, &[2, 7, 1]),
+            instruction(OP_VARIABLE, &[2, 3, 7]),
+        ]);
+        assert_eq!(footprint(&code).workgroup, 0);
+    }
+
+    #[test]
+    fn arrays_and_vectors() {
+        let code = module(&[
+            instruction(OP_TYPE_INT, &[1, 32, 0]),
+            instruction(OP_TYPE_FLOAT, &[2, 32]),
+            instruction(OP_TYPE_VECTOR, &[3, 2, 3]),
+            instruction(OP_CONSTANT, &[1, 4, 256]),
+            // shared vec3 data[256];
+            instruction(OP_TYPE_ARRAY, &[5, 3, 4]),
+            instruction(OP_TYPE_POINTER, &[6, STORAGE_CLASS_WORKGROUP, 5]),
+            instruction(OP_VARIABLE, &[6, 7, STORAGE_CLASS_WORKGROUP]),
+            // shared float total;
+            instruction(OP_TYPE_POINTER, &[8, STORAGE_CLASS_WORKGROUP, 2]),
+            instruction(OP_VARIABLE, &[8, 9, STORAGE_CLASS_WORKGROUP]),
+        ]);
+        assert_eq!(footprint(&code).workgroup, 256 * 16 + 4);
+    }
+
+    #[test]
+    fn structs_and_matrices() {
+        let code = module(&[
+            instruction(OP_TYPE_FLOAT, &[1, 32]),
+            instruction(OP_TYPE_FLOAT, &[2, 64]),
+            instruction(OP_TYPE_VECTOR, &[3, 1, 2]),
+            // mat3x2: three vec2 columns
+            instruction(OP_TYPE_MATRIX, &[4, 3, 3]),
+            // struct { float; double; mat3x2; }
+            instruction(OP_TYPE_STRUCT, &[5, 1, 2, 4]),
+            instruction(OP_TYPE_POINTER, &[6, STORAGE_CLASS_WORKGROUP, 5]),
+            instruction(OP_VARIABLE, &[6, 7, STORAGE_CLASS_WORKGROUP]),
+        ]);
+        assert_eq!(footprint(&code).workgroup, 40);
+    }
+
+    #[test]
+    fn push_constant_block() {
+        let code = module(&[
+            instruction(OP_MEMBER_DECORATE, &[6, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[6, 0, DECORATION_MATRIX_STRIDE, 16]),
+            instruction(OP_MEMBER_DECORATE, &[6, 1, DECORATION_OFFSET, 64]),
+            instruction(OP_MEMBER_DECORATE, &[6, 2, DECORATION_OFFSET, 76]),
+            instruction(OP_MEMBER_DECORATE, &[6, 3, DECORATION_OFFSET, 80]),
+            instruction(OP_DECORATE, &[5, DECORATION_ARRAY_STRIDE, 16]),
+            instruction(OP_TYPE_FLOAT, &[1, 32]),
+            instruction(OP_TYPE_VECTOR, &[2, 1, 3]),
+            instruction(OP_TYPE_MATRIX, &[3, 2, 4]),
+            instruction(OP_CONSTANT, &[1, 4, 4]),
+            // float[4] with a std140 stride
+            instruction(OP_TYPE_ARRAY, &[5, 1, 4]),
+            // struct { mat4x3; vec3; float; float[4]; }
+            instruction(OP_TYPE_STRUCT, &[6, 3, 2, 1, 5]),
+            instruction(OP_TYPE_POINTER, &[7, STORAGE_CLASS_PUSH_CONSTANT, 6]),
+            instruction(OP_VARIABLE, &[7, 8, STORAGE_CLASS_PUSH_CONSTANT]),
+        ]);
+        assert_eq!(
+            footprint(&code),
+            Footprint { workgroup: 0, push_constants: 144 },
+        );
+    }
+
+    #[test]
+    fn row_major_matrix() {
+        let code = module(&[
+            instruction(OP_MEMBER_DECORATE, &[4, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[4, 0, DECORATION_ROW_MAJOR]),
+            instruction(OP_MEMBER_DECORATE, &[4, 0, DECORATION_MATRIX_STRIDE, 16]),
+            instruction(OP_TYPE_FLOAT, &[1, 32]),
+            instruction(OP_TYPE_VECTOR, &[2, 1, 2]),
+            // mat4x2: four columns of two rows
+            instruction(OP_TYPE_MATRIX, &[3, 2, 4]),
+            instruction(OP_TYPE_STRUCT, &[4, 3]),
+            instruction(OP_TYPE_POINTER, &[5, STORAGE_CLASS_PUSH_CONSTANT, 4]),
+            instruction(OP_VARIABLE, &[5, 6, STORAGE_CLASS_PUSH_CONSTANT]),
+        ]);
+        assert_eq!(footprint(&code).push_constants, 32);
+    }
+
+    #[test]
+    fn truncated_module() {
+        let mut code = module(&[
+            instruction(OP_TYPE_FLOAT, &[1, 32]),
+            instruction(OP_TYPE_POINTER, &[2, STORAGE_CLASS_WORKGROUP, 1]),
+        ]);
+        // An instruction claiming more words than there are
+        code.push((4 << 16) | OP_VARIABLE);
+        code.push(2);
+        assert_eq!(footprint(&code).workgroup, 0);
+    }
+}
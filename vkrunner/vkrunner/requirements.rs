@@ -564,8 +564,8 @@ impl Requirements {
         Ok(())
     }
 
-    fn get_device_extensions(
-        &self,
+    /// Names of the extensions that the physical device supports.
+    pub(crate) fn get_device_extensions(
         vkinst: &vulkan_funcs::Instance,
         device: vk::VkPhysicalDevice
     ) -> Result<HashSet::<String>, Error> {
@@ -638,7 +638,7 @@ impl Requirements {
             return Ok(());
         }
 
-        let actual_extensions = self.get_device_extensions(vkinst, device)?;
+        let actual_extensions = Requirements::get_device_extensions(vkinst, device)?;
 
         for extension in self.extensions.iter() {
             if !actual_extensions.contains(extension) {
@@ -38,6 +38,7 @@ use crate::vk;
 use crate::config::Config;
 use std::cell::RefCell;
 use std::fmt;
+use std::mem;
 
 #[derive(Debug, Clone)]
 pub enum Shader {
@@ -60,6 +61,63 @@ pub(crate) struct Buffer {
     pub size: usize,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum TextureType {
+    Texture2D,
+    Array2D,
+    Cube,
+}
+
+/// The sampler state of a texture. The defaults are linear filtering
+/// without any blending between mipmap levels and repeating texture
+/// coordinates, like the defaults in GL.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct SamplerState {
+    pub mag_filter: vk::VkFilter,
+    pub min_filter: vk::VkFilter,
+    pub mipmap_mode: vk::VkSamplerMipmapMode,
+    pub lod_bias: f32,
+    pub address_modes: [vk::VkSamplerAddressMode; 3],
+    pub border_color: vk::VkBorderColor,
+    pub max_anisotropy: Option<f32>,
+    pub compare_op: Option<vk::VkCompareOp>,
+}
+
+impl Default for SamplerState {
+    fn default() -> SamplerState {
+        SamplerState {
+            mag_filter: vk::VK_FILTER_LINEAR,
+            min_filter: vk::VK_FILTER_LINEAR,
+            mipmap_mode: vk::VK_SAMPLER_MIPMAP_MODE_NEAREST,
+            lod_bias: 0.0,
+            address_modes: [vk::VK_SAMPLER_ADDRESS_MODE_REPEAT; 3],
+            border_color: vk::VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK,
+            max_anisotropy: None,
+            compare_op: None,
+        }
+    }
+}
+
+/// A sampled texture bound as a combined image sampler. The texels
+/// are R8G8B8A8_UNORM, or D32_SFLOAT for a depth texture, row by row
+/// starting from the top, with one layer after the other. The six layers of a cube texture are the
+/// faces in the order +X, -X, +Y, -Y, +Z, -Z. Only the first mipmap
+/// level is in the data. The others are generated from it when the
+/// texture is created.
+#[derive(Debug, Clone)]
+pub(crate) struct Texture {
+    pub desc_set: u32,
+    pub binding: u32,
+    pub texture_type: TextureType,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub mip_levels: u32,
+    pub depth: bool,
+    pub sampler: SamplerState,
+    pub data: Box<[u8]>,
+}
+
 #[derive(Debug)]
 pub struct Script {
     stages: [Box<[Shader]>; N_STAGES],
@@ -68,8 +126,13 @@ pub struct Script {
     requirements: Requirements,
     window_format: WindowFormat,
     vertex_data: Option<vbo::Vbo>,
+    instance_data: Option<vbo::Vbo>,
+    instance_divisor: u32,
     indices: Box<[u16]>,
     buffers: Box<[Buffer]>,
+    textures: Box<[Texture]>,
+    push_constant_size: Option<usize>,
+    push_constant_stages: Option<vk::VkShaderStageFlagBits>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -125,6 +188,22 @@ pub(crate) enum Operation {
         offset: usize,
         data: Box<[u8]>,
     },
+    // Writes the 64-bit device address of the buffer at
+    // `desc_set:binding` into the push constants.
+    SetPushAddress {
+        offset: usize,
+        desc_set: u32,
+        binding: u32,
+    },
+    // Writes the 64-bit device address of the buffer at
+    // `target_desc_set:target_binding` into another buffer.
+    SetBufferAddress {
+        desc_set: u32,
+        binding: u32,
+        offset: usize,
+        target_desc_set: u32,
+        target_binding: u32,
+    },
     Clear {
         color: [f32; 4],
         depth: f32,
@@ -132,6 +211,22 @@ pub(crate) enum Operation {
     },
 }
 
+impl Operation {
+    /// Returns the byte after the last one written to the push
+    /// constants by this operation, or `None` if it doesn’t write any.
+    pub(crate) fn push_constant_end(&self) -> Option<usize> {
+        match self {
+            Operation::SetPushCommand { offset, data } => {
+                Some(offset + data.len())
+            },
+            Operation::SetPushAddress { offset, .. } => {
+                Some(offset + mem::size_of::<vk::VkDeviceAddress>())
+            },
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct Command {
     pub line_num: usize,
@@ -180,6 +275,7 @@ enum Section {
     Require,
     Shader,
     VertexData,
+    InstanceData,
     Indices,
     Test,
 }
@@ -241,11 +337,16 @@ struct Loader<'a> {
     ubo_layout: slot::Layout,
     ssbo_layout: slot::Layout,
     vertex_data: Option<vbo::Vbo>,
+    instance_data: Option<vbo::Vbo>,
+    instance_divisor: u32,
     vbo_parser: Option<vbo::Parser>,
     indices: Vec<u16>,
     requirements: Requirements,
     window_format: WindowFormat,
     buffers: Vec<Buffer>,
+    textures: Vec<Texture>,
+    push_constant_size: Option<usize>,
+    push_constant_stages: Option<vk::VkShaderStageFlagBits>,
 }
 
 const DEFAULT_PUSH_LAYOUT: slot::Layout = slot::Layout {
@@ -272,6 +373,37 @@ static STAGE_NAMES: [(Stage, &'static str); N_STAGES] = [
     (Stage::Compute, "compute"),
 ];
 
+// Mappings of the sampler values of the texture command to Vulkan
+// enums, sorted alphabetically so we can do a binary search.
+static FILTER_NAMES: [(&'static str, vk::VkFilter); 2] = [
+    ("linear", vk::VK_FILTER_LINEAR),
+    ("nearest", vk::VK_FILTER_NEAREST),
+];
+
+static ADDRESS_MODE_NAMES: [(&'static str, vk::VkSamplerAddressMode); 4] = [
+    ("clamp_to_border", vk::VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER),
+    ("clamp_to_edge", vk::VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_EDGE),
+    ("mirrored_repeat", vk::VK_SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT),
+    ("repeat", vk::VK_SAMPLER_ADDRESS_MODE_REPEAT),
+];
+
+static BORDER_COLOR_NAMES: [(&'static str, vk::VkBorderColor); 3] = [
+    ("opaque_black", vk::VK_BORDER_COLOR_FLOAT_OPAQUE_BLACK),
+    ("opaque_white", vk::VK_BORDER_COLOR_FLOAT_OPAQUE_WHITE),
+    ("transparent_black", vk::VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK),
+];
+
+static COMPARE_OP_NAMES: [(&'static str, vk::VkCompareOp); 8] = [
+    ("always", vk::VK_COMPARE_OP_ALWAYS),
+    ("equal", vk::VK_COMPARE_OP_EQUAL),
+    ("greater", vk::VK_COMPARE_OP_GREATER),
+    ("greater_or_equal", vk::VK_COMPARE_OP_GREATER_OR_EQUAL),
+    ("less", vk::VK_COMPARE_OP_LESS),
+    ("less_or_equal", vk::VK_COMPARE_OP_LESS_OR_EQUAL),
+    ("never", vk::VK_COMPARE_OP_NEVER),
+    ("not_equal", vk::VK_COMPARE_OP_NOT_EQUAL),
+];
+
 // Mapping of topology name to Vulkan topology enum, sorted
 // alphabetically so we can do a binary search.
 static TOPOLOGY_NAMES: [(&'static str, vk::VkPrimitiveTopology); 22] = [
@@ -465,11 +597,16 @@ impl<'a> Loader<'a> {
             ubo_layout: DEFAULT_UBO_LAYOUT,
             ssbo_layout: DEFAULT_SSBO_LAYOUT,
             vertex_data: None,
+            instance_data: None,
+            instance_divisor: 1,
             vbo_parser: None,
             indices: Vec::new(),
             requirements: Requirements::new(),
             window_format: Default::default(),
             buffers: Vec::new(),
+            textures: Vec::new(),
+            push_constant_size: None,
+            push_constant_stages: None,
         })
     }
 
@@ -478,17 +615,50 @@ impl<'a> Loader<'a> {
             .push(self.current_source.take().unwrap());
     }
 
-    fn end_vertex_data(&mut self) -> Result<(), LoadError> {
-        match self.vbo_parser.take().unwrap().into_vbo() {
-            Ok(vbo) => {
-                self.vertex_data.replace(vbo);
-                Ok(())
-            },
-            Err(e) => Err(LoadError::Vbo {
+    fn take_vbo(&mut self) -> Result<vbo::Vbo, LoadError> {
+        let vbo = match self.vbo_parser.take().unwrap().into_vbo() {
+            Ok(vbo) => vbo,
+            Err(e) => return Err(LoadError::Vbo {
                 line_num: self.stream.line_num(),
                 detail: e,
             }),
+        };
+
+        // The vertex data and the instance data are bound to the same
+        // pipeline so they can’t share a location
+        let other = match self.current_section {
+            Section::InstanceData => &self.vertex_data,
+            _ => &self.instance_data,
+        };
+
+        if let Some(other) = other {
+            for attrib in vbo.attribs().iter() {
+                if other.attribs().iter().any(|other_attrib| {
+                    other_attrib.location() == attrib.location()
+                }) {
+                    return Err(error_at_line!(
+                        self,
+                        "Vertex attribute location {} used in both the \
+                         vertex data and the instance data",
+                        attrib.location()
+                    ));
+                }
+            }
         }
+
+        Ok(vbo)
+    }
+
+    fn end_vertex_data(&mut self) -> Result<(), LoadError> {
+        let vbo = self.take_vbo()?;
+        self.vertex_data.replace(vbo);
+        Ok(())
+    }
+
+    fn end_instance_data(&mut self) -> Result<(), LoadError> {
+        let vbo = self.take_vbo()?;
+        self.instance_data.replace(vbo);
+        Ok(())
     }
 
     fn end_section(&mut self) -> Result<(), LoadError> {
@@ -498,6 +668,7 @@ impl<'a> Loader<'a> {
             Section::Require => (),
             Section::Shader => self.end_shader(),
             Section::VertexData => self.end_vertex_data()?,
+            Section::InstanceData => self.end_instance_data()?,
             Section::Indices => (),
             Section::Test => (),
         }
@@ -589,6 +760,52 @@ impl<'a> Loader<'a> {
         Ok(())
     }
 
+    fn process_instance_data_header(
+        &mut self,
+        tail: &str,
+    ) -> Result<(), LoadError> {
+        let divisor = if let Some(tail) = strip_word_prefix(tail, "divisor") {
+            let (divisor, tail) = self.parse_u32(tail)?;
+
+            if !tail.trim().is_empty() {
+                return Err(error_at_line!(
+                    self,
+                    "Invalid instance data section header"
+                ));
+            }
+
+            divisor
+        } else if tail.trim().is_empty() {
+            1
+        } else {
+            return Err(error_at_line!(
+                self,
+                "Invalid instance data section header"
+            ));
+        };
+
+        if self.instance_data.is_some() {
+            return Err(error_at_line!(
+                self,
+                "Duplicate instance data section"
+            ));
+        }
+
+        // Any divisor other than 1 needs VK_EXT_vertex_attribute_divisor
+        if divisor != 1 {
+            self.requirements.add("vertexAttributeInstanceRateDivisor");
+        }
+        if divisor == 0 {
+            self.requirements.add("vertexAttributeInstanceRateZeroDivisor");
+        }
+
+        self.instance_divisor = divisor;
+        self.set_current_section(Section::InstanceData);
+        self.vbo_parser = Some(vbo::Parser::new());
+
+        Ok(())
+    }
+
     fn process_section_name(
         &mut self,
         section_name: &str,
@@ -645,6 +862,10 @@ impl<'a> Loader<'a> {
             return Ok(());
         }
 
+        if let Some(tail) = strip_words_prefix(section_name, "instance data") {
+            return self.process_instance_data_header(tail);
+        }
+
         Err(error_at_line!(self, "Unknown section “{}”", section_name))
     }
 
@@ -1154,6 +1375,8 @@ impl<'a> Loader<'a> {
 
             pos
         } else {
+            self.check_binding_unused_by_texture(desc_set, binding)?;
+
             self.buffers.push(Buffer {
                 desc_set,
                 binding,
@@ -1167,6 +1390,25 @@ impl<'a> Loader<'a> {
         Ok(&mut self.buffers[position])
     }
 
+    fn check_binding_unused_by_texture(
+        &self,
+        desc_set: u32,
+        binding: u32,
+    ) -> Result<(), LoadError> {
+        if self.textures.iter().any(|texture| {
+            texture.desc_set == desc_set && texture.binding == binding
+        }) {
+            Err(error_at_line!(
+                self,
+                "Binding point {}:{} used for both a buffer and a texture",
+                desc_set,
+                binding
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     fn layout_for_buffer_type(&self, buffer_type: BufferType) -> slot::Layout {
         match buffer_type {
             BufferType::Ubo => self.ubo_layout,
@@ -1224,6 +1466,55 @@ impl<'a> Loader<'a> {
         Ok(())
     }
 
+    // Parses the “<offset> <set:binding>” tail shared by the address
+    // commands. The target binding must be the last thing on the line.
+    fn parse_address_target(
+        &self,
+        line: &str,
+    ) -> Result<(usize, u32, u32), LoadError> {
+        let (offset, line) = self.parse_u32(line)?;
+        let (desc_set, binding, line) =
+            self.parse_desc_set_and_binding(line.trim_start())?;
+
+        if !line.trim_end().is_empty() {
+            return Err(error_at_line!(self, "Extra data at end of line"));
+        }
+
+        Ok((offset as usize, desc_set, binding))
+    }
+
+    fn process_set_buffer_address(
+        &mut self,
+        desc_set: u32,
+        binding: u32,
+        buffer_type: BufferType,
+        line: &str,
+    ) -> Result<(), LoadError> {
+        let (offset, target_desc_set, target_binding) =
+            self.parse_address_target(line)?;
+
+        let buffer = self.get_buffer(desc_set, binding, buffer_type)?;
+
+        let min_buffer_size = offset + mem::size_of::<vk::VkDeviceAddress>();
+
+        if buffer.size < min_buffer_size {
+            buffer.size = min_buffer_size;
+        }
+
+        self.commands.push(Command {
+            line_num: self.stream.line_num(),
+            op: Operation::SetBufferAddress {
+                desc_set,
+                binding,
+                offset,
+                target_desc_set,
+                target_binding,
+            },
+        });
+
+        Ok(())
+    }
+
     fn process_set_buffer_size(
         &mut self,
         desc_set: u32,
@@ -1455,6 +1746,70 @@ impl<'a> Loader<'a> {
         Ok(MatchResult::Matched)
     }
 
+    fn process_push_address(
+        &mut self,
+        line: &str,
+    ) -> Result<MatchResult, LoadError> {
+        let line = match strip_words_prefix(line, "push address") {
+            Some(l) => l,
+            None => return Ok(MatchResult::NotMatched),
+        };
+
+        let (offset, desc_set, binding) = self.parse_address_target(line)?;
+
+        self.commands.push(Command {
+            line_num: self.stream.line_num(),
+            op: Operation::SetPushAddress {
+                offset,
+                desc_set,
+                binding,
+            },
+        });
+
+        Ok(MatchResult::Matched)
+    }
+
+    // Handles “push size” and “push stages” which override the push
+    // constant range that is otherwise derived from the push commands
+    // and the stages present in the script.
+    fn process_push_range(
+        &mut self,
+        line: &str,
+    ) -> Result<MatchResult, LoadError> {
+        if let Some(line) = strip_words_prefix(line, "push size") {
+            let (size, line) = self.parse_u32(line)?;
+
+            if !line.trim_end().is_empty() {
+                return Err(error_at_line!(self, "Extra data at end of line"));
+            }
+
+            self.push_constant_size = Some(size as usize);
+
+            Ok(MatchResult::Matched)
+        } else if let Some(line) = strip_words_prefix(line, "push stages") {
+            let mut stages = 0;
+
+            for name in line.split(',') {
+                let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                match STAGE_NAMES.iter().find(|&&(_, n)| n == name) {
+                    Some(&(stage, _)) => stages |= stage.flag(),
+                    None => return Err(error_at_line!(
+                        self,
+                        "Unknown shader stage: {}",
+                        name
+                    )),
+                }
+            }
+
+            self.push_constant_stages = Some(stages);
+
+            Ok(MatchResult::Matched)
+        } else {
+            Ok(MatchResult::NotMatched)
+        }
+    }
+
     fn process_uniform_ubo(
         &mut self,
         line: &str,
@@ -1683,6 +2038,14 @@ impl<'a> Loader<'a> {
                 line
             )?;
             Ok(MatchResult::Matched)
+        } else if let Some(line) = strip_word_prefix(line, "address") {
+            self.process_set_buffer_address(
+                desc_set,
+                binding,
+                buffer_type,
+                line
+            )?;
+            Ok(MatchResult::Matched)
         } else {
             let (size, tail) = self.parse_u32(line)?;
 
@@ -1700,6 +2063,227 @@ impl<'a> Loader<'a> {
         }
     }
 
+    // Parses one of the names in a sorted table of sampler values.
+    // `what` names the value in the error messages.
+    fn parse_sampler_value<'b, T: Copy>(
+        &self,
+        line: &'b str,
+        names: &[(&'static str, T)],
+        what: &str,
+    ) -> Result<(T, &'b str), LoadError> {
+        let (word, tail) = match next_word(line) {
+            Some(next) => next,
+            None => return Err(error_at_line!(self, "Expected texture {}", what)),
+        };
+
+        match names.binary_search_by(|&(probe, _)| probe.cmp(word)) {
+            Ok(pos) => Ok((names[pos].1, tail)),
+            Err(_pos) => Err(error_at_line!(
+                self,
+                "Unknown texture {}: {}",
+                what,
+                word
+            )),
+        }
+    }
+
+    fn process_texture(
+        &mut self,
+        line: &str,
+    ) -> Result<MatchResult, LoadError> {
+        let line = match strip_word_prefix(line, "texture") {
+            Some(l) => l,
+            None => return Ok(MatchResult::NotMatched),
+        };
+
+        let (desc_set, binding, mut line) =
+            self.parse_desc_set_and_binding(line)?;
+
+        let mut texture_type = TextureType::Texture2D;
+        let mut layers = 1;
+        let mut mipmaps = false;
+        let mut depth = false;
+        let mut sampler = SamplerState::default();
+
+        loop {
+            if let Some(tail) = strip_word_prefix(line, "array") {
+                let (n_layers, tail) = self.parse_u32(tail)?;
+                texture_type = TextureType::Array2D;
+                layers = n_layers;
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "cube") {
+                texture_type = TextureType::Cube;
+                layers = 6;
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "mipmaps") {
+                mipmaps = true;
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "depth") {
+                depth = true;
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "filter") {
+                let (mag_filter, tail) =
+                    self.parse_sampler_value(tail, &FILTER_NAMES, "filter")?;
+                let (min_filter, tail) =
+                    self.parse_sampler_value(tail, &FILTER_NAMES, "filter")?;
+                let (mipmap_filter, tail) =
+                    self.parse_sampler_value(tail, &FILTER_NAMES, "filter")?;
+                sampler.mag_filter = mag_filter;
+                sampler.min_filter = min_filter;
+                sampler.mipmap_mode = if mipmap_filter == vk::VK_FILTER_LINEAR {
+                    vk::VK_SAMPLER_MIPMAP_MODE_LINEAR
+                } else {
+                    vk::VK_SAMPLER_MIPMAP_MODE_NEAREST
+                };
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "lod_bias") {
+                let (lod_bias, tail) = self.parse_f32(tail)?;
+                sampler.lod_bias = lod_bias;
+                line = tail;
+            } else if let Some(mut tail) = strip_word_prefix(line, "address") {
+                for address_mode in sampler.address_modes.iter_mut() {
+                    let (mode, next_tail) = self.parse_sampler_value(
+                        tail,
+                        &ADDRESS_MODE_NAMES,
+                        "address mode",
+                    )?;
+                    *address_mode = mode;
+                    tail = next_tail;
+                }
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "border") {
+                let (border_color, tail) = self.parse_sampler_value(
+                    tail,
+                    &BORDER_COLOR_NAMES,
+                    "border color",
+                )?;
+                sampler.border_color = border_color;
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "anisotropy") {
+                let (max_anisotropy, tail) = self.parse_f32(tail)?;
+                if max_anisotropy < 1.0 {
+                    return Err(error_at_line!(
+                        self,
+                        "Texture anisotropy must be at least 1"
+                    ));
+                }
+                sampler.max_anisotropy = Some(max_anisotropy);
+                line = tail;
+            } else if let Some(tail) = strip_word_prefix(line, "compare") {
+                let (compare_op, tail) = self.parse_sampler_value(
+                    tail,
+                    &COMPARE_OP_NAMES,
+                    "compare op",
+                )?;
+                sampler.compare_op = Some(compare_op);
+                line = tail;
+            } else {
+                break;
+            }
+        }
+
+        let (width, line) = self.parse_u32(line)?;
+        let (height, line) = self.parse_u32(line)?;
+        let path = line.trim();
+
+        if width == 0 || height == 0 || layers == 0 {
+            return Err(error_at_line!(self, "Texture size must not be zero"));
+        }
+        if texture_type == TextureType::Cube && width != height {
+            return Err(error_at_line!(
+                self,
+                "Cube texture faces must be square"
+            ));
+        }
+        // Comparisons are only supported for depth formats
+        if sampler.compare_op.is_some() && !depth {
+            return Err(error_at_line!(
+                self,
+                "Texture compare needs a depth texture"
+            ));
+        }
+        if path.is_empty() {
+            return Err(error_at_line!(self, "Expected texture file name"));
+        }
+
+        if self.textures.iter().any(|texture| {
+            texture.desc_set == desc_set && texture.binding == binding
+        }) {
+            return Err(error_at_line!(
+                self,
+                "Texture binding point {}:{} used twice",
+                desc_set,
+                binding
+            ));
+        }
+
+        if self.buffers.iter().any(|buffer| {
+            buffer.desc_set == desc_set && buffer.binding == binding
+        }) {
+            return Err(error_at_line!(
+                self,
+                "Binding point {}:{} used for both a buffer and a texture",
+                desc_set,
+                binding
+            ));
+        }
+
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) => return Err(error_at_line!(
+                self,
+                "Failed to read texture file {}: {}",
+                path,
+                e
+            )),
+        };
+
+        let expected_size =
+            width as usize * height as usize * layers as usize * 4;
+
+        if data.len() != expected_size {
+            let size = if texture_type == TextureType::Texture2D {
+                format!("{}x{}", width, height)
+            } else {
+                format!("{}x{}x{}", width, height, layers)
+            };
+            return Err(error_at_line!(
+                self,
+                "Texture file {} is {} bytes but a {} RGBA8 texture needs {}",
+                path,
+                data.len(),
+                size,
+                expected_size
+            ));
+        }
+
+        // A full chain goes down to 1x1
+        let mip_levels = if mipmaps {
+            u32::BITS - width.max(height).leading_zeros()
+        } else {
+            1
+        };
+
+        self.textures.push(Texture {
+            desc_set,
+            binding,
+            texture_type,
+            width,
+            height,
+            layers,
+            mip_levels,
+            depth,
+            sampler,
+            data: data.into_boxed_slice(),
+        });
+
+        if sampler.max_anisotropy.is_some() {
+            self.requirements.add("samplerAnisotropy");
+        }
+
+        Ok(MatchResult::Matched)
+    }
+
     fn process_probe_ssbo(
         &mut self,
         line: &str,
@@ -2002,12 +2586,15 @@ impl<'a> Loader<'a> {
         handle_match_result!(self.process_probe(line));
         handle_match_result!(self.process_uniform_ubo(line));
         handle_match_result!(self.process_layout(line));
+        handle_match_result!(self.process_push_range(line));
+        handle_match_result!(self.process_push_address(line));
         handle_match_result!(self.process_push(line));
         handle_match_result!(self.process_draw_rect(line));
         handle_match_result!(self.process_draw_arrays(line));
         handle_match_result!(self.process_entrypoint(line));
         handle_match_result!(self.process_compute(line));
         handle_match_result!(self.process_buffer_command(line));
+        handle_match_result!(self.process_texture(line));
         handle_match_result!(self.process_clear(line));
         handle_match_result!(self.process_pipeline_property(line));
         handle_match_result!(self.process_clear_values(line));
@@ -2027,7 +2614,9 @@ impl<'a> Loader<'a> {
             Section::Comment => Ok(()),
             Section::Require => self.process_require_line(line),
             Section::Shader => self.process_shader_line(line),
-            Section::VertexData => self.process_vertex_data_line(line),
+            Section::VertexData | Section::InstanceData => {
+                self.process_vertex_data_line(line)
+            },
             Section::Indices => self.process_indices_line(line),
             Section::Test => self.process_test_line(line),
         }
@@ -2048,11 +2637,34 @@ impl<'a> Loader<'a> {
 
         self.end_section()?;
 
+        if let Some(size) = self.push_constant_size {
+            for command in self.commands.iter() {
+                if let Some(end) = command.op.push_constant_end() {
+                    if end > size {
+                        return Err(LoadError::Invalid {
+                            line_num: command.line_num,
+                            message: format!(
+                                "Push constant data ends at byte {} which is \
+                                 beyond the push size of {}",
+                                end,
+                                size,
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
         self.buffers.sort_by(|a, b| {
             a.desc_set
                 .cmp(&b.desc_set)
                 .then_with(|| a.binding.cmp(&b.binding))
         });
+        self.textures.sort_by(|a, b| {
+            a.desc_set
+                .cmp(&b.desc_set)
+                .then_with(|| a.binding.cmp(&b.binding))
+        });
 
         Ok(Script {
             stages: self.stages.map(|stage| stage.into_boxed_slice()),
@@ -2061,8 +2673,13 @@ impl<'a> Loader<'a> {
             requirements: self.requirements,
             window_format: self.window_format,
             vertex_data: self.vertex_data,
+            instance_data: self.instance_data,
+            instance_divisor: self.instance_divisor,
             indices: self.indices.into_boxed_slice(),
             buffers: self.buffers.into_boxed_slice(),
+            textures: self.textures.into_boxed_slice(),
+            push_constant_size: self.push_constant_size,
+            push_constant_stages: self.push_constant_stages,
         })
     }
 }
@@ -2115,6 +2732,16 @@ impl Script {
         self.vertex_data.as_ref()
     }
 
+    /// Per-instance vertex attributes from the `[instance data]`
+    /// section. Each row is used for `instance_divisor` instances.
+    pub(crate) fn instance_data(&self) -> Option<&vbo::Vbo> {
+        self.instance_data.as_ref()
+    }
+
+    pub(crate) fn instance_divisor(&self) -> u32 {
+        self.instance_divisor
+    }
+
     pub(crate) fn indices(&self) -> &[u16] {
         &*self.indices
     }
@@ -2123,6 +2750,31 @@ impl Script {
         &*self.buffers
     }
 
+    pub(crate) fn textures(&self) -> &[Texture] {
+        &*self.textures
+    }
+
+    pub(crate) fn push_constant_size(&self) -> Option<usize> {
+        self.push_constant_size
+    }
+
+    pub(crate) fn push_constant_stages(
+        &self
+    ) -> Option<vk::VkShaderStageFlagBits> {
+        self.push_constant_stages
+    }
+
+    /// Returns true if any command writes a buffer device address, in
+    /// which case the buffers need to be created so that their
+    /// address can be queried.
+    pub(crate) fn uses_buffer_addresses(&self) -> bool {
+        self.commands.iter().any(|command| matches!(
+            command.op,
+            Operation::SetPushAddress { .. }
+                | Operation::SetBufferAddress { .. }
+        ))
+    }
+
     pub fn replace_shaders_stage_binary(
         &mut self,
         stage: Stage,
@@ -2358,6 +3010,92 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_push_range() {
+        let script = script_from_string(
+            "[test]\n\
+             push size 16\n\
+             push stages vertex, tessellation  control,fragment\n\
+             push uint 12 1\n".to_string()
+        );
+        assert_eq!(script.push_constant_size(), Some(16));
+        assert_eq!(
+            script.push_constant_stages(),
+            Some(
+                vk::VK_SHADER_STAGE_VERTEX_BIT
+                    | vk::VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT
+                    | vk::VK_SHADER_STAGE_FRAGMENT_BIT
+            ),
+        );
+
+        let script = script_from_string("[test]\npush uint 12 1\n".to_string());
+        assert_eq!(script.push_constant_size(), None);
+        assert_eq!(script.push_constant_stages(), None);
+
+        check_error(
+            "[test]\n\
+             push uint 12 1 2\n\
+             push size 16\n",
+            "line 2: Push constant data ends at byte 20 which is beyond the \
+             push size of 16",
+        );
+        check_test_command_error(
+            "push stages vertex, potato",
+            "Unknown shader stage: potato",
+        );
+        check_test_command_error(
+            "push size 16 32",
+            "Extra data at end of line",
+        );
+    }
+
+    #[test]
+    fn test_buffer_address() {
+        let script = check_test_command(
+            "push  address 8 1:2",
+            Operation::SetPushAddress {
+                offset: 8,
+                desc_set: 1,
+                binding: 2,
+            },
+        );
+        assert!(script.uses_buffer_addresses());
+
+        let script = check_test_command(
+            "ssbo 3 address 16 1:2",
+            Operation::SetBufferAddress {
+                desc_set: 0,
+                binding: 3,
+                offset: 16,
+                target_desc_set: 1,
+                target_binding: 2,
+            },
+        );
+        assert_eq!(script.buffers().len(), 1);
+        assert_eq!(script.buffers()[0].buffer_type, BufferType::Ssbo);
+        assert_eq!(script.buffers()[0].size, 24);
+        assert_eq!(script.push_constant_size(), None);
+
+        let script = script_from_string("[test]\npush uint 0 1\n".to_string());
+        assert!(!script.uses_buffer_addresses());
+
+        check_error(
+            "[test]\n\
+             push size 8\n\
+             push address 4 0\n",
+            "line 3: Push constant data ends at byte 12 which is beyond the \
+             push size of 8",
+        );
+        check_test_command_error(
+            "push address 0 1 2",
+            "Extra data at end of line",
+        );
+        check_test_command_error(
+            "ubo 1 address 0 1x",
+            "Invalid buffer binding",
+        );
+    }
+
     #[test]
     fn test_uniform_ubo() {
         let script = check_test_command(
@@ -3449,6 +4187,213 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_texture() {
+        let mut texels = crate::temp_file::TempFile::new().unwrap();
+        io::Write::write_all(
+            texels.file().unwrap(),
+            &[255, 0, 0, 255, 0, 0, 255, 128],
+        ).unwrap();
+        texels.close();
+        let path = texels.filename().to_str().unwrap();
+
+        let script = script_from_string(format!(
+            "[test]\n\
+             texture 1:2 2 1 {}\n\
+             texture 0 1 2 {}",
+            path,
+            path,
+        ));
+
+        let textures = script.textures();
+        assert_eq!(textures.len(), 2);
+        assert_eq!((textures[0].desc_set, textures[0].binding), (0, 0));
+        assert_eq!((textures[0].width, textures[0].height), (1, 2));
+        assert_eq!((textures[1].desc_set, textures[1].binding), (1, 2));
+        assert_eq!((textures[1].width, textures[1].height), (2, 1));
+        assert_eq!(&*textures[1].data, &[255, 0, 0, 255, 0, 0, 255, 128]);
+        assert_eq!(textures[1].texture_type, TextureType::Texture2D);
+        assert_eq!(textures[1].layers, 1);
+        assert_eq!(script.commands().len(), 0);
+
+        let mut faces = crate::temp_file::TempFile::new().unwrap();
+        io::Write::write_all(faces.file().unwrap(), &[7; 24]).unwrap();
+        faces.close();
+        let faces_path = faces.filename().to_str().unwrap();
+
+        let script = script_from_string(format!(
+            "[test]\n\
+             texture 0 array 2 1 1 {}\n\
+             texture 1 cube 1 1 {}",
+            path,
+            faces_path,
+        ));
+        let textures = script.textures();
+        assert_eq!(textures[0].texture_type, TextureType::Array2D);
+        assert_eq!((textures[0].width, textures[0].height), (1, 1));
+        assert_eq!(textures[0].layers, 2);
+        assert_eq!(textures[1].texture_type, TextureType::Cube);
+        assert_eq!(textures[1].layers, 6);
+        assert_eq!(textures[1].data.len(), 24);
+        assert_eq!(textures[1].mip_levels, 1);
+        assert_eq!(textures[1].sampler, SamplerState::default());
+
+        let script = script_from_string(format!(
+            "[test]\n\
+             texture 0 mipmaps filter nearest linear linear lod_bias -1.5 \
+             2 1 {}\n\
+             texture 1 cube mipmaps 1 1 {}",
+            path,
+            faces_path,
+        ));
+        let textures = script.textures();
+        assert_eq!(textures[0].texture_type, TextureType::Texture2D);
+        assert_eq!(textures[0].mip_levels, 2);
+        assert_eq!(
+            textures[0].sampler,
+            SamplerState {
+                mag_filter: vk::VK_FILTER_NEAREST,
+                min_filter: vk::VK_FILTER_LINEAR,
+                mipmap_mode: vk::VK_SAMPLER_MIPMAP_MODE_LINEAR,
+                lod_bias: -1.5,
+                ..Default::default()
+            },
+        );
+        assert_eq!(textures[1].texture_type, TextureType::Cube);
+        assert_eq!(textures[1].mip_levels, 1);
+
+        check_test_command_error(
+            &format!("texture 0 filter linear cubic linear 2 1 {}", path),
+            "Unknown texture filter: cubic",
+        );
+        check_test_command_error(
+            "texture 0 filter linear",
+            "Expected texture filter",
+        );
+
+        let script = script_from_string(format!(
+            "[test]\n\
+             texture 0 address clamp_to_edge mirrored_repeat \
+             clamp_to_border border opaque_white anisotropy 16 2 1 {}\n\
+             texture 1 depth compare less_or_equal 2 1 {}",
+            path,
+            path,
+        ));
+        let textures = script.textures();
+        assert!(!textures[0].depth);
+        assert_eq!(
+            textures[0].sampler.address_modes,
+            [
+                vk::VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_EDGE,
+                vk::VK_SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT,
+                vk::VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER,
+            ],
+        );
+        assert_eq!(
+            textures[0].sampler.border_color,
+            vk::VK_BORDER_COLOR_FLOAT_OPAQUE_WHITE,
+        );
+        assert_eq!(textures[0].sampler.max_anisotropy, Some(16.0));
+        assert_eq!(textures[0].sampler.compare_op, None);
+        assert!(textures[1].depth);
+        assert_eq!(
+            textures[1].sampler.compare_op,
+            Some(vk::VK_COMPARE_OP_LESS_OR_EQUAL),
+        );
+        assert_eq!(textures[1].sampler.max_anisotropy, None);
+        assert_eq!(
+            script.requirements().c_base_features().samplerAnisotropy,
+            vk::VK_TRUE,
+        );
+
+        check_test_command_error(
+            &format!("texture 0 address repeat repeat wrap 2 1 {}", path),
+            "Unknown texture address mode: wrap",
+        );
+        check_test_command_error(
+            &format!("texture 0 border red 2 1 {}", path),
+            "Unknown texture border color: red",
+        );
+        check_test_command_error(
+            &format!("texture 0 anisotropy 0.5 2 1 {}", path),
+            "Texture anisotropy must be at least 1",
+        );
+        check_test_command_error(
+            &format!("texture 0 compare less 2 1 {}", path),
+            "Texture compare needs a depth texture",
+        );
+
+        // The name tables are searched with a binary search
+        assert!(FILTER_NAMES.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(ADDRESS_MODE_NAMES.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(BORDER_COLOR_NAMES.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(COMPARE_OP_NAMES.windows(2).all(|w| w[0].0 < w[1].0));
+
+        check_test_command_error(
+            &format!("texture 0 array 3 1 1 {}", path),
+            &format!(
+                "Texture file {} is 8 bytes but a 1x1x3 RGBA8 texture needs 12",
+                path,
+            ),
+        );
+        check_test_command_error(
+            &format!("texture 0 array 0 1 1 {}", path),
+            "Texture size must not be zero",
+        );
+        check_test_command_error(
+            &format!("texture 0 cube 2 1 {}", faces_path),
+            "Cube texture faces must be square",
+        );
+
+        check_test_command_error(
+            &format!("texture 0 2 2 {}", path),
+            &format!(
+                "Texture file {} is 8 bytes but a 2x2 RGBA8 texture needs 16",
+                path,
+            ),
+        );
+        check_test_command_error(
+            &format!("texture 0 0 2 {}", path),
+            "Texture size must not be zero",
+        );
+        check_test_command_error("texture 0 2 1", "Expected texture file name");
+        check_test_command_error(
+            "texture 0 2 1 /nonexistent/texels",
+            &format!(
+                "Failed to read texture file /nonexistent/texels: {}",
+                fs::read("/nonexistent/texels").unwrap_err(),
+            ),
+        );
+        check_error(
+            &format!(
+                "[test]\n\
+                 texture 3 2 1 {}\n\
+                 texture 3 2 1 {}",
+                path,
+                path,
+            ),
+            "line 3: Texture binding point 0:3 used twice",
+        );
+        check_error(
+            &format!(
+                "[test]\n\
+                 ssbo 3 16\n\
+                 texture 3 2 1 {}",
+                path,
+            ),
+            "line 3: Binding point 0:3 used for both a buffer and a texture",
+        );
+        check_error(
+            &format!(
+                "[test]\n\
+                 texture 3 2 1 {}\n\
+                 ubo 3 subdata float 0 1.0",
+                path,
+            ),
+            "line 3: Binding point 0:3 used for both a buffer and a texture",
+        );
+    }
+
     #[test]
     fn test_vertex_data() {
         let script = script_from_string(
@@ -3487,6 +4432,69 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_instance_data() {
+        let script = script_from_string(
+            "[vertex data]\n\
+             0/R8_UNORM\n\
+             1\n\
+             [instance data]\n\
+             1/R8G8_UNORM\n\
+             2 3\n\
+             4 5".to_string()
+        );
+
+        assert_eq!(script.vertex_data().unwrap().raw_data(), &[1]);
+        let instance_data = script.instance_data().unwrap();
+        assert_eq!(instance_data.raw_data(), &[2, 3, 4, 5]);
+        assert_eq!(instance_data.attribs()[0].location(), 1);
+        assert_eq!(script.instance_divisor(), 1);
+        assert!(script.requirements().c_extensions().is_empty());
+
+        let script = script_from_string(
+            "[instance data divisor 3]\n\
+             0/R8_UNORM\n\
+             7".to_string()
+        );
+        assert_eq!(script.instance_data().unwrap().raw_data(), &[7]);
+        assert_eq!(script.instance_divisor(), 3);
+        let extensions = script.requirements().c_extensions();
+        assert_eq!(extensions.len(), 1);
+        assert_eq!(
+            unsafe { CStr::from_ptr(extensions[0] as *const std::ffi::c_char) },
+            c"VK_EXT_vertex_attribute_divisor",
+        );
+
+        check_error(
+            "[instance data divisor]\n\
+             0/R8_UNORM",
+            &format!("line 1: {}", "".parse::<u32>().unwrap_err()),
+        );
+        check_error(
+            "[instance data per vertex]\n\
+             0/R8_UNORM",
+            "line 1: Invalid instance data section header",
+        );
+        check_error(
+            "[instance data]\n\
+             0/R8_UNORM\n\
+             1\n\
+             [instance data]",
+            "line 4: Duplicate instance data section",
+        );
+        check_error(
+            "[instance data]\n\
+             0/R8_UNORM\n\
+             1\n\
+             [vertex data]\n\
+             0/R8G8_UNORM\n\
+             1 2\n\
+             [test]",
+            "line 7: Vertex attribute location 0 used in both the vertex \
+             data and the instance data",
+        );
+    }
+
     #[test]
     fn test_parse_version() {
         let source = Source::from_string(String::new());
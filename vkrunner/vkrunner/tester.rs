@@ -25,12 +25,14 @@
 
 use crate::window::Window;
 use crate::context::Context;
-use crate::pipeline_set::{PipelineSet, RectangleVertex};
+use crate::pipeline_set::{PipelineSet, RectangleVertex, INSTANCE_DATA_BINDING};
 use crate::pipeline_key;
 use crate::script::{Script, BufferType, Operation};
 use crate::inspect::Inspector;
 use crate::vk;
 use crate::buffer::{self, MappedMemory, DeviceMemory, Buffer};
+use crate::texture::{self, Texture};
+use crate::vbo::Vbo;
 use crate::flush_memory::{self, flush_memory};
 use crate::tolerance::Tolerance;
 use crate::slot;
@@ -40,6 +42,7 @@ use std::ptr;
 use std::mem;
 use std::rc::Rc;
 use std::ffi::c_int;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct CommandError {
@@ -58,10 +61,12 @@ pub enum Error {
     ProbeFailed(ProbeFailedError),
     InvalidateMappedMemoryRangesFailed,
     BufferError(buffer::Error),
+    TextureError(texture::Error),
     FlushMemoryError(flush_memory::Error),
     CommandErrors(Vec<CommandError>),
     InvalidBufferBinding { desc_set: u32, binding: u32 },
     InvalidBufferOffset,
+    BufferDeviceAddressUnavailable,
     SsboProbeFailed {
         slot_type: slot::Type,
         layout: slot::Layout,
@@ -79,6 +84,36 @@ pub struct ProbeFailedError {
     n_components: usize,
 }
 
+/// How the tester submits its command buffers and how long each
+/// submission took.
+#[derive(Debug, Default)]
+pub(crate) struct Submissions {
+    /// Submit each draw and dispatch on its own instead of only when
+    /// the results are needed, so that a GPU watchdog times each one
+    /// separately.
+    pub separate: bool,
+    /// Time from each submission until its fence signalled, in order.
+    pub times: Vec<Duration>,
+}
+
+impl Submissions {
+    /// A line summarising the submission times, e.g.
+    /// `Submissions: 3, longest 12.500 ms, total 20.000 ms`.
+    pub fn report(&self) -> String {
+        let Some(longest) = self.times.iter().max() else {
+            return "Submissions: 0".to_string();
+        };
+        let total = self.times.iter().sum::<Duration>();
+
+        format!(
+            "Submissions: {}, longest {:.3} ms, total {:.3} ms",
+            self.times.len(),
+            longest.as_secs_f64() * 1000.0,
+            total.as_secs_f64() * 1000.0,
+        )
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum State {
     /// Any rendering or computing has finished and we can read the
@@ -137,6 +172,7 @@ impl fmt::Display for Error {
                 Ok(())
             },
             Error::BufferError(e) => e.fmt(f),
+            Error::TextureError(e) => e.fmt(f),
             Error::FlushMemoryError(e) => e.fmt(f),
             Error::CommandErrors(errors) => {
                 for (num, e) in errors.iter().enumerate() {
@@ -154,6 +190,13 @@ impl fmt::Display for Error {
             Error::InvalidBufferOffset => {
                 write!(f, "Invalid buffer offset")
             },
+            Error::BufferDeviceAddressUnavailable => {
+                write!(
+                    f,
+                    "vkGetBufferDeviceAddress is not available. Buffer \
+                     addresses need a Vulkan 1.2 device."
+                )
+            },
         }
     }
 }
@@ -208,6 +251,12 @@ impl From<buffer::Error> for Error {
     }
 }
 
+impl From<texture::Error> for Error {
+    fn from(e: texture::Error) -> Error {
+        Error::TextureError(e)
+    }
+}
+
 impl From<flush_memory::Error> for Error {
     fn from(e: flush_memory::Error) -> Error {
         Error::FlushMemoryError(e)
@@ -305,9 +354,17 @@ impl TestBuffer {
     ) -> Result<TestBuffer, Error> {
         let buffer = Buffer::new(Rc::clone(&context), size, usage)?;
 
+        let allocate_flags =
+            if usage & vk::VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT != 0 {
+                vk::VK_MEMORY_ALLOCATE_DEVICE_ADDRESS_BIT
+            } else {
+                0
+            };
+
         let memory = DeviceMemory::new_buffer(
             Rc::clone(&context),
             vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT,
+            allocate_flags,
             buffer.buffer,
         )?;
 
@@ -323,11 +380,20 @@ fn allocate_buffer_objects(
 ) -> Result<Vec<TestBuffer>, Error> {
     let mut buffers = Vec::with_capacity(script.buffers().len());
 
+    // If any command takes the address of a buffer then all of the
+    // buffers are made addressable so that the commands don’t need to
+    // be checked against every binding.
+    let address_usage = if script.uses_buffer_addresses() {
+        vk::VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT
+    } else {
+        0
+    };
+
     for script_buffer in script.buffers().iter() {
         let usage = match script_buffer.buffer_type {
             BufferType::Ubo => vk::VK_BUFFER_USAGE_UNIFORM_BUFFER_BIT,
             BufferType::Ssbo => vk::VK_BUFFER_USAGE_STORAGE_BUFFER_BIT,
-        };
+        } | address_usage;
 
         buffers.push(TestBuffer::new(
             Rc::clone(window.context()),
@@ -339,10 +405,27 @@ fn allocate_buffer_objects(
     Ok(buffers)
 }
 
+fn allocate_textures(
+    window: &Window,
+    script: &Script,
+) -> Result<Vec<Texture>, Error> {
+    let mut textures = Vec::with_capacity(script.textures().len());
+
+    for script_texture in script.textures().iter() {
+        textures.push(Texture::new(
+            Rc::clone(window.context()),
+            script_texture,
+        )?);
+    }
+
+    Ok(textures)
+}
+
 fn write_descriptor_sets(
     window: &Window,
     script: &Script,
     buffers: &[TestBuffer],
+    textures: &[Texture],
     descriptor_sets: &[vk::VkDescriptorSet],
 ) {
     let script_buffers = script.buffers();
@@ -355,6 +438,25 @@ fn write_descriptor_sets(
         })
         .collect::<Vec<_>>();
 
+    let image_infos = textures.iter()
+        .map(Texture::image_info)
+        .collect::<Vec<_>>();
+
+    let texture_writes = script.textures().iter()
+        .enumerate()
+        .map(|(texture_num, texture)| vk::VkWriteDescriptorSet {
+            sType: vk::VK_STRUCTURE_TYPE_WRITE_DESCRIPTOR_SET,
+            pNext: ptr::null(),
+            dstSet: descriptor_sets[texture.desc_set as usize],
+            dstBinding: texture.binding,
+            dstArrayElement: 0,
+            descriptorCount: 1,
+            descriptorType: vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+            pBufferInfo: ptr::null(),
+            pImageInfo: image_infos[texture_num..].as_ptr(),
+            pTexelBufferView: ptr::null(),
+        });
+
     let writes = script_buffers.iter()
         .enumerate()
         .map(|(buffer_num, buffer)| vk::VkWriteDescriptorSet {
@@ -372,6 +474,7 @@ fn write_descriptor_sets(
             pImageInfo: ptr::null(),
             pTexelBufferView: ptr::null(),
         })
+        .chain(texture_writes)
         .collect::<Vec<_>>();
 
     unsafe {
@@ -401,6 +504,10 @@ struct Tester<'a> {
     pipeline_set: &'a PipelineSet,
     script: &'a Script,
     buffer_objects: Vec<TestBuffer>,
+    textures: Vec<Texture>,
+    // Set once the commands to copy the texels into the textures
+    // have been recorded in a command buffer.
+    textures_uploaded: bool,
     test_buffers: Vec<TestBuffer>,
     descriptor_sets: DescriptorSetVec<'a>,
     bound_pipeline: Option<usize>,
@@ -408,8 +515,10 @@ struct Tester<'a> {
     first_render: bool,
     state: State,
     vbo_buffer: Option<TestBuffer>,
+    instance_buffer: Option<TestBuffer>,
     index_buffer: Option<TestBuffer>,
     inspector: Option<Inspector>,
+    submissions: &'a mut Submissions,
 }
 
 impl<'a> Tester<'a> {
@@ -418,14 +527,17 @@ impl<'a> Tester<'a> {
         pipeline_set: &'a PipelineSet,
         script: &'a Script,
         inspector: Option<Inspector>,
+        submissions: &'a mut Submissions,
     ) -> Result<Tester<'a>, Error> {
         let buffer_objects = allocate_buffer_objects(window, script)?;
+        let textures = allocate_textures(window, script)?;
         let descriptor_sets = DescriptorSetVec::new(window, pipeline_set)?;
 
         write_descriptor_sets(
             window,
             script,
             &buffer_objects,
+            &textures,
             &descriptor_sets.handles,
         );
 
@@ -434,6 +546,8 @@ impl<'a> Tester<'a> {
             pipeline_set,
             script,
             buffer_objects,
+            textures,
+            textures_uploaded: false,
             test_buffers: Vec::new(),
             descriptor_sets,
             bound_pipeline: None,
@@ -441,8 +555,10 @@ impl<'a> Tester<'a> {
             first_render: true,
             state: State::Idle,
             vbo_buffer: None,
+            instance_buffer: None,
             index_buffer: None,
             inspector,
+            submissions,
         })
     }
 
@@ -523,6 +639,16 @@ impl<'a> Tester<'a> {
             self.bound_pipeline = None;
             self.bo_descriptor_set_bound = false;
 
+            // The textures are uploaded at the start of the first
+            // command buffer so that they are ready before anything
+            // can sample them.
+            if !self.textures_uploaded {
+                for texture in self.textures.iter() {
+                    texture.add_upload_commands(self.window.context());
+                }
+                self.textures_uploaded = true;
+            }
+
             Ok(())
         } else {
             Err(Error::BeginCommandBufferFailed)
@@ -695,8 +821,10 @@ impl<'a> Tester<'a> {
         }
 
         self.reset_fence()?;
+        let submitted = Instant::now();
         self.queue_submit()?;
         self.wait_for_fence()?;
+        self.submissions.times.push(submitted.elapsed());
         self.invalidate_window_linear_memory()?;
         self.invalidate_ssbos()?;
 
@@ -992,6 +1120,25 @@ impl<'a> Tester<'a> {
 
         let key = &self.script.pipeline_keys()[pipeline_num];
 
+        let shader_object = self.pipeline_set.shader_objects()[pipeline_num];
+
+        if shader_object != vk::null_handle() {
+            let stage = vk::VK_SHADER_STAGE_COMPUTE_BIT;
+
+            unsafe {
+                self.window.device().vkCmdBindShadersEXT.unwrap()(
+                    self.window.context().command_buffer(),
+                    1, // stageCount
+                    ptr::addr_of!(stage),
+                    ptr::addr_of!(shader_object),
+                );
+            }
+
+            self.bound_pipeline = Some(pipeline_num);
+
+            return;
+        }
+
         let bind_point = match key.pipeline_type() {
             pipeline_key::Type::Graphics => vk::VK_PIPELINE_BIND_POINT_GRAPHICS,
             pipeline_key::Type::Compute => vk::VK_PIPELINE_BIND_POINT_COMPUTE,
@@ -1030,33 +1177,49 @@ impl<'a> Tester<'a> {
         if let Some(ref buffer) = self.vbo_buffer {
             Ok(Some(buffer))
         } else if let Some(vbo) = self.script.vertex_data() {
-            let buffer = TestBuffer::new(
-                Rc::clone(self.window.context()),
-                vbo.raw_data().len(),
-                vk::VK_BUFFER_USAGE_VERTEX_BUFFER_BIT,
-            )?;
-
-            unsafe {
-                std::slice::from_raw_parts_mut(
-                    buffer.map.pointer as *mut u8,
-                    buffer.size
-                ).copy_from_slice(vbo.raw_data());
-            }
-
-            flush_memory(
-                self.window.context(),
-                buffer.memory.memory_type_index as usize,
-                buffer.memory.memory,
-                0, // offset
-                vk::VK_WHOLE_SIZE as vk::VkDeviceSize,
-            )?;
-
+            let buffer = self.create_vertex_buffer(vbo)?;
             Ok(Some(&*self.vbo_buffer.insert(buffer)))
         } else {
             Ok(None)
         }
     }
 
+    fn get_instance_buffer(&mut self) -> Result<Option<&TestBuffer>, Error> {
+        if let Some(ref buffer) = self.instance_buffer {
+            Ok(Some(buffer))
+        } else if let Some(vbo) = self.script.instance_data() {
+            let buffer = self.create_vertex_buffer(vbo)?;
+            Ok(Some(&*self.instance_buffer.insert(buffer)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn create_vertex_buffer(&self, vbo: &Vbo) -> Result<TestBuffer, Error> {
+        let buffer = TestBuffer::new(
+            Rc::clone(self.window.context()),
+            vbo.raw_data().len(),
+            vk::VK_BUFFER_USAGE_VERTEX_BUFFER_BIT,
+        )?;
+
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                buffer.map.pointer as *mut u8,
+                buffer.size
+            ).copy_from_slice(vbo.raw_data());
+        }
+
+        flush_memory(
+            self.window.context(),
+            buffer.memory.memory_type_index as usize,
+            buffer.memory.memory,
+            0, // offset
+            vk::VK_WHOLE_SIZE as vk::VkDeviceSize,
+        )?;
+
+        Ok(buffer)
+    }
+
     fn get_index_buffer(&mut self) -> Result<&TestBuffer, Error> {
         match self.index_buffer {
             Some(ref buffer) => Ok(buffer),
@@ -1207,6 +1370,20 @@ impl<'a> Tester<'a> {
             }
         }
 
+        if let Some(buffer) = self.get_instance_buffer()? {
+            let offset = 0;
+
+            unsafe {
+                context.device().vkCmdBindVertexBuffers.unwrap()(
+                    context.command_buffer(),
+                    INSTANCE_DATA_BINDING,
+                    1, // bindingCount
+                    ptr::addr_of!(buffer.buffer.buffer),
+                    ptr::addr_of!(offset)
+                );
+            }
+        }
+
         self.bind_bo_descriptor_set();
         self.bind_pipeline(pipeline_key);
 
@@ -1408,6 +1585,14 @@ impl<'a> Tester<'a> {
             unreachable!("bad op");
         };
 
+        self.push_constants(offset, data)
+    }
+
+    fn push_constants(
+        &mut self,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<(), Error> {
         if (self.state as usize) < State::CommandBuffer as usize {
             self.goto_state(State::CommandBuffer)?;
         }
@@ -1439,6 +1624,18 @@ impl<'a> Tester<'a> {
             unreachable!("bad op");
         };
 
+        self.write_buffer(desc_set, binding, offset, data);
+
+        Ok(())
+    }
+
+    fn write_buffer(
+        &mut self,
+        desc_set: u32,
+        binding: u32,
+        offset: usize,
+        data: &[u8],
+    ) {
         let buffer = self.get_buffer_object(desc_set, binding)
             .expect(
                 "The script parser should make a buffer mentioned by \
@@ -1456,6 +1653,66 @@ impl<'a> Tester<'a> {
         buffer_slice.copy_from_slice(data);
 
         buffer.pending_write = true;
+    }
+
+    fn buffer_address(
+        &mut self,
+        desc_set: u32,
+        binding: u32,
+    ) -> Result<vk::VkDeviceAddress, Error> {
+        let Some(get_buffer_device_address) =
+            self.window.device().vkGetBufferDeviceAddress
+        else {
+            return Err(Error::BufferDeviceAddressUnavailable);
+        };
+
+        let buffer = self.get_buffer_object(desc_set, binding)?.buffer.buffer;
+
+        let info = vk::VkBufferDeviceAddressInfo {
+            sType: vk::VK_STRUCTURE_TYPE_BUFFER_DEVICE_ADDRESS_INFO,
+            pNext: ptr::null(),
+            buffer,
+        };
+
+        Ok(unsafe {
+            get_buffer_device_address(
+                self.window.vk_device(),
+                ptr::addr_of!(info),
+            )
+        })
+    }
+
+    fn set_push_address(
+        &mut self,
+        op: &Operation,
+    ) -> Result<(), Error> {
+        let &Operation::SetPushAddress { offset, desc_set, binding } = op
+        else {
+            unreachable!("bad op");
+        };
+
+        let address = self.buffer_address(desc_set, binding)?;
+
+        self.push_constants(offset, &address.to_ne_bytes())
+    }
+
+    fn set_buffer_address(
+        &mut self,
+        op: &Operation,
+    ) -> Result<(), Error> {
+        let &Operation::SetBufferAddress {
+            desc_set,
+            binding,
+            offset,
+            target_desc_set,
+            target_binding,
+        } = op else {
+            unreachable!("bad op");
+        };
+
+        let address = self.buffer_address(target_desc_set, target_binding)?;
+
+        self.write_buffer(desc_set, binding, offset, &address.to_ne_bytes());
 
         Ok(())
     }
@@ -1530,7 +1787,7 @@ impl<'a> Tester<'a> {
         &mut self,
         op: &Operation,
     ) -> Result<(), Error> {
-        match op {
+        let res = match op {
             Operation::DrawRect { .. } => self.draw_rect(op),
             Operation::DrawArrays { .. } => self.draw_arrays(op),
             Operation::DispatchCompute { .. } => self.dispatch_compute(op),
@@ -1538,7 +1795,22 @@ impl<'a> Tester<'a> {
             Operation::ProbeSsbo { .. } => self.probe_ssbo(op),
             Operation::SetPushCommand { .. } => self.set_push_command(op),
             Operation::SetBufferData { .. } => self.set_buffer_data(op),
+            Operation::SetPushAddress { .. } => self.set_push_address(op),
+            Operation::SetBufferAddress { .. } => self.set_buffer_address(op),
             Operation::Clear { .. } => self.clear(op),
+        };
+
+        let is_gpu_work = matches!(
+            op,
+            Operation::DrawRect { .. }
+                | Operation::DrawArrays { .. }
+                | Operation::DispatchCompute { .. }
+        );
+
+        if res.is_ok() && is_gpu_work && self.submissions.separate {
+            self.goto_state(State::Idle)
+        } else {
+            res
         }
     }
 
@@ -1584,8 +1856,15 @@ pub(crate) fn run(
     pipeline_set: &PipelineSet,
     script: &Script,
     inspector: Option<Inspector>,
+    submissions: &mut Submissions,
 ) -> Result<(), Error> {
-    let mut tester = Tester::new(window, pipeline_set, script, inspector)?;
+    let mut tester = Tester::new(
+        window,
+        pipeline_set,
+        script,
+        inspector,
+        submissions,
+    )?;
     let mut errors = Vec::new();
 
     for command in script.commands().iter() {
@@ -1638,6 +1917,7 @@ mod test {
         fn new_full(
             source: &str,
             inspector: Option<Inspector>,
+            submissions: &mut Submissions,
         ) -> Result<TestData, Error> {
             let mut fake_vulkan = FakeVulkan::new();
 
@@ -1690,6 +1970,7 @@ mod test {
                 Rc::clone(&window),
                 &script,
                 false, // show_disassembly
+                false, // separate_shader_objects
             ).unwrap();
 
             run(
@@ -1697,6 +1978,7 @@ mod test {
                 &pipeline_set,
                 &script,
                 inspector,
+                submissions,
             )?;
 
             Ok(TestData {
@@ -1711,6 +1993,7 @@ mod test {
             TestData::new_full(
                 source,
                 None, // inspector
+                &mut Submissions::default(),
             )
         }
     }
@@ -1920,6 +2203,50 @@ mod test {
         assert_eq!(first_instance, 0);
     }
 
+    #[test]
+    fn instance_data() {
+        let test_data = TestData::new(
+            "[vertex data]\n\
+             0/R32_SFLOAT\n\
+             1\n\
+             [instance data]\n\
+             1/R8_UINT\n\
+             7\n\
+             9\n\
+             [test]\n\
+             draw arrays instanced TRIANGLE_LIST 0 1 2"
+        ).unwrap();
+
+        let mut binds = test_data.fake_vulkan.commands.iter().filter_map(
+            |command| match command {
+                &Command::BindVertexBuffers {
+                    first_binding,
+                    ref buffers,
+                    ..
+                } => Some((first_binding, buffers)),
+                _ => None,
+            }
+        );
+
+        let (first_binding, _) = binds.next().unwrap();
+        assert_eq!(first_binding, 0);
+
+        let (first_binding, buffers) = binds.next().unwrap();
+        assert_eq!(first_binding, INSTANCE_DATA_BINDING);
+
+        let HandleType::Buffer { memory: Some(memory), .. } =
+            test_data.fake_vulkan.get_freed_handle(buffers[0]).data
+        else { unreachable!("Failed to get buffer memory"); };
+
+        let HandleType::Memory { ref contents, .. } =
+            test_data.fake_vulkan.get_freed_handle(memory).data
+        else { unreachable!("Mismatched handle"); };
+
+        assert_eq!(contents, &[7, 9]);
+
+        assert!(binds.next().is_none());
+    }
+
     #[test]
     fn dispatch_compute() {
         let test_data = TestData::new(
@@ -1939,6 +2266,42 @@ mod test {
         assert!(commands.next().is_none());
     }
 
+    #[test]
+    fn separate_submissions() {
+        let source = "[test]\n\
+                      compute 1 1 1\n\
+                      compute 2 2 2\n\
+                      draw rect -1 -1 2 2";
+
+        let mut submissions = Submissions::default();
+        TestData::new_full(source, None, &mut submissions).unwrap();
+        assert_eq!(submissions.times.len(), 1);
+
+        let mut submissions = Submissions {
+            separate: true,
+            ..Default::default()
+        };
+        TestData::new_full(source, None, &mut submissions).unwrap();
+        assert_eq!(submissions.times.len(), 3);
+    }
+
+    #[test]
+    fn submissions_report() {
+        assert_eq!(Submissions::default().report(), "Submissions: 0");
+
+        let submissions = Submissions {
+            separate: false,
+            times: vec![
+                Duration::from_micros(12500),
+                Duration::from_micros(7500),
+            ],
+        };
+        assert_eq!(
+            submissions.report(),
+            "Submissions: 2, longest 12.500 ms, total 20.000 ms",
+        );
+    }
+
     #[test]
     fn clear() {
         let test_data = TestData::new(
@@ -2120,6 +2483,295 @@ mod test {
         }).expect("expected ssbo memory to be flushed");
     }
 
+    #[test]
+    fn texture_upload() {
+        let mut texels = crate::temp_file::TempFile::new().unwrap();
+        std::io::Write::write_all(
+            texels.file().unwrap(),
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+        ).unwrap();
+        texels.close();
+
+        let test_data = TestData::new(&format!(
+            "[fragment shader]\n\
+             03 02 23 07\n\
+             [test]\n\
+             texture 3 2 1 {}\n\
+             draw rect -1 -1 2 2",
+            texels.filename().to_str().unwrap(),
+        )).unwrap();
+
+        let mut commands = test_data.fake_vulkan.commands.iter();
+
+        // The texture upload is recorded before anything else
+        let &Command::PipelineBarrier {
+            ref image_memory_barriers,
+            ..
+        } = commands.next().unwrap()
+        else { unreachable!("Bad command"); };
+        assert_eq!(image_memory_barriers.len(), 1);
+        assert_eq!(
+            image_memory_barriers[0].newLayout,
+            vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+        );
+        let image = image_memory_barriers[0].image;
+
+        let &Command::CopyBufferToImage {
+            src_buffer,
+            dst_image,
+            dst_image_layout,
+            ref regions,
+        } = commands.next().unwrap()
+        else { unreachable!("Bad command"); };
+        assert_eq!(dst_image, image);
+        assert_eq!(dst_image_layout, vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].imageExtent.width, 2);
+        assert_eq!(regions[0].imageExtent.height, 1);
+
+        let &Command::PipelineBarrier {
+            ref image_memory_barriers,
+            ..
+        } = commands.next().unwrap()
+        else { unreachable!("Bad command"); };
+        assert_eq!(
+            image_memory_barriers[0].newLayout,
+            vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        let HandleType::Buffer {
+            memory: Some(memory_handle),
+            ..
+        } = test_data.fake_vulkan.get_freed_handle(src_buffer).data
+        else { unreachable!("failed to get staging buffer memory"); };
+
+        let HandleType::Memory {
+            ref contents,
+            ..
+        } = test_data.fake_vulkan.get_freed_handle(memory_handle).data
+        else { unreachable!("bad handle"); };
+
+        assert_eq!(contents, &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let &Command::BindDescriptorSets {
+            ref descriptor_sets,
+            ..
+        } = test_data.fake_vulkan.commands.iter().find(|command| {
+            matches!(command, Command::BindDescriptorSets { .. })
+        }).unwrap()
+        else { unreachable!() };
+
+        let HandleType::DescriptorSet {
+            ref bindings
+        } = test_data.fake_vulkan.get_freed_handle(descriptor_sets[0]).data
+        else { unreachable!("bad handle"); };
+
+        assert_eq!(
+            bindings[&3].descriptor_type,
+            vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+        );
+
+        let image_info = bindings[&3].image_info;
+        assert_eq!(
+            image_info.imageLayout,
+            vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        let HandleType::Sampler {
+            ref create_info
+        } = test_data.fake_vulkan.get_freed_handle(image_info.sampler).data
+        else { unreachable!("bad handle"); };
+
+        assert_eq!(create_info.magFilter, vk::VK_FILTER_LINEAR);
+        assert_eq!(
+            create_info.addressModeU,
+            vk::VK_SAMPLER_ADDRESS_MODE_REPEAT,
+        );
+
+        // The upload is only done once
+        assert_eq!(
+            test_data.fake_vulkan.commands.iter().filter(|command| {
+                matches!(command, Command::CopyBufferToImage { .. })
+            }).count(),
+            1,
+        );
+    }
+
+    #[test]
+    fn cube_texture_upload() {
+        let mut texels = crate::temp_file::TempFile::new().unwrap();
+        std::io::Write::write_all(texels.file().unwrap(), &[0; 96]).unwrap();
+        texels.close();
+
+        let test_data = TestData::new(&format!(
+            "[fragment shader]\n\
+             03 02 23 07\n\
+             [test]\n\
+             texture 0 cube mipmaps 2 2 {}\n\
+             draw rect -1 -1 2 2",
+            texels.filename().to_str().unwrap(),
+        )).unwrap();
+
+        let mut commands = test_data.fake_vulkan.commands.iter();
+
+        let &Command::PipelineBarrier {
+            ref image_memory_barriers,
+            ..
+        } = commands.next().unwrap()
+        else { unreachable!("Bad command"); };
+        assert_eq!(image_memory_barriers[0].subresourceRange.layerCount, 6);
+        assert_eq!(image_memory_barriers[0].subresourceRange.levelCount, 2);
+
+        // All of the faces of a mipmap level are copied with one region
+        let &Command::CopyBufferToImage { ref regions, .. } =
+            commands.next().unwrap()
+        else { unreachable!("Bad command"); };
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].imageSubresource.layerCount, 6);
+        assert_eq!(regions[0].bufferImageHeight, 2);
+        assert_eq!(regions[1].imageSubresource.mipLevel, 1);
+        assert_eq!(regions[1].bufferOffset, 96);
+        assert_eq!(regions[1].imageExtent.width, 1);
+    }
+
+    #[test]
+    fn depth_texture_sampler() {
+        let mut texels = crate::temp_file::TempFile::new().unwrap();
+        std::io::Write::write_all(
+            texels.file().unwrap(),
+            &0.5f32.to_le_bytes(),
+        ).unwrap();
+        texels.close();
+
+        let test_data = TestData::new(&format!(
+            "[fragment shader]\n\
+             03 02 23 07\n\
+             [test]\n\
+             texture 0 depth compare greater address clamp_to_border \
+             clamp_to_border repeat border opaque_white 1 1 {}\n\
+             draw rect -1 -1 2 2",
+            texels.filename().to_str().unwrap(),
+        )).unwrap();
+
+        let &Command::CopyBufferToImage { ref regions, .. } =
+            test_data.fake_vulkan.commands.iter().find(|command| {
+                matches!(command, Command::CopyBufferToImage { .. })
+            }).unwrap()
+        else { unreachable!("Bad command"); };
+        assert_eq!(
+            regions[0].imageSubresource.aspectMask,
+            vk::VK_IMAGE_ASPECT_DEPTH_BIT,
+        );
+
+        let &Command::BindDescriptorSets {
+            ref descriptor_sets,
+            ..
+        } = test_data.fake_vulkan.commands.iter().find(|command| {
+            matches!(command, Command::BindDescriptorSets { .. })
+        }).unwrap()
+        else { unreachable!() };
+
+        let HandleType::DescriptorSet {
+            ref bindings
+        } = test_data.fake_vulkan.get_freed_handle(descriptor_sets[0]).data
+        else { unreachable!("bad handle"); };
+
+        let HandleType::Sampler {
+            create_info: ref sampler
+        } = test_data.fake_vulkan.get_freed_handle(
+            bindings[&0].image_info.sampler
+        ).data
+        else { unreachable!("bad handle"); };
+
+        assert_eq!(sampler.compareEnable, vk::VK_TRUE);
+        assert_eq!(sampler.compareOp, vk::VK_COMPARE_OP_GREATER);
+        assert_eq!(
+            sampler.addressModeU,
+            vk::VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER,
+        );
+        assert_eq!(sampler.addressModeW, vk::VK_SAMPLER_ADDRESS_MODE_REPEAT);
+        assert_eq!(
+            sampler.borderColor,
+            vk::VK_BORDER_COLOR_FLOAT_OPAQUE_WHITE,
+        );
+        assert_eq!(sampler.anisotropyEnable, vk::VK_FALSE);
+    }
+
+    #[test]
+    fn buffer_addresses() {
+        let test_data = TestData::new(
+            "[fragment shader]\n\
+             03 02 23 07\n\
+             [test]\n\
+             ssbo 0 4\n\
+             ssbo 1 address 8 0\n\
+             push address 0 1\n\
+             draw rect -1 -1 2 2"
+        ).unwrap();
+
+        let &Command::BindDescriptorSets {
+            ref descriptor_sets,
+            ..
+        } = test_data.fake_vulkan.commands.iter().find(|command| {
+            matches!(command, Command::BindDescriptorSets { .. })
+        }).unwrap()
+        else { unreachable!() };
+
+        let HandleType::DescriptorSet {
+            ref bindings
+        } = test_data.fake_vulkan.get_freed_handle(descriptor_sets[0]).data
+        else { unreachable!("bad handle"); };
+
+        let target_buffer = bindings[&0].info.buffer;
+        let address_buffer = bindings[&1].info.buffer;
+
+        for &buffer in [target_buffer, address_buffer].iter() {
+            let HandleType::Buffer {
+                ref create_info,
+                ..
+            } = test_data.fake_vulkan.get_freed_handle(buffer).data
+            else { unreachable!("bad handle"); };
+
+            assert_ne!(
+                create_info.usage
+                    & vk::VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT,
+                0,
+            );
+        }
+
+        let HandleType::Buffer {
+            memory: Some(memory_handle),
+            ..
+        } = test_data.fake_vulkan.get_freed_handle(address_buffer).data
+        else { unreachable!("failed to get buffer memory"); };
+
+        let HandleType::Memory {
+            ref contents,
+            ..
+        } = test_data.fake_vulkan.get_freed_handle(memory_handle).data
+        else { unreachable!("bad handle"); };
+
+        assert_eq!(
+            &contents[8..16],
+            &FakeVulkan::buffer_device_address(target_buffer).to_ne_bytes(),
+        );
+
+        let &Command::PushConstants {
+            offset,
+            ref values,
+            ..
+        } = test_data.fake_vulkan.commands.iter().find(|command| {
+            matches!(command, Command::PushConstants { .. })
+        }).unwrap()
+        else { unreachable!() };
+
+        assert_eq!(offset, 0);
+        assert_eq!(
+            values.as_slice(),
+            &FakeVulkan::buffer_device_address(address_buffer).to_ne_bytes(),
+        );
+    }
+
     #[test]
     fn probe_ssbo_success() {
         TestData::new(
@@ -2270,6 +2922,7 @@ mod test {
             "[test]\n\
              ssbo 5 1024",
             Some(inspector),
+            &mut Submissions::default(),
         ).expect("expected test to pass");
 
         assert!(inspector_called);
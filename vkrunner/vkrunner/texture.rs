@@ -0,0 +1,637 @@
+// vkrunner
+//
+// Copyright 2026 The shaderc-vkrunner-mcp contributors
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next
+// paragraph) shall be included in all copies or substantial portions of the
+// Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+// THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Module containing the Vulkan objects for a texture declared in a
+//! script: the image and its memory, an image view, a sampler and a
+//! staging buffer holding the texels until they are copied into the
+//! image.
+
+use crate::vk;
+use crate::context::Context;
+use crate::buffer::{self, MappedMemory, DeviceMemory, Buffer};
+use crate::flush_memory::{self, flush_memory};
+use crate::script;
+use std::rc::Rc;
+use std::ptr;
+use std::fmt;
+
+// Both formats have four bytes per texel so the script only needs
+// to know the size of the texture to check the data
+fn format_and_aspect(
+    texture: &script::Texture,
+) -> (vk::VkFormat, vk::VkImageAspectFlags) {
+    if texture.depth {
+        (vk::VK_FORMAT_D32_SFLOAT, vk::VK_IMAGE_ASPECT_DEPTH_BIT)
+    } else {
+        (vk::VK_FORMAT_R8G8B8A8_UNORM, vk::VK_IMAGE_ASPECT_COLOR_BIT)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ImageError,
+    ImageViewError,
+    SamplerError,
+    BufferError(buffer::Error),
+    FlushMemoryError(flush_memory::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ImageError => write!(f, "Error creating texture vkImage"),
+            Error::ImageViewError => {
+                write!(f, "Error creating texture vkImageView")
+            },
+            Error::SamplerError => write!(f, "Error creating vkSampler"),
+            Error::BufferError(e) => e.fmt(f),
+            Error::FlushMemoryError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<buffer::Error> for Error {
+    fn from(e: buffer::Error) -> Error {
+        Error::BufferError(e)
+    }
+}
+
+impl From<flush_memory::Error> for Error {
+    fn from(e: flush_memory::Error) -> Error {
+        Error::FlushMemoryError(e)
+    }
+}
+
+#[derive(Debug)]
+struct Image {
+    image: vk::VkImage,
+    // Needed for the destructor
+    context: Rc<Context>,
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.device().vkDestroyImage.unwrap()(
+                self.context.vk_device(),
+                self.image,
+                ptr::null(), // allocator
+            );
+        }
+    }
+}
+
+impl Image {
+    fn new(
+        context: Rc<Context>,
+        texture: &script::Texture,
+    ) -> Result<Image, Error> {
+        let flags = if texture.texture_type == script::TextureType::Cube {
+            vk::VK_IMAGE_CREATE_CUBE_COMPATIBLE_BIT
+        } else {
+            0
+        };
+
+        let image_create_info = vk::VkImageCreateInfo {
+            sType: vk::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags,
+            imageType: vk::VK_IMAGE_TYPE_2D,
+            format: format_and_aspect(texture).0,
+            extent: vk::VkExtent3D {
+                width: texture.width,
+                height: texture.height,
+                depth: 1,
+            },
+            mipLevels: texture.mip_levels,
+            arrayLayers: texture.layers,
+            samples: vk::VK_SAMPLE_COUNT_1_BIT,
+            tiling: vk::VK_IMAGE_TILING_OPTIMAL,
+            usage: vk::VK_IMAGE_USAGE_TRANSFER_DST_BIT
+                | vk::VK_IMAGE_USAGE_SAMPLED_BIT,
+            sharingMode: vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: ptr::null(),
+            initialLayout: vk::VK_IMAGE_LAYOUT_UNDEFINED,
+        };
+
+        let mut image: vk::VkImage = vk::null_handle();
+
+        let res = unsafe {
+            context.device().vkCreateImage.unwrap()(
+                context.vk_device(),
+                ptr::addr_of!(image_create_info),
+                ptr::null(), // allocator
+                ptr::addr_of_mut!(image),
+            )
+        };
+
+        if res == vk::VK_SUCCESS {
+            Ok(Image { image, context })
+        } else {
+            Err(Error::ImageError)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ImageView {
+    image_view: vk::VkImageView,
+    // Needed for the destructor
+    context: Rc<Context>,
+}
+
+impl Drop for ImageView {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.device().vkDestroyImageView.unwrap()(
+                self.context.vk_device(),
+                self.image_view,
+                ptr::null(), // allocator
+            );
+        }
+    }
+}
+
+impl ImageView {
+    fn new(
+        context: Rc<Context>,
+        image: vk::VkImage,
+        texture: &script::Texture,
+    ) -> Result<ImageView, Error> {
+        let view_type = match texture.texture_type {
+            script::TextureType::Texture2D => vk::VK_IMAGE_VIEW_TYPE_2D,
+            script::TextureType::Array2D => vk::VK_IMAGE_VIEW_TYPE_2D_ARRAY,
+            script::TextureType::Cube => vk::VK_IMAGE_VIEW_TYPE_CUBE,
+        };
+
+        let (format, aspect) = format_and_aspect(texture);
+
+        let image_view_create_info = vk::VkImageViewCreateInfo {
+            sType: vk::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            image,
+            viewType: view_type,
+            format,
+            components: vk::VkComponentMapping {
+                r: vk::VK_COMPONENT_SWIZZLE_R,
+                g: vk::VK_COMPONENT_SWIZZLE_G,
+                b: vk::VK_COMPONENT_SWIZZLE_B,
+                a: vk::VK_COMPONENT_SWIZZLE_A,
+            },
+            subresourceRange: subresource_range(
+                aspect,
+                texture.mip_levels,
+                texture.layers,
+            ),
+        };
+
+        let mut image_view: vk::VkImageView = vk::null_handle();
+
+        let res = unsafe {
+            context.device().vkCreateImageView.unwrap()(
+                context.vk_device(),
+                ptr::addr_of!(image_view_create_info),
+                ptr::null(), // allocator
+                ptr::addr_of_mut!(image_view),
+            )
+        };
+
+        if res == vk::VK_SUCCESS {
+            Ok(ImageView { image_view, context })
+        } else {
+            Err(Error::ImageViewError)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Sampler {
+    sampler: vk::VkSampler,
+    // Needed for the destructor
+    context: Rc<Context>,
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.context.device().vkDestroySampler.unwrap()(
+                self.context.vk_device(),
+                self.sampler,
+                ptr::null(), // allocator
+            );
+        }
+    }
+}
+
+impl Sampler {
+    fn new(
+        context: Rc<Context>,
+        texture: &script::Texture,
+    ) -> Result<Sampler, Error> {
+        let state = &texture.sampler;
+
+        let sampler_create_info = vk::VkSamplerCreateInfo {
+            sType: vk::VK_STRUCTURE_TYPE_SAMPLER_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            magFilter: state.mag_filter,
+            minFilter: state.min_filter,
+            mipmapMode: state.mipmap_mode,
+            addressModeU: state.address_modes[0],
+            addressModeV: state.address_modes[1],
+            addressModeW: state.address_modes[2],
+            mipLodBias: state.lod_bias,
+            anisotropyEnable: state.max_anisotropy.is_some() as vk::VkBool32,
+            maxAnisotropy: state.max_anisotropy.unwrap_or(1.0),
+            compareEnable: state.compare_op.is_some() as vk::VkBool32,
+            compareOp: state.compare_op.unwrap_or(vk::VK_COMPARE_OP_NEVER),
+            minLod: 0.0,
+            maxLod: (texture.mip_levels - 1) as f32,
+            borderColor: state.border_color,
+            unnormalizedCoordinates: vk::VK_FALSE,
+        };
+
+        let mut sampler: vk::VkSampler = vk::null_handle();
+
+        let res = unsafe {
+            context.device().vkCreateSampler.unwrap()(
+                context.vk_device(),
+                ptr::addr_of!(sampler_create_info),
+                ptr::null(), // allocator
+                ptr::addr_of_mut!(sampler),
+            )
+        };
+
+        if res == vk::VK_SUCCESS {
+            Ok(Sampler { sampler, context })
+        } else {
+            Err(Error::SamplerError)
+        }
+    }
+}
+
+fn subresource_range(
+    aspect: vk::VkImageAspectFlags,
+    mip_levels: u32,
+    layers: u32,
+) -> vk::VkImageSubresourceRange {
+    vk::VkImageSubresourceRange {
+        aspectMask: aspect,
+        baseMipLevel: 0,
+        levelCount: mip_levels,
+        baseArrayLayer: 0,
+        layerCount: layers,
+    }
+}
+
+fn mip_level_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+// Appends the smaller mipmap levels to the texels of the first one.
+// Each texel of a level is the average of a 2x2 block of the level
+// above, repeating the last row or column of odd sizes. Depth texels
+// are averaged as floats and color texels per component.
+fn mip_chain(texture: &script::Texture) -> Vec<u8> {
+    let layers = texture.layers as usize;
+    let mut data = texture.data.to_vec();
+    let mut level_start = 0;
+
+    for level in 1..texture.mip_levels {
+        let (width, height) =
+            mip_level_size(texture.width, texture.height, level - 1);
+        let (width, height) = (width as usize, height as usize);
+        let (next_width, next_height) =
+            mip_level_size(texture.width, texture.height, level);
+        let layer_size = width * height * 4;
+        let next_start = data.len();
+
+        for layer in 0..layers {
+            let src = level_start + layer * layer_size;
+
+            for y in 0..next_height as usize {
+                for x in 0..next_width as usize {
+                    let block = [(0, 0), (1, 0), (0, 1), (1, 1)].map(
+                        |(dx, dy)| {
+                            let sx = (x * 2 + dx).min(width - 1);
+                            let sy = (y * 2 + dy).min(height - 1);
+                            let offset = src + (sy * width + sx) * 4;
+                            <[u8; 4]>::try_from(&data[offset..offset + 4])
+                                .unwrap()
+                        }
+                    );
+
+                    if texture.depth {
+                        let sum = block.iter()
+                            .map(|&texel| f32::from_le_bytes(texel))
+                            .sum::<f32>();
+                        data.extend((sum / 4.0).to_le_bytes());
+                    } else {
+                        for component in 0..4 {
+                            let sum = block.iter()
+                                .map(|texel| texel[component] as u32)
+                                .sum::<u32>();
+                            data.push(((sum + 2) / 4) as u8);
+                        }
+                    }
+                }
+            }
+        }
+
+        level_start = next_start;
+    }
+
+    data
+}
+
+#[derive(Debug)]
+pub struct Texture {
+    width: u32,
+    height: u32,
+    layers: u32,
+    mip_levels: u32,
+    aspect: vk::VkImageAspectFlags,
+    // The fields are dropped in order so the objects that use the
+    // image and the buffer are destroyed before them and the memory
+    // is freed last.
+    sampler: Sampler,
+    image_view: ImageView,
+    image: Image,
+    _memory: DeviceMemory,
+    _staging_map: MappedMemory,
+    staging_buffer: Buffer,
+    _staging_memory: DeviceMemory,
+}
+
+impl Texture {
+    pub fn new(
+        context: Rc<Context>,
+        texture: &script::Texture,
+    ) -> Result<Texture, Error> {
+        let image = Image::new(Rc::clone(&context), texture)?;
+        let memory = DeviceMemory::new_image(
+            Rc::clone(&context),
+            0, // memory_type_flags
+            image.image,
+        )?;
+        let image_view = ImageView::new(
+            Rc::clone(&context),
+            image.image,
+            texture,
+        )?;
+        let sampler = Sampler::new(Rc::clone(&context), texture)?;
+
+        let data = mip_chain(texture);
+
+        let staging_buffer = Buffer::new(
+            Rc::clone(&context),
+            data.len(),
+            vk::VK_BUFFER_USAGE_TRANSFER_SRC_BIT,
+        )?;
+        let staging_memory = DeviceMemory::new_buffer(
+            Rc::clone(&context),
+            vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT,
+            0, // allocate_flags
+            staging_buffer.buffer,
+        )?;
+        let staging_map = MappedMemory::new(
+            Rc::clone(&context),
+            staging_memory.memory,
+        )?;
+
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                staging_map.pointer as *mut u8,
+                data.len(),
+            ).copy_from_slice(&data);
+        }
+
+        flush_memory(
+            &context,
+            staging_memory.memory_type_index as usize,
+            staging_memory.memory,
+            0, // offset
+            vk::VK_WHOLE_SIZE as vk::VkDeviceSize,
+        )?;
+
+        Ok(Texture {
+            width: texture.width,
+            height: texture.height,
+            layers: texture.layers,
+            mip_levels: texture.mip_levels,
+            aspect: format_and_aspect(texture).1,
+            sampler,
+            image_view,
+            image,
+            _memory: memory,
+            _staging_map: staging_map,
+            staging_buffer,
+            _staging_memory: staging_memory,
+        })
+    }
+
+    /// Returns the descriptor info to bind the texture as a combined
+    /// image sampler once it has been uploaded.
+    pub fn image_info(&self) -> vk::VkDescriptorImageInfo {
+        vk::VkDescriptorImageInfo {
+            sampler: self.sampler.sampler,
+            imageView: self.image_view.image_view,
+            imageLayout: vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+        }
+    }
+
+    fn add_layout_barrier(
+        &self,
+        context: &Context,
+        src_stage_mask: vk::VkPipelineStageFlags,
+        dst_stage_mask: vk::VkPipelineStageFlags,
+        barrier: vk::VkImageMemoryBarrier,
+    ) {
+        unsafe {
+            context.device().vkCmdPipelineBarrier.unwrap()(
+                context.command_buffer(),
+                src_stage_mask,
+                dst_stage_mask,
+                0, // dependencyFlags
+                0, // memoryBarrierCount
+                ptr::null(), // pMemoryBarriers
+                0, // bufferMemoryBarrierCount
+                ptr::null(), // pBufferMemoryBarriers
+                1, // imageMemoryBarrierCount
+                ptr::addr_of!(barrier),
+            );
+        }
+    }
+
+    /// Records the commands to copy the texels from the staging
+    /// buffer into the image and to transition it to the layout used
+    /// for sampling.
+    pub fn add_upload_commands(&self, context: &Context) {
+        let base_barrier = vk::VkImageMemoryBarrier {
+            sType: vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: 0,
+            dstAccessMask: vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+            oldLayout: vk::VK_IMAGE_LAYOUT_UNDEFINED,
+            newLayout: vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+            srcQueueFamilyIndex: vk::VK_QUEUE_FAMILY_IGNORED as u32,
+            dstQueueFamilyIndex: vk::VK_QUEUE_FAMILY_IGNORED as u32,
+            image: self.image.image,
+            subresourceRange: subresource_range(
+                self.aspect,
+                self.mip_levels,
+                self.layers,
+            ),
+        };
+
+        self.add_layout_barrier(
+            context,
+            vk::VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT,
+            vk::VK_PIPELINE_STAGE_TRANSFER_BIT,
+            base_barrier,
+        );
+
+        // The layers of each mipmap level follow each other in the
+        // staging buffer so a single region copies all of them
+        let mut buffer_offset = 0;
+        let copy_regions = (0..self.mip_levels).map(|level| {
+            let (width, height) = mip_level_size(self.width, self.height, level);
+            let region = vk::VkBufferImageCopy {
+                bufferOffset: buffer_offset,
+                bufferRowLength: width,
+                bufferImageHeight: height,
+                imageSubresource: vk::VkImageSubresourceLayers {
+                    aspectMask: self.aspect,
+                    mipLevel: level,
+                    baseArrayLayer: 0,
+                    layerCount: self.layers,
+                },
+                imageOffset: vk::VkOffset3D { x: 0, y: 0, z: 0 },
+                imageExtent: vk::VkExtent3D {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            };
+            buffer_offset += width as vk::VkDeviceSize
+                * height as vk::VkDeviceSize
+                * self.layers as vk::VkDeviceSize
+                * 4;
+            region
+        }).collect::<Vec<_>>();
+
+        unsafe {
+            context.device().vkCmdCopyBufferToImage.unwrap()(
+                context.command_buffer(),
+                self.staging_buffer.buffer,
+                self.image.image,
+                vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+                copy_regions.len() as u32,
+                copy_regions.as_ptr(),
+            );
+        }
+
+        // Make the copy visible to any shader stage that samples the
+        // texture
+        self.add_layout_barrier(
+            context,
+            vk::VK_PIPELINE_STAGE_TRANSFER_BIT,
+            vk::VK_PIPELINE_STAGE_ALL_COMMANDS_BIT,
+            vk::VkImageMemoryBarrier {
+                srcAccessMask: vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+                dstAccessMask: vk::VK_ACCESS_SHADER_READ_BIT,
+                oldLayout: vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+                newLayout: vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+                ..base_barrier
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_texture(
+        width: u32,
+        height: u32,
+        layers: u32,
+        mip_levels: u32,
+        data: &[u8],
+    ) -> script::Texture {
+        script::Texture {
+            desc_set: 0,
+            binding: 0,
+            texture_type: if layers > 1 {
+                script::TextureType::Array2D
+            } else {
+                script::TextureType::Texture2D
+            },
+            width,
+            height,
+            layers,
+            mip_levels,
+            depth: false,
+            sampler: Default::default(),
+            data: data.to_vec().into_boxed_slice(),
+        }
+    }
+
+    #[test]
+    fn mip_chain_levels() {
+        let texels = [
+            0, 10, 100, 255, 4, 10, 100, 255,
+            8, 10, 200, 255, 12, 10, 200, 0,
+        ];
+
+        let texture = test_texture(2, 2, 1, 1, &texels);
+        assert_eq!(mip_chain(&texture), texels);
+
+        let texture = test_texture(2, 2, 1, 2, &texels);
+        let data = mip_chain(&texture);
+        assert_eq!(&data[..16], &texels);
+        assert_eq!(&data[16..], &[6, 10, 150, 191]);
+
+        // The second layer of each level follows the first one
+        let texture = test_texture(1, 2, 2, 2, &texels);
+        assert_eq!(
+            &mip_chain(&texture)[16..],
+            &[2, 10, 100, 255, 10, 10, 200, 128],
+        );
+
+        // Odd sizes repeat the last column
+        let texture = test_texture(3, 1, 1, 2, &texels[..12]);
+        assert_eq!(mip_chain(&texture)[12..], [2, 10, 100, 255]);
+        assert_eq!(mip_level_size(3, 1, 1), (1, 1));
+
+        let depths = [0.25f32, 0.5, 0.75, 1.0]
+            .iter()
+            .flat_map(|depth| depth.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut texture = test_texture(2, 2, 1, 2, &depths);
+        texture.depth = true;
+        assert_eq!(mip_chain(&texture)[16..], 0.625f32.to_le_bytes());
+    }
+}
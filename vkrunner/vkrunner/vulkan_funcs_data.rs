@@ -23,6 +23,7 @@ pub struct Instance {
     pub vkGetPhysicalDeviceFeatures2KHR: vk::PFN_vkGetPhysicalDeviceFeatures2KHR,
     pub vkGetPhysicalDeviceFormatProperties: vk::PFN_vkGetPhysicalDeviceFormatProperties,
     pub vkGetPhysicalDeviceMemoryProperties: vk::PFN_vkGetPhysicalDeviceMemoryProperties,
+    pub vkGetPhysicalDeviceMemoryProperties2: vk::PFN_vkGetPhysicalDeviceMemoryProperties2,
     pub vkGetPhysicalDeviceProperties: vk::PFN_vkGetPhysicalDeviceProperties,
     pub vkGetPhysicalDeviceProperties2: vk::PFN_vkGetPhysicalDeviceProperties2,
     pub vkGetPhysicalDeviceQueueFamilyProperties: vk::PFN_vkGetPhysicalDeviceQueueFamilyProperties,
@@ -43,6 +44,7 @@ pub struct Device {
     pub vkCmdBindDescriptorSets: vk::PFN_vkCmdBindDescriptorSets,
     pub vkCmdBindIndexBuffer: vk::PFN_vkCmdBindIndexBuffer,
     pub vkCmdBindPipeline: vk::PFN_vkCmdBindPipeline,
+    pub vkCmdBindShadersEXT: vk::PFN_vkCmdBindShadersEXT,
     pub vkCmdBindVertexBuffers: vk::PFN_vkCmdBindVertexBuffers,
     pub vkCmdClearAttachments: vk::PFN_vkCmdClearAttachments,
     pub vkCmdCopyBufferToImage: vk::PFN_vkCmdCopyBufferToImage,
@@ -72,6 +74,7 @@ pub struct Device {
     pub vkCreateSampler: vk::PFN_vkCreateSampler,
     pub vkCreateSemaphore: vk::PFN_vkCreateSemaphore,
     pub vkCreateShaderModule: vk::PFN_vkCreateShaderModule,
+    pub vkCreateShadersEXT: vk::PFN_vkCreateShadersEXT,
     pub vkDestroyBuffer: vk::PFN_vkDestroyBuffer,
     pub vkDestroyCommandPool: vk::PFN_vkDestroyCommandPool,
     pub vkDestroyDescriptorPool: vk::PFN_vkDestroyDescriptorPool,
@@ -87,12 +90,14 @@ pub struct Device {
     pub vkDestroyRenderPass: vk::PFN_vkDestroyRenderPass,
     pub vkDestroySampler: vk::PFN_vkDestroySampler,
     pub vkDestroySemaphore: vk::PFN_vkDestroySemaphore,
+    pub vkDestroyShaderEXT: vk::PFN_vkDestroyShaderEXT,
     pub vkDestroyShaderModule: vk::PFN_vkDestroyShaderModule,
     pub vkEndCommandBuffer: vk::PFN_vkEndCommandBuffer,
     pub vkFlushMappedMemoryRanges: vk::PFN_vkFlushMappedMemoryRanges,
     pub vkFreeCommandBuffers: vk::PFN_vkFreeCommandBuffers,
     pub vkFreeDescriptorSets: vk::PFN_vkFreeDescriptorSets,
     pub vkFreeMemory: vk::PFN_vkFreeMemory,
+    pub vkGetBufferDeviceAddress: vk::PFN_vkGetBufferDeviceAddress,
     pub vkGetBufferMemoryRequirements: vk::PFN_vkGetBufferMemoryRequirements,
     pub vkGetDeviceQueue: vk::PFN_vkGetDeviceQueue,
     pub vkGetImageMemoryRequirements: vk::PFN_vkGetImageMemoryRequirements,
@@ -149,6 +154,10 @@ impl Instance {
                 "vkGetPhysicalDeviceMemoryProperties\0".as_ptr().cast(),
                 user_data,
             )),
+            vkGetPhysicalDeviceMemoryProperties2: std::mem::transmute(get_instance_proc_cb(
+                "vkGetPhysicalDeviceMemoryProperties2\0".as_ptr().cast(),
+                user_data,
+            )),
             vkGetPhysicalDeviceProperties: std::mem::transmute(get_instance_proc_cb(
                 "vkGetPhysicalDeviceProperties\0".as_ptr().cast(),
                 user_data,
@@ -233,6 +242,12 @@ impl Device {
                     "vkCmdBindPipeline\0".as_ptr().cast(),
                 ))
             },
+            vkCmdBindShadersEXT: unsafe {
+                std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
+                    device,
+                    "vkCmdBindShadersEXT\0".as_ptr().cast(),
+                ))
+            },
             vkCmdBindVertexBuffers: unsafe {
                 std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
                     device,
@@ -407,6 +422,12 @@ impl Device {
                     "vkCreateShaderModule\0".as_ptr().cast(),
                 ))
             },
+            vkCreateShadersEXT: unsafe {
+                std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
+                    device,
+                    "vkCreateShadersEXT\0".as_ptr().cast(),
+                ))
+            },
             vkDestroyBuffer: unsafe {
                 std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
                     device,
@@ -497,6 +518,12 @@ impl Device {
                     "vkDestroySemaphore\0".as_ptr().cast(),
                 ))
             },
+            vkDestroyShaderEXT: unsafe {
+                std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
+                    device,
+                    "vkDestroyShaderEXT\0".as_ptr().cast(),
+                ))
+            },
             vkDestroyShaderModule: unsafe {
                 std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
                     device,
@@ -533,6 +560,12 @@ impl Device {
                     "vkFreeMemory\0".as_ptr().cast(),
                 ))
             },
+            vkGetBufferDeviceAddress: unsafe {
+                std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
+                    device,
+                    "vkGetBufferDeviceAddress\0".as_ptr().cast(),
+                ))
+            },
             vkGetBufferMemoryRequirements: unsafe {
                 std::mem::transmute(instance.vkGetDeviceProcAddr.unwrap()(
                     device,
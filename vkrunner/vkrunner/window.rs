@@ -660,6 +660,7 @@ impl Window {
         let linear_memory = DeviceMemory::new_buffer(
             Rc::clone(&context),
             vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT,
+            0, // allocate_flags
             linear_buffer.buffer,
         )?;
 